@@ -39,6 +39,7 @@ use rustls::error::{
 use rustls::pki_types::pem::PemObject;
 use rustls::pki_types::{
     CertificateDer, EchConfigListBytes, PrivateKeyDer, ServerName, SubjectPublicKeyInfoDer,
+    UnixTime,
 };
 use rustls::server::danger::{ClientIdentity, ClientVerifier, SignatureVerificationInput};
 use rustls::server::{
@@ -199,7 +200,8 @@ fn exec(
                 let len = client(&mut sess)
                     .early_data()
                     .expect("0rtt not available")
-                    .write_tls(b"hello".into(), &mut output);
+                    .write_tls(b"hello".into(), &mut output)
+                    .unwrap();
                 write_or_queue(&mut sess, &b"hello"[len..], &mut pending, &mut output).unwrap();
                 sent_message = true;
             } else if !opts.only_write_one_byte_after_handshake {
@@ -404,7 +406,8 @@ fn exec(
         }
 
         if opts.shut_down_after_handshake && !sent_shutdown && !sess.is_handshaking() {
-            sess.send_close_notify(&mut output);
+            sess.send_close_notify(&mut output)
+                .unwrap();
             sent_shutdown = true;
         }
 
@@ -1683,21 +1686,27 @@ fn align_time() {
 }
 
 impl server::StoresServerSessions for ServerCacheWithResumptionDelay {
-    fn put(&self, key: ServerSessionKey<'_>, mut value: Vec<u8>) -> bool {
+    fn put(
+        &self,
+        key: ServerSessionKey<'_>,
+        mut value: Vec<u8>,
+        mut expiry: server::SessionExpiry,
+    ) -> bool {
         // The creation time should be stored directly after the 2-byte version discriminant.
         let creation_time_sec = &mut value[2..10];
         let original = u64::from_be_bytes(creation_time_sec.try_into().unwrap());
         let delayed = original - self.delay as u64;
         creation_time_sec.copy_from_slice(&delayed.to_be_bytes());
-        self.storage.put(key, value)
+        expiry.created = UnixTime::since_unix_epoch(time::Duration::from_secs(delayed));
+        self.storage.put(key, value, expiry)
     }
 
-    fn get(&self, key: ServerSessionKey<'_>) -> Option<Vec<u8>> {
-        self.storage.get(key)
+    fn get(&self, key: ServerSessionKey<'_>, now: UnixTime) -> Option<Vec<u8>> {
+        self.storage.get(key, now)
     }
 
-    fn take(&self, key: ServerSessionKey<'_>) -> Option<Vec<u8>> {
-        self.storage.take(key)
+    fn take(&self, key: ServerSessionKey<'_>, now: UnixTime) -> Option<Vec<u8>> {
+        self.storage.take(key, now)
     }
 
     fn can_cache(&self) -> bool {
@@ -0,0 +1,250 @@
+//! A server which performs its key exchange in a separate key daemon.
+//!
+//! Some deployments require ephemeral key agreement to happen outside the process that
+//! terminates TLS: inside a secure enclave, or in a separate key daemon.  This is possible
+//! for TLS1.3 servers with a key exchange group that returns `true` from
+//! `SupportedKxGroup::defers_completion()`.  When such a group is negotiated,
+//! the handshake pauses at `ServerHandshake::CompleteKeyExchange`, and the caller
+//! can complete the key exchange however it likes.
+//!
+//! Here the "key daemon" is a stand-in: a thread listening on a Unix socket, which completes
+//! key exchanges using the aws-lc-rs provider.  A real daemon would be a separate process,
+//! and the server would likely talk to it asynchronously.
+//!
+//! Usage: cargo r --bin server_kx_daemon <path/to/cert.pem> <path/to/privatekey.pem>
+//!
+//! Note that `unwrap()` is used to deal with networking errors; this is not something
+//! that is sensible outside of example code.
+
+#[cfg(unix)]
+fn main() -> Result<(), Box<dyn core::error::Error>> {
+    unix::main()
+}
+
+#[cfg(not(unix))]
+fn main() {
+    panic!("this example needs Unix domain sockets");
+}
+
+#[cfg(unix)]
+mod unix {
+    use core::error::Error as StdError;
+    use std::io::{self, Read, Write};
+    use std::net::TcpListener;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use std::thread;
+    use std::{env, fs};
+
+    use rustls::crypto::kx::{
+        CompletedKeyExchange, NamedGroup, StartedKeyExchange, SupportedKxGroup,
+    };
+    use rustls::crypto::{CryptoProvider, Identity};
+    use rustls::enums::ProtocolVersion;
+    use rustls::error::Error;
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+    use rustls::server::ServerHandshake;
+    use rustls::{ServerConfig, VecInput};
+    use rustls_aws_lc_rs::{DEFAULT_PROVIDER, kx_group};
+
+    pub(super) fn main() -> Result<(), Box<dyn StdError>> {
+        let mut args = env::args();
+        args.next();
+        let cert_file = args
+            .next()
+            .expect("missing certificate file argument");
+        let private_key_file = args
+            .next()
+            .expect("missing private key file argument");
+
+        let socket = env::temp_dir().join(format!("rustls-kx-daemon-{}.sock", std::process::id()));
+        let daemon = UnixListener::bind(&socket)?;
+        thread::spawn(move || run_daemon(daemon));
+
+        // Replace the provider's key exchange groups with ones backed by the daemon.
+        let kx_groups = [kx_group::X25519, kx_group::SECP256R1]
+            .into_iter()
+            .map(|group| -> &'static dyn SupportedKxGroup {
+                Box::leak(Box::new(DaemonKxGroup {
+                    name: group.name(),
+                    socket: socket.clone(),
+                }))
+            })
+            .collect::<Vec<_>>();
+        let provider = CryptoProvider {
+            tls12_cipher_suites: Default::default(),
+            kx_groups: kx_groups.into(),
+            ..DEFAULT_PROVIDER
+        };
+
+        let certs = CertificateDer::pem_file_iter(cert_file)
+            .unwrap()
+            .map(|cert| cert.unwrap())
+            .collect();
+        let private_key = PrivateKeyDer::from_pem_file(private_key_file).unwrap();
+        let config = Arc::new(
+            ServerConfig::builder(Arc::new(provider))
+                .with_no_client_auth()
+                .with_single_cert(Arc::new(Identity::from_cert_chain(certs)?), private_key)?,
+        );
+
+        let listener = TcpListener::bind(format!("[::]:{}", 4443)).unwrap();
+        let (mut stream, _) = listener.accept()?;
+        let mut handshake = ServerHandshake::NeedsInput(ServerHandshake::start());
+        let mut input = VecInput::default();
+        let mut output = vec![];
+
+        let _connection = loop {
+            handshake = match handshake {
+                ServerHandshake::NeedsInput(receive) => {
+                    match receive.process(&mut input, &mut output)? {
+                        next @ ServerHandshake::NeedsInput(_) => {
+                            stream.write_all(&output)?;
+                            output.clear();
+                            input.read(&mut stream)?;
+                            next
+                        }
+                        next => next,
+                    }
+                }
+
+                ServerHandshake::Accepted(accepted) => {
+                    accepted.choose_config(config.clone(), &mut output)?
+                }
+
+                // This is where the key exchange leaves the process.  An asynchronous
+                // server would instead send the request, and resume this handshake once
+                // the daemon replies.
+                ServerHandshake::CompleteKeyExchange(kx) => {
+                    println!("completing {:?} key exchange in daemon", kx.group());
+                    let result = daemon_complete(&socket, kx.group(), kx.peer_key_share());
+                    kx.continue_with(result, &mut output)?
+                }
+
+                ServerHandshake::Complete(connection) => break connection,
+
+                other => panic!("unexpected ServerHandshake state {other:?}"),
+            };
+
+            stream.write_all(&output)?;
+            output.clear();
+        };
+
+        println!("handshake complete");
+        fs::remove_file(&socket)?;
+        Ok(())
+    }
+
+    /// A key exchange group whose key agreement happens in the key daemon.
+    #[derive(Debug)]
+    struct DaemonKxGroup {
+        name: NamedGroup,
+        socket: PathBuf,
+    }
+
+    impl SupportedKxGroup for DaemonKxGroup {
+        fn start(&self) -> Result<StartedKeyExchange, Error> {
+            // The daemon only offers the one-shot operation needed by TLS1.3 servers.
+            Err(Error::General(
+                "key daemon cannot start a key exchange".into(),
+            ))
+        }
+
+        fn start_and_complete(&self, peer_pub_key: &[u8]) -> Result<CompletedKeyExchange, Error> {
+            daemon_complete(&self.socket, self.name, peer_pub_key)
+        }
+
+        fn name(&self) -> NamedGroup {
+            self.name
+        }
+
+        fn defers_completion(&self) -> bool {
+            true
+        }
+    }
+
+    /// Ask the daemon at `socket` to complete a key exchange with a peer's key share.
+    ///
+    /// The request is the group and the peer's key share; the response is a status byte,
+    /// then our key share and the shared secret.  All byte strings are prefixed by a
+    /// big-endian `u16` length.
+    fn daemon_complete(
+        socket: &Path,
+        group: NamedGroup,
+        peer_pub_key: &[u8],
+    ) -> Result<CompletedKeyExchange, Error> {
+        let transact = || -> io::Result<Option<(Vec<u8>, Vec<u8>)>> {
+            let mut daemon = UnixStream::connect(socket)?;
+            daemon.write_all(&u16::from(group).to_be_bytes())?;
+            write_bytes(&mut daemon, peer_pub_key)?;
+
+            let mut status = [0u8];
+            daemon.read_exact(&mut status)?;
+            if status[0] != 0 {
+                return Ok(None);
+            }
+
+            let pub_key = read_bytes(&mut daemon)?;
+            let secret = read_bytes(&mut daemon)?;
+            Ok(Some((pub_key, secret)))
+        };
+
+        match transact() {
+            Ok(Some((pub_key, secret))) => Ok(CompletedKeyExchange {
+                group,
+                pub_key,
+                secret: secret.into(),
+            }),
+            Ok(None) => Err(Error::General("key daemon failed key exchange".into())),
+            Err(err) => Err(Error::General(format!("cannot reach key daemon: {err}"))),
+        }
+    }
+
+    /// The stand-in key daemon.
+    fn run_daemon(listener: UnixListener) {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+
+            let mut group = [0u8; 2];
+            stream.read_exact(&mut group).unwrap();
+            let group = NamedGroup::from(u16::from_be_bytes(group));
+            let peer_pub_key = read_bytes(&mut stream).unwrap();
+
+            let completed = DEFAULT_PROVIDER
+                .kx_groups
+                .iter()
+                .find(|skxg| {
+                    skxg.name() == group && group.usable_for_version(ProtocolVersion::TLSv1_3)
+                })
+                .and_then(|skxg| {
+                    skxg.start_and_complete(&peer_pub_key)
+                        .ok()
+                });
+
+            match completed {
+                Some(ckx) => {
+                    stream.write_all(&[0]).unwrap();
+                    write_bytes(&mut stream, &ckx.pub_key).unwrap();
+                    write_bytes(&mut stream, ckx.secret.secret_bytes()).unwrap();
+                }
+                None => stream.write_all(&[1]).unwrap(),
+            }
+        }
+    }
+
+    fn write_bytes(w: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+        let len = u16::try_from(bytes.len()).map_err(|_| io::ErrorKind::InvalidInput)?;
+        w.write_all(&len.to_be_bytes())?;
+        w.write_all(bytes)
+    }
+
+    fn read_bytes(r: &mut impl Read) -> io::Result<Vec<u8>> {
+        let mut len = [0u8; 2];
+        r.read_exact(&mut len)?;
+        let mut bytes = vec![0; usize::from(u16::from_be_bytes(len))];
+        r.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+}
@@ -4,5 +4,6 @@ mod early_exporter;
 mod ffdhe;
 mod ffdhe_kx_with_openssl;
 mod raw_key_openssl_interop;
+mod renegotiation;
 mod utils;
 mod validate_ffdhe_params;
@@ -0,0 +1,298 @@
+//! Tests for client-side TLS1.2 secure renegotiation against an OpenSSL server.
+//!
+//! The server demands a client certificate only once the client has sent
+//! some application data.  This mirrors the "client certificate step-up" that
+//! some legacy servers use.  A server which presents a different certificate
+//! in the renegotiation is refused.
+
+use core::ffi::{c_int, c_void};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::borrow::Cow;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::{fs, thread};
+
+use openssl::ssl::{
+    ClientHelloResponse, SslAcceptor, SslFiletype, SslMethod, SslRef, SslStream, SslVerifyMode,
+    SslVersion,
+};
+use rustls::crypto::{CertificateIdentity, CryptoProvider, Identity, VerifiedIdentity};
+use rustls::error::{Error, PeerMisbehaved};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::{ClientConfig, ClientConnection, Connection, RootCertStore, VecInput};
+use rustls_aws_lc_rs as provider;
+use rustls_util::complete_io;
+
+use crate::utils::verify_openssl3_available;
+
+unsafe extern "C" {
+    // Not exposed by the `openssl` crate.
+    fn SSL_renegotiate(ssl: *mut c_void) -> c_int;
+}
+
+#[test]
+fn rustls_client_follows_openssl_server_renegotiation() {
+    verify_openssl3_available();
+
+    let (port, server_thread) = spawn_server(false, |mut stream| {
+        renegotiate(&mut stream);
+
+        let mut buf = [0u8; 5];
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"again");
+        assert!(
+            stream
+                .ssl()
+                .peer_certificate()
+                .is_some()
+        );
+
+        stream.write_all(b"goodbye\n").unwrap();
+        stream.shutdown().unwrap();
+    });
+
+    let mut client = Client::connect(port);
+    let initial_identity = client
+        .conn
+        .peer_identity()
+        .cloned()
+        .unwrap();
+    assert_eq!(
+        end_entity(&initial_identity),
+        &load_certs(INITIAL_CERT_CHAIN_FILE)[0]
+    );
+    let initial_exporter = client.conn.exporter().unwrap();
+
+    client
+        .conn
+        .write_tls(b"hello".as_slice().into(), &mut client.output)
+        .unwrap();
+
+    // drive the renegotiation until it completes, which makes a new exporter available.
+    let renegotiated_exporter = loop {
+        if let Ok(exporter) = client.conn.exporter() {
+            break exporter;
+        }
+        client.complete_io().unwrap();
+    };
+
+    let mut initial_secret = [0u8; 32];
+    let mut renegotiated_secret = [0u8; 32];
+    initial_exporter
+        .derive(b"label", None, &mut initial_secret)
+        .unwrap();
+    renegotiated_exporter
+        .derive(b"label", None, &mut renegotiated_secret)
+        .unwrap();
+    assert_ne!(initial_secret, renegotiated_secret);
+
+    client
+        .conn
+        .write_tls(b"again".as_slice().into(), &mut client.output)
+        .unwrap();
+
+    while !client
+        .received_plaintext
+        .ends_with(b"\n")
+    {
+        client.complete_io().unwrap();
+    }
+    assert_eq!(client.received_plaintext, b"goodbye\n");
+    assert_eq!(client.conn.peer_identity(), Some(&initial_identity));
+
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn rustls_client_refuses_openssl_server_certificate_change_on_renegotiation() {
+    verify_openssl3_available();
+
+    let (port, server_thread) = spawn_server(true, |mut stream| {
+        renegotiate(&mut stream);
+
+        let mut buf = [0u8; 5];
+        stream.read_exact(&mut buf).unwrap_err();
+    });
+
+    let mut client = Client::connect(port);
+    client
+        .conn
+        .write_tls(b"hello".as_slice().into(), &mut client.output)
+        .unwrap();
+
+    let err = loop {
+        if let Err(err) = client.complete_io() {
+            break err;
+        }
+    };
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(
+        err.into_inner()
+            .unwrap()
+            .downcast::<Error>()
+            .unwrap(),
+        Box::new(Error::PeerMisbehaved(
+            PeerMisbehaved::ServerCertificateChangedDuringRenegotiation
+        ))
+    );
+
+    server_thread.join().unwrap();
+}
+
+/// Start an OpenSSL server, which reads "hello" from the client, then calls `then`.
+///
+/// In the renegotiation, the server demands a client certificate and, if
+/// `change_certificate`, presents a different certificate.
+fn spawn_server(
+    change_certificate: bool,
+    then: impl FnOnce(SslStream<TcpStream>) + Send + 'static,
+) -> (u16, thread::JoinHandle<()>) {
+    let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+    acceptor
+        .set_max_proto_version(Some(SslVersion::TLS1_2))
+        .unwrap();
+    acceptor
+        .set_certificate_chain_file(INITIAL_CERT_CHAIN_FILE)
+        .unwrap();
+    acceptor
+        .set_private_key_file(INITIAL_PRIV_KEY_FILE, SslFiletype::PEM)
+        .unwrap();
+    acceptor
+        .set_ca_file(CLIENT_CA_PEM_FILE)
+        .unwrap();
+    acceptor.set_client_hello_callback({
+        let client_hellos = AtomicUsize::new(0);
+        move |ssl, _alert| {
+            if client_hellos.fetch_add(1, Ordering::SeqCst) > 0 {
+                // this is the renegotiation: step up to client authentication.
+                ssl.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+                if change_certificate {
+                    ssl.set_certificate_chain_file(RENEGOTIATED_CERT_CHAIN_FILE)?;
+                    ssl.set_private_key_file(RENEGOTIATED_PRIV_KEY_FILE, SslFiletype::PEM)?;
+                }
+            }
+            Ok(ClientHelloResponse::SUCCESS)
+        }
+    });
+    let acceptor = acceptor.build();
+
+    let listener = TcpListener::bind(("localhost", 0)).unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let server_thread = thread::spawn(move || {
+        let (stream, _addr) = listener.accept().unwrap();
+        let mut stream = acceptor.accept(stream).unwrap();
+
+        let mut buf = [0u8; 5];
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hello");
+        assert!(
+            stream
+                .ssl()
+                .peer_certificate()
+                .is_none()
+        );
+
+        then(stream);
+    });
+
+    (port, server_thread)
+}
+
+/// Ask the client to renegotiate.
+fn renegotiate(stream: &mut SslStream<TcpStream>) {
+    let ssl = stream.ssl() as *const SslRef as *mut c_void;
+    assert_eq!(unsafe { SSL_renegotiate(ssl) }, 1);
+    // sends HelloRequest; the renegotiation is completed by the next read.
+    stream.do_handshake().unwrap();
+}
+
+/// A rustls client which allows renegotiation, connected over TCP.
+struct Client {
+    conn: ClientConnection,
+    tcp_stream: TcpStream,
+    input: VecInput,
+    output: Vec<u8>,
+    received_plaintext: Vec<u8>,
+}
+
+impl Client {
+    /// Connect to `port` and complete the initial handshake.
+    fn connect(port: u16) -> Self {
+        let mut config = ClientConfig::builder(Arc::new(TLS12_PROVIDER))
+            .with_root_certificates(root_ca())
+            .with_client_auth_cert(
+                Arc::new(Identity::from_cert_chain(load_certs(CLIENT_CERT_CHAIN_FILE)).unwrap()),
+                PrivateKeyDer::from_pem_file(CLIENT_PRIV_KEY_FILE).unwrap(),
+            )
+            .unwrap();
+        config.max_renegotiations = 1;
+
+        let server_name = ServerName::try_from("testserver.com").unwrap();
+        let mut output = Vec::new();
+        let conn = Arc::new(config)
+            .connect(server_name)
+            .build(&mut output)
+            .unwrap();
+
+        let mut client = Self {
+            conn,
+            tcp_stream: TcpStream::connect(("localhost", port)).unwrap(),
+            input: VecInput::default(),
+            output,
+            received_plaintext: Vec::new(),
+        };
+        client.complete_io().unwrap();
+        client
+    }
+
+    fn complete_io(&mut self) -> Result<(usize, usize), io::Error> {
+        complete_io(
+            &mut self.tcp_stream,
+            &mut self.input,
+            &mut self.received_plaintext,
+            &mut self.output,
+            &mut self.conn,
+        )
+    }
+}
+
+fn end_entity<'a>(identity: &'a VerifiedIdentity<'_>) -> &'a CertificateDer<'a> {
+    match identity.identity() {
+        Identity::X509(CertificateIdentity { end_entity, .. }) => end_entity,
+        _ => panic!("unexpected identity {identity:?}"),
+    }
+}
+
+fn root_ca() -> RootCertStore {
+    let mut res = RootCertStore::empty();
+    res.add_parsable_certificates(
+        [INITIAL_CA_FILE, RENEGOTIATED_CA_FILE]
+            .map(|path| CertificateDer::from(fs::read(path).unwrap())),
+    );
+    res
+}
+
+fn load_certs(path: &str) -> Vec<CertificateDer<'static>> {
+    CertificateDer::pem_file_iter(path)
+        .unwrap()
+        .map(|c| c.unwrap())
+        .collect()
+}
+
+const TLS12_PROVIDER: CryptoProvider = CryptoProvider {
+    tls13_cipher_suites: Cow::Borrowed(&[]),
+    ..provider::DEFAULT_PROVIDER
+};
+
+const INITIAL_CERT_CHAIN_FILE: &str = "../test-ca/rsa-2048/end.fullchain";
+const INITIAL_PRIV_KEY_FILE: &str = "../test-ca/rsa-2048/end.key";
+const INITIAL_CA_FILE: &str = "../test-ca/rsa-2048/ca.der";
+const RENEGOTIATED_CERT_CHAIN_FILE: &str = "../test-ca/rsa-3072/end.fullchain";
+const RENEGOTIATED_PRIV_KEY_FILE: &str = "../test-ca/rsa-3072/end.key";
+const RENEGOTIATED_CA_FILE: &str = "../test-ca/rsa-3072/ca.der";
+const CLIENT_CERT_CHAIN_FILE: &str = "../test-ca/rsa-2048/client.fullchain";
+const CLIENT_PRIV_KEY_FILE: &str = "../test-ca/rsa-2048/client.key";
+const CLIENT_CA_PEM_FILE: &str = "../test-ca/rsa-2048/ca.cert";
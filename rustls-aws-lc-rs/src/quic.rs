@@ -23,10 +23,7 @@ impl HeaderProtectionKey {
         // This implements "Header Protection Application" almost verbatim.
         // <https://datatracker.ietf.org/doc/html/rfc9001#section-5.4.1>
 
-        let mask = self
-            .0
-            .new_mask(sample)
-            .map_err(|_| Error::ApiMisuse(ApiMisuse::InvalidQuicHeaderProtectionSampleLength))?;
+        let mask = quic::HeaderProtectionKey::mask(self, sample)?;
 
         // The `unwrap()` will not panic because `new_mask` returns a
         // non-empty result.
@@ -87,6 +84,12 @@ impl quic::HeaderProtectionKey for HeaderProtectionKey {
         self.xor_in_place(sample, first, packet_number, true)
     }
 
+    fn mask(&self, sample: &[u8]) -> Result<[u8; 5], Error> {
+        self.0
+            .new_mask(sample)
+            .map_err(|_| Error::ApiMisuse(ApiMisuse::InvalidQuicHeaderProtectionSampleLength))
+    }
+
     #[inline]
     fn sample_len(&self) -> usize {
         self.0.algorithm().sample_len()
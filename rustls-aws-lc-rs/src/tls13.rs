@@ -4,8 +4,9 @@ use aws_lc_rs::hkdf::KeyType;
 use aws_lc_rs::{aead, hkdf, hmac};
 use pki_types::FipsStatus;
 use rustls::crypto::cipher::{
-    AeadKey, EncodedMessage, EncryptBuffer, InboundOpaque, Iv, MessageDecrypter, MessageEncrypter,
-    Nonce, OutboundPlain, Tls13AeadAlgorithm, UnsupportedOperationError, make_tls13_aad,
+    AeadKey, EncodedMessage, EncryptBuffer, InboundOpaque, InboundRecord, Iv, MessageDecrypter,
+    MessageEncrypter, Nonce, OutboundPlain, OutboundRecord, Tls13AeadAlgorithm,
+    UnsupportedOperationError, make_tls13_aad,
 };
 use rustls::crypto::tls13::{Hkdf, HkdfExpander, OkmBlock, OutputLengthError};
 use rustls::crypto::{self, CipherSuite};
@@ -245,13 +246,27 @@ impl MessageEncrypter for AeadMessageEncrypter {
         seq: u64,
         out: &'a mut [u8],
     ) -> Result<EncodedMessage<&'a [u8]>, Error> {
-        let total_len = self.encrypted_payload_len(msg.payload.len());
+        self.encrypt_padded(msg, 0, seq, out)
+    }
+
+    fn encrypt_padded<'a>(
+        &mut self,
+        msg: EncodedMessage<OutboundPlain<'_>>,
+        padding: usize,
+        seq: u64,
+        out: &'a mut [u8],
+    ) -> Result<EncodedMessage<&'a [u8]>, Error> {
+        let total_len = self.encrypted_payload_len(msg.payload.len() + padding);
 
         let typ = ContentType::ApplicationData;
         let nonce = aead::Nonce::assume_unique_for_key(Nonce::new(&self.iv, seq).to_array()?);
         let aad = aead::Aad::from(make_tls13_aad(typ, msg.version.encode(), total_len));
 
-        let payload = match msg.payload.single_chunk() {
+        let payload = match msg
+            .payload
+            .single_chunk()
+            .filter(|_| padding == 0)
+        {
             // Contiguous plaintext is sealed out-of-place, straight from the borrowed
             // input and the inner content type byte is specified as `extra_in`.
             Some(plain) => {
@@ -269,12 +284,13 @@ impl MessageEncrypter for AeadMessageEncrypter {
                     .map_err(|_| Error::EncryptError)?;
                 &*record
             }
-            // Fragmented plaintext is gathered into `out` and then sealed in place.
-            // We can't use the out-of-place seal as it requires contiguous input.
+            // Fragmented or padded plaintext is gathered into `out` and then sealed in
+            // place.  We can't use the out-of-place seal as it requires contiguous input.
             None => {
                 let mut payload = EncryptBuffer::new(out, total_len)?;
                 payload.extend_from_chunks(&msg.payload);
                 payload.extend_from_slice(&msg.typ.to_array());
+                payload.extend_zeroed(padding);
 
                 match self
                     .enc_key
@@ -298,6 +314,14 @@ impl MessageEncrypter for AeadMessageEncrypter {
     fn encrypted_payload_len(&self, payload_len: usize) -> usize {
         payload_len + 1 + self.enc_key.algorithm().tag_len()
     }
+
+    fn supports_padding(&self) -> bool {
+        true
+    }
+
+    fn encrypt_batch(&mut self, records: &mut [OutboundRecord<'_>], seq: u64) -> Result<(), Error> {
+        seal_batch(self, records, seq)
+    }
 }
 
 impl MessageDecrypter for AeadMessageDecrypter {
@@ -326,6 +350,11 @@ impl MessageDecrypter for AeadMessageDecrypter {
         payload.truncate(plain_len);
         msg.into_tls13_unpadded_message()
     }
+
+    fn decrypt_batch(&mut self, records: &mut [InboundRecord<'_>], seq: u64) -> Result<(), Error> {
+        let tag_len = self.dec_key.algorithm().tag_len();
+        open_batch(self, records, seq, tag_len)
+    }
 }
 
 struct GcmMessageEncrypter {
@@ -340,13 +369,27 @@ impl MessageEncrypter for GcmMessageEncrypter {
         seq: u64,
         out: &'a mut [u8],
     ) -> Result<EncodedMessage<&'a [u8]>, Error> {
-        let total_len = self.encrypted_payload_len(msg.payload.len());
+        self.encrypt_padded(msg, 0, seq, out)
+    }
+
+    fn encrypt_padded<'a>(
+        &mut self,
+        msg: EncodedMessage<OutboundPlain<'_>>,
+        padding: usize,
+        seq: u64,
+        out: &'a mut [u8],
+    ) -> Result<EncodedMessage<&'a [u8]>, Error> {
+        let total_len = self.encrypted_payload_len(msg.payload.len() + padding);
 
         let typ = ContentType::ApplicationData;
         let nonce = aead::Nonce::assume_unique_for_key(Nonce::new(&self.iv, seq).to_array()?);
         let aad = aead::Aad::from(make_tls13_aad(typ, msg.version.encode(), total_len));
 
-        let payload = match msg.payload.single_chunk() {
+        let payload = match msg
+            .payload
+            .single_chunk()
+            .filter(|_| padding == 0)
+        {
             // Contiguous plaintext is sealed out-of-place, straight from the borrowed
             // input and the inner content type byte is specified as `extra_in`.
             Some(plain) => {
@@ -364,12 +407,13 @@ impl MessageEncrypter for GcmMessageEncrypter {
                     .map_err(|_| Error::EncryptError)?;
                 &*record
             }
-            // Fragmented plaintext is gathered into `out` and then sealed in place.
-            // We can't use the out-of-place seal as it requires contiguous input.
+            // Fragmented or padded plaintext is gathered into `out` and then sealed in
+            // place.  We can't use the out-of-place seal as it requires contiguous input.
             None => {
                 let mut payload = EncryptBuffer::new(out, total_len)?;
                 payload.extend_from_chunks(&msg.payload);
                 payload.extend_from_slice(&msg.typ.to_array());
+                payload.extend_zeroed(padding);
 
                 match self
                     .enc_key
@@ -393,6 +437,14 @@ impl MessageEncrypter for GcmMessageEncrypter {
     fn encrypted_payload_len(&self, payload_len: usize) -> usize {
         payload_len + 1 + self.enc_key.algorithm().tag_len()
     }
+
+    fn supports_padding(&self) -> bool {
+        true
+    }
+
+    fn encrypt_batch(&mut self, records: &mut [OutboundRecord<'_>], seq: u64) -> Result<(), Error> {
+        seal_batch(self, records, seq)
+    }
 }
 
 struct GcmMessageDecrypter {
@@ -426,6 +478,63 @@ impl MessageDecrypter for GcmMessageDecrypter {
         payload.truncate(plain_len);
         msg.into_tls13_unpadded_message()
     }
+
+    fn decrypt_batch(&mut self, records: &mut [InboundRecord<'_>], seq: u64) -> Result<(), Error> {
+        let tag_len = self.dec_key.algorithm().tag_len();
+        open_batch(self, records, seq, tag_len)
+    }
+}
+
+/// Encrypt a batch of records with a single call into `encrypter`.
+///
+/// aws-lc has no multi-buffer AEAD, so each record is still sealed on its own, but
+/// every record's output region is checked first: a batch is either encrypted in
+/// full, or not at all, and a sealing key is never left part-way through a batch.
+fn seal_batch(
+    encrypter: &mut impl MessageEncrypter,
+    records: &mut [OutboundRecord<'_>],
+    seq: u64,
+) -> Result<(), Error> {
+    for record in records.iter_mut() {
+        let len = encrypter.encrypted_payload_len(record.msg.payload.len() + record.padding);
+        record_region(record.out, len)?;
+    }
+
+    for (record, seq) in records.iter_mut().zip(seq..) {
+        let encrypted =
+            encrypter.encrypt_padded(record.msg.clone(), record.padding, seq, record.out)?;
+        let (typ, version, len) = (encrypted.typ, encrypted.version, encrypted.payload.len());
+        record.encrypted = Some(EncodedMessage::new(typ, version, len));
+    }
+    Ok(())
+}
+
+/// Decrypt a batch of records with a single call into `decrypter`.
+///
+/// Records too short to hold a `tag_len`-byte tag are rejected before any record
+/// is decrypted.
+fn open_batch(
+    decrypter: &mut impl MessageDecrypter,
+    records: &mut [InboundRecord<'_>],
+    seq: u64,
+    tag_len: usize,
+) -> Result<(), Error> {
+    if records
+        .iter()
+        .any(|record| match record {
+            InboundRecord::Encrypted(msg) => msg.payload.len() < tag_len,
+            _ => false,
+        })
+    {
+        return Err(Error::DecryptError);
+    }
+
+    for (record, seq) in records.iter_mut().zip(seq..) {
+        if let Some(msg) = record.take_encrypted() {
+            *record = InboundRecord::Decrypted(decrypter.decrypt(msg, seq)?);
+        }
+    }
+    Ok(())
 }
 
 struct AwsLcHkdf(hkdf::Algorithm, hmac::Algorithm);
@@ -554,6 +663,110 @@ mod tests {
         }
     }
 
+    /// Batches encrypt and decrypt records exactly as one-at-a-time calls do.
+    ///
+    /// This starts from sequence number zero, as aws-lc's TLS1.3 GCM sealing key
+    /// requires for a run of records.
+    #[test]
+    fn batches_match_single_records() {
+        let plains = [&b"one"[..], b"", b"three records"];
+
+        for suite in ALL_TLS13_CIPHER_SUITES {
+            let key_len = suite.aead_alg.key_len();
+            let mut encrypter = suite
+                .aead_alg
+                .encrypter(test_key(key_len), Iv::from(TEST_IV));
+            let mut outs = plains
+                .iter()
+                .map(|plain| vec![0u8; encrypter.encrypted_payload_len(plain.len() + 2)])
+                .collect::<Vec<_>>();
+            let mut records = plains
+                .iter()
+                .zip(&mut outs)
+                .map(|(plain, out)| {
+                    OutboundRecord::new(message(OutboundPlain::from(*plain)), 2, out)
+                })
+                .collect::<Vec<_>>();
+            encrypter
+                .encrypt_batch(&mut records, 0)
+                .unwrap();
+
+            let mut sealed = Vec::new();
+            for (i, (record, plain)) in records.iter().zip(plains).enumerate() {
+                let encrypted = record.encrypted.as_ref().unwrap();
+                assert_eq!(encrypted.typ, ContentType::ApplicationData);
+                assert_eq!(encrypted.payload, record.out.len());
+
+                let mut single = vec![0u8; record.out.len()];
+                suite
+                    .aead_alg
+                    .encrypter(test_key(key_len), Iv::from(TEST_IV))
+                    .encrypt_padded(
+                        message(OutboundPlain::from(plain)),
+                        2,
+                        i as u64,
+                        &mut single,
+                    )
+                    .unwrap();
+                assert_eq!(*record.out, single, "{:?}", suite.common.suite);
+                sealed.push(single);
+            }
+
+            let mut records = sealed
+                .iter_mut()
+                .map(|sealed| {
+                    InboundRecord::Encrypted(EncodedMessage::new(
+                        ContentType::ApplicationData,
+                        EncodableVersion::Legacy(ProtocolVersion::TLSv1_2),
+                        InboundOpaque(sealed),
+                    ))
+                })
+                .collect::<Vec<_>>();
+            suite
+                .aead_alg
+                .decrypter(test_key(key_len), Iv::from(TEST_IV))
+                .decrypt_batch(&mut records, 0)
+                .unwrap();
+            for (record, plain) in records.iter().zip(plains) {
+                let InboundRecord::Decrypted(opened) = record else {
+                    panic!("record not decrypted");
+                };
+                assert_eq!(opened.payload, plain, "{:?}", suite.common.suite);
+            }
+        }
+    }
+
+    /// A batch with one undersized output region is rejected before anything is encrypted.
+    #[test]
+    fn batch_with_short_output_is_not_encrypted() {
+        let suite = TLS13_AES_128_GCM_SHA256;
+        let mut encrypter = suite
+            .aead_alg
+            .encrypter(test_key(suite.aead_alg.key_len()), Iv::from(TEST_IV));
+        let mut good = vec![0u8; encrypter.encrypted_payload_len(5)];
+        let mut short = vec![0u8; 3];
+        let mut records = [
+            OutboundRecord::new(message(OutboundPlain::from(&b"hello"[..])), 0, &mut good),
+            OutboundRecord::new(message(OutboundPlain::from(&b"hello"[..])), 0, &mut short),
+        ];
+        encrypter
+            .encrypt_batch(&mut records, TEST_SEQ)
+            .unwrap_err();
+        assert!(
+            records
+                .iter()
+                .all(|record| record.encrypted.is_none())
+        );
+    }
+
+    fn message(payload: OutboundPlain<'_>) -> EncodedMessage<OutboundPlain<'_>> {
+        EncodedMessage::new(
+            ContentType::ApplicationData,
+            EncodableVersion::Legacy(ProtocolVersion::TLSv1_3),
+            payload,
+        )
+    }
+
     fn seal(suite: &Tls13CipherSuite, payload: OutboundPlain<'_>, fill: u8) -> Vec<u8> {
         let mut encrypter = suite
             .aead_alg
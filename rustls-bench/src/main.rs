@@ -219,6 +219,8 @@ fn all_tests(args: &Args) {
         let params = Parameters::new(bench, args).with_plaintext_size(1024 * 1024);
         bench_bulk(&params);
         bench_bulk(&params.with_max_fragment(Some(10000)));
+        // Many small records per write: the case batched record encryption is for.
+        bench_bulk(&params.with_max_fragment(Some(1024)));
         bench_handshake(&params);
         bench_handshake(&params.with_client_auth(ClientAuth::Yes));
         bench_handshake(&params.with_resume(ResumptionParam::SessionId));
@@ -0,0 +1,198 @@
+//! Composite ML-DSA signature algorithms.
+//!
+//! These pair an ML-DSA signature with a classical ECDSA or Ed25519 signature, following
+//! [draft-ietf-lamps-pq-composite-sigs] for key, signature and message encoding, and
+//! [draft-reddy-tls-composite-mldsa] for the TLS `SignatureScheme` code points.
+//!
+//! Use [`CompositeSigningKey::new()`] with one of the algorithms here, an ML-DSA signing key
+//! (loaded with [`load_ml_dsa_key()`]), and a matching classical signing key (loaded with
+//! [`DEFAULT_PROVIDER`][crate::DEFAULT_PROVIDER]'s `key_provider`).
+//!
+//! [draft-ietf-lamps-pq-composite-sigs]: https://datatracker.ietf.org/doc/draft-ietf-lamps-pq-composite-sigs/
+//! [draft-reddy-tls-composite-mldsa]: https://datatracker.ietf.org/doc/draft-reddy-tls-composite-mldsa/
+//! [`CompositeSigningKey::new()`]: rustls::crypto::composite::CompositeSigningKey::new()
+
+use aws_lc_rs::digest;
+use aws_lc_rs::signature::PqdsaKeyPair;
+use rustls::Error;
+use rustls::crypto::composite::{
+    CompositeAlgorithm, CompositeComponent, ContextSigningKey, PostQuantumComponent,
+};
+use rustls::crypto::hash::{Context, Hash, Output};
+use rustls::crypto::{HashAlgorithm, SignatureScheme};
+use rustls::pki_types::{AlgorithmIdentifier, PrivateKeyDer};
+
+use crate::PqdsaKeyKind;
+use crate::ml_dsa::{ContextSigner, ContextVerifier};
+
+/// Load an ML-DSA private key, for use as the post-quantum half of a
+/// [`CompositeSigningKey`].
+///
+/// The key must be an ML-DSA-44, ML-DSA-65 or ML-DSA-87 key in PKCS#8 format.
+///
+/// [`CompositeSigningKey`]: rustls::crypto::composite::CompositeSigningKey
+pub fn load_ml_dsa_key(key_der: &PrivateKeyDer<'_>) -> Result<Box<dyn ContextSigningKey>, Error> {
+    if let PrivateKeyDer::Pkcs8(pkcs8) = key_der {
+        for kind in PqdsaKeyKind::iter() {
+            if let Ok(key_pair) = PqdsaKeyPair::from_pkcs8(kind.to_alg(), pkcs8.secret_pkcs8_der())
+            {
+                return Ok(Box::new(ContextSigner::new(kind, &key_pair)?));
+            }
+        }
+    }
+
+    Err(Error::General(
+        "failed to parse private key as ML-DSA".into(),
+    ))
+}
+
+/// ML-DSA-44 with ECDSA on P-256, pre-hashed with SHA-256.
+pub static MLDSA44_ECDSA_P256_SHA256: &CompositeAlgorithm = &CompositeAlgorithm {
+    scheme: SignatureScheme::MLDSA44_ECDSA_SECP256R1_SHA256,
+    // id-MLDSA44-ECDSA-P256-SHA256: 1.3.6.1.5.5.7.6.40
+    alg_id: AlgorithmIdentifier::from_slice(&[
+        0x06, 0x08, 0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x06, 0x28,
+    ]),
+    label: b"COMPSIG-MLDSA44-ECDSA-P256-SHA256",
+    prehash: &SHA256,
+    post_quantum: PostQuantumComponent {
+        verifier: &ContextVerifier(PqdsaKeyKind::MlDsa44),
+        public_key_len: ML_DSA_44_PUBLIC_KEY_LEN,
+        signature_len: ML_DSA_44_SIGNATURE_LEN,
+    },
+    classical: CompositeComponent {
+        scheme: SignatureScheme::ECDSA_NISTP256_SHA256,
+        verifier: rustls_aws_lc_rs::ECDSA_P256_SHA256,
+    },
+};
+
+/// ML-DSA-65 with ECDSA on P-384, pre-hashed with SHA-512.
+pub static MLDSA65_ECDSA_P384_SHA512: &CompositeAlgorithm = &CompositeAlgorithm {
+    scheme: SignatureScheme::MLDSA65_ECDSA_SECP384R1_SHA512,
+    // id-MLDSA65-ECDSA-P384-SHA512: 1.3.6.1.5.5.7.6.46
+    alg_id: AlgorithmIdentifier::from_slice(&[
+        0x06, 0x08, 0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x06, 0x2e,
+    ]),
+    label: b"COMPSIG-MLDSA65-ECDSA-P384-SHA512",
+    prehash: &SHA512,
+    post_quantum: PostQuantumComponent {
+        verifier: &ContextVerifier(PqdsaKeyKind::MlDsa65),
+        public_key_len: ML_DSA_65_PUBLIC_KEY_LEN,
+        signature_len: ML_DSA_65_SIGNATURE_LEN,
+    },
+    classical: CompositeComponent {
+        scheme: SignatureScheme::ECDSA_NISTP384_SHA384,
+        verifier: rustls_aws_lc_rs::ECDSA_P384_SHA384,
+    },
+};
+
+/// ML-DSA-87 with ECDSA on P-384, pre-hashed with SHA-512.
+pub static MLDSA87_ECDSA_P384_SHA512: &CompositeAlgorithm = &CompositeAlgorithm {
+    scheme: SignatureScheme::MLDSA87_ECDSA_SECP384R1_SHA512,
+    // id-MLDSA87-ECDSA-P384-SHA512: 1.3.6.1.5.5.7.6.49
+    alg_id: AlgorithmIdentifier::from_slice(&[
+        0x06, 0x08, 0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x06, 0x31,
+    ]),
+    label: b"COMPSIG-MLDSA87-ECDSA-P384-SHA512",
+    prehash: &SHA512,
+    post_quantum: PostQuantumComponent {
+        verifier: &ContextVerifier(PqdsaKeyKind::MlDsa87),
+        public_key_len: ML_DSA_87_PUBLIC_KEY_LEN,
+        signature_len: ML_DSA_87_SIGNATURE_LEN,
+    },
+    classical: CompositeComponent {
+        scheme: SignatureScheme::ECDSA_NISTP384_SHA384,
+        verifier: rustls_aws_lc_rs::ECDSA_P384_SHA384,
+    },
+};
+
+/// ML-DSA-44 with Ed25519, pre-hashed with SHA-512.
+pub static MLDSA44_ED25519_SHA512: &CompositeAlgorithm = &CompositeAlgorithm {
+    scheme: SignatureScheme::MLDSA44_ED25519,
+    // id-MLDSA44-Ed25519-SHA512: 1.3.6.1.5.5.7.6.39
+    alg_id: AlgorithmIdentifier::from_slice(&[
+        0x06, 0x08, 0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x06, 0x27,
+    ]),
+    label: b"COMPSIG-MLDSA44-Ed25519-SHA512",
+    prehash: &SHA512,
+    post_quantum: PostQuantumComponent {
+        verifier: &ContextVerifier(PqdsaKeyKind::MlDsa44),
+        public_key_len: ML_DSA_44_PUBLIC_KEY_LEN,
+        signature_len: ML_DSA_44_SIGNATURE_LEN,
+    },
+    classical: CompositeComponent {
+        scheme: SignatureScheme::ED25519,
+        verifier: rustls_aws_lc_rs::ED25519,
+    },
+};
+
+/// ML-DSA-65 with Ed25519, pre-hashed with SHA-512.
+pub static MLDSA65_ED25519_SHA512: &CompositeAlgorithm = &CompositeAlgorithm {
+    scheme: SignatureScheme::MLDSA65_ED25519,
+    // id-MLDSA65-Ed25519-SHA512: 1.3.6.1.5.5.7.6.48
+    alg_id: AlgorithmIdentifier::from_slice(&[
+        0x06, 0x08, 0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x06, 0x30,
+    ]),
+    label: b"COMPSIG-MLDSA65-Ed25519-SHA512",
+    prehash: &SHA512,
+    post_quantum: PostQuantumComponent {
+        verifier: &ContextVerifier(PqdsaKeyKind::MlDsa65),
+        public_key_len: ML_DSA_65_PUBLIC_KEY_LEN,
+        signature_len: ML_DSA_65_SIGNATURE_LEN,
+    },
+    classical: CompositeComponent {
+        scheme: SignatureScheme::ED25519,
+        verifier: rustls_aws_lc_rs::ED25519,
+    },
+};
+
+// Sizes from FIPS 204, table 2.
+const ML_DSA_44_PUBLIC_KEY_LEN: usize = 1312;
+const ML_DSA_44_SIGNATURE_LEN: usize = 2420;
+const ML_DSA_65_PUBLIC_KEY_LEN: usize = 1952;
+const ML_DSA_65_SIGNATURE_LEN: usize = 3309;
+const ML_DSA_87_PUBLIC_KEY_LEN: usize = 2592;
+const ML_DSA_87_SIGNATURE_LEN: usize = 4627;
+
+static SHA256: Sha = Sha(&digest::SHA256, HashAlgorithm::SHA256);
+static SHA512: Sha = Sha(&digest::SHA512, HashAlgorithm::SHA512);
+
+struct Sha(&'static digest::Algorithm, HashAlgorithm);
+
+impl Hash for Sha {
+    fn start(&self) -> Box<dyn Context> {
+        Box::new(ShaContext(digest::Context::new(self.0)))
+    }
+
+    fn hash(&self, data: &[u8]) -> Output {
+        Output::new(digest::digest(self.0, data).as_ref())
+    }
+
+    fn output_len(&self) -> usize {
+        self.0.output_len()
+    }
+
+    fn algorithm(&self) -> HashAlgorithm {
+        self.1
+    }
+}
+
+struct ShaContext(digest::Context);
+
+impl Context for ShaContext {
+    fn fork_finish(&self) -> Output {
+        Output::new(self.0.clone().finish().as_ref())
+    }
+
+    fn fork(&self) -> Box<dyn Context> {
+        Box::new(Self(self.0.clone()))
+    }
+
+    fn finish(self: Box<Self>) -> Output {
+        Output::new(self.0.finish().as_ref())
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+}
@@ -1,6 +1,9 @@
 //! This crate provides a [`CryptoProvider`] built on the default aws-lc-rs provider,
 //! with added support for three variants of the ML-DSA signature algorithm.
 //!
+//! It also provides [composite](composite) ML-DSA signature algorithms, which pair ML-DSA
+//! with a classical signature algorithm.
+//!
 //! Before rustls 0.23.22, this crate additionally provided support for the ML-KEM key exchange
 //! (both "pure" and hybrid variants), but these have been moved to the rustls crate itself.
 //! In rustls 0.23.22 and later, you can use rustls' `prefer-post-quantum` feature to determine
@@ -24,6 +27,9 @@ use rustls::pki_types::{
 };
 use rustls_aws_lc_rs::AwsLcRsVerificationAlgorithm;
 
+pub mod composite;
+mod ml_dsa;
+
 /// The default `CryptoProvider` backed by aws-lc-rs.
 pub const DEFAULT_PROVIDER: CryptoProvider = CryptoProvider {
     signature_verification_algorithms: SUPPORTED_SIG_ALGS,
@@ -134,7 +140,7 @@ impl Debug for PqdsaSigner {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
 enum PqdsaKeyKind {
     MlDsa44,
     MlDsa65,
@@ -194,6 +200,11 @@ static SUPPORTED_SIG_ALGS: WebPkiSupportedAlgorithms = match WebPkiSupportedAlgo
         ML_DSA_44,
         ML_DSA_65,
         ML_DSA_87,
+        composite::MLDSA44_ECDSA_P256_SHA256,
+        composite::MLDSA65_ECDSA_P384_SHA512,
+        composite::MLDSA87_ECDSA_P384_SHA512,
+        composite::MLDSA44_ED25519_SHA512,
+        composite::MLDSA65_ED25519_SHA512,
     ],
     &[
         // Note: for TLS1.2 the curve is not fixed by SignatureScheme. For TLS1.3 it is.
@@ -249,6 +260,26 @@ static SUPPORTED_SIG_ALGS: WebPkiSupportedAlgorithms = match WebPkiSupportedAlgo
         (SignatureScheme::ML_DSA_44, &[ML_DSA_44]),
         (SignatureScheme::ML_DSA_65, &[ML_DSA_65]),
         (SignatureScheme::ML_DSA_87, &[ML_DSA_87]),
+        (
+            SignatureScheme::MLDSA44_ECDSA_SECP256R1_SHA256,
+            &[composite::MLDSA44_ECDSA_P256_SHA256],
+        ),
+        (
+            SignatureScheme::MLDSA65_ECDSA_SECP384R1_SHA512,
+            &[composite::MLDSA65_ECDSA_P384_SHA512],
+        ),
+        (
+            SignatureScheme::MLDSA87_ECDSA_SECP384R1_SHA512,
+            &[composite::MLDSA87_ECDSA_P384_SHA512],
+        ),
+        (
+            SignatureScheme::MLDSA44_ED25519,
+            &[composite::MLDSA44_ED25519_SHA512],
+        ),
+        (
+            SignatureScheme::MLDSA65_ED25519,
+            &[composite::MLDSA65_ED25519_SHA512],
+        ),
     ],
 ) {
     Ok(algs) => algs,
@@ -288,6 +319,7 @@ mod tests {
         CertificateParams, CertifiedIssuer, ExtendedKeyUsagePurpose, IsCa, KeyPair, KeyUsagePurpose,
     };
     use rustls::crypto::Identity;
+    use rustls::crypto::composite::{CompositeAlgorithm, CompositeSigningKey};
     use rustls::{ClientConfig, RootCertStore, ServerConfig, ServerConnection, VecInput};
     use rustls_test::do_handshake;
 
@@ -346,4 +378,188 @@ mod tests {
             &mut server,
         );
     }
+
+    #[test]
+    fn composite_ml_dsa() {
+        for (algorithm, ml_dsa, classical) in [
+            (
+                composite::MLDSA44_ECDSA_P256_SHA256,
+                &rcgen::PKCS_ML_DSA_44,
+                &rcgen::PKCS_ECDSA_P256_SHA256,
+            ),
+            (
+                composite::MLDSA65_ECDSA_P384_SHA512,
+                &rcgen::PKCS_ML_DSA_65,
+                &rcgen::PKCS_ECDSA_P384_SHA384,
+            ),
+            (
+                composite::MLDSA87_ECDSA_P384_SHA512,
+                &rcgen::PKCS_ML_DSA_87,
+                &rcgen::PKCS_ECDSA_P384_SHA384,
+            ),
+            (
+                composite::MLDSA44_ED25519_SHA512,
+                &rcgen::PKCS_ML_DSA_44,
+                &rcgen::PKCS_ED25519,
+            ),
+            (
+                composite::MLDSA65_ED25519_SHA512,
+                &rcgen::PKCS_ML_DSA_65,
+                &rcgen::PKCS_ED25519,
+            ),
+        ] {
+            check_composite(algorithm, ml_dsa, classical);
+        }
+    }
+
+    fn check_composite(
+        algorithm: &'static CompositeAlgorithm,
+        ml_dsa: &'static rcgen::SignatureAlgorithm,
+        classical: &'static rcgen::SignatureAlgorithm,
+    ) {
+        let ml_dsa_key = KeyPair::generate_for(ml_dsa).unwrap();
+        let classical_key = KeyPair::generate_for(classical).unwrap();
+        let load = |key: &KeyPair| {
+            PqAwsLcRs
+                .load_private_key(PrivateKeyDer::try_from(key.serialize_der()).unwrap())
+                .unwrap()
+        };
+        let load_ml_dsa = |key: &KeyPair| {
+            composite::load_ml_dsa_key(&PrivateKeyDer::try_from(key.serialize_der()).unwrap())
+                .unwrap()
+        };
+
+        let key =
+            CompositeSigningKey::new(algorithm, load_ml_dsa(&ml_dsa_key), load(&classical_key))
+                .unwrap();
+
+        let raw_public_key = [ml_dsa_key.public_key_raw(), classical_key.public_key_raw()].concat();
+        assert_eq!(
+            key.public_key().unwrap(),
+            public_key_to_spki(&algorithm.alg_id, &raw_public_key)
+        );
+
+        assert!(
+            key.choose_scheme(&[SignatureScheme::ML_DSA_44])
+                .is_none()
+        );
+        let signer = key
+            .choose_scheme(&[algorithm.scheme])
+            .unwrap();
+        assert_eq!(signer.scheme(), algorithm.scheme);
+
+        let signature = signer.sign(b"hello world").unwrap();
+        assert!(
+            algorithm
+                .verify_signature(&raw_public_key, b"hello world", &signature)
+                .is_ok()
+        );
+        assert!(
+            algorithm
+                .verify_signature(&raw_public_key, b"goodbye world", &signature)
+                .is_err()
+        );
+
+        // corrupting either component invalidates the signature
+        for offset in [0, signature.len() - 1] {
+            let mut corrupt = signature.clone();
+            corrupt[offset] ^= 0x01;
+            assert!(
+                algorithm
+                    .verify_signature(&raw_public_key, b"hello world", &corrupt)
+                    .is_err()
+            );
+        }
+
+        // the ML-DSA component is signed with the label as its context string,
+        // so it is not a valid plain ML-DSA signature
+        let (ml_dsa_sig, _) = signature.split_at(algorithm.post_quantum.signature_len);
+        let m = message_representative(algorithm, b"hello world");
+        assert!(
+            algorithm
+                .post_quantum
+                .verifier
+                .verify_signature(ml_dsa_key.public_key_raw(), &m, algorithm.label, ml_dsa_sig)
+                .is_ok()
+        );
+        assert!(
+            algorithm
+                .post_quantum
+                .verifier
+                .verify_signature(ml_dsa_key.public_key_raw(), &m, &[], ml_dsa_sig)
+                .is_err()
+        );
+
+        // components are checked against the algorithm
+        assert!(
+            CompositeSigningKey::new(algorithm, load_ml_dsa(&ml_dsa_key), load(&ml_dsa_key))
+                .is_err()
+        );
+        assert!(
+            composite::load_ml_dsa_key(
+                &classical_key
+                    .serialize_der()
+                    .try_into()
+                    .unwrap()
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn composite_ml_dsa_construction() {
+        // Build a composite signature by hand, following draft-ietf-lamps-pq-composite-sigs:
+        //   M' = Prefix || Label || len(ctx) || ctx || PH(M)
+        //   mldsa_sig = ML-DSA.Sign(mldsa_sk, M', ctx=Label)
+        //   trad_sig = Trad.Sign(trad_sk, M')
+        let algorithm = composite::MLDSA65_ECDSA_P384_SHA512;
+        let message = b"The quick brown fox jumps over the lazy dog.";
+
+        let mut m = b"CompositeAlgorithmSignatures2025".to_vec();
+        m.extend_from_slice(b"COMPSIG-MLDSA65-ECDSA-P384-SHA512");
+        m.push(0);
+        m.extend_from_slice(
+            aws_lc_rs::digest::digest(&aws_lc_rs::digest::SHA512, message).as_ref(),
+        );
+        assert_eq!(m, message_representative(algorithm, message));
+
+        let ml_dsa_key = KeyPair::generate_for(&rcgen::PKCS_ML_DSA_65).unwrap();
+        let ml_dsa_sig = composite::load_ml_dsa_key(
+            &PrivateKeyDer::try_from(ml_dsa_key.serialize_der()).unwrap(),
+        )
+        .unwrap()
+        .sign(&m, algorithm.label)
+        .unwrap();
+
+        let ecdsa_key = aws_lc_rs::signature::EcdsaKeyPair::generate(
+            &aws_lc_rs::signature::ECDSA_P384_SHA384_ASN1_SIGNING,
+        )
+        .unwrap();
+        let ecdsa_sig = ecdsa_key
+            .sign(&aws_lc_rs::rand::SystemRandom::new(), &m)
+            .unwrap();
+
+        let public_key = [
+            ml_dsa_key.public_key_raw(),
+            aws_lc_rs::signature::KeyPair::public_key(&ecdsa_key).as_ref(),
+        ]
+        .concat();
+        let signature = [ml_dsa_sig.as_slice(), ecdsa_sig.as_ref()].concat();
+        assert!(
+            algorithm
+                .verify_signature(&public_key, message, &signature)
+                .is_ok()
+        );
+    }
+
+    /// Compute `M'` for `algorithm`, with an empty application context.
+    fn message_representative(algorithm: &CompositeAlgorithm, message: &[u8]) -> Vec<u8> {
+        [
+            b"CompositeAlgorithmSignatures2025",
+            algorithm.label,
+            &[0],
+            algorithm.prehash.hash(message).as_ref(),
+        ]
+        .concat()
+    }
 }
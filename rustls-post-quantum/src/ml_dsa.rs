@@ -0,0 +1,225 @@
+//! ML-DSA signing and verification with a context string.
+//!
+//! aws-lc-rs only signs and verifies ML-DSA with an empty context string, so this
+//! uses AWS-LC directly.  Composite signature algorithms need this, as the ML-DSA
+//! component is signed with the composite algorithm's label as its context string.
+
+use core::fmt::{self, Debug, Formatter};
+use core::ptr::{NonNull, null, null_mut};
+use std::os::raw::c_int;
+
+use aws_lc_rs::encoding::AsRawBytes;
+use aws_lc_rs::signature::{KeyPair, PqdsaKeyPair};
+use aws_lc_sys::{
+    EVP_DigestSign, EVP_DigestSignInit, EVP_DigestVerify, EVP_DigestVerifyInit, EVP_MD_CTX,
+    EVP_MD_CTX_free, EVP_MD_CTX_new, EVP_PKEY, EVP_PKEY_CTX, EVP_PKEY_CTX_set_signature_context,
+    EVP_PKEY_free, EVP_PKEY_pqdsa_new_raw_private_key, EVP_PKEY_pqdsa_new_raw_public_key,
+    NID_MLDSA44, NID_MLDSA65, NID_MLDSA87,
+};
+use rustls::Error;
+use rustls::crypto::composite::{ContextSigningKey, ContextVerificationAlgorithm};
+use rustls::crypto::public_key_to_spki;
+use rustls::pki_types::{InvalidSignature, SubjectPublicKeyInfoDer};
+
+use crate::PqdsaKeyKind;
+
+/// Verifies ML-DSA signatures made with a context string.
+pub(crate) struct ContextVerifier(pub(crate) PqdsaKeyKind);
+
+impl ContextVerificationAlgorithm for ContextVerifier {
+    fn verify_signature(
+        &self,
+        public_key: &[u8],
+        message: &[u8],
+        context: &[u8],
+        signature: &[u8],
+    ) -> Result<(), InvalidSignature> {
+        // SAFETY: `public_key` is valid for reads of its length during the call, and
+        // AWS-LC copies it.  The result is either null or a new `EVP_PKEY` that `Pkey`
+        // takes ownership of.
+        let key = Pkey::new(unsafe {
+            EVP_PKEY_pqdsa_new_raw_public_key(nid(self.0), public_key.as_ptr(), public_key.len())
+        })
+        .ok_or(InvalidSignature)?;
+
+        match key.verify(message, context, signature) {
+            true => Ok(()),
+            false => Err(InvalidSignature),
+        }
+    }
+}
+
+impl Debug for ContextVerifier {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ContextVerifier")
+            .field(&self.0.scheme())
+            .finish()
+    }
+}
+
+/// An ML-DSA signing key which signs with a context string.
+pub(crate) struct ContextSigner {
+    kind: PqdsaKeyKind,
+    key: Pkey,
+    public_key: SubjectPublicKeyInfoDer<'static>,
+}
+
+impl ContextSigner {
+    pub(crate) fn new(kind: PqdsaKeyKind, key_pair: &PqdsaKeyPair) -> Result<Self, Error> {
+        let private_key = key_pair
+            .private_key()
+            .as_raw_bytes()
+            .map_err(|_| Error::General("cannot encode ML-DSA private key".into()))?;
+        let private_key = private_key.as_ref();
+        // SAFETY: as for `EVP_PKEY_pqdsa_new_raw_public_key()` in `ContextVerifier`.
+        let key = Pkey::new(unsafe {
+            EVP_PKEY_pqdsa_new_raw_private_key(nid(kind), private_key.as_ptr(), private_key.len())
+        })
+        .ok_or_else(|| Error::General("cannot load ML-DSA private key".into()))?;
+
+        Ok(Self {
+            kind,
+            key,
+            public_key: public_key_to_spki(&kind.alg_id(), key_pair.public_key()),
+        })
+    }
+}
+
+impl ContextSigningKey for ContextSigner {
+    fn sign(&self, message: &[u8], context: &[u8]) -> Result<Vec<u8>, Error> {
+        let expected_sig_len = self.kind.to_alg().signature_len();
+        let sig = self
+            .key
+            .sign(message, context, expected_sig_len)
+            .ok_or_else(|| Error::General("signing failed".into()))?;
+
+        if sig.len() != expected_sig_len {
+            return Err(Error::General("unexpected signature length".into()));
+        }
+
+        Ok(sig)
+    }
+
+    fn public_key(&self) -> Option<SubjectPublicKeyInfoDer<'_>> {
+        Some(self.public_key.clone())
+    }
+}
+
+impl Debug for ContextSigner {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ContextSigner")
+            .field("scheme", &self.kind.scheme())
+            .finish_non_exhaustive()
+    }
+}
+
+/// An owned AWS-LC `EVP_PKEY`.
+struct Pkey(NonNull<EVP_PKEY>);
+
+impl Pkey {
+    fn new(pkey: *mut EVP_PKEY) -> Option<Self> {
+        NonNull::new(pkey).map(Self)
+    }
+
+    fn sign(&self, message: &[u8], context: &[u8], max_len: usize) -> Option<Vec<u8>> {
+        let md_ctx = MdCtx::new()?;
+        let mut pkey_ctx: *mut EVP_PKEY_CTX = null_mut();
+        let mut sig = vec![0u8; max_len];
+        let mut sig_len = sig.len();
+
+        // SAFETY: all pointers are valid for the duration of the calls, and `pkey_ctx`
+        // is owned by `md_ctx`.
+        let ok = unsafe {
+            EVP_DigestSignInit(
+                md_ctx.0.as_ptr(),
+                &mut pkey_ctx,
+                null(),
+                null_mut(),
+                self.0.as_ptr(),
+            ) == 1
+                && EVP_PKEY_CTX_set_signature_context(pkey_ctx, context.as_ptr(), context.len())
+                    == 1
+                && EVP_DigestSign(
+                    md_ctx.0.as_ptr(),
+                    sig.as_mut_ptr(),
+                    &mut sig_len,
+                    message.as_ptr(),
+                    message.len(),
+                ) == 1
+        };
+
+        match ok {
+            true => {
+                sig.truncate(sig_len);
+                Some(sig)
+            }
+            false => None,
+        }
+    }
+
+    fn verify(&self, message: &[u8], context: &[u8], signature: &[u8]) -> bool {
+        let Some(md_ctx) = MdCtx::new() else {
+            return false;
+        };
+        let mut pkey_ctx: *mut EVP_PKEY_CTX = null_mut();
+
+        // SAFETY: as for `sign()`.
+        unsafe {
+            EVP_DigestVerifyInit(
+                md_ctx.0.as_ptr(),
+                &mut pkey_ctx,
+                null(),
+                null_mut(),
+                self.0.as_ptr(),
+            ) == 1
+                && EVP_PKEY_CTX_set_signature_context(pkey_ctx, context.as_ptr(), context.len())
+                    == 1
+                && EVP_DigestVerify(
+                    md_ctx.0.as_ptr(),
+                    signature.as_ptr(),
+                    signature.len(),
+                    message.as_ptr(),
+                    message.len(),
+                ) == 1
+        }
+    }
+}
+
+impl Drop for Pkey {
+    fn drop(&mut self) {
+        // SAFETY: `self.0` is a valid `EVP_PKEY` owned by `self`, and is not used again.
+        unsafe { EVP_PKEY_free(self.0.as_ptr()) };
+    }
+}
+
+// SAFETY: the key is never mutated after construction, and AWS-LC allows an `EVP_PKEY`
+// to be used for signing and verification from several threads at once.
+unsafe impl Send for Pkey {}
+unsafe impl Sync for Pkey {}
+
+/// An owned AWS-LC `EVP_MD_CTX`.
+struct MdCtx(NonNull<EVP_MD_CTX>);
+
+impl MdCtx {
+    fn new() -> Option<Self> {
+        // SAFETY: `EVP_MD_CTX_new()` has no preconditions.  It returns either null
+        // or a new `EVP_MD_CTX` that `MdCtx` takes ownership of.
+        NonNull::new(unsafe { EVP_MD_CTX_new() }).map(Self)
+    }
+}
+
+impl Drop for MdCtx {
+    fn drop(&mut self) {
+        // SAFETY: `self.0` is a valid `EVP_MD_CTX` owned by `self`, and is not used again.
+        // This also frees any `EVP_PKEY_CTX` it owns.
+        unsafe { EVP_MD_CTX_free(self.0.as_ptr()) };
+    }
+}
+
+fn nid(kind: PqdsaKeyKind) -> c_int {
+    match kind {
+        PqdsaKeyKind::MlDsa44 => NID_MLDSA44,
+        PqdsaKeyKind::MlDsa65 => NID_MLDSA65,
+        PqdsaKeyKind::MlDsa87 => NID_MLDSA87,
+    }
+}
@@ -0,0 +1,13 @@
+//! Runs the `rustls_test::conformance` matrix for each provider in this workspace.
+
+mod aws_lc_rs {
+    rustls_test::provider_conformance_tests!(rustls_aws_lc_rs::DEFAULT_PROVIDER);
+}
+
+mod ring {
+    rustls_test::provider_conformance_tests!(rustls_ring::DEFAULT_PROVIDER);
+}
+
+mod rustcrypto {
+    rustls_test::provider_conformance_tests!(rustls_rustcrypto::DEFAULT_PROVIDER);
+}
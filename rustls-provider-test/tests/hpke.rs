@@ -77,11 +77,23 @@ impl TestVector {
             return None;
         }
 
-        match Self::lookup_suite(self.suite(), rustls_aws_lc_rs::hpke::ALL_SUPPORTED_SUITES) {
-            // aws-lc-rs supported the suite, test against itself
-            Some(aws_suite) => Some(vec![(aws_suite, aws_suite)]),
+        match (
+            Self::lookup_suite(self.suite(), rustls_aws_lc_rs::hpke::ALL_SUPPORTED_SUITES),
+            Self::lookup_suite(self.suite(), rustls_rustcrypto::hpke::ALL_SUPPORTED_SUITES),
+        ) {
+            // Both providers supported the suite. Test against self, and each other.
+            (Some(aws_suite), Some(rustcrypto_suite)) => Some(vec![
+                (aws_suite, aws_suite),
+                (rustcrypto_suite, rustcrypto_suite),
+                (aws_suite, rustcrypto_suite),
+                (rustcrypto_suite, aws_suite),
+            ]),
+            // aws-lc-rs supported the suite, not RustCrypto. Test against self.
+            (Some(aws_suite), None) => Some(vec![(aws_suite, aws_suite)]),
+            // RustCrypto supported the suite, not aws-lc-rs. Test against self.
+            (None, Some(rustcrypto_suite)) => Some(vec![(rustcrypto_suite, rustcrypto_suite)]),
             // Neither provider supported the suite - nothing to do.
-            None => None,
+            (None, None) => None,
         }
     }
 
@@ -214,7 +214,17 @@ impl MessageEncrypter for Tls13MessageEncrypter {
         seq: u64,
         out: &'a mut [u8],
     ) -> Result<EncodedMessage<&'a [u8]>, Error> {
-        let total_len = self.encrypted_payload_len(msg.payload.len());
+        self.encrypt_padded(msg, 0, seq, out)
+    }
+
+    fn encrypt_padded<'a>(
+        &mut self,
+        msg: EncodedMessage<OutboundPlain<'_>>,
+        padding: usize,
+        seq: u64,
+        out: &'a mut [u8],
+    ) -> Result<EncodedMessage<&'a [u8]>, Error> {
+        let total_len = self.encrypted_payload_len(msg.payload.len() + padding);
         let mut payload = EncryptBuffer::new(out, total_len)?;
 
         let typ = ContentType::ApplicationData;
@@ -222,6 +232,7 @@ impl MessageEncrypter for Tls13MessageEncrypter {
         let aad = aead::Aad::from(make_tls13_aad(typ, msg.version.encode(), total_len));
         payload.extend_from_chunks(&msg.payload);
         payload.extend_from_slice(&msg.typ.to_array());
+        payload.extend_zeroed(padding);
 
         match self
             .enc_key
@@ -241,6 +252,10 @@ impl MessageEncrypter for Tls13MessageEncrypter {
     fn encrypted_payload_len(&self, payload_len: usize) -> usize {
         payload_len + 1 + self.enc_key.algorithm().tag_len()
     }
+
+    fn supports_padding(&self) -> bool {
+        true
+    }
 }
 
 impl MessageDecrypter for Tls13MessageDecrypter {
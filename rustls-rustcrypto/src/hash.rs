@@ -0,0 +1,53 @@
+use alloc::boxed::Box;
+use core::marker::PhantomData;
+
+use pki_types::FipsStatus;
+use rustls::crypto::{self, HashAlgorithm};
+use sha2::Digest;
+
+pub(crate) static SHA256: Hash<sha2::Sha256> = Hash(PhantomData, HashAlgorithm::SHA256);
+pub(crate) static SHA384: Hash<sha2::Sha384> = Hash(PhantomData, HashAlgorithm::SHA384);
+
+pub(crate) struct Hash<D>(PhantomData<fn() -> D>, HashAlgorithm);
+
+impl<D: Digest + Clone + Send + Sync + 'static> crypto::hash::Hash for Hash<D> {
+    fn start(&self) -> Box<dyn crypto::hash::Context> {
+        Box::new(Context(D::new()))
+    }
+
+    fn hash(&self, bytes: &[u8]) -> crypto::hash::Output {
+        crypto::hash::Output::new(&D::digest(bytes))
+    }
+
+    fn output_len(&self) -> usize {
+        <D as Digest>::output_size()
+    }
+
+    fn algorithm(&self) -> HashAlgorithm {
+        self.1
+    }
+
+    fn fips(&self) -> FipsStatus {
+        super::fips()
+    }
+}
+
+struct Context<D>(D);
+
+impl<D: Digest + Clone + Send + Sync + 'static> crypto::hash::Context for Context<D> {
+    fn fork_finish(&self) -> crypto::hash::Output {
+        crypto::hash::Output::new(&self.0.clone().finalize())
+    }
+
+    fn fork(&self) -> Box<dyn crypto::hash::Context> {
+        Box::new(Self(self.0.clone()))
+    }
+
+    fn finish(self: Box<Self>) -> crypto::hash::Output {
+        crypto::hash::Output::new(&self.0.finalize())
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+}
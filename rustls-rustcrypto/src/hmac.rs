@@ -0,0 +1,57 @@
+use alloc::boxed::Box;
+use core::marker::PhantomData;
+
+use hmac::digest::KeyInit;
+use hmac::{Mac, SimpleHmac};
+use pki_types::FipsStatus;
+use rustls::crypto;
+use sha2::Digest;
+use sha2::digest::core_api::BlockSizeUser;
+
+pub(crate) static HMAC_SHA256: Hmac<sha2::Sha256> = Hmac(PhantomData);
+pub(crate) static HMAC_SHA384: Hmac<sha2::Sha384> = Hmac(PhantomData);
+#[allow(dead_code)] // Only used for TLS 1.2 prf test.
+pub(crate) static HMAC_SHA512: Hmac<sha2::Sha512> = Hmac(PhantomData);
+
+pub(crate) struct Hmac<D>(PhantomData<fn() -> D>);
+
+impl<D> crypto::hmac::Hmac for Hmac<D>
+where
+    D: Digest + BlockSizeUser + Clone + Send + Sync + 'static,
+{
+    fn with_key(&self, key: &[u8]) -> Box<dyn crypto::hmac::Key> {
+        // HMAC accepts keys of any length, so this cannot fail.
+        Box::new(Key(
+            <SimpleHmac<D> as KeyInit>::new_from_slice(key).expect("HMAC accepts any key length")
+        ))
+    }
+
+    fn hash_output_len(&self) -> usize {
+        <D as Digest>::output_size()
+    }
+
+    fn fips(&self) -> FipsStatus {
+        super::fips()
+    }
+}
+
+struct Key<D: Digest + BlockSizeUser>(SimpleHmac<D>);
+
+impl<D> crypto::hmac::Key for Key<D>
+where
+    D: Digest + BlockSizeUser + Clone + Send + Sync + 'static,
+{
+    fn sign_concat(&self, first: &[u8], middle: &[&[u8]], last: &[u8]) -> crypto::hmac::Tag {
+        let mut ctx = self.0.clone();
+        ctx.update(first);
+        for d in middle {
+            ctx.update(d);
+        }
+        ctx.update(last);
+        crypto::hmac::Tag::new(&ctx.finalize().into_bytes())
+    }
+
+    fn tag_len(&self) -> usize {
+        <D as Digest>::output_size()
+    }
+}
@@ -0,0 +1,1000 @@
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::{self, Debug, Formatter};
+use core::marker::PhantomData;
+
+use aes_gcm::{Aes128Gcm, Aes256Gcm};
+use chacha20poly1305::ChaCha20Poly1305;
+use p256::elliptic_curve::sec1::{FromEncodedPoint, ModulusSize, ToEncodedPoint};
+use p256::elliptic_curve::{AffinePoint, CurveArithmetic, FieldBytesSize, PublicKey, SecretKey};
+use pki_types::FipsStatus;
+use rand_core::OsRng;
+use rustls::crypto::hpke::{
+    EncapsulatedSecret, Hpke, HpkeAead, HpkeKdf, HpkeKem, HpkeOpener, HpkePrivateKey,
+    HpkePublicKey, HpkeSealer, HpkeSuite, HpkeSymmetricCipherSuite,
+};
+use rustls::crypto::tls13::{HkdfExpander, HkdfPrkExtract, HkdfUsingHmac};
+use rustls::error::{Error, OtherError};
+use zeroize::Zeroizing;
+
+use crate::hmac::{HMAC_SHA256, HMAC_SHA384};
+
+/// Default [RFC 9180] Hybrid Public Key Encryption (HPKE) suites supported by RustCrypto cryptography.
+///
+/// [RFC 9180]: https://www.rfc-editor.org/rfc/rfc9180.html
+pub static ALL_SUPPORTED_SUITES: &[&dyn Hpke] = &[
+    DH_KEM_P256_HKDF_SHA256_AES_128,
+    DH_KEM_P256_HKDF_SHA256_AES_256,
+    DH_KEM_P256_HKDF_SHA256_CHACHA20_POLY1305,
+    DH_KEM_P384_HKDF_SHA384_AES_128,
+    DH_KEM_P384_HKDF_SHA384_AES_256,
+    DH_KEM_P384_HKDF_SHA384_CHACHA20_POLY1305,
+    DH_KEM_X25519_HKDF_SHA256_AES_128,
+    DH_KEM_X25519_HKDF_SHA256_AES_256,
+    DH_KEM_X25519_HKDF_SHA256_CHACHA20_POLY1305,
+];
+
+/// HPKE suite using ECDH P-256 for agreement, HKDF SHA-256 for key derivation, and AEAD AES-128-GCM
+/// for symmetric encryption.
+pub static DH_KEM_P256_HKDF_SHA256_AES_128: &HpkeRustCrypto = &HpkeRustCrypto {
+    suite: HpkeSuite {
+        kem: HpkeKem::DHKEM_P256_HKDF_SHA256,
+        sym: HpkeSymmetricCipherSuite {
+            kdf_id: HpkeKdf::HKDF_SHA256,
+            aead_id: HpkeAead::AES_128_GCM,
+        },
+    },
+    dh_kem: DH_KEM_P256_HKDF_SHA256,
+    hkdf: HKDF_HMAC_SHA256,
+    aead: AES_128_GCM,
+};
+
+/// HPKE suite using ECDH P-256 for agreement, HKDF SHA-256 for key derivation and AEAD AES-256-GCM
+/// for symmetric encryption.
+pub static DH_KEM_P256_HKDF_SHA256_AES_256: &HpkeRustCrypto = &HpkeRustCrypto {
+    suite: HpkeSuite {
+        kem: HpkeKem::DHKEM_P256_HKDF_SHA256,
+        sym: HpkeSymmetricCipherSuite {
+            kdf_id: HpkeKdf::HKDF_SHA256,
+            aead_id: HpkeAead::AES_256_GCM,
+        },
+    },
+    dh_kem: DH_KEM_P256_HKDF_SHA256,
+    hkdf: HKDF_HMAC_SHA256,
+    aead: AES_256_GCM,
+};
+
+/// HPKE suite using ECDH P-256 for agreement, HKDF SHA-256 for key derivation, and AEAD
+/// CHACHA20-POLY-1305 for symmetric encryption.
+pub static DH_KEM_P256_HKDF_SHA256_CHACHA20_POLY1305: &HpkeRustCrypto = &HpkeRustCrypto {
+    suite: HpkeSuite {
+        kem: HpkeKem::DHKEM_P256_HKDF_SHA256,
+        sym: HpkeSymmetricCipherSuite {
+            kdf_id: HpkeKdf::HKDF_SHA256,
+            aead_id: HpkeAead::CHACHA20_POLY_1305,
+        },
+    },
+    dh_kem: DH_KEM_P256_HKDF_SHA256,
+    hkdf: HKDF_HMAC_SHA256,
+    aead: CHACHA20_POLY1305,
+};
+
+/// HPKE suite using ECDH P-384 for agreement, HKDF SHA-384 for key derivation, and AEAD AES-128-GCM
+/// for symmetric encryption.
+pub static DH_KEM_P384_HKDF_SHA384_AES_128: &HpkeRustCrypto = &HpkeRustCrypto {
+    suite: HpkeSuite {
+        kem: HpkeKem::DHKEM_P384_HKDF_SHA384,
+        sym: HpkeSymmetricCipherSuite {
+            kdf_id: HpkeKdf::HKDF_SHA384,
+            aead_id: HpkeAead::AES_128_GCM,
+        },
+    },
+    dh_kem: DH_KEM_P384_HKDF_SHA384,
+    hkdf: HKDF_HMAC_SHA384,
+    aead: AES_128_GCM,
+};
+
+/// HPKE suite using ECDH P-384 for agreement, HKDF SHA-384 for key derivation, and AEAD AES-256-GCM
+/// for symmetric encryption.
+pub static DH_KEM_P384_HKDF_SHA384_AES_256: &HpkeRustCrypto = &HpkeRustCrypto {
+    suite: HpkeSuite {
+        kem: HpkeKem::DHKEM_P384_HKDF_SHA384,
+        sym: HpkeSymmetricCipherSuite {
+            kdf_id: HpkeKdf::HKDF_SHA384,
+            aead_id: HpkeAead::AES_256_GCM,
+        },
+    },
+    dh_kem: DH_KEM_P384_HKDF_SHA384,
+    hkdf: HKDF_HMAC_SHA384,
+    aead: AES_256_GCM,
+};
+
+/// HPKE suite using ECDH P-384 for agreement, HKDF SHA-384 for key derivation, and AEAD
+/// CHACHA20-POLY-1305 for symmetric encryption.
+pub static DH_KEM_P384_HKDF_SHA384_CHACHA20_POLY1305: &HpkeRustCrypto = &HpkeRustCrypto {
+    suite: HpkeSuite {
+        kem: HpkeKem::DHKEM_P384_HKDF_SHA384,
+        sym: HpkeSymmetricCipherSuite {
+            kdf_id: HpkeKdf::HKDF_SHA384,
+            aead_id: HpkeAead::CHACHA20_POLY_1305,
+        },
+    },
+    dh_kem: DH_KEM_P384_HKDF_SHA384,
+    hkdf: HKDF_HMAC_SHA384,
+    aead: CHACHA20_POLY1305,
+};
+
+/// HPKE suite using ECDH X25519 for agreement, HKDF SHA-256 for key derivation, and AEAD AES-128-GCM
+/// for symmetric encryption.
+pub static DH_KEM_X25519_HKDF_SHA256_AES_128: &HpkeRustCrypto = &HpkeRustCrypto {
+    suite: HpkeSuite {
+        kem: HpkeKem::DHKEM_X25519_HKDF_SHA256,
+        sym: HpkeSymmetricCipherSuite {
+            kdf_id: HpkeKdf::HKDF_SHA256,
+            aead_id: HpkeAead::AES_128_GCM,
+        },
+    },
+    dh_kem: DH_KEM_X25519_HKDF_SHA256,
+    hkdf: HKDF_HMAC_SHA256,
+    aead: AES_128_GCM,
+};
+
+/// HPKE suite using ECDH X25519 for agreement, HKDF SHA-256 for key derivation, and AEAD AES-256-GCM
+/// for symmetric encryption.
+pub static DH_KEM_X25519_HKDF_SHA256_AES_256: &HpkeRustCrypto = &HpkeRustCrypto {
+    suite: HpkeSuite {
+        kem: HpkeKem::DHKEM_X25519_HKDF_SHA256,
+        sym: HpkeSymmetricCipherSuite {
+            kdf_id: HpkeKdf::HKDF_SHA256,
+            aead_id: HpkeAead::AES_256_GCM,
+        },
+    },
+    dh_kem: DH_KEM_X25519_HKDF_SHA256,
+    hkdf: HKDF_HMAC_SHA256,
+    aead: AES_256_GCM,
+};
+
+/// HPKE suite using ECDH X25519 for agreement, HKDF SHA-256 for key derivation, and AEAD
+/// CHACHA20-POLY-1305 for symmetric encryption.
+pub static DH_KEM_X25519_HKDF_SHA256_CHACHA20_POLY1305: &HpkeRustCrypto = &HpkeRustCrypto {
+    suite: HpkeSuite {
+        kem: HpkeKem::DHKEM_X25519_HKDF_SHA256,
+        sym: HpkeSymmetricCipherSuite {
+            kdf_id: HpkeKdf::HKDF_SHA256,
+            aead_id: HpkeAead::CHACHA20_POLY_1305,
+        },
+    },
+    dh_kem: DH_KEM_X25519_HKDF_SHA256,
+    hkdf: HKDF_HMAC_SHA256,
+    aead: CHACHA20_POLY1305,
+};
+
+/// `HpkeRustCrypto` holds the concrete instantiations of the algorithms specified by the [HpkeSuite].
+pub struct HpkeRustCrypto {
+    suite: HpkeSuite,
+    dh_kem: &'static DhKem,
+    hkdf: &'static dyn HkdfPrkExtract,
+    aead: &'static dyn HpkeAeadAlgorithm,
+}
+
+impl HpkeRustCrypto {
+    /// See [RFC 9180 §5.1 "Creating the Encryption Context"][0].
+    ///
+    /// [0]: https://www.rfc-editor.org/rfc/rfc9180.html#section-5.1
+    fn key_schedule(&self, shared_secret: &[u8], info: &[u8]) -> KeySchedule {
+        // Note: we use an empty IKM for the `psk_id_hash` and `secret` labelled extractions because
+        // there is no PSK ID in base mode HPKE.
+
+        let suite_id = LabeledSuiteId::Hpke(self.suite);
+        let psk_id_hash = labeled_extract_for_prk(self.hkdf, suite_id, None, Label::PskIdHash, &[]);
+        let info_hash = labeled_extract_for_prk(self.hkdf, suite_id, None, Label::InfoHash, info);
+        let key_schedule_context = [
+            &[0][..], // base mode (0x00)
+            &psk_id_hash,
+            &info_hash,
+        ]
+        .concat();
+
+        let secret = labeled_extract_for_expand(
+            self.hkdf,
+            suite_id,
+            Some(shared_secret),
+            Label::Secret,
+            &[],
+        );
+
+        let mut key = Zeroizing::new(vec![0u8; self.aead.key_len()]);
+        labeled_expand(
+            suite_id,
+            &*secret,
+            Label::Key,
+            &key_schedule_context,
+            &mut key,
+        );
+
+        let mut base_nonce = [0u8; NONCE_LEN];
+        labeled_expand(
+            suite_id,
+            &*secret,
+            Label::BaseNonce,
+            &key_schedule_context,
+            &mut base_nonce,
+        );
+
+        KeySchedule {
+            aead: self.aead,
+            key,
+            base_nonce,
+            seq_num: 0,
+        }
+    }
+}
+
+impl Hpke for HpkeRustCrypto {
+    fn seal(
+        &self,
+        info: &[u8],
+        aad: &[u8],
+        plaintext: &[u8],
+        pub_key: &HpkePublicKey,
+    ) -> Result<(EncapsulatedSecret, Vec<u8>), Error> {
+        let (encap, mut sealer) = self.setup_sealer(info, pub_key)?;
+        Ok((encap, sealer.seal(aad, plaintext)?))
+    }
+
+    fn setup_sealer(
+        &self,
+        info: &[u8],
+        pub_key: &HpkePublicKey,
+    ) -> Result<(EncapsulatedSecret, Box<dyn HpkeSealer + 'static>), Error> {
+        let (encap, sealer) = Sealer::new(self, info, pub_key)?;
+        Ok((encap, Box::new(sealer)))
+    }
+
+    fn open(
+        &self,
+        enc: &EncapsulatedSecret,
+        info: &[u8],
+        aad: &[u8],
+        ciphertext: &[u8],
+        secret_key: &HpkePrivateKey,
+    ) -> Result<Vec<u8>, Error> {
+        self.setup_opener(enc, info, secret_key)?
+            .open(aad, ciphertext)
+    }
+
+    fn setup_opener(
+        &self,
+        enc: &EncapsulatedSecret,
+        info: &[u8],
+        secret_key: &HpkePrivateKey,
+    ) -> Result<Box<dyn HpkeOpener + 'static>, Error> {
+        Ok(Box::new(Opener::new(self, enc, info, secret_key)?))
+    }
+
+    fn fips(&self) -> FipsStatus {
+        // None of the RustCrypto implementations have been FIPS validated.
+        FipsStatus::Unvalidated
+    }
+
+    fn generate_key_pair(&self) -> Result<(HpkePublicKey, HpkePrivateKey), Error> {
+        let (public_key, private_key) = self.dh_kem.dh.generate_key_pair();
+        Ok((HpkePublicKey(public_key), HpkePrivateKey::from(private_key)))
+    }
+
+    fn suite(&self) -> HpkeSuite {
+        self.suite
+    }
+}
+
+impl Debug for HpkeRustCrypto {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.suite.fmt(f)
+    }
+}
+
+/// Adapts a [KeySchedule] for the role of a [HpkeSealer].
+struct Sealer {
+    key_schedule: KeySchedule,
+}
+
+impl Sealer {
+    /// See [RFC 9180 §5.1.1 "Encryption to a Public Key"][0].
+    ///
+    /// [0]: https://www.rfc-editor.org/rfc/rfc9180.html#section-5.1.1
+    fn new(
+        suite: &HpkeRustCrypto,
+        info: &[u8],
+        pub_key: &HpkePublicKey,
+    ) -> Result<(EncapsulatedSecret, Self), Error> {
+        // def SetupBaseS(pkR, info):
+        //   shared_secret, enc = Encap(pkR)
+        //   return enc, KeyScheduleS(mode_base, shared_secret, info,
+        //                            default_psk, default_psk_id)
+
+        let (shared_secret, enc) = suite.dh_kem.encap(pub_key)?;
+        let key_schedule = suite.key_schedule(&shared_secret, info);
+        Ok((enc, Self { key_schedule }))
+    }
+
+    /// A **test only** constructor that uses a pre-specified ephemeral agreement private key
+    /// instead of one that is randomly generated.
+    #[cfg(test)]
+    fn test_only_new(
+        suite: &HpkeRustCrypto,
+        info: &[u8],
+        pub_key: &HpkePublicKey,
+        sk_e: &[u8],
+    ) -> Result<(EncapsulatedSecret, Self), Error> {
+        let (shared_secret, enc) = suite.dh_kem.encap_impl(pub_key, sk_e)?;
+        let key_schedule = suite.key_schedule(&shared_secret, info);
+        Ok((enc, Self { key_schedule }))
+    }
+}
+
+impl HpkeSealer for Sealer {
+    fn seal(&mut self, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, Error> {
+        // def ContextS.Seal(aad, pt):
+        //   ct = Seal(self.key, self.ComputeNonce(self.seq), aad, pt)
+        //   self.IncrementSeq()
+        //   return ct
+
+        let nonce = self.key_schedule.compute_nonce();
+        self.key_schedule.increment_seq_num()?;
+
+        let mut in_out_buffer = Vec::from(plaintext);
+        self.key_schedule
+            .aead
+            .seal(&self.key_schedule.key, &nonce, aad, &mut in_out_buffer)?;
+        Ok(in_out_buffer)
+    }
+}
+
+impl Debug for Sealer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sealer")
+            .finish_non_exhaustive()
+    }
+}
+
+/// Adapts a [KeySchedule] for the role of a [HpkeOpener].
+struct Opener {
+    key_schedule: KeySchedule,
+}
+
+impl Opener {
+    /// See [RFC 9180 §5.1.1 "Encryption to a Public Key"][0].
+    ///
+    /// [0]: https://www.rfc-editor.org/rfc/rfc9180.html#section-5.1.1
+    fn new(
+        suite: &HpkeRustCrypto,
+        enc: &EncapsulatedSecret,
+        info: &[u8],
+        secret_key: &HpkePrivateKey,
+    ) -> Result<Self, Error> {
+        // def SetupBaseR(enc, skR, info):
+        //   shared_secret = Decap(enc, skR)
+        //   return KeyScheduleR(mode_base, shared_secret, info,
+        //                       default_psk, default_psk_id)
+        let shared_secret = suite.dh_kem.decap(enc, secret_key)?;
+        Ok(Self {
+            key_schedule: suite.key_schedule(&shared_secret, info),
+        })
+    }
+}
+
+impl HpkeOpener for Opener {
+    fn open(&mut self, aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, Error> {
+        // def ContextR.Open(aad, ct):
+        //   pt = Open(self.key, self.ComputeNonce(self.seq), aad, ct)
+        //   if pt == OpenError:
+        //     raise OpenError
+        //   self.IncrementSeq()
+        //   return pt
+
+        let nonce = self.key_schedule.compute_nonce();
+        let mut in_out_buffer = Vec::from(ciphertext);
+        self.key_schedule
+            .aead
+            .open(&self.key_schedule.key, &nonce, aad, &mut in_out_buffer)?;
+        self.key_schedule.increment_seq_num()?;
+        Ok(in_out_buffer)
+    }
+}
+
+impl Debug for Opener {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Opener")
+            .finish_non_exhaustive()
+    }
+}
+
+/// A Diffie-Hellman (DH) based Key Encapsulation Mechanism (KEM).
+///
+/// See [RFC 9180 §4.1 "DH-Based KEM (DHKEM)"][0].
+///
+/// [0]: https://www.rfc-editor.org/rfc/rfc9180.html#section-4.1
+struct DhKem {
+    id: HpkeKem,
+    dh: &'static dyn DhGroup,
+    hkdf: &'static dyn HkdfPrkExtract,
+}
+
+impl DhKem {
+    /// See [RFC 9180 §4.1 "DH-Based KEM (DHKEM)"][0].
+    ///
+    /// [0]: https://www.rfc-editor.org/rfc/rfc9180.html#section-4.1
+    fn encap(
+        &self,
+        recipient: &HpkePublicKey,
+    ) -> Result<(Zeroizing<Vec<u8>>, EncapsulatedSecret), Error> {
+        // def Encap(pkR):
+        //   skE, pkE = GenerateKeyPair()
+
+        let (_, sk_e) = self.dh.generate_key_pair();
+        self.encap_impl(recipient, &sk_e)
+    }
+
+    fn encap_impl(
+        &self,
+        recipient: &HpkePublicKey,
+        sk_e: &[u8],
+    ) -> Result<(Zeroizing<Vec<u8>>, EncapsulatedSecret), Error> {
+        // def Encap(pkR):
+        //   skE, pkE = GenerateKeyPair()
+        //   dh = DH(skE, pkR)
+        //   enc = SerializePublicKey(pkE)
+        //
+        //   pkRm = SerializePublicKey(pkR)
+        //   kem_context = concat(enc, pkRm)
+        //
+        //   shared_secret = ExtractAndExpand(dh, kem_context)
+        //   return shared_secret, enc
+
+        let dh = self.dh.agree(sk_e, &recipient.0)?;
+        let enc = self.dh.public_key(sk_e)?;
+        let kem_context = [&enc[..], &recipient.0].concat();
+
+        Ok((
+            self.extract_and_expand(&dh, &kem_context),
+            EncapsulatedSecret(enc),
+        ))
+    }
+
+    /// See [RFC 9180 §4.1 "DH-Based KEM (DHKEM)"][0].
+    ///
+    /// [0]: https://www.rfc-editor.org/rfc/rfc9180.html#section-4.1
+    fn decap(
+        &self,
+        enc: &EncapsulatedSecret,
+        recipient: &HpkePrivateKey,
+    ) -> Result<Zeroizing<Vec<u8>>, Error> {
+        // def Decap(enc, skR):
+        //   pkE = DeserializePublicKey(enc)
+        //   dh = DH(skR, pkE)
+        //
+        //   pkRm = SerializePublicKey(pk(skR))
+        //   kem_context = concat(enc, pkRm)
+        //
+        //   shared_secret = ExtractAndExpand(dh, kem_context)
+        //   return shared_secret
+
+        let pk_rm = self
+            .dh
+            .public_key(recipient.secret_bytes())?;
+        let dh = self
+            .dh
+            .agree(recipient.secret_bytes(), &enc.0)?;
+        let kem_context = [&enc.0[..], &pk_rm].concat();
+
+        Ok(self.extract_and_expand(&dh, &kem_context))
+    }
+
+    /// See [RFC 9180 §4.1 "DH-Based KEM (DHKEM)"][0].
+    ///
+    /// [0]: https://www.rfc-editor.org/rfc/rfc9180.html#section-4.1
+    fn extract_and_expand(&self, dh: &[u8], kem_context: &[u8]) -> Zeroizing<Vec<u8>> {
+        // def ExtractAndExpand(dh, kem_context):
+        //   eae_prk = LabeledExtract("", "eae_prk", dh)
+        //   shared_secret = LabeledExpand(eae_prk, "shared_secret",
+        //                                 kem_context, Nsecret)
+        //   return shared_secret
+
+        let suite_id = LabeledSuiteId::Kem(self.id);
+        let expander = labeled_extract_for_expand(self.hkdf, suite_id, None, Label::EaePrk, dh);
+        let mut shared_secret = Zeroizing::new(vec![0u8; expander.hash_len()]);
+        labeled_expand(
+            suite_id,
+            &*expander,
+            Label::SharedSecret,
+            kem_context,
+            &mut shared_secret,
+        );
+        shared_secret
+    }
+}
+
+static DH_KEM_P256_HKDF_SHA256: &DhKem = &DhKem {
+    id: HpkeKem::DHKEM_P256_HKDF_SHA256,
+    dh: &NistDh::<p256::NistP256>(PhantomData),
+    hkdf: HKDF_HMAC_SHA256,
+};
+
+static DH_KEM_P384_HKDF_SHA384: &DhKem = &DhKem {
+    id: HpkeKem::DHKEM_P384_HKDF_SHA384,
+    dh: &NistDh::<p384::NistP384>(PhantomData),
+    hkdf: HKDF_HMAC_SHA384,
+};
+
+static DH_KEM_X25519_HKDF_SHA256: &DhKem = &DhKem {
+    id: HpkeKem::DHKEM_X25519_HKDF_SHA256,
+    dh: &X25519Dh,
+    hkdf: HKDF_HMAC_SHA256,
+};
+
+/// The DH primitive underlying a [DhKem].
+///
+/// Private keys are handled in their [RFC 9180 §7.1.2 "SerializePrivateKey"][0] form, and
+/// public keys in their [RFC 9180 §7.1.1 "SerializePublicKey"][1] form.
+///
+/// [0]: https://www.rfc-editor.org/rfc/rfc9180.html#section-7.1.2
+/// [1]: https://www.rfc-editor.org/rfc/rfc9180.html#section-7.1.1
+trait DhGroup: Send + Sync {
+    /// Generate a random key pair, returning the public and private keys.
+    fn generate_key_pair(&self) -> (Vec<u8>, Vec<u8>);
+
+    /// Compute the public key that corresponds to `private_key`.
+    fn public_key(&self, private_key: &[u8]) -> Result<Vec<u8>, Error>;
+
+    /// Compute the DH shared secret between `private_key` and `public_key`.
+    fn agree(&self, private_key: &[u8], public_key: &[u8]) -> Result<Zeroizing<Vec<u8>>, Error>;
+}
+
+struct X25519Dh;
+
+impl X25519Dh {
+    fn private_key(private_key: &[u8]) -> Result<x25519_dalek::StaticSecret, Error> {
+        <[u8; 32]>::try_from(private_key)
+            .map(x25519_dalek::StaticSecret::from)
+            .map_err(|_| hpke_error("invalid X25519 private key"))
+    }
+}
+
+impl DhGroup for X25519Dh {
+    fn generate_key_pair(&self) -> (Vec<u8>, Vec<u8>) {
+        let private_key = x25519_dalek::StaticSecret::random_from_rng(OsRng);
+        let public_key = x25519_dalek::PublicKey::from(&private_key);
+        (
+            public_key.as_bytes().to_vec(),
+            private_key.to_bytes().to_vec(),
+        )
+    }
+
+    fn public_key(&self, private_key: &[u8]) -> Result<Vec<u8>, Error> {
+        let private_key = Self::private_key(private_key)?;
+        Ok(x25519_dalek::PublicKey::from(&private_key)
+            .as_bytes()
+            .to_vec())
+    }
+
+    fn agree(&self, private_key: &[u8], public_key: &[u8]) -> Result<Zeroizing<Vec<u8>>, Error> {
+        let private_key = Self::private_key(private_key)?;
+        let public_key = <[u8; 32]>::try_from(public_key)
+            .map_err(|_| hpke_error("invalid X25519 public key"))?;
+        let dh = private_key.diffie_hellman(&x25519_dalek::PublicKey::from(public_key));
+
+        // RFC 9180 §7.1.4 requires rejecting the all-zero output of a low-order point.
+        match dh.was_contributory() {
+            true => Ok(Zeroizing::new(dh.as_bytes().to_vec())),
+            false => Err(hpke_error("X25519 shared secret was all-zero")),
+        }
+    }
+}
+
+struct NistDh<C>(PhantomData<fn() -> C>);
+
+impl<C> DhGroup for NistDh<C>
+where
+    C: CurveArithmetic,
+    AffinePoint<C>: FromEncodedPoint<C> + ToEncodedPoint<C>,
+    FieldBytesSize<C>: ModulusSize,
+{
+    fn generate_key_pair(&self) -> (Vec<u8>, Vec<u8>) {
+        let private_key = SecretKey::<C>::random(&mut OsRng);
+        let public_key = private_key
+            .public_key()
+            .to_encoded_point(false);
+        (
+            public_key.as_bytes().to_vec(),
+            private_key.to_bytes().to_vec(),
+        )
+    }
+
+    fn public_key(&self, private_key: &[u8]) -> Result<Vec<u8>, Error> {
+        let private_key = SecretKey::<C>::from_slice(private_key)
+            .map_err(|_| hpke_error("invalid ECDH private key"))?;
+        Ok(private_key
+            .public_key()
+            .to_encoded_point(false)
+            .as_bytes()
+            .to_vec())
+    }
+
+    fn agree(&self, private_key: &[u8], public_key: &[u8]) -> Result<Zeroizing<Vec<u8>>, Error> {
+        let private_key = SecretKey::<C>::from_slice(private_key)
+            .map_err(|_| hpke_error("invalid ECDH private key"))?;
+        let public_key = PublicKey::<C>::from_sec1_bytes(public_key)
+            .map_err(|_| hpke_error("invalid ECDH public key"))?;
+        let dh = p256::elliptic_curve::ecdh::diffie_hellman(
+            private_key.to_nonzero_scalar(),
+            public_key.as_affine(),
+        );
+        Ok(Zeroizing::new(dh.raw_secret_bytes().to_vec()))
+    }
+}
+
+/// An AEAD usable for HPKE, with its key supplied at each use.
+trait HpkeAeadAlgorithm: Send + Sync {
+    fn key_len(&self) -> usize;
+
+    fn seal(
+        &self,
+        key: &[u8],
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        in_out: &mut Vec<u8>,
+    ) -> Result<(), Error>;
+
+    fn open(
+        &self,
+        key: &[u8],
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        in_out: &mut Vec<u8>,
+    ) -> Result<(), Error>;
+}
+
+struct Aead<A>(PhantomData<fn() -> A>);
+
+impl<A: crate::tls13::AeadCipher> HpkeAeadAlgorithm for Aead<A> {
+    fn key_len(&self) -> usize {
+        A::key_size()
+    }
+
+    fn seal(
+        &self,
+        key: &[u8],
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        in_out: &mut Vec<u8>,
+    ) -> Result<(), Error> {
+        crate::tls13::aead_key::<A>(key)
+            .encrypt_in_place(&(*nonce).into(), aad, in_out)
+            .map_err(|_| hpke_error("encryption failed"))
+    }
+
+    fn open(
+        &self,
+        key: &[u8],
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        in_out: &mut Vec<u8>,
+    ) -> Result<(), Error> {
+        crate::tls13::aead_key::<A>(key)
+            .decrypt_in_place(&(*nonce).into(), aad, in_out)
+            .map_err(|_| hpke_error("decryption failed"))
+    }
+}
+
+static AES_128_GCM: &dyn HpkeAeadAlgorithm = &Aead::<Aes128Gcm>(PhantomData);
+static AES_256_GCM: &dyn HpkeAeadAlgorithm = &Aead::<Aes256Gcm>(PhantomData);
+static CHACHA20_POLY1305: &dyn HpkeAeadAlgorithm = &Aead::<ChaCha20Poly1305>(PhantomData);
+
+/// KeySchedule holds the derived AEAD key, base nonce, and seq number
+/// common to both a [Sealer] and [Opener].
+struct KeySchedule {
+    aead: &'static dyn HpkeAeadAlgorithm,
+    key: Zeroizing<Vec<u8>>,
+    base_nonce: [u8; NONCE_LEN],
+    seq_num: u32,
+}
+
+impl KeySchedule {
+    /// See [RFC 9180 §5.2 "Encryption and Decryption"][0].
+    ///
+    /// [0]: https://www.rfc-editor.org/rfc/rfc9180.html#section-5.2
+    fn compute_nonce(&self) -> [u8; NONCE_LEN] {
+        // def Context<ROLE>.ComputeNonce(seq):
+        //   seq_bytes = I2OSP(seq, Nn)
+        //   return xor(self.base_nonce, seq_bytes)
+
+        // Each new N-byte nonce is conceptually two parts:
+        //   * N-4 bytes of the base nonce (0s in `nonce` to XOR in as-is).
+        //   * 4 bytes derived from the sequence number XOR the base nonce.
+        let mut nonce = [0; NONCE_LEN];
+        let seq_bytes = self.seq_num.to_be_bytes();
+        nonce[NONCE_LEN - seq_bytes.len()..].copy_from_slice(&seq_bytes);
+
+        for (n, &b) in nonce.iter_mut().zip(&self.base_nonce) {
+            *n ^= b;
+        }
+
+        nonce
+    }
+
+    /// See [RFC 9180 §5.2 "Encryption and Decryption"][0].
+    ///
+    /// [0]: https://www.rfc-editor.org/rfc/rfc9180.html#section-5.2
+    fn increment_seq_num(&mut self) -> Result<(), Error> {
+        // def Context<ROLE>.IncrementSeq():
+        //   if self.seq >= (1 << (8*Nn)) - 1:
+        //     raise MessageLimitReachedError
+        //   self.seq += 1
+
+        // Our sequence number is narrower than the nonce, so it runs out first.
+        self.seq_num = self
+            .seq_num
+            .checked_add(1)
+            .ok_or_else(|| hpke_error("message limit reached"))?;
+        Ok(())
+    }
+}
+
+/// See [RFC 9180 §4 "Cryptographic Dependencies"][0].
+///
+/// [0]: https://www.rfc-editor.org/rfc/rfc9180.html#section-4
+fn labeled_extract_for_expand(
+    hkdf: &'static dyn HkdfPrkExtract,
+    suite_id: LabeledSuiteId,
+    salt: Option<&[u8]>,
+    label: Label,
+    ikm: &[u8],
+) -> Box<dyn HkdfExpander> {
+    // def LabeledExtract(salt, label, ikm):
+    //   labeled_ikm = concat("HPKE-v1", suite_id, label, ikm)
+    //   return Extract(salt, labeled_ikm)
+
+    let labeled_ikm = [&b"HPKE-v1"[..], &suite_id.encoded(), label.as_ref(), ikm].concat();
+    hkdf.extract_from_secret(salt, &labeled_ikm)
+}
+
+/// See [RFC 9180 §4 "Cryptographic Dependencies"][0].
+///
+/// [0]: https://www.rfc-editor.org/rfc/rfc9180.html#section-4
+fn labeled_extract_for_prk(
+    hkdf: &'static dyn HkdfPrkExtract,
+    suite_id: LabeledSuiteId,
+    salt: Option<&[u8]>,
+    label: Label,
+    ikm: &[u8],
+) -> Vec<u8> {
+    // def LabeledExtract(salt, label, ikm):
+    //   labeled_ikm = concat("HPKE-v1", suite_id, label, ikm)
+    //   return Extract(salt, labeled_ikm)
+
+    let labeled_ikm = [&b"HPKE-v1"[..], &suite_id.encoded(), label.as_ref(), ikm].concat();
+    hkdf.extract_prk_from_secret(salt, &labeled_ikm)
+}
+
+/// See [RFC 9180 §4 "Cryptographic Dependencies"][0].
+///
+/// [0]: https://www.rfc-editor.org/rfc/rfc9180.html#section-4
+fn labeled_expand(
+    suite_id: LabeledSuiteId,
+    expander: &dyn HkdfExpander,
+    label: Label,
+    kem_context: &[u8],
+    output: &mut [u8],
+) {
+    // def LabeledExpand(prk, label, info, L):
+    //   labeled_info = concat(I2OSP(L, 2), "HPKE-v1", suite_id,
+    //                         label, info)
+    //   return Expand(prk, labeled_info, L)
+
+    let output_len = u16::to_be_bytes(output.len() as u16);
+    let info = &[
+        &output_len[..],
+        b"HPKE-v1",
+        &suite_id.encoded(),
+        label.as_ref(),
+        kem_context,
+    ];
+
+    // All our outputs are far shorter than 255 * HashLen.
+    expander
+        .expand_slice(info, output)
+        .expect("HPKE output length within HKDF limits");
+}
+
+/// Label describes the possible labels for use with [labeled_extract_for_expand] and [labeled_expand].
+#[derive(Debug)]
+enum Label {
+    PskIdHash,
+    InfoHash,
+    Secret,
+    Key,
+    BaseNonce,
+    EaePrk,
+    SharedSecret,
+}
+
+impl AsRef<[u8]> for Label {
+    fn as_ref(&self) -> &[u8] {
+        match self {
+            Self::PskIdHash => b"psk_id_hash",
+            Self::InfoHash => b"info_hash",
+            Self::Secret => b"secret",
+            Self::Key => b"key",
+            Self::BaseNonce => b"base_nonce",
+            Self::EaePrk => b"eae_prk",
+            Self::SharedSecret => b"shared_secret",
+        }
+    }
+}
+
+/// LabeledSuiteId describes the possible suite ID values for use with [labeled_extract_for_expand] and
+/// [labeled_expand].
+#[derive(Debug, Copy, Clone)]
+enum LabeledSuiteId {
+    Hpke(HpkeSuite),
+    Kem(HpkeKem),
+}
+
+impl LabeledSuiteId {
+    /// The suite ID encoding depends on the context of use. In the general HPKE context,
+    /// we use a "HPKE" prefix and encode the entire ciphersuite. In the KEM context we use a
+    /// "KEM" prefix and only encode the KEM ID.
+    ///
+    /// See the bottom of [RFC 9180 §4](https://www.rfc-editor.org/rfc/rfc9180.html#section-4)
+    /// for more information.
+    fn encoded(&self) -> Vec<u8> {
+        match self {
+            Self::Hpke(suite) => [
+                &b"HPKE"[..],
+                &u16::from(suite.kem).to_be_bytes(),
+                &u16::from(suite.sym.kdf_id).to_be_bytes(),
+                &u16::from(suite.sym.aead_id).to_be_bytes(),
+            ]
+            .concat(),
+            Self::Kem(kem) => [&b"KEM"[..], &u16::from(*kem).to_be_bytes()].concat(),
+        }
+    }
+}
+
+/// An error from one of the HPKE primitives.
+#[derive(Debug)]
+struct HpkeError(&'static str);
+
+impl fmt::Display for HpkeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl core::error::Error for HpkeError {}
+
+fn hpke_error(reason: &'static str) -> Error {
+    Error::Other(OtherError::new(HpkeError(reason)))
+}
+
+const NONCE_LEN: usize = 12;
+
+static HKDF_HMAC_SHA256: &HkdfUsingHmac<'static> = &HkdfUsingHmac(&HMAC_SHA256);
+static HKDF_HMAC_SHA384: &HkdfUsingHmac<'static> = &HkdfUsingHmac(&HMAC_SHA384);
+
+#[cfg(test)]
+mod tests {
+    use alloc::format;
+
+    use super::*;
+
+    #[test]
+    fn smoke_test() {
+        for suite in ALL_SUPPORTED_SUITES {
+            _ = format!("{suite:?}"); // HpkeRustCrypto suites should be Debug.
+
+            // We should be able to generate a random keypair.
+            let (pk, sk) = suite.generate_key_pair().unwrap();
+
+            // Info value corresponds to the first RFC 9180 base mode test vector.
+            let info = &[
+                0x4f, 0x64, 0x65, 0x20, 0x6f, 0x6e, 0x20, 0x61, 0x20, 0x47, 0x72, 0x65, 0x63, 0x69,
+                0x61, 0x6e, 0x20, 0x55, 0x72, 0x6e,
+            ][..];
+
+            // We should be able to set up a sealer.
+            let (enc, mut sealer) = suite.setup_sealer(info, &pk).unwrap();
+
+            _ = format!("{sealer:?}"); // Sealer should be Debug.
+
+            // Setting up a sealer with an invalid public key should fail.
+            let bad_setup_res = suite.setup_sealer(info, &HpkePublicKey(vec![]));
+            assert!(matches!(bad_setup_res.unwrap_err(), Error::Other(_)));
+
+            // We should be able to seal some plaintext.
+            let aad = &[0xC0, 0xFF, 0xEE];
+            let pt = &[0xF0, 0x0D];
+            let ct = sealer.seal(aad, pt).unwrap();
+
+            // We should be able to set up an opener.
+            let mut opener = suite
+                .setup_opener(&enc, info, &sk)
+                .unwrap();
+            _ = format!("{opener:?}"); // Opener should be Debug.
+
+            // Setting up an opener with an invalid private key should fail.
+            let bad_key_res = suite.setup_opener(&enc, info, &HpkePrivateKey::from(vec![]));
+            assert!(matches!(bad_key_res.unwrap_err(), Error::Other(_)));
+
+            // Opening the plaintext should work with the correct opener and aad.
+            let pt_prime = opener.open(aad, &ct).unwrap();
+            assert_eq!(pt_prime, pt);
+
+            // Opening the plaintext with the correct opener and wrong aad should fail.
+            let open_res = opener.open(&[0x0], &ct);
+            assert!(matches!(open_res.unwrap_err(), Error::Other(_)));
+
+            // Opening the plaintext with the wrong opener should fail.
+            let mut sk_rm_prime = sk.secret_bytes().to_vec();
+            sk_rm_prime[10] ^= 0xFF; // Corrupt a byte of the private key.
+            let mut opener_two = suite
+                .setup_opener(&enc, info, &HpkePrivateKey::from(sk_rm_prime))
+                .unwrap();
+            let open_res = opener_two.open(aad, &ct);
+            assert!(matches!(open_res.unwrap_err(), Error::Other(_)));
+        }
+    }
+
+    #[test]
+    fn test_fips() {
+        for suite in ALL_SUPPORTED_SUITES {
+            assert_eq!(suite.fips(), FipsStatus::Unvalidated);
+        }
+    }
+
+    /// Confirm seal operations reproduce the first base mode test vector from [RFC 9180 Appendix A].
+    ///
+    /// [RFC 9180 Appendix A]: https://www.rfc-editor.org/rfc/rfc9180#appendix-A.1.1
+    #[test]
+    fn rfc9180_a_1_1_base_setup() {
+        let sk_em = hex("52c4a758a802cd8b936eceea314432798d5baf2d7e9235dc084ab1b9cfa2f736");
+        let pk_rm = hex("3948cfe0ad1ddb695d780e59077195da6c56506b027329794ab02bca80815c4d");
+        let sk_rm = hex("4612c550263fc8ad58375df3f557aac531d26850903e55a9f23f21d8534e8ac8");
+        let info = hex("4f6465206f6e2061204772656369616e2055726e");
+
+        let (enc, mut sealer) = Sealer::test_only_new(
+            DH_KEM_X25519_HKDF_SHA256_AES_128,
+            &info,
+            &HpkePublicKey(pk_rm),
+            &sk_em,
+        )
+        .unwrap();
+        assert_eq!(
+            enc.0,
+            hex("37fda3567bdbd628e88668c3c8d7e97d1d1253b6d4ea6d44c150f741f1bf4431")
+        );
+
+        let aad = hex("436f756e742d30");
+        let pt = hex("4265617574792069732074727574682c20747275746820626561757479");
+        let ct = sealer.seal(&aad, &pt).unwrap();
+        assert_eq!(
+            ct,
+            hex(
+                "f938558b5d72f1a23810b4be2ab4f84331acc02fc97babc53a52ae8218a355a96d8770ac83d07bea87e13c512a"
+            )
+        );
+
+        let mut opener = DH_KEM_X25519_HKDF_SHA256_AES_128
+            .setup_opener(&enc, &info, &HpkePrivateKey::from(sk_rm))
+            .unwrap();
+        assert_eq!(opener.open(&aad, &ct).unwrap(), pt);
+    }
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+}
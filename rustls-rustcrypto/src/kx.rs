@@ -0,0 +1,221 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+
+use p256::elliptic_curve::sec1::{FromEncodedPoint, ModulusSize, ToEncodedPoint};
+use p256::elliptic_curve::{AffinePoint, CurveArithmetic, FieldBytesSize, PublicKey, ecdh};
+use pki_types::FipsStatus;
+use rand_core::OsRng;
+use rustls::crypto::kx::{
+    ActiveKeyExchange, NamedGroup, SharedSecret, StartedKeyExchange, SupportedKxGroup,
+};
+use rustls::error::{Error, PeerMisbehaved};
+
+/// A list of the default key exchange groups supported by this provider.
+pub static DEFAULT_KX_GROUPS: &[&dyn SupportedKxGroup] = ALL_KX_GROUPS;
+
+/// A list of all the key exchange groups supported by this provider.
+pub static ALL_KX_GROUPS: &[&dyn SupportedKxGroup] = &[X25519, SECP256R1, SECP384R1];
+
+/// Ephemeral ECDH on curve25519 (see RFC 7748)
+pub static X25519: &dyn SupportedKxGroup = &X25519Group;
+
+/// Ephemeral ECDH on secp256r1 (aka NIST-P256)
+pub static SECP256R1: &dyn SupportedKxGroup = &NistGroup::<p256::NistP256> {
+    name: NamedGroup::secp256r1,
+    _curve: PhantomData,
+};
+
+/// Ephemeral ECDH on secp384r1 (aka NIST-P384)
+pub static SECP384R1: &dyn SupportedKxGroup = &NistGroup::<p384::NistP384> {
+    name: NamedGroup::secp384r1,
+    _curve: PhantomData,
+};
+
+struct X25519Group;
+
+impl SupportedKxGroup for X25519Group {
+    fn start(&self) -> Result<StartedKeyExchange, Error> {
+        let priv_key = x25519_dalek::EphemeralSecret::random_from_rng(OsRng);
+        let pub_key = x25519_dalek::PublicKey::from(&priv_key);
+        Ok(StartedKeyExchange::Single(Box::new(X25519KeyExchange {
+            priv_key,
+            pub_key,
+        })))
+    }
+
+    fn name(&self) -> NamedGroup {
+        NamedGroup::X25519
+    }
+
+    fn fips(&self) -> FipsStatus {
+        // X25519 is not approved for key agreement by SP 800-56Arev3.
+        FipsStatus::Unvalidated
+    }
+}
+
+impl fmt::Debug for X25519Group {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        NamedGroup::X25519.fmt(f)
+    }
+}
+
+struct X25519KeyExchange {
+    priv_key: x25519_dalek::EphemeralSecret,
+    pub_key: x25519_dalek::PublicKey,
+}
+
+impl ActiveKeyExchange for X25519KeyExchange {
+    fn complete(self: Box<Self>, peer: &[u8]) -> Result<SharedSecret, Error> {
+        let peer = <[u8; 32]>::try_from(peer).map_err(|_| PeerMisbehaved::InvalidKeyShare)?;
+        let secret = self
+            .priv_key
+            .diffie_hellman(&x25519_dalek::PublicKey::from(peer));
+
+        // Reject low-order points, which produce an all-zero shared secret.
+        match secret.was_contributory() {
+            true => Ok(SharedSecret::from(&secret.as_bytes()[..])),
+            false => Err(PeerMisbehaved::InvalidKeyShare.into()),
+        }
+    }
+
+    fn group(&self) -> NamedGroup {
+        NamedGroup::X25519
+    }
+
+    fn pub_key(&self) -> &[u8] {
+        self.pub_key.as_bytes()
+    }
+}
+
+/// A key-exchange group on one of the NIST prime curves.
+struct NistGroup<C> {
+    name: NamedGroup,
+    _curve: PhantomData<fn() -> C>,
+}
+
+impl<C> SupportedKxGroup for NistGroup<C>
+where
+    C: CurveArithmetic,
+    AffinePoint<C>: FromEncodedPoint<C> + ToEncodedPoint<C>,
+    FieldBytesSize<C>: ModulusSize,
+{
+    fn start(&self) -> Result<StartedKeyExchange, Error> {
+        let priv_key = ecdh::EphemeralSecret::<C>::random(&mut OsRng);
+        let pub_key = priv_key
+            .public_key()
+            .to_encoded_point(false)
+            .as_bytes()
+            .to_vec();
+
+        Ok(StartedKeyExchange::Single(Box::new(NistKeyExchange {
+            name: self.name,
+            priv_key,
+            pub_key,
+        })))
+    }
+
+    fn name(&self) -> NamedGroup {
+        self.name
+    }
+
+    fn fips(&self) -> FipsStatus {
+        super::fips()
+    }
+}
+
+impl<C> fmt::Debug for NistGroup<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+/// An in-progress key exchange on a NIST prime curve.
+struct NistKeyExchange<C: CurveArithmetic> {
+    name: NamedGroup,
+    priv_key: ecdh::EphemeralSecret<C>,
+    pub_key: Vec<u8>,
+}
+
+impl<C> ActiveKeyExchange for NistKeyExchange<C>
+where
+    C: CurveArithmetic,
+    AffinePoint<C>: FromEncodedPoint<C> + ToEncodedPoint<C>,
+    FieldBytesSize<C>: ModulusSize,
+{
+    fn complete(self: Box<Self>, peer: &[u8]) -> Result<SharedSecret, Error> {
+        // TLS1.3 only permits uncompressed points, and for TLS1.2 we only offer them.
+        // See `UncompressedPointRepresentation` in RFC 9846 section 4.3.8.2.
+        if !matches!(peer.first(), Some(0x04)) {
+            return Err(PeerMisbehaved::InvalidKeyShare.into());
+        }
+
+        let peer =
+            PublicKey::<C>::from_sec1_bytes(peer).map_err(|_| PeerMisbehaved::InvalidKeyShare)?;
+        let secret = self.priv_key.diffie_hellman(&peer);
+        Ok(SharedSecret::from(&secret.raw_secret_bytes()[..]))
+    }
+
+    fn group(&self) -> NamedGroup {
+        self.name
+    }
+
+    fn pub_key(&self) -> &[u8] {
+        &self.pub_key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::format;
+
+    use super::*;
+
+    #[test]
+    fn kxgroup_fmt_yields_name() {
+        assert_eq!("X25519", format!("{:?}", X25519));
+        assert_eq!("secp256r1", format!("{:?}", SECP256R1));
+    }
+
+    #[test]
+    fn key_exchange_agrees() {
+        for group in ALL_KX_GROUPS {
+            let a = group.start().unwrap().into_single();
+            let b = group.start().unwrap().into_single();
+            let b_pub = b.pub_key().to_vec();
+            let ab = a.pub_key().to_vec();
+            let secret_a = a.complete(&b_pub).unwrap();
+            let secret_b = b.complete(&ab).unwrap();
+            assert_eq!(
+                secret_a.secret_bytes(),
+                secret_b.secret_bytes(),
+                "{group:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_peer_keys() {
+        for group in ALL_KX_GROUPS {
+            let kx = group.start().unwrap().into_single();
+            let mut bad = kx.pub_key().to_vec();
+            bad.truncate(bad.len() - 1);
+            assert!(kx.complete(&bad).is_err(), "{group:?}");
+        }
+
+        // an all-zero X25519 share is a low-order point
+        let kx = X25519.start().unwrap().into_single();
+        assert!(kx.complete(&[0u8; 32]).is_err());
+
+        // compressed points are not allowed
+        let kx = SECP256R1.start().unwrap().into_single();
+        let compressed = p256::SecretKey::random(&mut OsRng)
+            .public_key()
+            .to_encoded_point(true);
+        assert!(
+            kx.complete(compressed.as_bytes())
+                .is_err()
+        );
+    }
+}
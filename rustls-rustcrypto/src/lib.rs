@@ -0,0 +1,204 @@
+//! A `CryptoProvider` implementation backed by the [RustCrypto] crates.
+//!
+//! This provider is written entirely in Rust and has no C or assembly build
+//! dependencies, so it can be used on targets where *ring* and aws-lc-rs
+//! cannot be built.  It supports `no_std` environments that have `alloc`
+//! available: disable the default `std` feature to use it there.  Note that
+//! without `std`, [`TicketerFactory::ticketer()`] is unavailable.
+//!
+//! Randomness is obtained from the operating system via [`getrandom`]; targets
+//! without a supported source of randomness must register a custom
+//! implementation as described in that crate's documentation.
+//!
+//! RSA is supported for signature verification only: the [`KeyProvider`]
+//! refuses to load RSA private keys.  The `rsa` crate's private-key operations
+//! are not constant-time ([RUSTSEC-2023-0071], the "Marvin" attack), and a
+//! server signing with such a key exposes it to a network timing
+//! side-channel.  Use an ECDSA or Ed25519 key instead.
+//!
+//! [RustCrypto]: https://github.com/RustCrypto
+//! [`getrandom`]: https://docs.rs/getrandom/0.2
+//! [RUSTSEC-2023-0071]: https://rustsec.org/advisories/RUSTSEC-2023-0071
+
+#![no_std]
+#![warn(clippy::exhaustive_enums, clippy::exhaustive_structs, missing_docs)]
+
+extern crate alloc;
+#[cfg(any(feature = "std", test))]
+extern crate std;
+
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+#[cfg(feature = "std")]
+use core::time::Duration;
+
+use pki_types::{FipsStatus, PrivateKeyDer};
+use rand_core::{OsRng, RngCore};
+use rustls::crypto::{
+    CryptoProvider, GetRandomFailed, KeyProvider, SecureRandom, SigningKey, TicketProducer,
+    TicketerFactory,
+};
+use rustls::error::Error;
+#[cfg(feature = "std")]
+use rustls::ticketer::TicketRotator;
+
+/// Using software keys for authentication.
+pub mod sign;
+use sign::{EcdsaSigner, Ed25519Signer};
+
+pub(crate) mod hash;
+
+pub(crate) mod hmac;
+
+/// Hybrid public key encryption (HPKE) suites.
+pub mod hpke;
+
+pub(crate) mod kx;
+pub use kx::{ALL_KX_GROUPS, DEFAULT_KX_GROUPS};
+
+#[cfg(feature = "std")]
+pub(crate) mod ticketer;
+#[cfg(feature = "std")]
+use ticketer::AeadTicketer;
+
+pub(crate) mod tls12;
+pub use tls12::{ALL_TLS12_CIPHER_SUITES, DEFAULT_TLS12_CIPHER_SUITES};
+
+pub(crate) mod tls13;
+pub use tls13::{ALL_TLS13_CIPHER_SUITES, DEFAULT_TLS13_CIPHER_SUITES};
+
+mod verify;
+use verify::SUPPORTED_SIG_ALGS;
+pub use verify::{
+    ALL_VERIFICATION_ALGS, ECDSA_P256_SHA256, ECDSA_P256_SHA384, ECDSA_P384_SHA256,
+    ECDSA_P384_SHA384, ED25519, RSA_PKCS1_2048_8192_SHA256,
+    RSA_PKCS1_2048_8192_SHA256_ABSENT_PARAMS, RSA_PKCS1_2048_8192_SHA384,
+    RSA_PKCS1_2048_8192_SHA384_ABSENT_PARAMS, RSA_PKCS1_2048_8192_SHA512,
+    RSA_PKCS1_2048_8192_SHA512_ABSENT_PARAMS, RSA_PKCS1_3072_8192_SHA384,
+    RSA_PSS_2048_8192_SHA256_LEGACY_KEY, RSA_PSS_2048_8192_SHA384_LEGACY_KEY,
+    RSA_PSS_2048_8192_SHA512_LEGACY_KEY,
+};
+
+/// The default `CryptoProvider` backed by the RustCrypto crates.
+pub const DEFAULT_PROVIDER: CryptoProvider = CryptoProvider {
+    tls12_cipher_suites: Cow::Borrowed(DEFAULT_TLS12_CIPHER_SUITES),
+    tls13_cipher_suites: Cow::Borrowed(DEFAULT_TLS13_CIPHER_SUITES),
+    kx_groups: Cow::Borrowed(DEFAULT_KX_GROUPS),
+    signature_verification_algorithms: SUPPORTED_SIG_ALGS,
+    secure_random: &RustCrypto,
+    key_provider: &RustCrypto,
+    ticketer_factory: &RustCrypto,
+};
+
+/// The default `CryptoProvider` backed by the RustCrypto crates that only supports TLS1.3.
+pub const DEFAULT_TLS13_PROVIDER: CryptoProvider = CryptoProvider {
+    tls12_cipher_suites: Cow::Borrowed(&[]),
+    ..DEFAULT_PROVIDER
+};
+
+/// The default `CryptoProvider` backed by the RustCrypto crates that only supports TLS1.2.
+///
+/// Use of TLS1.3 is **strongly** recommended.
+pub const DEFAULT_TLS12_PROVIDER: CryptoProvider = CryptoProvider {
+    tls13_cipher_suites: Cow::Borrowed(&[]),
+    ..DEFAULT_PROVIDER
+};
+
+/// Default crypto provider.
+#[derive(Debug)]
+struct RustCrypto;
+
+impl SecureRandom for RustCrypto {
+    fn fill(&self, buf: &mut [u8]) -> Result<(), GetRandomFailed> {
+        fill_random(buf)
+    }
+}
+
+impl KeyProvider for RustCrypto {
+    /// Load an ECDSA (P-256 or P-384) or Ed25519 private key.
+    ///
+    /// RSA private keys are rejected; see the crate documentation.
+    fn load_private_key(
+        &self,
+        key_der: PrivateKeyDer<'static>,
+    ) -> Result<Box<dyn SigningKey>, Error> {
+        if let Ok(ecdsa) = EcdsaSigner::try_from(&key_der) {
+            return Ok(Box::new(ecdsa));
+        }
+
+        if let PrivateKeyDer::Pkcs8(pkcs8) = key_der {
+            if let Ok(eddsa) = Ed25519Signer::try_from(&pkcs8) {
+                return Ok(Box::new(eddsa));
+            }
+        }
+
+        Err(Error::General(
+            "failed to parse private key as ECDSA or EdDSA".into(),
+        ))
+    }
+}
+
+impl TicketerFactory for RustCrypto {
+    /// Make the recommended `Ticketer`.
+    ///
+    /// This produces tickets:
+    ///
+    /// - where each lasts for at least 6 hours,
+    /// - with randomly generated keys, and
+    /// - where keys are rotated every 6 hours.
+    ///
+    /// The encryption mechanism used is Chacha20Poly1305.
+    fn ticketer(&self) -> Result<Arc<dyn TicketProducer>, Error> {
+        #[cfg(feature = "std")]
+        {
+            Ok(Arc::new(TicketRotator::new(SIX_HOURS, AeadTicketer::new)?))
+        }
+        #[cfg(not(feature = "std"))]
+        {
+            Err(Error::General(
+                "RustCrypto::ticketer() relies on std-only RwLock via TicketRotator".into(),
+            ))
+        }
+    }
+
+    fn fips(&self) -> FipsStatus {
+        fips()
+    }
+}
+
+/// All defined cipher suites supported by this provider appear in this module.
+pub mod cipher_suite {
+    pub use super::tls12::{
+        TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256, TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384,
+        TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256, TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256,
+        TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384, TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256,
+    };
+    pub use super::tls13::{
+        TLS13_AES_128_GCM_SHA256, TLS13_AES_256_GCM_SHA384, TLS13_CHACHA20_POLY1305_SHA256,
+    };
+}
+
+/// All defined key exchange groups supported by this provider appear in this module.
+///
+/// [`ALL_KX_GROUPS`] is provided as an array of all of these values.
+/// [`DEFAULT_KX_GROUPS`] is provided as an array of this provider's defaults.
+pub mod kx_group {
+    pub use super::kx::{SECP256R1, SECP384R1, X25519};
+}
+
+/// Return the FIPS validation status of this implementation.
+///
+/// The RustCrypto crates are not FIPS validated.
+pub fn fips() -> FipsStatus {
+    FipsStatus::Unvalidated
+}
+
+fn fill_random(buf: &mut [u8]) -> Result<(), GetRandomFailed> {
+    OsRng
+        .try_fill_bytes(buf)
+        .map_err(|_| GetRandomFailed)
+}
+
+#[cfg(feature = "std")]
+const SIX_HOURS: Duration = Duration::from_secs(6 * 60 * 60);
@@ -0,0 +1,390 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::{self, Debug, Formatter};
+
+use ed25519_dalek::pkcs8::DecodePrivateKey as _;
+use p256::ecdsa::signature::{SignatureEncoding, Signer as _};
+use pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer, SubjectPublicKeyInfoDer, alg_id};
+use rustls::crypto::{SignatureScheme, Signer, SigningKey, public_key_to_spki};
+use rustls::error::Error;
+
+/// A [`SigningKey`] and [`Signer`] implementation for ECDSA.
+///
+/// Each key supports exactly one signature scheme, so we can use the same type
+/// for both traits here.
+#[derive(Clone)]
+pub(super) struct EcdsaSigner {
+    key: Arc<EcdsaKey>,
+    scheme: SignatureScheme,
+}
+
+enum EcdsaKey {
+    P256(p256::ecdsa::SigningKey),
+    P384(p384::ecdsa::SigningKey),
+}
+
+impl EcdsaSigner {
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, Error> {
+        match &*self.key {
+            EcdsaKey::P256(key) => key
+                .try_sign(message)
+                .map(|sig: p256::ecdsa::DerSignature| sig.to_vec()),
+            EcdsaKey::P384(key) => key
+                .try_sign(message)
+                .map(|sig: p384::ecdsa::DerSignature| sig.to_vec()),
+        }
+        .map_err(|_| Error::General("signing failed".into()))
+    }
+}
+
+impl SigningKey for EcdsaSigner {
+    fn choose_scheme(&self, offered: &[SignatureScheme]) -> Option<Box<dyn Signer>> {
+        if offered.contains(&self.scheme) {
+            Some(Box::new(self.clone()))
+        } else {
+            None
+        }
+    }
+
+    fn public_key(&self) -> Option<SubjectPublicKeyInfoDer<'_>> {
+        Some(match &*self.key {
+            EcdsaKey::P256(key) => public_key_to_spki(
+                &alg_id::ECDSA_P256,
+                key.verifying_key()
+                    .to_encoded_point(false),
+            ),
+            EcdsaKey::P384(key) => public_key_to_spki(
+                &alg_id::ECDSA_P384,
+                key.verifying_key()
+                    .to_encoded_point(false),
+            ),
+        })
+    }
+}
+
+impl Signer for EcdsaSigner {
+    fn sign(self: Box<Self>, message: &[u8]) -> Result<Vec<u8>, Error> {
+        (*self).sign(message)
+    }
+
+    fn scheme(&self) -> SignatureScheme {
+        self.scheme
+    }
+}
+
+impl TryFrom<&PrivateKeyDer<'_>> for EcdsaSigner {
+    type Error = Error;
+
+    /// Parse `der` as any ECDSA key type, returning the first which works.
+    ///
+    /// Both SEC1 (PEM section starting with 'BEGIN EC PRIVATE KEY') and PKCS8
+    /// (PEM section starting with 'BEGIN PRIVATE KEY') encodings are supported.
+    fn try_from(der: &PrivateKeyDer<'_>) -> Result<Self, Self::Error> {
+        use p256::pkcs8::DecodePrivateKey;
+
+        let key = match der {
+            PrivateKeyDer::Sec1(sec1) => {
+                let der = sec1.secret_sec1_der();
+                if let Ok(key) = p256::SecretKey::from_sec1_der(der) {
+                    Some(EcdsaKey::P256(key.into()))
+                } else if let Ok(key) = p384::SecretKey::from_sec1_der(der) {
+                    Some(EcdsaKey::P384(key.into()))
+                } else {
+                    None
+                }
+            }
+            PrivateKeyDer::Pkcs8(pkcs8) => {
+                let der = pkcs8.secret_pkcs8_der();
+                if let Ok(key) = p256::SecretKey::from_pkcs8_der(der) {
+                    Some(EcdsaKey::P256(key.into()))
+                } else if let Ok(key) = p384::SecretKey::from_pkcs8_der(der) {
+                    Some(EcdsaKey::P384(key.into()))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+
+        let Some(key) = key else {
+            return Err(Error::General(
+                "failed to parse ECDSA private key as PKCS#8 or SEC1".into(),
+            ));
+        };
+
+        let scheme = match &key {
+            EcdsaKey::P256(_) => SignatureScheme::ECDSA_NISTP256_SHA256,
+            EcdsaKey::P384(_) => SignatureScheme::ECDSA_NISTP384_SHA384,
+        };
+
+        Ok(Self {
+            key: Arc::new(key),
+            scheme,
+        })
+    }
+}
+
+impl Debug for EcdsaSigner {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EcdsaSigner")
+            .field("scheme", &self.scheme)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A [`SigningKey`] and [`Signer`] implementation for ED25519.
+///
+/// Each key supports exactly one signature scheme, so we can use the same type
+/// for both traits here.
+#[derive(Clone)]
+pub(super) struct Ed25519Signer {
+    key: Arc<ed25519_dalek::SigningKey>,
+    scheme: SignatureScheme,
+}
+
+impl Ed25519Signer {
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, Error> {
+        Ok(self.key.sign(message).to_vec())
+    }
+}
+
+impl SigningKey for Ed25519Signer {
+    fn choose_scheme(&self, offered: &[SignatureScheme]) -> Option<Box<dyn Signer>> {
+        if offered.contains(&self.scheme) {
+            Some(Box::new(self.clone()))
+        } else {
+            None
+        }
+    }
+
+    fn public_key(&self) -> Option<SubjectPublicKeyInfoDer<'_>> {
+        Some(public_key_to_spki(
+            &alg_id::ED25519,
+            self.key.verifying_key().as_bytes(),
+        ))
+    }
+}
+
+impl Signer for Ed25519Signer {
+    fn sign(self: Box<Self>, message: &[u8]) -> Result<Vec<u8>, Error> {
+        (*self).sign(message)
+    }
+
+    fn scheme(&self) -> SignatureScheme {
+        self.scheme
+    }
+}
+
+impl TryFrom<&PrivatePkcs8KeyDer<'_>> for Ed25519Signer {
+    type Error = Error;
+
+    /// Parse `der` as an Ed25519 key.
+    ///
+    /// Note that, at the time of writing, Ed25519 does not have wide support
+    /// in browsers.  It is also not supported by the WebPKI, because the
+    /// CA/Browser Forum Baseline Requirements do not support it for publicly
+    /// trusted certificates.
+    fn try_from(der: &PrivatePkcs8KeyDer<'_>) -> Result<Self, Self::Error> {
+        match ed25519_dalek::SigningKey::from_pkcs8_der(der.secret_pkcs8_der()) {
+            Ok(key) => Ok(Self {
+                key: Arc::new(key),
+                scheme: SignatureScheme::ED25519,
+            }),
+            Err(e) => Err(Error::General(format!(
+                "failed to parse Ed25519 private key: {e}"
+            ))),
+        }
+    }
+}
+
+impl Debug for Ed25519Signer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ed25519Signer")
+            .field("scheme", &self.scheme)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::format;
+
+    use pki_types::{PrivatePkcs1KeyDer, PrivateSec1KeyDer};
+    use rustls::crypto::CryptoProvider;
+
+    use super::*;
+    use crate::DEFAULT_PROVIDER;
+
+    #[test]
+    fn can_load_ecdsa_nistp256_pkcs8() {
+        let key = PrivatePkcs8KeyDer::from(
+            &include_bytes!("../../rustls/src/testdata/nistp256key.pkcs8.der")[..],
+        );
+        assert!(Ed25519Signer::try_from(&key).is_err());
+        let key = PrivateKeyDer::Pkcs8(key);
+        assert!(load_key(&DEFAULT_PROVIDER, key.clone_key()).is_ok());
+        assert!(EcdsaSigner::try_from(&key).is_ok());
+    }
+
+    #[test]
+    fn can_load_ecdsa_nistp256_sec1() {
+        let key = PrivateKeyDer::Sec1(PrivateSec1KeyDer::from(
+            &include_bytes!("../../rustls/src/testdata/nistp256key.der")[..],
+        ));
+        assert!(load_key(&DEFAULT_PROVIDER, key.clone_key()).is_ok());
+        assert!(EcdsaSigner::try_from(&key).is_ok());
+    }
+
+    #[test]
+    fn can_sign_ecdsa_nistp256() {
+        let key = PrivateKeyDer::Sec1(PrivateSec1KeyDer::from(
+            &include_bytes!("../../rustls/src/testdata/nistp256key.der")[..],
+        ));
+
+        let k = load_key(&DEFAULT_PROVIDER, key.clone_key()).unwrap();
+        assert_eq!(
+            format!("{k:?}"),
+            "EcdsaSigner { scheme: ECDSA_NISTP256_SHA256, .. }"
+        );
+
+        assert!(
+            k.choose_scheme(&[SignatureScheme::ECDSA_NISTP384_SHA384])
+                .is_none()
+        );
+        let s = k
+            .choose_scheme(&[SignatureScheme::ECDSA_NISTP256_SHA256])
+            .unwrap();
+        assert_eq!(s.scheme(), SignatureScheme::ECDSA_NISTP256_SHA256);
+        check_signature(&*k, s, crate::ECDSA_P256_SHA256);
+    }
+
+    #[test]
+    fn can_load_ecdsa_nistp384_pkcs8() {
+        let key = PrivatePkcs8KeyDer::from(
+            &include_bytes!("../../rustls/src/testdata/nistp384key.pkcs8.der")[..],
+        );
+        assert!(Ed25519Signer::try_from(&key).is_err());
+        let key = PrivateKeyDer::Pkcs8(key);
+        assert!(load_key(&DEFAULT_PROVIDER, key.clone_key()).is_ok());
+        assert!(EcdsaSigner::try_from(&key).is_ok());
+    }
+
+    #[test]
+    fn can_sign_ecdsa_nistp384() {
+        let key = PrivateKeyDer::Sec1(PrivateSec1KeyDer::from(
+            &include_bytes!("../../rustls/src/testdata/nistp384key.der")[..],
+        ));
+
+        let k = load_key(&DEFAULT_PROVIDER, key.clone_key()).unwrap();
+        assert_eq!(
+            format!("{k:?}"),
+            "EcdsaSigner { scheme: ECDSA_NISTP384_SHA384, .. }"
+        );
+        let s = k
+            .choose_scheme(&[SignatureScheme::ECDSA_NISTP384_SHA384])
+            .unwrap();
+        check_signature(&*k, s, crate::ECDSA_P384_SHA384);
+    }
+
+    #[test]
+    fn can_sign_eddsa() {
+        let key =
+            PrivatePkcs8KeyDer::from(&include_bytes!("../../rustls/src/testdata/eddsakey.der")[..]);
+        assert!(EcdsaSigner::try_from(&PrivateKeyDer::Pkcs8(key.clone_key())).is_err());
+
+        let k = load_key(&DEFAULT_PROVIDER, PrivateKeyDer::Pkcs8(key)).unwrap();
+        assert_eq!(format!("{k:?}"), "Ed25519Signer { scheme: ED25519, .. }");
+        let s = k
+            .choose_scheme(&[SignatureScheme::ED25519])
+            .unwrap();
+        check_signature(&*k, s, crate::ED25519);
+    }
+
+    #[test]
+    fn cannot_load_rsa_keys() {
+        for key in [
+            PrivateKeyDer::Pkcs1(PrivatePkcs1KeyDer::from(
+                &include_bytes!("../../rustls/src/testdata/rsa2048key.pkcs1.der")[..],
+            )),
+            PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(
+                &include_bytes!("../../rustls/src/testdata/rsa2048key.pkcs8.der")[..],
+            )),
+        ] {
+            assert_eq!(
+                load_key(&DEFAULT_PROVIDER, key).err(),
+                Some(Error::General(
+                    "failed to parse private key as ECDSA or EdDSA".into()
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn cannot_load_invalid_pkcs8_encoding() {
+        let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(&b"invalid"[..]));
+        assert_eq!(
+            load_key(&DEFAULT_PROVIDER, key.clone_key()).err(),
+            Some(Error::General(
+                "failed to parse private key as ECDSA or EdDSA".into()
+            ))
+        );
+        assert_eq!(
+            EcdsaSigner::try_from(&key).err(),
+            Some(Error::General(
+                "failed to parse ECDSA private key as PKCS#8 or SEC1".into()
+            ))
+        );
+    }
+
+    /// Sign a message with `signer`, and check the signature verifies against
+    /// the public key of `key` using `verifier`.
+    fn check_signature(
+        key: &dyn SigningKey,
+        signer: Box<dyn Signer>,
+        verifier: &dyn pki_types::SignatureVerificationAlgorithm,
+    ) {
+        let spki = key.public_key().unwrap();
+        let public_key = subject_public_key(&spki);
+        let signature = signer.sign(b"hello").unwrap();
+
+        verifier
+            .verify_signature(public_key, b"hello", &signature)
+            .unwrap();
+        verifier
+            .verify_signature(public_key, b"goodbye", &signature)
+            .unwrap_err();
+    }
+
+    /// Extract the `subjectPublicKey` from a `SubjectPublicKeyInfo`.
+    fn subject_public_key<'a>(spki: &'a SubjectPublicKeyInfoDer<'_>) -> &'a [u8] {
+        let spki = contents(spki.as_ref());
+        let (header_len, len) = header(spki);
+        // skip the unused bits byte of the BIT STRING
+        &contents(&spki[header_len + len..])[1..]
+    }
+
+    fn contents(der: &[u8]) -> &[u8] {
+        let (header_len, len) = header(der);
+        &der[header_len..header_len + len]
+    }
+
+    fn header(der: &[u8]) -> (usize, usize) {
+        match der[1] {
+            len @ 0..=0x7f => (2, len as usize),
+            0x81 => (3, der[2] as usize),
+            0x82 => (4, u16::from_be_bytes([der[2], der[3]]) as usize),
+            _ => unreachable!(),
+        }
+    }
+
+    fn load_key(
+        provider: &CryptoProvider,
+        der: PrivateKeyDer<'static>,
+    ) -> Result<Box<dyn SigningKey>, Error> {
+        provider
+            .key_provider
+            .load_private_key(der)
+    }
+}
@@ -0,0 +1,481 @@
+use alloc::boxed::Box;
+use core::marker::PhantomData;
+
+use aes_gcm::aead::AeadInPlace;
+use aes_gcm::{Aes128Gcm, Aes256Gcm};
+use chacha20poly1305::ChaCha20Poly1305;
+use pki_types::FipsStatus;
+use rustls::crypto::cipher::{
+    AeadKey, EncodedMessage, EncryptBuffer, InboundOpaque, Iv, KeyBlockShape, MessageDecrypter,
+    MessageEncrypter, NONCE_LEN, Nonce, OutboundPlain, Tls12AeadAlgorithm,
+    UnsupportedOperationError, make_tls12_aad,
+};
+use rustls::crypto::kx::KeyExchangeAlgorithm;
+use rustls::crypto::tls12::PrfUsingHmac;
+use rustls::crypto::{CipherSuite, SignatureScheme};
+use rustls::error::Error;
+use rustls::version::TLS12_VERSION;
+use rustls::{CipherSuiteCommon, ConnectionTrafficSecrets, Tls12CipherSuite};
+
+use crate::tls13::{AeadCipher, aead_key, tag_len};
+
+/// The TLS1.2 cipher suite configuration that an application should use by default.
+///
+/// This will be [`ALL_TLS12_CIPHER_SUITES`] sans any supported cipher suites that
+/// shouldn't be enabled by most applications.
+pub static DEFAULT_TLS12_CIPHER_SUITES: &[&Tls12CipherSuite] = ALL_TLS12_CIPHER_SUITES;
+
+/// A list of all the TLS1.2 cipher suites supported by this provider.
+pub static ALL_TLS12_CIPHER_SUITES: &[&Tls12CipherSuite] = &[
+    TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256,
+    TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384,
+    TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256,
+    TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256,
+    TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384,
+    TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256,
+];
+
+/// The TLS1.2 ciphersuite TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256.
+pub static TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256: &Tls12CipherSuite = &Tls12CipherSuite {
+    common: CipherSuiteCommon {
+        suite: CipherSuite::TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256,
+        hash_provider: &super::hash::SHA256,
+        confidentiality_limit: u64::MAX,
+    },
+    protocol_version: TLS12_VERSION,
+    kx: KeyExchangeAlgorithm::ECDHE,
+    sign: TLS12_ECDSA_SCHEMES,
+    aead_alg: &ChaCha20Poly1305Aead,
+    prf_provider: &PrfUsingHmac(&super::hmac::HMAC_SHA256),
+};
+
+/// The TLS1.2 ciphersuite TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256
+pub static TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256: &Tls12CipherSuite = &Tls12CipherSuite {
+    common: CipherSuiteCommon {
+        suite: CipherSuite::TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256,
+        hash_provider: &super::hash::SHA256,
+        confidentiality_limit: u64::MAX,
+    },
+    protocol_version: TLS12_VERSION,
+    kx: KeyExchangeAlgorithm::ECDHE,
+    sign: TLS12_RSA_SCHEMES,
+    aead_alg: &ChaCha20Poly1305Aead,
+    prf_provider: &PrfUsingHmac(&super::hmac::HMAC_SHA256),
+};
+
+/// The TLS1.2 ciphersuite TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256
+pub static TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256: &Tls12CipherSuite = &Tls12CipherSuite {
+    common: CipherSuiteCommon {
+        suite: CipherSuite::TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256,
+        hash_provider: &super::hash::SHA256,
+        confidentiality_limit: 1 << 24,
+    },
+    protocol_version: TLS12_VERSION,
+    kx: KeyExchangeAlgorithm::ECDHE,
+    sign: TLS12_RSA_SCHEMES,
+    aead_alg: &AES128_GCM,
+    prf_provider: &PrfUsingHmac(&super::hmac::HMAC_SHA256),
+};
+
+/// The TLS1.2 ciphersuite TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384
+pub static TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384: &Tls12CipherSuite = &Tls12CipherSuite {
+    common: CipherSuiteCommon {
+        suite: CipherSuite::TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384,
+        hash_provider: &super::hash::SHA384,
+        confidentiality_limit: 1 << 24,
+    },
+    protocol_version: TLS12_VERSION,
+    kx: KeyExchangeAlgorithm::ECDHE,
+    sign: TLS12_RSA_SCHEMES,
+    aead_alg: &AES256_GCM,
+    prf_provider: &PrfUsingHmac(&super::hmac::HMAC_SHA384),
+};
+
+/// The TLS1.2 ciphersuite TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256
+pub static TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256: &Tls12CipherSuite = &Tls12CipherSuite {
+    common: CipherSuiteCommon {
+        suite: CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256,
+        hash_provider: &super::hash::SHA256,
+        confidentiality_limit: 1 << 24,
+    },
+    protocol_version: TLS12_VERSION,
+    kx: KeyExchangeAlgorithm::ECDHE,
+    sign: TLS12_ECDSA_SCHEMES,
+    aead_alg: &AES128_GCM,
+    prf_provider: &PrfUsingHmac(&super::hmac::HMAC_SHA256),
+};
+
+/// The TLS1.2 ciphersuite TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384
+pub static TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384: &Tls12CipherSuite = &Tls12CipherSuite {
+    common: CipherSuiteCommon {
+        suite: CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384,
+        hash_provider: &super::hash::SHA384,
+        confidentiality_limit: 1 << 24,
+    },
+    protocol_version: TLS12_VERSION,
+    kx: KeyExchangeAlgorithm::ECDHE,
+    sign: TLS12_ECDSA_SCHEMES,
+    aead_alg: &AES256_GCM,
+    prf_provider: &PrfUsingHmac(&super::hmac::HMAC_SHA384),
+};
+
+static TLS12_ECDSA_SCHEMES: &[SignatureScheme] = &[
+    SignatureScheme::ED25519,
+    SignatureScheme::ECDSA_NISTP384_SHA384,
+    SignatureScheme::ECDSA_NISTP256_SHA256,
+];
+
+static TLS12_RSA_SCHEMES: &[SignatureScheme] = &[
+    SignatureScheme::RSA_PSS_SHA512,
+    SignatureScheme::RSA_PSS_SHA384,
+    SignatureScheme::RSA_PSS_SHA256,
+    SignatureScheme::RSA_PKCS1_SHA512,
+    SignatureScheme::RSA_PKCS1_SHA384,
+    SignatureScheme::RSA_PKCS1_SHA256,
+];
+
+static AES128_GCM: GcmAlgorithm<Aes128Gcm> = GcmAlgorithm(PhantomData);
+static AES256_GCM: GcmAlgorithm<Aes256Gcm> = GcmAlgorithm(PhantomData);
+
+struct GcmAlgorithm<A>(PhantomData<fn() -> A>);
+
+impl<A: AeadCipher> Tls12AeadAlgorithm for GcmAlgorithm<A> {
+    fn decrypter(&self, dec_key: AeadKey, dec_iv: &[u8]) -> Box<dyn MessageDecrypter> {
+        let mut ret = GcmMessageDecrypter {
+            dec_key: aead_key::<A>(dec_key.as_ref()),
+            dec_salt: [0u8; 4],
+        };
+
+        debug_assert_eq!(dec_iv.len(), 4);
+        ret.dec_salt.copy_from_slice(dec_iv);
+        Box::new(ret)
+    }
+
+    fn encrypter(
+        &self,
+        enc_key: AeadKey,
+        write_iv: &[u8],
+        explicit: &[u8],
+    ) -> Box<dyn MessageEncrypter> {
+        Box::new(GcmMessageEncrypter {
+            enc_key: aead_key::<A>(enc_key.as_ref()),
+            iv: gcm_iv(write_iv, explicit),
+        })
+    }
+
+    fn key_block_shape(&self) -> KeyBlockShape {
+        KeyBlockShape {
+            enc_key_len: A::key_size(),
+            fixed_iv_len: 4,
+            explicit_nonce_len: 8,
+        }
+    }
+
+    fn extract_keys(
+        &self,
+        key: AeadKey,
+        write_iv: &[u8],
+        explicit: &[u8],
+    ) -> Result<ConnectionTrafficSecrets, UnsupportedOperationError> {
+        Ok(A::traffic_secrets(key, gcm_iv(write_iv, explicit)))
+    }
+
+    fn fips(&self) -> FipsStatus {
+        A::fips()
+    }
+}
+
+struct ChaCha20Poly1305Aead;
+
+impl Tls12AeadAlgorithm for ChaCha20Poly1305Aead {
+    fn decrypter(&self, dec_key: AeadKey, iv: &[u8]) -> Box<dyn MessageDecrypter> {
+        Box::new(ChaCha20Poly1305MessageDecrypter {
+            dec_key: aead_key::<ChaCha20Poly1305>(dec_key.as_ref()),
+            dec_offset: Iv::new(iv).expect("IV length validated by key_block_shape"),
+        })
+    }
+
+    fn encrypter(&self, enc_key: AeadKey, enc_iv: &[u8], _: &[u8]) -> Box<dyn MessageEncrypter> {
+        Box::new(ChaCha20Poly1305MessageEncrypter {
+            enc_key: aead_key::<ChaCha20Poly1305>(enc_key.as_ref()),
+            enc_offset: Iv::new(enc_iv).expect("IV length validated by key_block_shape"),
+        })
+    }
+
+    fn key_block_shape(&self) -> KeyBlockShape {
+        KeyBlockShape {
+            enc_key_len: 32,
+            fixed_iv_len: 12,
+            explicit_nonce_len: 0,
+        }
+    }
+
+    fn extract_keys(
+        &self,
+        key: AeadKey,
+        iv: &[u8],
+        _explicit: &[u8],
+    ) -> Result<ConnectionTrafficSecrets, UnsupportedOperationError> {
+        // This should always be true because KeyBlockShape and the Iv nonce len are in agreement.
+        debug_assert_eq!(NONCE_LEN, iv.len());
+        Ok(ConnectionTrafficSecrets::Chacha20Poly1305 {
+            key,
+            iv: Iv::new(iv).expect("IV length validated by key_block_shape"),
+        })
+    }
+
+    fn fips(&self) -> FipsStatus {
+        FipsStatus::Unvalidated // not fips approved
+    }
+}
+
+/// A `MessageEncrypter` for AES-GCM AEAD ciphersuites. TLS 1.2 only.
+struct GcmMessageEncrypter<A> {
+    enc_key: A,
+    iv: Iv,
+}
+
+/// A `MessageDecrypter` for AES-GCM AEAD ciphersuites.  TLS1.2 only.
+struct GcmMessageDecrypter<A> {
+    dec_key: A,
+    dec_salt: [u8; 4],
+}
+
+const GCM_EXPLICIT_NONCE_LEN: usize = 8;
+const GCM_TAG_LEN: usize = 16;
+const GCM_OVERHEAD: usize = GCM_EXPLICIT_NONCE_LEN + GCM_TAG_LEN;
+
+impl<A: AeadCipher> MessageDecrypter for GcmMessageDecrypter<A> {
+    fn decrypt<'a>(
+        &mut self,
+        mut msg: EncodedMessage<InboundOpaque<'a>>,
+        seq: u64,
+    ) -> Result<EncodedMessage<&'a [u8]>, Error> {
+        let payload = &mut msg.payload;
+        if payload.len() < GCM_OVERHEAD {
+            return Err(Error::DecryptError);
+        }
+
+        let mut nonce = [0u8; NONCE_LEN];
+        nonce[..4].copy_from_slice(&self.dec_salt);
+        nonce[4..].copy_from_slice(&payload[..GCM_EXPLICIT_NONCE_LEN]);
+
+        let plain_len = payload.len() - GCM_OVERHEAD;
+        let aad = make_tls12_aad(seq, msg.typ, msg.version.version(), plain_len);
+
+        let (plain, tag) = payload[GCM_EXPLICIT_NONCE_LEN..].split_at_mut(plain_len);
+        self.dec_key
+            .decrypt_in_place_detached(&nonce.into(), &aad, plain, (&*tag).into())
+            .map_err(|_| Error::DecryptError)?;
+
+        if plain_len > MAX_FRAGMENT_LEN {
+            return Err(Error::PeerSentOversizedRecord);
+        }
+
+        payload.copy_within(
+            GCM_EXPLICIT_NONCE_LEN..GCM_EXPLICIT_NONCE_LEN + plain_len,
+            0,
+        );
+        payload.truncate(plain_len);
+        Ok(msg.into_plain_message())
+    }
+}
+
+impl<A: AeadCipher> MessageEncrypter for GcmMessageEncrypter<A> {
+    fn encrypt<'a>(
+        &mut self,
+        msg: EncodedMessage<OutboundPlain<'_>>,
+        seq: u64,
+        out: &'a mut [u8],
+    ) -> Result<EncodedMessage<&'a [u8]>, Error> {
+        let total_len = self.encrypted_payload_len(msg.payload.len());
+        let mut payload = EncryptBuffer::new(out, total_len)?;
+
+        let nonce = Nonce::new(&self.iv, seq).to_array::<NONCE_LEN>()?;
+        let aad = make_tls12_aad(seq, msg.typ, msg.version.encode(), msg.payload.len());
+        payload.extend_from_slice(&nonce[4..]);
+        payload.extend_from_chunks(&msg.payload);
+
+        let tag = self
+            .enc_key
+            .encrypt_in_place_detached(
+                &nonce.into(),
+                &aad,
+                &mut payload.as_mut()[GCM_EXPLICIT_NONCE_LEN..],
+            )
+            .map_err(|_| Error::EncryptError)?;
+        payload.extend_from_slice(&tag);
+
+        Ok(EncodedMessage {
+            typ: msg.typ,
+            version: msg.version,
+            payload: payload.into_written(),
+        })
+    }
+
+    fn encrypted_payload_len(&self, payload_len: usize) -> usize {
+        payload_len + GCM_EXPLICIT_NONCE_LEN + tag_len::<A>()
+    }
+}
+
+/// The RFC 7905/RFC 7539 ChaCha20Poly1305 construction.
+/// This implementation does the AAD construction required in TLS1.2.
+/// TLS1.3 uses `TLS13MessageEncrypter`.
+struct ChaCha20Poly1305MessageEncrypter {
+    enc_key: ChaCha20Poly1305,
+    enc_offset: Iv,
+}
+
+/// The RFC 7905/RFC 7539 ChaCha20Poly1305 construction.
+/// This implementation does the AAD construction required in TLS1.2.
+/// TLS1.3 uses `TLS13MessageDecrypter`.
+struct ChaCha20Poly1305MessageDecrypter {
+    dec_key: ChaCha20Poly1305,
+    dec_offset: Iv,
+}
+
+const CHACHAPOLY1305_OVERHEAD: usize = 16;
+
+impl MessageDecrypter for ChaCha20Poly1305MessageDecrypter {
+    fn decrypt<'a>(
+        &mut self,
+        mut msg: EncodedMessage<InboundOpaque<'a>>,
+        seq: u64,
+    ) -> Result<EncodedMessage<&'a [u8]>, Error> {
+        let payload = &mut msg.payload;
+        let Some(plain_len) = payload
+            .len()
+            .checked_sub(CHACHAPOLY1305_OVERHEAD)
+        else {
+            return Err(Error::DecryptError);
+        };
+
+        let nonce = Nonce::new(&self.dec_offset, seq).to_array::<NONCE_LEN>()?;
+        let aad = make_tls12_aad(seq, msg.typ, msg.version.version(), plain_len);
+
+        let (plain, tag) = payload.split_at_mut(plain_len);
+        self.dec_key
+            .decrypt_in_place_detached(&nonce.into(), &aad, plain, (&*tag).into())
+            .map_err(|_| Error::DecryptError)?;
+
+        if plain_len > MAX_FRAGMENT_LEN {
+            return Err(Error::PeerSentOversizedRecord);
+        }
+
+        payload.truncate(plain_len);
+        Ok(msg.into_plain_message())
+    }
+}
+
+impl MessageEncrypter for ChaCha20Poly1305MessageEncrypter {
+    fn encrypt<'a>(
+        &mut self,
+        msg: EncodedMessage<OutboundPlain<'_>>,
+        seq: u64,
+        out: &'a mut [u8],
+    ) -> Result<EncodedMessage<&'a [u8]>, Error> {
+        let total_len = self.encrypted_payload_len(msg.payload.len());
+        let mut payload = EncryptBuffer::new(out, total_len)?;
+
+        let nonce = Nonce::new(&self.enc_offset, seq).to_array::<NONCE_LEN>()?;
+        let aad = make_tls12_aad(seq, msg.typ, msg.version.encode(), msg.payload.len());
+        payload.extend_from_chunks(&msg.payload);
+
+        let tag = self
+            .enc_key
+            .encrypt_in_place_detached(&nonce.into(), &aad, payload.as_mut())
+            .map_err(|_| Error::EncryptError)?;
+        payload.extend_from_slice(&tag);
+
+        Ok(EncodedMessage {
+            typ: msg.typ,
+            version: msg.version,
+            payload: payload.into_written(),
+        })
+    }
+
+    fn encrypted_payload_len(&self, payload_len: usize) -> usize {
+        payload_len + CHACHAPOLY1305_OVERHEAD
+    }
+}
+
+fn gcm_iv(write_iv: &[u8], explicit: &[u8]) -> Iv {
+    debug_assert_eq!(write_iv.len(), 4);
+    debug_assert_eq!(explicit.len(), 8);
+
+    // The GCM nonce is constructed from a 32-bit 'salt' derived
+    // from the master-secret, and a 64-bit explicit part,
+    // with no specified construction.  Thanks for that.
+    //
+    // We use the same construction as TLS1.3/ChaCha20Poly1305:
+    // a starting point extracted from the key block, xored with
+    // the sequence number.
+    let mut iv = [0; NONCE_LEN];
+    iv[..4].copy_from_slice(write_iv);
+    iv[4..].copy_from_slice(explicit);
+
+    Iv::new(&iv).expect("IV length is NONCE_LEN, which is within MAX_LEN")
+}
+
+const MAX_FRAGMENT_LEN: usize = 16384;
+
+#[cfg(test)]
+mod tests {
+    use rustls::crypto::hmac::Hmac;
+    use rustls::crypto::tls12::prf;
+
+    use crate::hmac;
+
+    // Below known answer tests come from https://mailarchive.ietf.org/arch/msg/tls/fzVCzk-z3FShgGJ6DOXqM1ydxms/
+
+    #[test]
+    fn check_sha256() {
+        let secret = b"\x9b\xbe\x43\x6b\xa9\x40\xf0\x17\xb1\x76\x52\x84\x9a\x71\xdb\x35";
+        let seed = b"\xa0\xba\x9f\x93\x6c\xda\x31\x18\x27\xa6\xf7\x96\xff\xd5\x19\x8c";
+        let label = b"test label";
+        let expect = include_bytes!("test-data/prf-result.1.bin");
+        let mut output = [0u8; 100];
+
+        prf(
+            &mut output,
+            &*hmac::HMAC_SHA256.with_key(secret),
+            label,
+            seed,
+        );
+        assert_eq!(expect.to_vec(), output.to_vec());
+    }
+
+    #[test]
+    fn check_sha512() {
+        let secret = b"\xb0\x32\x35\x23\xc1\x85\x35\x99\x58\x4d\x88\x56\x8b\xbb\x05\xeb";
+        let seed = b"\xd4\x64\x0e\x12\xe4\xbc\xdb\xfb\x43\x7f\x03\xe6\xae\x41\x8e\xe5";
+        let label = b"test label";
+        let expect = include_bytes!("test-data/prf-result.2.bin");
+        let mut output = [0u8; 196];
+
+        prf(
+            &mut output,
+            &*hmac::HMAC_SHA512.with_key(secret),
+            label,
+            seed,
+        );
+        assert_eq!(expect.to_vec(), output.to_vec());
+    }
+
+    #[test]
+    fn check_sha384() {
+        let secret = b"\xb8\x0b\x73\x3d\x6c\xee\xfc\xdc\x71\x56\x6e\xa4\x8e\x55\x67\xdf";
+        let seed = b"\xcd\x66\x5c\xf6\xa8\x44\x7d\xd6\xff\x8b\x27\x55\x5e\xdb\x74\x65";
+        let label = b"test label";
+        let expect = include_bytes!("test-data/prf-result.3.bin");
+        let mut output = [0u8; 148];
+
+        prf(
+            &mut output,
+            &*hmac::HMAC_SHA384.with_key(secret),
+            label,
+            seed,
+        );
+        assert_eq!(expect.to_vec(), output.to_vec());
+    }
+}
@@ -0,0 +1,241 @@
+use alloc::boxed::Box;
+use core::marker::PhantomData;
+
+use aes_gcm::aead::consts::U12;
+use aes_gcm::aead::generic_array::typenum::Unsigned;
+use aes_gcm::aead::{AeadCore, AeadInPlace, KeyInit, KeySizeUser};
+use aes_gcm::{Aes128Gcm, Aes256Gcm};
+use chacha20poly1305::ChaCha20Poly1305;
+use pki_types::FipsStatus;
+use rustls::crypto::CipherSuite;
+use rustls::crypto::cipher::{
+    AeadKey, EncodedMessage, EncryptBuffer, InboundOpaque, Iv, MessageDecrypter, MessageEncrypter,
+    Nonce, OutboundPlain, Tls13AeadAlgorithm, UnsupportedOperationError, make_tls13_aad,
+};
+use rustls::crypto::tls13::HkdfUsingHmac;
+use rustls::enums::ContentType;
+use rustls::error::Error;
+use rustls::version::TLS13_VERSION;
+use rustls::{CipherSuiteCommon, ConnectionTrafficSecrets, Tls13CipherSuite};
+
+/// The TLS1.3 cipher suite configuration that an application should use by default.
+///
+/// This will be [`ALL_TLS13_CIPHER_SUITES`] sans any supported cipher suites that
+/// shouldn't be enabled by most applications.
+pub static DEFAULT_TLS13_CIPHER_SUITES: &[&Tls13CipherSuite] = ALL_TLS13_CIPHER_SUITES;
+
+/// A list of all the TLS1.3 cipher suites supported by this provider.
+pub static ALL_TLS13_CIPHER_SUITES: &[&Tls13CipherSuite] = &[
+    TLS13_AES_128_GCM_SHA256,
+    TLS13_AES_256_GCM_SHA384,
+    TLS13_CHACHA20_POLY1305_SHA256,
+];
+
+/// The TLS1.3 ciphersuite TLS_CHACHA20_POLY1305_SHA256
+pub static TLS13_CHACHA20_POLY1305_SHA256: &Tls13CipherSuite = &Tls13CipherSuite {
+    common: CipherSuiteCommon {
+        suite: CipherSuite::TLS13_CHACHA20_POLY1305_SHA256,
+        hash_provider: &super::hash::SHA256,
+        // ref: <https://www.ietf.org/archive/id/draft-irtf-cfrg-aead-limits-08.html#section-5.2.1>
+        confidentiality_limit: u64::MAX,
+    },
+    protocol_version: TLS13_VERSION,
+    hkdf_provider: &HkdfUsingHmac(&super::hmac::HMAC_SHA256),
+    aead_alg: &Aead::<ChaCha20Poly1305>(PhantomData),
+    quic: None,
+};
+
+/// The TLS1.3 ciphersuite TLS_AES_256_GCM_SHA384
+pub static TLS13_AES_256_GCM_SHA384: &Tls13CipherSuite = &Tls13CipherSuite {
+    common: CipherSuiteCommon {
+        suite: CipherSuite::TLS13_AES_256_GCM_SHA384,
+        hash_provider: &super::hash::SHA384,
+        confidentiality_limit: 1 << 24,
+    },
+    protocol_version: TLS13_VERSION,
+    hkdf_provider: &HkdfUsingHmac(&super::hmac::HMAC_SHA384),
+    aead_alg: &Aead::<Aes256Gcm>(PhantomData),
+    quic: None,
+};
+
+/// The TLS1.3 ciphersuite TLS_AES_128_GCM_SHA256
+pub static TLS13_AES_128_GCM_SHA256: &Tls13CipherSuite = &Tls13CipherSuite {
+    common: CipherSuiteCommon {
+        suite: CipherSuite::TLS13_AES_128_GCM_SHA256,
+        hash_provider: &super::hash::SHA256,
+        confidentiality_limit: 1 << 24,
+    },
+    protocol_version: TLS13_VERSION,
+    hkdf_provider: &HkdfUsingHmac(&super::hmac::HMAC_SHA256),
+    aead_alg: &Aead::<Aes128Gcm>(PhantomData),
+    quic: None,
+};
+
+/// A RustCrypto AEAD algorithm, and how its keys are exported.
+pub(crate) trait AeadCipher:
+    AeadInPlace<NonceSize = U12> + KeyInit + Send + Sync + 'static
+{
+    fn traffic_secrets(key: AeadKey, iv: Iv) -> ConnectionTrafficSecrets;
+
+    fn fips() -> FipsStatus;
+}
+
+impl AeadCipher for Aes128Gcm {
+    fn traffic_secrets(key: AeadKey, iv: Iv) -> ConnectionTrafficSecrets {
+        ConnectionTrafficSecrets::Aes128Gcm { key, iv }
+    }
+
+    fn fips() -> FipsStatus {
+        super::fips()
+    }
+}
+
+impl AeadCipher for Aes256Gcm {
+    fn traffic_secrets(key: AeadKey, iv: Iv) -> ConnectionTrafficSecrets {
+        ConnectionTrafficSecrets::Aes256Gcm { key, iv }
+    }
+
+    fn fips() -> FipsStatus {
+        super::fips()
+    }
+}
+
+impl AeadCipher for ChaCha20Poly1305 {
+    fn traffic_secrets(key: AeadKey, iv: Iv) -> ConnectionTrafficSecrets {
+        ConnectionTrafficSecrets::Chacha20Poly1305 { key, iv }
+    }
+
+    fn fips() -> FipsStatus {
+        FipsStatus::Unvalidated // chacha20poly1305 not FIPS approved
+    }
+}
+
+/// Construct an AEAD instance from `key`.
+///
+/// The caller arranges that `key` is `key_len()` in bytes, so this cannot fail.
+pub(crate) fn aead_key<A: AeadCipher>(key: &[u8]) -> A {
+    A::new_from_slice(key).expect("key length validated by key_len")
+}
+
+pub(crate) fn tag_len<A: AeadCipher>() -> usize {
+    <A as AeadCore>::TagSize::USIZE
+}
+
+struct Aead<A>(PhantomData<fn() -> A>);
+
+impl<A: AeadCipher> Tls13AeadAlgorithm for Aead<A> {
+    fn encrypter(&self, key: AeadKey, iv: Iv) -> Box<dyn MessageEncrypter> {
+        Box::new(Tls13MessageEncrypter {
+            enc_key: aead_key::<A>(key.as_ref()),
+            iv,
+        })
+    }
+
+    fn decrypter(&self, key: AeadKey, iv: Iv) -> Box<dyn MessageDecrypter> {
+        Box::new(Tls13MessageDecrypter {
+            dec_key: aead_key::<A>(key.as_ref()),
+            iv,
+        })
+    }
+
+    fn key_len(&self) -> usize {
+        <A as KeySizeUser>::key_size()
+    }
+
+    fn extract_keys(
+        &self,
+        key: AeadKey,
+        iv: Iv,
+    ) -> Result<ConnectionTrafficSecrets, UnsupportedOperationError> {
+        Ok(A::traffic_secrets(key, iv))
+    }
+
+    fn fips(&self) -> FipsStatus {
+        A::fips()
+    }
+}
+
+struct Tls13MessageEncrypter<A> {
+    enc_key: A,
+    iv: Iv,
+}
+
+struct Tls13MessageDecrypter<A> {
+    dec_key: A,
+    iv: Iv,
+}
+
+impl<A: AeadCipher> MessageEncrypter for Tls13MessageEncrypter<A> {
+    fn encrypt<'a>(
+        &mut self,
+        msg: EncodedMessage<OutboundPlain<'_>>,
+        seq: u64,
+        out: &'a mut [u8],
+    ) -> Result<EncodedMessage<&'a [u8]>, Error> {
+        self.encrypt_padded(msg, 0, seq, out)
+    }
+
+    fn encrypt_padded<'a>(
+        &mut self,
+        msg: EncodedMessage<OutboundPlain<'_>>,
+        padding: usize,
+        seq: u64,
+        out: &'a mut [u8],
+    ) -> Result<EncodedMessage<&'a [u8]>, Error> {
+        let total_len = self.encrypted_payload_len(msg.payload.len() + padding);
+        let mut payload = EncryptBuffer::new(out, total_len)?;
+
+        let typ = ContentType::ApplicationData;
+        let nonce = Nonce::new(&self.iv, seq).to_array::<12>()?;
+        let aad = make_tls13_aad(typ, msg.version.encode(), total_len);
+        payload.extend_from_chunks(&msg.payload);
+        payload.extend_from_slice(&msg.typ.to_array());
+        payload.extend_zeroed(padding);
+
+        let tag = self
+            .enc_key
+            .encrypt_in_place_detached(&nonce.into(), &aad, payload.as_mut())
+            .map_err(|_| Error::EncryptError)?;
+        payload.extend_from_slice(&tag);
+
+        Ok(EncodedMessage {
+            typ,
+            version: msg.version,
+            payload: payload.into_written(),
+        })
+    }
+
+    fn encrypted_payload_len(&self, payload_len: usize) -> usize {
+        payload_len + 1 + tag_len::<A>()
+    }
+
+    fn supports_padding(&self) -> bool {
+        true
+    }
+}
+
+impl<A: AeadCipher> MessageDecrypter for Tls13MessageDecrypter<A> {
+    fn decrypt<'a>(
+        &mut self,
+        mut msg: EncodedMessage<InboundOpaque<'a>>,
+        seq: u64,
+    ) -> Result<EncodedMessage<&'a [u8]>, Error> {
+        let payload = &mut msg.payload;
+        let Some(plain_len) = payload
+            .len()
+            .checked_sub(tag_len::<A>())
+        else {
+            return Err(Error::DecryptError);
+        };
+
+        let nonce = Nonce::new(&self.iv, seq).to_array::<12>()?;
+        let aad = make_tls13_aad(msg.typ, msg.version.version(), payload.len());
+        let (plain, tag) = payload.split_at_mut(plain_len);
+        self.dec_key
+            .decrypt_in_place_detached(&nonce.into(), &aad, plain, (&*tag).into())
+            .map_err(|_| Error::DecryptError)?;
+
+        payload.truncate(plain_len);
+        msg.into_tls13_unpadded_message()
+    }
+}
@@ -0,0 +1,349 @@
+use p256::ecdsa::signature::Verifier;
+use p256::ecdsa::signature::hazmat::PrehashVerifier;
+use pki_types::{AlgorithmIdentifier, InvalidSignature, SignatureVerificationAlgorithm, alg_id};
+use rsa::pkcs1::der::Decode;
+use rsa::traits::SignatureScheme as _;
+use rsa::{BigUint, Pkcs1v15Sign, Pss, RsaPublicKey};
+use rustls::crypto::{SignatureScheme, WebPkiSupportedAlgorithms};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+/// A `WebPkiSupportedAlgorithms` value that reflects the signature verification
+/// algorithms supported by this provider.
+pub(crate) static SUPPORTED_SIG_ALGS: WebPkiSupportedAlgorithms =
+    match WebPkiSupportedAlgorithms::new(
+        &[
+            ECDSA_P256_SHA256,
+            ECDSA_P256_SHA384,
+            ECDSA_P384_SHA256,
+            ECDSA_P384_SHA384,
+            ED25519,
+            RSA_PSS_2048_8192_SHA256_LEGACY_KEY,
+            RSA_PSS_2048_8192_SHA384_LEGACY_KEY,
+            RSA_PSS_2048_8192_SHA512_LEGACY_KEY,
+            RSA_PKCS1_2048_8192_SHA256,
+            RSA_PKCS1_2048_8192_SHA384,
+            RSA_PKCS1_2048_8192_SHA512,
+            RSA_PKCS1_2048_8192_SHA256_ABSENT_PARAMS,
+            RSA_PKCS1_2048_8192_SHA384_ABSENT_PARAMS,
+            RSA_PKCS1_2048_8192_SHA512_ABSENT_PARAMS,
+        ],
+        &[
+            // Note: for TLS1.2 the curve is not fixed by SignatureScheme. For TLS1.3 it is.
+            (
+                SignatureScheme::ECDSA_NISTP384_SHA384,
+                &[ECDSA_P384_SHA384, ECDSA_P256_SHA384],
+            ),
+            (
+                SignatureScheme::ECDSA_NISTP256_SHA256,
+                &[ECDSA_P256_SHA256, ECDSA_P384_SHA256],
+            ),
+            (SignatureScheme::ED25519, &[ED25519]),
+            (
+                SignatureScheme::RSA_PSS_SHA512,
+                &[RSA_PSS_2048_8192_SHA512_LEGACY_KEY],
+            ),
+            (
+                SignatureScheme::RSA_PSS_SHA384,
+                &[RSA_PSS_2048_8192_SHA384_LEGACY_KEY],
+            ),
+            (
+                SignatureScheme::RSA_PSS_SHA256,
+                &[RSA_PSS_2048_8192_SHA256_LEGACY_KEY],
+            ),
+            (
+                SignatureScheme::RSA_PKCS1_SHA512,
+                &[RSA_PKCS1_2048_8192_SHA512],
+            ),
+            (
+                SignatureScheme::RSA_PKCS1_SHA384,
+                &[RSA_PKCS1_2048_8192_SHA384],
+            ),
+            (
+                SignatureScheme::RSA_PKCS1_SHA256,
+                &[RSA_PKCS1_2048_8192_SHA256],
+            ),
+        ],
+    ) {
+        Ok(algs) => algs,
+        Err(_) => panic!("bad WebPkiSupportedAlgorithms"),
+    };
+
+/// An array of all the verification algorithms exported by this crate.
+pub static ALL_VERIFICATION_ALGS: &[&dyn SignatureVerificationAlgorithm] = &[
+    ECDSA_P256_SHA256,
+    ECDSA_P256_SHA384,
+    ECDSA_P384_SHA256,
+    ECDSA_P384_SHA384,
+    ED25519,
+    RSA_PKCS1_2048_8192_SHA256,
+    RSA_PKCS1_2048_8192_SHA384,
+    RSA_PKCS1_2048_8192_SHA512,
+    RSA_PKCS1_2048_8192_SHA256_ABSENT_PARAMS,
+    RSA_PKCS1_2048_8192_SHA384_ABSENT_PARAMS,
+    RSA_PKCS1_2048_8192_SHA512_ABSENT_PARAMS,
+    RSA_PKCS1_3072_8192_SHA384,
+    RSA_PSS_2048_8192_SHA256_LEGACY_KEY,
+    RSA_PSS_2048_8192_SHA384_LEGACY_KEY,
+    RSA_PSS_2048_8192_SHA512_LEGACY_KEY,
+];
+
+/// A `SignatureVerificationAlgorithm` implemented using the RustCrypto crates.
+#[derive(Debug)]
+struct RustCryptoAlgorithm {
+    public_key_alg_id: AlgorithmIdentifier,
+    signature_alg_id: AlgorithmIdentifier,
+    verify: VerifyFn,
+}
+
+/// Verify `signature` over `message` using `public_key`, returning `None` on failure.
+type VerifyFn = fn(public_key: &[u8], message: &[u8], signature: &[u8]) -> Option<()>;
+
+impl SignatureVerificationAlgorithm for RustCryptoAlgorithm {
+    fn public_key_alg_id(&self) -> AlgorithmIdentifier {
+        self.public_key_alg_id
+    }
+
+    fn signature_alg_id(&self) -> AlgorithmIdentifier {
+        self.signature_alg_id
+    }
+
+    fn verify_signature(
+        &self,
+        public_key: &[u8],
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<(), InvalidSignature> {
+        (self.verify)(public_key, message, signature).ok_or(InvalidSignature)
+    }
+}
+
+/// ECDSA signatures using the P-256 curve and SHA-256.
+pub static ECDSA_P256_SHA256: &dyn SignatureVerificationAlgorithm = &RustCryptoAlgorithm {
+    public_key_alg_id: alg_id::ECDSA_P256,
+    signature_alg_id: alg_id::ECDSA_SHA256,
+    verify: |public_key, message, signature| {
+        verify_p256(public_key, &Sha256::digest(message), signature)
+    },
+};
+
+/// ECDSA signatures using the P-256 curve and SHA-384. Deprecated.
+pub static ECDSA_P256_SHA384: &dyn SignatureVerificationAlgorithm = &RustCryptoAlgorithm {
+    public_key_alg_id: alg_id::ECDSA_P256,
+    signature_alg_id: alg_id::ECDSA_SHA384,
+    verify: |public_key, message, signature| {
+        verify_p256(public_key, &Sha384::digest(message), signature)
+    },
+};
+
+/// ECDSA signatures using the P-384 curve and SHA-256. Deprecated.
+pub static ECDSA_P384_SHA256: &dyn SignatureVerificationAlgorithm = &RustCryptoAlgorithm {
+    public_key_alg_id: alg_id::ECDSA_P384,
+    signature_alg_id: alg_id::ECDSA_SHA256,
+    verify: |public_key, message, signature| {
+        verify_p384(public_key, &Sha256::digest(message), signature)
+    },
+};
+
+/// ECDSA signatures using the P-384 curve and SHA-384.
+pub static ECDSA_P384_SHA384: &dyn SignatureVerificationAlgorithm = &RustCryptoAlgorithm {
+    public_key_alg_id: alg_id::ECDSA_P384,
+    signature_alg_id: alg_id::ECDSA_SHA384,
+    verify: |public_key, message, signature| {
+        verify_p384(public_key, &Sha384::digest(message), signature)
+    },
+};
+
+/// RSA PKCS#1 1.5 signatures using SHA-256 for keys of 2048-8192 bits.
+pub static RSA_PKCS1_2048_8192_SHA256: &dyn SignatureVerificationAlgorithm = &RustCryptoAlgorithm {
+    public_key_alg_id: alg_id::RSA_ENCRYPTION,
+    signature_alg_id: alg_id::RSA_PKCS1_SHA256,
+    verify: verify_rsa_pkcs1::<Sha256, 2048>,
+};
+
+/// RSA PKCS#1 1.5 signatures using SHA-384 for keys of 2048-8192 bits.
+pub static RSA_PKCS1_2048_8192_SHA384: &dyn SignatureVerificationAlgorithm = &RustCryptoAlgorithm {
+    public_key_alg_id: alg_id::RSA_ENCRYPTION,
+    signature_alg_id: alg_id::RSA_PKCS1_SHA384,
+    verify: verify_rsa_pkcs1::<Sha384, 2048>,
+};
+
+/// RSA PKCS#1 1.5 signatures using SHA-512 for keys of 2048-8192 bits.
+pub static RSA_PKCS1_2048_8192_SHA512: &dyn SignatureVerificationAlgorithm = &RustCryptoAlgorithm {
+    public_key_alg_id: alg_id::RSA_ENCRYPTION,
+    signature_alg_id: alg_id::RSA_PKCS1_SHA512,
+    verify: verify_rsa_pkcs1::<Sha512, 2048>,
+};
+
+/// RSA PKCS#1 1.5 signatures using SHA-256 for keys of 2048-8192 bits,
+/// with illegally absent AlgorithmIdentifier parameters.
+///
+/// RFC 4055 says on sha256WithRSAEncryption and company:
+///
+/// >   When any of these four object identifiers appears within an
+/// >   AlgorithmIdentifier, the parameters MUST be NULL.  Implementations
+/// >   MUST accept the parameters being absent as well as present.
+///
+/// This algorithm covers the absent case, [`RSA_PKCS1_2048_8192_SHA256`] covers
+/// the present case.
+pub static RSA_PKCS1_2048_8192_SHA256_ABSENT_PARAMS: &dyn SignatureVerificationAlgorithm =
+    &RustCryptoAlgorithm {
+        public_key_alg_id: alg_id::RSA_ENCRYPTION,
+        signature_alg_id: AlgorithmIdentifier::from_slice(include_bytes!(
+            "data/alg-rsa-pkcs1-sha256-absent-params.der"
+        )),
+        verify: verify_rsa_pkcs1::<Sha256, 2048>,
+    };
+
+/// RSA PKCS#1 1.5 signatures using SHA-384 for keys of 2048-8192 bits,
+/// with illegally absent AlgorithmIdentifier parameters.
+///
+/// RFC 4055 says on sha256WithRSAEncryption and company:
+///
+/// >   When any of these four object identifiers appears within an
+/// >   AlgorithmIdentifier, the parameters MUST be NULL.  Implementations
+/// >   MUST accept the parameters being absent as well as present.
+///
+/// This algorithm covers the absent case, [`RSA_PKCS1_2048_8192_SHA384`] covers
+/// the present case.
+pub static RSA_PKCS1_2048_8192_SHA384_ABSENT_PARAMS: &dyn SignatureVerificationAlgorithm =
+    &RustCryptoAlgorithm {
+        public_key_alg_id: alg_id::RSA_ENCRYPTION,
+        signature_alg_id: AlgorithmIdentifier::from_slice(include_bytes!(
+            "data/alg-rsa-pkcs1-sha384-absent-params.der"
+        )),
+        verify: verify_rsa_pkcs1::<Sha384, 2048>,
+    };
+
+/// RSA PKCS#1 1.5 signatures using SHA-512 for keys of 2048-8192 bits,
+/// with illegally absent AlgorithmIdentifier parameters.
+///
+/// RFC 4055 says on sha256WithRSAEncryption and company:
+///
+/// >   When any of these four object identifiers appears within an
+/// >   AlgorithmIdentifier, the parameters MUST be NULL.  Implementations
+/// >   MUST accept the parameters being absent as well as present.
+///
+/// This algorithm covers the absent case, [`RSA_PKCS1_2048_8192_SHA512`] covers
+/// the present case.
+pub static RSA_PKCS1_2048_8192_SHA512_ABSENT_PARAMS: &dyn SignatureVerificationAlgorithm =
+    &RustCryptoAlgorithm {
+        public_key_alg_id: alg_id::RSA_ENCRYPTION,
+        signature_alg_id: AlgorithmIdentifier::from_slice(include_bytes!(
+            "data/alg-rsa-pkcs1-sha512-absent-params.der"
+        )),
+        verify: verify_rsa_pkcs1::<Sha512, 2048>,
+    };
+
+/// RSA PKCS#1 1.5 signatures using SHA-384 for keys of 3072-8192 bits.
+pub static RSA_PKCS1_3072_8192_SHA384: &dyn SignatureVerificationAlgorithm = &RustCryptoAlgorithm {
+    public_key_alg_id: alg_id::RSA_ENCRYPTION,
+    signature_alg_id: alg_id::RSA_PKCS1_SHA384,
+    verify: verify_rsa_pkcs1::<Sha384, 3072>,
+};
+
+/// RSA PSS signatures using SHA-256 for keys of 2048-8192 bits and of
+/// type rsaEncryption; see [RFC 4055 Section 1.2].
+///
+/// [RFC 4055 Section 1.2]: https://tools.ietf.org/html/rfc4055#section-1.2
+pub static RSA_PSS_2048_8192_SHA256_LEGACY_KEY: &dyn SignatureVerificationAlgorithm =
+    &RustCryptoAlgorithm {
+        public_key_alg_id: alg_id::RSA_ENCRYPTION,
+        signature_alg_id: alg_id::RSA_PSS_SHA256,
+        verify: verify_rsa_pss::<Sha256>,
+    };
+
+/// RSA PSS signatures using SHA-384 for keys of 2048-8192 bits and of
+/// type rsaEncryption; see [RFC 4055 Section 1.2].
+///
+/// [RFC 4055 Section 1.2]: https://tools.ietf.org/html/rfc4055#section-1.2
+pub static RSA_PSS_2048_8192_SHA384_LEGACY_KEY: &dyn SignatureVerificationAlgorithm =
+    &RustCryptoAlgorithm {
+        public_key_alg_id: alg_id::RSA_ENCRYPTION,
+        signature_alg_id: alg_id::RSA_PSS_SHA384,
+        verify: verify_rsa_pss::<Sha384>,
+    };
+
+/// RSA PSS signatures using SHA-512 for keys of 2048-8192 bits and of
+/// type rsaEncryption; see [RFC 4055 Section 1.2].
+///
+/// [RFC 4055 Section 1.2]: https://tools.ietf.org/html/rfc4055#section-1.2
+pub static RSA_PSS_2048_8192_SHA512_LEGACY_KEY: &dyn SignatureVerificationAlgorithm =
+    &RustCryptoAlgorithm {
+        public_key_alg_id: alg_id::RSA_ENCRYPTION,
+        signature_alg_id: alg_id::RSA_PSS_SHA512,
+        verify: verify_rsa_pss::<Sha512>,
+    };
+
+/// ED25519 signatures according to RFC 8410
+pub static ED25519: &dyn SignatureVerificationAlgorithm = &RustCryptoAlgorithm {
+    public_key_alg_id: alg_id::ED25519,
+    signature_alg_id: alg_id::ED25519,
+    verify: |public_key, message, signature| {
+        let public_key =
+            ed25519_dalek::VerifyingKey::from_bytes(public_key.try_into().ok()?).ok()?;
+        let signature = ed25519_dalek::Signature::from_slice(signature).ok()?;
+        public_key
+            .verify(message, &signature)
+            .ok()
+    },
+};
+
+fn verify_p256(public_key: &[u8], prehash: &[u8], signature: &[u8]) -> Option<()> {
+    let public_key = p256::ecdsa::VerifyingKey::from_sec1_bytes(public_key).ok()?;
+    let signature = p256::ecdsa::Signature::from_der(signature).ok()?;
+    public_key
+        .verify_prehash(prehash, &signature)
+        .ok()
+}
+
+fn verify_p384(public_key: &[u8], prehash: &[u8], signature: &[u8]) -> Option<()> {
+    let public_key = p384::ecdsa::VerifyingKey::from_sec1_bytes(public_key).ok()?;
+    let signature = p384::ecdsa::Signature::from_der(signature).ok()?;
+    public_key
+        .verify_prehash(prehash, &signature)
+        .ok()
+}
+
+fn verify_rsa_pkcs1<D, const MIN_BITS: usize>(
+    public_key: &[u8],
+    message: &[u8],
+    signature: &[u8],
+) -> Option<()>
+where
+    D: Digest + rsa::pkcs8::AssociatedOid,
+{
+    Pkcs1v15Sign::new::<D>()
+        .verify(
+            &rsa_public_key(public_key, MIN_BITS)?,
+            &D::digest(message),
+            signature,
+        )
+        .ok()
+}
+
+fn verify_rsa_pss<D>(public_key: &[u8], message: &[u8], signature: &[u8]) -> Option<()>
+where
+    D: Digest + rsa::signature::digest::DynDigest + Send + Sync + 'static,
+{
+    Pss::new::<D>()
+        .verify(
+            &rsa_public_key(public_key, 2048)?,
+            &D::digest(message),
+            signature,
+        )
+        .ok()
+}
+
+/// Parse a PKCS#1 `RSAPublicKey`, requiring a modulus of between `min_bits` and 8192 bits.
+fn rsa_public_key(der: &[u8], min_bits: usize) -> Option<RsaPublicKey> {
+    let key = rsa::pkcs1::RsaPublicKey::from_der(der).ok()?;
+    let n = BigUint::from_bytes_be(key.modulus.as_bytes());
+    if n.bits() < min_bits {
+        return None;
+    }
+
+    let e = BigUint::from_bytes_be(key.public_exponent.as_bytes());
+    RsaPublicKey::new_with_max_size(n, e, MAX_RSA_MODULUS_BITS).ok()
+}
+
+const MAX_RSA_MODULUS_BITS: usize = 8192;
@@ -0,0 +1,645 @@
+//! A conformance test-kit for `CryptoProvider` implementations.
+//!
+//! [`ConformanceTest`] takes any [`CryptoProvider`] and checks it against itself and
+//! each of the reference providers enabled in this crate (see [`reference_providers()`]).
+//! For every peer, and with the provider under test acting as both client and server,
+//! it covers:
+//!
+//! - every cipher suite × key exchange group × signature scheme × protocol version
+//!   that both sides support, with mutual authentication and application data
+//!   exchanged in both directions;
+//! - stateful and ticket-based resumption;
+//! - TLS1.3 key updates; and
+//! - keying material exporters.
+//!
+//! The [`provider_conformance_tests!`] macro expands to one `#[test]` per area above,
+//! which is the simplest way to run this against a custom provider:
+//!
+//! ```ignore
+//! rustls_test::provider_conformance_tests!(my_hsm_provider::provider());
+//! ```
+//!
+//! [`provider_conformance_tests!`]: crate::provider_conformance_tests
+
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::Arc;
+
+use rustls::crypto::kx::NamedGroup;
+use rustls::crypto::{
+    CipherSuite, Credentials, CryptoProvider, SignatureScheme, Signer, SigningKey, SingleCredential,
+};
+use rustls::enums::ProtocolVersion;
+use rustls::pki_types::SubjectPublicKeyInfoDer;
+use rustls::{
+    ClientConfig, ClientConnection, Connection, HandshakeKind, ServerConfig, ServerConnection,
+    SupportedCipherSuite, VecInput,
+};
+
+use crate::{
+    ClientConfigExt, KeyType, do_handshake_until_error, make_pair_for_arc_configs, transfer,
+    webpki_client_verifier_builder,
+};
+
+/// Expands to a set of `#[test]` functions that check `$provider` with [`ConformanceTest`].
+///
+/// `$provider` is an expression yielding a [`CryptoProvider`]; it is evaluated once per test.
+///
+/// [`ConformanceTest`]: crate::conformance::ConformanceTest
+/// [`CryptoProvider`]: rustls::crypto::CryptoProvider
+#[macro_export]
+macro_rules! provider_conformance_tests {
+    ($provider:expr) => {
+        #[test]
+        fn conformance_tls12_handshakes() {
+            $crate::conformance::ConformanceTest::new($provider)
+                .check_handshakes(::rustls::enums::ProtocolVersion::TLSv1_2);
+        }
+
+        #[test]
+        fn conformance_tls13_handshakes() {
+            $crate::conformance::ConformanceTest::new($provider)
+                .check_handshakes(::rustls::enums::ProtocolVersion::TLSv1_3);
+        }
+
+        #[test]
+        fn conformance_resumption() {
+            $crate::conformance::ConformanceTest::new($provider).check_resumption();
+        }
+
+        #[test]
+        fn conformance_key_update() {
+            $crate::conformance::ConformanceTest::new($provider).check_key_update();
+        }
+
+        #[test]
+        fn conformance_exporters() {
+            $crate::conformance::ConformanceTest::new($provider).check_exporters();
+        }
+    };
+}
+
+/// Return the reference providers enabled by this crate's features, with their names.
+pub fn reference_providers() -> Vec<(&'static str, CryptoProvider)> {
+    Vec::from([
+        #[cfg(feature = "aws-lc-rs")]
+        ("aws-lc-rs", rustls_aws_lc_rs::DEFAULT_PROVIDER),
+        #[cfg(feature = "ring")]
+        ("ring", rustls_ring::DEFAULT_PROVIDER),
+    ])
+}
+
+/// Checks a provider against itself and a set of peer providers.
+pub struct ConformanceTest {
+    provider: CryptoProvider,
+    peers: Vec<(&'static str, CryptoProvider)>,
+}
+
+impl ConformanceTest {
+    /// Test `provider` against itself and the [`reference_providers()`].
+    pub fn new(provider: CryptoProvider) -> Self {
+        Self {
+            provider,
+            peers: reference_providers(),
+        }
+    }
+
+    /// Also test against `peer`, referred to as `name` in case descriptions.
+    pub fn with_peer(mut self, name: &'static str, peer: CryptoProvider) -> Self {
+        self.peers.push((name, peer));
+        self
+    }
+
+    /// Enumerate every handshake for `version` that the provider and its peers have in common.
+    ///
+    /// A provider that does not support `version` yields no cases.
+    pub fn handshake_cases(&self, version: ProtocolVersion) -> Vec<HandshakeCase> {
+        let mut cases = vec![];
+        for (peer, peer_provider, roles) in self.pairings() {
+            let schemes = SIGNATURE_SCHEMES
+                .iter()
+                .filter(|(scheme, key_type)| {
+                    supports_scheme(&self.provider, *scheme, *key_type)
+                        && supports_scheme(peer_provider, *scheme, *key_type)
+                })
+                .collect::<Vec<_>>();
+
+            for suite in common_suites(&self.provider, peer_provider, version) {
+                for kx in common_kx_groups(&self.provider, peer_provider, suite) {
+                    for &&(scheme, key_type) in &schemes {
+                        if !usable_scheme(scheme, suite) {
+                            continue;
+                        }
+
+                        for &provider_role in roles {
+                            cases.push(HandshakeCase {
+                                version,
+                                suite: suite.suite(),
+                                kx,
+                                scheme,
+                                key_type,
+                                peer,
+                                provider_role,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        cases
+    }
+
+    /// Complete every handshake in [`Self::handshake_cases()`] for `version`.
+    ///
+    /// Each handshake uses mutual authentication, and checks the negotiated parameters
+    /// before exchanging application data in both directions.
+    pub fn check_handshakes(&self, version: ProtocolVersion) {
+        for case in self.handshake_cases(version) {
+            let (client_config, server_config) = self.configs_for(&case);
+            let mut pair = Pair::new(&client_config, &server_config, format!("{case}"));
+            pair.handshake();
+
+            assert_eq!(pair.client.protocol_version(), Some(version), "{case}");
+            assert_eq!(pair.server.protocol_version(), Some(version), "{case}");
+            for negotiated in [
+                pair.client.negotiated_cipher_suite(),
+                pair.server.negotiated_cipher_suite(),
+            ] {
+                assert_eq!(negotiated.map(|s| s.suite()), Some(case.suite), "{case}");
+            }
+            for negotiated in [
+                pair.client
+                    .negotiated_key_exchange_group(),
+                pair.server
+                    .negotiated_key_exchange_group(),
+            ] {
+                assert_eq!(negotiated.map(|g| g.name()), Some(case.kx), "{case}");
+            }
+
+            pair.exchange_data();
+        }
+    }
+
+    /// Check that sessions resume, both with server-side state and with tickets.
+    ///
+    /// This is done once per cipher suite, for each peer and role.
+    pub fn check_resumption(&self) {
+        for case in self.per_suite_cases() {
+            let ticket_provider = match case.provider_role {
+                Role::Server | Role::Both => &self.provider,
+                Role::Client => self.peer_provider(case.peer),
+            };
+
+            for stateless in [false, true] {
+                let kind = match stateless {
+                    true => "ticket",
+                    false => "stateful",
+                };
+
+                // fresh configs each time, so the client's session cache starts empty
+                let (client_config, mut server_config) = self.configs_for(&case);
+                if stateless {
+                    Arc::make_mut(&mut server_config).ticketer = Some(
+                        ticket_provider
+                            .ticketer_factory
+                            .ticketer()
+                            .expect("ticketer unavailable"),
+                    );
+                }
+
+                let label = format!("{kind}: {case}");
+                let mut first = Pair::new(&client_config, &server_config, label.clone());
+                first.handshake();
+                assert_eq!(
+                    first.client.handshake_kind(),
+                    Some(HandshakeKind::Full),
+                    "{kind}: {case}"
+                );
+                // deliver any tickets sent after the handshake
+                first.exchange_data();
+
+                let mut second = Pair::new(&client_config, &server_config, label);
+                second.handshake();
+                assert_eq!(
+                    second.client.handshake_kind(),
+                    Some(HandshakeKind::Resumed),
+                    "{kind}: {case}"
+                );
+                assert_eq!(
+                    second.server.handshake_kind(),
+                    Some(HandshakeKind::Resumed),
+                    "{kind}: {case}"
+                );
+                second.exchange_data();
+            }
+        }
+    }
+
+    /// Check that TLS1.3 connections can update their traffic keys in each direction.
+    ///
+    /// This is done once per TLS1.3 cipher suite, for each peer and role.
+    pub fn check_key_update(&self) {
+        for case in self
+            .per_suite_cases()
+            .into_iter()
+            .filter(|case| case.version == ProtocolVersion::TLSv1_3)
+        {
+            let (client_config, server_config) = self.configs_for(&case);
+            let mut pair = Pair::new(&client_config, &server_config, format!("{case}"));
+            pair.handshake();
+            pair.exchange_data();
+
+            pair.client
+                .refresh_traffic_keys(&mut pair.client_output)
+                .unwrap_or_else(|err| panic!("{case}: client key update failed: {err:?}"));
+            pair.exchange_data();
+
+            pair.server
+                .refresh_traffic_keys(&mut pair.server_output)
+                .unwrap_or_else(|err| panic!("{case}: server key update failed: {err:?}"));
+            pair.exchange_data();
+        }
+    }
+
+    /// Check that both sides agree on exported keying material.
+    ///
+    /// This is done once per cipher suite, for each peer and role.
+    pub fn check_exporters(&self) {
+        for case in self.per_suite_cases() {
+            let (client_config, server_config) = self.configs_for(&case);
+            let mut pair = Pair::new(&client_config, &server_config, format!("{case}"));
+            pair.handshake();
+
+            let client_exporter = pair.client.exporter().unwrap();
+            let server_exporter = pair.server.exporter().unwrap();
+            for context in [None, Some(&b"context"[..])] {
+                let mut client_secret = [0u8; 64];
+                let mut server_secret = [0u8; 64];
+                client_exporter
+                    .derive(b"label", context, &mut client_secret)
+                    .unwrap();
+                server_exporter
+                    .derive(b"label", context, &mut server_secret)
+                    .unwrap();
+                assert_eq!(client_secret, server_secret, "{case}");
+            }
+        }
+    }
+
+    /// The first handshake case for each cipher suite, peer and role.
+    fn per_suite_cases(&self) -> Vec<HandshakeCase> {
+        let mut cases = self.handshake_cases(ProtocolVersion::TLSv1_3);
+        cases.extend(self.handshake_cases(ProtocolVersion::TLSv1_2));
+
+        let mut seen = vec![];
+        cases.retain(|case| {
+            let key = (case.suite, case.peer, case.provider_role);
+            match seen.contains(&key) {
+                true => false,
+                false => {
+                    seen.push(key);
+                    true
+                }
+            }
+        });
+        cases
+    }
+
+    /// The provider under test paired with itself, then with each peer in each role.
+    fn pairings(&self) -> impl Iterator<Item = (&'static str, &CryptoProvider, &'static [Role])> {
+        [("self", &self.provider, &[Role::Both][..])]
+            .into_iter()
+            .chain(
+                self.peers
+                    .iter()
+                    .map(|(name, peer)| (*name, peer, &[Role::Client, Role::Server][..])),
+            )
+    }
+
+    fn peer_provider(&self, name: &str) -> &CryptoProvider {
+        self.pairings()
+            .find(|(peer, _, _)| *peer == name)
+            .map(|(_, provider, _)| provider)
+            .unwrap()
+    }
+
+    fn configs_for(&self, case: &HandshakeCase) -> (Arc<ClientConfig>, Arc<ServerConfig>) {
+        let peer = self.peer_provider(case.peer);
+        let (client, server) = match case.provider_role {
+            Role::Client => (&self.provider, peer),
+            Role::Server => (peer, &self.provider),
+            Role::Both => (&self.provider, &self.provider),
+        };
+        let client = case.restrict(client);
+        let server = case.restrict(server);
+
+        let client_key = SingleSchemeKey::load(&client, case.key_type.client_key(), case.scheme);
+        let client_config = ClientConfig::builder(client.into())
+            .add_root_certs(case.key_type)
+            .with_client_credential_resolver(Arc::new(SingleCredential::from(
+                Credentials::new(case.key_type.client_identity(), client_key).unwrap(),
+            )))
+            .unwrap();
+
+        let server_key = SingleSchemeKey::load(&server, case.key_type.key(), case.scheme);
+        let client_verifier =
+            webpki_client_verifier_builder(case.key_type.client_root_store(), &server)
+                .build()
+                .unwrap();
+        let server_config = ServerConfig::builder(server.into())
+            .with_client_cert_verifier(Arc::new(client_verifier))
+            .with_server_credential_resolver(Arc::new(SingleCredential::from(
+                Credentials::new(case.key_type.identity(), server_key).unwrap(),
+            )))
+            .unwrap();
+
+        (Arc::new(client_config), Arc::new(server_config))
+    }
+}
+
+/// One handshake in a [`ConformanceTest`].
+#[derive(Clone, Debug)]
+pub struct HandshakeCase {
+    /// The protocol version to negotiate.
+    pub version: ProtocolVersion,
+    /// The cipher suite to negotiate.
+    pub suite: CipherSuite,
+    /// The key exchange group to negotiate.
+    pub kx: NamedGroup,
+    /// The signature scheme used by both the client and server.
+    pub scheme: SignatureScheme,
+    /// The key type of both the client and server certificates.
+    pub key_type: KeyType,
+    /// The name of the peer provider.
+    pub peer: &'static str,
+    /// Which side(s) of the connection use the provider under test.
+    pub provider_role: Role,
+}
+
+impl HandshakeCase {
+    /// Reduce `provider` to just the parameters of this case.
+    fn restrict(&self, provider: &CryptoProvider) -> CryptoProvider {
+        let suite = find_suite(provider, self.suite).unwrap();
+        let kx = provider
+            .kx_groups
+            .iter()
+            .find(|group| group.name() == self.kx)
+            .copied()
+            .unwrap();
+
+        CryptoProvider {
+            tls12_cipher_suites: match suite {
+                SupportedCipherSuite::Tls12(suite) => Cow::Owned(vec![suite]),
+                _ => Cow::Borrowed(&[]),
+            },
+            tls13_cipher_suites: match suite {
+                SupportedCipherSuite::Tls13(suite) => Cow::Owned(vec![suite]),
+                _ => Cow::Borrowed(&[]),
+            },
+            kx_groups: Cow::Owned(vec![kx]),
+            ..provider.clone()
+        }
+    }
+}
+
+impl fmt::Display for HandshakeCase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} {:?} {:?} {:?} ({:?} keys) provider as {:?} against {}",
+            self.version,
+            self.suite,
+            self.kx,
+            self.scheme,
+            self.key_type,
+            self.provider_role,
+            self.peer
+        )
+    }
+}
+
+/// Which side(s) of a connection use the provider under test.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Role {
+    /// The provider under test is the client; the peer is the server.
+    Client,
+    /// The provider under test is the server; the peer is the client.
+    Server,
+    /// The provider under test is used on both sides.
+    Both,
+}
+
+/// A connected client and server, with the buffers between them.
+struct Pair {
+    client: ClientConnection,
+    server: ServerConnection,
+    client_input: VecInput,
+    client_output: Vec<u8>,
+    server_input: VecInput,
+    server_output: Vec<u8>,
+    /// Describes the case being checked, for failure messages.
+    label: String,
+}
+
+impl Pair {
+    fn new(
+        client_config: &Arc<ClientConfig>,
+        server_config: &Arc<ServerConfig>,
+        label: String,
+    ) -> Self {
+        let mut client_output = Vec::new();
+        let (client, server) =
+            make_pair_for_arc_configs(client_config, server_config, &mut client_output);
+        Self {
+            client,
+            server,
+            client_input: VecInput::default(),
+            client_output,
+            server_input: VecInput::default(),
+            server_output: Vec::new(),
+            label,
+        }
+    }
+
+    fn handshake(&mut self) {
+        if let Err(err) = do_handshake_until_error(
+            &mut self.client_input,
+            &mut self.client_output,
+            &mut self.client,
+            &mut self.server_input,
+            &mut self.server_output,
+            &mut self.server,
+        ) {
+            panic!("{}: handshake failed: {err:?}", self.label);
+        }
+    }
+
+    /// Send application data from the client to the server, and vice versa.
+    fn exchange_data(&mut self) {
+        let label = &self.label;
+        self.client
+            .write_tls(b"to-server".into(), &mut self.client_output)
+            .unwrap_or_else(|err| panic!("{label}: client write failed: {err:?}"));
+        self.server
+            .write_tls(b"to-client".into(), &mut self.server_output)
+            .unwrap_or_else(|err| panic!("{label}: server write failed: {err:?}"));
+
+        transfer(&mut self.client_output, &mut self.server_input);
+        let mut received = Vec::new();
+        self.server
+            .process_new_packets(&mut self.server_input, &mut self.server_output)
+            .handle_all(&mut received)
+            .unwrap_or_else(|err| panic!("{label}: server read failed: {err:?}"));
+        assert_eq!(received, b"to-server", "{label}");
+
+        transfer(&mut self.server_output, &mut self.client_input);
+        let mut received = Vec::new();
+        self.client
+            .process_new_packets(&mut self.client_input, &mut self.client_output)
+            .handle_all(&mut received)
+            .unwrap_or_else(|err| panic!("{label}: client read failed: {err:?}"));
+        assert_eq!(received, b"to-client", "{label}");
+    }
+}
+
+/// A [`SigningKey`] that will only sign with one [`SignatureScheme`].
+///
+/// This forces the peer to verify that scheme, whatever else it and this key support.
+#[derive(Debug)]
+struct SingleSchemeKey {
+    key: Box<dyn SigningKey>,
+    scheme: SignatureScheme,
+}
+
+impl SingleSchemeKey {
+    fn load(
+        provider: &CryptoProvider,
+        key: rustls::pki_types::PrivateKeyDer<'static>,
+        scheme: SignatureScheme,
+    ) -> Box<dyn SigningKey> {
+        Box::new(Self {
+            key: provider
+                .key_provider
+                .load_private_key(key)
+                .unwrap(),
+            scheme,
+        })
+    }
+}
+
+impl SigningKey for SingleSchemeKey {
+    fn choose_scheme(&self, offered: &[SignatureScheme]) -> Option<Box<dyn Signer>> {
+        match offered.contains(&self.scheme) {
+            true => self.key.choose_scheme(&[self.scheme]),
+            false => None,
+        }
+    }
+
+    fn public_key(&self) -> Option<SubjectPublicKeyInfoDer<'_>> {
+        self.key.public_key()
+    }
+}
+
+fn common_suites(
+    provider: &CryptoProvider,
+    peer: &CryptoProvider,
+    version: ProtocolVersion,
+) -> Vec<SupportedCipherSuite> {
+    let suites: Vec<SupportedCipherSuite> = match version {
+        ProtocolVersion::TLSv1_2 => provider
+            .tls12_cipher_suites
+            .iter()
+            .map(|suite| SupportedCipherSuite::Tls12(suite))
+            .collect(),
+        ProtocolVersion::TLSv1_3 => provider
+            .tls13_cipher_suites
+            .iter()
+            .map(|suite| SupportedCipherSuite::Tls13(suite))
+            .collect(),
+        _ => vec![],
+    };
+
+    suites
+        .into_iter()
+        .filter(|suite| find_suite(peer, suite.suite()).is_some())
+        .collect()
+}
+
+fn common_kx_groups(
+    provider: &CryptoProvider,
+    peer: &CryptoProvider,
+    suite: SupportedCipherSuite,
+) -> Vec<NamedGroup> {
+    provider
+        .kx_groups
+        .iter()
+        .map(|group| group.name())
+        .filter(|name| match suite {
+            SupportedCipherSuite::Tls12(suite) => {
+                name.usable_for_version(ProtocolVersion::TLSv1_2)
+                    && name.key_exchange_algorithm() == suite.kx
+            }
+            _ => true,
+        })
+        .filter(|name| {
+            peer.kx_groups
+                .iter()
+                .any(|group| group.name() == *name)
+        })
+        .collect()
+}
+
+fn find_suite(provider: &CryptoProvider, suite: CipherSuite) -> Option<SupportedCipherSuite> {
+    provider
+        .tls13_cipher_suites
+        .iter()
+        .map(|s| SupportedCipherSuite::Tls13(s))
+        .chain(
+            provider
+                .tls12_cipher_suites
+                .iter()
+                .map(|s| SupportedCipherSuite::Tls12(s)),
+        )
+        .find(|s| s.suite() == suite)
+}
+
+/// Whether `scheme` may be used for handshake signatures with `suite`.
+fn usable_scheme(scheme: SignatureScheme, suite: SupportedCipherSuite) -> bool {
+    match suite {
+        SupportedCipherSuite::Tls12(suite) => suite.sign.contains(&scheme),
+        // RSA-PKCS1 is not permitted for TLS1.3 handshake signatures.
+        _ => !matches!(
+            scheme,
+            SignatureScheme::RSA_PKCS1_SHA256
+                | SignatureScheme::RSA_PKCS1_SHA384
+                | SignatureScheme::RSA_PKCS1_SHA512
+        ),
+    }
+}
+
+/// Whether `provider` can both sign and verify `scheme` using keys of `key_type`.
+fn supports_scheme(provider: &CryptoProvider, scheme: SignatureScheme, key_type: KeyType) -> bool {
+    let verifies = provider
+        .signature_verification_algorithms
+        .supported_schemes()
+        .contains(&scheme);
+    let signs = provider
+        .key_provider
+        .load_private_key(key_type.key())
+        .is_ok_and(|key| key.choose_scheme(&[scheme]).is_some());
+    verifies && signs
+}
+
+/// Each signature scheme covered, with the test key type that produces it.
+static SIGNATURE_SCHEMES: &[(SignatureScheme, KeyType)] = &[
+    (SignatureScheme::ECDSA_NISTP256_SHA256, KeyType::EcdsaP256),
+    (SignatureScheme::ECDSA_NISTP384_SHA384, KeyType::EcdsaP384),
+    (SignatureScheme::ECDSA_NISTP521_SHA512, KeyType::EcdsaP521),
+    (SignatureScheme::ED25519, KeyType::Ed25519),
+    (SignatureScheme::RSA_PSS_SHA256, KeyType::Rsa2048),
+    (SignatureScheme::RSA_PSS_SHA384, KeyType::Rsa2048),
+    (SignatureScheme::RSA_PSS_SHA512, KeyType::Rsa2048),
+    (SignatureScheme::RSA_PKCS1_SHA256, KeyType::Rsa2048),
+    (SignatureScheme::RSA_PKCS1_SHA384, KeyType::Rsa2048),
+    (SignatureScheme::RSA_PKCS1_SHA512, KeyType::Rsa2048),
+];
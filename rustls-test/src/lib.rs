@@ -2204,6 +2204,8 @@ pub fn provider_with_suites(
     }
 }
 
+pub mod conformance;
+
 pub mod macros {
     //! Macros that bring a provider into the current scope.
     //!
@@ -2445,6 +2447,7 @@ pub mod encoding {
         pub const KEY_SHARE: u16 = 0x0033;
         pub const TRANSPORT_PARAMETERS: u16 = 0x0039;
         pub const ALPN: u16 = 0x0010;
+        pub const RENEGOTIATION_INFO: u16 = 0xff01;
 
         const SOME_POINT_ON_P256: &[u8] = &[
             4, 41, 39, 177, 5, 18, 186, 227, 237, 220, 254, 70, 120, 40, 18, 139, 173, 41, 3, 38,
@@ -11,14 +11,22 @@ mod tests_with_ring {
     mod compress;
     #[path = "api/crypto.rs"]
     mod crypto;
+    #[path = "api/dtls.rs"]
+    mod dtls;
     #[path = "api/ffdhe.rs"]
     mod ffdhe;
+    #[path = "api/handoff.rs"]
+    mod handoff;
     #[path = "api/io.rs"]
     mod io;
     #[path = "api/kernel.rs"]
     mod kernel;
     #[path = "api/kx.rs"]
     mod kx;
+    #[path = "api/observer.rs"]
+    mod observer;
+    #[path = "api/qlog.rs"]
+    mod qlog;
     #[path = "api/quic.rs"]
     mod quic;
     #[path = "api/raw_keys.rs"]
@@ -46,14 +54,22 @@ mod tests_with_aws_lc_rs {
     mod compress;
     #[path = "api/crypto.rs"]
     mod crypto;
+    #[path = "api/dtls.rs"]
+    mod dtls;
     #[path = "api/ffdhe.rs"]
     mod ffdhe;
+    #[path = "api/handoff.rs"]
+    mod handoff;
     #[path = "api/io.rs"]
     mod io;
     #[path = "api/kernel.rs"]
     mod kernel;
     #[path = "api/kx.rs"]
     mod kx;
+    #[path = "api/observer.rs"]
+    mod observer;
+    #[path = "api/qlog.rs"]
+    mod qlog;
     #[path = "api/quic.rs"]
     mod quic;
     #[path = "api/raw_keys.rs"]
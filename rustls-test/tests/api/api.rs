@@ -16,7 +16,9 @@ use rustls::crypto::{
     CipherSuite, Credentials, CryptoProvider, Identity, InconsistentKeys, SelectedCredential,
     SignatureScheme, Signer, SigningKey,
 };
-use rustls::enums::{ApplicationProtocol, ContentType, HandshakeType, ProtocolVersion};
+use rustls::enums::{
+    ApplicationProtocol, ApplicationSettings, ContentType, HandshakeType, ProtocolVersion,
+};
 use rustls::error::{AlertDescription, ApiMisuse, CertificateError, Error, PeerMisbehaved};
 use rustls::server::{
     ClientHello, ParsedCertificate, PreferServerOrder, ServerCredentialResolver, ServerHandshake,
@@ -180,6 +182,83 @@ fn connection_level_alpn_protocols() {
     assert_eq!(client.alpn_protocol(), Some(&ApplicationProtocol::Http11));
 }
 
+fn alps_test(
+    server_settings: Vec<ApplicationSettings>,
+    client_settings: Vec<ApplicationSettings>,
+    agreed: Option<(&[u8], &[u8])>,
+) {
+    for (client_config, server_config, expect) in MultiTest::new(provider::DEFAULT_PROVIDER) {
+        let mut server_config = Arc::unwrap_or_clone(server_config);
+        server_config.alpn_protocols =
+            vec![ApplicationProtocol::Http2, ApplicationProtocol::Http11];
+        server_config
+            .application_settings
+            .clone_from(&server_settings);
+
+        let mut client_config = Arc::unwrap_or_clone(client_config);
+        client_config.alpn_protocols =
+            vec![ApplicationProtocol::Http11, ApplicationProtocol::Http2];
+        client_config
+            .application_settings
+            .clone_from(&client_settings);
+
+        let mut client_output = Vec::new();
+        let mut server_output = Vec::new();
+        let (mut client, mut server) =
+            make_pair_for_configs(client_config, server_config, &mut client_output);
+        do_handshake(
+            &mut VecInput::default(),
+            &mut client_output,
+            &mut client,
+            &mut VecInput::default(),
+            &mut server_output,
+            &mut server,
+        );
+
+        // ALPS is only available in TLS1.3
+        let agreed = agreed.filter(|_| expect.version == ProtocolVersion::TLSv1_3);
+        assert_eq!(client.alpn_protocol(), Some(&ApplicationProtocol::Http2));
+        assert_eq!(
+            client.peer_application_settings(),
+            agreed.map(|(from_server, _)| from_server)
+        );
+        assert_eq!(
+            server.peer_application_settings(),
+            agreed.map(|(_, from_client)| from_client)
+        );
+    }
+}
+
+#[test]
+fn application_settings() {
+    let h2 =
+        |settings: &[u8]| ApplicationSettings::new(ApplicationProtocol::Http2, settings.to_vec());
+    let http11 =
+        |settings: &[u8]| ApplicationSettings::new(ApplicationProtocol::Http11, settings.to_vec());
+
+    // no support
+    alps_test(vec![], vec![], None);
+
+    // server support
+    alps_test(vec![h2(b"server")], vec![], None);
+
+    // client support
+    alps_test(vec![], vec![h2(b"client")], None);
+
+    // exchanged for the selected protocol
+    alps_test(
+        vec![h2(b"server"), http11(b"other")],
+        vec![h2(b"client")],
+        Some((b"server", b"client")),
+    );
+
+    // empty settings are still exchanged
+    alps_test(vec![h2(b"")], vec![h2(b"")], Some((b"", b"")));
+
+    // settings only for an unselected protocol
+    alps_test(vec![http11(b"server")], vec![http11(b"client")], None);
+}
+
 #[test]
 fn server_selects_unoffered_alpn_checked() {
     let result = unoffered_alpn_test(true);
@@ -1878,6 +1957,88 @@ fn test_illegal_server_renegotiation_attempt_after_tls12_handshake() {
     );
 }
 
+#[test]
+fn test_allowed_server_renegotiation_attempt_after_tls12_handshake() {
+    let provider = provider::DEFAULT_TLS12_PROVIDER;
+    let mut client_config = make_client_config(KeyType::default(), &provider);
+    client_config.max_renegotiations = 1;
+    let mut server_config = make_server_config(KeyType::default(), &provider);
+    server_config.enable_secret_extraction = true;
+
+    let mut client_output = Vec::new();
+    let mut server_output = Vec::new();
+    let (mut client, mut server) =
+        make_pair_for_configs(client_config, server_config, &mut client_output);
+    let mut client_input = VecInput::default();
+    let mut server_input = VecInput::default();
+    do_handshake(
+        &mut client_input,
+        &mut client_output,
+        &mut client,
+        &mut server_input,
+        &mut server_output,
+        &mut server,
+    );
+
+    let mut raw_server = RawTls::new_server(server);
+
+    let msg = EncodedMessage {
+        typ: ContentType::Handshake,
+        version: EncodableVersion::Legacy(ProtocolVersion::TLSv1_2),
+        payload: Payload::new(encoding::handshake_framing(
+            HandshakeType::HelloRequest,
+            vec![],
+        )),
+    };
+
+    // the client starts a new handshake, protected by the current keys
+    raw_server.encrypt_and_send(&msg, &mut client_input);
+    client
+        .process_new_packets(&mut client_input, &mut client_output)
+        .handle_all(&mut Vec::new())
+        .unwrap();
+    raw_server.receive_and_decrypt(&mut client_output, |m| {
+        assert_eq!(m.version.version(), ProtocolVersion::TLSv1_2);
+        assert_eq!(m.typ, ContentType::Handshake);
+        assert_eq!(m.payload[0], u8::from(HandshakeType::ClientHello));
+    });
+
+    // further requests are refused until it completes
+    raw_server.encrypt_and_send(&msg, &mut client_input);
+    client
+        .process_new_packets(&mut client_input, &mut client_output)
+        .handle_all(&mut Vec::new())
+        .unwrap();
+    raw_server.receive_and_decrypt(&mut client_output, |m| {
+        assert_eq!(m.typ, ContentType::Alert);
+        assert_eq!(m.payload, &[0x01, 100]); // Warning=1, NoRenegotiation=100
+    });
+}
+
+#[test]
+fn test_server_rejects_renegotiation_info_in_initial_client_hello() {
+    let provider = provider::DEFAULT_TLS12_PROVIDER;
+    let server_config = Arc::new(make_server_config(KeyType::Rsa2048, &provider));
+    let mut server_output = Vec::new();
+    let mut server = ServerConnection::new(server_config).unwrap();
+
+    let mut input = encoding::message_framing(
+        ContentType::Handshake,
+        ProtocolVersion::TLSv1_2,
+        encoding::basic_client_hello(vec![encoding::Extension {
+            typ: encoding::Extension::RENEGOTIATION_INFO,
+            body: encoding::len_u8(vec![0u8; 12]),
+        }]),
+    );
+    assert_eq!(
+        server
+            .process_new_packets(&mut SliceInput::new(&mut input), &mut server_output)
+            .handle_all(&mut Vec::new())
+            .unwrap_err(),
+        Error::PeerMisbehaved(PeerMisbehaved::IncorrectRenegotiationInfo)
+    );
+}
+
 #[test]
 fn test_illegal_client_renegotiation_attempt_after_tls13_handshake() {
     let provider = provider::DEFAULT_TLS13_PROVIDER;
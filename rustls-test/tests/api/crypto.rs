@@ -768,6 +768,124 @@ fn tls12_connection_fails_after_key_reaches_confidentiality_limit() {
     }
 }
 
+#[test]
+fn test_automatic_refresh_traffic_keys_mid_write() {
+    // Each record of a long write is 64 bytes on the wire: header, 42 bytes of
+    // content, content type and tag.
+    const RECORD_LEN: usize = 64;
+    const RECORD_CONTENT_LEN: usize = RECORD_LEN - 5 - 1 - 16;
+    const KEY_UPDATE_LEN: usize = 5 + 5 + 1 + 16;
+
+    let provider = aes_128_gcm_with_1024_confidentiality_limit(provider::DEFAULT_PROVIDER);
+    let mut client_config = ClientConfig::builder(provider.clone()).finish(KeyType::default());
+    client_config.max_fragment_size = Some(RECORD_LEN);
+    let server_config = ServerConfig::builder(provider).finish(KeyType::default());
+
+    let (mut client, mut server, mut client_output, mut server_input) =
+        handshake_pair(client_config, server_config);
+
+    // Leave two records before the limit, which falls within the next write.
+    for _ in 0..CONFIDENTIALITY_LIMIT - 2 {
+        client
+            .write_tls(b"x".into(), &mut client_output)
+            .unwrap();
+    }
+    transfer(&mut client_output, &mut server_input);
+    server
+        .process_new_packets(&mut server_input, &mut Vec::new())
+        .handle_all(&mut Vec::new())
+        .unwrap();
+
+    let message = [0x5a; 4 * RECORD_CONTENT_LEN];
+    client
+        .write_tls(message.as_slice().into(), &mut client_output)
+        .unwrap();
+    assert_eq!(
+        transfer(&mut client_output, &mut server_input),
+        4 * RECORD_LEN + KEY_UPDATE_LEN
+    );
+
+    let mut received = Vec::new();
+    server
+        .process_new_packets(&mut server_input, &mut Vec::new())
+        .handle_all(&mut received)
+        .unwrap();
+    assert_eq!(received, message);
+}
+
+#[test]
+fn tls12_connection_closes_at_confidentiality_limit_mid_write() {
+    // Each record of a long write is 64 bytes on the wire: header, explicit nonce,
+    // 35 bytes of content and tag.
+    const RECORD_LEN: usize = 64;
+    const RECORD_CONTENT_LEN: usize = RECORD_LEN - 5 - 8 - 16;
+
+    let provider = Arc::new(CryptoProvider {
+        tls13_cipher_suites: Default::default(),
+        ..Arc::unwrap_or_clone(aes_128_gcm_with_1024_confidentiality_limit(
+            provider::DEFAULT_PROVIDER,
+        ))
+    });
+
+    let kt = KeyType::EcdsaP256;
+    let mut client_config = ClientConfig::builder(provider.clone()).finish(kt);
+    client_config.max_fragment_size = Some(RECORD_LEN);
+    let server_config = ServerConfig::builder(provider).finish(kt);
+
+    let (mut client, mut server, mut client_output, mut server_input) =
+        handshake_pair(client_config, server_config);
+
+    // Leave two records before the limit, which falls within the next write.
+    for _ in 0..CONFIDENTIALITY_LIMIT - 3 {
+        client
+            .write_tls(b"x".into(), &mut client_output)
+            .unwrap();
+    }
+    transfer(&mut client_output, &mut server_input);
+    server
+        .process_new_packets(&mut server_input, &mut Vec::new())
+        .handle_all(&mut Vec::new())
+        .unwrap();
+
+    // The records up to the limit are sent, followed by a `close_notify`.
+    let message = [0x5a; 4 * RECORD_CONTENT_LEN];
+    client
+        .write_tls(message.as_slice().into(), &mut client_output)
+        .unwrap();
+    transfer(&mut client_output, &mut server_input);
+
+    let mut received = Vec::new();
+    let state = server
+        .process_new_packets(&mut server_input, &mut Vec::new())
+        .handle_all(&mut received)
+        .unwrap();
+    assert_eq!(received, &message[..2 * RECORD_CONTENT_LEN]);
+    assert!(state.peer_has_closed());
+}
+
+/// Complete a handshake, returning the connections, the client's output and the
+/// server's input.
+fn handshake_pair(
+    client_config: ClientConfig,
+    server_config: ServerConfig,
+) -> (ClientConnection, ServerConnection, Vec<u8>, VecInput) {
+    let mut client_output = Vec::new();
+    let mut server_output = Vec::new();
+    let (mut client, mut server) =
+        make_pair_for_configs(client_config, server_config, &mut client_output);
+    let mut client_input = VecInput::default();
+    let mut server_input = VecInput::default();
+    do_handshake(
+        &mut client_input,
+        &mut client_output,
+        &mut client,
+        &mut server_input,
+        &mut server_output,
+        &mut server,
+    );
+    (client, server, client_output, server_input)
+}
+
 #[test]
 fn test_keys_match_for_all_signing_key_types() {
     let provider = provider::DEFAULT_PROVIDER;
@@ -0,0 +1,330 @@
+//! Tests for DTLS
+
+#![allow(clippy::disallowed_types, clippy::duplicate_mod)]
+
+use core::iter;
+use core::time::Duration;
+use std::sync::Arc;
+use std::time::Instant;
+
+use rustls::HandshakeKind;
+use rustls::dtls::{self, Connection};
+use rustls::enums::ProtocolVersion;
+use rustls::error::ApiMisuse;
+use rustls::{ClientConfig, ServerConfig};
+use rustls_test::{KeyType, make_client_config, make_server_config, server_name};
+
+use super::provider;
+
+#[test]
+fn dtls_handshake_and_data() {
+    let (client_config, server_config) = configs();
+    let mut pair = Pair::new(client_config, server_config);
+    pair.handshake(|_| false);
+
+    for conn in [&*pair.client as &dyn Connection, &*pair.server] {
+        assert!(!conn.is_handshaking());
+        assert_eq!(conn.protocol_version(), Some(ProtocolVersion::DTLSv1_3));
+        assert_eq!(
+            conn.handshake_kind(),
+            Some(HandshakeKind::FullWithHelloRetryRequest)
+        );
+    }
+
+    pair.client.send(b"hello").unwrap();
+    pair.server.send(b"world").unwrap();
+    pair.exchange(|_| false);
+    assert_eq!(pair.server.recv().as_deref(), Some(&b"hello"[..]));
+    assert_eq!(pair.client.recv().as_deref(), Some(&b"world"[..]));
+    assert_eq!(pair.client.recv(), None);
+
+    pair.client.send_close_notify();
+    pair.exchange(|_| false);
+    assert!(pair.server.peer_has_closed());
+    assert_eq!(
+        pair.client.send(b"too late"),
+        Err(ApiMisuse::WriteTlsAfterSendPathClosed.into())
+    );
+}
+
+#[test]
+fn dtls_handshake_survives_loss() {
+    for seed in 1..=20 {
+        let (client_config, server_config) = configs();
+        let mut pair = Pair::new(client_config, server_config);
+        let mut lose = lossy(seed, 30);
+        pair.handshake(&mut lose);
+        assert!(!pair.client.is_handshaking());
+        assert!(!pair.server.is_handshaking());
+
+        pair.client.send(b"ping").unwrap();
+        pair.exchange(|_| false);
+        assert_eq!(pair.server.recv().as_deref(), Some(&b"ping"[..]));
+    }
+}
+
+#[test]
+fn dtls_retransmits_after_timeout() {
+    let (client_config, server_config) = configs();
+    let mut pair = Pair::new(client_config, server_config);
+
+    // Lose the first `ClientHello`.
+    let start = pair.now;
+    let first = pair.poll_client();
+    assert!(!first.is_empty());
+    let timeout = pair.client.poll_timeout().unwrap();
+    assert!(timeout > pair.now);
+
+    // Nothing happens before the timeout.
+    pair.client.handle_timeout(pair.now);
+    assert!(pair.poll_client().is_empty());
+
+    pair.now = timeout;
+    pair.client.handle_timeout(pair.now);
+    let second = pair.poll_client();
+    assert_eq!(first.len(), second.len());
+    assert_ne!(first, second, "retransmission must use new record numbers");
+
+    // The backoff doubles.
+    let next_timeout = pair.client.poll_timeout().unwrap();
+    assert_eq!(next_timeout - pair.now, (timeout - start) * 2);
+
+    for datagram in second {
+        pair.server
+            .read_datagram(&datagram)
+            .unwrap();
+    }
+    pair.handshake(|_| false);
+    assert!(!pair.client.is_handshaking());
+}
+
+#[test]
+fn dtls_fragments_handshake() {
+    let (client_config, server_config) = configs();
+    let mut pair = Pair::new(client_config, server_config);
+    pair.client.set_max_datagram_size(300);
+    pair.server.set_max_datagram_size(300);
+    pair.max_seen = 0;
+    pair.handshake(|_| false);
+
+    assert!(!pair.client.is_handshaking());
+    assert!(pair.max_seen <= 300);
+    assert!(pair.datagrams > 10);
+}
+
+#[test]
+fn dtls_fragments_with_loss() {
+    let (client_config, server_config) = configs();
+    let mut pair = Pair::new(client_config, server_config);
+    pair.client.set_max_datagram_size(400);
+    pair.server.set_max_datagram_size(400);
+    pair.handshake(lossy(1234, 25));
+    assert!(!pair.client.is_handshaking());
+    assert!(!pair.server.is_handshaking());
+}
+
+#[test]
+fn dtls_discards_tampered_records() {
+    let (client_config, server_config) = configs();
+    let mut pair = Pair::new(client_config, server_config);
+    pair.handshake(|_| false);
+
+    pair.client.send(b"hello").unwrap();
+    let mut datagram = pair
+        .client
+        .poll_datagram(pair.now)
+        .unwrap();
+    let last = datagram.len() - 1;
+    datagram[last] ^= 1;
+    pair.server
+        .read_datagram(&datagram)
+        .unwrap();
+    assert_eq!(pair.server.recv(), None);
+
+    // Garbage is ignored too.
+    pair.server
+        .read_datagram(&[0x2f, 0xff, 0xff])
+        .unwrap();
+    pair.server.read_datagram(&[]).unwrap();
+
+    // Replays are discarded.
+    pair.client.send(b"again").unwrap();
+    let datagram = pair
+        .client
+        .poll_datagram(pair.now)
+        .unwrap();
+    pair.server
+        .read_datagram(&datagram)
+        .unwrap();
+    pair.server
+        .read_datagram(&datagram)
+        .unwrap();
+    assert_eq!(pair.server.recv().as_deref(), Some(&b"again"[..]));
+    assert_eq!(pair.server.recv(), None);
+}
+
+#[test]
+fn dtls_resumption() {
+    let (client_config, server_config) = configs();
+    let mut pair = Pair::new(client_config.clone(), server_config.clone());
+    pair.handshake(|_| false);
+
+    let mut pair = Pair::new(client_config, server_config);
+    pair.handshake(|_| false);
+    assert_eq!(
+        pair.client.handshake_kind(),
+        Some(HandshakeKind::ResumedWithHelloRetryRequest)
+    );
+    assert_eq!(
+        pair.server.handshake_kind(),
+        Some(HandshakeKind::ResumedWithHelloRetryRequest)
+    );
+}
+
+#[test]
+fn dtls_send_before_handshake() {
+    let (client_config, server_config) = configs();
+    let mut pair = Pair::new(client_config, server_config);
+    assert_eq!(
+        pair.client.send(b"early"),
+        Err(ApiMisuse::WriteTlsBeforeHandshakeComplete.into())
+    );
+
+    pair.handshake(|_| false);
+    assert_eq!(
+        pair.client.send(&[0u8; 1200]),
+        Err(ApiMisuse::DtlsRecordTooLarge.into())
+    );
+}
+
+#[test]
+fn dtls_forbids_early_data() {
+    let (client_config, server_config) = configs();
+
+    let mut client_config = Arc::into_inner(client_config).unwrap();
+    client_config.enable_early_data = true;
+    assert_eq!(
+        dtls::ClientConnection::new(Arc::new(client_config), server_name("localhost")).err(),
+        Some(ApiMisuse::DtlsForbidsEarlyData.into())
+    );
+
+    let mut server_config = Arc::into_inner(server_config).unwrap();
+    server_config.max_early_data_size = 1234;
+    assert_eq!(
+        dtls::ServerConnection::new(Arc::new(server_config)).err(),
+        Some(ApiMisuse::DtlsForbidsEarlyData.into())
+    );
+}
+
+/// Loses roughly `percent`% of datagrams, pseudo-randomly.
+fn lossy(seed: u64, percent: u64) -> impl FnMut(usize) -> bool {
+    let mut state = seed;
+    move |_| {
+        // xorshift64
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state % 100 < percent
+    }
+}
+
+fn configs() -> (Arc<ClientConfig>, Arc<ServerConfig>) {
+    let kt = KeyType::default();
+    let provider = provider::DEFAULT_TLS13_PROVIDER;
+    (
+        Arc::new(make_client_config(kt, &provider)),
+        Arc::new(make_server_config(kt, &provider)),
+    )
+}
+
+/// A client and server, connected by an in-memory datagram channel.
+struct Pair {
+    client: Box<dtls::ClientConnection>,
+    server: Box<dtls::ServerConnection>,
+    now: Instant,
+    /// Number of datagrams sent in either direction.
+    datagrams: usize,
+    /// The largest datagram sent.
+    max_seen: usize,
+}
+
+impl Pair {
+    fn new(client_config: Arc<ClientConfig>, server_config: Arc<ServerConfig>) -> Self {
+        Self {
+            client: Box::new(
+                dtls::ClientConnection::new(client_config, server_name("localhost")).unwrap(),
+            ),
+            server: Box::new(dtls::ServerConnection::new(server_config).unwrap()),
+            now: Instant::now(),
+            datagrams: 0,
+            max_seen: 0,
+        }
+    }
+
+    /// Run the handshake to completion.
+    ///
+    /// The `n`th datagram (in either direction) is lost if `lose(n)` is true.
+    fn handshake(&mut self, mut lose: impl FnMut(usize) -> bool) {
+        for _ in 0..100 {
+            self.exchange(&mut lose);
+            if !self.client.is_handshaking()
+                && !self.server.is_handshaking()
+                && self.client.poll_timeout().is_none()
+                && self.server.poll_timeout().is_none()
+            {
+                return;
+            }
+
+            // Nothing is in flight: wait for the next retransmission.
+            let timeout = [self.client.poll_timeout(), self.server.poll_timeout()]
+                .into_iter()
+                .flatten()
+                .min()
+                .expect("handshake stalled without a timer");
+            assert!(timeout - self.now <= Duration::from_secs(60));
+            self.now = timeout;
+            self.client.handle_timeout(self.now);
+            self.server.handle_timeout(self.now);
+        }
+
+        panic!("handshake did not complete");
+    }
+
+    /// Deliver datagrams in both directions until neither side has any to send.
+    fn exchange(&mut self, mut lose: impl FnMut(usize) -> bool) {
+        loop {
+            let mut moved = false;
+            while let Some(datagram) = self.client.poll_datagram(self.now) {
+                moved = true;
+                if !self.deliver(&datagram, &mut lose) {
+                    continue;
+                }
+                self.server
+                    .read_datagram(&datagram)
+                    .unwrap();
+            }
+            while let Some(datagram) = self.server.poll_datagram(self.now) {
+                moved = true;
+                if !self.deliver(&datagram, &mut lose) {
+                    continue;
+                }
+                self.client
+                    .read_datagram(&datagram)
+                    .unwrap();
+            }
+            if !moved {
+                return;
+            }
+        }
+    }
+
+    fn poll_client(&mut self) -> Vec<Vec<u8>> {
+        iter::from_fn(|| self.client.poll_datagram(self.now)).collect()
+    }
+
+    fn deliver(&mut self, datagram: &[u8], lose: &mut impl FnMut(usize) -> bool) -> bool {
+        self.datagrams += 1;
+        self.max_seen = self.max_seen.max(datagram.len());
+        !lose(self.datagrams)
+    }
+}
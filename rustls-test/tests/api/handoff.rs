@@ -0,0 +1,365 @@
+//! Tests for serializing and restoring established connections.
+
+#![allow(clippy::disallowed_types, clippy::duplicate_mod)]
+
+use std::sync::Arc;
+
+use rustls::crypto::TicketProducer;
+use rustls::enums::ProtocolVersion;
+use rustls::error::ApiMisuse;
+use rustls::{
+    ClientConfig, ClientConnection, Connection, Error, HandshakeKind, ServerConfig,
+    ServerConnection, VecInput,
+};
+use rustls_test::{MultiTest, do_handshake, make_pair_for_arc_configs, transfer};
+
+use super::provider;
+
+#[test]
+fn serialize_and_restore() {
+    for (client_config, server_config, expect) in MultiTest::new(provider::DEFAULT_PROVIDER) {
+        let (client_config, server_config) = with_secret_extraction(client_config, server_config);
+        let sealer = sealer();
+
+        let mut client_input = VecInput::default();
+        let mut client_output = Vec::new();
+        let mut server_input = VecInput::default();
+        let mut server_output = Vec::new();
+        let (mut client, mut server) =
+            make_pair_for_arc_configs(&client_config, &server_config, &mut client_output);
+        do_handshake(
+            &mut client_input,
+            &mut client_output,
+            &mut client,
+            &mut server_input,
+            &mut server_output,
+            &mut server,
+        );
+        exchange(
+            &mut client_input,
+            &mut client_output,
+            &mut client,
+            &mut server_input,
+            &mut server_output,
+            &mut server,
+        );
+        let suite = client
+            .negotiated_cipher_suite()
+            .unwrap()
+            .suite();
+        let tickets = client.tls13_tickets_received();
+
+        let serialized = client
+            .dangerous_serialize(&*sealer)
+            .unwrap();
+        let mut client =
+            ClientConnection::dangerous_restore(client_config.clone(), &serialized, &*sealer)
+                .unwrap();
+        let serialized = server
+            .dangerous_serialize(&*sealer)
+            .unwrap();
+        let mut server =
+            ServerConnection::dangerous_restore(server_config.clone(), &serialized, &*sealer)
+                .unwrap();
+
+        // the negotiated facts survive the transition
+        assert!(!client.is_handshaking());
+        assert!(!server.is_handshaking());
+        assert_eq!(client.protocol_version(), Some(expect.version));
+        assert_eq!(server.protocol_version(), Some(expect.version));
+        assert_eq!(
+            server
+                .negotiated_cipher_suite()
+                .unwrap()
+                .suite(),
+            suite
+        );
+        assert_eq!(client.handshake_kind(), Some(HandshakeKind::Full));
+        assert!(client.peer_identity().is_some());
+        assert_eq!(server.peer_identity().is_some(), expect.client_auth);
+        assert_eq!(client.tls13_tickets_received(), tickets);
+
+        // and the connection continues from where it left off
+        for _ in 0..2 {
+            exchange(
+                &mut client_input,
+                &mut client_output,
+                &mut client,
+                &mut server_input,
+                &mut server_output,
+                &mut server,
+            );
+        }
+
+        if expect.version == ProtocolVersion::TLSv1_3 {
+            server
+                .refresh_traffic_keys(&mut server_output)
+                .unwrap();
+            exchange(
+                &mut client_input,
+                &mut client_output,
+                &mut client,
+                &mut server_input,
+                &mut server_output,
+                &mut server,
+            );
+        }
+
+        let client_exporter = client.exporter().unwrap();
+        let server_exporter = server.exporter().unwrap();
+        assert_eq!(
+            client_exporter
+                .derive(b"label", Some(b"context"), [0u8; 32])
+                .unwrap(),
+            server_exporter
+                .derive(b"label", Some(b"context"), [0u8; 32])
+                .unwrap(),
+        );
+    }
+}
+
+#[test]
+fn restored_tls13_client_accepts_tickets() {
+    let (client_config, server_config) = tls13_configs();
+    let sealer = sealer();
+
+    let mut client_input = VecInput::default();
+    let mut client_output = Vec::new();
+    let mut server_input = VecInput::default();
+    let mut server_output = Vec::new();
+    let (mut client, mut server) =
+        make_pair_for_arc_configs(&client_config, &server_config, &mut client_output);
+    client
+        .set_resumption_data(b"handoff")
+        .unwrap();
+    while client.is_handshaking() {
+        transfer(&mut client_output, &mut server_input);
+        server
+            .process_new_packets(&mut server_input, &mut server_output)
+            .handle_all(&mut Vec::new())
+            .unwrap();
+        transfer(&mut server_output, &mut client_input);
+        client
+            .process_new_packets(&mut client_input, &mut client_output)
+            .handle_all(&mut Vec::new())
+            .unwrap();
+    }
+
+    // the server sends its tickets once it receives the client's `Finished`
+    transfer(&mut client_output, &mut server_input);
+    server
+        .process_new_packets(&mut server_input, &mut server_output)
+        .handle_all(&mut Vec::new())
+        .unwrap();
+    assert!(!server.is_handshaking());
+    transfer(&mut server_output, &mut client_input);
+    assert_eq!(client.tls13_tickets_received(), 0);
+
+    // and they are delivered to the restored client
+    let serialized = client
+        .dangerous_serialize(&*sealer)
+        .unwrap();
+    let mut client =
+        ClientConnection::dangerous_restore(client_config.clone(), &serialized, &*sealer).unwrap();
+    client
+        .process_new_packets(&mut client_input, &mut client_output)
+        .handle_all(&mut Vec::new())
+        .unwrap();
+    assert!(client.tls13_tickets_received() > 0);
+
+    // and can be used to resume
+    let (mut client, mut server) =
+        make_pair_for_arc_configs(&client_config, &server_config, &mut client_output);
+    do_handshake(
+        &mut VecInput::default(),
+        &mut client_output,
+        &mut client,
+        &mut VecInput::default(),
+        &mut server_output,
+        &mut server,
+    );
+    assert_eq!(client.handshake_kind(), Some(HandshakeKind::Resumed));
+    assert_eq!(client.received_resumption_data(), Some(&b"handoff"[..]));
+}
+
+#[test]
+fn serialize_split_connection() {
+    let (client_config, server_config) = tls13_configs();
+    let sealer = sealer();
+    let (client, server) = connected_pair(&client_config, &server_config);
+
+    let serialized = client
+        .split()
+        .unwrap()
+        .dangerous_serialize(&*sealer)
+        .unwrap();
+    let mut client =
+        ClientConnection::dangerous_restore(client_config, &serialized, &*sealer).unwrap();
+    let serialized = server
+        .split()
+        .unwrap()
+        .dangerous_serialize(&*sealer)
+        .unwrap();
+    let mut server =
+        ServerConnection::dangerous_restore(server_config, &serialized, &*sealer).unwrap();
+
+    exchange(
+        &mut VecInput::default(),
+        &mut Vec::new(),
+        &mut client,
+        &mut VecInput::default(),
+        &mut Vec::new(),
+        &mut server,
+    );
+}
+
+#[test]
+fn restore_rejects_tampering() {
+    let (client_config, server_config) = tls13_configs();
+    let sealer = sealer();
+    let (client, _) = connected_pair(&client_config, &server_config);
+
+    let mut serialized = client
+        .dangerous_serialize(&*sealer)
+        .unwrap();
+    let last = serialized.len() - 1;
+    serialized[last] ^= 1;
+    assert_eq!(
+        ClientConnection::dangerous_restore(client_config, &serialized, &*sealer).err(),
+        Some(Error::DecryptError)
+    );
+}
+
+#[test]
+fn restore_rejects_other_side() {
+    let (client_config, server_config) = tls13_configs();
+    let sealer = sealer();
+    let (client, server) = connected_pair(&client_config, &server_config);
+
+    let serialized = client
+        .dangerous_serialize(&*sealer)
+        .unwrap();
+    assert_eq!(
+        ServerConnection::dangerous_restore(server_config, &serialized, &*sealer).err(),
+        Some(ApiMisuse::IncompatibleSerializedConnection.into())
+    );
+
+    let serialized = server
+        .dangerous_serialize(&*sealer)
+        .unwrap();
+    assert_eq!(
+        ClientConnection::dangerous_restore(client_config, &serialized, &*sealer).err(),
+        Some(ApiMisuse::IncompatibleSerializedConnection.into())
+    );
+}
+
+#[test]
+fn serialize_requires_secret_extraction() {
+    for (client_config, server_config, _) in MultiTest::new(provider::DEFAULT_PROVIDER) {
+        let sealer = sealer();
+        let (client, server) = connected_pair(&client_config, &server_config);
+
+        assert_eq!(
+            client
+                .dangerous_serialize(&*sealer)
+                .err(),
+            Some(ApiMisuse::SecretExtractionRequiresPriorOptIn.into())
+        );
+        assert_eq!(
+            server
+                .dangerous_serialize(&*sealer)
+                .err(),
+            Some(ApiMisuse::SecretExtractionRequiresPriorOptIn.into())
+        );
+    }
+}
+
+#[test]
+fn serialize_requires_completed_handshake() {
+    let (client_config, server_config) = tls13_configs();
+    let sealer = sealer();
+    let mut client_output = Vec::new();
+    let (client, _) = make_pair_for_arc_configs(&client_config, &server_config, &mut client_output);
+
+    assert_eq!(
+        client
+            .dangerous_serialize(&*sealer)
+            .err(),
+        Some(Error::HandshakeNotComplete)
+    );
+}
+
+fn exchange(
+    client_input: &mut VecInput,
+    client_output: &mut Vec<u8>,
+    client: &mut ClientConnection,
+    server_input: &mut VecInput,
+    server_output: &mut Vec<u8>,
+    server: &mut ServerConnection,
+) {
+    client
+        .write_tls(b"to-server".into(), client_output)
+        .unwrap();
+    transfer(client_output, server_input);
+    let mut received = Vec::new();
+    server
+        .process_new_packets(server_input, server_output)
+        .handle_all(&mut received)
+        .unwrap();
+    assert_eq!(received, b"to-server");
+
+    server
+        .write_tls(b"to-client".into(), server_output)
+        .unwrap();
+    transfer(server_output, client_input);
+    let mut received = Vec::new();
+    client
+        .process_new_packets(client_input, client_output)
+        .handle_all(&mut received)
+        .unwrap();
+    assert_eq!(received, b"to-client");
+}
+
+fn connected_pair(
+    client_config: &Arc<ClientConfig>,
+    server_config: &Arc<ServerConfig>,
+) -> (ClientConnection, ServerConnection) {
+    let mut client_output = Vec::new();
+    let (mut client, mut server) =
+        make_pair_for_arc_configs(client_config, server_config, &mut client_output);
+    do_handshake(
+        &mut VecInput::default(),
+        &mut client_output,
+        &mut client,
+        &mut VecInput::default(),
+        &mut Vec::new(),
+        &mut server,
+    );
+    (client, server)
+}
+
+fn tls13_configs() -> (Arc<ClientConfig>, Arc<ServerConfig>) {
+    let (client_config, server_config, _) = MultiTest::new(provider::DEFAULT_TLS13_PROVIDER)
+        .into_iter()
+        .next()
+        .unwrap();
+    with_secret_extraction(client_config, server_config)
+}
+
+fn with_secret_extraction(
+    client_config: Arc<ClientConfig>,
+    server_config: Arc<ServerConfig>,
+) -> (Arc<ClientConfig>, Arc<ServerConfig>) {
+    let mut client_config = Arc::unwrap_or_clone(client_config);
+    client_config.enable_secret_extraction = true;
+    let mut server_config = Arc::unwrap_or_clone(server_config);
+    server_config.enable_secret_extraction = true;
+    (Arc::new(client_config), Arc::new(server_config))
+}
+
+fn sealer() -> Arc<dyn TicketProducer> {
+    provider::DEFAULT_PROVIDER
+        .ticketer_factory
+        .ticketer()
+        .unwrap()
+}
@@ -5,17 +5,26 @@
 
 use core::fmt::Debug;
 use core::mem;
+use std::borrow::Cow;
 use std::io::{self, BufRead, IoSlice, Read, Write};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, OnceLock};
 
 use pki_types::DnsName;
+use rustls::crypto::cipher::{
+    AeadKey, EncodedMessage, Iv, MessageDecrypter, MessageEncrypter, OutboundPlain,
+    Tls13AeadAlgorithm, UnsupportedOperationError,
+};
+use rustls::crypto::{CipherSuiteCommon, CryptoProvider};
 use rustls::enums::{ContentType, HandshakeType, ProtocolVersion};
 use rustls::error::{
     AlertDescription, ApiMisuse, Error, InvalidMessage, PeerIncompatible, PeerMisbehaved,
 };
 use rustls::server::ServerHandshake;
+use rustls::split::{ReceiveTrafficState, SplitConnection};
 use rustls::{
-    ClientConfig, Connection, HandshakeKind, ServerConfig, ServerConnection, SliceInput, VecInput,
+    ClientConfig, ClientConnection, Connection, ConnectionTrafficSecrets, HandshakeKind,
+    RecordPadder, RecordPadding, ServerConfig, ServerConnection, SliceInput, Tls13CipherSuite,
+    VecInput,
 };
 use rustls_test::{
     ClientConfigExt, KeyType, MultiTest, OtherSession, ServerConfigExt, TestNonBlockIo,
@@ -1879,6 +1888,466 @@ fn test_server_mtu_reduction() {
     }
 }
 
+#[test]
+fn test_record_padding_block_size() {
+    let provider = provider::DEFAULT_TLS13_PROVIDER;
+    let mut client_config = make_client_config(KeyType::default(), &provider);
+    client_config.record_padding = RecordPadding::BlockSize(256);
+    let mut server_config = make_server_config(KeyType::default(), &provider);
+    server_config.record_padding = RecordPadding::BlockSize(256);
+
+    let mut client_output = Vec::new();
+    let mut server_output = Vec::new();
+    let (mut client, mut server) =
+        make_pair_for_configs(client_config, server_config, &mut client_output);
+    let mut client_input = VecInput::default();
+    let mut server_input = VecInput::default();
+
+    transfer(&mut client_output, &mut server_input);
+    server
+        .process_new_packets(&mut server_input, &mut server_output)
+        .handle_all(&mut Vec::new())
+        .unwrap();
+
+    // the encrypted handshake flight (including the certificate) is padded
+    let encrypted = records(&server_output)
+        .into_iter()
+        .filter(|(typ, _)| *typ == ContentType::ApplicationData)
+        .map(|(_, len)| len)
+        .collect::<Vec<_>>();
+    assert!(!encrypted.is_empty());
+    for len in encrypted {
+        assert_eq!((len - TLS13_OVERHEAD) % 256, 0, "record length {len}");
+    }
+
+    transfer(&mut server_output, &mut client_input);
+    do_handshake(
+        &mut client_input,
+        &mut client_output,
+        &mut client,
+        &mut server_input,
+        &mut server_output,
+        &mut server,
+    );
+
+    client
+        .write_tls(b"hello".as_slice().into(), &mut client_output)
+        .unwrap();
+    assert_eq!(message_lengths(&client_output), [256 + TLS13_OVERHEAD]);
+
+    transfer(&mut client_output, &mut server_input);
+    let mut received = Vec::new();
+    server
+        .process_new_packets(&mut server_input, &mut server_output)
+        .handle_all(&mut received)
+        .unwrap();
+    assert_eq!(received, b"hello");
+}
+
+#[test]
+fn test_record_padding_max_fragment() {
+    let provider = provider::DEFAULT_TLS13_PROVIDER;
+    let mut client_config = make_client_config(KeyType::default(), &provider);
+    client_config.max_fragment_size = Some(512);
+    client_config.record_padding = RecordPadding::MaxFragment;
+
+    let (mut client, mut server, mut server_input) = handshake_pair(
+        client_config,
+        make_server_config(KeyType::default(), &provider),
+    );
+
+    let mut client_output = Vec::new();
+    client
+        .write_tls(b"hello".as_slice().into(), &mut client_output)
+        .unwrap();
+    client
+        .write_tls([0u8; 600].as_slice().into(), &mut client_output)
+        .unwrap();
+    assert_eq!(message_lengths(&client_output), [512, 512, 512]);
+
+    transfer(&mut client_output, &mut server_input);
+    let mut received = Vec::new();
+    server
+        .process_new_packets(&mut server_input, &mut Vec::new())
+        .handle_all(&mut received)
+        .unwrap();
+    assert_eq!(&received[..5], b"hello");
+    assert_eq!(received.len(), 605);
+}
+
+#[test]
+fn test_record_padding_custom() {
+    #[derive(Debug, Default)]
+    struct FixedPadder {
+        types: Mutex<Vec<ContentType>>,
+    }
+
+    impl RecordPadder for FixedPadder {
+        fn padding(&self, typ: ContentType, _len: usize, limit: usize) -> usize {
+            self.types.lock().unwrap().push(typ);
+            assert!(limit > 7);
+            7
+        }
+    }
+
+    let provider = provider::DEFAULT_TLS13_PROVIDER;
+    let padder = Arc::new(FixedPadder::default());
+    let mut client_config = make_client_config(KeyType::default(), &provider);
+    client_config.record_padding = RecordPadding::Custom(padder.clone());
+
+    let (mut client, mut server, mut server_input) = handshake_pair(
+        client_config,
+        make_server_config(KeyType::default(), &provider),
+    );
+    assert_eq!(
+        mem::take(&mut *padder.types.lock().unwrap()),
+        [ContentType::Handshake]
+    );
+
+    let mut client_output = Vec::new();
+    client
+        .write_tls(b"hello".as_slice().into(), &mut client_output)
+        .unwrap();
+    assert_eq!(message_lengths(&client_output), [5 + 7 + TLS13_OVERHEAD]);
+    assert_eq!(
+        *padder.types.lock().unwrap(),
+        [ContentType::ApplicationData]
+    );
+
+    transfer(&mut client_output, &mut server_input);
+    let mut received = Vec::new();
+    server
+        .process_new_packets(&mut server_input, &mut Vec::new())
+        .handle_all(&mut received)
+        .unwrap();
+    assert_eq!(received, b"hello");
+}
+
+#[test]
+fn test_record_padding_ignored_for_tls12() {
+    let provider = provider::DEFAULT_TLS12_PROVIDER;
+    let mut client_config = make_client_config(KeyType::default(), &provider);
+    client_config.max_fragment_size = Some(512);
+    client_config.record_padding = RecordPadding::MaxFragment;
+
+    let (mut client, mut server, mut server_input) = handshake_pair(
+        client_config,
+        make_server_config(KeyType::default(), &provider),
+    );
+
+    let mut client_output = Vec::new();
+    client
+        .write_tls(b"hello".as_slice().into(), &mut client_output)
+        .unwrap();
+    let lengths = message_lengths(&client_output);
+    assert_eq!(lengths.len(), 1);
+    assert!(lengths[0] < 64);
+
+    transfer(&mut client_output, &mut server_input);
+    let mut received = Vec::new();
+    server
+        .process_new_packets(&mut server_input, &mut Vec::new())
+        .handle_all(&mut received)
+        .unwrap();
+    assert_eq!(received, b"hello");
+}
+
+#[test]
+fn test_record_padding_ignored_without_encrypter_support() {
+    let provider = no_padding_provider(provider::DEFAULT_TLS13_PROVIDER);
+    let mut client_config = make_client_config(KeyType::default(), &provider);
+    client_config.max_fragment_size = Some(512);
+    client_config.record_padding = RecordPadding::MaxFragment;
+
+    let (mut client, mut server, mut server_input) = handshake_pair(
+        client_config,
+        make_server_config(KeyType::default(), &provider),
+    );
+
+    let mut client_output = Vec::new();
+    client
+        .write_tls(b"hello".as_slice().into(), &mut client_output)
+        .unwrap();
+    let lengths = message_lengths(&client_output);
+    assert_eq!(lengths.len(), 1);
+    assert!(lengths[0] < 64);
+
+    transfer(&mut client_output, &mut server_input);
+    let mut received = Vec::new();
+    server
+        .process_new_packets(&mut server_input, &mut Vec::new())
+        .handle_all(&mut received)
+        .unwrap();
+    assert_eq!(received, b"hello");
+}
+
+/// `provider` with one TLS1.3 cipher suite, whose encrypters cannot add record padding.
+fn no_padding_provider(provider: CryptoProvider) -> CryptoProvider {
+    static AEAD: OnceLock<NoPaddingAead> = OnceLock::new();
+    static SUITE: OnceLock<Tls13CipherSuite> = OnceLock::new();
+
+    let tls13 = provider.tls13_cipher_suites[0];
+    let aead_alg = AEAD.get_or_init(|| NoPaddingAead(tls13.aead_alg));
+    let suite = SUITE.get_or_init(|| Tls13CipherSuite {
+        aead_alg,
+        common: CipherSuiteCommon { ..tls13.common },
+        ..*tls13
+    });
+
+    CryptoProvider {
+        tls13_cipher_suites: Cow::Owned(vec![suite]),
+        ..provider
+    }
+}
+
+struct NoPaddingAead(&'static dyn Tls13AeadAlgorithm);
+
+impl Tls13AeadAlgorithm for NoPaddingAead {
+    fn encrypter(&self, key: AeadKey, iv: Iv) -> Box<dyn MessageEncrypter> {
+        Box::new(NoPaddingEncrypter(self.0.encrypter(key, iv)))
+    }
+
+    fn decrypter(&self, key: AeadKey, iv: Iv) -> Box<dyn MessageDecrypter> {
+        self.0.decrypter(key, iv)
+    }
+
+    fn key_len(&self) -> usize {
+        self.0.key_len()
+    }
+
+    fn iv_len(&self) -> usize {
+        self.0.iv_len()
+    }
+
+    fn extract_keys(
+        &self,
+        key: AeadKey,
+        iv: Iv,
+    ) -> Result<ConnectionTrafficSecrets, UnsupportedOperationError> {
+        self.0.extract_keys(key, iv)
+    }
+}
+
+/// Uses the default `supports_padding()` and `encrypt_padded()`.
+struct NoPaddingEncrypter(Box<dyn MessageEncrypter>);
+
+impl MessageEncrypter for NoPaddingEncrypter {
+    fn encrypt<'a>(
+        &mut self,
+        msg: EncodedMessage<OutboundPlain<'_>>,
+        seq: u64,
+        out: &'a mut [u8],
+    ) -> Result<EncodedMessage<&'a [u8]>, Error> {
+        self.0.encrypt(msg, seq, out)
+    }
+
+    fn encrypted_payload_len(&self, payload_len: usize) -> usize {
+        self.0
+            .encrypted_payload_len(payload_len)
+    }
+}
+
+#[test]
+fn test_write_tls_into_slice() {
+    let provider = provider::DEFAULT_PROVIDER;
+    let (mut client, mut server, mut server_input) = handshake_pair(
+        make_client_config(KeyType::default(), &provider),
+        make_server_config(KeyType::default(), &provider),
+    );
+
+    let mut small = [0u8; 8];
+    let err = client
+        .write_tls(b"hello".as_slice().into(), &mut &mut small[..])
+        .unwrap_err();
+    let Error::InsufficientOutputSpace { required } = err else {
+        panic!("unexpected error {err:?}");
+    };
+    assert_eq!(small, [0u8; 8]);
+
+    // Retrying with exactly the requested space succeeds and fills it.
+    let mut tls = vec![0u8; required];
+    let mut remaining = &mut tls[..];
+    client
+        .write_tls(b"hello".as_slice().into(), &mut remaining)
+        .unwrap();
+    assert!(remaining.is_empty());
+
+    server_input
+        .read(&mut io::Cursor::new(&tls))
+        .unwrap();
+    let mut received = Vec::new();
+    server
+        .process_new_packets(&mut server_input, &mut Vec::new())
+        .handle_all(&mut received)
+        .unwrap();
+    assert_eq!(received, b"hello");
+}
+
+#[test]
+fn test_send_close_notify_into_slice() {
+    let provider = provider::DEFAULT_PROVIDER;
+    let (mut client, mut server, mut server_input) = handshake_pair(
+        make_client_config(KeyType::default(), &provider),
+        make_server_config(KeyType::default(), &provider),
+    );
+
+    let mut small = [0u8; 4];
+    let err = client
+        .send_close_notify(&mut &mut small[..])
+        .unwrap_err();
+    let Error::InsufficientOutputSpace { required } = err else {
+        panic!("unexpected error {err:?}");
+    };
+
+    let mut tls = vec![0u8; required];
+    client
+        .send_close_notify(&mut &mut tls[..])
+        .unwrap();
+    // Once sent, there is nothing more to write.
+    client
+        .send_close_notify(&mut &mut small[..])
+        .unwrap();
+
+    server_input
+        .read(&mut io::Cursor::new(&tls))
+        .unwrap();
+    let io_state = server
+        .process_new_packets(&mut server_input, &mut Vec::new())
+        .handle_all(&mut Vec::new())
+        .unwrap();
+    assert!(io_state.peer_has_closed());
+}
+
+#[test]
+fn test_handshake_into_small_slice_is_fatal() {
+    let provider = provider::DEFAULT_PROVIDER;
+    let client_config = Arc::new(make_client_config(KeyType::default(), &provider));
+    let err = client_config
+        .connect(server_name("localhost"))
+        .build(&mut &mut [0u8; 16][..])
+        .unwrap_err();
+    assert!(matches!(err, Error::InsufficientOutputSpace { .. }));
+
+    let mut client_output = Vec::new();
+    let (_client, mut server) = make_pair_for_configs(
+        make_client_config(KeyType::default(), &provider),
+        make_server_config(KeyType::default(), &provider),
+        &mut client_output,
+    );
+    let mut server_input = VecInput::default();
+    transfer(&mut client_output, &mut server_input);
+
+    let mut small = [0u8; 16];
+    let err = server
+        .process_new_packets(&mut server_input, &mut &mut small[..])
+        .handle_all(&mut Vec::new())
+        .unwrap_err();
+    let Error::InsufficientOutputSpace { required } = err else {
+        panic!("unexpected error {err:?}");
+    };
+    assert!(required > small.len());
+
+    // The error is fatal, and is reported again.
+    assert_eq!(
+        server
+            .process_new_packets(&mut server_input, &mut Vec::new())
+            .handle_all(&mut Vec::new()),
+        Err(Error::InsufficientOutputSpace { required })
+    );
+}
+
+#[test]
+fn test_handshake_stops_at_first_record_that_does_not_fit() {
+    let provider = provider::DEFAULT_TLS13_PROVIDER;
+    let server_config = Arc::new(make_server_config(KeyType::default(), &provider));
+    let mut client_output = Vec::new();
+    let (_client, mut server) = make_pair_for_arc_configs(
+        &Arc::new(make_client_config(KeyType::default(), &provider)),
+        &server_config,
+        &mut client_output,
+    );
+
+    let mut server_input = VecInput::default();
+    server_input
+        .read(&mut io::Cursor::new(&client_output))
+        .unwrap();
+    let mut flight = Vec::new();
+    server
+        .process_new_packets(&mut server_input, &mut flight)
+        .handle_all(&mut Vec::new())
+        .unwrap();
+    let lengths = message_lengths(&flight);
+    assert!(lengths.len() > 2);
+
+    // With room for just the `ServerHello`, the handshake fails when writing the
+    // next record, rather than carrying on and failing after the whole flight.
+    let mut server = ServerConnection::new(server_config).unwrap();
+    let mut server_input = VecInput::default();
+    server_input
+        .read(&mut io::Cursor::new(&client_output))
+        .unwrap();
+    let mut tls = vec![0u8; lengths[0]];
+    let err = server
+        .process_new_packets(&mut server_input, &mut &mut tls[..])
+        .handle_all(&mut Vec::new())
+        .unwrap_err();
+    assert_eq!(
+        err,
+        Error::InsufficientOutputSpace {
+            required: lengths[0] + lengths[1]
+        }
+    );
+    assert_eq!(message_lengths(&tls), &lengths[..1]);
+}
+
+#[test]
+fn test_handle_all_into_slice() {
+    let provider = provider::DEFAULT_PROVIDER;
+    let (mut client, mut server, mut server_input) = handshake_pair(
+        make_client_config(KeyType::default(), &provider),
+        make_server_config(KeyType::default(), &provider),
+    );
+
+    let mut client_output = Vec::new();
+    client
+        .write_tls(b"hello world".as_slice().into(), &mut client_output)
+        .unwrap();
+    transfer(&mut client_output, &mut server_input);
+
+    let mut received = [0u8; 5];
+    assert_eq!(
+        server
+            .process_new_packets(&mut server_input, &mut Vec::new())
+            .handle_all(&mut &mut received[..]),
+        Err(Error::InsufficientOutputSpace { required: 11 })
+    );
+}
+
+/// Record header, inner content type and AEAD tag.
+const TLS13_OVERHEAD: usize = 5 + 1 + 16;
+
+/// Complete a handshake, returning the connections and the server's input buffer.
+fn handshake_pair(
+    client_config: ClientConfig,
+    server_config: ServerConfig,
+) -> (ClientConnection, ServerConnection, VecInput) {
+    let mut client_output = Vec::new();
+    let mut server_output = Vec::new();
+    let (mut client, mut server) =
+        make_pair_for_configs(client_config, server_config, &mut client_output);
+    let mut client_input = VecInput::default();
+    let mut server_input = VecInput::default();
+    do_handshake(
+        &mut client_input,
+        &mut client_output,
+        &mut client,
+        &mut server_input,
+        &mut server_output,
+        &mut server,
+    );
+    (client, server, server_input)
+}
+
 fn check_client_max_fragment_size(size: usize) -> Option<Error> {
     let provider = provider::DEFAULT_PROVIDER;
     let mut client_config = make_client_config(KeyType::default(), &provider);
@@ -2040,6 +2509,63 @@ fn test_full_server_handshake() {
     }
 }
 
+#[test]
+fn test_server_handshake_leaves_data_after_finished() {
+    let provider = provider::DEFAULT_PROVIDER;
+    let client_config = Arc::new(make_client_config(KeyType::EcdsaP256, &provider));
+    let server_config = Arc::new(make_server_config(KeyType::EcdsaP256, &provider));
+
+    let mut buf = Vec::new();
+    let mut client = client_config
+        .connect(server_name("localhost"))
+        .build(&mut buf)
+        .unwrap();
+
+    let mut acceptor_input = VecInput::default();
+    acceptor_input
+        .read(&mut buf.as_slice())
+        .unwrap();
+    let mut server_output = vec![];
+    let ServerHandshake::Accepted(accepted) = ServerHandshake::start()
+        .process(&mut acceptor_input, &mut server_output)
+        .unwrap()
+    else {
+        panic!("unexpected state");
+    };
+    let ServerHandshake::NeedsInput(server) = accepted
+        .choose_config(server_config, &mut server_output)
+        .unwrap()
+    else {
+        panic!("unexpected state");
+    };
+
+    // client's second flight is immediately followed by application data
+    let mut client_output = vec![];
+    client
+        .process_new_packets(&mut SliceInput::new(&mut server_output), &mut client_output)
+        .handle_all(&mut Vec::new())
+        .unwrap();
+    client
+        .write_tls(b"hello".into(), &mut client_output)
+        .unwrap();
+
+    let mut client_input = SliceInput::new(&mut client_output);
+    let ServerHandshake::Complete(SplitConnection { receive, .. }) = server
+        .process(&mut client_input, &mut Vec::new())
+        .unwrap()
+    else {
+        panic!("unexpected state");
+    };
+
+    match receive
+        .read(&mut client_input, &mut Vec::new())
+        .unwrap()
+    {
+        ReceiveTrafficState::Available(mut data) => assert_eq!(data.data(), b"hello"),
+        other => panic!("unexpected state {other:?}"),
+    }
+}
+
 #[test]
 fn test_server_handshake() {
     for (client_config, server_config, _) in MultiTest::new(provider::DEFAULT_PROVIDER) {
@@ -2296,7 +2822,9 @@ fn server_close_notify() {
         client
             .write_tls(b"from-client!".into(), &mut client_output)
             .unwrap();
-        server.send_close_notify(&mut server_output);
+        server
+            .send_close_notify(&mut server_output)
+            .unwrap();
 
         transfer(&mut server_output, &mut client_input);
         let iter = client.process_new_packets(&mut client_input, &mut client_output);
@@ -2336,7 +2864,9 @@ fn client_close_notify() {
         client
             .write_tls(b"from-client!".into(), &mut client_output)
             .unwrap();
-        client.send_close_notify(&mut client_output);
+        client
+            .send_close_notify(&mut client_output)
+            .unwrap();
 
         transfer(&mut client_output, &mut server_input);
         let iter = server.process_new_packets(&mut server_input, &mut server_output);
@@ -2498,7 +3028,9 @@ fn test_complete_io_with_no_io_needed() {
     client
         .write_tls(b"hello".into(), &mut client_output)
         .unwrap();
-    client.send_close_notify(&mut client_output);
+    client
+        .send_close_notify(&mut client_output)
+        .unwrap();
     transfer(&mut client_output, &mut server_input);
     server
         .process_new_packets(&mut server_input, &mut server_output)
@@ -2507,7 +3039,9 @@ fn test_complete_io_with_no_io_needed() {
     server
         .write_tls(b"hello".into(), &mut server_output)
         .unwrap();
-    server.send_close_notify(&mut server_output);
+    server
+        .send_close_notify(&mut server_output)
+        .unwrap();
     transfer(&mut server_output, &mut client_input);
     client
         .process_new_packets(&mut client_input, &mut client_output)
@@ -2567,7 +3101,9 @@ fn test_junk_after_close_notify_received() {
     client
         .write_tls(b"hello".into(), &mut client_output)
         .unwrap();
-    client.send_close_notify(&mut client_output);
+    client
+        .send_close_notify(&mut client_output)
+        .unwrap();
 
     let mut client_buffer = mem::take(&mut client_output);
 
@@ -2612,7 +3148,9 @@ fn test_data_after_close_notify_is_ignored() {
     client
         .write_tls(b"before".into(), &mut client_output)
         .unwrap();
-    client.send_close_notify(&mut client_output);
+    client
+        .send_close_notify(&mut client_output)
+        .unwrap();
     assert_eq!(
         client
             .write_tls(b"after".into(), &mut client_output)
@@ -2672,11 +3210,15 @@ fn test_subsequent_close_notify_ignored() {
     );
     client_output.clear();
     let mut server_input = VecInput::default();
-    client.send_close_notify(&mut client_output);
+    client
+        .send_close_notify(&mut client_output)
+        .unwrap();
     assert!(transfer(&mut client_output, &mut server_input) > 0);
 
     // does nothing
-    client.send_close_notify(&mut client_output);
+    client
+        .send_close_notify(&mut client_output)
+        .unwrap();
     assert_eq!(transfer(&mut client_output, &mut server_input), 0);
 }
 
@@ -2699,7 +3241,9 @@ fn test_second_close_notify_after_handshake() {
         &mut server_output,
         &mut server,
     );
-    client.send_close_notify(&mut client_output);
+    client
+        .send_close_notify(&mut client_output)
+        .unwrap();
     assert!(transfer(&mut client_output, &mut server_input) > 0);
     server
         .process_new_packets(&mut server_input, &mut server_output)
@@ -2707,7 +3251,9 @@ fn test_second_close_notify_after_handshake() {
         .unwrap();
 
     // does nothing
-    client.send_close_notify(&mut client_output);
+    client
+        .send_close_notify(&mut client_output)
+        .unwrap();
     assert_eq!(transfer(&mut client_output, &mut server_input), 0);
 }
 
@@ -2730,7 +3276,9 @@ fn test_read_tls_artificial_eof_after_close_notify() {
         &mut server_output,
         &mut server,
     );
-    client.send_close_notify(&mut client_output);
+    client
+        .send_close_notify(&mut client_output)
+        .unwrap();
     assert!(transfer(&mut client_output, &mut server_input) > 0);
     server
         .process_new_packets(&mut server_input, &mut server_output)
@@ -2746,6 +3294,16 @@ fn test_read_tls_artificial_eof_after_close_notify() {
     );
 }
 
+fn records(mut tls: &[u8]) -> Vec<(ContentType, usize)> {
+    let mut records = Vec::new();
+    while !tls.is_empty() {
+        let length = 5 + u16::from_be_bytes([tls[3], tls[4]]) as usize;
+        records.push((ContentType::from(tls[0]), length));
+        tls = &tls[length..];
+    }
+    records
+}
+
 fn message_lengths(mut tls: &[u8]) -> Vec<usize> {
     let mut lengths = Vec::new();
     while !tls.is_empty() {
@@ -6,7 +6,7 @@
 
 use std::sync::Arc;
 
-use rustls::client::{ClientSide, Resumption};
+use rustls::client::{ClientSessionStore, ClientSide, Resumption};
 use rustls::enums::ProtocolVersion;
 use rustls::error::{ApiMisuse, InvalidMessage};
 use rustls::kernel::KernelConnection;
@@ -22,7 +22,7 @@ use super::provider;
 fn kernel_connection() {
     for (client_config, server_config, expect) in MultiTest::new(provider::DEFAULT_PROVIDER) {
         let ((client_secrets, mut client_kernel), (server_secrets, mut server_kernel)) =
-            kernel_pair(client_config, server_config);
+            kernel_pair(client_config, server_config, b"");
 
         // the negotiated facts survive the transition
         assert_eq!(client_kernel.protocol_version(), expect.version);
@@ -106,7 +106,7 @@ fn kernel_connection() {
 #[test]
 fn tls12_handle_new_session_ticket() {
     for (client_config, server_config, expect) in MultiTest::new(provider::DEFAULT_TLS12_PROVIDER) {
-        let ((_, mut client_kernel), _) = kernel_pair(client_config, server_config);
+        let ((_, mut client_kernel), _) = kernel_pair(client_config, server_config, b"");
         assert_eq!(expect.version, ProtocolVersion::TLSv1_2);
         assert_eq!(client_kernel.protocol_version(), ProtocolVersion::TLSv1_2);
         assert_eq!(
@@ -127,7 +127,8 @@ fn tls13_handle_new_session_ticket() {
         client_config.resumption = Resumption::store(storage.clone());
         let client_config = Arc::new(client_config);
 
-        let ((_, mut client_kernel), _) = kernel_pair(client_config, server_config);
+        let ((_, mut client_kernel), _) =
+            kernel_pair(client_config, server_config, b"resumption data");
 
         assert_eq!(expect.version, ProtocolVersion::TLSv1_3);
         assert_eq!(client_kernel.protocol_version(), ProtocolVersion::TLSv1_3);
@@ -163,16 +164,21 @@ fn tls13_handle_new_session_ticket() {
                   \x00\x00",
             )
             .unwrap();
-        assert!(matches!(
-            storage.ops_and_reset()[..],
-            [ClientStorageOp::InsertTls13Ticket(_)]
-        ));
+        let ops = storage.ops_and_reset();
+        let [ClientStorageOp::InsertTls13Ticket(key)] = &ops[..] else {
+            panic!("expected one ticket to be stored");
+        };
+
+        // and carries the data set before the handshake
+        let ticket = storage.take_tls13_ticket(key).unwrap();
+        assert_eq!(ticket.resumption_data(), b"resumption data");
     }
 }
 
 fn kernel_pair(
     client_config: Arc<ClientConfig>,
     server_config: Arc<ServerConfig>,
+    resumption_data: &[u8],
 ) -> (
     (ExtractedSecrets, KernelConnection<ClientSide>),
     (ExtractedSecrets, KernelConnection<ServerSide>),
@@ -187,6 +193,9 @@ fn kernel_pair(
     let mut server_output = Vec::new();
     let (mut client, mut server) =
         make_pair_for_configs(client_config, server_config, &mut client_output);
+    client
+        .set_resumption_data(resumption_data)
+        .unwrap();
     do_handshake(
         &mut VecInput::default(),
         &mut client_output,
@@ -9,17 +9,22 @@ use std::sync::Arc;
 use rustls::client::Resumption;
 use rustls::crypto::CryptoProvider;
 use rustls::crypto::kx::{
-    ActiveKeyExchange, HybridKeyExchange, NamedGroup, SharedSecret, StartedKeyExchange,
-    SupportedKxGroup,
+    ActiveKeyExchange, CompletedKeyExchange, HybridKeyExchange, NamedGroup, SharedSecret,
+    StartedKeyExchange, SupportedKxGroup,
 };
 use rustls::enums::{ContentType, ProtocolVersion};
-use rustls::error::{AlertDescription, Error, InvalidMessage, PeerIncompatible, PeerMisbehaved};
-use rustls::{ClientConfig, Connection, HandshakeKind, ServerConfig, VecInput};
+use rustls::error::{
+    AlertDescription, ApiMisuse, Error, InvalidMessage, PeerIncompatible, PeerMisbehaved,
+};
+use rustls::server::{CompleteKeyExchange, ServerHandshake};
+use rustls::{
+    ClientConfig, ClientConnection, Connection, HandshakeKind, ServerConfig, SliceInput, VecInput,
+};
 use rustls_test::{
     ClientConfigExt, ClientStorage, ClientStorageOp, ErrorFromPeer, KeyType, MultiTest,
     OtherSession, ServerConfigExt, do_handshake, do_handshake_until_error, encoding,
-    make_client_config_with_kx_groups, make_pair, make_pair_for_configs, make_server_config,
-    make_server_config_with_kx_groups, transfer,
+    make_client_config, make_client_config_with_kx_groups, make_pair, make_pair_for_configs,
+    make_server_config, make_server_config_with_kx_groups, server_name, transfer,
 };
 
 use super::provider;
@@ -595,3 +600,233 @@ impl HybridKeyExchange for FakeHybridActive {
         self
     }
 }
+
+#[test]
+fn test_server_deferred_key_exchange() {
+    for external in [false, true] {
+        let (mut client, kx) = start_deferred_key_exchange();
+        assert_eq!(kx.group(), NamedGroup::X25519);
+
+        let mut server_output = vec![];
+        let server = match external {
+            true => {
+                let ckx = provider::kx_group::X25519.start_and_complete(kx.peer_key_share());
+                kx.continue_with(ckx, &mut server_output)
+            }
+            false => kx.use_kx_group(&mut server_output),
+        }
+        .unwrap();
+        assert!(!server_output.is_empty());
+
+        let mut client_output = vec![];
+        client
+            .process_new_packets(&mut SliceInput::new(&mut server_output), &mut client_output)
+            .handle_all(&mut Vec::new())
+            .unwrap();
+
+        let ServerHandshake::NeedsInput(receive) = server else {
+            panic!("unexpected state");
+        };
+        let server = receive
+            .process(&mut SliceInput::new(&mut client_output), &mut server_output)
+            .unwrap();
+        assert!(matches!(server, ServerHandshake::Complete(_)));
+        assert!(!client.is_handshaking());
+        assert_eq!(
+            client
+                .negotiated_key_exchange_group()
+                .unwrap()
+                .name(),
+            NamedGroup::X25519
+        );
+    }
+}
+
+#[test]
+fn test_server_deferred_key_exchange_into_slice() {
+    let (mut client, kx) = start_deferred_key_exchange();
+
+    let mut buf = [0u8; 16_384];
+    let capacity = buf.len();
+    let mut unused = &mut buf[..];
+    let server = kx.use_kx_group(&mut unused).unwrap();
+    let written = capacity - unused.len();
+    assert!(written > 0);
+
+    let mut client_output = vec![];
+    client
+        .process_new_packets(
+            &mut SliceInput::new(&mut buf[..written]),
+            &mut client_output,
+        )
+        .handle_all(&mut Vec::new())
+        .unwrap();
+
+    let ServerHandshake::NeedsInput(receive) = server else {
+        panic!("unexpected state");
+    };
+    let server = receive
+        .process(&mut SliceInput::new(&mut client_output), &mut Vec::new())
+        .unwrap();
+    assert!(matches!(server, ServerHandshake::Complete(_)));
+    assert!(!client.is_handshaking());
+}
+
+#[test]
+fn test_server_deferred_key_exchange_failure() {
+    let (_, kx) = start_deferred_key_exchange();
+    let mut server_output = vec![];
+    assert_eq!(
+        kx.continue_with(
+            Err(PeerMisbehaved::InvalidKeyShare.into()),
+            &mut server_output
+        )
+        .unwrap_err(),
+        PeerMisbehaved::InvalidKeyShare.into()
+    );
+    assert_eq!(
+        server_output,
+        encoding::alert(AlertDescription::IllegalParameter, &[])
+    );
+
+    let (_, kx) = start_deferred_key_exchange();
+    let secp256r1 = provider::kx_group::SECP256R1;
+    let peer = secp256r1.start().unwrap().into_single();
+    let ckx = secp256r1.start_and_complete(peer.pub_key());
+    assert_eq!(
+        kx.continue_with(ckx, &mut Vec::new())
+            .unwrap_err(),
+        ApiMisuse::CompletedKeyExchangeGroupMismatch.into()
+    );
+}
+
+#[test]
+fn test_buffered_server_completes_deferred_key_exchange() {
+    let provider = provider::DEFAULT_PROVIDER;
+    let client_config = make_client_config(KeyType::default(), &provider);
+    let server_config =
+        make_server_config_with_kx_groups(KeyType::default(), vec![&DEFERRED_X25519], &provider);
+    let mut client_output = Vec::new();
+    let mut server_output = Vec::new();
+    let (mut client, mut server) =
+        make_pair_for_configs(client_config, server_config, &mut client_output);
+    do_handshake_until_error(
+        &mut VecInput::default(),
+        &mut client_output,
+        &mut client,
+        &mut VecInput::default(),
+        &mut server_output,
+        &mut server,
+    )
+    .unwrap();
+    assert_eq!(
+        server
+            .negotiated_key_exchange_group()
+            .unwrap()
+            .name(),
+        NamedGroup::X25519
+    );
+}
+
+#[test]
+fn test_tls12_server_does_not_defer_key_exchange() {
+    let provider = provider::DEFAULT_TLS12_PROVIDER;
+    let client_config = Arc::new(make_client_config(KeyType::default(), &provider));
+    let server_config = Arc::new(make_server_config_with_kx_groups(
+        KeyType::default(),
+        vec![&DEFERRED_X25519],
+        &provider,
+    ));
+
+    let mut client_output = vec![];
+    let mut client = client_config
+        .connect(server_name("localhost"))
+        .build(&mut client_output)
+        .unwrap();
+
+    let mut server_output = vec![];
+    let ServerHandshake::Accepted(accepted) = ServerHandshake::start()
+        .process(&mut SliceInput::new(&mut client_output), &mut server_output)
+        .unwrap()
+    else {
+        panic!("unexpected state");
+    };
+
+    // only TLS1.3 handshakes pause for the key exchange
+    let ServerHandshake::NeedsInput(receive) = accepted
+        .choose_config(server_config, &mut server_output)
+        .unwrap()
+    else {
+        panic!("unexpected state");
+    };
+    assert!(!server_output.is_empty());
+
+    client_output.clear();
+    client
+        .process_new_packets(&mut SliceInput::new(&mut server_output), &mut client_output)
+        .handle_all(&mut Vec::new())
+        .unwrap();
+    let server = receive
+        .process(&mut SliceInput::new(&mut client_output), &mut server_output)
+        .unwrap();
+    assert!(matches!(server, ServerHandshake::Complete(_)));
+    assert_eq!(client.protocol_version(), Some(ProtocolVersion::TLSv1_2));
+}
+
+/// Feed a `ClientHello` to a server whose only group defers completion.
+fn start_deferred_key_exchange() -> (ClientConnection, CompleteKeyExchange) {
+    let provider = provider::DEFAULT_PROVIDER;
+    let client_config = Arc::new(make_client_config(KeyType::default(), &provider));
+    let server_config = Arc::new(make_server_config_with_kx_groups(
+        KeyType::default(),
+        vec![&DEFERRED_X25519],
+        &provider,
+    ));
+
+    let mut client_output = vec![];
+    let client = client_config
+        .connect(server_name("localhost"))
+        .build(&mut client_output)
+        .unwrap();
+
+    let mut server_output = vec![];
+    let ServerHandshake::Accepted(accepted) = ServerHandshake::start()
+        .process(&mut SliceInput::new(&mut client_output), &mut server_output)
+        .unwrap()
+    else {
+        panic!("unexpected state");
+    };
+    let ServerHandshake::CompleteKeyExchange(kx) = accepted
+        .choose_config(server_config, &mut server_output)
+        .unwrap()
+    else {
+        panic!("unexpected state");
+    };
+
+    // nothing can be sent until the key exchange is complete
+    assert!(server_output.is_empty());
+    (client, kx)
+}
+
+static DEFERRED_X25519: DeferredKxGroup = DeferredKxGroup(provider::kx_group::X25519);
+
+#[derive(Debug)]
+struct DeferredKxGroup(&'static dyn SupportedKxGroup);
+
+impl SupportedKxGroup for DeferredKxGroup {
+    fn start(&self) -> Result<StartedKeyExchange, Error> {
+        self.0.start()
+    }
+
+    fn start_and_complete(&self, peer_pub_key: &[u8]) -> Result<CompletedKeyExchange, Error> {
+        self.0.start_and_complete(peer_pub_key)
+    }
+
+    fn name(&self) -> NamedGroup {
+        self.0.name()
+    }
+
+    fn defers_completion(&self) -> bool {
+        true
+    }
+}
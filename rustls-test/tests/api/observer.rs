@@ -0,0 +1,312 @@
+//! Tests for `ConnectionObserver`.
+
+#![allow(clippy::disallowed_types, clippy::duplicate_mod)]
+
+use core::mem;
+use core::time::Duration;
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use provider::cipher_suite;
+use rustls::client::Resumption;
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::UnixTime;
+use rustls::time_provider::TimeProvider;
+use rustls::{
+    ClientConfig, Connection, ConnectionEvent, ConnectionObserver, ServerConfig, VecInput,
+};
+use rustls_test::{
+    ClientStorage, KeyType, do_handshake, do_handshake_until_both_error, make_client_config,
+    make_client_config_with_kx_groups, make_disjoint_suite_configs, make_pair_for_arc_configs,
+    make_server_config, make_server_config_with_kx_groups, transfer,
+};
+
+use super::provider;
+
+#[test]
+fn full_handshake() {
+    let (client_config, client_events) = observe_client(make_client_config(
+        KeyType::default(),
+        &provider::DEFAULT_TLS13_PROVIDER,
+    ));
+    let (server_config, server_events) = observe_server(make_server_config(
+        KeyType::default(),
+        &provider::DEFAULT_TLS13_PROVIDER,
+    ));
+
+    handshake(&client_config, &server_config);
+    assert_eq!(
+        client_events.take(),
+        ["ClientHelloSent", "CertificateVerified"]
+    );
+    assert_eq!(server_events.take(), ["ClientHelloReceived"]);
+}
+
+#[test]
+fn resumed_handshake() {
+    for provider in [
+        provider::DEFAULT_TLS12_PROVIDER,
+        provider::DEFAULT_TLS13_PROVIDER,
+    ] {
+        let mut client_config = make_client_config(KeyType::default(), &provider);
+        client_config.resumption = Resumption::store(Arc::new(ClientStorage::new()));
+        let (client_config, client_events) = observe_client(client_config);
+        let (server_config, server_events) =
+            observe_server(make_server_config(KeyType::default(), &provider));
+
+        handshake(&client_config, &server_config);
+        client_events.take();
+        server_events.take();
+
+        handshake(&client_config, &server_config);
+        assert_eq!(
+            client_events.take(),
+            ["ClientHelloSent", "ResumptionAccepted"]
+        );
+        assert_eq!(
+            server_events.take(),
+            [
+                "ClientHelloReceived",
+                "ResumptionOffered { rejection: None }",
+                "ResumptionAccepted"
+            ]
+        );
+    }
+}
+
+#[test]
+fn resumption_rejections() {
+    let kt = KeyType::default();
+    let provider = provider::DEFAULT_TLS13_PROVIDER;
+    let ticketer = provider
+        .ticketer_factory
+        .ticketer()
+        .unwrap();
+    let with_suite = |suite| CryptoProvider {
+        tls13_cipher_suites: Cow::Owned(vec![suite]),
+        ..provider.clone()
+    };
+
+    let mut client_config = make_client_config(kt, &provider);
+    client_config.resumption = Resumption::store(Arc::new(ClientStorage::new()));
+    let client_config = Arc::new(client_config);
+
+    let mut issuing_config =
+        make_server_config(kt, &with_suite(cipher_suite::TLS13_AES_128_GCM_SHA256));
+    issuing_config.ticketer = Some(ticketer.clone());
+    let issuing_config = Arc::new(issuing_config);
+
+    let mut other_suite = make_server_config(
+        kt,
+        &with_suite(cipher_suite::TLS13_CHACHA20_POLY1305_SHA256),
+    );
+    other_suite.ticketer = Some(ticketer.clone());
+
+    let mut other_ticketer = make_server_config(kt, &provider);
+    other_ticketer.ticketer = Some(
+        provider
+            .ticketer_factory
+            .ticketer()
+            .unwrap(),
+    );
+
+    let mut later = make_server_config(kt, &provider);
+    later.ticketer = Some(ticketer.clone());
+    later.time_provider = Arc::new(Later(Duration::from_secs(30 * 24 * 60 * 60)));
+
+    for (server_config, rejection) in [
+        (other_suite, "CipherSuiteMismatch"),
+        (other_ticketer, "Undecryptable"),
+        (later, "Expired"),
+    ] {
+        handshake(&client_config, &issuing_config);
+
+        let (server_config, server_events) = observe_server(server_config);
+        handshake(&client_config, &server_config);
+        assert_eq!(
+            server_events.take(),
+            [
+                "ClientHelloReceived".to_string(),
+                format!("ResumptionOffered {{ rejection: Some({rejection}) }}"),
+                "ResumptionRejected".to_string(),
+            ]
+        );
+    }
+}
+
+#[test]
+fn hello_retry_request() {
+    let provider = provider::DEFAULT_TLS13_PROVIDER;
+    let (client_config, client_events) = observe_client(make_client_config_with_kx_groups(
+        KeyType::default(),
+        vec![provider::kx_group::SECP384R1, provider::kx_group::X25519],
+        &provider,
+    ));
+    let (server_config, server_events) = observe_server(make_server_config_with_kx_groups(
+        KeyType::default(),
+        vec![provider::kx_group::X25519],
+        &provider,
+    ));
+
+    handshake(&client_config, &server_config);
+    assert_eq!(
+        client_events.take(),
+        [
+            "ClientHelloSent",
+            "HelloRetryRequest",
+            "ClientHelloSent",
+            "CertificateVerified"
+        ]
+    );
+    assert_eq!(
+        server_events.take(),
+        [
+            "ClientHelloReceived",
+            "HelloRetryRequest",
+            "ClientHelloReceived"
+        ]
+    );
+}
+
+#[test]
+fn key_update_and_close_notify() {
+    let (client_config, client_events) = observe_client(make_client_config(
+        KeyType::default(),
+        &provider::DEFAULT_TLS13_PROVIDER,
+    ));
+    let (server_config, server_events) = observe_server(make_server_config(
+        KeyType::default(),
+        &provider::DEFAULT_TLS13_PROVIDER,
+    ));
+
+    let mut client_output = Vec::new();
+    let mut server_input = VecInput::default();
+    let (mut client, mut server) =
+        make_pair_for_arc_configs(&client_config, &server_config, &mut client_output);
+    do_handshake(
+        &mut VecInput::default(),
+        &mut client_output,
+        &mut client,
+        &mut server_input,
+        &mut Vec::new(),
+        &mut server,
+    );
+    client_events.take();
+    server_events.take();
+
+    client
+        .refresh_traffic_keys(&mut client_output)
+        .unwrap();
+    client
+        .send_close_notify(&mut client_output)
+        .unwrap();
+    transfer(&mut client_output, &mut server_input);
+    server
+        .process_new_packets(&mut server_input, &mut Vec::new())
+        .handle_all(&mut Vec::new())
+        .unwrap();
+
+    assert_eq!(client_events.take(), ["KeyUpdateSent", "CloseNotifySent"]);
+    // the server responds to the client's request with its own `KeyUpdate`
+    assert_eq!(
+        server_events.take(),
+        ["KeyUpdateSent", "KeyUpdateReceived", "CloseNotifyReceived"]
+    );
+}
+
+#[test]
+fn fatal_alerts() {
+    let (client_config, server_config) = make_disjoint_suite_configs(provider::DEFAULT_PROVIDER);
+    let (client_config, client_events) = observe_client(client_config);
+    let (server_config, server_events) = observe_server(server_config);
+
+    let mut client_output = Vec::new();
+    let (mut client, mut server) =
+        make_pair_for_arc_configs(&client_config, &server_config, &mut client_output);
+    do_handshake_until_both_error(
+        &mut VecInput::default(),
+        &mut client_output,
+        &mut client,
+        &mut VecInput::default(),
+        &mut Vec::new(),
+        &mut server,
+    )
+    .unwrap_err();
+
+    assert_eq!(
+        client_events.take(),
+        [
+            "ClientHelloSent",
+            "AlertReceived { description: HandshakeFailure, error: Some(AlertReceived(HandshakeFailure)) }"
+        ]
+    );
+    assert_eq!(
+        server_events.take(),
+        [
+            "ClientHelloReceived",
+            "AlertSent { description: HandshakeFailure, error: Some(PeerIncompatible(NoCipherSuitesInCommon)) }"
+        ]
+    );
+}
+
+fn handshake(client_config: &Arc<ClientConfig>, server_config: &Arc<ServerConfig>) {
+    let mut client_output = Vec::new();
+    let (mut client, mut server) =
+        make_pair_for_arc_configs(client_config, server_config, &mut client_output);
+    do_handshake(
+        &mut VecInput::default(),
+        &mut client_output,
+        &mut client,
+        &mut VecInput::default(),
+        &mut Vec::new(),
+        &mut server,
+    );
+}
+
+fn observe_client(mut config: ClientConfig) -> (Arc<ClientConfig>, Arc<Recorder>) {
+    let recorder = Arc::new(Recorder::default());
+    config.observer = Some(recorder.clone());
+    (Arc::new(config), recorder)
+}
+
+fn observe_server(mut config: ServerConfig) -> (Arc<ServerConfig>, Arc<Recorder>) {
+    let recorder = Arc::new(Recorder::default());
+    config.observer = Some(recorder.clone());
+    (Arc::new(config), recorder)
+}
+
+#[derive(Debug, Default)]
+struct Recorder(Mutex<Vec<String>>);
+
+impl Recorder {
+    fn take(&self) -> Vec<String> {
+        mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+impl ConnectionObserver for Recorder {
+    fn observe(&self, event: &ConnectionEvent<'_>) {
+        let event = match event {
+            // The age depends on timing, so is left out.
+            ConnectionEvent::ResumptionOffered { rejection, .. } => {
+                format!("ResumptionOffered {{ rejection: {rejection:?} }}")
+            }
+            _ => format!("{event:?}"),
+        };
+        self.0.lock().unwrap().push(event);
+    }
+}
+
+/// Provides a time `self.0` in the future.
+#[derive(Debug)]
+struct Later(Duration);
+
+impl TimeProvider for Later {
+    fn current_time(&self) -> Option<UnixTime> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap();
+        Some(UnixTime::since_unix_epoch(now + self.0))
+    }
+}
@@ -0,0 +1,177 @@
+//! Tests for qlog-style connection traces.
+
+#![allow(clippy::disallowed_types, clippy::duplicate_mod)]
+
+use std::sync::{Arc, Mutex};
+
+use rustls::qlog::{QlogSink, QlogTracer};
+use rustls::{ClientConfig, ServerConfig, VecInput};
+use rustls_test::{
+    KeyType, do_handshake, make_client_config, make_pair_for_arc_configs, make_server_config,
+};
+use serde_json::Value;
+
+use super::provider;
+
+#[test]
+fn traces_handshake() {
+    for (provider, version) in [
+        (provider::DEFAULT_TLS12_PROVIDER, "TLSv1_2"),
+        (provider::DEFAULT_TLS13_PROVIDER, "TLSv1_3"),
+    ] {
+        let (client_config, client_traces) =
+            trace_client(make_client_config(KeyType::default(), &provider));
+        let (server_config, server_traces) =
+            trace_server(make_server_config(KeyType::default(), &provider));
+        handshake(&client_config, &server_config);
+
+        let client = client_traces.only();
+        let server = server_traces.only();
+        assert_eq!(client[0]["trace"]["vantage_point"]["type"], "client");
+        assert_eq!(server[0]["trace"]["vantage_point"]["type"], "server");
+        for trace in [&client, &server] {
+            assert_eq!(trace[0]["qlog_format"], "JSON-SEQ");
+            assert!(trace[0]["trace"]["common_fields"]["reference_time"].is_u64());
+            assert!(
+                trace[1..]
+                    .iter()
+                    .all(|event| event["time"].is_number())
+            );
+        }
+
+        let hello = event(&client, "tls:handshake_message_sent", "ClientHello");
+        assert!(
+            hello["extensions"]
+                .as_array()
+                .unwrap()
+                .contains(&"ServerName".into())
+        );
+        assert!(hello["length"].as_u64().unwrap() > 0);
+        assert_eq!(
+            hello,
+            event(&server, "tls:handshake_message_received", "ClientHello")
+        );
+
+        // every message the server sends is received by the client, in the same order
+        assert_eq!(
+            messages(&server, "tls:handshake_message_sent"),
+            messages(&client, "tls:handshake_message_received")
+        );
+        assert_eq!(
+            messages(&client, "tls:handshake_message_sent"),
+            messages(&server, "tls:handshake_message_received")
+        );
+
+        for trace in [&client, &server] {
+            assert!(trace.iter().any(|event| {
+                event["name"] == "tls:parameters_set" && event["data"]["version"] == version
+            }));
+            assert!(trace.iter().any(|event| {
+                event["name"] == "tls:record_sent" && event["data"]["content_type"] == "Handshake"
+            }));
+            assert!(trace.iter().any(|event| {
+                event["name"] == "tls:record_received"
+                    && event["data"]["content_type"] == "Handshake"
+            }));
+        }
+    }
+}
+
+#[test]
+fn tracer_can_decline() {
+    let mut client_config = make_client_config(KeyType::default(), &provider::DEFAULT_PROVIDER);
+    client_config.qlog = Some(Arc::new(Declines));
+    let server_config = make_server_config(KeyType::default(), &provider::DEFAULT_PROVIDER);
+    handshake(&Arc::new(client_config), &Arc::new(server_config));
+}
+
+fn event<'a>(trace: &'a [Value], name: &str, message_type: &str) -> &'a Value {
+    &trace
+        .iter()
+        .find(|event| event["name"] == name && event["data"]["message_type"] == message_type)
+        .unwrap()["data"]
+}
+
+fn messages<'a>(trace: &'a [Value], name: &str) -> Vec<&'a Value> {
+    trace
+        .iter()
+        .filter(|event| event["name"] == name)
+        .map(|event| &event["data"])
+        .collect()
+}
+
+fn handshake(client_config: &Arc<ClientConfig>, server_config: &Arc<ServerConfig>) {
+    let mut client_output = Vec::new();
+    let (mut client, mut server) =
+        make_pair_for_arc_configs(client_config, server_config, &mut client_output);
+    do_handshake(
+        &mut VecInput::default(),
+        &mut client_output,
+        &mut client,
+        &mut VecInput::default(),
+        &mut Vec::new(),
+        &mut server,
+    );
+}
+
+fn trace_client(mut config: ClientConfig) -> (Arc<ClientConfig>, Arc<Traces>) {
+    let traces = Arc::new(Traces::default());
+    config.qlog = Some(traces.clone());
+    (Arc::new(config), traces)
+}
+
+fn trace_server(mut config: ServerConfig) -> (Arc<ServerConfig>, Arc<Traces>) {
+    let traces = Arc::new(Traces::default());
+    config.qlog = Some(traces.clone());
+    (Arc::new(config), traces)
+}
+
+#[derive(Debug, Default)]
+struct Traces(Mutex<Vec<Arc<Trace>>>);
+
+impl Traces {
+    /// Return the records of the only trace started, parsed as JSON.
+    fn only(&self) -> Vec<Value> {
+        let traces = self.0.lock().unwrap();
+        assert_eq!(traces.len(), 1);
+        traces[0]
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|record| serde_json::from_str(record).unwrap())
+            .collect()
+    }
+}
+
+impl QlogTracer for Traces {
+    fn new_trace(&self) -> Option<Arc<dyn QlogSink>> {
+        let trace = Arc::new(Trace::default());
+        self.0
+            .lock()
+            .unwrap()
+            .push(trace.clone());
+        Some(trace)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Trace(Mutex<Vec<String>>);
+
+impl QlogSink for Trace {
+    fn write_record(&self, record: &str) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(record.to_owned());
+    }
+}
+
+#[derive(Debug)]
+struct Declines;
+
+impl QlogTracer for Declines {
+    fn new_trace(&self) -> Option<Arc<dyn QlogSink>> {
+        None
+    }
+}
@@ -2,6 +2,7 @@
 
 #![allow(clippy::disallowed_types, clippy::duplicate_mod)]
 
+use core::time::Duration;
 use std::borrow::Cow;
 use std::sync::Arc;
 
@@ -12,6 +13,7 @@ use rustls::error::{
     AlertDescription, ApiMisuse, CertificateError, Error, InvalidMessage, PeerIncompatible,
     PeerMisbehaved,
 };
+use rustls::pki_types::UnixTime;
 use rustls::quic::{self, Connection, QuicEvent, ServerHandshake, Side};
 use rustls::server::Tls13Tickets;
 use rustls::{CipherSuiteCommon, HandshakeKind, SliceInput, Tls13CipherSuite, VecInput};
@@ -24,36 +26,6 @@ use super::provider;
 
 #[test]
 fn test_quic_handshake() {
-    fn equal_packet_keys(x: &dyn quic::PacketKey, y: &dyn quic::PacketKey) -> bool {
-        // Check that these two sets of keys are equal.
-        let mut buf = [0; 32];
-        let (header, payload_tag) = buf.split_at_mut(8);
-        let (payload, tag_buf) = payload_tag.split_at_mut(8);
-        let tag = x
-            .encrypt_in_place(42, header, payload, None)
-            .unwrap();
-        tag_buf.copy_from_slice(tag.as_ref());
-
-        let result = y.decrypt_in_place(42, header, payload_tag, None);
-        match result {
-            Ok(payload) => payload == [0; 8],
-            Err(_) => false,
-        }
-    }
-
-    fn compatible_keys(x: &quic::KeyChange, y: &quic::KeyChange) -> bool {
-        fn keys(kc: &quic::KeyChange) -> &quic::Keys {
-            match kc {
-                quic::KeyChange::Handshake { keys } => keys,
-                quic::KeyChange::OneRtt { keys, .. } => keys,
-            }
-        }
-
-        let (x, y) = (keys(x), keys(y));
-        equal_packet_keys(x.local.packet.as_ref(), y.remote.packet.as_ref())
-            && equal_packet_keys(x.remote.packet.as_ref(), y.local.packet.as_ref())
-    }
-
     let kt = KeyType::default();
     let provider = provider::DEFAULT_TLS13_PROVIDER;
     let mut client_config = make_client_config(kt, &provider);
@@ -754,6 +726,36 @@ fn test_quic_client_rejects_tls12_server() {
     );
 }
 
+fn equal_packet_keys(x: &dyn quic::PacketKey, y: &dyn quic::PacketKey) -> bool {
+    // Check that these two sets of keys are equal.
+    let mut buf = [0; 32];
+    let (header, payload_tag) = buf.split_at_mut(8);
+    let (payload, tag_buf) = payload_tag.split_at_mut(8);
+    let tag = x
+        .encrypt_in_place(42, header, payload, None)
+        .unwrap();
+    tag_buf.copy_from_slice(tag.as_ref());
+
+    let result = y.decrypt_in_place(42, header, payload_tag, None);
+    match result {
+        Ok(payload) => payload == [0; 8],
+        Err(_) => false,
+    }
+}
+
+fn compatible_keys(x: &quic::KeyChange, y: &quic::KeyChange) -> bool {
+    fn keys(kc: &quic::KeyChange) -> &quic::Keys {
+        match kc {
+            quic::KeyChange::Handshake { keys } => keys,
+            quic::KeyChange::OneRtt { keys, .. } => keys,
+        }
+    }
+
+    let (x, y) = (keys(x), keys(y));
+    equal_packet_keys(x.local.packet.as_ref(), y.remote.packet.as_ref())
+        && equal_packet_keys(x.remote.packet.as_ref(), y.local.packet.as_ref())
+}
+
 fn do_quic_handshake(client: &mut impl Connection, server: &mut impl Connection) {
     while client.is_handshaking() || server.is_handshaking() {
         quic_transfer(client, server).unwrap();
@@ -813,6 +815,140 @@ fn flatten_events(send: &mut impl Connection) -> Vec<u8> {
     out
 }
 
+#[test]
+fn test_quic_compatible_version_negotiation() {
+    let kt = KeyType::default();
+    let provider = provider::DEFAULT_TLS13_PROVIDER;
+    let client_config = Arc::new(make_client_config(kt, &provider));
+    let server_config = Arc::new(make_server_config(kt, &provider));
+
+    let new_pair = || {
+        let client = quic::ClientConnection::new(
+            client_config.clone(),
+            quic::Version::V1,
+            server_name("localhost"),
+            b"client params".to_vec(),
+        )
+        .unwrap();
+        let server = quic::ServerConnection::new(
+            server_config.clone(),
+            quic::Version::V1,
+            b"server params".to_vec(),
+        )
+        .unwrap();
+        (client, server)
+    };
+
+    // Both sides switch to V2 after the client's first flight.
+    let (mut client, mut server) = new_pair();
+    let client_hello = client.events().collect::<Vec<_>>();
+    server
+        .set_version(quic::Version::V2)
+        .unwrap();
+    assert_eq!(server.version(), quic::Version::V2);
+    quic_insert(client_hello, &mut server).unwrap();
+
+    client
+        .set_version(quic::Version::V2)
+        .unwrap();
+    let server_keys = quic_transfer(&mut server, &mut client).unwrap();
+    let client_keys = quic_transfer(&mut client, &mut server).unwrap();
+    assert!(!client.is_handshaking());
+    assert!(!server.is_handshaking());
+    assert!(compatible_keys(
+        server_keys.handshake.as_ref().unwrap(),
+        client_keys.handshake.as_ref().unwrap()
+    ));
+    assert!(compatible_keys(
+        server_keys.one_rtt.as_ref().unwrap(),
+        client_keys.one_rtt.as_ref().unwrap()
+    ));
+
+    // Key updates follow the new version too.
+    let (
+        Some(quic::KeyChange::OneRtt {
+            next: mut server_next,
+            ..
+        }),
+        Some(quic::KeyChange::OneRtt {
+            next: mut client_next,
+            ..
+        }),
+    ) = (server_keys.one_rtt, client_keys.one_rtt)
+    else {
+        panic!("expected 1-RTT keys");
+    };
+    let server_next = server_next.next_packet_keys();
+    let client_next = client_next.next_packet_keys();
+    assert!(equal_packet_keys(
+        server_next.local.as_ref(),
+        client_next.remote.as_ref()
+    ));
+
+    // Too late once handshake keys are derived.
+    assert_eq!(
+        client.set_version(quic::Version::V1),
+        Err(ApiMisuse::QuicVersionChangedAfterHandshakeKeys.into())
+    );
+    assert_eq!(
+        server.set_version(quic::Version::V1),
+        Err(ApiMisuse::QuicVersionChangedAfterHandshakeKeys.into())
+    );
+
+    // If only the server switches, the handshake keys disagree.
+    let (mut client, mut server) = new_pair();
+    server
+        .set_version(quic::Version::V2)
+        .unwrap();
+    quic_transfer(&mut client, &mut server).unwrap();
+    let server_keys = quic_transfer(&mut server, &mut client).unwrap();
+    let client_keys = quic_transfer(&mut client, &mut server).unwrap();
+    assert!(!compatible_keys(
+        server_keys.handshake.as_ref().unwrap(),
+        client_keys.handshake.as_ref().unwrap()
+    ));
+}
+
+#[test]
+fn test_quic_server_handshake_switches_version() {
+    let kt = KeyType::default();
+    let provider = provider::DEFAULT_TLS13_PROVIDER;
+    let client_config = Arc::new(make_client_config(kt, &provider));
+    let server_config = Arc::new(make_server_config(kt, &provider));
+
+    let mut client = quic::ClientConnection::new(
+        client_config,
+        quic::Version::V1,
+        server_name("localhost"),
+        b"client params".to_vec(),
+    )
+    .unwrap();
+
+    let mut client_initial = flatten_events(&mut client);
+    let mut output = vec![];
+    let mut accepted = match ServerHandshake::start(quic::Version::V1)
+        .process(&mut SliceInput::new(&mut client_initial), &mut output)
+        .unwrap()
+    {
+        ServerHandshake::Accepted(accepted) => accepted,
+        other => panic!("unexpected {other:?}"),
+    };
+    accepted.set_version(quic::Version::V2);
+    let server = accepted
+        .choose_config(server_config, b"server params".to_vec(), &mut output)
+        .unwrap();
+    let ServerHandshake::NeedsInput(_) = server else {
+        panic!("unexpected {server:?}");
+    };
+
+    client
+        .set_version(quic::Version::V2)
+        .unwrap();
+    let client_keys = quic_insert(output, &mut client).unwrap();
+    assert!(client_keys.handshake.is_some());
+    assert!(!client.is_handshaking());
+}
+
 #[test]
 fn test_quic_resumption_data_basic() {
     let server_params = b"server params";
@@ -1143,6 +1279,132 @@ fn packet_key_api() {
     assert_eq!(payload.len(), buf.len() - header_len - tag_len);
 }
 
+#[test]
+fn retry_integrity_tag_test_vectors() {
+    use provider::cipher_suite::TLS13_AES_128_GCM_SHA256;
+    use rustls::quic::Version;
+
+    const ORIGINAL_DCID: &[u8] = &[0x83, 0x94, 0xc8, 0xf0, 0x3e, 0x51, 0x57, 0x08];
+
+    // https://www.rfc-editor.org/rfc/rfc9001.html#name-retry
+    const RETRY_V1: &[u8] = &[
+        0xff, 0x00, 0x00, 0x00, 0x01, 0x00, 0x08, 0xf0, 0x67, 0xa5, 0x50, 0x2a, 0x42, 0x62, 0xb5,
+        0x74, 0x6f, 0x6b, 0x65, 0x6e, 0x04, 0xa2, 0x65, 0xba, 0x2e, 0xff, 0x4d, 0x82, 0x90, 0x58,
+        0xfb, 0x3f, 0x0f, 0x24, 0x96, 0xba,
+    ];
+
+    // https://www.rfc-editor.org/rfc/rfc9369.html#name-retry
+    const RETRY_V2: &[u8] = &[
+        0xcf, 0x6b, 0x33, 0x43, 0xcf, 0x00, 0x08, 0xf0, 0x67, 0xa5, 0x50, 0x2a, 0x42, 0x62, 0xb5,
+        0x74, 0x6f, 0x6b, 0x65, 0x6e, 0xc8, 0x64, 0x6c, 0xe8, 0xbf, 0xe3, 0x39, 0x52, 0xd9, 0x55,
+        0x54, 0x36, 0x65, 0xdc, 0xc7, 0xb6,
+    ];
+
+    let suite = quic::Suite::try_from(TLS13_AES_128_GCM_SHA256).unwrap();
+    for (version, packet) in [(Version::V1, RETRY_V1), (Version::V2, RETRY_V2)] {
+        let (without_tag, tag) = packet.split_at(packet.len() - 16);
+        let computed = suite
+            .retry_integrity_tag(version, ORIGINAL_DCID, without_tag)
+            .unwrap();
+        assert_eq!(computed.as_ref(), tag);
+
+        suite
+            .verify_retry_integrity_tag(version, ORIGINAL_DCID, packet)
+            .unwrap();
+
+        let mut tampered = packet.to_vec();
+        tampered[10] ^= 1;
+        assert_eq!(
+            suite.verify_retry_integrity_tag(version, ORIGINAL_DCID, &tampered),
+            Err(Error::DecryptError)
+        );
+        assert_eq!(
+            suite.verify_retry_integrity_tag(version, &ORIGINAL_DCID[1..], packet),
+            Err(Error::DecryptError)
+        );
+        assert_eq!(
+            suite.verify_retry_integrity_tag(version, ORIGINAL_DCID, &packet[..15]),
+            Err(Error::DecryptError)
+        );
+    }
+
+    // The other version's key does not verify.
+    assert_eq!(
+        suite.verify_retry_integrity_tag(Version::V2, ORIGINAL_DCID, RETRY_V1),
+        Err(Error::DecryptError)
+    );
+}
+
+#[test]
+fn retry_integrity_tag_requires_aes_128_gcm() {
+    use provider::cipher_suite::TLS13_AES_256_GCM_SHA384;
+
+    let suite = quic::Suite::try_from(TLS13_AES_256_GCM_SHA384).unwrap();
+    assert_eq!(
+        suite
+            .retry_integrity_tag(quic::Version::V1, &[1, 2, 3], &[0xff])
+            .err(),
+        Some(ApiMisuse::QuicRetryRequiresAes128Gcm.into())
+    );
+}
+
+#[test]
+fn address_validation_tokens() {
+    use quic::{AddressToken, AddressTokenSealer};
+
+    let sealer = AddressTokenSealer::new(
+        provider::DEFAULT_PROVIDER
+            .ticketer_factory
+            .ticketer()
+            .unwrap(),
+        Duration::from_secs(10),
+        Duration::from_secs(3600),
+    );
+    let address = b"192.0.2.1:4433";
+    let now = UnixTime::since_unix_epoch(Duration::from_secs(1_700_000_000));
+    let later = |secs| UnixTime::since_unix_epoch(Duration::from_secs(now.as_secs() + secs));
+
+    let retry = sealer
+        .seal_retry_token(address, &[1, 2, 3, 4], now)
+        .unwrap();
+    assert_eq!(
+        sealer.open(&retry, address, later(10)),
+        Some(AddressToken::Retry {
+            original_dst_connection_id: vec![1, 2, 3, 4]
+        })
+    );
+    assert_eq!(sealer.open(&retry, address, later(11)), None);
+    assert_eq!(sealer.open(&retry, b"192.0.2.2:4433", now), None);
+
+    let new_token = sealer
+        .seal_new_token(address, now)
+        .unwrap();
+    assert_eq!(
+        sealer.open(&new_token, address, later(3600)),
+        Some(AddressToken::NewToken)
+    );
+    assert_eq!(sealer.open(&new_token, address, later(3601)), None);
+
+    let mut tampered = new_token.clone();
+    let last = tampered.len() - 1;
+    tampered[last] ^= 1;
+    assert_eq!(sealer.open(&tampered, address, now), None);
+    assert_eq!(sealer.open(&[], address, now), None);
+
+    // Tokens from another sealer are rejected.
+    let other = AddressTokenSealer::new(
+        provider::DEFAULT_PROVIDER
+            .ticketer_factory
+            .ticketer()
+            .unwrap(),
+        Duration::from_secs(10),
+        Duration::from_secs(3600),
+    );
+    assert_eq!(other.open(&new_token, address, now), None);
+
+    assert_eq!(sealer.seal_new_token(&[0; 256], now), None);
+}
+
 #[test]
 fn test_quic_exporter() {
     let provider = provider::DEFAULT_TLS13_PROVIDER;
@@ -2,24 +2,38 @@
 
 #![allow(clippy::disallowed_types, clippy::duplicate_mod)]
 
-use core::sync::atomic::{AtomicUsize, Ordering};
+use core::net::SocketAddr;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use core::time::Duration;
+use std::collections::HashMap;
 use std::fmt;
-use std::io::Read;
-use std::sync::Arc;
-
-use rustls::client::{Resumption, TicketRequest};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use rustls::client::{
+    EarlyDataStatus, Resumption, ResumptionFailure, ResumptionOffer, TicketRequest,
+};
 use rustls::crypto::kx::NamedGroup;
 use rustls::crypto::{CertificateIdentity, Identity};
-use rustls::enums::ProtocolVersion;
+use rustls::enums::{ApplicationProtocol, ApplicationSettings, ProtocolVersion};
 use rustls::error::{ApiMisuse, Error, PeerMisbehaved};
-use rustls::server::{ServerSessionKey, Tls13Tickets};
+use rustls::pki_types::{DnsName, ServerName, UnixTime};
+use rustls::server::{
+    IssuedTicket, RemoteSessionStore, ServerSessionKey, SessionExpiry, SessionStoreClient,
+    TicketId, TicketRegistry, TicketRevocationRegistry, Tls13Tickets,
+};
+use rustls::time_provider::TimeProvider;
 use rustls::{ClientConfig, Connection, HandshakeKind, ServerConfig, ServerConnection, VecInput};
 use rustls_test::{
     ClientConfigExt, ClientStorage, ClientStorageOp, ErrorFromPeer, KeyType, MultiTest,
     ServerConfigExt, do_handshake, do_handshake_until_error, make_client_config,
     make_client_config_with_auth, make_client_config_with_kx_groups, make_pair,
     make_pair_for_arc_configs, make_pair_for_configs, make_server_config,
-    make_server_config_with_kx_groups, transfer, webpki_server_verifier_builder,
+    make_server_config_with_kx_groups, make_server_config_with_mandatory_client_auth, transfer,
+    webpki_server_verifier_builder,
 };
 
 use super::provider;
@@ -401,6 +415,87 @@ fn test_tls13_client_resumption_does_not_reuse_tickets() {
     assert!(matches!(ops[0], ClientStorageOp::TakeTls13Ticket(_, false)));
 }
 
+#[test]
+fn tls13_client_resumption_data() {
+    let kt = KeyType::default();
+    let provider = provider::DEFAULT_TLS13_PROVIDER;
+    let client_config = Arc::new(make_client_config(kt, &provider));
+    let server_config = Arc::new(make_server_config(kt, &provider));
+
+    let mut client_input = VecInput::default();
+    let mut server_input = VecInput::default();
+    let mut connect = |data: &[u8]| {
+        let mut client_output = Vec::new();
+        let mut server_output = Vec::new();
+        let (mut client, mut server) =
+            make_pair_for_arc_configs(&client_config, &server_config, &mut client_output);
+        client
+            .set_resumption_data(data)
+            .unwrap();
+        do_handshake(
+            &mut client_input,
+            &mut client_output,
+            &mut client,
+            &mut server_input,
+            &mut server_output,
+            &mut server,
+        );
+        assert_eq!(client.tls13_tickets_received(), 2);
+        (
+            client.handshake_kind(),
+            client
+                .received_resumption_data()
+                .map(|data| data.to_vec()),
+        )
+    };
+
+    assert_eq!(connect(b"first"), (Some(HandshakeKind::Full), None));
+    assert_eq!(
+        connect(b"second"),
+        (Some(HandshakeKind::Resumed), Some(b"first".to_vec()))
+    );
+
+    // Tickets received on the resumed connection carry its data.
+    assert_eq!(
+        connect(b""),
+        (Some(HandshakeKind::Resumed), Some(b"second".to_vec()))
+    );
+    assert_eq!(
+        connect(b""),
+        (Some(HandshakeKind::Resumed), Some(Vec::new()))
+    );
+}
+
+#[test]
+fn client_resumption_data_errors() {
+    let provider = provider::DEFAULT_TLS13_PROVIDER;
+    let client_config = make_client_config(KeyType::EcdsaP256, &provider);
+    let server_config = make_server_config(KeyType::Ed25519, &provider);
+    let mut client_output = Vec::new();
+    let mut server_output = Vec::new();
+    let (mut client, mut server) =
+        make_pair_for_configs(client_config, server_config, &mut client_output);
+
+    assert_eq!(
+        client.set_resumption_data(&[0; 1 << 15]),
+        Err(ApiMisuse::ResumptionDataTooLong.into())
+    );
+    assert_eq!(client.set_resumption_data(&[0; (1 << 15) - 1]), Ok(()));
+
+    // The client does not trust the server's certificate issuer.
+    let Err(ErrorFromPeer::Client(err)) = do_handshake_until_error(
+        &mut VecInput::default(),
+        &mut client_output,
+        &mut client,
+        &mut VecInput::default(),
+        &mut server_output,
+        &mut server,
+    ) else {
+        panic!("expected client handshake error");
+    };
+    assert_eq!(client.set_resumption_data(b"late"), Err(err));
+}
+
 #[test]
 fn tls13_stateful_resumption() {
     let kt = KeyType::default();
@@ -616,6 +711,216 @@ fn tls13_stateless_resumption() {
     assert_eq!(server.handshake_kind(), Some(HandshakeKind::Resumed));
 }
 
+#[test]
+fn tls13_client_resumption_diagnostics() {
+    let kt = KeyType::default();
+    let provider = Arc::new(provider::DEFAULT_TLS13_PROVIDER);
+    let clock = Arc::new(Clock::default());
+    let mut client_config =
+        ClientConfig::builder_with_details(provider.clone(), clock.clone()).finish(kt);
+    client_config.enable_early_data = true;
+    client_config.resumption = Resumption::store(Arc::new(ClientStorage::new()));
+    let client_config = Arc::new(client_config);
+
+    let server_config = || {
+        let mut server_config = make_server_config(kt, &provider);
+        server_config.max_early_data_size = 1234;
+        Arc::new(server_config)
+    };
+    let issuing_config = server_config();
+
+    let mut client_input = VecInput::default();
+    let mut server_input = VecInput::default();
+    let mut connect = |server_config: &Arc<ServerConfig>| {
+        let mut client_output = Vec::new();
+        let mut server_output = Vec::new();
+        let (mut client, mut server) =
+            make_pair_for_arc_configs(&client_config, server_config, &mut client_output);
+        do_handshake(
+            &mut client_input,
+            &mut client_output,
+            &mut client,
+            &mut server_input,
+            &mut server_output,
+            &mut server,
+        );
+        client
+            .resumption_diagnostics()
+            .unwrap()
+            .clone()
+    };
+
+    let diags = connect(&issuing_config);
+    assert_eq!(
+        diags.session_key.server_name,
+        ServerName::try_from("localhost").unwrap()
+    );
+    assert_eq!(diags.offered, None);
+    assert!(!diags.accepted);
+    assert_eq!(diags.failure, Some(ResumptionFailure::NoStoredSession));
+    assert_eq!(diags.early_data, EarlyDataStatus::NotOffered);
+
+    let diags = connect(&issuing_config);
+    assert_eq!(diags.offered, Some(ResumptionOffer::Tls13Ticket));
+    assert!(diags.accepted);
+    assert_eq!(diags.failure, None);
+    assert_eq!(diags.early_data, EarlyDataStatus::Accepted);
+
+    // A server with different ticket keys cannot accept the ticket, nor the early data.
+    let diags = connect(&server_config());
+    assert_eq!(diags.offered, Some(ResumptionOffer::Tls13Ticket));
+    assert!(!diags.accepted);
+    assert_eq!(diags.failure, Some(ResumptionFailure::ServerRejected));
+    assert_eq!(diags.early_data, EarlyDataStatus::Rejected);
+
+    // Tickets past their lifetime are discarded without being offered.
+    clock.advance(Duration::from_secs(8 * 24 * 60 * 60));
+    let diags = connect(&issuing_config);
+    assert_eq!(diags.offered, None);
+    assert!(!diags.accepted);
+    assert_eq!(diags.failure, Some(ResumptionFailure::ExpiredLocally));
+    assert_eq!(diags.early_data, EarlyDataStatus::NotOffered);
+}
+
+#[test]
+fn tls12_client_resumption_diagnostics() {
+    let kt = KeyType::default();
+    let provider = provider::DEFAULT_TLS12_PROVIDER;
+    let client_config = Arc::new(make_client_config(kt, &provider));
+    let server_config = Arc::new(make_server_config(kt, &provider));
+
+    let mut client_input = VecInput::default();
+    let mut server_input = VecInput::default();
+    let mut connect = || {
+        let mut client_output = Vec::new();
+        let mut server_output = Vec::new();
+        let (mut client, mut server) =
+            make_pair_for_arc_configs(&client_config, &server_config, &mut client_output);
+        do_handshake(
+            &mut client_input,
+            &mut client_output,
+            &mut client,
+            &mut server_input,
+            &mut server_output,
+            &mut server,
+        );
+        client
+            .resumption_diagnostics()
+            .unwrap()
+            .clone()
+    };
+
+    let diags = connect();
+    assert_eq!(diags.failure, Some(ResumptionFailure::NoStoredSession));
+
+    let diags = connect();
+    assert_eq!(diags.offered, Some(ResumptionOffer::Tls12SessionId));
+    assert!(diags.accepted);
+    assert_eq!(diags.failure, None);
+    assert_eq!(diags.early_data, EarlyDataStatus::NotOffered);
+}
+
+#[test]
+fn server_does_not_resume_tickets_older_than_their_lifetime() {
+    let kt = KeyType::default();
+    for provider in [
+        provider::DEFAULT_TLS12_PROVIDER,
+        provider::DEFAULT_TLS13_PROVIDER,
+    ] {
+        let ticketer = provider
+            .ticketer_factory
+            .ticketer()
+            .unwrap();
+        let lifetime = ticketer.lifetime();
+
+        let resume_after = |age: Duration| {
+            let mut client_config = make_client_config(kt, &provider);
+            client_config.resumption = Resumption::store(Arc::new(ClientStorage::new()));
+            let client_config = Arc::new(client_config);
+
+            let clock = Arc::new(Clock::default());
+            let mut server_config = make_server_config(kt, &provider);
+            server_config.ticketer = Some(ticketer.clone());
+            server_config.time_provider = clock.clone();
+            let server_config = Arc::new(server_config);
+
+            let server = handshake_for_arc_configs(&client_config, &server_config);
+            assert_eq!(server.handshake_kind(), Some(HandshakeKind::Full));
+
+            clock.advance(age);
+            handshake_for_arc_configs(&client_config, &server_config).handshake_kind()
+        };
+
+        let margin = Duration::from_secs(60);
+        assert_eq!(
+            resume_after(lifetime - margin),
+            Some(HandshakeKind::Resumed)
+        );
+        assert_eq!(resume_after(lifetime + margin), Some(HandshakeKind::Full));
+    }
+}
+
+#[test]
+fn server_session_lifetime_reaches_store() {
+    let kt = KeyType::default();
+    for provider in [
+        provider::DEFAULT_TLS12_PROVIDER,
+        provider::DEFAULT_TLS13_PROVIDER,
+    ] {
+        let lifetime = Duration::from_secs(60 * 60);
+
+        let resume_after = |age: Duration| {
+            let mut client_config = make_client_config(kt, &provider);
+            client_config.resumption = Resumption::store(Arc::new(ClientStorage::new()));
+            let client_config = Arc::new(client_config);
+
+            let clock = Arc::new(Clock::default());
+            let storage = Arc::new(ServerStorage::new());
+            let mut server_config = make_server_config(kt, &provider);
+            server_config.session_storage = storage.clone();
+            server_config.session_lifetime = lifetime;
+            server_config.time_provider = clock.clone();
+            let server_config = Arc::new(server_config);
+
+            let server = handshake_for_arc_configs(&client_config, &server_config);
+            assert_eq!(server.handshake_kind(), Some(HandshakeKind::Full));
+            let expiry = storage.last_expiry().unwrap();
+            assert_eq!(expiry.lifetime, lifetime);
+            assert_eq!(expiry.created, clock.current_time().unwrap());
+
+            clock.advance(age);
+            handshake_for_arc_configs(&client_config, &server_config).handshake_kind()
+        };
+
+        let margin = Duration::from_secs(60);
+        assert_eq!(
+            resume_after(lifetime - margin),
+            Some(HandshakeKind::Resumed)
+        );
+        assert_eq!(resume_after(lifetime + margin), Some(HandshakeKind::Full));
+    }
+}
+
+/// A clock which can be moved forward.
+#[derive(Debug, Default)]
+struct Clock {
+    offset: AtomicU64,
+}
+
+impl Clock {
+    fn advance(&self, by: Duration) {
+        self.offset
+            .fetch_add(by.as_secs(), Ordering::SeqCst);
+    }
+}
+
+impl TimeProvider for Clock {
+    fn current_time(&self) -> Option<UnixTime> {
+        let now = UnixTime::now().as_secs() + self.offset.load(Ordering::SeqCst);
+        Some(UnixTime::since_unix_epoch(Duration::from_secs(now)))
+    }
+}
+
 #[test]
 fn early_data_not_available() {
     let mut client_output = Vec::new();
@@ -675,14 +980,14 @@ fn early_data_is_available_on_resumption() {
             .early_data()
             .unwrap()
             .write_tls(b"".into(), &mut client_output),
-        0
+        Ok(0)
     );
     assert_eq!(
         client
             .early_data()
             .unwrap()
             .write_tls(b"hello".into(), &mut client_output),
-        5
+        Ok(5)
     );
     let client_early_exporter = client
         .early_data()
@@ -740,6 +1045,157 @@ fn early_data_is_available_on_resumption() {
     assert_eq!(client_secret, server_secret);
 }
 
+fn early_data_alps_configs() -> (ClientConfig, ServerConfig) {
+    let (client_config, server_config) = early_data_configs();
+    let mut client_config = Arc::unwrap_or_clone(client_config);
+    client_config.alpn_protocols = vec![ApplicationProtocol::Http2];
+    client_config.application_settings = vec![ApplicationSettings::new(
+        ApplicationProtocol::Http2,
+        b"client".to_vec(),
+    )];
+
+    let mut server_config = Arc::unwrap_or_clone(server_config);
+    server_config.alpn_protocols = vec![ApplicationProtocol::Http2];
+    server_config.application_settings = vec![ApplicationSettings::new(
+        ApplicationProtocol::Http2,
+        b"server".to_vec(),
+    )];
+    (client_config, server_config)
+}
+
+/// Resumes with early data after a full handshake using `first_server_config`.
+///
+/// Returns the client and server connections after the resumed handshake.
+fn early_data_with_alps(
+    client_config: &Arc<ClientConfig>,
+    first_server_config: &Arc<ServerConfig>,
+    second_server_config: &Arc<ServerConfig>,
+) -> (rustls::ClientConnection, ServerConnection) {
+    let mut client_output = Vec::new();
+    let mut server_output = Vec::new();
+    let (mut client, mut server) =
+        make_pair_for_arc_configs(client_config, first_server_config, &mut client_output);
+    do_handshake(
+        &mut VecInput::default(),
+        &mut client_output,
+        &mut client,
+        &mut VecInput::default(),
+        &mut server_output,
+        &mut server,
+    );
+    assert_eq!(client.peer_application_settings(), Some(&b"server"[..]));
+    assert_eq!(server.peer_application_settings(), Some(&b"client"[..]));
+
+    let mut client_output = Vec::new();
+    let mut server_output = Vec::new();
+    let (mut client, mut server) =
+        make_pair_for_arc_configs(client_config, second_server_config, &mut client_output);
+    if let Some(mut early_data) = client.early_data() {
+        assert_eq!(
+            early_data.write_tls(b"hello".into(), &mut client_output),
+            Ok(5)
+        );
+        // The server's settings are assumed to be those of the previous session.
+        assert_eq!(client.peer_application_settings(), Some(&b"server"[..]));
+    }
+    do_handshake(
+        &mut VecInput::default(),
+        &mut client_output,
+        &mut client,
+        &mut VecInput::default(),
+        &mut server_output,
+        &mut server,
+    );
+    (client, server)
+}
+
+#[test]
+fn early_data_with_unchanged_application_settings() {
+    let (client_config, server_config) = early_data_alps_configs();
+    let (client_config, server_config) = (Arc::new(client_config), Arc::new(server_config));
+
+    let (client, mut server) = early_data_with_alps(&client_config, &server_config, &server_config);
+    assert!(client.is_early_data_accepted());
+    assert_eq!(client.peer_application_settings(), Some(&b"server"[..]));
+    assert_eq!(server.peer_application_settings(), Some(&b"client"[..]));
+
+    let mut received_early_data = [0u8; 5];
+    assert_eq!(
+        server
+            .early_data()
+            .expect("early_data didn't happen")
+            .read(&mut received_early_data)
+            .expect("early_data failed unexpectedly"),
+        5
+    );
+    assert_eq!(&received_early_data[..], b"hello");
+}
+
+#[test]
+fn early_data_rejected_when_server_application_settings_change() {
+    let (client_config, server_config) = early_data_alps_configs();
+    let mut changed_server_config = server_config.clone();
+    changed_server_config.application_settings = vec![ApplicationSettings::new(
+        ApplicationProtocol::Http2,
+        b"changed".to_vec(),
+    )];
+
+    let (client, mut server) = early_data_with_alps(
+        &Arc::new(client_config),
+        &Arc::new(server_config),
+        &Arc::new(changed_server_config),
+    );
+    assert_eq!(client.handshake_kind(), Some(HandshakeKind::Resumed));
+    assert!(!client.is_early_data_accepted());
+    assert!(server.early_data().is_none());
+    assert_eq!(client.peer_application_settings(), Some(&b"changed"[..]));
+    assert_eq!(server.peer_application_settings(), Some(&b"client"[..]));
+}
+
+#[test]
+fn early_data_not_offered_when_client_application_settings_change() {
+    let (client_config, server_config) = early_data_alps_configs();
+    let server_config = Arc::new(server_config);
+    let client_config = Arc::new(client_config);
+
+    let mut client_output = Vec::new();
+    let mut server_output = Vec::new();
+    let (mut client, mut server) =
+        make_pair_for_arc_configs(&client_config, &server_config, &mut client_output);
+    do_handshake(
+        &mut VecInput::default(),
+        &mut client_output,
+        &mut client,
+        &mut VecInput::default(),
+        &mut server_output,
+        &mut server,
+    );
+
+    // Sessions are shared between these configs, which differ only in their settings.
+    let mut changed_client_config = Arc::unwrap_or_clone(client_config);
+    changed_client_config.application_settings = vec![ApplicationSettings::new(
+        ApplicationProtocol::Http2,
+        b"changed".to_vec(),
+    )];
+    let changed_client_config = Arc::new(changed_client_config);
+
+    let mut client_output = Vec::new();
+    let mut server_output = Vec::new();
+    let (mut client, mut server) =
+        make_pair_for_arc_configs(&changed_client_config, &server_config, &mut client_output);
+    assert!(client.early_data().is_none());
+    do_handshake(
+        &mut VecInput::default(),
+        &mut client_output,
+        &mut client,
+        &mut VecInput::default(),
+        &mut server_output,
+        &mut server,
+    );
+    assert_eq!(client.handshake_kind(), Some(HandshakeKind::Resumed));
+    assert_eq!(server.peer_application_settings(), Some(&b"changed"[..]));
+}
+
 #[test]
 fn early_data_not_available_on_server_before_client_hello() {
     let mut server = ServerConnection::new(Arc::new(make_server_config(
@@ -787,7 +1243,7 @@ fn early_data_is_limited_on_client() {
             .early_data()
             .unwrap()
             .write_tls((&[0xaa; 1234 + 1]).into(), &mut client_output),
-        1234
+        Ok(1234)
     );
     do_handshake(
         &mut client_input,
@@ -862,7 +1318,7 @@ fn server_detects_excess_early_data() {
             .early_data()
             .unwrap()
             .write_tls((&[0xaa; 2024]).into(), &mut client_output),
-        2024
+        Ok(2024)
     );
     assert_eq!(
         do_handshake_until_error(
@@ -902,7 +1358,7 @@ fn server_detects_excess_streamed_early_data() {
             .early_data()
             .unwrap()
             .write_tls((&[0xaa; 1024]).into(), &mut client_output),
-        1024
+        Ok(1024)
     );
     transfer(&mut client_output, &mut server_input);
     server
@@ -926,7 +1382,7 @@ fn server_detects_excess_streamed_early_data() {
             .early_data()
             .unwrap()
             .write_tls((&[0xbb; 1000]).into(), &mut client_output),
-        1000
+        Ok(1000)
     );
     transfer(&mut client_output, &mut server_input);
     assert_eq!(
@@ -943,6 +1399,7 @@ struct ServerStorage {
     put_count: AtomicUsize,
     get_count: AtomicUsize,
     take_count: AtomicUsize,
+    last_expiry: Mutex<Option<SessionExpiry>>,
 }
 
 impl ServerStorage {
@@ -952,6 +1409,7 @@ impl ServerStorage {
             put_count: AtomicUsize::new(0),
             get_count: AtomicUsize::new(0),
             take_count: AtomicUsize::new(0),
+            last_expiry: Mutex::new(None),
         }
     }
 
@@ -964,6 +1422,9 @@ impl ServerStorage {
     fn takes(&self) -> usize {
         self.take_count.load(Ordering::SeqCst)
     }
+    fn last_expiry(&self) -> Option<SessionExpiry> {
+        *self.last_expiry.lock().unwrap()
+    }
 }
 
 impl fmt::Debug for ServerStorage {
@@ -977,22 +1438,23 @@ impl fmt::Debug for ServerStorage {
 }
 
 impl rustls::server::StoresServerSessions for ServerStorage {
-    fn put(&self, key: ServerSessionKey<'_>, value: Vec<u8>) -> bool {
+    fn put(&self, key: ServerSessionKey<'_>, value: Vec<u8>, expiry: SessionExpiry) -> bool {
         self.put_count
             .fetch_add(1, Ordering::SeqCst);
-        self.storage.put(key, value)
+        *self.last_expiry.lock().unwrap() = Some(expiry);
+        self.storage.put(key, value, expiry)
     }
 
-    fn get(&self, key: ServerSessionKey<'_>) -> Option<Vec<u8>> {
+    fn get(&self, key: ServerSessionKey<'_>, now: UnixTime) -> Option<Vec<u8>> {
         self.get_count
             .fetch_add(1, Ordering::SeqCst);
-        self.storage.get(key)
+        self.storage.get(key, now)
     }
 
-    fn take(&self, key: ServerSessionKey<'_>) -> Option<Vec<u8>> {
+    fn take(&self, key: ServerSessionKey<'_>, now: UnixTime) -> Option<Vec<u8>> {
         self.take_count
             .fetch_add(1, Ordering::SeqCst);
-        self.storage.take(key)
+        self.storage.take(key, now)
     }
 
     fn can_cache(&self) -> bool {
@@ -1240,3 +1702,361 @@ fn tls13_ticket_request_survives_hello_retry_request() {
         .count();
     assert_eq!(ticket_inserts, 2);
 }
+
+#[test]
+fn tls13_ticket_registry_makes_tickets_single_use() {
+    let provider = provider::DEFAULT_TLS13_PROVIDER;
+
+    for registry in [None, Some(TicketRevocationRegistry::new(16))] {
+        let client_config = Arc::new(make_client_config(KeyType::default(), &provider));
+        let mut server_config = make_server_config(KeyType::default(), &provider);
+        server_config.ticketer = Some(
+            provider
+                .ticketer_factory
+                .ticketer()
+                .unwrap(),
+        );
+        server_config.ticket_registry = registry
+            .clone()
+            .map(|r| r as Arc<dyn TicketRegistry>);
+        let server_config = Arc::new(server_config);
+
+        let server = handshake_for_arc_configs(&client_config, &server_config);
+        assert_eq!(server.handshake_kind(), Some(HandshakeKind::Full));
+
+        // Resume, keeping a copy of the `ClientHello`.
+        let mut client_output = Vec::new();
+        let mut server_output = Vec::new();
+        let (mut client, mut server) =
+            make_pair_for_arc_configs(&client_config, &server_config, &mut client_output);
+        let mut client_hello = client_output.clone();
+        do_handshake(
+            &mut VecInput::default(),
+            &mut client_output,
+            &mut client,
+            &mut VecInput::default(),
+            &mut server_output,
+            &mut server,
+        );
+        assert_eq!(server.handshake_kind(), Some(HandshakeKind::Resumed));
+
+        // Replay the `ClientHello`, offering the same ticket again.
+        let mut server = ServerConnection::new(server_config.clone()).unwrap();
+        let mut server_input = VecInput::default();
+        transfer(&mut client_hello, &mut server_input);
+        server
+            .process_new_packets(&mut server_input, &mut server_output)
+            .handle_all(&mut Vec::new())
+            .unwrap();
+
+        let expected = match registry {
+            Some(_) => HandshakeKind::Full,
+            None => HandshakeKind::Resumed,
+        };
+        assert_eq!(server.handshake_kind(), Some(expected));
+    }
+}
+
+#[test]
+fn tls13_declined_ticket_is_not_stored() {
+    let provider = provider::DEFAULT_TLS13_PROVIDER;
+    let client_config = Arc::new(make_client_config(KeyType::default(), &provider));
+    let storage = Arc::new(ServerStorage::new());
+    let mut server_config = make_server_config(KeyType::default(), &provider);
+    server_config.session_storage = storage.clone();
+    server_config.ticket_registry = Some(Arc::new(DecliningRegistry));
+    let server_config = Arc::new(server_config);
+
+    let mut client_output = Vec::new();
+    let mut server_output = Vec::new();
+    let (mut client, mut server) =
+        make_pair_for_arc_configs(&client_config, &server_config, &mut client_output);
+    do_handshake(
+        &mut VecInput::default(),
+        &mut client_output,
+        &mut client,
+        &mut VecInput::default(),
+        &mut server_output,
+        &mut server,
+    );
+    assert_eq!(server.handshake_kind(), Some(HandshakeKind::Full));
+    assert_eq!(client.tls13_tickets_received(), 0);
+    assert_eq!(storage.puts(), 0);
+
+    let server = handshake_for_arc_configs(&client_config, &server_config);
+    assert_eq!(server.handshake_kind(), Some(HandshakeKind::Full));
+    assert_eq!(storage.puts(), 0);
+}
+
+/// A registry which declines every ticket, but would allow any to be redeemed.
+#[derive(Debug)]
+struct DecliningRegistry;
+
+impl TicketRegistry for DecliningRegistry {
+    fn register(&self, _ticket: &IssuedTicket<'_>) -> bool {
+        false
+    }
+
+    fn redeem(&self, _id: &TicketId, _now: UnixTime) -> bool {
+        true
+    }
+}
+
+#[test]
+fn tls13_ticket_registry_revokes_peer_identity() {
+    let kt = KeyType::default();
+    let provider = provider::DEFAULT_TLS13_PROVIDER;
+    let registry = TicketRevocationRegistry::new(16);
+
+    let client_config = Arc::new(make_client_config_with_auth(kt, &provider));
+    let mut server_config = make_server_config_with_mandatory_client_auth(kt, &provider);
+    server_config.ticket_registry = Some(registry.clone());
+    let server_config = Arc::new(server_config);
+
+    let server = handshake_for_arc_configs(&client_config, &server_config);
+    assert_eq!(server.handshake_kind(), Some(HandshakeKind::Full));
+    let identity = server.peer_identity().unwrap().clone();
+
+    // The server sends two tickets by default.
+    assert_eq!(registry.revoke_peer_identity(&identity), 2);
+    assert_eq!(registry.revoke_peer_identity(&identity), 0);
+
+    let server = handshake_for_arc_configs(&client_config, &server_config);
+    assert_eq!(server.handshake_kind(), Some(HandshakeKind::Full));
+    let server = handshake_for_arc_configs(&client_config, &server_config);
+    assert_eq!(server.handshake_kind(), Some(HandshakeKind::Resumed));
+}
+
+#[test]
+fn tls13_ticket_registry_revokes_server_name() {
+    let provider = provider::DEFAULT_TLS13_PROVIDER;
+    let registry = TicketRevocationRegistry::new(16);
+
+    let client_config = Arc::new(make_client_config(KeyType::default(), &provider));
+    let mut server_config = make_server_config(KeyType::default(), &provider);
+    server_config.ticket_registry = Some(registry.clone());
+    let server_config = Arc::new(server_config);
+
+    let server = handshake_for_arc_configs(&client_config, &server_config);
+    assert_eq!(server.handshake_kind(), Some(HandshakeKind::Full));
+
+    let other = DnsName::try_from("example.com").unwrap();
+    assert_eq!(registry.revoke_server_name(&other), 0);
+    let localhost = DnsName::try_from("localhost").unwrap();
+    assert_eq!(registry.revoke_server_name(&localhost), 2);
+
+    let server = handshake_for_arc_configs(&client_config, &server_config);
+    assert_eq!(server.handshake_kind(), Some(HandshakeKind::Full));
+}
+
+fn handshake_for_arc_configs(
+    client_config: &Arc<ClientConfig>,
+    server_config: &Arc<ServerConfig>,
+) -> ServerConnection {
+    let mut client_output = Vec::new();
+    let mut server_output = Vec::new();
+    let (mut client, mut server) =
+        make_pair_for_arc_configs(client_config, server_config, &mut client_output);
+    do_handshake(
+        &mut VecInput::default(),
+        &mut client_output,
+        &mut client,
+        &mut VecInput::default(),
+        &mut server_output,
+        &mut server,
+    );
+    server
+}
+
+#[test]
+fn remote_session_store_resumes() {
+    let store = StandInStore::spawn();
+
+    for provider in [
+        provider::DEFAULT_TLS12_PROVIDER,
+        provider::DEFAULT_TLS13_PROVIDER,
+    ] {
+        let client_config = Arc::new(make_client_config(KeyType::default(), &provider));
+        let mut server_config = make_server_config(KeyType::default(), &provider);
+        server_config.session_storage = Arc::new(RemoteSessionStore::new(store.client()));
+        let server_config = Arc::new(server_config);
+
+        for expected in [HandshakeKind::Full, HandshakeKind::Resumed] {
+            let mut client_output = Vec::new();
+            let mut server_output = Vec::new();
+            let (mut client, mut server) =
+                make_pair_for_arc_configs(&client_config, &server_config, &mut client_output);
+            do_handshake(
+                &mut VecInput::default(),
+                &mut client_output,
+                &mut client,
+                &mut VecInput::default(),
+                &mut server_output,
+                &mut server,
+            );
+            assert_eq!(client.handshake_kind(), Some(expected));
+            assert_eq!(server.handshake_kind(), Some(expected));
+        }
+    }
+
+    // Losing the store degrades to full handshakes.
+    store.clear();
+    let provider = provider::DEFAULT_TLS13_PROVIDER;
+    let client_config = Arc::new(make_client_config(KeyType::default(), &provider));
+    let mut server_config = make_server_config(KeyType::default(), &provider);
+    server_config.session_storage = Arc::new(RemoteSessionStore::new(StandInClient {
+        addr: "127.0.0.1:1".parse().unwrap(),
+    }));
+    let server_config = Arc::new(server_config);
+    for _ in 0..2 {
+        let mut client_output = Vec::new();
+        let mut server_output = Vec::new();
+        let (mut client, mut server) =
+            make_pair_for_arc_configs(&client_config, &server_config, &mut client_output);
+        do_handshake(
+            &mut VecInput::default(),
+            &mut client_output,
+            &mut client,
+            &mut VecInput::default(),
+            &mut server_output,
+            &mut server,
+        );
+        assert_eq!(server.handshake_kind(), Some(HandshakeKind::Full));
+    }
+}
+
+/// An in-process stand-in for a networked key-value store, such as memcached.
+///
+/// This speaks a line-based protocol over TCP, with keys and values hex-encoded:
+/// `set <key> <ttl-secs> <value>`, `get <key>` and `take <key>`.
+struct StandInStore {
+    addr: SocketAddr,
+    entries: Arc<Mutex<Entries>>,
+}
+
+impl StandInStore {
+    fn spawn() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let entries = Arc::new(Mutex::new(HashMap::new()));
+
+        let shared = entries.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                let entries = shared.clone();
+                thread::spawn(move || Self::serve(stream, &entries));
+            }
+        });
+
+        Self { addr, entries }
+    }
+
+    fn serve(stream: TcpStream, entries: &Mutex<Entries>) {
+        let mut writer = stream.try_clone().unwrap();
+        for line in BufReader::new(stream).lines() {
+            let Ok(line) = line else {
+                return;
+            };
+            let words = line.split(' ').collect::<Vec<_>>();
+            let mut entries = entries.lock().unwrap();
+            let now = Instant::now();
+            entries.retain(|_, (deadline, _)| *deadline > now);
+
+            let value = match words[..] {
+                ["set", key, ttl, value] => {
+                    let deadline = now + Duration::from_secs(ttl.parse().unwrap());
+                    entries.insert(from_hex(key), (deadline, from_hex(value)));
+                    writeln!(writer, "ok").unwrap();
+                    continue;
+                }
+                ["get", key] => entries
+                    .get(&from_hex(key))
+                    .map(|(_, value)| value.clone()),
+                ["take", key] => entries
+                    .remove(&from_hex(key))
+                    .map(|(_, value)| value),
+                _ => panic!("unexpected request {line:?}"),
+            };
+
+            match value {
+                Some(value) => writeln!(writer, "value {}", to_hex(&value)).unwrap(),
+                None => writeln!(writer, "none").unwrap(),
+            }
+        }
+    }
+
+    fn client(&self) -> StandInClient {
+        StandInClient { addr: self.addr }
+    }
+
+    fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+/// Values and the deadline after which they are deleted, by key.
+type Entries = HashMap<Vec<u8>, (Instant, Vec<u8>)>;
+
+#[derive(Debug)]
+struct StandInClient {
+    addr: SocketAddr,
+}
+
+impl StandInClient {
+    fn request(&self, request: &str) -> io::Result<String> {
+        let mut stream = TcpStream::connect(self.addr)?;
+        writeln!(stream, "{request}")?;
+        let mut response = String::new();
+        BufReader::new(stream).read_line(&mut response)?;
+        Ok(response.trim_end().to_owned())
+    }
+
+    fn fetch(&self, verb: &str, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        let response = self.request(&format!("{verb} {}", to_hex(key)))?;
+        match response.split_once(' ') {
+            Some(("value", value)) => Ok(Some(from_hex(value))),
+            _ if response == "none" => Ok(None),
+            _ => Err(io::Error::other(response)),
+        }
+    }
+}
+
+impl SessionStoreClient for StandInClient {
+    fn set(&self, key: &[u8], value: &[u8], ttl: Duration) -> io::Result<()> {
+        let response = self.request(&format!(
+            "set {} {} {}",
+            to_hex(key),
+            ttl.as_secs(),
+            to_hex(value)
+        ))?;
+        match response.as_str() {
+            "ok" => Ok(()),
+            _ => Err(io::Error::other(response)),
+        }
+    }
+
+    fn get(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        self.fetch("get", key)
+    }
+
+    fn take(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        self.fetch("take", key)
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+fn from_hex(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
@@ -9,7 +9,9 @@ use pki_types::UnixTime;
 use rustls::client::danger::{
     HandshakeSignatureValid, ServerIdentity, ServerVerifier, SignatureVerificationInput,
 };
-use rustls::client::{WebPkiServerVerifier, verify_identity_signed_by_trust_anchor};
+use rustls::client::{
+    RootHintTrimming, WebPkiServerVerifier, verify_identity_signed_by_trust_anchor,
+};
 use rustls::crypto::{
     Credentials, Identity, SelectedCredential, SignatureScheme, VerifiedIdentity,
 };
@@ -344,6 +346,101 @@ fn client_can_request_certain_trusted_cas() {
     assert_eq!(cas_unaware_error_count, key_types.len() - 1);
 }
 
+#[test]
+fn webpki_verifier_can_advertise_trusted_cas() {
+    let provider = provider::DEFAULT_PROVIDER;
+    // As above: CAs with different names, and the same sigalgs.
+    let key_types = [KeyType::Rsa2048, KeyType::Rsa3072, KeyType::Rsa4096];
+    let cert_resolver = ResolvesCertChainByCaName(
+        key_types
+            .iter()
+            .map(|kt| {
+                (
+                    kt.ca_distinguished_name(),
+                    kt.credentials_with_cert_chain(&provider)
+                        .unwrap(),
+                )
+            })
+            .collect(),
+    );
+
+    let server_config = Arc::new(
+        ServerConfig::builder(provider.clone().into())
+            .with_no_client_auth()
+            .with_server_credential_resolver(Arc::new(cert_resolver))
+            .unwrap(),
+    );
+
+    let mut too_small_error_count = 0;
+
+    for key_type in key_types {
+        let mut root_store = RootCertStore::empty();
+        root_store
+            .add(key_type.ca_cert())
+            .unwrap();
+        let root_store = Arc::new(root_store);
+
+        // The subject fits, so is advertised and the server picks a chain we trust.
+        let verifier = WebPkiServerVerifier::builder(root_store.clone(), &provider)
+            .advertise_root_hint_subjects(1024, RootHintTrimming::InOrder)
+            .build()
+            .unwrap();
+        assert_eq!(
+            verifier.root_hint_subjects().as_deref(),
+            Some(&[key_type.ca_distinguished_name()][..])
+        );
+
+        let client_config = ClientConfig::builder(provider.clone().into())
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(verifier))
+            .with_no_client_auth()
+            .unwrap();
+
+        let mut client_output = Vec::new();
+        let mut server_output = Vec::new();
+        let (mut client, mut server) =
+            make_pair_for_arc_configs(&Arc::new(client_config), &server_config, &mut client_output);
+        do_handshake(
+            &mut VecInput::default(),
+            &mut client_output,
+            &mut client,
+            &mut VecInput::default(),
+            &mut server_output,
+            &mut server,
+        );
+
+        // The subject doesn't fit, so nothing is advertised.
+        let verifier = WebPkiServerVerifier::builder(root_store, &provider)
+            .advertise_root_hint_subjects(16, RootHintTrimming::AllOrNothing)
+            .build()
+            .unwrap();
+        assert!(verifier.root_hint_subjects().is_none());
+
+        let client_config = ClientConfig::builder(provider.clone().into())
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(verifier))
+            .with_no_client_auth()
+            .unwrap();
+
+        let mut client_output = Vec::new();
+        let mut server_output = Vec::new();
+        let (mut client, mut server) =
+            make_pair_for_arc_configs(&Arc::new(client_config), &server_config, &mut client_output);
+        too_small_error_count += do_handshake_until_error(
+            &mut VecInput::default(),
+            &mut client_output,
+            &mut client,
+            &mut VecInput::default(),
+            &mut server_output,
+            &mut server,
+        )
+        .is_err() as usize;
+    }
+
+    // Without hints, only the client trusting the default chain succeeds.
+    assert_eq!(too_small_error_count, key_types.len() - 1);
+}
+
 #[test]
 fn client_checks_server_certificate_with_given_ip_address() {
     fn check_server_name(
@@ -8,7 +8,7 @@ use std::io::Cursor;
 
 use rustls::error::{AlertDescription, ApiMisuse, InvalidMessage};
 use rustls::split::{ReceiveTraffic, ReceiveTrafficState, SplitConnection};
-use rustls::{Connection, Error, SideData, SliceInput, VecInput};
+use rustls::{Connection, Error, RecordPadding, SideData, SliceInput, VecInput};
 use rustls_test::{KeyType, do_handshake, make_pair};
 
 #[test]
@@ -70,7 +70,9 @@ fn split_pairwise() {
     );
 
     let mut flight = Vec::new();
-    client_send.write(b"client to server".as_slice().into(), &mut flight);
+    client_send
+        .write(b"client to server".as_slice().into(), &mut flight)
+        .unwrap();
     server_recv = check_receive_all(
         server_recv,
         flight,
@@ -82,7 +84,9 @@ fn split_pairwise() {
     .unwrap();
 
     let mut flight = Vec::new();
-    server_send.write(b"server to client".as_slice().into(), &mut flight);
+    server_send
+        .write(b"server to client".as_slice().into(), &mut flight)
+        .unwrap();
     client_recv = check_receive_all(
         client_recv,
         flight,
@@ -94,13 +98,80 @@ fn split_pairwise() {
     .unwrap();
 
     let mut flight = Vec::new();
-    client_send.close(&mut flight);
+    client_send.close(&mut flight).unwrap();
     check_receive_all(server_recv, flight, ExpectCloseNotify);
     let mut flight = Vec::new();
-    server_send.close(&mut flight);
+    server_send.close(&mut flight).unwrap();
     check_receive_all(client_recv, flight, ExpectCloseNotify);
 }
 
+#[test]
+fn split_write_into_slice() {
+    let mut client_output = Vec::new();
+    let mut server_output = Vec::new();
+    let (mut client, mut server) = make_pair(
+        KeyType::default(),
+        &super::provider::DEFAULT_PROVIDER,
+        &mut client_output,
+    );
+    let (mut client_input, mut server_input) = (VecInput::default(), VecInput::default());
+    do_handshake(
+        &mut client_input,
+        &mut client_output,
+        &mut client,
+        &mut server_input,
+        &mut server_output,
+        &mut server,
+    );
+
+    let SplitConnection {
+        send: mut client_send,
+        receive: _,
+        outputs: _,
+    } = client.split().unwrap();
+    let SplitConnection {
+        send: _,
+        receive: mut server_recv,
+        outputs: _,
+    } = server.split().unwrap();
+
+    let mut small = [0u8; 8];
+    let err = client_send
+        .write(b"client to server".as_slice().into(), &mut &mut small[..])
+        .unwrap_err();
+    let Error::InsufficientOutputSpace { required } = err else {
+        panic!("unexpected error {err:?}");
+    };
+
+    let mut flight = vec![0u8; required];
+    client_send
+        .write(b"client to server".as_slice().into(), &mut &mut flight[..])
+        .unwrap();
+    server_recv = check_receive_all(
+        server_recv,
+        flight,
+        ExpectData {
+            expected: b"client to server",
+            then: ExpectReadMore,
+        },
+    )
+    .unwrap();
+
+    // A failed close hands back the sender, so it can be retried.
+    let (client_send, err) = client_send
+        .close(&mut &mut small[..2])
+        .unwrap_err();
+    let Error::InsufficientOutputSpace { required } = err else {
+        panic!("unexpected error {err:?}");
+    };
+
+    let mut flight = vec![0u8; required];
+    client_send
+        .close(&mut &mut flight[..])
+        .unwrap();
+    check_receive_all(server_recv, flight, ExpectCloseNotify);
+}
+
 #[test]
 fn split_incremental() {
     let mut client_output = Vec::new();
@@ -132,7 +203,9 @@ fn split_incremental() {
     } = server.split().unwrap();
 
     let mut flight = Vec::new();
-    client_send.write(b"client to server".as_slice().into(), &mut flight);
+    client_send
+        .write(b"client to server".as_slice().into(), &mut flight)
+        .unwrap();
 
     // messages are not consumed until they are fully provided.
     for ll in 1..flight.len() - 1 {
@@ -150,6 +223,140 @@ fn split_incremental() {
     );
 }
 
+#[test]
+fn split_read_vectored() {
+    let mut client_output = Vec::new();
+    let mut server_output = Vec::new();
+    let (mut client, mut server) = make_pair(
+        KeyType::default(),
+        &super::provider::DEFAULT_PROVIDER,
+        &mut client_output,
+    );
+    let (mut client_input, mut server_input) = (VecInput::default(), VecInput::default());
+    do_handshake(
+        &mut client_input,
+        &mut client_output,
+        &mut client,
+        &mut server_input,
+        &mut server_output,
+        &mut server,
+    );
+
+    let SplitConnection {
+        send: mut client_send,
+        receive: _,
+        outputs: _,
+    } = client.split().unwrap();
+    let SplitConnection {
+        send: _,
+        receive: server_recv,
+        outputs: _,
+    } = server.split().unwrap();
+
+    let large = vec![0x55u8; 40_000];
+    let mut flight = Vec::new();
+    for data in [&b"one"[..], b"two", &large] {
+        client_send
+            .write(data.into(), &mut flight)
+            .unwrap();
+    }
+    let data_len = flight.len();
+    client_send.close(&mut flight).unwrap();
+
+    // A partial record is left for later.
+    let mut chunk = flight[..flight.len() - 1].to_vec();
+    let mut inp = SliceInput::new(&mut chunk);
+    let server_recv = match server_recv
+        .read_vectored(&mut inp, &mut Vec::new())
+        .unwrap()
+    {
+        ReceiveTrafficState::Available(mut received) => {
+            let chunks = received.chunks().collect::<Vec<_>>();
+            assert!(chunks.len() > 3);
+            assert_eq!(chunks[0], b"one");
+            assert_eq!(chunks[1], b"two");
+            assert_eq!(chunks[2..].concat(), large);
+            assert_eq!(received.data(), b"one");
+            match received.into_next() {
+                ReceiveTrafficState::ReadMore(recv) => recv,
+                other => panic!("unexpected state {other:?}"),
+            }
+        }
+        other => panic!("unexpected state {other:?}"),
+    };
+    assert_eq!(inp.into_used(), data_len);
+
+    check_receive_all(server_recv, flight[data_len..].to_vec(), ExpectCloseNotify);
+}
+
+#[test]
+fn split_write_padded() {
+    let mut client_output = Vec::new();
+    let mut server_output = Vec::new();
+    let (mut client, mut server) = make_pair(
+        KeyType::default(),
+        &super::provider::DEFAULT_PROVIDER,
+        &mut client_output,
+    );
+    let (mut client_input, mut server_input) = (VecInput::default(), VecInput::default());
+    do_handshake(
+        &mut client_input,
+        &mut client_output,
+        &mut client,
+        &mut server_input,
+        &mut server_output,
+        &mut server,
+    );
+
+    let SplitConnection {
+        send: mut client_send,
+        receive: _,
+        outputs: _,
+    } = client.split().unwrap();
+    let SplitConnection {
+        send: _,
+        receive: mut server_recv,
+        outputs: _,
+    } = server.split().unwrap();
+
+    // header, inner content type and AEAD tag
+    const OVERHEAD: usize = 5 + 1 + 16;
+
+    let mut flight = Vec::new();
+    client_send
+        .write_padded(
+            b"padded".as_slice().into(),
+            &RecordPadding::BlockSize(64),
+            &mut flight,
+        )
+        .unwrap();
+    assert_eq!(flight.len(), 64 + OVERHEAD);
+    server_recv = check_receive_all(
+        server_recv,
+        flight,
+        ExpectData {
+            expected: b"padded",
+            then: ExpectReadMore,
+        },
+    )
+    .unwrap();
+
+    // the override applies only to that write
+    let mut flight = Vec::new();
+    client_send
+        .write(b"unpadded".as_slice().into(), &mut flight)
+        .unwrap();
+    assert_eq!(flight.len(), 8 + OVERHEAD);
+    check_receive_all(
+        server_recv,
+        flight,
+        ExpectData {
+            expected: b"unpadded",
+            then: ExpectReadMore,
+        },
+    );
+}
+
 #[test]
 fn split_client_tickets_received() {
     let mut client_output = Vec::new();
@@ -241,7 +448,9 @@ fn key_update() {
     .unwrap();
 
     let mut flight = Vec::new();
-    server_send.write(b"server to client".as_slice().into(), &mut flight);
+    server_send
+        .write(b"server to client".as_slice().into(), &mut flight)
+        .unwrap();
     check_receive_all(
         client_recv,
         flight,
@@ -252,7 +461,9 @@ fn key_update() {
     );
 
     let mut flight = Vec::new();
-    client_send.write(b"client to server".as_slice().into(), &mut flight);
+    client_send
+        .write(b"client to server".as_slice().into(), &mut flight)
+        .unwrap();
     check_receive_all(
         server_recv,
         flight,
@@ -297,7 +508,9 @@ fn key_update_alongside_data() {
     client_send
         .refresh_traffic_keys(&mut flight)
         .unwrap();
-    client_send.write(b"client to server".as_slice().into(), &mut flight);
+    client_send
+        .write(b"client to server".as_slice().into(), &mut flight)
+        .unwrap();
     check_receive_all(
         server_recv,
         flight,
@@ -339,8 +552,10 @@ fn close_alongside_data() {
     } = server.split().unwrap();
 
     let mut flight = Vec::new();
-    client_send.write(b"client to server".as_slice().into(), &mut flight);
-    client_send.close(&mut flight);
+    client_send
+        .write(b"client to server".as_slice().into(), &mut flight)
+        .unwrap();
+    client_send.close(&mut flight).unwrap();
     flight.extend(b"rubbish");
 
     // receive of appdata does not consume subsequent data
@@ -386,10 +601,10 @@ fn read_invalid_data_and_send_alert() {
         err.error,
         Error::InvalidMessage(InvalidMessage::InvalidContentType)
     );
-    assert!(!err.tls().is_empty());
+    assert!(!alert.is_empty());
 
     server_input
-        .read(&mut Cursor::new(err.tls()))
+        .read(&mut Cursor::new(&alert))
         .unwrap();
     assert_eq!(
         server
@@ -0,0 +1,631 @@
+//! Runtime-independent machinery shared by the `futures_io` and `tokio` adapters.
+//!
+//! Each adapter wraps its runtime's I/O type in a [`Transport`], and the types here
+//! drive a rustls connection over that transport using only non-blocking polls.
+
+use core::mem;
+use core::task::{Context, Poll, ready};
+use std::io::{self, Read};
+use std::sync::{Arc, Mutex};
+
+use rustls::crypto::cipher::OutboundPlain;
+use rustls::server::{
+    Accepted, ClientHello, NeedsInput, ServerConfig, ServerHandshake, ServerSide,
+};
+use rustls::split::{ReceiveTraffic, ReceiveTrafficState, SendTraffic, SplitConnection};
+use rustls::{Connection, SideData, TlsInputBuffer, VecInput};
+
+/// A non-blocking byte stream, as provided by an async runtime.
+pub(crate) trait Transport: Unpin {
+    fn poll_read(&mut self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>>;
+
+    fn poll_write(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>>;
+
+    fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+
+    fn poll_close(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+}
+
+/// A TLS connection `C` over the transport `T`.
+pub(crate) struct TlsStream<C, T> {
+    pub(crate) conn: C,
+    pub(crate) io: T,
+    input: VecInput,
+    output: Vec<u8>,
+    plaintext: Vec<u8>,
+    peer_closed: bool,
+    sent_close: bool,
+}
+
+impl<C: Connection, T: Transport> TlsStream<C, T> {
+    /// Make a new stream, where `output` holds any TLS data `conn` has already produced.
+    pub(crate) fn new(conn: C, io: T, output: Vec<u8>) -> Self {
+        Self {
+            conn,
+            io,
+            input: VecInput::default(),
+            output,
+            plaintext: Vec::new(),
+            peer_closed: false,
+            sent_close: false,
+        }
+    }
+
+    /// Perform I/O until the handshake is complete.
+    pub(crate) fn poll_handshake(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        loop {
+            ready!(poll_drain(&mut self.io, &mut self.output, cx))?;
+            if !self.conn.is_handshaking() {
+                return Poll::Ready(Ok(()));
+            }
+
+            if ready!(poll_fill(&mut self.input, &mut self.io, cx))? == 0 {
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "peer closed connection during TLS handshake",
+                )));
+            }
+            self.process_new_packets(cx)?;
+        }
+    }
+
+    pub(crate) fn poll_read(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        ready!(self.poll_handshake(cx))?;
+
+        loop {
+            if !self.plaintext.is_empty() || buf.is_empty() {
+                return Poll::Ready(Ok(take_plaintext(&mut self.plaintext, buf)));
+            } else if self.peer_closed {
+                return Poll::Ready(Ok(0));
+            }
+
+            // Start sending anything produced by earlier records (such as a key update
+            // response), but don't wait for it.
+            if let Poll::Ready(Err(err)) = poll_drain(&mut self.io, &mut self.output, cx) {
+                return Poll::Ready(Err(err));
+            }
+
+            if ready!(poll_fill(&mut self.input, &mut self.io, cx))? == 0
+                && self.input.has_seen_eof()
+            {
+                return Poll::Ready(Err(unexpected_eof()));
+            }
+            self.process_new_packets(cx)?;
+        }
+    }
+
+    pub(crate) fn poll_write(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        ready!(self.poll_handshake(cx))?;
+
+        // Apply backpressure: don't encrypt more until earlier data has been sent.
+        ready!(poll_drain(&mut self.io, &mut self.output, cx))?;
+
+        let buf = &buf[..Ord::min(buf.len(), MAX_WRITE)];
+        self.conn
+            .write_tls(buf.into(), &mut self.output)
+            .map_err(io::Error::other)?;
+
+        if let Poll::Ready(Err(err)) = poll_drain(&mut self.io, &mut self.output, cx) {
+            return Poll::Ready(Err(err));
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    pub(crate) fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.poll_handshake(cx))?;
+        ready!(poll_drain(&mut self.io, &mut self.output, cx))?;
+        self.io.poll_flush(cx)
+    }
+
+    /// Send a `close_notify` alert, then close the transport.
+    pub(crate) fn poll_close(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if !self.sent_close {
+            self.conn
+                .send_close_notify(&mut self.output)
+                .map_err(io::Error::other)?;
+            self.sent_close = true;
+        }
+
+        ready!(poll_drain(&mut self.io, &mut self.output, cx))?;
+        self.io.poll_close(cx)
+    }
+
+    /// Split into halves that can be used independently, given the `split` of `conn`.
+    pub(crate) fn split<Side: SideData>(
+        self,
+        split: impl FnOnce(C) -> Result<SplitConnection<Side>, rustls::Error>,
+    ) -> io::Result<(ReadHalf<Side, T>, WriteHalf<T>)> {
+        let Self {
+            conn,
+            io,
+            input,
+            output,
+            plaintext,
+            peer_closed,
+            sent_close,
+        } = self;
+
+        let SplitConnection { send, receive, .. } = split(conn).map_err(io::Error::other)?;
+        let send = match sent_close {
+            true => None,
+            false => Some(send),
+        };
+
+        let (mut read, write) = halves(io, send, receive, input, output);
+        read.plaintext = plaintext;
+        read.peer_closed = peer_closed;
+        Ok((read, write))
+    }
+
+    fn process_new_packets(&mut self, cx: &mut Context<'_>) -> io::Result<()> {
+        match self
+            .conn
+            .process_new_packets(&mut self.input, &mut self.output)
+            .handle_all(&mut self.plaintext)
+        {
+            Ok(state) => {
+                self.peer_closed |= state.peer_has_closed();
+                Ok(())
+            }
+            Err(err) => {
+                // Try to send the alert describing this error, but don't wait for it.
+                let _ = poll_drain(&mut self.io, &mut self.output, cx);
+                Err(io::Error::new(io::ErrorKind::InvalidData, err))
+            }
+        }
+    }
+}
+
+/// Receives the `ClientHello` of a connection, to choose a [`ServerConfig`] for it.
+pub(crate) struct Acceptor<T> {
+    io: Option<T>,
+    input: VecInput,
+    output: Vec<u8>,
+    handshake: Option<NeedsInput>,
+}
+
+impl<T: Transport> Acceptor<T> {
+    pub(crate) fn new(io: T) -> Self {
+        Self {
+            io: Some(io),
+            input: VecInput::default(),
+            output: Vec::new(),
+            handshake: Some(ServerHandshake::start()),
+        }
+    }
+
+    pub(crate) fn poll_accept(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<StartHandshake<T>>> {
+        let Some(io) = &mut self.io else {
+            return Poll::Ready(Err(io::Error::other("acceptor polled after completion")));
+        };
+
+        loop {
+            ready!(poll_drain(io, &mut self.output, cx))?;
+
+            let Some(handshake) = self.handshake.take() else {
+                return Poll::Ready(Err(io::Error::other("acceptor failed earlier")));
+            };
+
+            match handshake.process(&mut self.input, &mut self.output) {
+                Ok(ServerHandshake::NeedsInput(handshake)) => {
+                    self.handshake = Some(handshake);
+                }
+                Ok(ServerHandshake::Accepted(accepted)) => {
+                    return Poll::Ready(Ok(StartHandshake {
+                        accepted,
+                        io: self.io.take().unwrap(),
+                        input: mem::take(&mut self.input),
+                        output: mem::take(&mut self.output),
+                    }));
+                }
+                Ok(_) => {
+                    return Poll::Ready(Err(io::Error::other(
+                        "unexpected server handshake state before ClientHello",
+                    )));
+                }
+                Err(err) => {
+                    let _ = poll_drain(io, &mut self.output, cx);
+                    return Poll::Ready(Err(io::Error::new(io::ErrorKind::InvalidData, err)));
+                }
+            }
+
+            if ready!(poll_fill(&mut self.input, io, cx))? == 0 {
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "peer closed connection before sending ClientHello",
+                )));
+            }
+        }
+    }
+}
+
+/// A connection for which a `ClientHello` has been received.
+pub(crate) struct StartHandshake<T> {
+    accepted: Accepted,
+    io: T,
+    input: VecInput,
+    output: Vec<u8>,
+}
+
+impl<T: Transport> StartHandshake<T> {
+    pub(crate) fn client_hello(&self) -> ClientHello<'_> {
+        self.accepted.client_hello()
+    }
+
+    pub(crate) fn into_handshake(self, config: Arc<ServerConfig>) -> ServerHandshaker<T> {
+        let Self {
+            accepted,
+            io,
+            input,
+            mut output,
+        } = self;
+
+        let (handshake, error) = match accepted.choose_config(config, &mut output) {
+            Ok(handshake) => (Some(handshake), None),
+            Err(err) => (None, Some(err)),
+        };
+
+        ServerHandshaker {
+            io: Some(io),
+            input,
+            output,
+            handshake,
+            error,
+            needs_read: false,
+        }
+    }
+}
+
+/// Completes a server handshake started with [`Acceptor`].
+pub(crate) struct ServerHandshaker<T> {
+    io: Option<T>,
+    input: VecInput,
+    output: Vec<u8>,
+    handshake: Option<ServerHandshake>,
+    error: Option<rustls::Error>,
+    needs_read: bool,
+}
+
+impl<T: Transport> ServerHandshaker<T> {
+    pub(crate) fn poll_complete(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<ServerHalves<T>>> {
+        let Some(io) = &mut self.io else {
+            return Poll::Ready(Err(io::Error::other("handshake polled after completion")));
+        };
+
+        loop {
+            ready!(poll_drain(io, &mut self.output, cx))?;
+
+            if let Some(err) = self.error.take() {
+                return Poll::Ready(Err(io::Error::new(io::ErrorKind::InvalidData, err)));
+            }
+
+            let Some(handshake) = self.handshake.take() else {
+                return Poll::Ready(Err(io::Error::other("handshake failed earlier")));
+            };
+
+            let next = match handshake {
+                ServerHandshake::NeedsInput(handshake) if self.needs_read => {
+                    self.handshake = Some(ServerHandshake::NeedsInput(handshake));
+                    if ready!(poll_fill(&mut self.input, io, cx))? == 0 {
+                        return Poll::Ready(Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "peer closed connection during TLS handshake",
+                        )));
+                    }
+                    self.needs_read = false;
+                    continue;
+                }
+                ServerHandshake::NeedsInput(handshake) => {
+                    let next = handshake.process(&mut self.input, &mut self.output);
+                    self.needs_read = matches!(next, Ok(ServerHandshake::NeedsInput(_)));
+                    next
+                }
+                ServerHandshake::CompleteKeyExchange(key_exchange) => {
+                    key_exchange.use_kx_group(&mut self.output)
+                }
+                ServerHandshake::VerifyClientIdentity(verify) => {
+                    verify.use_verifier_trait(&mut self.output)
+                }
+                ServerHandshake::Complete(SplitConnection { send, receive, .. }) => {
+                    let io = self.io.take().unwrap();
+                    return Poll::Ready(Ok(halves(
+                        io,
+                        Some(send),
+                        receive,
+                        mem::take(&mut self.input),
+                        mem::take(&mut self.output),
+                    )));
+                }
+                _ => Err(rustls::Error::General(
+                    "unexpected server handshake state".into(),
+                )),
+            };
+
+            match next {
+                Ok(handshake) => self.handshake = Some(handshake),
+                Err(err) => self.error = Some(err),
+            }
+        }
+    }
+}
+
+/// State shared between a [`ReadHalf`] and a [`WriteHalf`].
+struct Shared<T> {
+    io: T,
+    output: Vec<u8>,
+    /// `None` once a `close_notify` has been sent.
+    send: Option<SendTraffic>,
+}
+
+fn halves<Side: SideData, T>(
+    io: T,
+    send: Option<SendTraffic>,
+    receive: ReceiveTraffic<Side>,
+    input: VecInput,
+    output: Vec<u8>,
+) -> (ReadHalf<Side, T>, WriteHalf<T>) {
+    let shared = Arc::new(Mutex::new(Shared { io, output, send }));
+    (
+        ReadHalf {
+            shared: shared.clone(),
+            receive: Some(receive),
+            input,
+            plaintext: Vec::new(),
+            peer_closed: false,
+        },
+        WriteHalf { shared },
+    )
+}
+
+pub(crate) type ServerHalves<T> = (ReadHalf<ServerSide, T>, WriteHalf<T>);
+
+/// The receiving half of a split TLS connection.
+pub(crate) struct ReadHalf<Side: SideData, T> {
+    shared: Arc<Mutex<Shared<T>>>,
+    /// `None` after an error.
+    receive: Option<ReceiveTraffic<Side>>,
+    input: VecInput,
+    plaintext: Vec<u8>,
+    peer_closed: bool,
+}
+
+impl<Side: SideData, T: Transport> ReadHalf<Side, T> {
+    pub(crate) fn poll_read(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            if !self.plaintext.is_empty() || buf.is_empty() {
+                return Poll::Ready(Ok(take_plaintext(&mut self.plaintext, buf)));
+            } else if self.peer_closed {
+                return Poll::Ready(Ok(0));
+            }
+
+            let Some(receive) = self.receive.take() else {
+                return Poll::Ready(Err(io::Error::other("connection failed earlier")));
+            };
+
+            let mut shared = self.shared.lock().unwrap();
+            let Shared { io, output, send } = &mut *shared;
+
+            let state = match receive.read(&mut self.input, output) {
+                Ok(state) => state,
+                Err(err) => {
+                    // Try to send the alert describing this error, but don't wait for it.
+                    let _ = poll_drain(io, output, cx);
+                    return Poll::Ready(Err(io::Error::new(io::ErrorKind::InvalidData, err.error)));
+                }
+            };
+
+            let mut next = receive_step(state, &mut self.plaintext, send, output)?;
+            loop {
+                next = match next {
+                    ReceiveStep::Next(state) => {
+                        receive_step(state, &mut self.plaintext, send, output)?
+                    }
+                    ReceiveStep::ReadMore(receive) => {
+                        self.receive = Some(receive);
+                        break;
+                    }
+                    ReceiveStep::CloseNotify => {
+                        self.peer_closed = true;
+                        break;
+                    }
+                };
+            }
+
+            if !self.plaintext.is_empty() || self.peer_closed {
+                continue;
+            }
+
+            // Start sending anything produced by the received records (such as a key
+            // update response), but don't wait for it.
+            if let Poll::Ready(Err(err)) = poll_drain(io, output, cx) {
+                return Poll::Ready(Err(err));
+            }
+
+            if ready!(poll_fill(&mut self.input, io, cx))? == 0 && self.input.has_seen_eof() {
+                return Poll::Ready(Err(unexpected_eof()));
+            }
+        }
+    }
+}
+
+/// Handle one [`ReceiveTrafficState`], appending any received data to `plaintext`.
+fn receive_step<Side: SideData>(
+    state: ReceiveTrafficState<'_, Side>,
+    plaintext: &mut Vec<u8>,
+    send: &mut Option<SendTraffic>,
+    output: &mut Vec<u8>,
+) -> io::Result<ReceiveStep<Side>> {
+    Ok(match state {
+        ReceiveTrafficState::Available(mut data) => {
+            plaintext.extend_from_slice(data.data());
+            ReceiveStep::Next(data.into_next())
+        }
+        ReceiveTrafficState::FlushSender(flush) => {
+            // Nothing more may be sent after our `close_notify`.
+            if let Some(send) = send {
+                send.write(OutboundPlain::new_empty(), output)
+                    .map_err(io::Error::other)?;
+            }
+            ReceiveStep::Next(flush.into_next())
+        }
+        ReceiveTrafficState::ReadMore(receive) => ReceiveStep::ReadMore(receive),
+        ReceiveTrafficState::CloseNotify => ReceiveStep::CloseNotify,
+    })
+}
+
+enum ReceiveStep<Side: SideData> {
+    Next(ReceiveTrafficState<'static, Side>),
+    ReadMore(ReceiveTraffic<Side>),
+    CloseNotify,
+}
+
+/// The sending half of a split TLS connection.
+pub(crate) struct WriteHalf<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+impl<T: Transport> WriteHalf<T> {
+    pub(crate) fn poll_write(&self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let mut shared = self.shared.lock().unwrap();
+        let Shared { io, output, send } = &mut *shared;
+
+        // Apply backpressure: don't encrypt more until earlier data has been sent.
+        ready!(poll_drain(io, output, cx))?;
+
+        let Some(send) = send else {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "write after TLS close_notify was sent",
+            )));
+        };
+
+        let buf = &buf[..Ord::min(buf.len(), MAX_WRITE)];
+        send.write(buf.into(), output)
+            .map_err(io::Error::other)?;
+
+        if let Poll::Ready(Err(err)) = poll_drain(io, output, cx) {
+            return Poll::Ready(Err(err));
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    pub(crate) fn poll_flush(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut shared = self.shared.lock().unwrap();
+        let Shared { io, output, .. } = &mut *shared;
+        ready!(poll_drain(io, output, cx))?;
+        io.poll_flush(cx)
+    }
+
+    /// Send a `close_notify` alert, then close the transport.
+    ///
+    /// The [`ReadHalf`] can still receive data until the peer closes its side.
+    pub(crate) fn poll_close(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut shared = self.shared.lock().unwrap();
+        let Shared { io, output, send } = &mut *shared;
+
+        if let Some(send) = send.take() {
+            send.close(output)
+                .map_err(|(_, err)| io::Error::other(err))?;
+        }
+
+        ready!(poll_drain(io, output, cx))?;
+        io.poll_close(cx)
+    }
+}
+
+/// Write `output` to `io`, draining the bytes that were written.
+fn poll_drain<T: Transport>(
+    io: &mut T,
+    output: &mut Vec<u8>,
+    cx: &mut Context<'_>,
+) -> Poll<io::Result<()>> {
+    while !output.is_empty() {
+        match ready!(io.poll_write(cx, output))? {
+            0 => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+            n if n > output.len() => {
+                // This is really unrecoverable, since the amount of data written
+                // is now unknown.  Consume all the potentially-written data in
+                // case the caller ignores the error.
+                // See <https://github.com/rustls/rustls/issues/2316> for background.
+                let available = output.len();
+                output.clear();
+                return Poll::Ready(Err(io::Error::other(format!(
+                    "illegal write() return value ({n} > {available})"
+                ))));
+            }
+            n => {
+                output.drain(..n);
+            }
+        }
+    }
+
+    Poll::Ready(Ok(()))
+}
+
+/// Read more TLS data from `io` into `input`.
+fn poll_fill<T: Transport>(
+    input: &mut VecInput,
+    io: &mut T,
+    cx: &mut Context<'_>,
+) -> Poll<io::Result<usize>> {
+    loop {
+        match input.read(&mut SyncReader { io, cx }) {
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Poll::Pending,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            result => return Poll::Ready(result),
+        }
+    }
+}
+
+/// Adapts a [`Transport`] to [`io::Read`], for [`VecInput::read()`].
+///
+/// A pending read is reported as [`io::ErrorKind::WouldBlock`].
+struct SyncReader<'a, 'b, T> {
+    io: &'a mut T,
+    cx: &'a mut Context<'b>,
+}
+
+impl<T: Transport> Read for SyncReader<'_, '_, T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.io.poll_read(self.cx, buf) {
+            Poll::Ready(result) => result,
+            Poll::Pending => Err(io::ErrorKind::WouldBlock.into()),
+        }
+    }
+}
+
+/// Move as much of `plaintext` as fits into `buf`.
+fn take_plaintext(plaintext: &mut Vec<u8>, buf: &mut [u8]) -> usize {
+    let len = Ord::min(plaintext.len(), buf.len());
+    buf[..len].copy_from_slice(&plaintext[..len]);
+    plaintext.drain(..len);
+    len
+}
+
+fn unexpected_eof() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "peer closed connection without sending TLS close_notify",
+    )
+}
+
+/// The most plaintext encrypted by a single write.
+const MAX_WRITE: usize = 64 * 1024;
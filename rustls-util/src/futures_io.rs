@@ -0,0 +1,268 @@
+//! Adapters for using rustls with [`futures_io`] I/O types.
+//!
+//! Use [`connect()`] or [`accept()`] to complete a handshake over any
+//! [`AsyncRead`] + [`AsyncWrite`] transport, and then use the resulting [`TlsStream`]
+//! like the transport itself.  [`TlsStream::into_split()`] separates the stream into
+//! halves which can be used concurrently.
+//!
+//! For servers that need to see the `ClientHello` before choosing a [`ServerConfig`],
+//! use [`start_handshake()`] and then [`StartHandshake::complete()`].
+//!
+//! Closing a [`TlsStream`] or [`WriteHalf`] sends a `close_notify` alert to the peer.
+//! Reads return end-of-file once the peer's `close_notify` is received; if the transport
+//! reaches end-of-file without one, an [`io::ErrorKind::UnexpectedEof`] error is returned.
+
+use core::fmt;
+use core::future::poll_fn;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use std::io;
+use std::sync::Arc;
+
+use ::futures_io::{AsyncRead, AsyncWrite};
+use rustls::client::{ClientConnection, ClientSide};
+use rustls::pki_types::ServerName;
+use rustls::server::{ClientHello, ServerConfig, ServerConnection, ServerSide};
+use rustls::{ClientConfig, Connection, SideData};
+
+use crate::async_io;
+
+/// Perform a TLS handshake as a client over `io`, connecting to `name`.
+pub async fn connect<IO: AsyncRead + AsyncWrite + Unpin>(
+    config: Arc<ClientConfig>,
+    name: ServerName<'static>,
+    io: IO,
+) -> io::Result<TlsStream<ClientConnection, IO>> {
+    let mut output = Vec::new();
+    let conn = config
+        .connect(name)
+        .build(&mut output)
+        .map_err(io::Error::other)?;
+    TlsStream::handshake(async_io::TlsStream::new(conn, Io(io), output)).await
+}
+
+/// Perform a TLS handshake as a server over `io`.
+pub async fn accept<IO: AsyncRead + AsyncWrite + Unpin>(
+    config: Arc<ServerConfig>,
+    io: IO,
+) -> io::Result<TlsStream<ServerConnection, IO>> {
+    let conn = ServerConnection::new(config).map_err(io::Error::other)?;
+    TlsStream::handshake(async_io::TlsStream::new(conn, Io(io), Vec::new())).await
+}
+
+/// Receive a `ClientHello` over `io`.
+///
+/// The returned [`StartHandshake`] allows the `ClientHello` to be inspected before
+/// choosing a [`ServerConfig`] for the connection.
+pub async fn start_handshake<IO: AsyncRead + AsyncWrite + Unpin>(
+    io: IO,
+) -> io::Result<StartHandshake<IO>> {
+    let mut acceptor = async_io::Acceptor::new(Io(io));
+    Ok(StartHandshake(
+        poll_fn(|cx| acceptor.poll_accept(cx)).await?,
+    ))
+}
+
+/// A TLS connection `C` over the transport `IO`.
+///
+/// This is returned by [`connect()`] and [`accept()`] once the handshake is complete.
+pub struct TlsStream<C, IO>(async_io::TlsStream<C, Io<IO>>);
+
+impl<C: Connection, IO: AsyncRead + AsyncWrite + Unpin> TlsStream<C, IO> {
+    async fn handshake(mut inner: async_io::TlsStream<C, Io<IO>>) -> io::Result<Self> {
+        poll_fn(|cx| inner.poll_handshake(cx)).await?;
+        Ok(Self(inner))
+    }
+}
+
+impl<C, IO> TlsStream<C, IO> {
+    /// Return the connection and the underlying transport.
+    pub fn get_ref(&self) -> (&C, &IO) {
+        (&self.0.conn, &self.0.io.0)
+    }
+
+    /// Return the connection and the underlying transport, mutably.
+    ///
+    /// Reading or writing the transport directly will corrupt the TLS stream.
+    pub fn get_mut(&mut self) -> (&mut C, &mut IO) {
+        (&mut self.0.conn, &mut self.0.io.0)
+    }
+}
+
+impl<IO: AsyncRead + AsyncWrite + Unpin> TlsStream<ClientConnection, IO> {
+    /// Split into halves which can be used concurrently.
+    ///
+    /// These are built on [`SendTraffic`] and [`ReceiveTraffic`], and share `IO`.
+    ///
+    /// [`SendTraffic`]: rustls::split::SendTraffic
+    /// [`ReceiveTraffic`]: rustls::split::ReceiveTraffic
+    pub fn into_split(self) -> io::Result<(ReadHalf<ClientSide, IO>, WriteHalf<IO>)> {
+        let (read, write) = self.0.split(ClientConnection::split)?;
+        Ok((ReadHalf(read), WriteHalf(write)))
+    }
+}
+
+impl<IO: AsyncRead + AsyncWrite + Unpin> TlsStream<ServerConnection, IO> {
+    /// Split into halves which can be used concurrently.
+    ///
+    /// These are built on [`SendTraffic`] and [`ReceiveTraffic`], and share `IO`.
+    ///
+    /// [`SendTraffic`]: rustls::split::SendTraffic
+    /// [`ReceiveTraffic`]: rustls::split::ReceiveTraffic
+    pub fn into_split(self) -> io::Result<(ReadHalf<ServerSide, IO>, WriteHalf<IO>)> {
+        let (read, write) = self.0.split(ServerConnection::split)?;
+        Ok((ReadHalf(read), WriteHalf(write)))
+    }
+}
+
+// Nothing is pinned structurally.
+impl<C, IO> Unpin for TlsStream<C, IO> {}
+
+impl<C: Connection, IO: AsyncRead + AsyncWrite + Unpin> AsyncRead for TlsStream<C, IO> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().0.poll_read(cx, buf)
+    }
+}
+
+impl<C: Connection, IO: AsyncRead + AsyncWrite + Unpin> AsyncWrite for TlsStream<C, IO> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().0.poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().0.poll_flush(cx)
+    }
+
+    /// Send a `close_notify` alert to the peer, and then close the transport.
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().0.poll_close(cx)
+    }
+}
+
+impl<C: fmt::Debug, IO: fmt::Debug> fmt::Debug for TlsStream<C, IO> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsStream")
+            .field("conn", &self.0.conn)
+            .field("io", &self.0.io.0)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A connection for which a `ClientHello` has been received.
+///
+/// This is returned by [`start_handshake()`].
+pub struct StartHandshake<IO>(async_io::StartHandshake<Io<IO>>);
+
+impl<IO: AsyncRead + AsyncWrite + Unpin> StartHandshake<IO> {
+    /// Get the received [`ClientHello`].
+    pub fn client_hello(&self) -> ClientHello<'_> {
+        self.0.client_hello()
+    }
+
+    /// Complete the handshake using `config`.
+    ///
+    /// The established connection is returned as a pair of halves which can be
+    /// used concurrently.
+    pub async fn complete(
+        self,
+        config: Arc<ServerConfig>,
+    ) -> io::Result<(ReadHalf<ServerSide, IO>, WriteHalf<IO>)> {
+        let mut handshake = self.0.into_handshake(config);
+        let (read, write) = poll_fn(|cx| handshake.poll_complete(cx)).await?;
+        Ok((ReadHalf(read), WriteHalf(write)))
+    }
+}
+
+impl<IO> fmt::Debug for StartHandshake<IO> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StartHandshake")
+            .finish_non_exhaustive()
+    }
+}
+
+/// The receiving half of a TLS connection.
+///
+/// This is returned by [`TlsStream::into_split()`] and [`StartHandshake::complete()`].
+pub struct ReadHalf<Side: SideData, IO>(async_io::ReadHalf<Side, Io<IO>>);
+
+// Nothing is pinned structurally.
+impl<Side: SideData, IO> Unpin for ReadHalf<Side, IO> {}
+
+impl<Side: SideData, IO: AsyncRead + AsyncWrite + Unpin> AsyncRead for ReadHalf<Side, IO> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().0.poll_read(cx, buf)
+    }
+}
+
+impl<Side: SideData, IO> fmt::Debug for ReadHalf<Side, IO> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadHalf")
+            .finish_non_exhaustive()
+    }
+}
+
+/// The sending half of a TLS connection.
+///
+/// This is returned by [`TlsStream::into_split()`] and [`StartHandshake::complete()`].
+pub struct WriteHalf<IO>(async_io::WriteHalf<Io<IO>>);
+
+impl<IO: AsyncRead + AsyncWrite + Unpin> AsyncWrite for WriteHalf<IO> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().0.poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().0.poll_flush(cx)
+    }
+
+    /// Send a `close_notify` alert to the peer, and then close the transport.
+    ///
+    /// The [`ReadHalf`] continues to receive data until the peer closes the connection.
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().0.poll_close(cx)
+    }
+}
+
+impl<IO> fmt::Debug for WriteHalf<IO> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WriteHalf")
+            .finish_non_exhaustive()
+    }
+}
+
+/// A `futures-io` transport.
+struct Io<IO>(IO);
+
+impl<IO: AsyncRead + AsyncWrite + Unpin> async_io::Transport for Io<IO> {
+    fn poll_read(&mut self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+
+    fn poll_write(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_close(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_close(cx)
+    }
+}
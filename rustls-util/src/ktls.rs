@@ -0,0 +1,601 @@
+//! Linux kernel TLS (kTLS) offload.
+//!
+//! Once rustls has completed a handshake, [`KtlsStream`] installs the connection's
+//! traffic secrets on its [`TcpStream`] so that the kernel encrypts and decrypts
+//! application data.  Reading and writing a [`KtlsStream`] then uses ordinary socket
+//! calls, which allows `sendfile()` and `splice()` to be used on the socket too.
+//!
+//! Records other than application data are still handled here: a received
+//! `close_notify` alert ends the stream, other alerts are reported as errors,
+//! TLS1.3 `KeyUpdate` messages rekey the kernel, and TLS1.3 `NewSessionTicket`
+//! messages are passed to rustls so the ticket can be used for resumption.
+//!
+//! ```no_run
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let config: std::sync::Arc<rustls::ClientConfig> = todo!();
+//! use std::net::TcpStream;
+//!
+//! use rustls::VecInput;
+//! use rustls::pki_types::ServerName;
+//! use rustls_util::ktls::KtlsStream;
+//!
+//! // `config` must have `enable_secret_extraction` set.
+//! let mut socket = TcpStream::connect("example.com:443")?;
+//! let mut output = Vec::new();
+//! let mut conn = config
+//!     .connect(ServerName::try_from("example.com")?)
+//!     .build(&mut output)?;
+//! let mut input = VecInput::default();
+//! rustls_util::complete_io(&mut socket, &mut input, &mut Vec::new(), &mut output, &mut conn)?;
+//!
+//! let stream = KtlsStream::client(socket, conn.split()?)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! This requires a kernel built with `CONFIG_TLS`.  TLS1.3 key updates additionally
+//! require a kernel which supports TLS1.3 rekeying.  The AES-GCM, ChaCha20-Poly1305
+//! and SM4 cipher suites are supported.
+//!
+//! This module is only available on Linux, when the `ktls` crate feature is enabled.
+
+use core::ffi::{c_int, c_void};
+use core::fmt;
+use core::mem;
+use core::ops::Range;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::os::fd::AsRawFd;
+
+use rustls::client::ClientSide;
+use rustls::crypto::cipher::{AeadKey, Iv};
+use rustls::enums::{ContentType, HandshakeType, ProtocolVersion};
+use rustls::error::{AlertDescription, ApiMisuse, Error, InvalidMessage, PeerMisbehaved};
+use rustls::kernel::KernelConnection;
+use rustls::server::ServerSide;
+use rustls::split::SplitConnection;
+use rustls::{ConnectionTrafficSecrets, SideData};
+
+/// A TCP connection whose TLS records are encrypted and decrypted by the kernel.
+pub struct KtlsStream<Side> {
+    socket: TcpStream,
+    conn: KernelConnection<Side>,
+    handle_new_session_ticket: fn(&mut KernelConnection<Side>, &[u8]) -> Result<(), Error>,
+    /// The payload of the most recently received record.
+    record: Box<[u8]>,
+    /// The part of `record` which is application data yet to be read.
+    plaintext: Range<usize>,
+    peer_closed: bool,
+}
+
+impl KtlsStream<ClientSide> {
+    /// Offload the client connection `conn` to the kernel.
+    ///
+    /// See [`KtlsStream::server()`] for the requirements.
+    pub fn client(socket: TcpStream, conn: SplitConnection<ClientSide>) -> io::Result<Self> {
+        Self::new(socket, conn, KernelConnection::handle_new_session_ticket)
+    }
+}
+
+impl KtlsStream<ServerSide> {
+    /// Offload the server connection `conn` to the kernel.
+    ///
+    /// `conn` must have been established over `socket` with
+    /// [`ServerConfig::enable_secret_extraction`] set.  All TLS data produced by
+    /// `conn` must already have been written to `socket`, and all TLS data read from
+    /// `socket` must already have been processed by `conn`.
+    ///
+    /// An error with kind [`io::ErrorKind::Unsupported`] is returned if the kernel does
+    /// not support TLS, or does not support the negotiated cipher suite.  `socket` is
+    /// unusable after any error.
+    ///
+    /// [`ServerConfig::enable_secret_extraction`]: rustls::ServerConfig::enable_secret_extraction
+    pub fn server(socket: TcpStream, conn: SplitConnection<ServerSide>) -> io::Result<Self> {
+        Self::new(socket, conn, |_, _| {
+            Err(Error::General(
+                "NewSessionTicket received from client".into(),
+            ))
+        })
+    }
+}
+
+impl<Side: SideData> KtlsStream<Side> {
+    fn new(
+        socket: TcpStream,
+        conn: SplitConnection<Side>,
+        handle_new_session_ticket: fn(&mut KernelConnection<Side>, &[u8]) -> Result<(), Error>,
+    ) -> io::Result<Self> {
+        let (secrets, conn) = conn
+            .dangerous_into_kernel_connection()
+            .map_err(io::Error::other)?;
+
+        match setsockopt(&socket, libc::SOL_TCP, libc::TCP_ULP, b"tls") {
+            Err(err) if err.raw_os_error() == Some(libc::ENOENT) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "kernel TLS is not available",
+                ));
+            }
+            result => result?,
+        }
+
+        let version = conn.protocol_version();
+        set_traffic_secrets(&socket, libc::TLS_TX, version, secrets.tx)?;
+        set_traffic_secrets(&socket, libc::TLS_RX, version, secrets.rx)?;
+
+        Ok(Self {
+            socket,
+            conn,
+            handle_new_session_ticket,
+            record: vec![0; MAX_FRAGMENT_LEN].into_boxed_slice(),
+            plaintext: 0..0,
+            peer_closed: false,
+        })
+    }
+}
+
+impl<Side> KtlsStream<Side> {
+    /// Send a `close_notify` alert, and shut down the sending side of the socket.
+    ///
+    /// Reads continue to work until the peer closes its side of the connection.
+    pub fn send_close_notify(&mut self) -> io::Result<()> {
+        self.send_record(ContentType::Alert, &close_notify())?;
+        self.socket.shutdown(Shutdown::Write)
+    }
+
+    /// Update the keys used to encrypt data sent to the peer.
+    ///
+    /// This sends a TLS1.3 `KeyUpdate` message which does not ask the peer to update
+    /// its own keys.  It fails for TLS1.2 connections, which cannot update keys.
+    pub fn refresh_traffic_keys(&mut self) -> io::Result<()> {
+        self.send_key_update(false)
+    }
+
+    /// Return the underlying socket.
+    ///
+    /// Reading from the socket directly will lose any TLS records other than application
+    /// data, such as alerts and key updates.
+    pub fn get_ref(&self) -> &TcpStream {
+        &self.socket
+    }
+
+    /// Return the [`KernelConnection`] which holds the connection's secrets.
+    pub fn kernel_connection(&self) -> &KernelConnection<Side> {
+        &self.conn
+    }
+
+    fn handle_alert(&mut self, len: usize) -> io::Result<()> {
+        let &[level, description] = &self.record[..len] else {
+            return Err(self.fatal(
+                AlertDescription::DecodeError,
+                InvalidMessage::TrailingData("AlertMessagePayload").into(),
+            ));
+        };
+
+        let description = AlertDescription::from(description);
+        match description {
+            AlertDescription::CloseNotify => {
+                self.peer_closed = true;
+                Ok(())
+            }
+            // TLS1.2 warning alerts are ignored.
+            _ if level == ALERT_LEVEL_WARNING
+                && self.conn.protocol_version() == ProtocolVersion::TLSv1_2 =>
+            {
+                Ok(())
+            }
+            _ => Err(invalid_data(Error::AlertReceived(description))),
+        }
+    }
+
+    fn handle_handshake(&mut self, len: usize) -> io::Result<()> {
+        let mut offset = 0;
+        while offset < len {
+            let (typ, payload) = match &self.record[offset..len] {
+                [typ, a, b, c, rest @ ..]
+                    if rest.len() >= u32::from_be_bytes([0, *a, *b, *c]) as usize =>
+                {
+                    let payload_len = u32::from_be_bytes([0, *a, *b, *c]) as usize;
+                    offset += 4 + payload_len;
+                    (HandshakeType::from(*typ), offset - payload_len..offset)
+                }
+                // Post-handshake messages are small, so they are not expected to span records.
+                _ => {
+                    return Err(self.fatal(
+                        AlertDescription::DecodeError,
+                        InvalidMessage::MessageTooShort.into(),
+                    ));
+                }
+            };
+
+            match typ {
+                HandshakeType::NewSessionTicket => {
+                    let result = (self.handle_new_session_ticket)(
+                        &mut self.conn,
+                        &self.record[payload.clone()],
+                    );
+                    if let Err(err) = result {
+                        return Err(self.fatal(AlertDescription::UnexpectedMessage, err));
+                    }
+                }
+                HandshakeType::KeyUpdate
+                    if offset == len
+                        && self.conn.protocol_version() == ProtocolVersion::TLSv1_3 =>
+                {
+                    let update_requested = match self.record[payload] {
+                        [0] => false,
+                        [1] => true,
+                        _ => {
+                            return Err(self.fatal(
+                                AlertDescription::DecodeError,
+                                InvalidMessage::InvalidKeyUpdate.into(),
+                            ));
+                        }
+                    };
+
+                    let secrets = self
+                        .conn
+                        .update_rx_secret()
+                        .map_err(invalid_data)?;
+                    rekey(&self.socket, libc::TLS_RX, secrets)?;
+
+                    if update_requested {
+                        self.send_key_update(false)?;
+                    }
+                }
+                // A `KeyUpdate` must be the last message in its record.
+                HandshakeType::KeyUpdate if offset != len => {
+                    return Err(self.fatal(
+                        AlertDescription::UnexpectedMessage,
+                        PeerMisbehaved::KeyEpochWithPendingFragment.into(),
+                    ));
+                }
+                _ => {
+                    return Err(self.fatal(
+                        AlertDescription::UnexpectedMessage,
+                        Error::General(format!("unexpected {typ:?} message")),
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn send_key_update(&mut self, update_requested: bool) -> io::Result<()> {
+        if self.conn.protocol_version() != ProtocolVersion::TLSv1_3 {
+            return Err(io::Error::other(Error::from(
+                ApiMisuse::KeyUpdateNotAvailableForTls12,
+            )));
+        }
+
+        let message = [
+            u8::from(HandshakeType::KeyUpdate),
+            0,
+            0,
+            1,
+            u8::from(update_requested),
+        ];
+        self.send_record(ContentType::Handshake, &message)?;
+
+        let secrets = self
+            .conn
+            .update_tx_secret()
+            .map_err(invalid_data)?;
+        rekey(&self.socket, libc::TLS_TX, secrets)
+    }
+
+    /// Send a fatal alert, and return an error for `err`.
+    fn fatal(&self, description: AlertDescription, err: Error) -> io::Error {
+        let alert = [ALERT_LEVEL_FATAL, u8::from(description)];
+        // Failing to send the alert does not change the outcome.
+        let _ = self.send_record(ContentType::Alert, &alert);
+        invalid_data(err)
+    }
+
+    /// Receive one record into `self.record`, returning its type and payload length.
+    fn recv_record(&mut self) -> io::Result<(ContentType, usize)> {
+        let mut iov = libc::iovec {
+            iov_base: self.record.as_mut_ptr().cast(),
+            iov_len: self.record.len(),
+        };
+        let mut control = ControlBuffer::new();
+
+        // SAFETY: all-zero is a valid `msghdr`.
+        let mut msg = unsafe { mem::zeroed::<libc::msghdr>() };
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.0.as_mut_ptr().cast();
+        // SAFETY: `CMSG_SPACE()` only does arithmetic.
+        msg.msg_controllen = unsafe { libc::CMSG_SPACE(1) } as _;
+
+        // SAFETY: `msg` refers to `iov` and `control`, which are valid for writes of
+        // the given lengths for the duration of the call.
+        let len = unsafe { libc::recvmsg(self.socket.as_raw_fd(), &mut msg, 0) };
+        let len = usize::try_from(len).map_err(|_| io::Error::last_os_error())?;
+        if msg.msg_flags & libc::MSG_CTRUNC != 0 {
+            return Err(io::Error::other("kernel TLS control message was truncated"));
+        }
+
+        let mut typ = ContentType::ApplicationData;
+        // SAFETY: `msg` was filled in by `recvmsg()`, so its control messages are well-formed.
+        let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(&msg) };
+        while !cmsg.is_null() {
+            // SAFETY: `cmsg` is non-null, and points into `control`.
+            let header = unsafe { &*cmsg };
+            if header.cmsg_level == libc::SOL_TLS && header.cmsg_type == libc::TLS_GET_RECORD_TYPE {
+                // SAFETY: this control message carries a one-byte record type.
+                typ = ContentType::from(unsafe { *libc::CMSG_DATA(cmsg) });
+            }
+            // SAFETY: as above.
+            cmsg = unsafe { libc::CMSG_NXTHDR(&msg, cmsg) };
+        }
+
+        Ok((typ, len))
+    }
+
+    /// Send `payload` as a single record of type `typ`.
+    fn send_record(&self, typ: ContentType, payload: &[u8]) -> io::Result<()> {
+        let mut iov = libc::iovec {
+            iov_base: payload.as_ptr().cast_mut().cast(),
+            iov_len: payload.len(),
+        };
+        let mut control = ControlBuffer::new();
+
+        // SAFETY: all-zero is a valid `msghdr`.
+        let mut msg = unsafe { mem::zeroed::<libc::msghdr>() };
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.0.as_mut_ptr().cast();
+        // SAFETY: `CMSG_SPACE()` only does arithmetic.
+        msg.msg_controllen = unsafe { libc::CMSG_SPACE(1) } as _;
+
+        // SAFETY: `msg_control` has room for a control message with one byte of data.
+        unsafe {
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            (*cmsg).cmsg_level = libc::SOL_TLS;
+            (*cmsg).cmsg_type = libc::TLS_SET_RECORD_TYPE;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(1) as _;
+            *libc::CMSG_DATA(cmsg) = u8::from(typ);
+        }
+
+        // SAFETY: `msg` refers to `iov` and `control`, which are valid for reads of the
+        // given lengths for the duration of the call.  The kernel does not write
+        // through `iov_base`.
+        let sent = unsafe { libc::sendmsg(self.socket.as_raw_fd(), &msg, 0) };
+        match usize::try_from(sent) {
+            Ok(sent) if sent == payload.len() => Ok(()),
+            Ok(_) => Err(io::ErrorKind::WriteZero.into()),
+            Err(_) => Err(io::Error::last_os_error()),
+        }
+    }
+}
+
+impl<Side> Read for KtlsStream<Side> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if !self.plaintext.is_empty() || buf.is_empty() {
+                let len = Ord::min(buf.len(), self.plaintext.len());
+                let start = self.plaintext.start;
+                buf[..len].copy_from_slice(&self.record[start..start + len]);
+                self.plaintext.start += len;
+                return Ok(len);
+            } else if self.peer_closed {
+                return Ok(0);
+            }
+
+            let (typ, len) = match self.recv_record() {
+                Ok((_, 0)) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "peer closed connection without sending TLS close_notify: \
+                         https://docs.rs/rustls/latest/rustls/manual/_03_howto/index.html#unexpected-eof",
+                    ));
+                }
+                Ok(record) => record,
+                Err(err) if err.raw_os_error() == Some(libc::EBADMSG) => {
+                    // The kernel has already sent a `bad_record_mac` alert.
+                    return Err(invalid_data(Error::DecryptError));
+                }
+                Err(err) => return Err(err),
+            };
+
+            match typ {
+                ContentType::ApplicationData => self.plaintext = 0..len,
+                ContentType::Alert => self.handle_alert(len)?,
+                ContentType::Handshake => self.handle_handshake(len)?,
+                _ => {
+                    return Err(self.fatal(
+                        AlertDescription::UnexpectedMessage,
+                        Error::General(format!("unexpected {typ:?} record")),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+impl<Side> Write for KtlsStream<Side> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.socket.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.socket.flush()
+    }
+}
+
+impl<Side> fmt::Debug for KtlsStream<Side> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KtlsStream")
+            .field("socket", &self.socket)
+            .field("peer_closed", &self.peer_closed)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Install `secrets` on `socket` for the `direction` (`TLS_TX` or `TLS_RX`).
+fn set_traffic_secrets(
+    socket: &TcpStream,
+    direction: c_int,
+    version: ProtocolVersion,
+    (seq, secrets): (u64, ConnectionTrafficSecrets),
+) -> io::Result<()> {
+    let version = match version {
+        ProtocolVersion::TLSv1_2 => libc::TLS_1_2_VERSION,
+        ProtocolVersion::TLSv1_3 => libc::TLS_1_3_VERSION,
+        _ => return Err(unsupported("protocol version")),
+    };
+    let rec_seq = seq.to_be_bytes();
+
+    match secrets {
+        ConnectionTrafficSecrets::Aes128Gcm { key, iv } => {
+            let (salt, iv) = split_iv(&iv)?;
+            let info = libc::tls12_crypto_info_aes_gcm_128 {
+                info: crypto_info(version, libc::TLS_CIPHER_AES_GCM_128),
+                iv,
+                key: key_bytes(&key)?,
+                salt,
+                rec_seq,
+            };
+            setsockopt(socket, libc::SOL_TLS, direction, &info)
+        }
+        ConnectionTrafficSecrets::Aes256Gcm { key, iv } => {
+            let (salt, iv) = split_iv(&iv)?;
+            let info = libc::tls12_crypto_info_aes_gcm_256 {
+                info: crypto_info(version, libc::TLS_CIPHER_AES_GCM_256),
+                iv,
+                key: key_bytes(&key)?,
+                salt,
+                rec_seq,
+            };
+            setsockopt(socket, libc::SOL_TLS, direction, &info)
+        }
+        ConnectionTrafficSecrets::Chacha20Poly1305 { key, iv } => {
+            let info = libc::tls12_crypto_info_chacha20_poly1305 {
+                info: crypto_info(version, libc::TLS_CIPHER_CHACHA20_POLY1305),
+                iv: iv
+                    .as_ref()
+                    .try_into()
+                    .map_err(|_| unsupported("IV length"))?,
+                key: key_bytes(&key)?,
+                salt: [],
+                rec_seq,
+            };
+            setsockopt(socket, libc::SOL_TLS, direction, &info)
+        }
+        ConnectionTrafficSecrets::Sm4Gcm { key, iv } => {
+            let (salt, iv) = split_iv(&iv)?;
+            let info = libc::tls12_crypto_info_sm4_gcm {
+                info: crypto_info(version, libc::TLS_CIPHER_SM4_GCM),
+                iv,
+                key: key_bytes(&key)?,
+                salt,
+                rec_seq,
+            };
+            setsockopt(socket, libc::SOL_TLS, direction, &info)
+        }
+        ConnectionTrafficSecrets::Sm4Ccm { key, iv } => {
+            let (salt, iv) = split_iv(&iv)?;
+            let info = libc::tls12_crypto_info_sm4_ccm {
+                info: crypto_info(version, libc::TLS_CIPHER_SM4_CCM),
+                iv,
+                key: key_bytes(&key)?,
+                salt,
+                rec_seq,
+            };
+            setsockopt(socket, libc::SOL_TLS, direction, &info)
+        }
+        _ => Err(unsupported("cipher suite")),
+    }
+}
+
+/// Install updated TLS1.3 `secrets` on `socket` for the `direction`.
+fn rekey(
+    socket: &TcpStream,
+    direction: c_int,
+    secrets: (u64, ConnectionTrafficSecrets),
+) -> io::Result<()> {
+    match set_traffic_secrets(socket, direction, ProtocolVersion::TLSv1_3, secrets) {
+        // Older kernels refuse to replace installed secrets.
+        Err(err) if err.raw_os_error() == Some(libc::EBUSY) => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "kernel does not support TLS1.3 key updates",
+        )),
+        result => result,
+    }
+}
+
+fn crypto_info(version: u16, cipher_type: u16) -> libc::tls_crypto_info {
+    libc::tls_crypto_info {
+        version,
+        cipher_type,
+    }
+}
+
+/// Split a 12-byte `iv` into the kernel's 4-byte salt and 8-byte IV.
+fn split_iv(iv: &Iv) -> io::Result<([u8; 4], [u8; 8])> {
+    match iv.as_ref().split_first_chunk::<4>() {
+        Some((salt, rest)) => Ok((
+            *salt,
+            rest.try_into()
+                .map_err(|_| unsupported("IV length"))?,
+        )),
+        None => Err(unsupported("IV length")),
+    }
+}
+
+fn key_bytes<const N: usize>(key: &AeadKey) -> io::Result<[u8; N]> {
+    key.as_ref()
+        .try_into()
+        .map_err(|_| unsupported("key length"))
+}
+
+fn setsockopt<T>(socket: &TcpStream, level: c_int, name: c_int, value: &T) -> io::Result<()> {
+    // SAFETY: `value` is valid for reads of `size_of::<T>()` bytes for the duration
+    // of the call.
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            name,
+            (value as *const T).cast::<c_void>(),
+            size_of::<T>() as libc::socklen_t,
+        )
+    };
+    match ret {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+fn close_notify() -> [u8; 2] {
+    [ALERT_LEVEL_WARNING, u8::from(AlertDescription::CloseNotify)]
+}
+
+fn unsupported(what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{what} is not supported by kernel TLS"),
+    )
+}
+
+fn invalid_data(err: Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+/// Space for one control message with one byte of data, aligned for `cmsghdr`.
+#[repr(C)]
+struct ControlBuffer([u64; 4]);
+
+impl ControlBuffer {
+    fn new() -> Self {
+        Self([0; 4])
+    }
+}
+
+const ALERT_LEVEL_WARNING: u8 = 1;
+const ALERT_LEVEL_FATAL: u8 = 2;
+
+/// The maximum length of a TLS record's plaintext.
+const MAX_FRAGMENT_LEN: usize = 16_384;
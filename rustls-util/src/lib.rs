@@ -8,6 +8,21 @@ pub use key_log_file::KeyLogFile;
 mod stream;
 pub use crate::stream::{Stream, StreamOwned};
 
+#[cfg(any(feature = "futures-io", feature = "tokio"))]
+mod async_io;
+
+#[cfg(feature = "futures-io")]
+pub mod futures_io;
+
+#[cfg(all(feature = "ktls", target_os = "linux"))]
+pub mod ktls;
+
+#[cfg(feature = "pkcs11")]
+pub mod pkcs11;
+
+#[cfg(feature = "tokio")]
+pub mod tokio;
+
 /// This function uses `io` to complete any outstanding IO for
 /// the connection.
 ///
@@ -0,0 +1,231 @@
+//! The subset of the PKCS#11 (version 2.40 / 3.0) C API used by this module.
+//!
+//! Names follow the specification's, so they can be cross-referenced easily.
+
+#![allow(non_camel_case_types, non_snake_case)]
+
+use core::ffi::{c_ulong, c_void};
+
+pub(super) type CK_ULONG = c_ulong;
+pub(super) type CK_RV = CK_ULONG;
+pub(super) type CK_SLOT_ID = CK_ULONG;
+pub(super) type CK_SESSION_HANDLE = CK_ULONG;
+pub(super) type CK_OBJECT_HANDLE = CK_ULONG;
+pub(super) type CK_BBOOL = u8;
+
+pub(super) const CKR_OK: CK_RV = 0x0;
+pub(super) const CKR_ATTRIBUTE_SENSITIVE: CK_RV = 0x11;
+pub(super) const CKR_ATTRIBUTE_TYPE_INVALID: CK_RV = 0x12;
+pub(super) const CKR_USER_ALREADY_LOGGED_IN: CK_RV = 0x100;
+pub(super) const CKR_CRYPTOKI_ALREADY_INITIALIZED: CK_RV = 0x191;
+
+pub(super) const CK_TRUE: CK_BBOOL = 1;
+pub(super) const CK_UNAVAILABLE_INFORMATION: CK_ULONG = !0;
+
+pub(super) const CKF_OS_LOCKING_OK: CK_ULONG = 0x2;
+pub(super) const CKF_SERIAL_SESSION: CK_ULONG = 0x4;
+pub(super) const CKF_TOKEN_INITIALIZED: CK_ULONG = 0x400;
+
+pub(super) const CKU_USER: CK_ULONG = 1;
+
+pub(super) const CKA_CLASS: CK_ULONG = 0x0;
+pub(super) const CKA_LABEL: CK_ULONG = 0x3;
+pub(super) const CKA_KEY_TYPE: CK_ULONG = 0x100;
+pub(super) const CKA_ID: CK_ULONG = 0x102;
+pub(super) const CKA_MODULUS: CK_ULONG = 0x120;
+pub(super) const CKA_PUBLIC_EXPONENT: CK_ULONG = 0x122;
+pub(super) const CKA_PUBLIC_KEY_INFO: CK_ULONG = 0x129;
+pub(super) const CKA_EC_PARAMS: CK_ULONG = 0x180;
+pub(super) const CKA_EC_POINT: CK_ULONG = 0x181;
+
+pub(super) const CKO_PUBLIC_KEY: CK_ULONG = 2;
+pub(super) const CKO_PRIVATE_KEY: CK_ULONG = 3;
+
+pub(super) const CKK_RSA: CK_ULONG = 0x0;
+pub(super) const CKK_EC: CK_ULONG = 0x3;
+pub(super) const CKK_EC_EDWARDS: CK_ULONG = 0x40;
+
+pub(super) const CKM_SHA256_RSA_PKCS: CK_ULONG = 0x40;
+pub(super) const CKM_SHA384_RSA_PKCS: CK_ULONG = 0x41;
+pub(super) const CKM_SHA512_RSA_PKCS: CK_ULONG = 0x42;
+pub(super) const CKM_SHA256_RSA_PKCS_PSS: CK_ULONG = 0x43;
+pub(super) const CKM_SHA384_RSA_PKCS_PSS: CK_ULONG = 0x44;
+pub(super) const CKM_SHA512_RSA_PKCS_PSS: CK_ULONG = 0x45;
+pub(super) const CKM_SHA256: CK_ULONG = 0x250;
+pub(super) const CKM_SHA384: CK_ULONG = 0x260;
+pub(super) const CKM_SHA512: CK_ULONG = 0x270;
+pub(super) const CKM_ECDSA_SHA256: CK_ULONG = 0x1044;
+pub(super) const CKM_ECDSA_SHA384: CK_ULONG = 0x1045;
+pub(super) const CKM_ECDSA_SHA512: CK_ULONG = 0x1046;
+pub(super) const CKM_EDDSA: CK_ULONG = 0x1057;
+
+pub(super) const CKG_MGF1_SHA256: CK_ULONG = 0x2;
+pub(super) const CKG_MGF1_SHA384: CK_ULONG = 0x3;
+pub(super) const CKG_MGF1_SHA512: CK_ULONG = 0x4;
+
+// The specification requires 1-byte packing of structures on Windows.
+#[repr(C)]
+#[cfg_attr(windows, repr(packed))]
+#[derive(Clone, Copy, Default)]
+pub(super) struct CK_VERSION {
+    pub(super) major: u8,
+    pub(super) minor: u8,
+}
+
+#[repr(C)]
+#[cfg_attr(windows, repr(packed))]
+pub(super) struct CK_ATTRIBUTE {
+    pub(super) type_: CK_ULONG,
+    pub(super) pValue: *mut c_void,
+    pub(super) ulValueLen: CK_ULONG,
+}
+
+#[repr(C)]
+#[cfg_attr(windows, repr(packed))]
+pub(super) struct CK_MECHANISM {
+    pub(super) mechanism: CK_ULONG,
+    pub(super) pParameter: *mut c_void,
+    pub(super) ulParameterLen: CK_ULONG,
+}
+
+#[repr(C)]
+#[cfg_attr(windows, repr(packed))]
+pub(super) struct CK_RSA_PKCS_PSS_PARAMS {
+    pub(super) hashAlg: CK_ULONG,
+    pub(super) mgf: CK_ULONG,
+    pub(super) sLen: CK_ULONG,
+}
+
+#[repr(C)]
+#[cfg_attr(windows, repr(packed))]
+pub(super) struct CK_C_INITIALIZE_ARGS {
+    pub(super) CreateMutex: *mut c_void,
+    pub(super) DestroyMutex: *mut c_void,
+    pub(super) LockMutex: *mut c_void,
+    pub(super) UnlockMutex: *mut c_void,
+    pub(super) flags: CK_ULONG,
+    pub(super) pReserved: *mut c_void,
+}
+
+#[repr(C)]
+#[cfg_attr(windows, repr(packed))]
+pub(super) struct CK_TOKEN_INFO {
+    pub(super) label: [u8; 32],
+    pub(super) manufacturerID: [u8; 32],
+    pub(super) model: [u8; 16],
+    pub(super) serialNumber: [u8; 16],
+    pub(super) flags: CK_ULONG,
+    pub(super) ulMaxSessionCount: CK_ULONG,
+    pub(super) ulSessionCount: CK_ULONG,
+    pub(super) ulMaxRwSessionCount: CK_ULONG,
+    pub(super) ulRwSessionCount: CK_ULONG,
+    pub(super) ulMaxPinLen: CK_ULONG,
+    pub(super) ulMinPinLen: CK_ULONG,
+    pub(super) ulTotalPublicMemory: CK_ULONG,
+    pub(super) ulFreePublicMemory: CK_ULONG,
+    pub(super) ulTotalPrivateMemory: CK_ULONG,
+    pub(super) ulFreePrivateMemory: CK_ULONG,
+    pub(super) hardwareVersion: CK_VERSION,
+    pub(super) firmwareVersion: CK_VERSION,
+    pub(super) utcTime: [u8; 16],
+}
+
+/// An unused entry in [`CK_FUNCTION_LIST`].
+type Unused = Option<unsafe extern "C" fn()>;
+
+/// The leading part of `CK_FUNCTION_LIST`, up to and including `C_Sign`.
+///
+/// We only ever access this through a pointer provided by the module, so
+/// omitting the trailing entries is sound.
+#[repr(C)]
+#[cfg_attr(windows, repr(packed))]
+pub(super) struct CK_FUNCTION_LIST {
+    pub(super) version: CK_VERSION,
+    pub(super) C_Initialize: unsafe extern "C" fn(pInitArgs: *mut c_void) -> CK_RV,
+    pub(super) C_Finalize: unsafe extern "C" fn(pReserved: *mut c_void) -> CK_RV,
+    C_GetInfo: Unused,
+    C_GetFunctionList: Unused,
+    pub(super) C_GetSlotList: unsafe extern "C" fn(
+        tokenPresent: CK_BBOOL,
+        pSlotList: *mut CK_SLOT_ID,
+        pulCount: *mut CK_ULONG,
+    ) -> CK_RV,
+    C_GetSlotInfo: Unused,
+    pub(super) C_GetTokenInfo:
+        unsafe extern "C" fn(slotID: CK_SLOT_ID, pInfo: *mut CK_TOKEN_INFO) -> CK_RV,
+    C_GetMechanismList: Unused,
+    C_GetMechanismInfo: Unused,
+    C_InitToken: Unused,
+    C_InitPIN: Unused,
+    C_SetPIN: Unused,
+    pub(super) C_OpenSession: unsafe extern "C" fn(
+        slotID: CK_SLOT_ID,
+        flags: CK_ULONG,
+        pApplication: *mut c_void,
+        Notify: *mut c_void,
+        phSession: *mut CK_SESSION_HANDLE,
+    ) -> CK_RV,
+    pub(super) C_CloseSession: unsafe extern "C" fn(hSession: CK_SESSION_HANDLE) -> CK_RV,
+    C_CloseAllSessions: Unused,
+    C_GetSessionInfo: Unused,
+    C_GetOperationState: Unused,
+    C_SetOperationState: Unused,
+    pub(super) C_Login: unsafe extern "C" fn(
+        hSession: CK_SESSION_HANDLE,
+        userType: CK_ULONG,
+        pPin: *const u8,
+        ulPinLen: CK_ULONG,
+    ) -> CK_RV,
+    C_Logout: Unused,
+    C_CreateObject: Unused,
+    C_CopyObject: Unused,
+    C_DestroyObject: Unused,
+    C_GetObjectSize: Unused,
+    pub(super) C_GetAttributeValue: unsafe extern "C" fn(
+        hSession: CK_SESSION_HANDLE,
+        hObject: CK_OBJECT_HANDLE,
+        pTemplate: *mut CK_ATTRIBUTE,
+        ulCount: CK_ULONG,
+    ) -> CK_RV,
+    C_SetAttributeValue: Unused,
+    pub(super) C_FindObjectsInit: unsafe extern "C" fn(
+        hSession: CK_SESSION_HANDLE,
+        pTemplate: *mut CK_ATTRIBUTE,
+        ulCount: CK_ULONG,
+    ) -> CK_RV,
+    pub(super) C_FindObjects: unsafe extern "C" fn(
+        hSession: CK_SESSION_HANDLE,
+        phObject: *mut CK_OBJECT_HANDLE,
+        ulMaxObjectCount: CK_ULONG,
+        pulObjectCount: *mut CK_ULONG,
+    ) -> CK_RV,
+    pub(super) C_FindObjectsFinal: unsafe extern "C" fn(hSession: CK_SESSION_HANDLE) -> CK_RV,
+    C_EncryptInit: Unused,
+    C_Encrypt: Unused,
+    C_EncryptUpdate: Unused,
+    C_EncryptFinal: Unused,
+    C_DecryptInit: Unused,
+    C_Decrypt: Unused,
+    C_DecryptUpdate: Unused,
+    C_DecryptFinal: Unused,
+    C_DigestInit: Unused,
+    C_Digest: Unused,
+    C_DigestUpdate: Unused,
+    C_DigestKey: Unused,
+    C_DigestFinal: Unused,
+    pub(super) C_SignInit: unsafe extern "C" fn(
+        hSession: CK_SESSION_HANDLE,
+        pMechanism: *mut CK_MECHANISM,
+        hKey: CK_OBJECT_HANDLE,
+    ) -> CK_RV,
+    pub(super) C_Sign: unsafe extern "C" fn(
+        hSession: CK_SESSION_HANDLE,
+        pData: *const u8,
+        ulDataLen: CK_ULONG,
+        pSignature: *mut u8,
+        pulSignatureLen: *mut CK_ULONG,
+    ) -> CK_RV,
+}
+
+pub(super) type C_GetFunctionList =
+    unsafe extern "C" fn(ppFunctionList: *mut *const CK_FUNCTION_LIST) -> CK_RV;
@@ -0,0 +1,859 @@
+//! Private keys held in PKCS#11 tokens, such as HSMs and smartcards.
+//!
+//! [`Pkcs11Module`] loads a PKCS#11 module (a shared library supplied by the
+//! token vendor), and [`Pkcs11KeyProvider`] finds private keys in its tokens by
+//! [RFC 7512] URI.  Signing is performed by the token: the private key never
+//! leaves it.
+//!
+//! The following key types and signature schemes are supported:
+//!
+//! - RSA keys, with RSA-PSS and RSA-PKCS#1 v1.5 using SHA-256, SHA-384 or SHA-512;
+//! - ECDSA keys on the P-256, P-384 or P-521 curves; and
+//! - Ed25519 keys.
+//!
+//! ```no_run
+//! # fn main() -> Result<(), rustls::Error> {
+//! use rustls_util::pkcs11::{Pkcs11KeyProvider, Pkcs11Module};
+//!
+//! let module = Pkcs11Module::load("/usr/lib/softhsm/libsofthsm2.so")?;
+//! let keys = Pkcs11KeyProvider::new(module).with_pin("1234");
+//! let key = keys.load_uri("pkcs11:token=my-token;object=server-key")?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The resulting key can be used with `rustls::crypto::Credentials::new()`, which checks that
+//! it matches the certificate's public key.
+//!
+//! This module is only available when the `pkcs11` crate feature is enabled.
+//!
+//! [RFC 7512]: https://www.rfc-editor.org/rfc/rfc7512
+
+use core::ffi::c_void;
+use core::fmt::{self, Debug, Formatter};
+use core::ptr;
+use std::ffi::OsStr;
+use std::sync::{Arc, Mutex};
+
+use libloading::Library;
+use rustls::crypto::{KeyProvider, SignatureScheme, Signer, SigningKey, public_key_to_spki};
+use rustls::error::Error;
+use rustls::pki_types::{PrivateKeyDer, SubjectPublicKeyInfoDer, alg_id};
+
+mod ffi;
+use ffi::*;
+
+mod uri;
+use uri::Pkcs11Uri;
+
+/// A loaded and initialized PKCS#11 module.
+pub struct Pkcs11Module {
+    functions: *const CK_FUNCTION_LIST,
+    /// Whether we initialized the module, and so must finalize it.
+    finalize: bool,
+    // Declared last, so the library is unloaded after `Drop::drop()` runs.
+    _library: Library,
+}
+
+impl Pkcs11Module {
+    /// Load the PKCS#11 module at `path`, and initialize it.
+    ///
+    /// The module is initialized for use by multiple threads, using operating system
+    /// locking primitives.  It is finalized when the returned value is dropped, unless
+    /// it was already initialized elsewhere in this process.
+    pub fn load(path: impl AsRef<OsStr>) -> Result<Arc<Self>, Error> {
+        let path = path.as_ref();
+
+        // SAFETY: loading a library runs its initialization routines; we must trust
+        // the module that the caller chose.
+        let library = unsafe { Library::new(path) }.map_err(|err| {
+            Error::General(format!(
+                "cannot load PKCS#11 module {}: {err}",
+                path.display()
+            ))
+        })?;
+
+        // SAFETY: `C_GetFunctionList` has this signature in all PKCS#11 versions.
+        let get_function_list =
+            unsafe { library.get::<C_GetFunctionList>(b"C_GetFunctionList") }
+                .map_err(|err| Error::General(format!("not a PKCS#11 module: {err}")))?;
+
+        let mut functions = ptr::null();
+        // SAFETY: `functions` is a valid out-pointer.
+        check("C_GetFunctionList", unsafe {
+            get_function_list(&mut functions)
+        })?;
+        if functions.is_null() {
+            return Err(Error::General(
+                "C_GetFunctionList returned no function list".into(),
+            ));
+        }
+
+        let mut args = CK_C_INITIALIZE_ARGS {
+            CreateMutex: ptr::null_mut(),
+            DestroyMutex: ptr::null_mut(),
+            LockMutex: ptr::null_mut(),
+            UnlockMutex: ptr::null_mut(),
+            flags: CKF_OS_LOCKING_OK,
+            pReserved: ptr::null_mut(),
+        };
+
+        // SAFETY: `functions` was checked to be non-null above, and the module keeps it
+        // valid until it is unloaded.  `args` outlives the call.
+        let rv = unsafe {
+            ((*functions).C_Initialize)(&mut args as *mut CK_C_INITIALIZE_ARGS as *mut c_void)
+        };
+        let finalize = match rv {
+            CKR_OK => true,
+            CKR_CRYPTOKI_ALREADY_INITIALIZED => false,
+            rv => return Err(failed("C_Initialize", rv)),
+        };
+
+        Ok(Arc::new(Self {
+            functions,
+            finalize,
+            _library: library,
+        }))
+    }
+
+    /// Find the slot holding the single initialized token matching `uri`.
+    fn find_slot(&self, uri: &Pkcs11Uri) -> Result<CK_SLOT_ID, Error> {
+        let f = self.functions();
+
+        let mut count = 0;
+        // SAFETY: a null slot list asks only for the count.
+        check("C_GetSlotList", unsafe {
+            (f.C_GetSlotList)(CK_TRUE, ptr::null_mut(), &mut count)
+        })?;
+        let mut slots = vec![0; count as usize];
+        // SAFETY: `slots` has room for `count` entries.
+        check("C_GetSlotList", unsafe {
+            (f.C_GetSlotList)(CK_TRUE, slots.as_mut_ptr(), &mut count)
+        })?;
+        slots.truncate(count as usize);
+
+        let mut matching = vec![];
+        for slot in slots {
+            if uri.slot_id.is_some_and(|id| id != slot) {
+                continue;
+            }
+
+            // SAFETY: `CK_TOKEN_INFO` is plain data, for which all zeroes is valid.
+            let mut info: CK_TOKEN_INFO = unsafe { core::mem::zeroed() };
+            // SAFETY: `info` is a valid out-pointer.
+            check("C_GetTokenInfo", unsafe {
+                (f.C_GetTokenInfo)(slot, &mut info)
+            })?;
+
+            let flags = info.flags;
+            let matches = |wanted: &Option<String>, actual: &[u8]| match wanted {
+                Some(wanted) => wanted.as_bytes() == trim_padding(actual),
+                None => true,
+            };
+            if flags & CKF_TOKEN_INITIALIZED != 0
+                && matches(&uri.token, &info.label)
+                && matches(&uri.manufacturer, &info.manufacturerID)
+                && matches(&uri.model, &info.model)
+                && matches(&uri.serial, &info.serialNumber)
+            {
+                matching.push(slot);
+            }
+        }
+
+        match matching[..] {
+            [slot] => Ok(slot),
+            [] => Err(Error::General("no PKCS#11 token matches URI".into())),
+            _ => Err(Error::General(
+                "more than one PKCS#11 token matches URI".into(),
+            )),
+        }
+    }
+
+    fn functions(&self) -> &CK_FUNCTION_LIST {
+        // SAFETY: checked to be non-null in `load()`, and valid while `_library` is loaded.
+        unsafe { &*self.functions }
+    }
+}
+
+impl Drop for Pkcs11Module {
+    fn drop(&mut self) {
+        if self.finalize {
+            // SAFETY: all sessions hold an `Arc<Self>`, so none remain.
+            unsafe { (self.functions().C_Finalize)(ptr::null_mut()) };
+        }
+    }
+}
+
+impl Debug for Pkcs11Module {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let version = self.functions().version;
+        f.debug_struct("Pkcs11Module")
+            .field("version", &format!("{}.{}", version.major, version.minor))
+            .finish_non_exhaustive()
+    }
+}
+
+// SAFETY: the module is initialized with `CKF_OS_LOCKING_OK`, so may be called from
+// multiple threads.  Each session is used by one thread at a time (see `Session`).
+unsafe impl Send for Pkcs11Module {}
+// SAFETY: as above.
+unsafe impl Sync for Pkcs11Module {}
+
+/// A [`KeyProvider`] for private keys held in PKCS#11 tokens.
+///
+/// Keys are loaded with [`Pkcs11KeyProvider::load_uri()`].  Keys supplied
+/// as DER via [`KeyProvider::load_private_key()`] are delegated to the
+/// fallback provider, if one was given with [`Pkcs11KeyProvider::with_fallback()`].
+pub struct Pkcs11KeyProvider {
+    module: Arc<Pkcs11Module>,
+    pin: Option<String>,
+    fallback: Option<&'static dyn KeyProvider>,
+}
+
+impl Pkcs11KeyProvider {
+    /// Make a new `Pkcs11KeyProvider` finding keys in `module`.
+    pub fn new(module: Arc<Pkcs11Module>) -> Self {
+        Self {
+            module,
+            pin: None,
+            fallback: None,
+        }
+    }
+
+    /// Log in to tokens with `pin`, unless the key's URI gives a `pin-value` or `pin-source`.
+    pub fn with_pin(mut self, pin: impl Into<String>) -> Self {
+        self.pin = Some(pin.into());
+        self
+    }
+
+    /// Use `fallback` to load keys given to [`KeyProvider::load_private_key()`].
+    pub fn with_fallback(mut self, fallback: &'static dyn KeyProvider) -> Self {
+        self.fallback = Some(fallback);
+        self
+    }
+
+    /// Find the private key identified by the PKCS#11 `uri`.
+    ///
+    /// `uri` must identify exactly one initialized token, and exactly one private
+    /// key object within it.  The `object` (`CKA_LABEL`) and `id` (`CKA_ID`)
+    /// attributes are used to select the key.  The `module-name` and `module-path`
+    /// attributes are ignored: the key is always found via this provider's module.
+    pub fn load_uri(&self, uri: &str) -> Result<Box<dyn SigningKey>, Error> {
+        let uri = Pkcs11Uri::parse(uri)?;
+        if let Some(object_type) = &uri.object_type
+            && object_type != "private"
+        {
+            return Err(Error::General(format!(
+                "PKCS#11 URI identifies a `{object_type}` object, not a private key"
+            )));
+        }
+
+        let slot = self.module.find_slot(&uri)?;
+        let session = Session::open(self.module.clone(), slot)?;
+        if let Some(pin) = uri.pin()?.or_else(|| self.pin.clone()) {
+            session.login(&pin)?;
+        }
+
+        let mut template = vec![(CKA_CLASS, CKO_PRIVATE_KEY.to_ne_bytes().to_vec())];
+        if let Some(label) = &uri.object {
+            template.push((CKA_LABEL, label.as_bytes().to_vec()));
+        }
+        if let Some(id) = &uri.id {
+            template.push((CKA_ID, id.clone()));
+        }
+
+        let handle = match session.find_objects(&template)?[..] {
+            [handle] => handle,
+            [] => return Err(Error::General("no PKCS#11 private key matches URI".into())),
+            _ => {
+                return Err(Error::General(
+                    "more than one PKCS#11 private key matches URI".into(),
+                ));
+            }
+        };
+
+        let kind = KeyKind::new(&session, handle)?;
+        let public_key = kind.public_key(&session, handle)?;
+        Ok(Box::new(Pkcs11SigningKey {
+            key: Arc::new(TokenKey {
+                session: Mutex::new(session),
+                handle,
+                kind,
+                public_key,
+            }),
+        }))
+    }
+}
+
+impl KeyProvider for Pkcs11KeyProvider {
+    fn load_private_key(
+        &self,
+        key_der: PrivateKeyDer<'static>,
+    ) -> Result<Box<dyn SigningKey>, Error> {
+        match self.fallback {
+            Some(fallback) => fallback.load_private_key(key_der),
+            None => Err(Error::General(
+                "PKCS#11 keys must be loaded with Pkcs11KeyProvider::load_uri()".into(),
+            )),
+        }
+    }
+}
+
+impl Debug for Pkcs11KeyProvider {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // Note: we deliberately omit the PIN from the debug output.
+        f.debug_struct("Pkcs11KeyProvider")
+            .field("module", &self.module)
+            .field("fallback", &self.fallback)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A [`SigningKey`] for a private key held in a PKCS#11 token.
+struct Pkcs11SigningKey {
+    key: Arc<TokenKey>,
+}
+
+impl SigningKey for Pkcs11SigningKey {
+    fn choose_scheme(&self, offered: &[SignatureScheme]) -> Option<Box<dyn Signer>> {
+        self.key
+            .kind
+            .schemes()
+            .iter()
+            .find(|scheme| offered.contains(scheme))
+            .map(|&scheme| {
+                Box::new(Pkcs11Signer {
+                    key: self.key.clone(),
+                    scheme,
+                }) as Box<dyn Signer>
+            })
+    }
+
+    fn public_key(&self) -> Option<SubjectPublicKeyInfoDer<'_>> {
+        self.key
+            .public_key
+            .as_ref()
+            .map(|spki| SubjectPublicKeyInfoDer::from(spki.as_ref()))
+    }
+}
+
+impl Debug for Pkcs11SigningKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pkcs11SigningKey")
+            .field("kind", &self.key.kind)
+            .finish_non_exhaustive()
+    }
+}
+
+struct Pkcs11Signer {
+    key: Arc<TokenKey>,
+    scheme: SignatureScheme,
+}
+
+impl Signer for Pkcs11Signer {
+    fn sign(self: Box<Self>, message: &[u8]) -> Result<Vec<u8>, Error> {
+        let (mechanism, mut pss_params) = match self.scheme {
+            SignatureScheme::RSA_PKCS1_SHA256 => (CKM_SHA256_RSA_PKCS, None),
+            SignatureScheme::RSA_PKCS1_SHA384 => (CKM_SHA384_RSA_PKCS, None),
+            SignatureScheme::RSA_PKCS1_SHA512 => (CKM_SHA512_RSA_PKCS, None),
+            SignatureScheme::RSA_PSS_SHA256 => (
+                CKM_SHA256_RSA_PKCS_PSS,
+                Some(pss_params(CKM_SHA256, CKG_MGF1_SHA256, 32)),
+            ),
+            SignatureScheme::RSA_PSS_SHA384 => (
+                CKM_SHA384_RSA_PKCS_PSS,
+                Some(pss_params(CKM_SHA384, CKG_MGF1_SHA384, 48)),
+            ),
+            SignatureScheme::RSA_PSS_SHA512 => (
+                CKM_SHA512_RSA_PKCS_PSS,
+                Some(pss_params(CKM_SHA512, CKG_MGF1_SHA512, 64)),
+            ),
+            SignatureScheme::ECDSA_NISTP256_SHA256 => (CKM_ECDSA_SHA256, None),
+            SignatureScheme::ECDSA_NISTP384_SHA384 => (CKM_ECDSA_SHA384, None),
+            SignatureScheme::ECDSA_NISTP521_SHA512 => (CKM_ECDSA_SHA512, None),
+            SignatureScheme::ED25519 => (CKM_EDDSA, None),
+            _ => unreachable!(), // only schemes from `KeyKind::schemes()` are used
+        };
+
+        let mut mechanism = CK_MECHANISM {
+            mechanism,
+            pParameter: ptr::null_mut(),
+            ulParameterLen: 0,
+        };
+        if let Some(params) = &mut pss_params {
+            mechanism.pParameter = params as *mut CK_RSA_PKCS_PSS_PARAMS as *mut c_void;
+            mechanism.ulParameterLen = size_of::<CK_RSA_PKCS_PSS_PARAMS>() as CK_ULONG;
+        }
+
+        let signature =
+            self.key
+                .session
+                .lock()
+                .unwrap()
+                .sign(&mut mechanism, self.key.handle, message)?;
+
+        match self.key.kind {
+            // PKCS#11 produces `r || s`; TLS wants a DER `Ecdsa-Sig-Value`.
+            KeyKind::Ecdsa(_) => ecdsa_signature_to_der(&signature),
+            _ => Ok(signature),
+        }
+    }
+
+    fn scheme(&self) -> SignatureScheme {
+        self.scheme
+    }
+}
+
+impl Debug for Pkcs11Signer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pkcs11Signer")
+            .field("scheme", &self.scheme)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A private key object, and the session through which it is used.
+struct TokenKey {
+    session: Mutex<Session>,
+    handle: CK_OBJECT_HANDLE,
+    kind: KeyKind,
+    public_key: Option<SubjectPublicKeyInfoDer<'static>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum KeyKind {
+    Rsa,
+    /// An ECDSA key, which is only usable with the given scheme.
+    Ecdsa(SignatureScheme),
+    Ed25519,
+}
+
+impl KeyKind {
+    fn new(session: &Session, handle: CK_OBJECT_HANDLE) -> Result<Self, Error> {
+        let key_type = session
+            .attribute(handle, CKA_KEY_TYPE)?
+            .and_then(|value| value.try_into().ok())
+            .map(CK_ULONG::from_ne_bytes);
+
+        let ec_params = || {
+            session
+                .attribute(handle, CKA_EC_PARAMS)?
+                .ok_or_else(|| Error::General("PKCS#11 key has no CKA_EC_PARAMS".into()))
+        };
+
+        match key_type {
+            Some(CKK_RSA) => Ok(Self::Rsa),
+            Some(CKK_EC) => match &ec_params()?[..] {
+                OID_SECP256R1 => Ok(Self::Ecdsa(SignatureScheme::ECDSA_NISTP256_SHA256)),
+                OID_SECP384R1 => Ok(Self::Ecdsa(SignatureScheme::ECDSA_NISTP384_SHA384)),
+                OID_SECP521R1 => Ok(Self::Ecdsa(SignatureScheme::ECDSA_NISTP521_SHA512)),
+                _ => Err(Error::General(
+                    "PKCS#11 key uses an unsupported curve".into(),
+                )),
+            },
+            Some(CKK_EC_EDWARDS) => match &ec_params()?[..] {
+                OID_ED25519 | NAME_ED25519 => Ok(Self::Ed25519),
+                _ => Err(Error::General(
+                    "PKCS#11 key uses an unsupported curve".into(),
+                )),
+            },
+            _ => Err(Error::General(
+                "PKCS#11 key is of an unsupported type".into(),
+            )),
+        }
+    }
+
+    fn schemes(&self) -> &[SignatureScheme] {
+        match self {
+            Self::Rsa => &[
+                SignatureScheme::RSA_PSS_SHA512,
+                SignatureScheme::RSA_PSS_SHA384,
+                SignatureScheme::RSA_PSS_SHA256,
+                SignatureScheme::RSA_PKCS1_SHA512,
+                SignatureScheme::RSA_PKCS1_SHA384,
+                SignatureScheme::RSA_PKCS1_SHA256,
+            ],
+            Self::Ecdsa(scheme) => core::slice::from_ref(scheme),
+            Self::Ed25519 => &[SignatureScheme::ED25519],
+        }
+    }
+
+    /// Find the public key for the private key `handle`.
+    ///
+    /// This uses `CKA_PUBLIC_KEY_INFO` if the token supports it.  Otherwise, RSA public keys
+    /// are built from attributes of the private key, and other public keys are read from the
+    /// public key object with the same `CKA_ID`.  `None` is returned if there is no such object.
+    fn public_key(
+        &self,
+        session: &Session,
+        handle: CK_OBJECT_HANDLE,
+    ) -> Result<Option<SubjectPublicKeyInfoDer<'static>>, Error> {
+        if let Some(spki) = session
+            .attribute(handle, CKA_PUBLIC_KEY_INFO)?
+            .filter(|spki| !spki.is_empty())
+        {
+            return Ok(Some(spki.into()));
+        }
+
+        let alg_id = match self {
+            Self::Rsa => {
+                let (Some(modulus), Some(exponent)) = (
+                    session.attribute(handle, CKA_MODULUS)?,
+                    session.attribute(handle, CKA_PUBLIC_EXPONENT)?,
+                ) else {
+                    return Ok(None);
+                };
+
+                let mut rsa_public_key = der_unsigned_integer(&modulus);
+                rsa_public_key.extend(der_unsigned_integer(&exponent));
+                return Ok(Some(public_key_to_spki(
+                    &alg_id::RSA_ENCRYPTION,
+                    der_tlv(DER_SEQUENCE, &rsa_public_key),
+                )));
+            }
+            Self::Ecdsa(SignatureScheme::ECDSA_NISTP256_SHA256) => alg_id::ECDSA_P256,
+            Self::Ecdsa(SignatureScheme::ECDSA_NISTP384_SHA384) => alg_id::ECDSA_P384,
+            Self::Ecdsa(_) => alg_id::ECDSA_P521,
+            Self::Ed25519 => alg_id::ED25519,
+        };
+
+        let Some(id) = session.attribute(handle, CKA_ID)? else {
+            return Ok(None);
+        };
+        let template = [
+            (CKA_CLASS, CKO_PUBLIC_KEY.to_ne_bytes().to_vec()),
+            (CKA_ID, id),
+        ];
+        let [public] = session.find_objects(&template)?[..] else {
+            return Ok(None);
+        };
+
+        Ok(session
+            .attribute(public, CKA_EC_POINT)?
+            .map(|point| public_key_to_spki(&alg_id, unwrap_octet_string(&point))))
+    }
+}
+
+/// A PKCS#11 session, which is closed when dropped.
+///
+/// A session can only be used by one thread at a time.
+struct Session {
+    module: Arc<Pkcs11Module>,
+    handle: CK_SESSION_HANDLE,
+}
+
+impl Session {
+    fn open(module: Arc<Pkcs11Module>, slot: CK_SLOT_ID) -> Result<Self, Error> {
+        let mut handle = 0;
+        // SAFETY: `handle` is a valid out-pointer; no notification callback is given.
+        check("C_OpenSession", unsafe {
+            (module.functions().C_OpenSession)(
+                slot,
+                CKF_SERIAL_SESSION,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                &mut handle,
+            )
+        })?;
+        Ok(Self { module, handle })
+    }
+
+    fn login(&self, pin: &str) -> Result<(), Error> {
+        // SAFETY: `pin` is valid for its length.
+        match unsafe {
+            (self.module.functions().C_Login)(
+                self.handle,
+                CKU_USER,
+                pin.as_ptr(),
+                pin.len() as CK_ULONG,
+            )
+        } {
+            // Login state is shared by all sessions with the token.
+            CKR_OK | CKR_USER_ALREADY_LOGGED_IN => Ok(()),
+            rv => Err(failed("C_Login", rv)),
+        }
+    }
+
+    /// Find up to two objects matching `template`.
+    ///
+    /// That is sufficient to tell whether the match is unique.
+    fn find_objects(
+        &self,
+        template: &[(CK_ULONG, Vec<u8>)],
+    ) -> Result<Vec<CK_OBJECT_HANDLE>, Error> {
+        let f = self.module.functions();
+        let mut attributes = template
+            .iter()
+            .map(|(type_, value)| CK_ATTRIBUTE {
+                type_: *type_,
+                pValue: value.as_ptr() as *mut c_void,
+                ulValueLen: value.len() as CK_ULONG,
+            })
+            .collect::<Vec<_>>();
+
+        // SAFETY: `attributes` and the values it points to outlive the call, and are only read.
+        check("C_FindObjectsInit", unsafe {
+            (f.C_FindObjectsInit)(
+                self.handle,
+                attributes.as_mut_ptr(),
+                attributes.len() as CK_ULONG,
+            )
+        })?;
+
+        let mut found = [0; 2];
+        let mut count = 0;
+        // SAFETY: `found` has room for the given number of handles.
+        let rv = unsafe {
+            (f.C_FindObjects)(
+                self.handle,
+                found.as_mut_ptr(),
+                found.len() as CK_ULONG,
+                &mut count,
+            )
+        };
+        // SAFETY: the search was initialized above.
+        let final_rv = unsafe { (f.C_FindObjectsFinal)(self.handle) };
+        check("C_FindObjects", rv)?;
+        check("C_FindObjectsFinal", final_rv)?;
+
+        Ok(found[..count as usize].to_vec())
+    }
+
+    /// Read the attribute `type_` of `object`.
+    ///
+    /// Returns `None` if the object does not have that attribute, or it is sensitive.
+    fn attribute(
+        &self,
+        object: CK_OBJECT_HANDLE,
+        type_: CK_ULONG,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let f = self.module.functions();
+        let mut attribute = CK_ATTRIBUTE {
+            type_,
+            pValue: ptr::null_mut(),
+            ulValueLen: 0,
+        };
+
+        // SAFETY: a null `pValue` asks only for the length.
+        match unsafe { (f.C_GetAttributeValue)(self.handle, object, &mut attribute, 1) } {
+            CKR_OK if attribute.ulValueLen != CK_UNAVAILABLE_INFORMATION => {}
+            CKR_OK | CKR_ATTRIBUTE_SENSITIVE | CKR_ATTRIBUTE_TYPE_INVALID => return Ok(None),
+            rv => return Err(failed("C_GetAttributeValue", rv)),
+        }
+
+        let mut value = vec![0u8; attribute.ulValueLen as usize];
+        attribute.pValue = value.as_mut_ptr() as *mut c_void;
+        // SAFETY: `value` has room for `ulValueLen` bytes.
+        check("C_GetAttributeValue", unsafe {
+            (f.C_GetAttributeValue)(self.handle, object, &mut attribute, 1)
+        })?;
+        value.truncate(attribute.ulValueLen as usize);
+        Ok(Some(value))
+    }
+
+    fn sign(
+        &self,
+        mechanism: &mut CK_MECHANISM,
+        key: CK_OBJECT_HANDLE,
+        message: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        let f = self.module.functions();
+        // SAFETY: `mechanism` and any parameters it points to outlive the call.
+        check("C_SignInit", unsafe {
+            (f.C_SignInit)(self.handle, mechanism, key)
+        })?;
+
+        let mut len = 0;
+        // SAFETY: a null signature buffer asks only for the length, and does not
+        // end the operation.
+        check("C_Sign", unsafe {
+            (f.C_Sign)(
+                self.handle,
+                message.as_ptr(),
+                message.len() as CK_ULONG,
+                ptr::null_mut(),
+                &mut len,
+            )
+        })?;
+
+        let mut signature = vec![0u8; len as usize];
+        // SAFETY: `signature` has room for `len` bytes.
+        check("C_Sign", unsafe {
+            (f.C_Sign)(
+                self.handle,
+                message.as_ptr(),
+                message.len() as CK_ULONG,
+                signature.as_mut_ptr(),
+                &mut len,
+            )
+        })?;
+        signature.truncate(len as usize);
+        Ok(signature)
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        // SAFETY: the session is open, and not used after this.
+        unsafe { (self.module.functions().C_CloseSession)(self.handle) };
+    }
+}
+
+fn pss_params(hash: CK_ULONG, mgf: CK_ULONG, salt_len: CK_ULONG) -> CK_RSA_PKCS_PSS_PARAMS {
+    CK_RSA_PKCS_PSS_PARAMS {
+        hashAlg: hash,
+        mgf,
+        sLen: salt_len,
+    }
+}
+
+/// Convert a PKCS#11 ECDSA signature (`r || s`) into a DER `Ecdsa-Sig-Value`.
+fn ecdsa_signature_to_der(signature: &[u8]) -> Result<Vec<u8>, Error> {
+    if signature.is_empty() || !signature.len().is_multiple_of(2) {
+        return Err(Error::General(
+            "PKCS#11 module returned a malformed ECDSA signature".into(),
+        ));
+    }
+
+    let (r, s) = signature.split_at(signature.len() / 2);
+    let mut body = der_unsigned_integer(r);
+    body.extend(der_unsigned_integer(s));
+    Ok(der_tlv(DER_SEQUENCE, &body))
+}
+
+/// Remove the DER `OCTET STRING` that PKCS#11 specifies around `CKA_EC_POINT`.
+///
+/// Some modules omit it, in which case `point` is returned unchanged.
+fn unwrap_octet_string(point: &[u8]) -> &[u8] {
+    let inner = match point {
+        [DER_OCTET_STRING, len @ 0..=0x7f, rest @ ..] => Some((*len as usize, rest)),
+        [DER_OCTET_STRING, 0x81, len, rest @ ..] => Some((*len as usize, rest)),
+        [DER_OCTET_STRING, 0x82, hi, lo, rest @ ..] => {
+            Some((usize::from(u16::from_be_bytes([*hi, *lo])), rest))
+        }
+        _ => None,
+    };
+
+    match inner {
+        Some((len, rest)) if len == rest.len() => rest,
+        _ => point,
+    }
+}
+
+fn der_unsigned_integer(bytes: &[u8]) -> Vec<u8> {
+    let first_nonzero = bytes
+        .iter()
+        .position(|&b| b != 0)
+        .unwrap_or_else(|| bytes.len().saturating_sub(1));
+    let bytes = &bytes[first_nonzero..];
+
+    let mut body = Vec::with_capacity(bytes.len() + 1);
+    if bytes
+        .first()
+        .is_none_or(|&b| b & 0x80 != 0)
+    {
+        body.push(0);
+    }
+    body.extend_from_slice(bytes);
+    der_tlv(DER_INTEGER, &body)
+}
+
+fn der_tlv(tag: u8, body: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(body.len() + 4);
+    out.push(tag);
+    match body.len() {
+        len @ 0..=0x7f => out.push(len as u8),
+        len @ 0x80..=0xff => out.extend([0x81, len as u8]),
+        len => {
+            out.push(0x82);
+            out.extend((len as u16).to_be_bytes());
+        }
+    }
+    out.extend_from_slice(body);
+    out
+}
+
+fn trim_padding(field: &[u8]) -> &[u8] {
+    let end = field
+        .iter()
+        .rposition(|&b| b != b' ')
+        .map_or(0, |i| i + 1);
+    &field[..end]
+}
+
+fn check(function: &str, rv: CK_RV) -> Result<(), Error> {
+    match rv {
+        CKR_OK => Ok(()),
+        rv => Err(failed(function, rv)),
+    }
+}
+
+fn failed(function: &str, rv: CK_RV) -> Error {
+    Error::General(format!("PKCS#11 {function} failed: CKR {rv:#x}"))
+}
+
+const DER_INTEGER: u8 = 0x02;
+const DER_OCTET_STRING: u8 = 0x04;
+const DER_SEQUENCE: u8 = 0x30;
+
+/// DER `OBJECT IDENTIFIER`s for the supported values of `CKA_EC_PARAMS`.
+const OID_SECP256R1: &[u8] = &[0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const OID_SECP384R1: &[u8] = &[0x06, 0x05, 0x2b, 0x81, 0x04, 0x00, 0x22];
+const OID_SECP521R1: &[u8] = &[0x06, 0x05, 0x2b, 0x81, 0x04, 0x00, 0x23];
+const OID_ED25519: &[u8] = &[0x06, 0x03, 0x2b, 0x65, 0x70];
+/// PKCS#11 3.0 also allows Edwards curves to be named with a `PrintableString`.
+const NAME_ED25519: &[u8] = b"\x13\x0cedwards25519";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ecdsa_signature_conversion() {
+        assert_eq!(
+            ecdsa_signature_to_der(&[0x01, 0x02, 0x80, 0x00]).unwrap(),
+            [
+                0x30, 0x09, 0x02, 0x02, 0x01, 0x02, 0x02, 0x03, 0x00, 0x80, 0x00
+            ]
+        );
+        assert_eq!(
+            ecdsa_signature_to_der(&[0x00, 0x00, 0x00, 0x7f]).unwrap(),
+            [0x30, 0x06, 0x02, 0x01, 0x00, 0x02, 0x01, 0x7f]
+        );
+        assert!(ecdsa_signature_to_der(&[]).is_err());
+        assert!(ecdsa_signature_to_der(&[1, 2, 3]).is_err());
+
+        // P-521 signatures need a long-form length
+        let der = ecdsa_signature_to_der(&[0xff; 132]).unwrap();
+        assert_eq!(&der[..3], &[0x30, 0x81, 0x8a]);
+        assert_eq!(der.len(), 3 + 0x8a);
+    }
+
+    #[test]
+    fn ec_point_unwrapping() {
+        assert_eq!(
+            unwrap_octet_string(&[0x04, 0x02, 0xaa, 0xbb]),
+            &[0xaa, 0xbb]
+        );
+        assert_eq!(
+            unwrap_octet_string(&[0x04, 0x03, 0xaa, 0xbb]),
+            &[0x04, 0x03, 0xaa, 0xbb]
+        );
+
+        let mut long = vec![0x04, 0x81, 0x85];
+        long.extend([0x04; 0x85]);
+        assert_eq!(unwrap_octet_string(&long), &[0x04; 0x85]);
+
+        // an unwrapped uncompressed P-256 point starts with 0x04 too
+        let mut raw = vec![0x04];
+        raw.extend([0x41; 64]);
+        assert_eq!(unwrap_octet_string(&raw), &raw[..]);
+    }
+
+    #[test]
+    fn token_info_padding() {
+        assert_eq!(trim_padding(b"softhsm   "), b"softhsm");
+        assert_eq!(trim_padding(b"    "), b"");
+        assert_eq!(trim_padding(b"a b"), b"a b");
+    }
+}
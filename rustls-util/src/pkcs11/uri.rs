@@ -0,0 +1,279 @@
+use std::fs;
+
+use rustls::error::Error;
+
+use super::ffi::CK_SLOT_ID;
+
+/// A parsed `pkcs11:` URI, as defined by [RFC 7512].
+///
+/// Only the attributes that identify a token and a private key object, and
+/// those that supply a PIN, are retained.  Other attributes defined by the
+/// RFC are accepted and ignored, as are vendor-specific (`x-`) attributes.
+///
+/// [RFC 7512]: https://www.rfc-editor.org/rfc/rfc7512
+#[derive(Debug, Default, PartialEq)]
+pub(super) struct Pkcs11Uri {
+    pub(super) token: Option<String>,
+    pub(super) manufacturer: Option<String>,
+    pub(super) model: Option<String>,
+    pub(super) serial: Option<String>,
+    pub(super) slot_id: Option<CK_SLOT_ID>,
+    pub(super) object: Option<String>,
+    pub(super) id: Option<Vec<u8>>,
+    pub(super) object_type: Option<String>,
+    pub(super) pin_value: Option<String>,
+    pub(super) pin_source: Option<String>,
+}
+
+impl Pkcs11Uri {
+    pub(super) fn parse(uri: &str) -> Result<Self, Error> {
+        let Some(rest) = uri.strip_prefix("pkcs11:") else {
+            return Err(invalid("missing `pkcs11:` scheme"));
+        };
+
+        let (path, query) = match rest.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (rest, None),
+        };
+
+        let mut out = Self::default();
+        for attr in path
+            .split(';')
+            .filter(|attr| !attr.is_empty())
+        {
+            let (name, value) = split_attr(attr)?;
+            let slot = match name {
+                "token" => &mut out.token,
+                "manufacturer" => &mut out.manufacturer,
+                "model" => &mut out.model,
+                "serial" => &mut out.serial,
+                "object" => &mut out.object,
+                "type" => &mut out.object_type,
+                "id" => {
+                    if out
+                        .id
+                        .replace(percent_decode(value)?)
+                        .is_some()
+                    {
+                        return Err(duplicate(name));
+                    }
+                    continue;
+                }
+                "slot-id" => {
+                    let slot_id = decode_utf8(value)?
+                        .parse()
+                        .map_err(|_| invalid("`slot-id` is not a number"))?;
+                    if out.slot_id.replace(slot_id).is_some() {
+                        return Err(duplicate(name));
+                    }
+                    continue;
+                }
+                "library-manufacturer"
+                | "library-description"
+                | "library-version"
+                | "slot-manufacturer"
+                | "slot-description" => continue,
+                _ if name.starts_with("x-") => continue,
+                _ => return Err(invalid(&format!("unknown path attribute `{name}`"))),
+            };
+
+            if slot
+                .replace(decode_utf8(value)?)
+                .is_some()
+            {
+                return Err(duplicate(name));
+            }
+        }
+
+        for attr in query
+            .into_iter()
+            .flat_map(|query| query.split('&'))
+            .filter(|attr| !attr.is_empty())
+        {
+            let (name, value) = split_attr(attr)?;
+            let slot = match name {
+                "pin-value" => &mut out.pin_value,
+                "pin-source" => &mut out.pin_source,
+                // The module is chosen by the caller, not by the URI.
+                "module-name" | "module-path" => continue,
+                _ if name.starts_with("x-") => continue,
+                _ => return Err(invalid(&format!("unknown query attribute `{name}`"))),
+            };
+
+            if slot
+                .replace(decode_utf8(value)?)
+                .is_some()
+            {
+                return Err(duplicate(name));
+            }
+        }
+
+        Ok(out)
+    }
+
+    /// Return the PIN given by `pin-value` or `pin-source`, if any.
+    ///
+    /// `pin-source` is interpreted as the name of a file containing the PIN,
+    /// optionally prefixed with `file:`.
+    pub(super) fn pin(&self) -> Result<Option<String>, Error> {
+        if let Some(pin) = &self.pin_value {
+            return Ok(Some(pin.clone()));
+        }
+
+        let Some(source) = &self.pin_source else {
+            return Ok(None);
+        };
+
+        let path = source
+            .strip_prefix("file:")
+            .unwrap_or(source);
+        let pin = fs::read_to_string(path)
+            .map_err(|err| Error::General(format!("cannot read PKCS#11 pin-source: {err}")))?;
+        Ok(Some(
+            pin.trim_end_matches(['\r', '\n'])
+                .to_owned(),
+        ))
+    }
+}
+
+fn split_attr(attr: &str) -> Result<(&str, &str), Error> {
+    attr.split_once('=')
+        .ok_or_else(|| invalid(&format!("attribute `{attr}` has no value")))
+}
+
+fn decode_utf8(value: &str) -> Result<String, Error> {
+    String::from_utf8(percent_decode(value)?).map_err(|_| invalid("attribute is not UTF-8"))
+}
+
+fn percent_decode(value: &str) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::with_capacity(value.len());
+    let mut bytes = value.bytes();
+    while let Some(b) = bytes.next() {
+        if b != b'%' {
+            out.push(b);
+            continue;
+        }
+
+        let hex = [bytes.next(), bytes.next()];
+        let [Some(hi), Some(lo)] = hex else {
+            return Err(invalid("truncated percent-encoding"));
+        };
+        let decoded = core::str::from_utf8(&[hi, lo])
+            .ok()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            .ok_or_else(|| invalid("invalid percent-encoding"))?;
+        out.push(decoded);
+    }
+    Ok(out)
+}
+
+fn duplicate(name: &str) -> Error {
+    invalid(&format!("duplicate attribute `{name}`"))
+}
+
+fn invalid(why: &str) -> Error {
+    Error::General(format!("invalid PKCS#11 URI: {why}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rfc7512_examples() {
+        assert_eq!(Pkcs11Uri::parse("pkcs11:").unwrap(), Pkcs11Uri::default());
+
+        assert_eq!(
+            Pkcs11Uri::parse("pkcs11:object=my-pubkey;type=public").unwrap(),
+            Pkcs11Uri {
+                object: Some("my-pubkey".into()),
+                object_type: Some("public".into()),
+                ..Default::default()
+            }
+        );
+
+        assert_eq!(
+            Pkcs11Uri::parse(
+                "pkcs11:token=The%20Software%20PKCS%2311%20Softtoken;\
+                 manufacturer=Snake%20Oil,%20Inc.;\
+                 model=1.0;\
+                 object=my-certificate;\
+                 type=cert;\
+                 id=%69%95%3E%5C%F4%BD%EC%91;\
+                 serial=\
+                 ?pin-source=file:/etc/token_pin"
+            )
+            .unwrap(),
+            Pkcs11Uri {
+                token: Some("The Software PKCS#11 Softtoken".into()),
+                manufacturer: Some("Snake Oil, Inc.".into()),
+                model: Some("1.0".into()),
+                serial: Some("".into()),
+                object: Some("my-certificate".into()),
+                id: Some(vec![0x69, 0x95, 0x3e, 0x5c, 0xf4, 0xbd, 0xec, 0x91]),
+                object_type: Some("cert".into()),
+                pin_source: Some("file:/etc/token_pin".into()),
+                ..Default::default()
+            }
+        );
+
+        assert_eq!(
+            Pkcs11Uri::parse(
+                "pkcs11:object=my-sign-key;type=private?module-name=mypkcs11&pin-value=1234"
+            )
+            .unwrap(),
+            Pkcs11Uri {
+                object: Some("my-sign-key".into()),
+                object_type: Some("private".into()),
+                pin_value: Some("1234".into()),
+                ..Default::default()
+            }
+        );
+
+        assert_eq!(
+            Pkcs11Uri::parse(
+                "pkcs11:slot-description=Sun%20Metaslot;library-version=1;slot-id=7;x-vendor=1"
+            )
+            .unwrap(),
+            Pkcs11Uri {
+                slot_id: Some(7),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_uris() {
+        for uri in [
+            "pkcs12:object=a",
+            "object=a",
+            "pkcs11:object",
+            "pkcs11:object=a;object=b",
+            "pkcs11:id=%1",
+            "pkcs11:id=%zz",
+            "pkcs11:slot-id=seven",
+            "pkcs11:unknown=1",
+            "pkcs11:object=a?unknown=1",
+            "pkcs11:object=%ff",
+        ] {
+            assert!(Pkcs11Uri::parse(uri).is_err(), "{uri}");
+        }
+    }
+
+    #[test]
+    fn pin_prefers_pin_value() {
+        let uri = Pkcs11Uri::parse("pkcs11:?pin-value=1234&pin-source=/nonexistent").unwrap();
+        assert_eq!(uri.pin().unwrap(), Some("1234".into()));
+
+        let uri = Pkcs11Uri::parse("pkcs11:?pin-source=file:/nonexistent").unwrap();
+        assert!(uri.pin().is_err());
+
+        assert_eq!(
+            Pkcs11Uri::parse("pkcs11:")
+                .unwrap()
+                .pin()
+                .unwrap(),
+            None
+        );
+    }
+}
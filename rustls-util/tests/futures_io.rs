@@ -0,0 +1,226 @@
+//! Tests for `rustls_util::futures_io` over in-memory pipes.
+
+#![cfg(feature = "futures-io")]
+
+use core::future::poll_fn;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use futures_io::{AsyncRead, AsyncWrite};
+use rustls::crypto::Identity;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::{ClientConfig, RootCertStore, ServerConfig};
+use rustls_util::futures_io::{accept, connect, start_handshake};
+use tokio::io::{DuplexStream, ReadBuf, duplex};
+
+#[tokio::test]
+async fn client_and_server_round_trip() {
+    let (client_io, server_io) = pipe();
+    let large = vec![0x5a; 100_000];
+
+    let client = async {
+        let mut stream = connect(client_config(), server_name(), client_io)
+            .await
+            .unwrap();
+        write_all(&mut stream, &large)
+            .await
+            .unwrap();
+        close(&mut stream).await.unwrap();
+        read_to_end(&mut stream).await.unwrap()
+    };
+
+    let server = async {
+        let mut stream = accept(server_config(), server_io)
+            .await
+            .unwrap();
+        assert_eq!(read_to_end(&mut stream).await.unwrap(), large);
+        write_all(&mut stream, b"goodbye")
+            .await
+            .unwrap();
+        close(&mut stream).await.unwrap();
+    };
+
+    let (received, ()) = tokio::join!(client, server);
+    assert_eq!(received, b"goodbye");
+}
+
+#[tokio::test]
+async fn server_start_handshake_with_split_halves() {
+    let (client_io, server_io) = pipe();
+
+    let client = async {
+        let stream = connect(client_config(), server_name(), client_io)
+            .await
+            .unwrap();
+        let (mut read, mut write) = stream.into_split().unwrap();
+        write_all(&mut write, b"ping")
+            .await
+            .unwrap();
+        close(&mut write).await.unwrap();
+        read_to_end(&mut read).await.unwrap()
+    };
+
+    let server = async {
+        let start = start_handshake(server_io)
+            .await
+            .unwrap();
+        assert_eq!(
+            start
+                .client_hello()
+                .server_name()
+                .map(|name| name.as_ref()),
+            Some("testserver.com")
+        );
+
+        let (mut read, mut write) = start
+            .complete(server_config())
+            .await
+            .unwrap();
+        assert_eq!(read_to_end(&mut read).await.unwrap(), b"ping");
+        write_all(&mut write, b"pong")
+            .await
+            .unwrap();
+        close(&mut write).await.unwrap();
+    };
+
+    let (received, ()) = tokio::join!(client, server);
+    assert_eq!(received, b"pong");
+}
+
+#[tokio::test]
+async fn truncation_is_an_error() {
+    let (client_io, server_io) = pipe();
+
+    let client = async {
+        let mut stream = connect(client_config(), server_name(), client_io)
+            .await
+            .unwrap();
+        let err = read_to_end(&mut stream)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    };
+
+    let server = async {
+        let mut stream = accept(server_config(), server_io)
+            .await
+            .unwrap();
+        write_all(&mut stream, b"partial")
+            .await
+            .unwrap();
+        // Close the transport without sending `close_notify`.
+        close(stream.get_mut().1).await.unwrap();
+    };
+
+    tokio::join!(client, server);
+}
+
+async fn write_all(io: &mut (impl AsyncWrite + Unpin), mut buf: &[u8]) -> io::Result<()> {
+    while !buf.is_empty() {
+        let n = poll_fn(|cx| Pin::new(&mut *io).poll_write(cx, buf)).await?;
+        buf = &buf[n..];
+    }
+    poll_fn(|cx| Pin::new(&mut *io).poll_flush(cx)).await
+}
+
+async fn read_to_end(io: &mut (impl AsyncRead + Unpin)) -> io::Result<Vec<u8>> {
+    let mut received = Vec::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        match poll_fn(|cx| Pin::new(&mut *io).poll_read(cx, &mut buf)).await? {
+            0 => return Ok(received),
+            n => received.extend_from_slice(&buf[..n]),
+        }
+    }
+}
+
+async fn close(io: &mut (impl AsyncWrite + Unpin)) -> io::Result<()> {
+    poll_fn(|cx| Pin::new(&mut *io).poll_close(cx)).await
+}
+
+fn pipe() -> (Pipe, Pipe) {
+    let (a, b) = duplex(4096);
+    (Pipe(a), Pipe(b))
+}
+
+/// A [`DuplexStream`] exposed through the `futures-io` traits.
+struct Pipe(DuplexStream);
+
+impl AsyncRead for Pipe {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut buf = ReadBuf::new(buf);
+        match tokio::io::AsyncRead::poll_read(Pin::new(&mut self.0), cx, &mut buf) {
+            Poll::Ready(Ok(())) => Poll::Ready(Ok(buf.filled().len())),
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl AsyncWrite for Pipe {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        tokio::io::AsyncWrite::poll_write(Pin::new(&mut self.0), cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        tokio::io::AsyncWrite::poll_flush(Pin::new(&mut self.0), cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        tokio::io::AsyncWrite::poll_shutdown(Pin::new(&mut self.0), cx)
+    }
+}
+
+fn client_config() -> Arc<ClientConfig> {
+    let mut roots = RootCertStore::empty();
+    roots.add_parsable_certificates(
+        CertificateDer::pem_file_iter(test_ca().join("ca.cert"))
+            .unwrap()
+            .map(|result| result.unwrap()),
+    );
+
+    Arc::new(
+        ClientConfig::builder(Arc::new(rustls_ring::DEFAULT_PROVIDER))
+            .with_root_certificates(roots)
+            .with_no_client_auth()
+            .unwrap(),
+    )
+}
+
+fn server_config() -> Arc<ServerConfig> {
+    let identity = Identity::from_cert_chain(
+        CertificateDer::pem_file_iter(test_ca().join("end.fullchain"))
+            .unwrap()
+            .map(|result| result.unwrap())
+            .collect(),
+    )
+    .unwrap();
+    let key = PrivateKeyDer::from_pem_file(test_ca().join("end.key")).unwrap();
+
+    Arc::new(
+        ServerConfig::builder(Arc::new(rustls_ring::DEFAULT_PROVIDER))
+            .with_no_client_auth()
+            .with_single_cert(Arc::new(identity), key)
+            .unwrap(),
+    )
+}
+
+fn server_name() -> ServerName<'static> {
+    ServerName::try_from("testserver.com").unwrap()
+}
+
+fn test_ca() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../test-ca/ecdsa-p256")
+}
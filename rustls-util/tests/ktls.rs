@@ -0,0 +1,261 @@
+//! Tests for `rustls_util::ktls` over loopback sockets.
+//!
+//! These are skipped if the kernel does not support TLS.
+
+#![cfg(all(feature = "ktls", target_os = "linux"))]
+
+use std::borrow::Cow;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+
+use rustls::client::ClientSide;
+use rustls::crypto::{CryptoProvider, Identity};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::server::ServerSide;
+use rustls::{
+    ClientConfig, Connection, HandshakeKind, RootCertStore, ServerConfig, ServerConnection,
+    VecInput,
+};
+use rustls_ring::cipher_suite::*;
+use rustls_ring::{DEFAULT_TLS12_PROVIDER, DEFAULT_TLS13_PROVIDER};
+use rustls_util::complete_io;
+use rustls_util::ktls::KtlsStream;
+
+#[test]
+fn tls13_cipher_suites() {
+    for suite in [
+        TLS13_AES_128_GCM_SHA256,
+        TLS13_AES_256_GCM_SHA384,
+        TLS13_CHACHA20_POLY1305_SHA256,
+    ] {
+        let provider = Arc::new(CryptoProvider {
+            tls13_cipher_suites: Cow::Owned(vec![suite]),
+            ..DEFAULT_TLS13_PROVIDER
+        });
+        let Some((client, server, _)) =
+            connect(&client_config(&provider), server_config(&provider))
+        else {
+            return;
+        };
+        exchange(client, server);
+    }
+}
+
+#[test]
+fn tls12_cipher_suites() {
+    for suite in [
+        TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256,
+        TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384,
+        TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256,
+    ] {
+        let provider = Arc::new(CryptoProvider {
+            tls12_cipher_suites: Cow::Owned(vec![suite]),
+            ..DEFAULT_TLS12_PROVIDER
+        });
+        let Some((mut client, server, _)) =
+            connect(&client_config(&provider), server_config(&provider))
+        else {
+            return;
+        };
+        assert_eq!(
+            client
+                .refresh_traffic_keys()
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::Other
+        );
+        exchange(client, server);
+    }
+}
+
+#[test]
+fn tls13_key_update() {
+    let provider = Arc::new(DEFAULT_TLS13_PROVIDER);
+    let Some((mut client, mut server, _)) =
+        connect(&client_config(&provider), server_config(&provider))
+    else {
+        return;
+    };
+
+    match client.refresh_traffic_keys() {
+        Err(err) if err.kind() == io::ErrorKind::Unsupported => {
+            eprintln!("skipping: {err}");
+            return;
+        }
+        result => result.unwrap(),
+    }
+    client
+        .write_all(b"after client update")
+        .unwrap();
+    let mut received = [0u8; 19];
+    server
+        .read_exact(&mut received)
+        .unwrap();
+    assert_eq!(&received, b"after client update");
+
+    server.refresh_traffic_keys().unwrap();
+    server
+        .write_all(b"after server update")
+        .unwrap();
+    client
+        .read_exact(&mut received)
+        .unwrap();
+    assert_eq!(&received, b"after server update");
+
+    exchange(client, server);
+}
+
+#[test]
+fn tls13_session_tickets_are_usable() {
+    let provider = Arc::new(DEFAULT_TLS13_PROVIDER);
+    let client_config = client_config(&provider);
+    let server_config = server_config(&provider);
+
+    let Some((client, server, kind)) = connect(&client_config, server_config.clone()) else {
+        return;
+    };
+    assert_eq!(kind, HandshakeKind::Full);
+    // The client receives the server's tickets through the kernel.
+    exchange(client, server);
+
+    let (client, server, kind) = connect(&client_config, server_config).unwrap();
+    assert_eq!(kind, HandshakeKind::Resumed);
+    exchange(client, server);
+}
+
+#[test]
+fn truncation_is_an_error() {
+    let provider = Arc::new(DEFAULT_TLS13_PROVIDER);
+    let Some((mut client, server, _)) =
+        connect(&client_config(&provider), server_config(&provider))
+    else {
+        return;
+    };
+
+    drop(server);
+    let err = client
+        .read_to_end(&mut Vec::new())
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+}
+
+/// Send data in both directions, then close both sides.
+fn exchange(mut client: KtlsStream<ClientSide>, mut server: KtlsStream<ServerSide>) {
+    let large = vec![0x5a; 100_000];
+
+    // The server speaks first, and writes more than fits in one record.
+    let writer = {
+        let large = large.clone();
+        thread::spawn(move || {
+            server.write_all(&large).unwrap();
+            server.send_close_notify().unwrap();
+            server
+        })
+    };
+
+    let mut received = Vec::new();
+    client
+        .read_to_end(&mut received)
+        .unwrap();
+    assert_eq!(received, large);
+
+    let mut server = writer.join().unwrap();
+    client.write_all(b"goodbye").unwrap();
+    client.send_close_notify().unwrap();
+
+    let mut received = Vec::new();
+    server
+        .read_to_end(&mut received)
+        .unwrap();
+    assert_eq!(received, b"goodbye");
+}
+
+/// Complete a handshake over loopback, and offload both sides to the kernel.
+///
+/// Returns `None` if the kernel does not support TLS.
+fn connect(
+    client_config: &Arc<ClientConfig>,
+    server_config: Arc<ServerConfig>,
+) -> Option<(
+    KtlsStream<ClientSide>,
+    KtlsStream<ServerSide>,
+    HandshakeKind,
+)> {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = thread::spawn(move || {
+        let (mut socket, _) = listener.accept().unwrap();
+        let mut conn = ServerConnection::new(server_config).unwrap();
+        handshake(&mut socket, &mut conn, Vec::new());
+        KtlsStream::server(socket, conn.split().unwrap())
+    });
+
+    let mut socket = TcpStream::connect(addr).unwrap();
+    let mut output = Vec::new();
+    let mut conn = client_config
+        .connect(ServerName::try_from("testserver.com").unwrap())
+        .build(&mut output)
+        .unwrap();
+    handshake(&mut socket, &mut conn, output);
+    let kind = conn.handshake_kind().unwrap();
+    let client = KtlsStream::client(socket, conn.split().unwrap());
+
+    match (client, server.join().unwrap()) {
+        (Ok(client), Ok(server)) => Some((client, server, kind)),
+        (Err(err), _) | (_, Err(err)) if err.kind() == io::ErrorKind::Unsupported => {
+            eprintln!("skipping: {err}");
+            None
+        }
+        (client, server) => panic!("client: {:?}, server: {:?}", client.err(), server.err()),
+    }
+}
+
+fn handshake(socket: &mut TcpStream, conn: &mut impl Connection, mut output: Vec<u8>) {
+    let mut input = VecInput::default();
+    while conn.is_handshaking() || !output.is_empty() {
+        complete_io(socket, &mut input, &mut Vec::new(), &mut output, conn).unwrap();
+    }
+}
+
+fn client_config(provider: &Arc<CryptoProvider>) -> Arc<ClientConfig> {
+    let mut roots = RootCertStore::empty();
+    roots.add_parsable_certificates(
+        CertificateDer::pem_file_iter(test_ca().join("ca.cert"))
+            .unwrap()
+            .map(|result| result.unwrap()),
+    );
+
+    let mut config = ClientConfig::builder(provider.clone())
+        .with_root_certificates(roots)
+        .with_no_client_auth()
+        .unwrap();
+    config.enable_secret_extraction = true;
+    Arc::new(config)
+}
+
+fn server_config(provider: &Arc<CryptoProvider>) -> Arc<ServerConfig> {
+    let identity = Identity::from_cert_chain(
+        CertificateDer::pem_file_iter(test_ca().join("end.fullchain"))
+            .unwrap()
+            .map(|result| result.unwrap())
+            .collect(),
+    )
+    .unwrap();
+    let key = PrivateKeyDer::from_pem_file(test_ca().join("end.key")).unwrap();
+
+    let mut config = ServerConfig::builder(provider.clone())
+        .with_no_client_auth()
+        .with_single_cert(Arc::new(identity), key)
+        .unwrap();
+    config.enable_secret_extraction = true;
+    Arc::new(config)
+}
+
+fn test_ca() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../test-ca/ecdsa-p256")
+}
@@ -0,0 +1,99 @@
+//! Tests for `rustls_util::pkcs11` against a real PKCS#11 module.
+//!
+//! These need a token prepared by `admin/softhsm-test`, which also runs them.
+
+#![cfg(feature = "pkcs11")]
+
+use std::env;
+use std::path::PathBuf;
+
+use rustls::crypto::SignatureScheme;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, SubjectPublicKeyInfoDer};
+use rustls_util::pkcs11::{Pkcs11KeyProvider, Pkcs11Module};
+
+#[test]
+#[ignore = "needs a PKCS#11 token: run admin/softhsm-test"]
+fn signs_with_token_keys() {
+    let module = Pkcs11Module::load(env::var("RUSTLS_PKCS11_MODULE").unwrap()).unwrap();
+    let provider = Pkcs11KeyProvider::new(module).with_pin(env::var("RUSTLS_PKCS11_PIN").unwrap());
+    let verify = rustls_ring::DEFAULT_PROVIDER.signature_verification_algorithms;
+
+    for key_type in [
+        "rsa-2048",
+        "ecdsa-p256",
+        "ecdsa-p384",
+        "ecdsa-p521",
+        "eddsa",
+    ] {
+        let key = provider
+            .load_uri(&format!("pkcs11:token=rustls;object={key_type}"))
+            .unwrap();
+
+        let test_ca = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("../test-ca")
+            .join(key_type);
+        let spki = SubjectPublicKeyInfoDer::from_pem_file(test_ca.join("end.spki.pem")).unwrap();
+        assert_eq!(key.public_key().unwrap(), spki, "{key_type}");
+
+        let cert = CertificateDer::from_pem_file(test_ca.join("end.cert")).unwrap();
+        let cert = webpki::EndEntityCert::try_from(&cert).unwrap();
+
+        let mut signed = 0;
+        for &(scheme, algs) in verify.mapping() {
+            let Some(signer) = key.choose_scheme(&[scheme]) else {
+                continue;
+            };
+            assert_eq!(signer.scheme(), scheme);
+
+            let signature = signer.sign(b"hello world").unwrap();
+            assert!(
+                algs.iter().any(|alg| cert
+                    .verify_signature(*alg, b"hello world", &signature)
+                    .is_ok()),
+                "{key_type} {scheme:?}"
+            );
+            signed += 1;
+        }
+
+        // ring cannot verify P-521 signatures
+        assert!(signed > 0 || key_type == "ecdsa-p521", "{key_type}");
+    }
+
+    assert!(
+        provider
+            .load_uri("pkcs11:token=rustls;object=missing")
+            .is_err()
+    );
+    assert!(
+        provider
+            .load_uri("pkcs11:token=missing;object=eddsa")
+            .is_err()
+    );
+    assert!(
+        provider
+            .load_uri("pkcs11:token=rustls;object=eddsa;type=cert")
+            .is_err()
+    );
+}
+
+#[test]
+#[ignore = "needs a PKCS#11 token: run admin/softhsm-test"]
+fn rsa_key_offers_pss_first() {
+    let module = Pkcs11Module::load(env::var("RUSTLS_PKCS11_MODULE").unwrap()).unwrap();
+    let provider = Pkcs11KeyProvider::new(module);
+    let pin = env::var("RUSTLS_PKCS11_PIN").unwrap();
+    let key = provider
+        .load_uri(&format!(
+            "pkcs11:token=rustls;object=rsa-2048?pin-value={pin}"
+        ))
+        .unwrap();
+
+    let signer = key
+        .choose_scheme(&[
+            SignatureScheme::RSA_PKCS1_SHA256,
+            SignatureScheme::RSA_PSS_SHA256,
+        ])
+        .unwrap();
+    assert_eq!(signer.scheme(), SignatureScheme::RSA_PSS_SHA256);
+}
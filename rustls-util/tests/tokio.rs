@@ -0,0 +1,232 @@
+//! Tests for `rustls_util::tokio` over in-memory pipes.
+
+#![cfg(feature = "tokio")]
+
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use rustls::crypto::Identity;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::{ClientConfig, Connection, RootCertStore, ServerConfig};
+use rustls_util::tokio::{accept, connect, start_handshake};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, duplex};
+
+#[tokio::test]
+async fn client_and_server_round_trip() {
+    let (client_io, server_io) = duplex(4096);
+    let large = vec![0x5a; 100_000];
+
+    let client = async {
+        let mut stream = connect(client_config(), server_name(), client_io)
+            .await
+            .unwrap();
+        assert!(!stream.get_ref().0.is_handshaking());
+        stream.write_all(&large).await.unwrap();
+        stream.shutdown().await.unwrap();
+
+        let mut received = Vec::new();
+        stream
+            .read_to_end(&mut received)
+            .await
+            .unwrap();
+        received
+    };
+
+    let server = async {
+        let mut stream = accept(server_config(), server_io)
+            .await
+            .unwrap();
+        let mut received = Vec::new();
+        stream
+            .read_to_end(&mut received)
+            .await
+            .unwrap();
+        assert_eq!(received, large);
+
+        stream
+            .write_all(b"goodbye")
+            .await
+            .unwrap();
+        stream.shutdown().await.unwrap();
+    };
+
+    let (received, ()) = tokio::join!(client, server);
+    assert_eq!(received, b"goodbye");
+}
+
+#[tokio::test]
+async fn server_start_handshake_with_split_halves() {
+    let (client_io, server_io) = duplex(4096);
+
+    let client = async {
+        let mut stream = connect(client_config(), server_name(), client_io)
+            .await
+            .unwrap();
+        stream.write_all(b"ping").await.unwrap();
+
+        let mut received = [0u8; 4];
+        stream
+            .read_exact(&mut received)
+            .await
+            .unwrap();
+        assert_eq!(&received, b"pong");
+        stream.shutdown().await.unwrap();
+        assert_eq!(
+            stream
+                .read(&mut [0u8; 1])
+                .await
+                .unwrap(),
+            0
+        );
+    };
+
+    let server = async {
+        let start = start_handshake(server_io)
+            .await
+            .unwrap();
+        assert_eq!(
+            start
+                .client_hello()
+                .server_name()
+                .map(|name| name.as_ref()),
+            Some("testserver.com")
+        );
+
+        let (mut read, mut write) = start
+            .complete(server_config())
+            .await
+            .unwrap();
+        let mut received = [0u8; 4];
+        read.read_exact(&mut received)
+            .await
+            .unwrap();
+        assert_eq!(&received, b"ping");
+
+        write.write_all(b"pong").await.unwrap();
+        assert_eq!(read.read(&mut [0u8; 1]).await.unwrap(), 0);
+        write.shutdown().await.unwrap();
+    };
+
+    tokio::join!(client, server);
+}
+
+#[tokio::test]
+async fn split_halves_are_concurrent() {
+    let (client_io, server_io) = duplex(1024);
+    let large = vec![0xa5; 200_000];
+
+    let client = async {
+        let stream = connect(client_config(), server_name(), client_io)
+            .await
+            .unwrap();
+        let (mut read, mut write) = stream.into_split().unwrap();
+
+        // The server echoes everything, so neither direction can complete alone.
+        let writer = async {
+            write.write_all(&large).await.unwrap();
+            write.shutdown().await.unwrap();
+        };
+        let reader = async {
+            let mut received = Vec::new();
+            read.read_to_end(&mut received)
+                .await
+                .unwrap();
+            received
+        };
+        let ((), received) = tokio::join!(writer, reader);
+        assert_eq!(received, large);
+    };
+
+    let server = async {
+        let stream = accept(server_config(), server_io)
+            .await
+            .unwrap();
+        let (mut read, mut write) = stream.into_split().unwrap();
+        tokio::io::copy(&mut read, &mut write)
+            .await
+            .unwrap();
+        write.shutdown().await.unwrap();
+    };
+
+    tokio::join!(client, server);
+}
+
+#[tokio::test]
+async fn truncation_is_an_error() {
+    let (client_io, server_io) = duplex(4096);
+
+    let client = async {
+        let mut stream = connect(client_config(), server_name(), client_io)
+            .await
+            .unwrap();
+        let err = stream
+            .read_to_end(&mut Vec::new())
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    };
+
+    let server = async {
+        let mut stream = accept(server_config(), server_io)
+            .await
+            .unwrap();
+        stream
+            .write_all(b"partial")
+            .await
+            .unwrap();
+        stream.flush().await.unwrap();
+        // Close the transport without sending `close_notify`.
+        stream
+            .get_mut()
+            .1
+            .shutdown()
+            .await
+            .unwrap();
+    };
+
+    tokio::join!(client, server);
+}
+
+fn client_config() -> Arc<ClientConfig> {
+    let mut roots = RootCertStore::empty();
+    roots.add_parsable_certificates(
+        CertificateDer::pem_file_iter(test_ca().join("ca.cert"))
+            .unwrap()
+            .map(|result| result.unwrap()),
+    );
+
+    Arc::new(
+        ClientConfig::builder(Arc::new(rustls_ring::DEFAULT_PROVIDER))
+            .with_root_certificates(roots)
+            .with_no_client_auth()
+            .unwrap(),
+    )
+}
+
+fn server_config() -> Arc<ServerConfig> {
+    let identity = Identity::from_cert_chain(
+        CertificateDer::pem_file_iter(test_ca().join("end.fullchain"))
+            .unwrap()
+            .map(|result| result.unwrap())
+            .collect(),
+    )
+    .unwrap();
+    let key = PrivateKeyDer::from_pem_file(test_ca().join("end.key")).unwrap();
+
+    Arc::new(
+        ServerConfig::builder(Arc::new(rustls_ring::DEFAULT_PROVIDER))
+            .with_no_client_auth()
+            .with_single_cert(Arc::new(identity), key)
+            .unwrap(),
+    )
+}
+
+fn server_name() -> ServerName<'static> {
+    ServerName::try_from("testserver.com").unwrap()
+}
+
+fn test_ca() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../test-ca/ecdsa-p256")
+}
@@ -13,23 +13,24 @@ use super::handy::{ClientSessionMemoryCache, FailResolveClientCert, NoClientSess
 use super::{Tls12Session, Tls13Session};
 use crate::builder::{ConfigBuilder, WantsVerifier};
 use crate::client::connection::ClientConnectionBuilder;
-use crate::common_state::Protocol;
+use crate::common_state::{Protocol, Side};
 #[cfg(doc)]
 use crate::crypto;
 use crate::crypto::kx::NamedGroup;
 use crate::crypto::{CipherSuite, CryptoProvider, SelectedCredential, SignatureScheme, hash};
 #[cfg(feature = "webpki")]
 use crate::crypto::{Credentials, Identity, SingleCredential};
-use crate::enums::{ApplicationProtocol, CertificateType, ProtocolVersion};
+use crate::enums::{ApplicationProtocol, ApplicationSettings, CertificateType, ProtocolVersion};
 use crate::error::{ApiMisuse, Error};
 use crate::key_log::NoKeyLog;
+use crate::qlog::{Qlog, QlogTracer};
 use crate::suites::SupportedCipherSuite;
 use crate::sync::Arc;
 use crate::time_provider::{DefaultTimeProvider, TimeProvider};
 use crate::verify::ServerVerifier;
 #[cfg(feature = "webpki")]
 use crate::webpki::{self, WebPkiServerVerifier};
-use crate::{DistinguishedName, DynHasher, KeyLog, compress};
+use crate::{ConnectionObserver, DistinguishedName, DynHasher, KeyLog, RecordPadding, compress};
 
 /// Common configuration for (typically) all connections made by a program.
 ///
@@ -50,13 +51,18 @@ use crate::{DistinguishedName, DynHasher, KeyLog, compress};
 /// # Defaults
 ///
 /// * [`ClientConfig::max_fragment_size`]: the default is `None` (meaning 16kB).
+/// * [`ClientConfig::record_padding`]: the default is [`RecordPadding::None`].
 /// * [`ClientConfig::resumption`]: supports resumption with up to 256 server names, using session
 ///   ids or tickets, with a max of eight tickets per server.
 /// * [`ClientConfig::alpn_protocols`]: the default is empty -- no ALPN protocol is negotiated.
+/// * [`ClientConfig::application_settings`]: the default is empty -- no ALPS extension is sent.
 /// * [`ClientConfig::key_log`]: key material is not logged.
+/// * [`ClientConfig::observer`]: connections are not observed.
+/// * [`ClientConfig::qlog`]: connections are not traced.
 /// * [`ClientConfig::cert_decompressors`]: depends on the crate features, see [`compress::default_cert_decompressors()`].
 /// * [`ClientConfig::cert_compressors`]: depends on the crate features, see [`compress::default_cert_compressors()`].
 /// * [`ClientConfig::cert_compression_cache`]: caches the most recently used 4 compressions
+/// * [`ClientConfig::max_renegotiations`]: the default is zero -- TLS1.2 renegotiation is refused.
 ///
 /// [`RootCertStore`]: crate::RootCertStore
 #[derive(Clone, Debug)]
@@ -70,6 +76,15 @@ pub struct ClientConfig {
     /// The default is true.
     pub check_selected_alpn: bool,
 
+    /// Application settings (ALPS) to exchange for particular ALPN protocols.
+    ///
+    /// The ALPS extension is offered for every protocol in [`ClientConfig::alpn_protocols`]
+    /// that has an entry here.  If the server selects such a protocol and responds with
+    /// its own settings, our settings are sent in the client's second flight.
+    ///
+    /// ALPS is only available in TLS1.3.  The default is empty.
+    pub application_settings: Vec<ApplicationSettings>,
+
     /// How and when the client can resume a previous session.
     ///
     /// # Sharing `resumption` between `ClientConfig`s
@@ -106,6 +121,15 @@ pub struct ClientConfig {
     /// [TLS maximum]: https://datatracker.ietf.org/doc/html/rfc9846#section-5.1
     pub max_fragment_size: Option<usize>,
 
+    /// How to pad TLS1.3 records, to hide the length of their contents.
+    ///
+    /// This applies to every record encrypted under TLS1.3, including those
+    /// carrying handshake messages.  It can be overridden for individual writes
+    /// with [`SendTraffic::write_padded()`].
+    ///
+    /// [`SendTraffic::write_padded()`]: crate::split::SendTraffic::write_padded()
+    pub record_padding: RecordPadding,
+
     /// Whether to send the Server Name Indication (SNI) extension
     /// during the client handshake.
     ///
@@ -119,6 +143,16 @@ pub struct ClientConfig {
     /// See [RFC 9850](https://datatracker.ietf.org/doc/html/rfc9850) for background.
     pub key_log: Arc<dyn KeyLog>,
 
+    /// Observes the progress of connections made with this config.
+    ///
+    /// The default is `None`.
+    pub observer: Option<Arc<dyn ConnectionObserver>>,
+
+    /// Records a qlog-style trace of each connection made with this config.
+    ///
+    /// See the [`qlog`](crate::qlog) module for details.  The default is `None`.
+    pub qlog: Option<Arc<dyn QlogTracer>>,
+
     /// Allows traffic secrets to be extracted after the handshake,
     /// e.g. for kTLS setup.
     pub enable_secret_extraction: bool,
@@ -150,6 +184,31 @@ pub struct ClientConfig {
     /// [RFC 9149]: https://datatracker.ietf.org/doc/html/rfc9149
     pub send_ticket_request: Option<TicketRequest>,
 
+    /// How many times a TLS1.2 server may renegotiate a connection.
+    ///
+    /// When this is non-zero, a server which supports [RFC 5746] secure renegotiation
+    /// may send a `HelloRequest` to start a new handshake on an established
+    /// TLS1.2 connection -- for example, to request a client certificate.  Renegotiation
+    /// is only ever initiated by the server, and each new handshake is bound to the
+    /// previous one using the `renegotiation_info` extension.  Once it completes,
+    /// the new server identity is available from [`ConnectionOutputs::peer_identity()`].
+    ///
+    /// To prevent the [triple handshake attack], renegotiation is refused unless the
+    /// connection uses the [RFC 7627] extended main secret, and a renegotiation fails if
+    /// the server presents a different end-entity certificate from the first handshake.
+    ///
+    /// Application data received during a renegotiation is rejected.  Connections
+    /// restored from a serialized form cannot be renegotiated.
+    ///
+    /// This has no effect on TLS1.3 connections.  The default is zero, meaning
+    /// requests to renegotiate are refused.
+    ///
+    /// [RFC 5746]: https://datatracker.ietf.org/doc/html/rfc5746
+    /// [RFC 7627]: https://datatracker.ietf.org/doc/html/rfc7627
+    /// [triple handshake attack]: https://web.archive.org/web/www.mitls.org/pages/attacks/3SHAKE
+    /// [`ConnectionOutputs::peer_identity()`]: crate::ConnectionOutputs::peer_identity()
+    pub max_renegotiations: u8,
+
     /// Items that affect the fundamental security properties of a connection.
     pub(super) domain: SecurityDomain,
 
@@ -293,6 +352,14 @@ impl ClientConfig {
             .ok_or(Error::FailedToGetCurrentTime)
     }
 
+    pub(super) fn start_qlog(&self) -> Qlog {
+        Qlog::start(
+            self.qlog.as_ref(),
+            Side::Client,
+            self.domain.time_provider.current_time(),
+        )
+    }
+
     /// A hash which partitions this config's use of the [`Self::resumption`] store.
     pub(super) fn config_hash(&self) -> [u8; 32] {
         self.domain.config_hash
@@ -766,14 +833,19 @@ impl ConfigBuilder<ClientConfig, WantsClientCert> {
         Ok(ClientConfig {
             alpn_protocols: Vec::new(),
             check_selected_alpn: true,
+            application_settings: Vec::new(),
             resumption: Resumption::default(),
             max_fragment_size: None,
+            record_padding: RecordPadding::None,
             enable_sni: true,
             key_log: Arc::new(NoKeyLog {}),
+            observer: None,
+            qlog: None,
             enable_secret_extraction: false,
             enable_early_data: false,
             require_ems,
             send_ticket_request: None,
+            max_renegotiations: 0,
             domain: SecurityDomain::new(
                 self.provider,
                 client_auth_cert_resolver,
@@ -4,23 +4,26 @@ use core::ops::Deref;
 
 use pki_types::{FipsStatus, ServerName};
 
-use super::config::ClientConfig;
-use super::hs::ClientHelloInput;
+use super::config::{ClientConfig, ClientSessionKey};
+use super::hs::{ClientHelloInput, ClientState};
 use crate::TlsInputBuffer;
 use crate::client::EchStatus;
-use crate::common_state::{CommonState, ConnectionOutputs, EarlyDataEvent, Event, Protocol, Side};
+use crate::common_state::{
+    CommonState, ConnectionOutputs, EarlyDataEvent, Event, Protocol, ResumptionEvent, Side,
+};
 use crate::conn::private::SideOutput;
 use crate::conn::split::SplitConnection;
 use crate::conn::{
-    Connection, ConnectionCommon, KeyingMaterialExporter, MessageHandler, SideCommonOutput,
-    SideData,
+    Connection, ConnectionCommon, HandoffData, KeyingMaterialExporter, MessageHandler,
+    SideCommonOutput, SideData, TlsOutputBuffer, TrackedOutput,
 };
 #[cfg(doc)]
 use crate::crypto;
-use crate::crypto::cipher::OutboundPlain;
+use crate::crypto::TicketProducer;
+use crate::crypto::cipher::{OutboundPlain, Payload};
 use crate::enums::ApplicationProtocol;
-use crate::error::Error;
-use crate::msgs::ClientExtensionsInput;
+use crate::error::{ApiMisuse, Error, InvalidMessage};
+use crate::msgs::{ClientExtensionsInput, Codec, Reader, SizedPayload};
 use crate::quic::QuicOutput;
 use crate::suites::ExtractedSecrets;
 use crate::sync::Arc;
@@ -107,12 +110,123 @@ impl ClientConnection {
             .recv
             .tls13_tickets_received
     }
+
+    /// Set application data to store alongside TLS1.3 tickets received on this connection.
+    ///
+    /// Defaults to the empty byte string.  The data is stored in the [`ClientSessionStore`]
+    /// with each ticket received after this call, and is returned by
+    /// [`ClientConnection::received_resumption_data()`] on a later connection that
+    /// resumes using one of those tickets.  Must be less than 2^15 bytes, otherwise
+    /// [`ApiMisuse::ResumptionDataTooLong`] is returned.
+    ///
+    /// The data is never sent to the server.
+    ///
+    /// Returns the connection's error if it has already failed.
+    ///
+    /// [`ClientSessionStore`]: crate::client::ClientSessionStore
+    pub fn set_resumption_data(&mut self, data: &[u8]) -> Result<(), Error> {
+        if data.len() >= 2usize.pow(15) {
+            return Err(ApiMisuse::ResumptionDataTooLong.into());
+        }
+        match &mut self.inner.state {
+            Ok(st) => {
+                st.set_resumption_data(data);
+                Ok(())
+            }
+            Err(e) => Err(e.clone()),
+        }
+    }
+
+    /// Application data stored with the ticket this connection resumed, if any.
+    ///
+    /// Recovered from the prior connection's [`ClientConnection::set_resumption_data()`].
+    ///
+    /// Returns `Some` if and only if the server accepted a TLS1.3 resumption.
+    pub fn received_resumption_data(&self) -> Option<&[u8]> {
+        self.inner
+            .side
+            .received_resumption_data()
+    }
+
+    /// Describes this connection's attempt to resume a prior session.
+    ///
+    /// This says which stored session (if any) was offered, whether the server
+    /// accepted it, and, if the connection did not resume, why not.
+    ///
+    /// Returns `None` for a connection restored by [`ClientConnection::dangerous_restore()`].
+    pub fn resumption_diagnostics(&self) -> Option<&ResumptionDiagnostics> {
+        self.inner.side.resumption_diagnostics()
+    }
+
+    /// Serialize this established connection, so it can be restored in another process.
+    ///
+    /// The result is sealed with `sealer`, and can be restored with
+    /// [`ClientConnection::dangerous_restore()`] using a `sealer` that can decrypt it.
+    /// This consumes the connection: continuing to use it after serialization would
+    /// reuse nonces once the restored connection sends data.
+    ///
+    /// The serialized connection contains the connection's traffic secrets.
+    /// It is only as safe as the `sealer`'s keys.
+    ///
+    /// This fails if:
+    ///
+    /// - the connection was not made with [`ClientConfig::enable_secret_extraction`] set.
+    /// - the handshake is not complete. Check with [`Connection::is_handshaking()`].
+    /// - a partially received message is buffered.  Supply the rest of it to
+    ///   [`Connection::process_new_packets()`] first.
+    ///
+    /// Early data state and the early exporter are not carried over.
+    pub fn dangerous_serialize(self, sealer: &dyn TicketProducer) -> Result<Vec<u8>, Error> {
+        self.inner.dangerous_serialize(sealer)
+    }
+
+    /// Restore a connection serialized by [`ClientConnection::dangerous_serialize()`].
+    ///
+    /// `config` must have the same cipher suites available as the one used to make the
+    /// original connection.  It also supplies the session storage used for any
+    /// TLS1.3 tickets received after restoration.
+    pub fn dangerous_restore(
+        config: Arc<ClientConfig>,
+        serialized: &[u8],
+        sealer: &dyn TicketProducer,
+    ) -> Result<Self, Error> {
+        let mut inner = ConnectionCommon::dangerous_restore(
+            serialized,
+            sealer,
+            config.provider(),
+            config.fips(),
+            |restored, output| ClientState::restore(&config, restored, output),
+        )?;
+        inner
+            .common
+            .send
+            .set_max_fragment_size(config.max_fragment_size)?;
+        inner
+            .common
+            .send
+            .set_record_padding(config.record_padding.clone());
+        inner
+            .common
+            .set_observer(config.observer.as_ref());
+        inner
+            .common
+            .set_qlog(config.start_qlog());
+        inner
+            .common
+            .send
+            .refresh_restored_traffic_keys();
+        Ok(Self { inner })
+    }
 }
 
 impl Connection for ClientConnection {
     type Side = ClientSide;
 
-    fn write_tls(&mut self, plaintext: OutboundPlain<'_>, tls: &mut Vec<u8>) -> Result<(), Error> {
+    fn write_tls(
+        &mut self,
+        plaintext: OutboundPlain<'_>,
+        tls: &mut dyn TlsOutputBuffer,
+    ) -> Result<(), Error> {
         self.inner.write_tls(plaintext, tls)
     }
 
@@ -123,7 +237,7 @@ impl Connection for ClientConnection {
     fn process_new_packets<'a, 'm>(
         &'a mut self,
         input: &'m mut dyn TlsInputBuffer,
-        tls: &'a mut Vec<u8>,
+        tls: &'a mut dyn TlsOutputBuffer,
     ) -> MessageHandler<'a, 'm, ClientSide> {
         self.inner
             .process_new_packets(input, tls)
@@ -137,12 +251,12 @@ impl Connection for ClientConnection {
         self.inner.dangerous_extract_secrets()
     }
 
-    fn refresh_traffic_keys(&mut self, tls: &mut Vec<u8>) -> Result<(), Error> {
+    fn refresh_traffic_keys(&mut self, tls: &mut dyn TlsOutputBuffer) -> Result<(), Error> {
         self.inner.refresh_traffic_keys(tls)
     }
 
-    fn send_close_notify(&mut self, tls: &mut Vec<u8>) {
-        self.inner.send_close_notify(tls);
+    fn send_close_notify(&mut self, tls: &mut dyn TlsOutputBuffer) -> Result<(), Error> {
+        self.inner.send_close_notify(tls)
     }
 
     fn is_handshaking(&self) -> bool {
@@ -179,7 +293,7 @@ impl ClientConnectionBuilder {
     }
 
     /// Finalize the builder and create the `ClientConnection`.
-    pub fn build(self, tls: &mut Vec<u8>) -> Result<ClientConnection, Error> {
+    pub fn build(self, tls: &mut dyn TlsOutputBuffer) -> Result<ClientConnection, Error> {
         let Self {
             config,
             name,
@@ -216,20 +330,26 @@ impl<'a> WriteEarlyData<'a> {
     /// Yields the number of bytes of `plaintext` that were consumed.  This may be less than
     /// the length of `plaintext` if the server has limited the amount of early data that
     /// may be sent.
-    pub fn write_tls(&mut self, plaintext: OutboundPlain<'_>, tls: &mut Vec<u8>) -> usize {
+    pub fn write_tls(
+        &mut self,
+        plaintext: OutboundPlain<'_>,
+        tls: &mut dyn TlsOutputBuffer,
+    ) -> Result<usize, Error> {
         let state = &mut self.early_data;
         let plaintext = match state.state {
             EarlyDataState::Ready | EarlyDataState::Sending | EarlyDataState::Accepted => {
                 let take = Ord::min(plaintext.len(), state.left);
-                state.left -= take;
                 plaintext.split_at(take).0
             }
-            EarlyDataState::AcceptedFinished => return 0,
+            EarlyDataState::AcceptedFinished => return Ok(0),
         };
 
-        self.common
+        let sent = self
+            .common
             .send
-            .send_appdata_encrypt(plaintext, tls)
+            .send_appdata_encrypt(plaintext, tls)?;
+        self.early_data.left -= sent;
+        Ok(sent)
     }
 
     /// How many bytes you may send.  Writes will become short
@@ -269,23 +389,30 @@ impl ConnectionCommon<ClientSide> {
         extra_exts: ClientExtensionsInput,
         quic: Option<&mut dyn QuicOutput>,
         protocol: Protocol,
-        tls: &mut Vec<u8>,
+        tls: &mut dyn TlsOutputBuffer,
     ) -> Result<Self, Error> {
         let mut common_state = CommonState::new(Side::Client, config.fips());
         common_state
             .send
             .set_max_fragment_size(config.max_fragment_size)?;
+        common_state
+            .send
+            .set_record_padding(config.record_padding.clone());
+        common_state.set_observer(config.observer.as_ref());
+        common_state.set_qlog(config.start_qlog());
         let mut data = ClientConnectionData::default();
 
+        let mut tls = TrackedOutput::new(tls);
         let mut output = SideCommonOutput {
             side: &mut data,
             quic,
             common: &mut common_state,
-            tls,
+            tls: &mut tls,
         };
 
         let input = ClientHelloInput::new(name, &extra_exts, protocol, &mut output, config)?;
         let state = input.start_handshake(extra_exts, &mut output)?;
+        tls.result()?;
 
         Ok(Self::new(state, data, common_state))
     }
@@ -310,13 +437,59 @@ impl SideData for ClientSide {}
 
 impl crate::conn::private::Side for ClientSide {
     type Data = ClientConnectionData;
-    type State = super::hs::ClientState;
+    type State = ClientState;
+    const SIDE: Side = Side::Client;
+}
+
+impl HandoffData for ClientConnectionData {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        bytes.push(match self.ech_status {
+            EchStatus::NotOffered => 0,
+            EchStatus::Grease => 1,
+            EchStatus::Offered => 2,
+            EchStatus::Accepted => 3,
+            EchStatus::Rejected => 4,
+        });
+        match &self.received_resumption_data {
+            Some(data) => {
+                bytes.push(1);
+                SizedPayload::<u16>::from(Payload::Borrowed(data)).encode(bytes);
+            }
+            None => bytes.push(0),
+        }
+    }
+
+    fn read(r: &mut Reader<'_>) -> Result<Self, InvalidMessage> {
+        let ech_status = match u8::read(r)? {
+            1 => EchStatus::Grease,
+            2 => EchStatus::Offered,
+            3 => EchStatus::Accepted,
+            4 => EchStatus::Rejected,
+            _ => EchStatus::NotOffered,
+        };
+        let received_resumption_data = match u8::read(r)? {
+            1 => Some(SizedPayload::<u16>::read(r)?.into_vec()),
+            _ => None,
+        };
+        Ok(Self {
+            early_data: None,
+            ech_status,
+            received_resumption_data,
+            resumption: None,
+        })
+    }
 }
 
 impl SideOutput for ClientConnectionData {
     fn emit(&mut self, ev: Event<'_>) {
+        if let (Event::EarlyData(event), Some(resumption)) = (&ev, &mut self.resumption) {
+            resumption.record_early_data(event);
+        }
+
         match ev {
             Event::EchStatus(ech) => self.ech_status = ech,
+            Event::ResumptionData(data) => self.received_resumption_data = Some(data),
+            Event::Resumption(event) => self.record_resumption(event),
             Event::EarlyData(event) => match (event, &mut self.early_data) {
                 (EarlyDataEvent::Enable(sz), None) => self.early_data = Some(EarlyData::new(sz)),
                 (EarlyDataEvent::Start, Some(early_data)) => {
@@ -347,6 +520,142 @@ impl SideOutput for ClientConnectionData {
 pub(crate) struct ClientConnectionData {
     early_data: Option<EarlyData>,
     ech_status: EchStatus,
+    received_resumption_data: Option<Vec<u8>>,
+    resumption: Option<ResumptionDiagnostics>,
+}
+
+impl ClientConnectionData {
+    pub(crate) fn received_resumption_data(&self) -> Option<&[u8]> {
+        self.received_resumption_data.as_deref()
+    }
+
+    pub(crate) fn resumption_diagnostics(&self) -> Option<&ResumptionDiagnostics> {
+        self.resumption.as_ref()
+    }
+
+    fn record_resumption(&mut self, event: ResumptionEvent) {
+        match (event, &mut self.resumption) {
+            (ResumptionEvent::Lookup(session_key, failure), resumption) => {
+                *resumption = Some(ResumptionDiagnostics {
+                    session_key,
+                    offered: None,
+                    accepted: false,
+                    failure,
+                    early_data: EarlyDataStatus::NotOffered,
+                });
+            }
+            // Nothing to record if there was no lookup.
+            (_, None) => {}
+            (ResumptionEvent::Offered(offer), Some(resumption)) => {
+                resumption.offered = Some(offer);
+                resumption.failure = None;
+            }
+            (ResumptionEvent::NotOffered(failure), Some(resumption)) => {
+                resumption.offered = None;
+                resumption.failure = Some(failure);
+            }
+            (ResumptionEvent::Accepted, Some(resumption)) if resumption.offered.is_some() => {
+                resumption.accepted = true;
+                resumption.failure = None;
+            }
+            (ResumptionEvent::Rejected(failure), Some(resumption))
+                if resumption.offered.is_some() =>
+            {
+                resumption.failure = Some(failure);
+            }
+            (ResumptionEvent::Accepted | ResumptionEvent::Rejected(_), Some(_)) => {}
+        }
+    }
+}
+
+/// Describes a client connection's attempt to resume a prior session.
+///
+/// Available from [`ClientConnection::resumption_diagnostics()`].
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ResumptionDiagnostics {
+    /// The key used to look up a stored session in the [`ClientSessionStore`].
+    ///
+    /// [`ClientSessionStore`]: crate::client::ClientSessionStore
+    pub session_key: ClientSessionKey<'static>,
+
+    /// What was offered for resumption in the final `ClientHello`, if anything.
+    pub offered: Option<ResumptionOffer>,
+
+    /// Whether the server accepted the offer and the handshake was resumed.
+    pub accepted: bool,
+
+    /// Why the connection is not resuming.
+    ///
+    /// This is `None` if resumption was accepted, or if the server has
+    /// not yet responded to an offer.
+    pub failure: Option<ResumptionFailure>,
+
+    /// What happened to early data.
+    pub early_data: EarlyDataStatus,
+}
+
+impl ResumptionDiagnostics {
+    fn record_early_data(&mut self, event: &EarlyDataEvent) {
+        self.early_data = match (event, self.early_data) {
+            (EarlyDataEvent::Enable(_), _) => EarlyDataStatus::Offered,
+            (EarlyDataEvent::Accepted, EarlyDataStatus::Offered) => EarlyDataStatus::Accepted,
+            (EarlyDataEvent::Rejected, EarlyDataStatus::Offered) => EarlyDataStatus::Rejected,
+            (_, status) => status,
+        };
+    }
+}
+
+/// Which kind of stored session a client offered to the server.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ResumptionOffer {
+    /// A TLS1.3 ticket, sent in the `pre_shared_key` extension.
+    Tls13Ticket,
+    /// A TLS1.2 ticket, sent in the `session_ticket` extension.
+    Tls12Ticket,
+    /// A TLS1.2 session ID.
+    Tls12SessionId,
+}
+
+/// Why a client connection did not resume a prior session.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ResumptionFailure {
+    /// The [`ClientSessionStore`] had no session for this server.
+    ///
+    /// [`ClientSessionStore`]: crate::client::ClientSessionStore
+    NoStoredSession,
+    /// The stored session had passed its lifetime, so was discarded without being offered.
+    ExpiredLocally,
+    /// The current time was unavailable, so the stored session could not be checked for expiry.
+    TimeUnavailable,
+    /// The stored session's cipher suite cannot be used for this connection.
+    ///
+    /// For example, the server selected a cipher suite in a `HelloRetryRequest`
+    /// with a different hash function to the stored session's.
+    SuiteMismatch,
+    /// The stored session is for a protocol version that is not in use.
+    ///
+    /// For example, a TLS1.3 ticket was offered but the server selected TLS1.2.
+    VersionMismatch,
+    /// The server chose not to resume the offered session.
+    ServerRejected,
+}
+
+/// What happened to a client's early data.
+#[non_exhaustive]
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub enum EarlyDataStatus {
+    /// Early data was not offered.
+    #[default]
+    NotOffered,
+    /// Early data was offered but we do not yet know whether the server accepted it.
+    Offered,
+    /// Early data was offered and the server accepted.
+    Accepted,
+    /// Early data was offered and the server rejected.
+    Rejected,
 }
 
 pub(super) struct EarlyData {
@@ -1039,9 +1039,11 @@ mod tests {
                         SubjectPublicKeyInfoDer::from(&b"spki"[..]),
                     )),
                     quic_params: None,
+                    application_settings: None,
                 },
                 &[0x55; 32],
                 UnixTime::now(),
+                &[],
             ),
         );
 
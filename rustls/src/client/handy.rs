@@ -250,9 +250,11 @@ mod tests {
                         },
                     )),
                     quic_params: None,
+                    application_settings: None,
                 },
                 &[],
                 now,
+                &[],
             ),
         );
 
@@ -10,18 +10,20 @@ use pki_types::ServerName;
 use super::config::{ClientSessionKey, Tls12Resumption};
 use super::ech::{EchMode, EchState, EchStatus};
 use super::{
-    ClientHelloDetails, ClientSessionCommon, Retrieved, Tls12Session, Tls13Session, tls12, tls13,
+    ClientHelloDetails, ClientSessionCommon, ResumptionFailure, ResumptionOffer, Retrieved,
+    Tls12Session, Tls13Session, tls12, tls13,
 };
 use crate::check::inappropriate_handshake_message;
-use crate::common_state::{EarlyDataEvent, Event, Output, OutputEvent, Protocol};
-use crate::conn::{Input, StateMachine};
+use crate::common_state::{EarlyDataEvent, Event, Output, OutputEvent, Protocol, ResumptionEvent};
+use crate::conn::{HandoffSecrets, Input, Restored, StateMachine};
 use crate::crypto::cipher::{EncodableVersion, Payload};
 use crate::crypto::kx::{KeyExchangeAlgorithm, StartedKeyExchange, SupportedKxGroup};
 use crate::crypto::{CipherSuite, CryptoProvider, rand};
 use crate::enums::{
-    ApplicationProtocol, CertificateType, ContentType, HandshakeType, ProtocolVersion,
+    ApplicationProtocol, ApplicationSettings, CertificateType, ContentType, HandshakeType,
+    ProtocolVersion,
 };
-use crate::error::{ApiMisuse, Error, PeerIncompatible, PeerMisbehaved};
+use crate::error::{ApiMisuse, Error, InvalidMessage, PeerIncompatible, PeerMisbehaved};
 use crate::hash_hs::HandshakeHashBuffer;
 use crate::kernel::KernelState;
 use crate::msgs::{
@@ -31,10 +33,11 @@ use crate::msgs::{
     MessagePayload, PskKeyExchangeModes, Random, ServerHelloPayload, ServerNamePayload, SessionId,
     SupportedEcPointFormats, SupportedProtocolVersions, TransportParameters,
 };
+use crate::observer::ConnectionEvent;
 use crate::sealed::Sealed;
 use crate::suites::{PartiallyExtractedSecrets, Suite, SupportedCipherSuite};
 use crate::sync::Arc;
-use crate::tls12::Tls12CipherSuite;
+use crate::tls12::{ConnectionSecrets, Tls12CipherSuite};
 use crate::tls13::Tls13CipherSuite;
 use crate::tls13::key_schedule::{KeyScheduleEarlyClient, KeyScheduleTrafficSend};
 use crate::tracing::{debug, trace};
@@ -62,7 +65,7 @@ impl StateMachine for ClientState {
         true
     }
 
-    fn handle_without_input(self) -> Result<Self, Error> {
+    fn handle_without_input<'m>(self, _output: &mut dyn Output<'m>) -> Result<Self, Error> {
         Ok(self)
     }
 
@@ -91,6 +94,83 @@ impl StateMachine for ClientState {
             _ => Err(Error::HandshakeNotComplete),
         }
     }
+
+    fn handoff_secrets(
+        &self,
+        send_keys: &Option<Box<KeyScheduleTrafficSend>>,
+    ) -> Result<HandoffSecrets, Error> {
+        match self {
+            Self::Tls12(tls12::Tls12State::Traffic(e)) => e.handoff_secrets(),
+            Self::Tls13(tls13::Tls13State::Traffic(e)) => e.handoff_secrets(send_keys),
+            _ => Err(Error::HandshakeNotComplete),
+        }
+    }
+}
+
+impl ClientState {
+    /// Set the application data to store with TLS1.3 tickets received from now on.
+    pub(crate) fn set_resumption_data(&mut self, data: &[u8]) {
+        match self {
+            Self::ServerHello(e) => e.input.resumption_data = data.to_vec(),
+            Self::ServerHelloOrHelloRetryRequest(e) => e.next.input.resumption_data = data.to_vec(),
+            // TLS1.2 sessions do not carry resumption data.
+            Self::Tls12(_) => {}
+            Self::Tls13(sm) => sm.set_resumption_data(data),
+        }
+    }
+
+    /// Restore the traffic state of a serialized connection.
+    pub(crate) fn restore(
+        config: &Arc<ClientConfig>,
+        restored: Restored<'_>,
+        output: &mut dyn Output<'_>,
+    ) -> Result<Self, Error> {
+        let Some(peer_identity) = restored.peer_identity else {
+            return Err(InvalidMessage::MissingData("PeerIdentity").into());
+        };
+
+        match (restored.suite, restored.secrets) {
+            (
+                SupportedCipherSuite::Tls12(suite),
+                HandoffSecrets::Tls12 {
+                    randoms,
+                    master_secret,
+                },
+            ) => Ok(tls12::ExpectTraffic::restore(
+                config,
+                ConnectionSecrets::new_resume(randoms, suite, &master_secret),
+                restored.exporter.is_some(),
+                &peer_identity,
+                output,
+                &restored.proof,
+            )
+            .into()),
+            (
+                SupportedCipherSuite::Tls13(suite),
+                HandoffSecrets::Tls13 {
+                    tx,
+                    rx,
+                    resumption: Some((resumption, server_name, resumption_data)),
+                },
+            ) => Ok(tls13::ExpectTraffic::restore(
+                config,
+                suite,
+                tls13::RestoredTraffic {
+                    tx,
+                    rx,
+                    resumption,
+                    server_name,
+                    resumption_data,
+                    exporter: restored.exporter,
+                },
+                peer_identity,
+                output,
+                &restored.proof,
+            )
+            .into()),
+            _ => Err(ApiMisuse::IncompatibleSerializedConnection.into()),
+        }
+    }
 }
 
 pub(crate) struct ExpectServerHello {
@@ -206,7 +286,8 @@ impl ExpectServerHello {
 
         let config = &self.input.config;
 
-        let server_version = if server_hello.legacy_version == ProtocolVersion::TLSv1_2 {
+        let protocol = self.input.protocol;
+        let server_version = if server_hello.legacy_version == protocol.legacy_version() {
             server_hello
                 .selected_version
                 .unwrap_or(server_hello.legacy_version)
@@ -214,9 +295,17 @@ impl ExpectServerHello {
             server_hello.legacy_version
         };
 
+        // From here on, DTLS1.3 is handled as TLS1.3.
+        let server_version = match (protocol.is_dtls(), server_version) {
+            (true, ProtocolVersion::DTLSv1_3) => ProtocolVersion::TLSv1_3,
+            (true, _) => return Err(PeerIncompatible::ServerDoesNotSupportDtls13.into()),
+            (false, version) => version,
+        };
+
         match server_version {
             ProtocolVersion::TLSv1_3
-                if config.supports_version(ProtocolVersion::TLSv1_3, self.input.protocol) =>
+                if config.supports_version(ProtocolVersion::TLSv1_3, self.input.protocol)
+                    && !self.input.renegotiating() =>
             {
                 self.with_version::<Tls13CipherSuite>(server_hello, &input, output)
             }
@@ -320,9 +409,9 @@ impl ExpectServerHelloOrHelloRetryRequest {
         }
 
         // Or asks us to talk a protocol we didn't offer, or doesn't support HRR at all.
-        let Some(ProtocolVersion::TLSv1_3) = hrr.supported_versions else {
+        if hrr.supported_versions != Some(self.next.input.protocol.tls13_version()) {
             return Err(PeerMisbehaved::IllegalHelloRetryRequestWithUnsupportedVersion.into());
-        };
+        }
         output.output(OutputEvent::ProtocolVersion(ProtocolVersion::TLSv1_3));
 
         // Or asks us to use a ciphersuite we didn't offer.
@@ -385,6 +474,7 @@ impl ExpectServerHelloOrHelloRetryRequest {
             _ => offered_key_share,
         };
 
+        output.observe(ConnectionEvent::HelloRetryRequest);
         emit_client_hello_for_retry(
             transcript_buffer,
             Some(hrr),
@@ -435,6 +525,10 @@ pub(crate) struct ClientHelloInput {
     pub(super) session_id: SessionId,
     pub(super) session_key: ClientSessionKey<'static>,
     pub(super) prev_ech_ext: Option<EncryptedClientHello>,
+    /// `None` if TLS1.2 renegotiation is not allowed.
+    pub(super) renegotiation: Option<tls12::Renegotiation>,
+    /// Application data to store with TLS1.3 tickets.
+    pub(super) resumption_data: Vec<u8>,
 }
 
 impl ClientHelloInput {
@@ -492,6 +586,7 @@ impl ClientHelloInput {
         );
 
         let random = Random::new(config.provider().secure_random)?;
+        let renegotiation = tls12::Renegotiation::new(&config);
         Ok(Self {
             config,
             resuming,
@@ -502,9 +597,45 @@ impl ClientHelloInput {
             session_id,
             session_key,
             prev_ech_ext: None,
+            renegotiation,
+            resumption_data: Vec::new(),
+        })
+    }
+
+    /// Prepare a TLS1.2 handshake which renegotiates an established connection.
+    ///
+    /// This never offers resumption, early data or any other protocol version.
+    pub(super) fn renegotiate(
+        config: Arc<ClientConfig>,
+        session_key: ClientSessionKey<'static>,
+        renegotiation: tls12::Renegotiation,
+    ) -> Result<Self, Error> {
+        let hello = ClientHelloDetails::new(
+            Vec::new(),
+            rand::random_u16(config.provider().secure_random)?,
+        );
+
+        Ok(Self {
+            random: Random::new(config.provider().secure_random)?,
+            config,
+            resuming: None,
+            sent_tls13_fake_ccs: false,
+            hello,
+            protocol: Protocol::Tcp,
+            session_id: SessionId::empty(),
+            session_key,
+            prev_ech_ext: None,
+            renegotiation: Some(renegotiation),
+            resumption_data: Vec::new(),
         })
     }
 
+    pub(super) fn renegotiating(&self) -> bool {
+        self.renegotiation
+            .as_ref()
+            .is_some_and(|r| r.renegotiating())
+    }
+
     pub(super) fn start_handshake(
         self,
         extra_exts: ClientExtensionsInput,
@@ -523,6 +654,7 @@ impl ClientHelloInput {
         let key_share = if self
             .config
             .supports_version(ProtocolVersion::TLSv1_3, self.protocol)
+            && !self.renegotiating()
         {
             Some(tls13::initial_key_share(&self.config, &self.session_key)?)
         } else {
@@ -530,7 +662,7 @@ impl ClientHelloInput {
         };
 
         let ech_state = match self.config.ech_mode.as_ref() {
-            Some(EchMode::Enable(ech_config)) => {
+            Some(EchMode::Enable(ech_config)) if !self.renegotiating() => {
                 Some(ech_config.state(self.session_key.server_name.clone(), &self.config)?)
             }
             _ => None,
@@ -571,9 +703,12 @@ fn emit_client_hello_for_retry(
     // builder semantics.
     let forbids_tls12 = input.protocol.is_quic() || ech_state.is_some();
 
+    let tls13 =
+        config.supports_version(ProtocolVersion::TLSv1_3, input.protocol) && !input.renegotiating();
     let supported_versions = SupportedProtocolVersions {
-        tls13: config.supports_version(ProtocolVersion::TLSv1_3, input.protocol),
+        tls13: tls13 && !input.protocol.is_dtls(),
         tls12: config.supports_version(ProtocolVersion::TLSv1_2, input.protocol) && !forbids_tls12,
+        dtls13: tls13 && input.protocol.is_dtls(),
     };
 
     // should be unreachable thanks to config builder
@@ -616,7 +751,7 @@ fn emit_client_hello_for_retry(
         exts.transport_parameters = Some(v.clone());
     }
 
-    if supported_versions.tls13 {
+    if tls13 {
         if let Some(cas_extension) = config.verifier().root_hint_subjects() {
             exts.certificate_authority_names = Some(cas_extension.to_vec());
         }
@@ -651,7 +786,7 @@ fn emit_client_hello_for_retry(
     };
 
     if let Some(GroupAndKeyShare { share, .. }) = &key_share {
-        debug_assert!(supported_versions.tls13);
+        debug_assert!(tls13);
         let mut shares = vec![KeyShareEntry::new(share.group(), share.pub_key())];
 
         if !retryreq
@@ -677,7 +812,7 @@ fn emit_client_hello_for_retry(
         exts.cookie = Some(cookie.to_vec().into());
     }
 
-    if supported_versions.tls13 {
+    if tls13 {
         // We could support PSK_KE here too. Such connections don't
         // have forward secrecy, and are similar to TLS1.2 resumption.
         exts.preshared_key_modes = Some(PskKeyExchangeModes {
@@ -691,21 +826,32 @@ fn emit_client_hello_for_retry(
                 resumption_count: ticket_req.resumption_count,
             });
         }
+
+        // Offer ALPS for each offered ALPN protocol that has settings configured.
+        let alps_protocols = input
+            .hello
+            .alpn_protocols
+            .iter()
+            .filter(|p| ApplicationSettings::find(&config.application_settings, p).is_some())
+            .cloned()
+            .collect::<Vec<_>>();
+        if !alps_protocols.is_empty() {
+            exts.application_settings = Some(alps_protocols);
+        }
     }
 
-    input.hello.offered_cert_compression =
-        if supported_versions.tls13 && !config.cert_decompressors.is_empty() {
-            exts.certificate_compression_algorithms = Some(
-                config
-                    .cert_decompressors
-                    .iter()
-                    .map(|dec| dec.algorithm())
-                    .collect(),
-            );
-            true
-        } else {
-            false
-        };
+    input.hello.offered_cert_compression = if tls13 && !config.cert_decompressors.is_empty() {
+        exts.certificate_compression_algorithms = Some(
+            config
+                .cert_decompressors
+                .iter()
+                .map(|dec| dec.algorithm())
+                .collect(),
+        );
+        true
+    } else {
+        false
+    };
 
     let client_certificate_types = config
         .resolver()
@@ -764,13 +910,27 @@ fn emit_client_hello_for_retry(
         })
         .collect();
 
-    if supported_versions.tls12 {
-        // We don't do renegotiation at all, in fact.
-        cipher_suites.push(CipherSuite::TLS_EMPTY_RENEGOTIATION_INFO_SCSV);
+    match &input.renegotiation {
+        // A renegotiation is bound to the connection by the `verify_data`
+        // from our previous `Finished` message.
+        Some(renegotiation) if renegotiation.renegotiating() => {
+            exts.renegotiation_info = Some(
+                renegotiation
+                    .client_verify_data()
+                    .to_vec()
+                    .into(),
+            );
+        }
+        // Otherwise, signal support for secure renegotiation.  We only
+        // renegotiate if this is allowed by `ClientConfig::max_renegotiations`.
+        _ if supported_versions.tls12 => {
+            cipher_suites.push(CipherSuite::TLS_EMPTY_RENEGOTIATION_INFO_SCSV);
+        }
+        _ => {}
     }
 
     let mut chp_payload = ClientHelloPayload {
-        client_version: ProtocolVersion::TLSv1_2,
+        client_version: input.protocol.legacy_version(),
         random: input.random,
         session_id: input.session_id,
         cipher_suites,
@@ -782,7 +942,7 @@ fn emit_client_hello_for_retry(
         .ech_mode
         .as_ref()
         .and_then(|mode| match mode {
-            EchMode::Grease(cfg) => Some(cfg.grease_ext(
+            EchMode::Grease(cfg) if !input.renegotiating() => Some(cfg.grease_ext(
                 config.provider().secure_random,
                 input.session_key.server_name.clone(),
                 &chp_payload,
@@ -858,6 +1018,8 @@ fn emit_client_hello_for_retry(
 
     let ch = Message {
         version: match retryreq {
+            // A renegotiation happens within an established TLS1.2 connection.
+            _ if input.renegotiating() => EncodableVersion::Legacy(ProtocolVersion::TLSv1_2),
             // <https://datatracker.ietf.org/doc/html/rfc9846#section-5.1>:
             // "This value MUST be set to 0x0303 for all records generated
             //  by a TLS 1.3 implementation ..."
@@ -872,24 +1034,25 @@ fn emit_client_hello_for_retry(
         payload: MessagePayload::handshake(chp),
     };
 
-    if retryreq.is_some() {
+    if retryreq.is_some() && !input.protocol.is_datagram() {
         // send dummy CCS to fool middleboxes prior
         // to second client hello
-        tls13::emit_fake_ccs(&mut input.sent_tls13_fake_ccs, output);
+        tls13::emit_fake_ccs(&mut input.sent_tls13_fake_ccs, output)?;
     }
 
     trace!("Sending ClientHello {ch:#?}");
 
     transcript_buffer.add_message(&ch);
-    output.send_msg(ch, false);
+    output.send_msg(ch, input.renegotiating())?;
+    output.observe(ConnectionEvent::ClientHelloSent);
 
     // Calculate the hash of ClientHello and use it to derive EarlyTrafficSecret
-    let early_data_key_schedule =
-        tls13_early_data_key_schedule.map(|(resuming_suite, schedule)| {
+    let early_data_key_schedule = tls13_early_data_key_schedule
+        .map(|(resuming_suite, schedule)| {
             if !early_data_enabled {
                 // No early data if a HelloRetryRequest happens
                 output.emit(Event::EarlyData(EarlyDataEvent::Rejected));
-                return (schedule, false);
+                return Ok::<_, Error>((schedule, false));
             }
 
             let (transcript_buffer, random) = match &ech_state {
@@ -913,9 +1076,10 @@ fn emit_client_hello_for_retry(
                 &mut input.sent_tls13_fake_ccs,
                 transcript_buffer,
                 random,
-            );
-            (schedule, true)
-        });
+            )?;
+            Ok((schedule, true))
+        })
+        .transpose()?;
 
     let mut next = Box::new(ExpectServerHello {
         input,
@@ -928,7 +1092,7 @@ fn emit_client_hello_for_retry(
         done_retry: false,
     });
 
-    Ok(if supported_versions.tls13 && retryreq.is_none() {
+    Ok(if tls13 && retryreq.is_none() {
         ClientState::ServerHelloOrHelloRetryRequest(Box::new(
             ExpectServerHelloOrHelloRetryRequest { next, extra_exts },
         ))
@@ -980,6 +1144,11 @@ fn prepare_resumption<'a>(
     let resuming = match resuming {
         Some(resuming) if !resuming.ticket().is_empty() => resuming,
         _ => {
+            // A TLS 1.2 session without a ticket is offered by session ID.
+            if let Some(ClientSessionValue::Tls12(_)) = resuming.as_deref() {
+                offered(output, ResumptionOffer::Tls12SessionId);
+            }
+
             if config.supports_version(ProtocolVersion::TLSv1_2, protocol)
                 && config.resumption.tls12_resumption == Tls12Resumption::SessionIdOrTickets
             {
@@ -996,24 +1165,37 @@ fn prepare_resumption<'a>(
             && config.resumption.tls12_resumption == Tls12Resumption::SessionIdOrTickets
         {
             exts.session_ticket = Some(ClientSessionTicket::Offer(Payload::new(resuming.ticket())));
+            offered(output, ResumptionOffer::Tls12Ticket);
+        } else {
+            not_offered(output, ResumptionFailure::VersionMismatch);
         }
         return None; // TLS 1.2, so nothing to return here
     };
 
     if !config.supports_version(ProtocolVersion::TLSv1_3, protocol) {
+        not_offered(output, ResumptionFailure::VersionMismatch);
         return None;
     }
 
     // If the server selected TLS 1.2, we can't resume.
     let suite = match suite {
         Some(SupportedCipherSuite::Tls13(suite)) => Some(suite),
-        Some(SupportedCipherSuite::Tls12(_)) => return None,
+        Some(SupportedCipherSuite::Tls12(_)) => {
+            not_offered(output, ResumptionFailure::VersionMismatch);
+            return None;
+        }
         None => None,
     };
 
     // If the selected cipher suite can't select from the session's, we can't resume.
     if let Some(suite) = suite {
-        suite.can_resume_from(tls13.suite.suite())?;
+        if suite
+            .can_resume_from(tls13.suite.suite())
+            .is_none()
+        {
+            not_offered(output, ResumptionFailure::SuiteMismatch);
+            return None;
+        }
     }
 
     // Similarly, if the suite is not usable for the protocol.
@@ -1022,14 +1204,24 @@ fn prepare_resumption<'a>(
         .suite()
         .usable_for_protocol(protocol)
     {
+        not_offered(output, ResumptionFailure::SuiteMismatch);
         return None;
     }
 
+    offered(output, ResumptionOffer::Tls13Ticket);
     let early_data_enabled =
         tls13::prepare_resumption(config, output, &tls13, exts, suite.is_some());
     Some((tls13, early_data_enabled))
 }
 
+fn offered(output: &mut dyn Output<'_>, offer: ResumptionOffer) {
+    output.emit(Event::Resumption(ResumptionEvent::Offered(offer)));
+}
+
+fn not_offered(output: &mut dyn Output<'_>, failure: ResumptionFailure) {
+    output.emit(Event::Resumption(ResumptionEvent::NotOffered(failure)));
+}
+
 pub(super) fn process_alpn_protocol(
     output: &mut dyn Output<'_>,
     offered_protocols: &[ApplicationProtocol<'_>],
@@ -1079,23 +1271,28 @@ impl ClientSessionValue {
                     .tls12_session(key)
                     .map(ClientSessionValue::Tls12)
             })
+            .ok_or(ResumptionFailure::NoStoredSession)
             .and_then(|resuming| {
-                let now = config
-                    .current_time()
-                    .map_err(|_err| debug!("Could not get current time: {_err}"))
-                    .ok()?;
+                let now = config.current_time().map_err(|_err| {
+                    debug!("Could not get current time: {_err}");
+                    ResumptionFailure::TimeUnavailable
+                })?;
 
                 let retrieved = Retrieved::new(resuming, now);
                 match retrieved.has_expired() {
-                    false => Some(retrieved),
-                    true => None,
+                    false => Ok(retrieved),
+                    true => Err(ResumptionFailure::ExpiredLocally),
                 }
-            })
-            .or_else(|| {
-                debug!("No cached session for {key:?}");
-                None
             });
 
+        output.emit(Event::Resumption(ResumptionEvent::Lookup(
+            key.clone(),
+            found.as_ref().err().copied(),
+        )));
+        let found = found
+            .map_err(|_failure| debug!("No cached session for {key:?}: {_failure:?}"))
+            .ok();
+
         if let Some(quic) = output.quic() {
             if let Some(quic_params) = found
                 .as_ref()
@@ -19,7 +19,7 @@ use crate::tracing::{debug, trace};
 use crate::verify::{DistinguishedName, VerifiedIdentity};
 #[cfg(feature = "webpki")]
 pub use crate::webpki::{
-    ServerVerifierBuilder, VerifierBuilderError, WebPkiServerVerifier,
+    RootHintTrimming, ServerVerifierBuilder, VerifierBuilderError, WebPkiServerVerifier,
     verify_identity_signed_by_trust_anchor, verify_server_name,
 };
 use crate::{Tls12CipherSuite, compress};
@@ -31,7 +31,10 @@ pub use config::{
 };
 
 mod connection;
-pub use connection::{ClientConnection, ClientConnectionBuilder, ClientSide, WriteEarlyData};
+pub use connection::{
+    ClientConnection, ClientConnectionBuilder, ClientSide, EarlyDataStatus, ResumptionDiagnostics,
+    ResumptionFailure, ResumptionOffer, WriteEarlyData,
+};
 
 mod ech;
 pub use ech::{EchConfig, EchGreaseConfig, EchMode, EchStatus};
@@ -122,6 +125,8 @@ pub struct Tls13Session {
     max_early_data_size: u32,
     pub(crate) common: ClientSessionCommon,
     quic_params: SizedPayload<'static, u16, MaybeEmpty>,
+    resumption_data: SizedPayload<'static, u16, MaybeEmpty>,
+    pub(crate) application_settings: Option<NegotiatedApplicationSettings>,
 }
 
 impl Tls13Session {
@@ -143,6 +148,11 @@ impl Tls13Session {
                 max_early_data_size: u32::read(reader)?,
                 common: ClientSessionCommon::read(reader)?,
                 quic_params: SizedPayload::<u16, MaybeEmpty>::read(reader)?.into_owned(),
+                resumption_data: SizedPayload::<u16, MaybeEmpty>::read(reader)?.into_owned(),
+                application_settings: match u8::read(reader)? {
+                    1 => Some(NegotiatedApplicationSettings::read(reader)?),
+                    _ => None,
+                },
             })
         })
     }
@@ -152,6 +162,7 @@ impl Tls13Session {
         input: Tls13ClientSessionInput,
         secret: &[u8],
         time_now: UnixTime,
+        resumption_data: &[u8],
     ) -> Self {
         Self {
             suite: input.suite,
@@ -170,9 +181,27 @@ impl Tls13Session {
             quic_params: input
                 .quic_params
                 .unwrap_or_else(|| SizedPayload::from(Payload::new(Vec::new()))),
+            resumption_data: SizedPayload::from(Payload::new(resumption_data.to_vec())),
+            application_settings: input.application_settings,
         }
     }
 
+    /// Application data attached to this ticket by the connection that received it.
+    ///
+    /// See [`ClientConnection::set_resumption_data()`].
+    pub fn resumption_data(&self) -> &[u8] {
+        self.resumption_data.bytes()
+    }
+
+    /// The application settings the server sent via ALPS on the connection that received this ticket.
+    ///
+    /// These are assumed to still apply when sending early data on a resumed connection.
+    pub fn peer_application_settings(&self) -> Option<&[u8]> {
+        self.application_settings
+            .as_ref()
+            .map(|alps| alps.peer.as_slice())
+    }
+
     /// Encode this ticket into `buf` for persistence.
     pub fn encode(&self, buf: &mut Vec<u8>) {
         self.suite
@@ -185,6 +214,14 @@ impl Tls13Session {
         buf.extend_from_slice(&self.max_early_data_size.to_be_bytes());
         self.common.encode(buf);
         self.quic_params.encode(buf);
+        self.resumption_data.encode(buf);
+        match &self.application_settings {
+            Some(alps) => {
+                1u8.encode(buf);
+                alps.encode(buf);
+            }
+            None => 0u8.encode(buf),
+        }
     }
 
     /// Test only: replace `max_early_data_size` with `new`
@@ -218,6 +255,35 @@ pub(crate) struct Tls13ClientSessionInput {
     pub(crate) suite: Tls13ProtocolSuite,
     pub(crate) peer_identity: VerifiedIdentity<'static>,
     pub(crate) quic_params: Option<SizedPayload<'static, u16, MaybeEmpty>>,
+    pub(crate) application_settings: Option<NegotiatedApplicationSettings>,
+}
+
+/// Application settings exchanged via ALPS on a TLS1.3 connection.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct NegotiatedApplicationSettings {
+    pub(crate) protocol: ApplicationProtocol<'static>,
+    pub(crate) local: Vec<u8>,
+    pub(crate) peer: Vec<u8>,
+}
+
+impl Codec<'_> for NegotiatedApplicationSettings {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        self.protocol.encode(bytes);
+        SizedPayload::<u16, MaybeEmpty>::from(Payload::Borrowed(&self.local)).encode(bytes);
+        SizedPayload::<u16, MaybeEmpty>::from(Payload::Borrowed(&self.peer)).encode(bytes);
+    }
+
+    fn read(r: &mut Reader<'_>) -> Result<Self, InvalidMessage> {
+        Ok(Self {
+            protocol: ApplicationProtocol::read(r)?.to_owned(),
+            local: SizedPayload::<u16, MaybeEmpty>::read(r)?
+                .bytes()
+                .to_vec(),
+            peer: SizedPayload::<u16, MaybeEmpty>::read(r)?
+                .bytes()
+                .to_vec(),
+        })
+    }
 }
 
 /// A stored TLS 1.2 client session value.
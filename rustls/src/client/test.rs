@@ -4,15 +4,18 @@ use alloc::vec::Vec;
 use core::hash::Hasher;
 use core::sync::atomic::{AtomicBool, Ordering};
 use core::time::Duration;
-use std::sync::OnceLock;
+use std::sync::{Mutex, OnceLock};
 use std::vec;
 
 use pki_types::{CertificateDer, FipsStatus, ServerName, UnixTime};
 
-use super::{Tls12Session, Tls13ClientSessionInput, Tls13Session};
+use super::{NegotiatedApplicationSettings, Tls12Session, Tls13ClientSessionInput, Tls13Session};
 use crate::client::{ClientConfig, ClientConnection, Resumption, Tls12Resumption};
+use crate::common_state::Side;
+use crate::conn::ConnectionRandoms;
 use crate::crypto::cipher::{
-    EncodableVersion, EncodedMessage, MessageEncrypter, Payload, encode_record_header,
+    EncodableVersion, EncodedMessage, InboundOpaque, MessageDecrypter, MessageEncrypter, Payload,
+    encode_record_header,
 };
 use crate::crypto::kx::{self, NamedGroup, SharedSecret, StartedKeyExchange, SupportedKxGroup};
 use crate::crypto::test_provider::{FakeKeyExchangeGroup, KEY_EXCHANGE_GROUP, TLS_TEST_SUITE};
@@ -21,7 +24,9 @@ use crate::crypto::{
     CipherSuite, Credentials, CryptoProvider, Identity, SignatureScheme, SingleCredential,
     TEST_PROVIDER, TLS13_TEST_SUITE, tls12_only, tls13_only, tls13_suite,
 };
-use crate::enums::{CertificateType, HandshakeType, ProtocolVersion};
+use crate::enums::{
+    ApplicationProtocol, CertificateType, ContentType, HandshakeType, ProtocolVersion,
+};
 use crate::error::{Error, PeerIncompatible, PeerMisbehaved};
 use crate::msgs::{
     CertificateChain, ClientHelloPayload, Codec, Compression, ECCurveType, EcParameters,
@@ -36,6 +41,7 @@ use crate::pki_types::PrivateKeyDer;
 use crate::pki_types::pem::PemObject;
 use crate::suites::Suite;
 use crate::sync::Arc;
+use crate::tls12::ConnectionSecrets;
 use crate::tls13::Tls13ProtocolSuite;
 use crate::tls13::key_schedule::{derive_traffic_iv, derive_traffic_key};
 use crate::verify::{
@@ -108,9 +114,15 @@ fn tls13_client_session_value_roundtrip() {
             quic_params: Some(SizedPayload::<u16, MaybeEmpty>::from(vec![
                 0xaa, 0xbb, 0xcc, 0xdd,
             ])),
+            application_settings: Some(NegotiatedApplicationSettings {
+                protocol: ApplicationProtocol::Http2,
+                local: vec![0x01],
+                peer: vec![0x02, 0x03],
+            }),
         },
         &[0x55; 48],
         UnixTime::since_unix_epoch(Duration::from_secs(9999999)),
+        b"settings",
     );
 
     let mut encoded = Vec::new();
@@ -125,6 +137,9 @@ fn tls13_client_session_value_roundtrip() {
     assert_eq!(decoded.age_add, age_add);
     assert_eq!(decoded.max_early_data_size, session.max_early_data_size);
     assert_eq!(decoded.quic_params.bytes(), session.quic_params.bytes());
+    assert_eq!(decoded.resumption_data(), b"settings");
+    assert_eq!(decoded.application_settings, session.application_settings);
+    assert_eq!(decoded.peer_application_settings(), Some(&[0x02, 0x03][..]));
     assert_eq!(decoded.common.ticket(), session.common.ticket());
     assert_eq!(decoded.common.epoch, session.common.epoch);
     assert_eq!(*decoded.common.peer_identity(), peer_identity.into());
@@ -948,3 +963,322 @@ fn process(input: &mut VecInput, conn: &mut ClientConnection) -> Result<(), Erro
         .handle_all(&mut Vec::new())?;
     Ok(())
 }
+
+#[test]
+fn test_client_refuses_renegotiation_without_extended_main_secret() {
+    let mut server = Tls12ScriptedServer::new(1);
+    server
+        .handshake(false, b"server cert")
+        .unwrap();
+
+    server.hello_request();
+    assert_eq!(
+        server.client_records(),
+        vec![(ContentType::Alert, vec![0x01, 100])]
+    );
+}
+
+#[test]
+fn test_client_refuses_renegotiation_with_different_server_certificate() {
+    let mut server = Tls12ScriptedServer::new(2);
+    server
+        .handshake(true, b"server cert")
+        .unwrap();
+
+    server.hello_request();
+    server
+        .handshake(true, b"server cert")
+        .unwrap();
+
+    server.hello_request();
+    assert_eq!(
+        server.handshake(true, b"another server cert"),
+        Err(PeerMisbehaved::ServerCertificateChangedDuringRenegotiation.into())
+    );
+}
+
+/// Plays the server side of TLS1.2 handshakes (and renegotiations) against a client.
+///
+/// This uses `TLS_TEST_SUITE`, whose record protection does not depend on its keys,
+/// so the only secret needed is the master secret for the `Finished` messages:
+/// that is taken from the client's key log.
+struct Tls12ScriptedServer {
+    conn: ClientConnection,
+    key_log: Arc<MasterSecretLog>,
+    input: VecInput,
+    output: Vec<u8>,
+    transcript: Vec<u8>,
+    read: Option<(Box<dyn MessageDecrypter>, u64)>,
+    pending_read: Option<Box<dyn MessageDecrypter>>,
+    write: Option<(Box<dyn MessageEncrypter>, u64)>,
+    client_verify_data: Vec<u8>,
+    server_verify_data: Vec<u8>,
+}
+
+impl Tls12ScriptedServer {
+    fn new(max_renegotiations: u8) -> Self {
+        let key_log = Arc::new(MasterSecretLog::default());
+        let mut config = ClientConfig::builder(Arc::new(tls12_only(TEST_PROVIDER)))
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(AcceptAnyServer))
+            .with_no_client_auth()
+            .unwrap();
+        config.key_log = key_log.clone();
+        config.max_renegotiations = max_renegotiations;
+
+        let mut output = Vec::new();
+        let conn = Arc::new(config)
+            .connect(ServerName::try_from("localhost").unwrap())
+            .build(&mut output)
+            .unwrap();
+
+        Self {
+            conn,
+            key_log,
+            input: VecInput::default(),
+            output,
+            transcript: Vec::new(),
+            read: None,
+            pending_read: None,
+            write: None,
+            client_verify_data: Vec::new(),
+            server_verify_data: Vec::new(),
+        }
+    }
+
+    /// Complete a full handshake, presenting `cert` as the server certificate.
+    fn handshake(&mut self, ems: bool, cert: &[u8]) -> Result<(), Error> {
+        let client_hello = match &self.client_records()[..] {
+            [(ContentType::Handshake, client_hello)] => client_hello.clone(),
+            records => panic!("expected ClientHello, got {records:?}"),
+        };
+        assert_eq!(client_hello[0], u8::from(HandshakeType::ClientHello));
+        self.transcript = client_hello.clone();
+        let client_random = Random::read_bytes(&client_hello[6..38]).unwrap();
+        let server_random = Random([0x5e; 32]);
+
+        let renegotiation_info =
+            [&self.client_verify_data[..], &self.server_verify_data[..]].concat();
+        self.send_handshake(HandshakePayload::ServerHello(ServerHelloPayload {
+            random: server_random,
+            compression_method: Compression::Null,
+            cipher_suite: TLS_TEST_SUITE.suite(),
+            legacy_version: ProtocolVersion::TLSv1_2,
+            session_id: SessionId::empty(),
+            extensions: Box::new(ServerExtensions {
+                extended_main_secret_ack: ems.then_some(()),
+                renegotiation_info: Some(SizedPayload::from(renegotiation_info)),
+                ..ServerExtensions::default()
+            }),
+        }));
+        self.send_handshake(HandshakePayload::Certificate(CertificateChain(vec![
+            CertificateDer::from(cert),
+        ])));
+        self.send_handshake(HandshakePayload::ServerKeyExchange(
+            ServerKeyExchangePayload::Known(ServerKeyExchange {
+                dss: DigitallySignedStruct::new(
+                    SignatureScheme::ECDSA_NISTP256_SHA256,
+                    b"not checked".to_vec(),
+                ),
+                params: ServerKeyExchangeParams::Ecdh(ServerEcdhParams {
+                    curve_params: EcParameters {
+                        curve_type: ECCurveType::NamedCurve,
+                        named_group: KEY_EXCHANGE_GROUP.name(),
+                    },
+                    public: KEY_EXCHANGE_GROUP
+                        .start()
+                        .unwrap()
+                        .pub_key()
+                        .to_vec()
+                        .into(),
+                }),
+            }),
+        ));
+        self.send_handshake(HandshakePayload::ServerHelloDone);
+        self.process()?;
+
+        let master_secret = self.key_log.0.lock().unwrap().unwrap();
+        let secrets = ConnectionSecrets::new_resume(
+            ConnectionRandoms::new(client_random, server_random),
+            TLS_TEST_SUITE,
+            &master_secret,
+        );
+        let (decrypter, encrypter) = secrets.make_cipher_pair(Side::Server);
+        self.pending_read = Some(decrypter);
+
+        let records = self.client_records();
+        let [
+            (ContentType::Handshake, client_kx),
+            (ContentType::ChangeCipherSpec, _),
+            (ContentType::Handshake, client_finished),
+        ] = &records[..]
+        else {
+            panic!("expected client's second flight, got {records:?}");
+        };
+        assert_eq!(client_kx[0], u8::from(HandshakeType::ClientKeyExchange));
+        assert_eq!(client_finished[0], u8::from(HandshakeType::Finished));
+        self.transcript
+            .extend_from_slice(client_kx);
+        self.transcript
+            .extend_from_slice(client_finished);
+        self.client_verify_data = client_finished[4..].to_vec();
+
+        let mut verify_data = [0u8; 12];
+        TLS_TEST_SUITE
+            .prf_provider
+            .new_secret(&master_secret)
+            .prf(
+                &mut verify_data,
+                b"server finished",
+                TLS_TEST_SUITE
+                    .common
+                    .hash_provider
+                    .hash(&self.transcript)
+                    .as_ref(),
+            );
+        self.server_verify_data = verify_data.to_vec();
+
+        self.send_record(ContentType::ChangeCipherSpec, &[0x01]);
+        self.write = Some((encrypter, 0));
+        self.send_handshake(HandshakePayload::Finished(Payload::new(verify_data)));
+        self.process()
+    }
+
+    fn hello_request(&mut self) {
+        let hello_request = HandshakeMessagePayload(HandshakePayload::HelloRequest).get_encoding();
+        self.send_record(ContentType::Handshake, &hello_request);
+        self.process().unwrap();
+    }
+
+    fn send_handshake(&mut self, payload: HandshakePayload<'_>) {
+        let encoded = HandshakeMessagePayload(payload).get_encoding();
+        self.transcript
+            .extend_from_slice(&encoded);
+        self.send_record(ContentType::Handshake, &encoded);
+    }
+
+    fn send_record(&mut self, typ: ContentType, payload: &[u8]) {
+        let plain = EncodedMessage::new(
+            typ,
+            EncodableVersion::Legacy(ProtocolVersion::TLSv1_2),
+            Payload::Borrowed(payload),
+        );
+        let plain = plain.borrow_outbound();
+
+        let record = match &mut self.write {
+            Some((encrypter, seq)) => {
+                let mut record =
+                    vec![0u8; HEADER_SIZE + encrypter.encrypted_payload_len(payload.len())];
+                let encrypted = encrypter
+                    .encrypt(plain, *seq, &mut record[HEADER_SIZE..])
+                    .unwrap();
+                *seq += 1;
+
+                let (typ, version, len) =
+                    (encrypted.typ, encrypted.version, encrypted.payload.len());
+                record.truncate(HEADER_SIZE + len);
+                record[..HEADER_SIZE].copy_from_slice(&encode_record_header(
+                    typ,
+                    version,
+                    u16::try_from(len).unwrap(),
+                ));
+                record
+            }
+            None => plain.to_unencrypted_bytes(),
+        };
+
+        self.input
+            .read(&mut record.as_slice())
+            .unwrap();
+    }
+
+    /// Take the records the client has written, removing their protection.
+    fn client_records(&mut self) -> Vec<(ContentType, Vec<u8>)> {
+        let mut records = Vec::new();
+        let mut rest = &mut core::mem::take(&mut self.output)[..];
+        while !rest.is_empty() {
+            let typ = ContentType::from(rest[0]);
+            let len = usize::from(u16::from_be_bytes([rest[3], rest[4]]));
+            let (record, tail) = core::mem::take(&mut rest).split_at_mut(HEADER_SIZE + len);
+            rest = tail;
+
+            let payload = match &mut self.read {
+                Some((decrypter, seq)) => {
+                    let opaque = EncodedMessage::new(
+                        typ,
+                        EncodableVersion::Legacy(ProtocolVersion::TLSv1_2),
+                        InboundOpaque(&mut record[HEADER_SIZE..]),
+                    );
+                    let plain = decrypter.decrypt(opaque, *seq).unwrap();
+                    *seq += 1;
+                    (plain.typ, plain.payload.to_vec())
+                }
+                None => (typ, record[HEADER_SIZE..].to_vec()),
+            };
+
+            if payload.0 == ContentType::ChangeCipherSpec {
+                self.read = Some((self.pending_read.take().unwrap(), 0));
+            }
+            records.push(payload);
+        }
+        records
+    }
+
+    fn process(&mut self) -> Result<(), Error> {
+        self.conn
+            .process_new_packets(&mut self.input, &mut self.output)
+            .handle_all(&mut Vec::new())?;
+        Ok(())
+    }
+}
+
+/// Records the latest TLS1.2 master secret.
+#[derive(Debug, Default)]
+struct MasterSecretLog(Mutex<Option<[u8; 48]>>);
+
+impl KeyLog for MasterSecretLog {
+    fn will_log(&self, label: &str) -> bool {
+        label == "CLIENT_RANDOM"
+    }
+
+    fn log(&self, _label: &str, _client_random: &[u8], secret: &[u8]) {
+        *self.0.lock().unwrap() = Some(secret.try_into().unwrap());
+    }
+}
+
+#[derive(Debug)]
+struct AcceptAnyServer;
+
+impl ServerVerifier for AcceptAnyServer {
+    fn verify_identity<'a>(
+        &self,
+        identity: &ServerIdentity<'a, '_>,
+    ) -> Result<VerifiedIdentity<'a>, Error> {
+        Ok(VerifiedIdentity::assertion(identity.identity.clone()))
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        _input: &SignatureVerificationInput<'_>,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    #[cfg_attr(coverage_nightly, coverage(off))]
+    fn verify_tls13_signature(
+        &self,
+        _input: &SignatureVerificationInput<'_>,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        unreachable!()
+    }
+
+    fn request_ocsp_response(&self) -> bool {
+        false
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        vec![SignatureScheme::ECDSA_NISTP256_SHA256]
+    }
+
+    fn hash_config(&self, _: &mut dyn Hasher) {}
+}
@@ -6,15 +6,16 @@ use core::time::Duration;
 
 pub(crate) use server_hello::TLS12_HANDLER;
 use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
 
 use super::config::{ClientConfig, ClientSessionKey};
-use super::hs::ClientState;
-use super::{ClientAuthDetails, ServerCertDetails, Tls12Session};
+use super::hs::{ClientHelloInput, ClientState};
+use super::{ClientAuthDetails, ResumptionFailure, ServerCertDetails, Tls12Session};
 use crate::ConnectionTrafficSecrets;
 use crate::check::{inappropriate_handshake_message, inappropriate_message};
-use crate::common_state::{HandshakeKind, Output, OutputEvent, Side};
+use crate::common_state::{Event, HandshakeKind, Output, OutputEvent, ResumptionEvent, Side};
 use crate::conn::kernel::KernelState;
-use crate::conn::{ConnectionRandoms, Input};
+use crate::conn::{ConnectionRandoms, HandoffSecrets, Input};
 use crate::crypto::cipher::{EncodableVersion, MessageDecrypter, MessageEncrypter, Payload};
 use crate::crypto::kx::KeyExchangeAlgorithm;
 use crate::crypto::{Identity, Signer};
@@ -23,10 +24,11 @@ use crate::error::{ApiMisuse, Error, InvalidMessage, PeerIncompatible, PeerMisbe
 use crate::hash_hs::HandshakeHash;
 use crate::msgs::{
     CertificateChain, ChangeCipherSpecPayload, ClientDhParams, ClientEcdhParams,
-    ClientKeyExchangeParams, HandshakeAlignedProof, HandshakeMessagePayload, HandshakePayload,
-    Message, MessagePayload, NewSessionTicketPayload, NewSessionTicketPayloadTls13,
-    ServerKeyExchangeParams, SessionId, SizedPayload,
+    ClientExtensionsInput, ClientKeyExchangeParams, HandshakeAlignedProof, HandshakeMessagePayload,
+    HandshakePayload, Message, MessagePayload, NewSessionTicketPayload,
+    NewSessionTicketPayloadTls13, ServerKeyExchangeParams, SessionId, SizedPayload,
 };
+use crate::observer::ConnectionEvent;
 use crate::suites::{PartiallyExtractedSecrets, Suite};
 use crate::sync::Arc;
 use crate::tls12::{self, ConnectionSecrets, Tls12CipherSuite};
@@ -72,9 +74,7 @@ impl Tls12State {
 
 mod server_hello {
     use super::*;
-    use crate::client::hs::{
-        ClientHandler, ClientHelloInput, ClientSessionValue, ClientState, ExpectServerHello,
-    };
+    use crate::client::hs::{ClientHandler, ClientSessionValue, ClientState, ExpectServerHello};
     use crate::common_state::Protocol;
     use crate::msgs::ServerHelloPayload;
     use crate::sealed::Sealed;
@@ -108,12 +108,15 @@ mod server_hello {
             // Look for TLS1.3 downgrade signal in server random
             // both the server random and TLS12_DOWNGRADE_SENTINEL are
             // public values and don't require constant time comparison
+            //
+            // A renegotiation only offers TLS1.2, so the marker is expected there.
             let has_downgrade_marker = randoms.server[24..] == tls12::DOWNGRADE_SENTINEL;
             if st
                 .input
                 .config
                 .supports_version(ProtocolVersion::TLSv1_3, Protocol::Tcp)
                 && has_downgrade_marker
+                && !st.input.renegotiating()
             {
                 return Err(PeerMisbehaved::AttemptedDowngradeToTls12WhenTls13IsSupported.into());
             }
@@ -130,6 +133,17 @@ mod server_hello {
                 return Err(PeerMisbehaved::ServerEchoedCompatibilitySessionId.into());
             }
 
+            let renegotiation = Renegotiation::check_server_hello(
+                st.input.renegotiation,
+                server_hello
+                    .renegotiation_info
+                    .as_ref()
+                    .map(|info| info.bytes()),
+            )?;
+            let renegotiating = renegotiation
+                .as_ref()
+                .is_some_and(|r| r.renegotiating());
+
             let ClientHelloInput {
                 config,
                 session_key,
@@ -141,7 +155,12 @@ mod server_hello {
                 .resuming
                 .and_then(|resuming| match resuming.value {
                     ClientSessionValue::Tls12(inner) => Some(inner),
-                    ClientSessionValue::Tls13(_) => None,
+                    ClientSessionValue::Tls13(_) => {
+                        output.emit(Event::Resumption(ResumptionEvent::Rejected(
+                            ResumptionFailure::VersionMismatch,
+                        )));
+                        None
+                    }
                 });
 
             // Doing EMS?
@@ -174,7 +193,12 @@ mod server_hello {
 
             // See if we're successfully resuming.
             if let Some(resuming) = resuming_session {
-                if resuming.session_id == server_hello.session_id {
+                if resuming.session_id != server_hello.session_id {
+                    output.emit(Event::Resumption(ResumptionEvent::Rejected(
+                        ResumptionFailure::ServerRejected,
+                    )));
+                    output.observe(ConnectionEvent::ResumptionRejected);
+                } else {
                     debug!("Server agreed to resume");
 
                     // Is the server telling lies about the ciphersuite?
@@ -197,6 +221,8 @@ mod server_hello {
 
                     let (dec, enc) = secrets.make_cipher_pair(Side::Client);
                     output.output(OutputEvent::HandshakeKind(HandshakeKind::Resumed));
+                    output.emit(Event::Resumption(ResumptionEvent::Accepted));
+                    output.observe(ConnectionEvent::ResumptionAccepted);
                     // Since we're resuming, we verified the certificate and
                     // proof of possession in the prior session.
                     let peer_identity =
@@ -209,6 +235,7 @@ mod server_hello {
                         session_key,
                         using_ems,
                         transcript,
+                        renegotiation,
                     };
                     return if must_issue_new_ticket {
                         Ok(Box::new(ExpectNewTicket {
@@ -235,7 +262,9 @@ mod server_hello {
                 }
             }
 
-            output.output(OutputEvent::HandshakeKind(HandshakeKind::Full));
+            if !renegotiating {
+                output.output(OutputEvent::HandshakeKind(HandshakeKind::Full));
+            }
             Ok(Box::new(ExpectCertificate {
                 hs: HandshakeState {
                     config,
@@ -243,6 +272,7 @@ mod server_hello {
                     session_key,
                     using_ems,
                     transcript,
+                    renegotiation,
                 },
                 randoms,
                 suite,
@@ -473,10 +503,10 @@ impl From<Box<ExpectServerKx>> for ClientState {
 }
 
 fn emit_certificate(
-    transcript: &mut HandshakeHash,
+    hs: &mut HandshakeState,
     cert_chain: CertificateChain<'_>,
     output: &mut dyn Output<'_>,
-) {
+) -> Result<(), Error> {
     let cert = Message {
         version: EncodableVersion::Legacy(ProtocolVersion::TLSv1_2),
         payload: MessagePayload::handshake(HandshakeMessagePayload(HandshakePayload::Certificate(
@@ -484,16 +514,16 @@ fn emit_certificate(
         ))),
     };
 
-    transcript.add_message(&cert);
-    output.send_msg(cert, false);
+    hs.transcript.add_message(&cert);
+    output.send_msg(cert, hs.renegotiating())
 }
 
 fn emit_client_kx(
-    transcript: &mut HandshakeHash,
+    hs: &mut HandshakeState,
     kxa: KeyExchangeAlgorithm,
     output: &mut dyn Output<'_>,
     pub_key: &[u8],
-) {
+) -> Result<(), Error> {
     let mut buf = Vec::new();
     match kxa {
         KeyExchangeAlgorithm::ECDHE => ClientKeyExchangeParams::Ecdh(ClientEcdhParams {
@@ -513,16 +543,17 @@ fn emit_client_kx(
         )),
     };
 
-    transcript.add_message(&ckx);
-    output.send_msg(ckx, false);
+    hs.transcript.add_message(&ckx);
+    output.send_msg(ckx, hs.renegotiating())
 }
 
 fn emit_certverify(
-    transcript: &mut HandshakeHash,
+    hs: &mut HandshakeState,
     signer: Box<dyn Signer>,
     output: &mut dyn Output<'_>,
 ) -> Result<(), Error> {
-    let message = transcript
+    let message = hs
+        .transcript
         .take_handshake_buf()
         .ok_or_else(|| Error::General("Expected transcript".to_owned()))?;
 
@@ -537,29 +568,31 @@ fn emit_certverify(
         )),
     };
 
-    transcript.add_message(&m);
-    output.send_msg(m, false);
-    Ok(())
+    hs.transcript.add_message(&m);
+    output.send_msg(m, hs.renegotiating())
 }
 
-fn emit_ccs(output: &mut dyn Output<'_>) {
+fn emit_ccs(hs: &HandshakeState, output: &mut dyn Output<'_>) -> Result<(), Error> {
     output.send_msg(
         Message {
             version: EncodableVersion::Legacy(ProtocolVersion::TLSv1_2),
             payload: MessagePayload::ChangeCipherSpec(ChangeCipherSpecPayload {}),
         },
-        false,
-    );
+        hs.renegotiating(),
+    )
 }
 
 fn emit_finished(
     secrets: &ConnectionSecrets,
-    transcript: &mut HandshakeHash,
+    hs: &mut HandshakeState,
     output: &mut dyn Output<'_>,
     proof: &HandshakeAlignedProof,
-) {
-    let vh = transcript.current_hash();
+) -> Result<(), Error> {
+    let vh = hs.transcript.current_hash();
     let verify_data = secrets.client_verify_data(&vh, proof);
+    if let Some(renegotiation) = &mut hs.renegotiation {
+        renegotiation.client_verify_data = verify_data.to_vec();
+    }
     let verify_data_payload = Payload::Borrowed(&verify_data);
 
     let f = Message {
@@ -569,8 +602,8 @@ fn emit_finished(
         ))),
     };
 
-    transcript.add_message(&f);
-    output.send_msg(f, true);
+    hs.transcript.add_message(&f);
+    output.send_msg(f, true)
 }
 
 struct ServerKxDetails {
@@ -754,6 +787,9 @@ impl ExpectServerDone {
         let purported_identity =
             Identity::from_peer(self.server_cert.cert_chain.0, CertificateType::X509)?
                 .ok_or(PeerMisbehaved::NoCertificatesPresented)?;
+        if let Some(renegotiation) = &self.hs.renegotiation {
+            renegotiation.check_server_identity(&purported_identity)?;
+        }
 
         let peer_identity = self
             .hs
@@ -765,6 +801,7 @@ impl ExpectServerDone {
                 ocsp_response: &self.server_cert.ocsp_response,
                 now: self.hs.config.current_time()?,
             })?;
+        output.observe(ConnectionEvent::CertificateVerified);
 
         // 2.
         // Build up the contents of the signed message.
@@ -804,7 +841,7 @@ impl ExpectServerDone {
                     CertificateChain::from_signer(credentials)
                 }
             };
-            emit_certificate(&mut self.hs.transcript, certs, output);
+            emit_certificate(&mut self.hs, certs, output)?;
         }
 
         // 4a.
@@ -836,7 +873,7 @@ impl ExpectServerDone {
         let kx = skxg.start()?.into_single();
 
         // 4b.
-        emit_client_kx(&mut self.hs.transcript, self.suite.kx, output, kx.pub_key());
+        emit_client_kx(&mut self.hs, self.suite.kx, output, kx.pub_key())?;
         // Note: EMS handshake hash only runs up to ClientKeyExchange.
         let ems_seed = self
             .hs
@@ -845,7 +882,7 @@ impl ExpectServerDone {
 
         // 4c.
         if let Some(ClientAuthDetails::Verify { credentials, .. }) = self.client_auth {
-            emit_certverify(&mut self.hs.transcript, credentials.signer, output)?;
+            emit_certverify(&mut self.hs, credentials.signer, output)?;
         }
 
         // 4d. Derive secrets.
@@ -858,10 +895,12 @@ impl ExpectServerDone {
             self.randoms,
             suite,
         )?;
-        output.output(OutputEvent::KeyExchangeGroup(skxg));
+        if !self.hs.renegotiating() {
+            output.output(OutputEvent::KeyExchangeGroup(skxg));
+        }
 
         // 4e. CCS. We are definitely going to switch on encryption.
-        emit_ccs(output);
+        emit_ccs(&self.hs, output)?;
 
         // 4f. Now commit secrets.
         self.hs.config.key_log.log(
@@ -880,7 +919,7 @@ impl ExpectServerDone {
         );
 
         // 5.
-        emit_finished(&secrets, &mut self.hs.transcript, output, &proof);
+        emit_finished(&secrets, &mut self.hs, output, &proof)?;
 
         if self.must_issue_new_ticket {
             Ok(Box::new(ExpectNewTicket {
@@ -1107,6 +1146,13 @@ impl ExpectFinished {
                 }
             };
 
+        if let Some(renegotiation) = &mut st.hs.renegotiation {
+            renegotiation.server_verify_data = expect_verify_data.to_vec();
+            if renegotiation.server_identity.is_none() {
+                renegotiation.server_identity = Some(st.peer_identity.identity().clone());
+            }
+        }
+
         // Hash this message too.
         st.hs
             .transcript
@@ -1115,7 +1161,7 @@ impl ExpectFinished {
         st.save_session();
 
         if let Some((_, encrypter)) = st.resuming.take() {
-            emit_ccs(output);
+            emit_ccs(&st.hs, output)?;
             output.send().set_encrypter(
                 encrypter,
                 st.secrets
@@ -1123,23 +1169,39 @@ impl ExpectFinished {
                     .common
                     .confidentiality_limit,
             );
-            emit_finished(&st.secrets, &mut st.hs.transcript, output, &proof);
+            emit_finished(&st.secrets, &mut st.hs, output, &proof)?;
         }
 
-        let extracted_secrets = st
-            .hs
-            .config
-            .enable_secret_extraction
-            .then(|| st.secrets.extract_secrets(Side::Client));
-
         let _cert_verified = st.peer_identity.as_marker();
         output.output(OutputEvent::PeerIdentity(st.peer_identity));
         output.output(OutputEvent::ExtendedMainSecret(st.hs.using_ems));
-        output.output(OutputEvent::Exporter(st.secrets.into_exporter()));
+        output.output(OutputEvent::Exporter(st.secrets.exporter()));
         output.start_traffic();
 
+        // Without extended main secret, the handshake is not bound to this
+        // connection and renegotiation is open to the triple handshake attack.
+        let renegotiation = st
+            .hs
+            .renegotiation
+            .filter(|r| r.remaining > 0 && st.hs.using_ems)
+            .map(|state| {
+                Box::new(Renegotiable {
+                    config: st.hs.config.clone(),
+                    session_key: st.hs.session_key,
+                    state,
+                })
+            });
+        let recv = output.receive();
+        recv.renegotiating = false;
+        recv.renegotiation_allowed = renegotiation.is_some();
+
         Ok(Box::new(ExpectTraffic {
-            extracted_secrets,
+            secrets: st
+                .hs
+                .config
+                .enable_secret_extraction
+                .then_some(st.secrets),
+            renegotiation,
             _cert_verified,
             _sig_verified: st.sig_verified,
             _fin_verified: fin_verified,
@@ -1173,25 +1235,193 @@ struct HandshakeState {
     session_key: ClientSessionKey<'static>,
     using_ems: bool,
     transcript: HandshakeHash,
+    renegotiation: Option<Renegotiation>,
+}
+
+impl HandshakeState {
+    /// Whether this handshake renegotiates an established connection.
+    ///
+    /// If so, all our messages are protected by the connection's current keys.
+    fn renegotiating(&self) -> bool {
+        self.renegotiation
+            .as_ref()
+            .is_some_and(|r| r.renegotiating())
+    }
+}
+
+/// Tracks [RFC 5746] secure renegotiation across the handshakes of a connection.
+///
+/// [RFC 5746]: https://datatracker.ietf.org/doc/html/rfc5746
+pub(crate) struct Renegotiation {
+    /// How many more times the server may renegotiate.
+    remaining: u8,
+    /// The `verify_data` of our latest `Finished` message; empty before the first.
+    client_verify_data: Vec<u8>,
+    /// The `verify_data` of the server's latest `Finished` message; empty before the first.
+    server_verify_data: Vec<u8>,
+    /// The server's identity from the first handshake, which a renegotiation must not change.
+    ///
+    /// Together with requiring [RFC 7627] extended main secret, this prevents the
+    /// [triple handshake attack].
+    ///
+    /// [RFC 7627]: https://datatracker.ietf.org/doc/html/rfc7627
+    /// [triple handshake attack]: https://web.archive.org/web/www.mitls.org/pages/attacks/3SHAKE
+    server_identity: Option<Identity<'static>>,
+}
+
+impl Renegotiation {
+    /// Returns `None` unless `config` allows renegotiation.
+    pub(super) fn new(config: &ClientConfig) -> Option<Self> {
+        (config.max_renegotiations > 0).then(|| Self {
+            remaining: config.max_renegotiations,
+            client_verify_data: Vec::new(),
+            server_verify_data: Vec::new(),
+            server_identity: None,
+        })
+    }
+
+    /// Check the server's `renegotiation_info` extension.
+    ///
+    /// In an initial handshake, this must be empty, and if absent the server does
+    /// not support secure renegotiation.  In a renegotiation, it must contain the
+    /// `verify_data` of both `Finished` messages of the previous handshake.
+    ///
+    /// Returns the state to carry forward, which is `None` if renegotiation is not
+    /// possible on this connection.
+    fn check_server_hello(
+        renegotiation: Option<Self>,
+        renegotiation_info: Option<&[u8]>,
+    ) -> Result<Option<Self>, Error> {
+        match (renegotiation, renegotiation_info) {
+            (Some(r), Some(info)) => {
+                let expected = [&r.client_verify_data[..], &r.server_verify_data[..]].concat();
+                match ConstantTimeEq::ct_eq(&expected[..], info).into() {
+                    true => Ok(Some(r)),
+                    false => Err(PeerMisbehaved::IncorrectRenegotiationInfo.into()),
+                }
+            }
+            (Some(r), None) if !r.renegotiating() => {
+                debug!("Server does not support secure renegotiation");
+                Ok(None)
+            }
+            (None, Some([]) | None) => Ok(None),
+            _ => Err(PeerMisbehaved::IncorrectRenegotiationInfo.into()),
+        }
+    }
+
+    /// Check the server presented the same end-entity certificate (or raw public key)
+    /// as in the first handshake.
+    fn check_server_identity(&self, identity: &Identity<'_>) -> Result<(), Error> {
+        let same = match (&self.server_identity, identity) {
+            (Some(Identity::X509(original)), Identity::X509(new)) => {
+                original.end_entity == new.end_entity
+            }
+            (Some(original), new) => original == new,
+            (None, _) => true,
+        };
+
+        match same {
+            true => Ok(()),
+            false => Err(PeerMisbehaved::ServerCertificateChangedDuringRenegotiation.into()),
+        }
+    }
+
+    /// Whether the handshake in progress renegotiates an established connection.
+    pub(super) fn renegotiating(&self) -> bool {
+        !self.client_verify_data.is_empty()
+    }
+
+    pub(super) fn client_verify_data(&self) -> &[u8] {
+        &self.client_verify_data
+    }
+}
+
+/// What we need to renegotiate an established connection.
+struct Renegotiable {
+    config: Arc<ClientConfig>,
+    session_key: ClientSessionKey<'static>,
+    state: Renegotiation,
+}
+
+impl Renegotiable {
+    /// Respond to a `HelloRequest` by starting a new handshake.
+    fn start(mut self, output: &mut dyn Output<'_>) -> Result<ClientState, Error> {
+        debug!("Server requested renegotiation");
+        self.state.remaining -= 1;
+        let recv = output.receive();
+        recv.renegotiation_allowed = false;
+        recv.renegotiating = true;
+        ClientHelloInput::renegotiate(self.config, self.session_key, self.state)?
+            .start_handshake(ClientExtensionsInput::default(), output)
+    }
 }
 
 // -- Traffic transit state --
 pub(super) struct ExpectTraffic {
     // only `Some` if `config.enable_secret_extraction` is true
-    extracted_secrets: Option<Result<PartiallyExtractedSecrets, Error>>,
+    secrets: Option<ConnectionSecrets>,
+    // only `Some` if the server may renegotiate
+    renegotiation: Option<Box<Renegotiable>>,
     _cert_verified: PeerVerified,
     _sig_verified: HandshakeSignatureValid,
     _fin_verified: FinishedMessageVerified,
 }
 
 impl ExpectTraffic {
+    /// Resume traffic using `secrets` taken from a serialized connection.
+    pub(super) fn restore(
+        config: &ClientConfig,
+        secrets: ConnectionSecrets,
+        exporter: bool,
+        peer_identity: &VerifiedIdentity<'_>,
+        output: &mut dyn Output<'_>,
+        proof: &HandshakeAlignedProof,
+    ) -> Box<Self> {
+        let (dec, enc) = secrets.make_cipher_pair(Side::Client);
+        output
+            .receive()
+            .decrypt_state
+            .set_message_decrypter(dec, proof);
+        output.send().set_encrypter(
+            enc,
+            secrets
+                .suite()
+                .common
+                .confidentiality_limit,
+        );
+        if exporter {
+            output.output(OutputEvent::Exporter(secrets.exporter()));
+        }
+        output.start_traffic();
+
+        Box::new(Self {
+            secrets: config
+                .enable_secret_extraction
+                .then_some(secrets),
+            renegotiation: None,
+            _cert_verified: peer_identity.as_marker(),
+            _sig_verified: HandshakeSignatureValid::assertion(),
+            _fin_verified: FinishedMessageVerified::assertion(),
+        })
+    }
+
     fn handle<'m>(
-        self: Box<Self>,
-        Input { message, .. }: Input<'m>,
+        mut self: Box<Self>,
+        input: Input<'m>,
         output: &mut dyn Output<'m>,
     ) -> Result<ClientState, Error> {
-        match message.payload {
+        match input.message.payload {
             MessagePayload::ApplicationData(payload) => output.received_plaintext(payload),
+            MessagePayload::Handshake {
+                parsed: HandshakeMessagePayload(HandshakePayload::HelloRequest),
+                ..
+            } if self.renegotiation.is_some() => {
+                // The new handshake must not start mid-way through another message.
+                let _aligned = input.check_aligned_handshake()?;
+                if let Some(renegotiation) = self.renegotiation.take() {
+                    return renegotiation.start(output);
+                }
+            }
             payload => {
                 return Err(inappropriate_message(
                     &payload,
@@ -1206,8 +1436,18 @@ impl ExpectTraffic {
         mut self: Box<Self>,
         _send_keys: &Option<Box<KeyScheduleTrafficSend>>,
     ) -> Result<(PartiallyExtractedSecrets, Box<dyn KernelState + 'static>), Error> {
-        match self.extracted_secrets.take() {
-            Some(extracted_secrets) => Ok((extracted_secrets?, self)),
+        match self.secrets.take() {
+            Some(secrets) => Ok((secrets.extract_secrets(Side::Client)?, self)),
+            None => Err(ApiMisuse::SecretExtractionRequiresPriorOptIn.into()),
+        }
+    }
+
+    pub(super) fn handoff_secrets(&self) -> Result<HandoffSecrets, Error> {
+        match &self.secrets {
+            Some(secrets) => Ok(HandoffSecrets::Tls12 {
+                randoms: secrets.randoms.clone(),
+                master_secret: Zeroizing::new(*secrets.master_secret()),
+            }),
             None => Err(ApiMisuse::SecretExtractionRequiresPriorOptIn.into()),
         }
     }
@@ -2,6 +2,7 @@ use alloc::boxed::Box;
 use alloc::vec;
 use alloc::vec::Vec;
 
+use pki_types::ServerName;
 use subtle::ConstantTimeEq;
 
 use super::config::{ClientConfig, ClientSessionKey, ClientSessionStore};
@@ -11,37 +12,42 @@ use super::hs::{
     GroupAndKeyShare, process_alpn_protocol,
 };
 use super::{
-    ClientAuthDetails, ClientHelloDetails, Retrieved, ServerCertDetails, Tls13ClientSessionInput,
-    Tls13Session,
+    ClientAuthDetails, ClientHelloDetails, NegotiatedApplicationSettings, ResumptionFailure,
+    Retrieved, ServerCertDetails, Tls13ClientSessionInput, Tls13Session,
 };
 use crate::check::inappropriate_handshake_message;
 use crate::common_state::{
-    EarlyDataEvent, Event, HandshakeFlightTls13, HandshakeKind, Output, OutputEvent, Protocol, Side,
+    EarlyDataEvent, Event, HandshakeFlightTls13, HandshakeKind, Output, OutputEvent,
+    ResumptionEvent, Side,
 };
 use crate::conn::kernel::KernelState;
-use crate::conn::{ConnectionRandoms, Input, TrafficTemperCounters};
+use crate::conn::{ConnectionRandoms, HandoffSecrets, Input, TrafficTemperCounters};
 use crate::crypto::cipher::{EncodableVersion, Payload};
 use crate::crypto::hash::Hash;
 use crate::crypto::kx::{ActiveKeyExchange, HybridKeyExchange, SharedSecret, StartedKeyExchange};
+use crate::crypto::tls13::OkmBlock;
 use crate::crypto::{Identity, SelectedCredential, SignatureScheme, Signer, VerifiedIdentity};
-use crate::enums::{CertificateType, ContentType, HandshakeType, ProtocolVersion};
+use crate::enums::{
+    ApplicationSettings, CertificateType, ContentType, HandshakeType, ProtocolVersion,
+};
 use crate::error::{
     ApiMisuse, Error, InvalidMessage, PeerIncompatible, PeerMisbehaved, RejectedEch,
 };
 use crate::hash_hs::{HandshakeHash, HandshakeHashBuffer};
 use crate::msgs::{
     CERTIFICATE_MAX_SIZE_LIMIT, CertificatePayloadTls13, ChangeCipherSpecPayload, ClientExtensions,
-    Codec, EchConfigPayload, EncryptedExtensions, ExtensionType, HandshakeMessagePayload,
-    HandshakePayload, KeyShareEntry, KeyUpdateRequest, MaybeEmpty, Message, MessagePayload,
-    NewSessionTicketPayloadTls13, PresharedKeyBinder, PresharedKeyIdentity, PresharedKeyOffer,
-    ServerHelloPayload, SizedPayload,
+    Codec, EchConfigPayload, EncryptedExtensions, ExtensionType, HandshakeAlignedProof,
+    HandshakeMessagePayload, HandshakePayload, KeyShareEntry, KeyUpdateRequest, MaybeEmpty,
+    Message, MessagePayload, NewSessionTicketPayloadTls13, PresharedKeyBinder,
+    PresharedKeyIdentity, PresharedKeyOffer, ServerHelloPayload, SizedPayload,
 };
+use crate::observer::ConnectionEvent;
 use crate::sealed::Sealed;
 use crate::suites::PartiallyExtractedSecrets;
 use crate::sync::Arc;
 use crate::tls13::key_schedule::{
-    KeyScheduleEarlyClient, KeyScheduleHandshake, KeySchedulePreHandshake, KeyScheduleResumption,
-    KeyScheduleTrafficReceive, KeyScheduleTrafficSend,
+    KeyScheduleEarlyClient, KeyScheduleExporter, KeyScheduleHandshake, KeySchedulePreHandshake,
+    KeyScheduleResumption, KeyScheduleTraffic, KeyScheduleTrafficReceive, KeyScheduleTrafficSend,
 };
 use crate::tls13::{
     Tls13CipherSuite, Tls13ProtocolSuite, construct_client_verify_message,
@@ -52,7 +58,7 @@ use crate::verify::{
     DigitallySignedStruct, FinishedMessageVerified, HandshakeSignatureValid, PeerVerified,
     ServerIdentity, SignatureVerificationInput,
 };
-use crate::{ConnectionTrafficSecrets, KeyLog, compress, crypto, quic};
+use crate::{ConnectionTrafficSecrets, KeyLog, compress, crypto};
 
 #[expect(private_interfaces)]
 pub(crate) enum Tls13State {
@@ -87,6 +93,21 @@ impl Tls13State {
             Self::QuicTraffic(e) => e.handle(input, output),
         }
     }
+
+    pub(crate) fn set_resumption_data(&mut self, data: &[u8]) {
+        let resumption_data = match self {
+            Self::EncryptedExtensions(e) => &mut e.hs.resumption_data,
+            Self::CertificateOrCompressedCertificateOrCertReq(e) => &mut e.hs.resumption_data,
+            Self::CertificateOrCompressedCertificate(e) => &mut e.hs.resumption_data,
+            Self::CertificateOrCertReq(e) => &mut e.hs.resumption_data,
+            Self::Certificate(e) => &mut e.hs.resumption_data,
+            Self::CertificateVerify(e) => &mut e.hs.resumption_data,
+            Self::Finished(e) => &mut e.hs.resumption_data,
+            Self::Traffic(e) => &mut e.resumption_data,
+            Self::QuicTraffic(e) => &mut e.0.resumption_data,
+        };
+        *resumption_data = data.to_vec();
+    }
 }
 
 pub(crate) static TLS13_HANDLER: &dyn ClientHandler<Tls13CipherSuite> = &Handler;
@@ -135,6 +156,7 @@ impl ClientHandler<Tls13CipherSuite> for Handler {
             mut hello,
             session_key,
             protocol,
+            resumption_data,
             ..
         } = st.input;
 
@@ -151,10 +173,7 @@ impl ClientHandler<Tls13CipherSuite> for Handler {
         let our_key_share = KeyExchangeChoice::new(&config, output, our_key_share, their_key_share)
             .map_err(|_| PeerMisbehaved::WrongGroupForKeyShare)?;
 
-        let suite = match protocol {
-            Protocol::Tcp => Tls13ProtocolSuite::Tcp(suite),
-            Protocol::Quic(_) => Tls13ProtocolSuite::Quic(quic::Suite::try_from(suite)?),
-        };
+        let suite = Tls13ProtocolSuite::new(suite, protocol)?;
 
         let (key_schedule_pre_handshake, in_early_traffic) =
             match (server_hello.preshared_key, st.early_data_key_schedule) {
@@ -184,6 +203,8 @@ impl ClientHandler<Tls13CipherSuite> for Handler {
                             }
 
                             debug!("Resuming using PSK");
+                            output.emit(Event::Resumption(ResumptionEvent::Accepted));
+                            output.observe(ConnectionEvent::ResumptionAccepted);
                             // The key schedule has been initialized and set in fill_in_psk_binder()
                         }
                         _ => {
@@ -195,11 +216,19 @@ impl ClientHandler<Tls13CipherSuite> for Handler {
                         in_early_traffic,
                     )
                 }
-                _ => {
+                (_, early_data_key_schedule) => {
                     debug!("Not resuming");
+                    if resuming_session.take().is_some() {
+                        output.emit(Event::Resumption(ResumptionEvent::Rejected(
+                            ResumptionFailure::ServerRejected,
+                        )));
+                        output.observe(ConnectionEvent::ResumptionRejected);
+                    }
                     // Discard the early data key schedule.
                     output.emit(Event::EarlyData(EarlyDataEvent::Rejected));
-                    resuming_session.take();
+                    if let Some((_, true)) = early_data_key_schedule {
+                        output.observe(ConnectionEvent::EarlyDataRejected);
+                    }
                     (KeySchedulePreHandshake::new(Side::Client, suite)?, false)
                 }
             };
@@ -265,8 +294,8 @@ impl ClientHandler<Tls13CipherSuite> for Handler {
             &proof,
         );
 
-        if !key_schedule.is_quic() {
-            emit_fake_ccs(&mut sent_tls13_fake_ccs, output);
+        if !key_schedule.is_datagram() {
+            emit_fake_ccs(&mut sent_tls13_fake_ccs, output)?;
         }
 
         output.output(OutputEvent::HandshakeKind(
@@ -285,6 +314,8 @@ impl ClientHandler<Tls13CipherSuite> for Handler {
                 randoms,
                 transcript,
                 key_schedule,
+                application_settings: None,
+                resumption_data,
             },
             resuming_session,
             suite,
@@ -416,16 +447,33 @@ pub(super) fn prepare_resumption(
     // The EarlyData extension MUST be supplied together with the
     // PreSharedKey extension.
     let max_early_data_size = resuming_session.max_early_data_size;
-    let early_data_enabled = if config.enable_early_data && max_early_data_size > 0 && !doing_retry
-    {
-        output.emit(Event::EarlyData(EarlyDataEvent::Enable(
-            max_early_data_size as usize,
-        )));
-        exts.early_data_request = Some(());
-        true
-    } else {
-        false
-    };
+
+    // Early data is written assuming the ALPS settings of the previous connection,
+    // so we must offer the same settings for the same protocol again.
+    let alps = resuming_session
+        .application_settings
+        .as_ref();
+    let alps_unchanged = alps.is_none_or(|alps| {
+        exts.application_settings
+            .as_ref()
+            .is_some_and(|offered| offered.contains(&alps.protocol))
+            && ApplicationSettings::find(&config.application_settings, &alps.protocol)
+                == Some(alps.local.as_slice())
+    });
+
+    let early_data_enabled =
+        if config.enable_early_data && max_early_data_size > 0 && !doing_retry && alps_unchanged {
+            output.emit(Event::EarlyData(EarlyDataEvent::Enable(
+                max_early_data_size as usize,
+            )));
+            output.output(OutputEvent::PeerApplicationSettings(
+                alps.map(|alps| alps.peer.clone()),
+            ));
+            exts.early_data_request = Some(());
+            true
+        } else {
+            false
+        };
 
     // Finally, and only for TLS1.3 with a ticket resumption, include a binder
     // for our ticket.  This must go last.
@@ -456,10 +504,10 @@ pub(super) fn derive_early_traffic_secret(
     sent_tls13_fake_ccs: &mut bool,
     transcript_buffer: &HandshakeHashBuffer,
     client_random: &[u8; 32],
-) {
-    if !early_key_schedule.is_quic() {
+) -> Result<(), Error> {
+    if !early_key_schedule.is_datagram() {
         // For middlebox compatibility
-        emit_fake_ccs(sent_tls13_fake_ccs, output);
+        emit_fake_ccs(sent_tls13_fake_ccs, output)?;
     }
 
     let client_hello_hash = transcript_buffer.hash_given(hash_alg, &[]);
@@ -477,11 +525,15 @@ pub(super) fn derive_early_traffic_secret(
     // Now the client can send encrypted early data
     output.emit(Event::EarlyData(EarlyDataEvent::Start));
     trace!("Starting early data traffic");
+    Ok(())
 }
 
-pub(super) fn emit_fake_ccs(sent_tls13_fake_ccs: &mut bool, output: &mut dyn Output<'_>) {
+pub(super) fn emit_fake_ccs(
+    sent_tls13_fake_ccs: &mut bool,
+    output: &mut dyn Output<'_>,
+) -> Result<(), Error> {
     if core::mem::replace(sent_tls13_fake_ccs, true) {
-        return;
+        return Ok(());
     }
 
     output.send_msg(
@@ -490,7 +542,7 @@ pub(super) fn emit_fake_ccs(sent_tls13_fake_ccs: &mut bool, output: &mut dyn Out
             payload: MessagePayload::ChangeCipherSpec(ChangeCipherSpecPayload {}),
         },
         false,
-    );
+    )
 }
 
 fn validate_encrypted_extensions(
@@ -542,6 +594,33 @@ impl ExpectEncryptedExtensions {
             self.hs.config.check_selected_alpn,
         )?;
 
+        // ALPS is only acceptable for a selected protocol we offered it for.
+        self.hs.application_settings = match (&exts.application_settings, selected_alpn) {
+            (None, _) => None,
+            (Some(peer), Some(protocol))
+                if self
+                    .hello
+                    .alpn_protocols
+                    .contains(protocol) =>
+            {
+                let local =
+                    ApplicationSettings::find(&self.hs.config.application_settings, protocol)
+                        .ok_or(PeerMisbehaved::UnsolicitedEncryptedExtension)?;
+                Some(NegotiatedApplicationSettings {
+                    protocol: protocol.to_owned(),
+                    local: local.to_vec(),
+                    peer: peer.bytes().to_vec(),
+                })
+            }
+            (Some(_), _) => return Err(PeerMisbehaved::UnsolicitedEncryptedExtension.into()),
+        };
+        output.output(OutputEvent::PeerApplicationSettings(
+            self.hs
+                .application_settings
+                .as_ref()
+                .map(|alps| alps.peer.clone()),
+        ));
+
         // RFC 9001 says: "While ALPN only specifies that servers use this alert, QUIC clients MUST
         // use error 0x0178 to terminate a connection when ALPN negotiation fails." We judge that
         // the user intended to use ALPN (rather than some out-of-band protocol negotiation
@@ -592,7 +671,7 @@ impl ExpectEncryptedExtensions {
         };
 
         // QUIC transport parameters
-        let quic_params = if let Some(quic) = output.quic() {
+        let quic_params = if let Some(quic) = output.quic().filter(|q| q.is_quic()) {
             let Some(quic_params) = exts.transport_parameters.as_ref() else {
                 return Err(PeerMisbehaved::MissingQuicTransportParameters.into());
             };
@@ -609,9 +688,21 @@ impl ExpectEncryptedExtensions {
             Some(resuming_session) => {
                 if self.in_early_traffic {
                     match exts.early_data_ack {
-                        Some(()) => output.emit(Event::EarlyData(EarlyDataEvent::Accepted)),
+                        Some(()) => {
+                            // The early data was sent assuming the remembered settings.
+                            if resuming_session.application_settings != self.hs.application_settings
+                            {
+                                return Err(
+                                    PeerMisbehaved::EarlyDataAcceptedWithVariedApplicationSettings
+                                        .into(),
+                                );
+                            }
+                            output.emit(Event::EarlyData(EarlyDataEvent::Accepted));
+                            output.observe(ConnectionEvent::EarlyDataAccepted);
+                        }
                         None => {
                             output.emit(Event::EarlyData(EarlyDataEvent::Rejected));
+                            output.observe(ConnectionEvent::EarlyDataRejected);
                             // If no early traffic, set the encryption key for handshakes
                             self.hs
                                 .key_schedule
@@ -621,17 +712,25 @@ impl ExpectEncryptedExtensions {
                     }
                 }
 
+                output.emit(Event::ResumptionData(
+                    resuming_session
+                        .resumption_data()
+                        .to_vec(),
+                ));
+
                 // We *don't* reverify the certificate chain here: resumption is a
                 // continuation of the previous session in terms of security policy.
                 let peer_identity =
                     VerifiedIdentity::assertion(resuming_session.peer_identity().clone());
                 let sig_verified = HandshakeSignatureValid::assertion();
+                let application_settings = self.hs.application_settings.clone();
                 Ok(Box::new(ExpectFinished {
                     hs: self.hs,
                     session_input: Tls13ClientSessionInput {
                         suite: self.suite,
                         peer_identity,
                         quic_params,
+                        application_settings,
                     },
                     client_auth: None,
                     sig_verified,
@@ -1132,7 +1231,7 @@ impl ExpectCertificateVerify {
     fn handle(
         mut self: Box<Self>,
         Input { message, .. }: Input<'_>,
-        _output: &mut dyn Output<'_>,
+        output: &mut dyn Output<'_>,
     ) -> Result<ClientState, Error> {
         let cert_verify = require_handshake_msg!(
             message,
@@ -1159,6 +1258,7 @@ impl ExpectCertificateVerify {
                 ocsp_response: &self.server_cert.ocsp_response,
                 now: self.hs.config.current_time()?,
             })?;
+        output.observe(ConnectionEvent::CertificateVerified);
 
         // 2. Verify their signature on the handshake.
         let handshake_hash = self.hs.transcript.current_hash();
@@ -1174,12 +1274,14 @@ impl ExpectCertificateVerify {
 
         self.hs.transcript.add_message(&message);
 
+        let application_settings = self.hs.application_settings.clone();
         Ok(Box::new(ExpectFinished {
             hs: self.hs,
             session_input: Tls13ClientSessionInput {
                 suite: self.suite,
                 peer_identity: peer_identity.into_owned(),
                 quic_params: self.quic_params,
+                application_settings,
             },
             client_auth: self.client_auth,
             sig_verified,
@@ -1256,6 +1358,17 @@ fn emit_certverify_tls13(
     Ok(())
 }
 
+fn emit_encrypted_extensions_tls13(flight: &mut HandshakeFlightTls13<'_>, settings: &[u8]) {
+    let exts = EncryptedExtensions {
+        application_settings: Some(Payload::new(settings)),
+        ..Default::default()
+    };
+
+    flight.add(HandshakeMessagePayload(
+        HandshakePayload::EncryptedExtensions(Box::new(exts)),
+    ));
+}
+
 fn emit_finished_tls13(
     flight: &mut HandshakeFlightTls13<'_>,
     verify_data: &crypto::hmac::PublicTag,
@@ -1267,7 +1380,10 @@ fn emit_finished_tls13(
     )));
 }
 
-fn emit_end_of_early_data_tls13(transcript: &mut HandshakeHash, output: &mut dyn Output<'_>) {
+fn emit_end_of_early_data_tls13(
+    transcript: &mut HandshakeHash,
+    output: &mut dyn Output<'_>,
+) -> Result<(), Error> {
     let m = Message {
         version: EncodableVersion::Legacy(ProtocolVersion::TLSv1_3),
         payload: MessagePayload::handshake(HandshakeMessagePayload(
@@ -1276,7 +1392,7 @@ fn emit_end_of_early_data_tls13(transcript: &mut HandshakeHash, output: &mut dyn
     };
 
     transcript.add_message(&m);
-    output.send_msg(m, true);
+    output.send_msg(m, true)
 }
 
 struct ExpectFinished {
@@ -1324,8 +1440,8 @@ impl ExpectFinished {
         /* The EndOfEarlyData message to server is still encrypted with early data keys,
          * but appears in the transcript after the server Finished. */
         if st.in_early_traffic {
-            if !st.hs.key_schedule.is_quic() {
-                emit_end_of_early_data_tls13(&mut st.hs.transcript, output);
+            if !st.hs.key_schedule.is_datagram() {
+                emit_end_of_early_data_tls13(&mut st.hs.transcript, output)?;
             }
             output.emit(Event::EarlyData(EarlyDataEvent::Finished));
             st.hs
@@ -1335,6 +1451,11 @@ impl ExpectFinished {
 
         let mut flight = HandshakeFlightTls13::new(&mut st.hs.transcript);
 
+        /* If ALPS was negotiated, our settings come first in the second flight. */
+        if let Some(alps) = &st.hs.application_settings {
+            emit_encrypted_extensions_tls13(&mut flight, &alps.local);
+        }
+
         /* Send our authentication/finished messages.  These are still encrypted
          * with our handshake keys. */
         if let Some(client_auth) = st.client_auth {
@@ -1384,7 +1505,7 @@ impl ExpectFinished {
             );
 
         emit_finished_tls13(&mut flight, &verify_data);
-        flight.finish(output);
+        flight.finish(output)?;
 
         /* We're now sure this server supports TLS1.3.  But if we run out of TLS1.3 tickets
          * when connecting to it again, we definitely don't want to attempt a TLS1.2 resumption. */
@@ -1422,7 +1543,7 @@ impl ExpectFinished {
             .into());
         }
 
-        let is_quic = key_schedule_recv.is_quic();
+        let is_datagram = key_schedule_recv.is_datagram();
 
         let st = ExpectTraffic {
             config: st.hs.config.clone(),
@@ -1435,9 +1556,10 @@ impl ExpectFinished {
             _cert_verified,
             _sig_verified: st.sig_verified,
             _fin_verified: fin,
+            resumption_data: st.hs.resumption_data,
         };
 
-        Ok(match is_quic {
+        Ok(match is_datagram {
             true => Box::new(ExpectQuicTraffic(st)).into(),
             false => Box::new(st).into(),
         })
@@ -1456,6 +1578,8 @@ struct HandshakeState {
     randoms: ConnectionRandoms,
     transcript: HandshakeHash,
     key_schedule: KeyScheduleHandshake,
+    application_settings: Option<NegotiatedApplicationSettings>,
+    resumption_data: Vec<u8>,
 }
 
 // -- Traffic transit state (TLS1.3) --
@@ -1472,16 +1596,74 @@ pub(super) struct ExpectTraffic {
     _cert_verified: PeerVerified,
     _sig_verified: HandshakeSignatureValid,
     _fin_verified: FinishedMessageVerified,
+    /// Application data to store with tickets received from now on.
+    resumption_data: Vec<u8>,
 }
 
 impl ExpectTraffic {
+    /// Resume traffic using secrets taken from a serialized connection.
+    pub(super) fn restore(
+        config: &Arc<ClientConfig>,
+        suite: &'static Tls13CipherSuite,
+        restored: RestoredTraffic<'_>,
+        peer_identity: VerifiedIdentity<'static>,
+        output: &mut dyn Output<'_>,
+        proof: &HandshakeAlignedProof,
+    ) -> Box<Self> {
+        let RestoredTraffic {
+            tx,
+            rx,
+            resumption,
+            server_name,
+            resumption_data,
+            exporter,
+        } = restored;
+
+        let key_schedule_recv =
+            KeyScheduleTraffic::restore(Side::Client, suite, tx, rx, output, proof);
+        if let Some(secret) = exporter {
+            output.output(OutputEvent::Exporter(Box::new(
+                KeyScheduleExporter::restore(Side::Client, suite, OkmBlock::new(secret)),
+            )));
+        }
+        output.start_traffic();
+
+        Box::new(Self {
+            config: config.clone(),
+            session_storage: config.resumption.store.clone(),
+            session_key: ClientSessionKey {
+                config_hash: config.config_hash(),
+                server_name,
+            },
+            _cert_verified: peer_identity.as_marker(),
+            session_input: Tls13ClientSessionInput {
+                suite: Tls13ProtocolSuite::Tcp(suite),
+                peer_identity,
+                quic_params: None,
+                application_settings: None,
+            },
+            key_schedule_recv,
+            resumption: KeyScheduleResumption::restore(Side::Client, suite, resumption),
+            counters: TrafficTemperCounters::default(),
+            _sig_verified: HandshakeSignatureValid::assertion(),
+            _fin_verified: FinishedMessageVerified::assertion(),
+            resumption_data,
+        })
+    }
+
     fn handle_new_ticket_impl(&self, nst: &NewSessionTicketPayloadTls13) -> Result<(), Error> {
         let secret = self
             .resumption
             .derive_ticket_psk(nst.nonce.bytes());
 
         let now = self.config.current_time()?;
-        let value = Tls13Session::new(nst, self.session_input.clone(), secret.as_ref(), now);
+        let value = Tls13Session::new(
+            nst,
+            self.session_input.clone(),
+            secret.as_ref(),
+            now,
+            &self.resumption_data,
+        );
         if self.key_schedule_recv.is_quic() {
             if let Some(sz) = nst.extensions.max_early_data_size {
                 if sz != 0 && sz != 0xffff_ffff {
@@ -1500,8 +1682,10 @@ impl ExpectTraffic {
         output: &mut dyn Output<'_>,
         nst: &NewSessionTicketPayloadTls13,
     ) -> Result<(), Error> {
-        let received = &mut output.receive().tls13_tickets_received;
-        *received = received.saturating_add(1);
+        let recv = output.receive();
+        recv.tls13_tickets_received = recv
+            .tls13_tickets_received
+            .saturating_add(1);
         self.handle_new_ticket_impl(nst)
     }
 
@@ -1529,6 +1713,7 @@ impl ExpectTraffic {
         // Update our read-side keys.
         self.key_schedule_recv
             .update_decrypter(output.receive(), &proof);
+        output.observe(ConnectionEvent::KeyUpdateReceived);
         Ok(())
     }
 }
@@ -1592,6 +1777,39 @@ impl ExpectTraffic {
             self,
         ))
     }
+
+    pub(super) fn handoff_secrets(
+        &self,
+        send_keys: &Option<Box<KeyScheduleTrafficSend>>,
+    ) -> Result<HandoffSecrets, Error> {
+        if !self.config.enable_secret_extraction {
+            return Err(ApiMisuse::SecretExtractionRequiresPriorOptIn.into());
+        }
+        let Some(send_keys) = send_keys else {
+            return Err(Error::Unreachable(
+                "send_keys required for TLS1.3 handoff_secrets",
+            ));
+        };
+        Ok(HandoffSecrets::Tls13 {
+            tx: send_keys.secret().clone(),
+            rx: self.key_schedule_recv.secret().clone(),
+            resumption: Some((
+                self.resumption.secret().clone(),
+                self.session_key.server_name.clone(),
+                self.resumption_data.clone(),
+            )),
+        })
+    }
+}
+
+/// The secrets of a serialized TLS1.3 client connection.
+pub(super) struct RestoredTraffic<'a> {
+    pub(super) tx: OkmBlock,
+    pub(super) rx: OkmBlock,
+    pub(super) resumption: OkmBlock,
+    pub(super) server_name: ServerName<'static>,
+    pub(super) resumption_data: Vec<u8>,
+    pub(super) exporter: Option<&'a [u8]>,
 }
 
 impl KernelState for ExpectTraffic {
@@ -5,18 +5,21 @@ use core::ops::{Deref, DerefMut, Range};
 
 use pki_types::{DnsName, FipsStatus};
 
-use crate::client::EchStatus;
-use crate::conn::{Exporter, KeyingMaterialExporter, ReceivePath, SendOutput, SendPath};
+use crate::client::{ClientSessionKey, EchStatus, ResumptionFailure, ResumptionOffer};
+use crate::conn::{
+    Exporter, KeyingMaterialExporter, ReceivePath, SendOutput, SendPath, TlsOutputBuffer,
+};
 use crate::crypto::cipher::{EncodableVersion, Payload};
 use crate::crypto::kx::SupportedKxGroup;
 use crate::enums::{ApplicationProtocol, ProtocolVersion};
 use crate::error::{AlertDescription, ApiMisuse, Error};
 use crate::hash_hs::HandshakeHash;
-use crate::msgs::{
-    AlertLevel, Codec, Delocator, HandshakeMessagePayload, Locator, Message, MessagePayload,
-};
+use crate::msgs::{Codec, Delocator, HandshakeMessagePayload, Locator, Message, MessagePayload};
+use crate::observer::{ConnectionEvent, ConnectionObserver, Observer};
+use crate::qlog::Qlog;
 use crate::quic::{self, QuicOutput};
 use crate::suites::SupportedCipherSuite;
+use crate::sync::Arc;
 use crate::verify::VerifiedIdentity;
 
 /// Connection state common to both client and server connections.
@@ -37,6 +40,19 @@ impl CommonState {
         }
     }
 
+    /// Report events on this connection to `observer`.
+    pub(crate) fn set_observer(&mut self, observer: Option<&Arc<dyn ConnectionObserver>>) {
+        let observer = Observer::new(observer);
+        self.send.observer = observer.clone();
+        self.recv.observer = observer;
+    }
+
+    /// Record a trace of this connection to `qlog`.
+    pub(crate) fn set_qlog(&mut self, qlog: Qlog) {
+        self.send.qlog = qlog.clone();
+        self.recv.qlog = qlog;
+    }
+
     pub(crate) fn early_exporter(&mut self) -> Result<KeyingMaterialExporter, Error> {
         match self.early_exporter.take() {
             Some(inner) => Ok(KeyingMaterialExporter { inner }),
@@ -50,7 +66,7 @@ impl CommonState {
     /// `close_notify` or fatal alert was already sent.
     ///
     /// [`Connection::write_tls`]: crate::Connection::write_tls
-    pub fn send_close_notify(&mut self, tls: &mut Vec<u8>) {
+    pub fn send_close_notify(&mut self, tls: &mut dyn TlsOutputBuffer) -> Result<(), Error> {
         self.send.send_close_notify(tls)
     }
 
@@ -97,8 +113,11 @@ pub struct ConnectionOutputs {
     alpn_protocol: Option<ApplicationProtocol<'static>>,
     peer_identity: Option<VerifiedIdentity<'static>>,
     extended_main_secret: Option<bool>,
+    peer_application_settings: Option<Vec<u8>>,
     pub(crate) exporter: Option<Box<dyn Exporter>>,
     pub(crate) early_exporter: Option<Box<dyn Exporter>>,
+    /// Whether this is a DTLS connection; see [`Self::protocol_version()`].
+    pub(crate) dtls: bool,
 }
 
 impl ConnectionOutputs {
@@ -123,6 +142,23 @@ impl ConnectionOutputs {
         self.alpn_protocol.as_ref()
     }
 
+    /// Retrieves the application settings sent by the peer via ALPS.
+    ///
+    /// These apply to the protocol in [`ConnectionOutputs::alpn_protocol()`].
+    /// See [`ApplicationSettings`] for more information.
+    ///
+    /// For clients sending early data, this is initially the server's settings
+    /// remembered from the resumed session.  If the server accepts the early data,
+    /// it must send the same settings again.
+    ///
+    /// Returns `None` if ALPS was not negotiated, or until the settings are known.
+    ///
+    /// [`ApplicationSettings`]: crate::enums::ApplicationSettings
+    pub fn peer_application_settings(&self) -> Option<&[u8]> {
+        self.peer_application_settings
+            .as_deref()
+    }
+
     /// Retrieves the cipher suite agreed with the peer.
     ///
     /// This returns None until the cipher suite is agreed.
@@ -145,9 +181,13 @@ impl ConnectionOutputs {
 
     /// Retrieves the protocol version agreed with the peer.
     ///
-    /// This returns `None` until the version is agreed.
+    /// This returns `None` until the version is agreed.  DTLS connections, which
+    /// run the TLS1.3 handshake, report [`ProtocolVersion::DTLSv1_3`].
     pub fn protocol_version(&self) -> Option<ProtocolVersion> {
-        self.negotiated_version
+        match self.negotiated_version {
+            Some(ProtocolVersion::TLSv1_3) if self.dtls => Some(ProtocolVersion::DTLSv1_3),
+            version => version,
+        }
     }
 
     /// Whether the Extended Main Secret extension was negotiated.
@@ -199,6 +239,9 @@ impl ConnectionOutput for ConnectionOutputs {
                 assert!(self.handshake_kind.is_none());
                 self.handshake_kind = Some(hk);
             }
+            OutputEvent::PeerApplicationSettings(settings) => {
+                self.peer_application_settings = settings
+            }
             OutputEvent::KeyExchangeGroup(kxg) => {
                 assert!(self.negotiated_kx_group.is_none());
                 self.negotiated_kx_group = Some(kxg);
@@ -221,8 +264,10 @@ impl fmt::Debug for ConnectionOutputs {
             alpn_protocol,
             peer_identity,
             extended_main_secret,
+            peer_application_settings,
             exporter: _,
             early_exporter: _,
+            dtls: _,
         } = self;
         f.debug_struct("ConnectionOutputs")
             .field("negotiated_version", negotiated_version)
@@ -232,16 +277,21 @@ impl fmt::Debug for ConnectionOutputs {
             .field("alpn_protocol", alpn_protocol)
             .field("peer_identity", peer_identity)
             .field("extended_main_secret", extended_main_secret)
+            .field("peer_application_settings", peer_application_settings)
             .finish_non_exhaustive()
     }
 }
 
 /// Send an alert via `output` if `error` specifies one.
-pub(crate) fn maybe_send_fatal_alert(send: &mut dyn SendOutput, error: &Error, tls: &mut Vec<u8>) {
+pub(crate) fn maybe_send_fatal_alert(
+    send: &mut dyn SendOutput,
+    error: &Error,
+    tls: &mut dyn TlsOutputBuffer,
+) {
     let Ok(alert) = AlertDescription::try_from(error) else {
         return;
     };
-    send.send_alert(AlertLevel::Fatal, alert, tls);
+    send.send_fatal_alert(alert, error, tls);
 }
 
 /// Describes which sort of handshake happened.
@@ -282,7 +332,8 @@ pub(crate) trait Output<'m> {
 
     fn output(&mut self, ev: OutputEvent<'_>);
 
-    fn send_msg(&mut self, m: Message<'_>, must_encrypt: bool);
+    /// Send a handshake message (or an alert), failing if it does not fit in the output.
+    fn send_msg(&mut self, m: Message<'_>, must_encrypt: bool) -> Result<(), Error>;
 
     fn quic(&mut self) -> Option<&mut dyn QuicOutput> {
         None
@@ -295,6 +346,10 @@ pub(crate) trait Output<'m> {
     fn receive(&mut self) -> &mut ReceivePath;
 
     fn send(&mut self) -> &mut dyn SendOutput;
+
+    fn observe(&mut self, event: ConnectionEvent<'_>) {
+        self.receive().observer.observe(event);
+    }
 }
 
 pub(crate) trait ConnectionOutput {
@@ -308,6 +363,7 @@ pub(crate) enum Event<'a> {
     EchStatus(EchStatus),
     ReceivedServerName(Option<DnsName<'static>>),
     ResumptionData(Vec<u8>),
+    Resumption(ResumptionEvent),
 }
 
 pub(crate) enum OutputEvent<'a> {
@@ -318,6 +374,7 @@ pub(crate) enum OutputEvent<'a> {
     ExtendedMainSecret(bool),
     HandshakeKind(HandshakeKind),
     KeyExchangeGroup(&'static dyn SupportedKxGroup),
+    PeerApplicationSettings(Option<Vec<u8>>),
     PeerIdentity(VerifiedIdentity<'static>),
     ProtocolVersion(ProtocolVersion),
 }
@@ -335,6 +392,19 @@ pub(crate) enum EarlyDataEvent {
     Rejected,
 }
 
+pub(crate) enum ResumptionEvent {
+    /// client: looked up a stored session, failing for the given reason
+    Lookup(ClientSessionKey<'static>, Option<ResumptionFailure>),
+    /// client: offered a stored session in the `ClientHello`
+    Offered(ResumptionOffer),
+    /// client: held a stored session, but could not offer it
+    NotOffered(ResumptionFailure),
+    /// client: the server accepted our offer
+    Accepted,
+    /// client: the server did not accept our offer
+    Rejected(ResumptionFailure),
+}
+
 /// Lifetime-erased equivalent to [`Payload`]
 ///
 /// Stores an index into [`Payload`] buffer enabling in-place decryption
@@ -392,6 +462,8 @@ pub enum Protocol {
     Tcp,
     /// QUIC, standardized in RFC 9001
     Quic(quic::Version),
+    /// DTLS 1.3, standardized in RFC 9147
+    Dtls,
 }
 
 impl Protocol {
@@ -399,12 +471,40 @@ impl Protocol {
         matches!(self, Self::Quic(_))
     }
 
+    pub(crate) fn is_dtls(&self) -> bool {
+        matches!(self, Self::Dtls)
+    }
+
+    /// Whether TLS records are replaced by a datagram-oriented layer.
+    ///
+    /// Both QUIC and DTLS protect handshake messages themselves, and
+    /// omit the TLS1.3 middlebox compatibility and `EndOfEarlyData` messages.
+    pub(crate) fn is_datagram(&self) -> bool {
+        matches!(self, Self::Quic(_) | Self::Dtls)
+    }
+
     pub(crate) fn supports_version(&self, version: ProtocolVersion) -> bool {
         match self {
-            Self::Quic(_) => version == ProtocolVersion::TLSv1_3,
+            Self::Quic(_) | Self::Dtls => version == ProtocolVersion::TLSv1_3,
             Self::Tcp => true,
         }
     }
+
+    /// The `legacy_version` used in `ClientHello` and `ServerHello` messages.
+    pub(crate) fn legacy_version(&self) -> ProtocolVersion {
+        match self {
+            Self::Dtls => ProtocolVersion::DTLSv1_2,
+            Self::Tcp | Self::Quic(_) => ProtocolVersion::TLSv1_2,
+        }
+    }
+
+    /// How TLS1.3 is identified in the `supported_versions` extension.
+    pub(crate) fn tls13_version(&self) -> ProtocolVersion {
+        match self {
+            Self::Dtls => ProtocolVersion::DTLSv1_3,
+            Self::Tcp | Self::Quic(_) => ProtocolVersion::TLSv1_3,
+        }
+    }
 }
 
 pub(crate) struct HandshakeFlight<'a, const TLS13: bool> {
@@ -427,7 +527,7 @@ impl<'a, const TLS13: bool> HandshakeFlight<'a, TLS13> {
             .add(&self.body[start_len..]);
     }
 
-    pub(crate) fn finish(self, output: &mut dyn Output<'_>) {
+    pub(crate) fn finish(self, output: &mut dyn Output<'_>) -> Result<(), Error> {
         let m = Message {
             version: EncodableVersion::Legacy(match TLS13 {
                 true => ProtocolVersion::TLSv1_3,
@@ -436,7 +536,7 @@ impl<'a, const TLS13: bool> HandshakeFlight<'a, TLS13> {
             payload: MessagePayload::HandshakeFlight(Payload::new(self.body)),
         };
 
-        output.send_msg(m, TLS13);
+        output.send_msg(m, TLS13)
     }
 }
 
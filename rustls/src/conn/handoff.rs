@@ -0,0 +1,415 @@
+//! Serialization of established connections, so they can be handed to another process.
+//!
+//! A serialized connection is sealed with a [`TicketProducer`], so it is both
+//! confidential and authenticated.  The format is private to this module, and
+//! carries a version number inside the sealed plaintext.
+
+use alloc::vec::Vec;
+
+use pki_types::{FipsStatus, ServerName};
+use zeroize::Zeroizing;
+
+use super::{
+    ConnectionCommon, ConnectionRandoms, ReceivePath, SendPath, SideCommonOutput, SideData,
+    StateMachine,
+};
+use crate::common_state::{
+    CommonState, ConnectionOutputs, HandshakeKind, Output, OutputEvent, Side,
+};
+use crate::crypto::cipher::Payload;
+use crate::crypto::kx::NamedGroup;
+use crate::crypto::tls13::OkmBlock;
+use crate::crypto::{CipherSuite, CryptoProvider, Identity, TicketProducer};
+use crate::enums::{ApplicationProtocol, ProtocolVersion};
+use crate::error::{ApiMisuse, Error, InvalidMessage};
+use crate::msgs::{Codec, HandshakeAlignedProof, MaybeEmpty, Reader, SizedPayload};
+use crate::suites::SupportedCipherSuite;
+use crate::verify::VerifiedIdentity;
+
+/// The version of the serialization format.
+///
+/// A serialized connection can only be restored by a build of rustls with the same version.
+const FORMAT_VERSION: u16 = 4;
+
+impl<Side: SideData> ConnectionCommon<Side> {
+    pub(crate) fn dangerous_serialize(self, sealer: &dyn TicketProducer) -> Result<Vec<u8>, Error> {
+        if self.common.is_handshaking() {
+            return Err(Error::HandshakeNotComplete);
+        }
+
+        serialize::<Side>(
+            &self.state?,
+            &self.common.send,
+            &self.common.recv,
+            &self.common.outputs,
+            &self.side,
+            sealer,
+        )
+    }
+
+    /// Restore a connection serialized by [`serialize()`].
+    ///
+    /// `restore` builds the state machine from the connection's secrets, and
+    /// installs its traffic keys.
+    pub(crate) fn dangerous_restore(
+        sealed: &[u8],
+        sealer: &dyn TicketProducer,
+        provider: &CryptoProvider,
+        fips: FipsStatus,
+        restore: impl FnOnce(Restored<'_>, &mut dyn Output<'_>) -> Result<Side::State, Error>,
+    ) -> Result<Self, Error> {
+        let plain = Zeroizing::new(
+            sealer
+                .decrypt(sealed)
+                .ok_or(Error::DecryptError)?,
+        );
+
+        Reader::new(&plain).all("SerializedConnection", |r| {
+            if u16::read(r)? != FORMAT_VERSION || read_side(r)? != Side::SIDE {
+                return Err(ApiMisuse::IncompatibleSerializedConnection.into());
+            }
+
+            let mut data = Side::Data::read(r)?;
+            let mut common = CommonState::new(Side::SIDE, fips);
+            let Some(proof) = common.recv.deframer.aligned() else {
+                return Err(Error::Unreachable("new deframer is not aligned"));
+            };
+
+            let version = ProtocolVersion::read(r)?;
+            let suite = find_suite(version, CipherSuite::read(r)?, provider)?;
+            let kind = read_handshake_kind(r)?;
+            let group = read_optional::<NamedGroup>(r)?.and_then(|name| {
+                provider
+                    .kx_groups
+                    .iter()
+                    .find(|group| group.name() == name)
+                    .copied()
+            });
+            let alpn = read_optional::<ApplicationProtocol<'_>>(r)?;
+            let peer_identity = read_optional::<Identity<'_>>(r)?
+                .map(|identity| VerifiedIdentity::assertion(identity.into_owned()));
+            let ems = match u8::read(r)? {
+                0 => None,
+                value => Some(value == 2),
+            };
+            let peer_application_settings =
+                read_optional::<SizedPayload<'_, u16, MaybeEmpty>>(r)?.map(|s| s.into_vec());
+            let secrets = HandoffSecrets::read(r, suite)?;
+            let exporter = match u8::read(r)? {
+                1 => Some(take_secret(r, suite)?),
+                _ => None,
+            };
+
+            let mut tls = Vec::new();
+            let mut output = SideCommonOutput {
+                side: &mut data,
+                quic: None,
+                common: &mut common,
+                tls: &mut tls,
+            };
+            output.output(OutputEvent::ProtocolVersion(version));
+            output.output(OutputEvent::CipherSuite(suite));
+            output.output(OutputEvent::HandshakeKind(kind));
+            if let Some(group) = group {
+                output.output(OutputEvent::KeyExchangeGroup(group));
+            }
+            if let Some(alpn) = alpn {
+                output.output(OutputEvent::ApplicationProtocol(alpn));
+            }
+            if let Some(identity) = &peer_identity {
+                output.output(OutputEvent::PeerIdentity(identity.clone()));
+            }
+            if let Some(ems) = ems {
+                output.output(OutputEvent::ExtendedMainSecret(ems));
+            }
+            if peer_application_settings.is_some() {
+                output.output(OutputEvent::PeerApplicationSettings(
+                    peer_application_settings,
+                ));
+            }
+
+            let restored = Restored {
+                suite,
+                secrets,
+                exporter,
+                peer_identity,
+                proof,
+            };
+            let state = restore(restored, &mut output)?;
+
+            // nb. after `restore`, which resets the sequence numbers when installing keys.
+            common.send.read_handoff(r)?;
+            common
+                .recv
+                .decrypt_state
+                .restore_read_seq(u64::read(r)?);
+            common.recv.has_received_close_notify = matches!(u8::read(r)?, 1);
+            common.recv.tls13_tickets_received = u32::read(r)?;
+
+            Ok(Self::new(state, data, common))
+        })
+    }
+}
+
+/// Serialize and seal the post-handshake state of a connection.
+pub(crate) fn serialize<Side: SideData>(
+    state: &Side::State,
+    send: &SendPath,
+    recv: &ReceivePath,
+    outputs: &ConnectionOutputs,
+    data: &Side::Data,
+    sealer: &dyn TicketProducer,
+) -> Result<Vec<u8>, Error> {
+    if recv.deframer.is_active() {
+        return Err(ApiMisuse::SerializeWithPartialMessage.into());
+    }
+
+    let (Some(version), Some(suite), Some(kind)) = (
+        outputs.protocol_version(),
+        outputs.negotiated_cipher_suite(),
+        outputs.handshake_kind(),
+    ) else {
+        return Err(Error::HandshakeNotComplete);
+    };
+    let secrets = state.handoff_secrets(&send.tls13_key_schedule)?;
+
+    let mut plain = Zeroizing::new(Vec::new());
+    FORMAT_VERSION.encode(&mut plain);
+    encode_side(Side::SIDE, &mut plain);
+    data.encode(&mut plain);
+
+    version.encode(&mut plain);
+    suite.suite().encode(&mut plain);
+    encode_handshake_kind(kind, &mut plain);
+    encode_optional(
+        outputs
+            .negotiated_key_exchange_group()
+            .map(|group| group.name())
+            .as_ref(),
+        &mut plain,
+    );
+    encode_optional(outputs.alpn_protocol(), &mut plain);
+    encode_optional(
+        outputs
+            .peer_identity()
+            .map(|identity| &**identity),
+        &mut plain,
+    );
+    plain.push(match outputs.extended_main_secret() {
+        None => 0,
+        Some(false) => 1,
+        Some(true) => 2,
+    });
+    encode_optional(
+        outputs
+            .peer_application_settings()
+            .map(|s| SizedPayload::<u16, MaybeEmpty>::from(Payload::Borrowed(s)))
+            .as_ref(),
+        &mut plain,
+    );
+    secrets.encode(&mut plain);
+    match &outputs.exporter {
+        Some(exporter) => {
+            plain.push(1);
+            plain.extend_from_slice(exporter.secret());
+        }
+        None => plain.push(0),
+    }
+
+    send.encode_handoff(&mut plain);
+    recv.decrypt_state
+        .read_seq()
+        .encode(&mut plain);
+    plain.push(recv.has_received_close_notify as u8);
+    recv.tls13_tickets_received
+        .encode(&mut plain);
+
+    sealer
+        .encrypt(&plain)
+        .ok_or(Error::EncryptError)
+}
+
+/// The parts of a serialized connection needed to restore its state machine.
+pub(crate) struct Restored<'a> {
+    pub(crate) suite: SupportedCipherSuite,
+    pub(crate) secrets: HandoffSecrets,
+    /// The exporter secret, if the exporter had not been used.
+    ///
+    /// For TLS1.2 this is the master secret, which is also in `secrets`.
+    pub(crate) exporter: Option<&'a [u8]>,
+    pub(crate) peer_identity: Option<VerifiedIdentity<'static>>,
+    pub(crate) proof: HandshakeAlignedProof,
+}
+
+/// The secrets held by a connection's state machine once the handshake is complete.
+pub(crate) enum HandoffSecrets {
+    Tls12 {
+        randoms: ConnectionRandoms,
+        master_secret: Zeroizing<[u8; 48]>,
+    },
+    Tls13 {
+        tx: OkmBlock,
+        rx: OkmBlock,
+        /// The `resumption_master_secret`, server name and data to store with
+        /// new tickets, for clients only.
+        resumption: Option<(OkmBlock, ServerName<'static>, Vec<u8>)>,
+    },
+}
+
+impl HandoffSecrets {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        match self {
+            Self::Tls12 {
+                randoms,
+                master_secret,
+            } => {
+                bytes.extend_from_slice(&randoms.client);
+                bytes.extend_from_slice(&randoms.server);
+                bytes.extend_from_slice(&**master_secret);
+            }
+            Self::Tls13 { tx, rx, resumption } => {
+                bytes.extend_from_slice(tx.as_ref());
+                bytes.extend_from_slice(rx.as_ref());
+                match resumption {
+                    Some((secret, server_name, data)) => {
+                        bytes.push(1);
+                        bytes.extend_from_slice(secret.as_ref());
+                        encode_str(&server_name.to_str(), bytes);
+                        SizedPayload::<u16>::from(Payload::Borrowed(data)).encode(bytes);
+                    }
+                    None => bytes.push(0),
+                }
+            }
+        }
+    }
+
+    fn read(r: &mut Reader<'_>, suite: SupportedCipherSuite) -> Result<Self, InvalidMessage> {
+        Ok(match suite {
+            SupportedCipherSuite::Tls12(_) => Self::Tls12 {
+                randoms: ConnectionRandoms {
+                    client: *r.take_array("Random")?,
+                    server: *r.take_array("Random")?,
+                },
+                master_secret: Zeroizing::new(*r.take_array("MasterSecret")?),
+            },
+            SupportedCipherSuite::Tls13(_) => Self::Tls13 {
+                tx: OkmBlock::new(take_secret(r, suite)?),
+                rx: OkmBlock::new(take_secret(r, suite)?),
+                resumption: match u8::read(r)? {
+                    1 => {
+                        let secret = OkmBlock::new(take_secret(r, suite)?);
+                        let server_name = ServerName::try_from(read_str(r)?)
+                            .map_err(|_| InvalidMessage::InvalidServerName)?;
+                        let data = SizedPayload::<u16>::read(r)?.into_vec();
+                        Some((secret, server_name.to_owned(), data))
+                    }
+                    _ => None,
+                },
+            },
+        })
+    }
+}
+
+/// Connection data specific to one side, which is carried by a serialized connection.
+pub(crate) trait HandoffData: Default + Sized {
+    fn encode(&self, bytes: &mut Vec<u8>);
+
+    fn read(r: &mut Reader<'_>) -> Result<Self, InvalidMessage>;
+}
+
+pub(crate) fn encode_str(value: &str, bytes: &mut Vec<u8>) {
+    SizedPayload::<u16>::from(Payload::Borrowed(value.as_bytes())).encode(bytes);
+}
+
+pub(crate) fn read_str<'a>(r: &mut Reader<'a>) -> Result<&'a str, InvalidMessage> {
+    let len = usize::from(u16::read(r)?);
+    let bytes = r
+        .take(len)
+        .ok_or(InvalidMessage::MessageTooShort)?;
+    core::str::from_utf8(bytes).map_err(|_| InvalidMessage::InvalidServerName)
+}
+
+fn encode_optional<'a, T: Codec<'a>>(value: Option<&T>, bytes: &mut Vec<u8>) {
+    match value {
+        Some(value) => {
+            bytes.push(1);
+            value.encode(bytes);
+        }
+        None => bytes.push(0),
+    }
+}
+
+fn read_optional<'a, T: Codec<'a>>(r: &mut Reader<'a>) -> Result<Option<T>, InvalidMessage> {
+    Ok(match u8::read(r)? {
+        1 => Some(T::read(r)?),
+        _ => None,
+    })
+}
+
+/// Take a secret of the length used by `suite`.
+fn take_secret<'a>(
+    r: &mut Reader<'a>,
+    suite: SupportedCipherSuite,
+) -> Result<&'a [u8], InvalidMessage> {
+    let len = match suite {
+        SupportedCipherSuite::Tls12(_) => 48,
+        SupportedCipherSuite::Tls13(_) => suite.hash_provider().output_len(),
+    };
+    r.take(len)
+        .ok_or(InvalidMessage::MissingData("Secret"))
+}
+
+fn find_suite(
+    version: ProtocolVersion,
+    suite: CipherSuite,
+    provider: &CryptoProvider,
+) -> Result<SupportedCipherSuite, Error> {
+    let found = match version {
+        ProtocolVersion::TLSv1_2 => provider
+            .tls12_cipher_suites
+            .iter()
+            .find(|s| s.common.suite == suite)
+            .map(|s| SupportedCipherSuite::Tls12(s)),
+        ProtocolVersion::TLSv1_3 => provider
+            .tls13_cipher_suites
+            .iter()
+            .find(|s| s.common.suite == suite)
+            .map(|s| SupportedCipherSuite::Tls13(s)),
+        _ => return Err(ApiMisuse::IncompatibleSerializedConnection.into()),
+    };
+    found.ok_or_else(|| ApiMisuse::ResumingFromUnknownCipherSuite(suite).into())
+}
+
+fn encode_side(side: Side, bytes: &mut Vec<u8>) {
+    bytes.push(match side {
+        Side::Client => 0,
+        Side::Server => 1,
+    });
+}
+
+fn read_side(r: &mut Reader<'_>) -> Result<Side, Error> {
+    match u8::read(r)? {
+        0 => Ok(Side::Client),
+        1 => Ok(Side::Server),
+        _ => Err(ApiMisuse::IncompatibleSerializedConnection.into()),
+    }
+}
+
+fn encode_handshake_kind(kind: HandshakeKind, bytes: &mut Vec<u8>) {
+    bytes.push(match kind {
+        HandshakeKind::Full => 0,
+        HandshakeKind::FullWithHelloRetryRequest => 1,
+        HandshakeKind::Resumed => 2,
+        HandshakeKind::ResumedWithHelloRetryRequest => 3,
+    });
+}
+
+fn read_handshake_kind(r: &mut Reader<'_>) -> Result<HandshakeKind, Error> {
+    match u8::read(r)? {
+        0 => Ok(HandshakeKind::Full),
+        1 => Ok(HandshakeKind::FullWithHelloRetryRequest),
+        2 => Ok(HandshakeKind::Resumed),
+        3 => Ok(HandshakeKind::ResumedWithHelloRetryRequest),
+        _ => Err(ApiMisuse::IncompatibleSerializedConnection.into()),
+    }
+}
@@ -1,5 +1,4 @@
 use alloc::boxed::Box;
-use alloc::vec::Vec;
 use core::fmt::{self, Debug};
 use core::ops::{Deref, DerefMut};
 
@@ -19,6 +18,9 @@ use crate::suites::{ExtractedSecrets, PartiallyExtractedSecrets};
 use crate::sync::Arc;
 use crate::tls13::key_schedule::KeyScheduleTrafficSend;
 
+mod handoff;
+pub(crate) use handoff::{HandoffData, HandoffSecrets, Restored, encode_str, read_str};
+
 // pub so that it can be re-exported from the crate root
 pub mod kernel;
 
@@ -27,7 +29,8 @@ pub(crate) use receive::{Input, MessageIter, ReceivePath, TrafficTemperCounters}
 pub use receive::{SliceInput, TlsInputBuffer, VecInput};
 
 mod send;
-pub(crate) use send::{SendOutput, SendPath};
+pub use send::{RecordPadder, RecordPadding, TlsOutputBuffer};
+pub(crate) use send::{SendOutput, SendPath, TrackedOutput};
 
 pub(crate) mod split;
 use split::SplitConnection;
@@ -42,7 +45,15 @@ pub trait Connection: Debug + Deref<Target = ConnectionOutputs> {
     /// This will fail if either the handshake is not complete yet (because we don't yet have the
     /// keys to encrypt application data) or if the send path has been closed by sending a
     /// `close_notify` alert.
-    fn write_tls(&mut self, plaintext: OutboundPlain<'_>, tls: &mut Vec<u8>) -> Result<(), Error>;
+    ///
+    /// If `tls` does not have room for the resulting records, nothing is written and
+    /// [`Error::InsufficientOutputSpace`] is returned; the call can be retried with a
+    /// larger buffer.
+    fn write_tls(
+        &mut self,
+        plaintext: OutboundPlain<'_>,
+        tls: &mut dyn TlsOutputBuffer,
+    ) -> Result<(), Error>;
 
     /// Returns true if the caller should call [`Self::process_new_packets()`] as soon as possible.
     fn wants_read(&self) -> bool;
@@ -51,7 +62,7 @@ pub trait Connection: Debug + Deref<Target = ConnectionOutputs> {
     fn process_new_packets<'a, 'm>(
         &'a mut self,
         input: &'m mut dyn TlsInputBuffer,
-        tls: &'a mut Vec<u8>,
+        tls: &'a mut dyn TlsOutputBuffer,
     ) -> MessageHandler<'a, 'm, Self::Side>;
 
     /// Returns an object that can derive key material from the agreed connection secrets.
@@ -99,14 +110,17 @@ pub trait Connection: Debug + Deref<Target = ConnectionOutputs> {
     ///
     /// rustls only allows one outstanding request at a time; this function succeeds
     /// but sends nothing if a request is already in-flight.
-    fn refresh_traffic_keys(&mut self, tls: &mut Vec<u8>) -> Result<(), Error>;
+    fn refresh_traffic_keys(&mut self, tls: &mut dyn TlsOutputBuffer) -> Result<(), Error>;
 
     /// Writes a `close_notify` warning alert into `tls`.
     ///
     /// This informs the peer that the connection is being closed.
     ///
     /// Does nothing if any `close_notify` or fatal alert was already sent.
-    fn send_close_notify(&mut self, tls: &mut Vec<u8>);
+    ///
+    /// If `tls` does not have room for the alert, nothing is written and
+    /// [`Error::InsufficientOutputSpace`] is returned.
+    fn send_close_notify(&mut self, tls: &mut dyn TlsOutputBuffer) -> Result<(), Error>;
 
     /// Returns true if the connection is currently performing the TLS handshake.
     ///
@@ -151,7 +165,7 @@ impl<Side: SideData> ConnectionCommon<Side> {
     pub(crate) fn process_new_packets<'a, 'm>(
         &'a mut self,
         input: &'m mut dyn TlsInputBuffer,
-        tls: &'a mut Vec<u8>,
+        tls: &'a mut dyn TlsOutputBuffer,
     ) -> MessageHandler<'a, 'm, Side> {
         MessageHandler::new(input, tls, self)
     }
@@ -159,7 +173,7 @@ impl<Side: SideData> ConnectionCommon<Side> {
     pub(crate) fn write_tls(
         &mut self,
         plaintext: OutboundPlain<'_>,
-        tls: &mut Vec<u8>,
+        tls: &mut dyn TlsOutputBuffer,
     ) -> Result<(), Error> {
         if plaintext.is_empty() {
             return Ok(());
@@ -175,7 +189,7 @@ impl<Side: SideData> ConnectionCommon<Side> {
 
         self.common
             .send
-            .send_appdata_encrypt(plaintext, tls);
+            .send_appdata_encrypt(plaintext, tls)?;
 
         Ok(())
     }
@@ -189,7 +203,10 @@ impl<Side: SideData> ConnectionCommon<Side> {
             .has_received_close_notify
     }
 
-    pub(crate) fn refresh_traffic_keys(&mut self, tls: &mut Vec<u8>) -> Result<(), Error> {
+    pub(crate) fn refresh_traffic_keys(
+        &mut self,
+        tls: &mut dyn TlsOutputBuffer,
+    ) -> Result<(), Error> {
         self.common
             .send
             .refresh_traffic_keys(tls)
@@ -263,21 +280,31 @@ impl ConnectionCommon<ServerSide> {
         exts: ServerExtensionsInput,
         quic: Option<&mut dyn QuicOutput>,
         config: Arc<ServerConfig>,
-        tls: &mut Vec<u8>,
+        tls: &mut dyn TlsOutputBuffer,
     ) -> Result<(), Error> {
         self.common
             .send
             .set_max_fragment_size(config.max_fragment_size)?;
+        self.common
+            .send
+            .set_record_padding(config.record_padding.clone());
+        self.common
+            .set_observer(config.observer.as_ref());
+        self.common
+            .set_qlog(config.start_qlog());
         self.common.fips = config.fips();
 
+        let mut tls = TrackedOutput::new(tls);
         let mut output = SideCommonOutput {
             side: &mut self.side,
             quic,
             common: &mut self.common,
-            tls,
+            tls: &mut tls,
         };
 
-        self.state = Ok(choose.use_config(config, exts, &mut output)?);
+        let state = choose.use_config(config, exts, &mut output)?;
+        tls.result()?;
+        self.state = Ok(state);
         Ok(())
     }
 }
@@ -312,7 +339,7 @@ pub struct MessageHandler<'a, 'm, Side: SideData> {
 impl<'a, 'm, Side: SideData> MessageHandler<'a, 'm, Side> {
     pub(crate) fn new(
         input: &'m mut dyn TlsInputBuffer,
-        tls: &'a mut Vec<u8>,
+        tls: &'a mut dyn TlsOutputBuffer,
         core: &'a mut ConnectionCommon<Side>,
     ) -> Self {
         Self {
@@ -334,9 +361,13 @@ impl<'a, 'm, Side: SideData> MessageHandler<'a, 'm, Side> {
     /// [`Connection::send_close_notify()`]. Any alert produced by the error will have
     /// been appended to the `tls` buffer; most likely you will want to send that data
     /// to the peer and then close the underlying connection.
-    pub fn handle_all(mut self, buf: &mut Vec<u8>) -> Result<IoState, Error> {
+    ///
+    /// If `buf` runs out of space, [`Error::InsufficientOutputSpace`] is returned and the
+    /// plaintext that did not fit is lost.  Use [`Self::next_payload()`] to receive
+    /// plaintext without copying it.
+    pub fn handle_all(mut self, buf: &mut dyn TlsOutputBuffer) -> Result<IoState, Error> {
         while let Some(result) = self.next_payload() {
-            buf.extend_from_slice(result?.bytes());
+            buf.extend_from_slice(result?.bytes())?;
         }
 
         Ok(self.state())
@@ -453,9 +484,12 @@ pub(crate) trait Exporter: Send + Sync {
     ///
     /// Must fill in `output` entirely, or return an error.
     fn derive(&self, label: &[u8], context: Option<&[u8]>, output: &mut [u8]) -> Result<(), Error>;
+
+    /// Returns the base secret, so it can be carried by a serialized connection.
+    fn secret(&self) -> &[u8];
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub(crate) struct ConnectionRandoms {
     pub(crate) client: [u8; 32],
     pub(crate) server: [u8; 32],
@@ -500,7 +534,7 @@ pub(crate) struct SideCommonOutput<'a, 'q> {
     pub(crate) side: &'a mut dyn SideOutput,
     pub(crate) quic: Option<&'q mut dyn QuicOutput>,
     pub(crate) common: &'a mut CommonState,
-    pub(crate) tls: &'a mut Vec<u8>,
+    pub(crate) tls: &'a mut dyn TlsOutputBuffer,
 }
 
 impl<'q> Output<'_> for SideCommonOutput<'_, 'q> {
@@ -509,6 +543,10 @@ impl<'q> Output<'_> for SideCommonOutput<'_, 'q> {
     }
 
     fn output(&mut self, ev: OutputEvent<'_>) {
+        self.common
+            .recv
+            .qlog
+            .parameters_set(&ev);
         if let OutputEvent::ProtocolVersion(ver) = ev {
             self.common.recv.negotiated_version = Some(ver);
             self.common.send.negotiated_version(ver);
@@ -516,9 +554,12 @@ impl<'q> Output<'_> for SideCommonOutput<'_, 'q> {
         self.common.outputs.handle(ev);
     }
 
-    fn send_msg(&mut self, m: Message<'_>, must_encrypt: bool) {
+    fn send_msg(&mut self, m: Message<'_>, must_encrypt: bool) -> Result<(), Error> {
         match self.quic() {
-            Some(quic) => quic.send_msg(m, must_encrypt),
+            Some(quic) => {
+                quic.send_msg(m, must_encrypt);
+                Ok(())
+            }
             None => self
                 .common
                 .send
@@ -560,9 +601,11 @@ pub(crate) mod private {
 
     pub(crate) trait Side: Debug {
         /// Data storage type.
-        type Data: SideOutput;
+        type Data: SideOutput + HandoffData;
         /// State machine type.
         type State: StateMachine;
+        /// Which side of the connection this is.
+        const SIDE: crate::common_state::Side;
     }
 
     pub(crate) trait SideOutput {
@@ -582,7 +625,7 @@ pub(crate) trait StateMachine: Sized {
     /// Advance the state machine using no input if possible.
     ///
     /// This should return `Ok(self)` otherwise.
-    fn handle_without_input(self) -> Result<Self, Error>;
+    fn handle_without_input<'m>(self, output: &mut dyn Output<'m>) -> Result<Self, Error>;
 
     fn is_traffic(&self) -> bool;
     fn handle_decrypt_error(&mut self);
@@ -590,4 +633,10 @@ pub(crate) trait StateMachine: Sized {
         self,
         send_keys: &Option<Box<KeyScheduleTrafficSend>>,
     ) -> Result<(PartiallyExtractedSecrets, Box<dyn KernelState + 'static>), Error>;
+
+    /// Return the secrets needed to restore this state in another process.
+    fn handoff_secrets(
+        &self,
+        send_keys: &Option<Box<KeyScheduleTrafficSend>>,
+    ) -> Result<HandoffSecrets, Error>;
 }
@@ -4,7 +4,7 @@ use core::mem;
 use core::ops::Range;
 use std::io::{self, Read};
 
-use super::send::{SendOutput, SendPath};
+use super::send::{SendOutput, SendPath, TlsOutputBuffer, TrackedOutput};
 use super::split::SendAdapter;
 use crate::SideData;
 use crate::common_state::{
@@ -21,12 +21,14 @@ use crate::msgs::{
     AlertLevel, AlertLevelName, AlertMessagePayload, Deframed, Deframer, Delocator,
     HandshakeAlignedProof, Locator, Message, MessagePayload,
 };
+use crate::observer::{ConnectionEvent, Observer};
+use crate::qlog::Qlog;
 use crate::quic::QuicOutput;
 use crate::tracing::{trace, warn};
 
 pub(crate) struct MessageIter<'a, 'm, Side: SideData, Send: SendOutput + 'a> {
     pub(super) input: &'m mut dyn TlsInputBuffer,
-    pub(super) tls: &'a mut Vec<u8>,
+    pub(super) tls: &'a mut dyn TlsOutputBuffer,
     pub(super) recv: &'a mut ReceivePath,
     pub(super) state: &'a mut Result<Side::State, Error>,
     pub(super) output: JoinOutput<'a, Send>,
@@ -36,7 +38,7 @@ pub(crate) struct MessageIter<'a, 'm, Side: SideData, Send: SendOutput + 'a> {
 impl<'a, 'm, Side: SideData> MessageIter<'a, 'm, Side, SendPath> {
     pub(crate) fn new(
         input: &'m mut dyn TlsInputBuffer,
-        tls: &'a mut Vec<u8>,
+        tls: &'a mut dyn TlsOutputBuffer,
         quic: Option<&'a mut dyn QuicOutput>,
         conn: &'a mut ConnectionCommon<Side>,
         advance: bool,
@@ -60,7 +62,7 @@ impl<'a, 'm, Side: SideData> MessageIter<'a, 'm, Side, SendPath> {
 impl<'a, 'm, 's, Side: SideData> MessageIter<'a, 'm, Side, SendAdapter<'s>> {
     pub(super) fn receive(
         input: &'m mut dyn TlsInputBuffer,
-        tls: &'a mut Vec<u8>,
+        tls: &'a mut dyn TlsOutputBuffer,
         state: &'a mut Result<Side::State, Error>,
         recv: &'a mut ReceivePath,
         output: JoinOutput<'a, SendAdapter<'s>>,
@@ -87,6 +89,9 @@ impl<'a, 'm, Side: SideData, Send: SendOutput + 'a> MessageIter<'a, 'm, Side, Se
             }
         };
 
+        // Handshake messages that do not fit fail the handshake at once.  Anything
+        // else we fail to write is reported once the current message is handled.
+        let mut tls = TrackedOutput::new(&mut *self.tls);
         let mut plaintext = None;
         while st.wants_input() {
             let buffer = self.input.slice_mut();
@@ -95,7 +100,7 @@ impl<'a, 'm, Side: SideData, Send: SendOutput + 'a> MessageIter<'a, 'm, Side, Se
 
             let mut output = CaptureAppData {
                 recv: self.recv,
-                tls: self.tls,
+                tls: &mut tls,
                 other: &mut self.output,
                 plaintext_locator: &locator,
                 received_plaintext: &mut plaintext,
@@ -133,12 +138,12 @@ impl<'a, 'm, Side: SideData, Send: SendOutput + 'a> MessageIter<'a, 'm, Side, Se
                 && matches!(msg.typ, ContentType::Handshake | ContentType::Alert)
             {
                 // <https://datatracker.ietf.org/doc/html/rfc9846#section-5.4>
-                output.other.send.send_alert(
-                    AlertLevel::Fatal,
+                let error = Error::from(PeerMisbehaved::EmptyFragment);
+                output.other.send.send_fatal_alert(
                     AlertDescription::UnexpectedMessage,
+                    &error,
                     output.tls,
                 );
-                let error = Error::from(PeerMisbehaved::EmptyFragment);
                 *self.state = Err(error.clone());
                 return Some(Err(error));
             }
@@ -164,25 +169,27 @@ impl<'a, 'm, Side: SideData, Send: SendOutput + 'a> MessageIter<'a, 'm, Side, Se
             }
 
             if self.advance && !st.wants_input() {
-                st = match st.handle_without_input() {
+                st = match st.handle_without_input(&mut output) {
                     Ok(st) => st,
                     Err(err) => {
-                        maybe_send_fatal_alert(self.output.send, &err, self.tls);
+                        maybe_send_fatal_alert(output.other.send, &err, output.tls);
                         *self.state = Err(err.clone());
                         return Some(Err(err));
                     }
                 };
             }
 
+            if let Err(e) = tls.result() {
+                *self.state = Err(e.clone());
+                return Some(Err(e));
+            }
+
             if self.recv.has_received_close_notify {
                 // "Any data received after a closure alert has been received MUST be ignored."
                 // -- <https://datatracker.ietf.org/doc/html/rfc9846#section-6.1>
 
-                // First, discard actually-processed bytes.
-                self.input
-                    .discard(self.recv.deframer.take_discard());
-
-                // Then the rest of any input data.
+                // Discard the rest of any input data along with the processed bytes,
+                // once the caller is done with any plaintext borrowed from `input`.
                 let entirety = self.input.slice_mut().len();
                 self.recv.deframer.set_discard(entirety);
                 self.input.received_close_notify();
@@ -193,6 +200,12 @@ impl<'a, 'm, Side: SideData, Send: SendOutput + 'a> MessageIter<'a, 'm, Side, Se
                 *self.state = Ok(st);
                 return Some(Ok(payload));
             }
+
+            // Handshake drivers stop once traffic is established, so that
+            // any following records are left in `input` for the traffic receiver.
+            if !self.advance && st.is_traffic() {
+                break;
+            }
         }
 
         *self.state = Ok(st);
@@ -208,6 +221,12 @@ pub(crate) struct ReceivePath {
     side: Side,
     pub(crate) decrypt_state: DecryptionState,
     pub(crate) may_receive_application_data: bool,
+    /// If a TLS1.2 client will pass a `HelloRequest` to its state machine,
+    /// rather than refusing it.
+    pub(crate) renegotiation_allowed: bool,
+    /// If a TLS1.2 renegotiation is in progress, so every message -- including
+    /// `ChangeCipherSpec` -- is protected by the current keys.
+    pub(crate) renegotiating: bool,
     /// If the peer has signaled end of stream.
     pub(crate) has_received_close_notify: bool,
     temper_counters: TemperCounters,
@@ -219,6 +238,8 @@ pub(crate) struct ReceivePath {
     seen_consecutive_empty_fragments: u8,
 
     pub(crate) tls13_tickets_received: u32,
+    pub(crate) observer: Observer,
+    pub(crate) qlog: Qlog,
 }
 
 impl ReceivePath {
@@ -227,12 +248,16 @@ impl ReceivePath {
             side,
             decrypt_state: DecryptionState::new(),
             may_receive_application_data: false,
+            renegotiation_allowed: false,
+            renegotiating: false,
             has_received_close_notify: false,
             temper_counters: TemperCounters::default(),
             negotiated_version: None,
             deframer: Deframer::default(),
             seen_consecutive_empty_fragments: 0,
             tls13_tickets_received: 0,
+            observer: Observer::default(),
+            qlog: Qlog::default(),
         }
     }
 
@@ -269,6 +294,8 @@ impl ReceivePath {
                 plaintext: message,
                 want_close_before_decrypt: _,
             } = message;
+            self.qlog
+                .record_received(message.typ, message.payload.len());
 
             if self.deframer.aligned().is_none() && message.typ != ContentType::Handshake {
                 // "Handshake messages MUST NOT be interleaved with other record
@@ -329,8 +356,8 @@ impl ReceivePath {
         };
 
         let allowed_plaintext = match message.typ {
-            // CCS messages are always plaintext.
-            ContentType::ChangeCipherSpec => true,
+            // CCS messages are plaintext, except during renegotiation.
+            ContentType::ChangeCipherSpec => !self.renegotiating,
             // Alerts are allowed to be plaintext if-and-only-if:
             // * The negotiated protocol version is TLS 1.3. - In TLS 1.2 it is unambiguous when
             //   keying changes based on the CCS message. Only TLS 1.3 requires these heuristics.
@@ -392,7 +419,7 @@ impl ReceivePath {
         &mut self,
         msg: EncodedMessage<&'a [u8]>,
         aligned_handshake: Option<HandshakeAlignedProof>,
-        tls: &mut Vec<u8>,
+        tls: &mut dyn TlsOutputBuffer,
         send: &mut dyn SendOutput,
     ) -> Result<Option<Input<'a>>, Error> {
         // Drop CCS messages during handshake in TLS1.3
@@ -403,6 +430,10 @@ impl ReceivePath {
 
         // Now we can fully parse the message payload.
         let message = Message::try_from(msg)?;
+        if let MessagePayload::Handshake { parsed, encoded } = &message.payload {
+            self.qlog
+                .message_received(parsed, encoded.bytes().len());
+        }
 
         // For alerts, we have separate logic.
         if let MessagePayload::Alert(alert) = &message.payload {
@@ -411,7 +442,7 @@ impl ReceivePath {
         }
 
         // For TLS1.2, outside of the handshake, send rejection alerts for
-        // renegotiation requests.  These can occur any time.
+        // renegotiation requests we do not allow.  These can occur any time.
         if self.reject_renegotiation_request(&message, tls, send)? {
             return Ok(None);
         }
@@ -444,7 +475,7 @@ impl ReceivePath {
     fn reject_renegotiation_request(
         &mut self,
         msg: &Message<'_>,
-        tls: &mut Vec<u8>,
+        tls: &mut dyn TlsOutputBuffer,
         send: &mut dyn SendOutput,
     ) -> Result<bool, Error> {
         if !self.may_receive_application_data
@@ -458,7 +489,7 @@ impl ReceivePath {
             Side::Server => HandshakeType::ClientHello,
         };
 
-        if msg.handshake_type() != Some(reject_ty) {
+        if msg.handshake_type() != Some(reject_ty) || self.renegotiation_allowed {
             return Ok(false);
         }
         self.temper_counters
@@ -479,6 +510,8 @@ impl ReceivePath {
         // caller.  But do not treat unauthenticated alerts like this.
         if self.may_receive_application_data && alert.description == AlertDescription::CloseNotify {
             self.has_received_close_notify = true;
+            self.observer
+                .observe(ConnectionEvent::CloseNotifyReceived);
             return Ok(());
         }
 
@@ -500,9 +533,20 @@ impl ReceivePath {
                 warn!("TLS alert warning received: {alert:?}");
             }
 
+            self.observer
+                .observe(ConnectionEvent::AlertReceived {
+                    description: alert.description,
+                    error: None,
+                });
+
             return Ok(());
         }
 
+        self.observer
+            .observe(ConnectionEvent::AlertReceived {
+                description: alert.description,
+                error: Some(&err),
+            });
         Err(err)
     }
 }
@@ -516,7 +560,7 @@ enum DeframeResult<'b> {
 struct CaptureAppData<'a, 'j, 'm, Send: SendOutput + 'a> {
     recv: &'a mut ReceivePath,
     other: &'a mut JoinOutput<'j, Send>,
-    tls: &'a mut Vec<u8>,
+    tls: &'a mut dyn TlsOutputBuffer,
     /// Store a [`Locator`] initialized from the current receive buffer
     ///
     /// Allows received plaintext data to be unborrowed and stored in
@@ -538,6 +582,7 @@ impl<'a, 'm, Send: SendOutput + 'a> Output<'m> for CaptureAppData<'a, '_, 'm, Se
     }
 
     fn output(&mut self, ev: OutputEvent<'_>) {
+        self.recv.qlog.parameters_set(&ev);
         if let OutputEvent::ProtocolVersion(ver) = ev {
             self.recv.negotiated_version = Some(ver);
             self.other.send.negotiated_version(ver);
@@ -545,9 +590,12 @@ impl<'a, 'm, Send: SendOutput + 'a> Output<'m> for CaptureAppData<'a, '_, 'm, Se
         self.other.outputs.handle(ev);
     }
 
-    fn send_msg(&mut self, m: Message<'_>, must_encrypt: bool) {
+    fn send_msg(&mut self, m: Message<'_>, must_encrypt: bool) -> Result<(), Error> {
         match self.other.quic.as_deref_mut() {
-            Some(quic) => quic.send_msg(m, must_encrypt),
+            Some(quic) => {
+                quic.send_msg(m, must_encrypt);
+                Ok(())
+            }
             None => self
                 .other
                 .send
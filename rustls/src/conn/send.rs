@@ -1,13 +1,21 @@
 use alloc::boxed::Box;
 use alloc::vec::Vec;
+use core::cmp::{max, min};
+use core::fmt::Debug;
+use core::mem;
 
 use crate::crypto::cipher::{
     EncodableVersion, EncodedMessage, EncryptionState, MessageEncrypter, OutboundPlain, Payload,
     PreEncryptAction,
 };
 use crate::enums::{ContentType, ProtocolVersion};
-use crate::error::{AlertDescription, Error};
-use crate::msgs::{AlertLevel, Fragmenter, HEADER_SIZE, Message};
+use crate::error::{AlertDescription, Error, InvalidMessage};
+use crate::msgs::{
+    AlertLevel, Codec, Fragmenter, HEADER_SIZE, Message, MessagePayload, Reader, SizedPayload,
+};
+use crate::observer::{ConnectionEvent, Observer};
+use crate::qlog::Qlog;
+use crate::sync::Arc;
 use crate::tls13::key_schedule::KeyScheduleTrafficSend;
 use crate::tracing::{debug, error};
 
@@ -23,20 +31,31 @@ pub(crate) struct SendPath {
     key_update_local: KeyUpdateLocal,
     key_update_remote: KeyUpdateRemote,
     negotiated_version: Option<ProtocolVersion>,
+    record_padding: RecordPadding,
     pub(crate) tls13_key_schedule: Option<Box<KeyScheduleTrafficSend>>,
+    pub(crate) observer: Observer,
+    pub(crate) qlog: Qlog,
 }
 
 impl SendPath {
-    pub(crate) fn send_close_notify(&mut self, tls: &mut Vec<u8>) {
+    pub(crate) fn send_close_notify(&mut self, tls: &mut dyn TlsOutputBuffer) -> Result<(), Error> {
         if self.has_sent_close_notify {
-            return;
+            return Ok(());
         }
+        self.check_close_notify_space(tls)?;
+
         debug!("Sending warning alert {:?}", AlertDescription::CloseNotify);
         self.has_sent_close_notify = true;
-        self.send_alert(AlertLevel::Warning, AlertDescription::CloseNotify, tls);
+        let mut output = TrackedOutput::new(tls);
+        self.send_alert(
+            AlertLevel::Warning,
+            AlertDescription::CloseNotify,
+            &mut output,
+        );
+        output.result()
     }
 
-    fn preflight_encrypt(&mut self, n: usize, tls: &mut Vec<u8>) -> Result<(), Error> {
+    fn preflight_encrypt(&mut self, n: usize, tls: &mut dyn TlsOutputBuffer) -> Result<(), Error> {
         match self
             .encrypt_state
             .pre_encrypt_action(n as u64)
@@ -55,7 +74,7 @@ impl SendPath {
                         error!(
                             "traffic keys exhausted, closing connection to prevent security failure"
                         );
-                        self.send_close_notify(tls);
+                        self.send_close_notify(tls)?;
                         Err(Error::EncryptError)
                     }
                 }
@@ -67,12 +86,29 @@ impl SendPath {
     }
 
     /// Like send_msg_encrypt, but operate on an appdata directly.
+    ///
+    /// Nothing is written if `tls` does not have room for all the resulting records.
     pub(crate) fn send_appdata_encrypt(
         &mut self,
         payload: OutboundPlain<'_>,
-        tls: &mut Vec<u8>,
-    ) -> usize {
+        tls: &mut dyn TlsOutputBuffer,
+    ) -> Result<usize, Error> {
+        let padding = self.record_padding.clone();
+        self.send_appdata_encrypt_padded(payload, &padding, tls)
+    }
+
+    /// Like `send_appdata_encrypt`, but pad records according to `padding`
+    /// rather than the configured policy.
+    pub(crate) fn send_appdata_encrypt_padded(
+        &mut self,
+        payload: OutboundPlain<'_>,
+        padding: &RecordPadding,
+        tls: &mut dyn TlsOutputBuffer,
+    ) -> Result<usize, Error> {
         let len = payload.len();
+        self.check_appdata_space(len, padding, tls)?;
+
+        let mut output = TrackedOutput::new(tls);
         self.send_messages::<true>(
             self.message_fragmenter.fragment(
                 ContentType::ApplicationData,
@@ -81,48 +117,204 @@ impl SendPath {
                 self.encrypt_state
                     .encrypted_record_overhead(),
             ),
-            tls,
-        );
-        self.maybe_refresh_traffic_keys(tls);
-        len
+            padding,
+            &mut output,
+        )?;
+        self.maybe_refresh_traffic_keys(&mut output);
+        output.result()?;
+        Ok(len)
     }
 
-    /// Encrypt and queue each fragment in `iter`.
+    /// Encrypt and queue each fragment in `iter`, padding them according to `padding`.
+    ///
+    /// This stops at the first record that cannot be written.
     fn send_messages<'a, const MUST_ENCRYPT: bool>(
         &mut self,
         iter: impl ExactSizeIterator<Item = EncodedMessage<OutboundPlain<'a>>>,
-        tls: &mut Vec<u8>,
-    ) {
+        padding: &RecordPadding,
+        tls: &mut dyn TlsOutputBuffer,
+    ) -> Result<(), Error> {
         self.perhaps_write_key_update(tls);
         let count = iter.len();
         let mut iter = iter.peekable();
         if let Some(first) = iter.peek() {
             let record_len = HEADER_SIZE
                 + match MUST_ENCRYPT {
-                    true => self
-                        .encrypt_state
-                        .encrypted_len(first.payload.len()),
+                    true => {
+                        let len = first.payload.len();
+                        let max_padding = self
+                            .padding_limit(len)
+                            .map_or(0, |limit| padding.max_padding(len, limit));
+                        self.encrypt_state
+                            .encrypted_len(len + max_padding)
+                    }
                     false => first.payload.len(),
                 };
             tls.reserve(count * record_len);
         }
 
+        if !MUST_ENCRYPT {
+            for m in iter {
+                let (typ, len) = (m.typ, m.payload.len());
+                m.encode_unencrypted(tls)?;
+                self.qlog.record_sent(typ, len);
+            }
+            return Ok(());
+        }
+
+        // Records are encrypted in batches, so a `MessageEncrypter` that can
+        // encrypt several records at once gets the chance to.
+        let mut batch = Vec::with_capacity(min(count, ENCRYPT_BATCH_LEN));
         for m in iter {
             // Alerts are always sendable -- never quashed by a PreEncryptAction.
-            if MUST_ENCRYPT
-                && m.typ != ContentType::Alert
-                && self.preflight_encrypt(0, tls).is_err()
+            if m.typ != ContentType::Alert
+                && self
+                    .encrypt_state
+                    .pre_encrypt_action(batch.len() as u64)
+                    .is_some()
             {
-                return;
+                // Encrypt what we have so far, before any remedial action.
+                self.send_batch(&mut batch, tls)?;
+                match self.preflight_encrypt(0, tls) {
+                    Ok(()) => {}
+                    // The traffic keys are exhausted: the rest is not sent.
+                    Err(Error::EncryptError) => return Ok(()),
+                    Err(err) => return Err(err),
+                }
             }
 
-            match MUST_ENCRYPT {
-                true => self
-                    .encrypt_state
-                    .encrypt_outgoing(m, tls),
-                false => m.encode_unencrypted(tls),
+            let (typ, len) = (m.typ, m.payload.len());
+            let padding = self
+                .padding_limit(len)
+                .map_or(0, |limit| padding.padding(typ, len, limit));
+            batch.push((m, padding));
+            if batch.len() == ENCRYPT_BATCH_LEN {
+                self.send_batch(&mut batch, tls)?;
             }
         }
+
+        self.send_batch(&mut batch, tls)
+    }
+
+    /// Encrypt and write the records in `batch`, leaving it empty.
+    fn send_batch(
+        &mut self,
+        batch: &mut Vec<(EncodedMessage<OutboundPlain<'_>>, usize)>,
+        tls: &mut dyn TlsOutputBuffer,
+    ) -> Result<(), Error> {
+        self.encrypt_state
+            .encrypt_outgoing_batch(batch, tls)?;
+        for (m, _) in batch.drain(..) {
+            self.qlog
+                .record_sent(m.typ, m.payload.len());
+        }
+        Ok(())
+    }
+
+    /// Check that `tls` has room for `len` bytes of application data, padded according
+    /// to `padding`, plus anything that may be sent along with them.
+    fn check_appdata_space(
+        &self,
+        len: usize,
+        padding: &RecordPadding,
+        tls: &dyn TlsOutputBuffer,
+    ) -> Result<(), Error> {
+        let Some(remaining) = tls.remaining() else {
+            return Ok(());
+        };
+
+        let max_plain = max(
+            self.message_fragmenter
+                .max_payload_len()
+                .saturating_sub(
+                    self.encrypt_state
+                        .encrypted_record_overhead(),
+                ),
+            1,
+        );
+        let (full, rest) = (len / max_plain, len % max_plain);
+        let mut required = self.queued_len() + full * self.record_bound(max_plain, padding);
+        if rest > 0 {
+            required += self.record_bound(rest, padding);
+        }
+
+        // Sending these records may exhaust the traffic keys, which sends a key
+        // update (for TLS1.3) or a `close_notify` alert (for TLS1.2).
+        let records = (full + usize::from(rest > 0)) as u64;
+        if matches!(self.key_update_local, KeyUpdateLocal::Requested)
+            || (0..=records).any(|n| {
+                self.encrypt_state
+                    .pre_encrypt_action(n)
+                    .is_some()
+            })
+        {
+            required += self.record_bound(KEY_UPDATE_LEN, &self.record_padding);
+        }
+
+        check_space(required, remaining)
+    }
+
+    /// Check that `tls` has room for a `close_notify` alert.
+    fn check_close_notify_space(&self, tls: &dyn TlsOutputBuffer) -> Result<(), Error> {
+        match tls.remaining() {
+            Some(remaining) if !self.has_sent_close_notify => check_space(
+                self.queued_len() + self.record_bound(ALERT_LEN, &self.record_padding),
+                remaining,
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that `tls` has room for a key update request.
+    fn check_key_update_space(&self, tls: &dyn TlsOutputBuffer) -> Result<(), Error> {
+        match tls.remaining() {
+            Some(remaining) => check_space(
+                self.queued_len() + self.record_bound(KEY_UPDATE_LEN, &self.record_padding),
+                remaining,
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// An upper bound on the length of a record holding `len` bytes of content.
+    fn record_bound(&self, len: usize, padding: &RecordPadding) -> usize {
+        if !self.encrypt_state.is_encrypting() {
+            return HEADER_SIZE + len;
+        }
+
+        let max_padding = self
+            .padding_limit(len)
+            .map_or(0, |limit| padding.max_padding(len, limit));
+        HEADER_SIZE
+            + self
+                .encrypt_state
+                .encrypted_len(len + max_padding)
+    }
+
+    /// The length of the queued key update, which is written before anything else.
+    fn queued_len(&self) -> usize {
+        match &self.key_update_remote {
+            KeyUpdateRemote::Queued(message) => message.len(),
+            KeyUpdateRemote::Idle => 0,
+        }
+    }
+
+    /// The most padding that may be added to a record holding `len` bytes of content.
+    ///
+    /// This is `None` if records cannot be padded at all: before TLS1.3 is negotiated,
+    /// or if the encrypter does not support padding.
+    fn padding_limit(&self, len: usize) -> Option<usize> {
+        if self.negotiated_version != Some(ProtocolVersion::TLSv1_3)
+            || !self.encrypt_state.supports_padding()
+        {
+            return None;
+        }
+
+        Some(
+            self.message_fragmenter
+                .max_payload_len()
+                .saturating_sub(self.encrypt_state.encrypted_len(len)),
+        )
     }
 
     pub(crate) fn start_outgoing_traffic(&mut self) {
@@ -130,12 +322,62 @@ impl SendPath {
         debug_assert!(self.encrypt_state.is_encrypting());
     }
 
-    fn perhaps_write_key_update(&mut self, tls: &mut Vec<u8>) {
+    fn perhaps_write_key_update(&mut self, tls: &mut dyn TlsOutputBuffer) {
         let KeyUpdateRemote::Queued(message) = &mut self.key_update_remote else {
             return;
         };
-        tls.append(message);
-        self.key_update_remote = KeyUpdateRemote::Idle;
+        if tls.extend_from_slice(message).is_ok() {
+            self.key_update_remote = KeyUpdateRemote::Idle;
+        }
+    }
+
+    /// Encode the state carried by a serialized connection.
+    pub(super) fn encode_handoff(&self, bytes: &mut Vec<u8>) {
+        self.encrypt_state
+            .write_seq()
+            .encode(bytes);
+        bytes.push(self.has_sent_close_notify as u8);
+        bytes.push(match self.key_update_local {
+            KeyUpdateLocal::Idle => 0,
+            KeyUpdateLocal::Requested => 1,
+            KeyUpdateLocal::Outstanding => 2,
+        });
+        match &self.key_update_remote {
+            KeyUpdateRemote::Queued(message) => {
+                bytes.push(1);
+                SizedPayload::<u16>::from(Payload::Borrowed(message)).encode(bytes);
+            }
+            KeyUpdateRemote::Idle => bytes.push(0),
+        }
+    }
+
+    /// Restore the state encoded by `encode_handoff()`.
+    ///
+    /// This must be called after the encrypter is installed.
+    pub(super) fn read_handoff(&mut self, r: &mut Reader<'_>) -> Result<(), InvalidMessage> {
+        self.encrypt_state
+            .restore_write_seq(u64::read(r)?);
+        self.has_sent_close_notify = matches!(u8::read(r)?, 1);
+        self.key_update_local = match u8::read(r)? {
+            1 => KeyUpdateLocal::Requested,
+            2 => KeyUpdateLocal::Outstanding,
+            _ => KeyUpdateLocal::Idle,
+        };
+        self.key_update_remote = match u8::read(r)? {
+            1 => KeyUpdateRemote::Queued(SizedPayload::<u16>::read(r)?.into_vec()),
+            _ => KeyUpdateRemote::Idle,
+        };
+        Ok(())
+    }
+
+    /// Move a restored TLS1.3 connection onto fresh sending keys.
+    ///
+    /// Some AEAD implementations require a key's first record to use sequence number
+    /// zero, so a restored key is used for exactly one record: a queued `KeyUpdate`.
+    pub(crate) fn refresh_restored_traffic_keys(&mut self) {
+        if self.tls13_key_schedule.is_some() && self.encrypt_state.write_seq() > 0 {
+            self.queue_requested_key_update();
+        }
     }
 
     pub(crate) fn set_max_fragment_size(&mut self, new: Option<usize>) -> Result<(), Error> {
@@ -143,33 +385,74 @@ impl SendPath {
             .set_max_fragment_size(new)
     }
 
+    pub(crate) fn set_record_padding(&mut self, padding: RecordPadding) {
+        self.record_padding = padding;
+    }
+
     /// Trigger a `refresh_traffic_keys` if requested.
-    fn maybe_refresh_traffic_keys(&mut self, tls: &mut Vec<u8>) {
+    fn maybe_refresh_traffic_keys(&mut self, tls: &mut dyn TlsOutputBuffer) {
         if let KeyUpdateLocal::Requested = self.key_update_local {
             let _ = self.send_key_update_request(tls);
         }
     }
 
-    pub(crate) fn refresh_traffic_keys(&mut self, tls: &mut Vec<u8>) -> Result<(), Error> {
+    pub(crate) fn refresh_traffic_keys(
+        &mut self,
+        tls: &mut dyn TlsOutputBuffer,
+    ) -> Result<(), Error> {
         if let KeyUpdateLocal::Outstanding = self.key_update_local {
             return Ok(());
         }
+        self.check_key_update_space(tls)?;
         self.send_key_update_request(tls)
     }
 
-    fn send_key_update_request(&mut self, tls: &mut Vec<u8>) -> Result<(), Error> {
+    fn send_key_update_request(&mut self, tls: &mut dyn TlsOutputBuffer) -> Result<(), Error> {
         let ks = self.tls13_key_schedule.take();
 
         let Some(mut ks) = ks else {
             return Err(Error::HandshakeNotComplete);
         };
 
-        self.send_msg(Message::build_key_update_request(), true, tls);
+        // The peer must receive the request before anything encrypted with the new keys.
+        let mut output = TrackedOutput::new(tls);
+        if let Err(err) = self.send_msg(Message::build_key_update_request(), true, &mut output) {
+            self.tls13_key_schedule = Some(ks);
+            return Err(err);
+        }
+
         ks.update_encrypter(self);
         self.key_update_local = KeyUpdateLocal::Outstanding;
         self.tls13_key_schedule = Some(ks);
+        self.observer
+            .observe(ConnectionEvent::KeyUpdateSent);
         Ok(())
     }
+
+    fn send_alert_for(
+        &mut self,
+        level: AlertLevel,
+        desc: AlertDescription,
+        error: Option<&Error>,
+        tls: &mut dyn TlsOutputBuffer,
+    ) {
+        match level {
+            AlertLevel::Fatal if self.has_sent_fatal_alert => return,
+            AlertLevel::Fatal => self.has_sent_fatal_alert = true,
+            _ => {}
+        };
+
+        // Any shortfall is reported by the `TrackedOutput` at the API boundary.
+        let _ = self.send_msg(
+            Message::build_alert(level, desc),
+            self.encrypt_state.is_encrypting(),
+            tls,
+        );
+        self.observer.observe(match desc {
+            AlertDescription::CloseNotify => ConnectionEvent::CloseNotifySent,
+            description => ConnectionEvent::AlertSent { description, error },
+        });
+    }
 }
 
 impl SendOutput for SendPath {
@@ -183,15 +466,26 @@ impl SendOutput for SendPath {
         }
 
         let message = EncodedMessage::<Payload<'static>>::from(Message::build_key_update_notify());
+        let len = message.payload.bytes().len();
+        let padding = self
+            .padding_limit(len)
+            .map_or(0, |limit| {
+                self.record_padding
+                    .padding(message.typ, len, limit)
+            });
         let mut queued = Vec::new();
-        self.encrypt_state
-            .encrypt_outgoing(message.borrow_outbound(), &mut queued);
+        // Writing to a `Vec` cannot fail.
+        let _ =
+            self.encrypt_state
+                .encrypt_outgoing(message.borrow_outbound(), padding, &mut queued);
         self.key_update_remote = KeyUpdateRemote::Queued(queued);
 
         if let Some(mut ks) = self.tls13_key_schedule.take() {
             ks.update_encrypter_for_key_update(self);
             self.tls13_key_schedule = Some(ks);
         }
+        self.observer
+            .observe(ConnectionEvent::KeyUpdateSent);
     }
 
     fn note_key_update_response(&mut self) {
@@ -209,18 +503,22 @@ impl SendOutput for SendPath {
         self.tls13_key_schedule = Some(schedule);
     }
 
-    fn send_alert(&mut self, level: AlertLevel, desc: AlertDescription, tls: &mut Vec<u8>) {
-        match level {
-            AlertLevel::Fatal if self.has_sent_fatal_alert => return,
-            AlertLevel::Fatal => self.has_sent_fatal_alert = true,
-            _ => {}
-        };
+    fn send_alert(
+        &mut self,
+        level: AlertLevel,
+        desc: AlertDescription,
+        tls: &mut dyn TlsOutputBuffer,
+    ) {
+        self.send_alert_for(level, desc, None, tls);
+    }
 
-        self.send_msg(
-            Message::build_alert(level, desc),
-            self.encrypt_state.is_encrypting(),
-            tls,
-        );
+    fn send_fatal_alert(
+        &mut self,
+        desc: AlertDescription,
+        error: &Error,
+        tls: &mut dyn TlsOutputBuffer,
+    ) {
+        self.send_alert_for(AlertLevel::Fatal, desc, Some(error), tls);
     }
 
     fn start_traffic(&mut self) {
@@ -229,7 +527,24 @@ impl SendOutput for SendPath {
     }
 
     /// Send a raw TLS message, fragmenting it if needed.
-    fn send_msg(&mut self, m: Message<'_>, must_encrypt: bool, tls: &mut Vec<u8>) {
+    fn send_msg(
+        &mut self,
+        m: Message<'_>,
+        must_encrypt: bool,
+        tls: &mut dyn TlsOutputBuffer,
+    ) -> Result<(), Error> {
+        match &m.payload {
+            MessagePayload::Handshake { parsed, encoded } => self
+                .qlog
+                .message_sent(parsed, encoded.bytes().len()),
+            MessagePayload::HandshakeFlight(flight) => self.qlog.flight_sent(
+                flight.bytes(),
+                self.negotiated_version
+                    .unwrap_or(ProtocolVersion::TLSv1_2),
+            ),
+            _ => {}
+        }
+
         let encoded = EncodedMessage::from(m);
         let fragments = self.message_fragmenter.fragment(
             encoded.typ,
@@ -239,9 +554,10 @@ impl SendOutput for SendPath {
                 .encrypted_record_overhead(),
         );
 
+        let padding = self.record_padding.clone();
         match must_encrypt {
-            true => self.send_messages::<true>(fragments, tls),
-            false => self.send_messages::<false>(fragments, tls),
+            true => self.send_messages::<true>(fragments, &padding, tls),
+            false => self.send_messages::<false>(fragments, &padding, tls),
         }
     }
 }
@@ -258,11 +574,103 @@ impl Default for SendPath {
             key_update_local: KeyUpdateLocal::Idle,
             key_update_remote: KeyUpdateRemote::Idle,
             negotiated_version: None,
+            record_padding: RecordPadding::None,
             tls13_key_schedule: None,
+            observer: Observer::default(),
+            qlog: Qlog::default(),
         }
     }
 }
 
+/// How to pad TLS1.3 records, to disguise the length of their contents.
+///
+/// TLS1.3 records may carry zero bytes of padding after their contents (see
+/// [RFC 9846 section 5.4]), so that an observer only learns the padded length.
+/// Padding is added to every record encrypted with TLS1.3 traffic keys, including
+/// encrypted handshake messages such as `Certificate`, alerts and key updates.
+///
+/// Records are never padded beyond the maximum fragment size.  No padding is added to
+/// TLS1.2 records, or by a [`MessageEncrypter`] that does not
+/// [support it](MessageEncrypter::supports_padding).
+///
+/// Padding costs bandwidth, and only hides lengths within a record: the number of
+/// records sent still reveals roughly how much data was written.
+///
+/// [RFC 9846 section 5.4]: https://datatracker.ietf.org/doc/html/rfc9846#section-5.4
+#[non_exhaustive]
+#[derive(Clone, Debug, Default)]
+pub enum RecordPadding {
+    /// Records are not padded.
+    #[default]
+    None,
+
+    /// Pad the contents of each record to a multiple of this many bytes.
+    ///
+    /// A block size of zero or one adds no padding.
+    BlockSize(u16),
+
+    /// Pad every record to the maximum fragment size.
+    MaxFragment,
+
+    /// Choose the padding for each record with a [`RecordPadder`].
+    Custom(Arc<dyn RecordPadder>),
+}
+
+impl RecordPadding {
+    /// The padding to add to a record of type `typ` holding `len` bytes, at most `limit`.
+    fn padding(&self, typ: ContentType, len: usize, limit: usize) -> usize {
+        let padding = match self {
+            Self::None => 0,
+            Self::BlockSize(block @ 2..) => {
+                let block = usize::from(*block);
+                (block - len % block) % block
+            }
+            Self::BlockSize(_) => 0,
+            Self::MaxFragment => limit,
+            Self::Custom(padder) => padder.padding(typ, len, limit),
+        };
+        min(padding, limit)
+    }
+
+    /// An upper bound on the padding for any record holding `len` bytes, at most `limit`.
+    fn max_padding(&self, len: usize, limit: usize) -> usize {
+        match self {
+            Self::None | Self::BlockSize(_) => {
+                self.padding(ContentType::ApplicationData, len, limit)
+            }
+            Self::MaxFragment | Self::Custom(_) => limit,
+        }
+    }
+}
+
+/// Chooses the padding for each TLS1.3 record.
+///
+/// Used with [`RecordPadding::Custom`].
+pub trait RecordPadder: Debug + Send + Sync {
+    /// Return the number of zero bytes of padding to add to a record.
+    ///
+    /// `typ` is the true content type of the record, and `len` is the length of
+    /// its contents.  `limit` is the most padding the record can carry; larger
+    /// return values are reduced to `limit`.
+    fn padding(&self, typ: ContentType, len: usize, limit: usize) -> usize;
+}
+
+fn check_space(required: usize, remaining: usize) -> Result<(), Error> {
+    match required > remaining {
+        true => Err(Error::InsufficientOutputSpace { required }),
+        false => Ok(()),
+    }
+}
+
+/// The length of an encoded alert message.
+const ALERT_LEN: usize = 2;
+
+/// The length of an encoded `KeyUpdate` handshake message.
+const KEY_UPDATE_LEN: usize = 5;
+
+/// The most records passed to [`MessageEncrypter::encrypt_batch()`] at once.
+const ENCRYPT_BATCH_LEN: usize = 16;
+
 /// State machine for TLS1.3 key updates triggered by us.
 ///
 /// This sits at [`Self::Idle`] for TLS1.2 connections.
@@ -288,6 +696,152 @@ enum KeyUpdateRemote {
     Queued(Vec<u8>),
 }
 
+/// An abstraction over buffers (either owned or borrowed) that TLS data is written into.
+///
+/// This is the counterpart of [`TlsInputBuffer`] for data sent to the peer.  It is
+/// implemented for `Vec<u8>`, which grows as needed, and for `&mut [u8]`, which is
+/// advanced past the bytes written to it (in the same way as `std::io::Write` is
+/// for `&mut [u8]`).
+///
+/// An operation that cannot fit its output in the buffer fails with
+/// [`Error::InsufficientOutputSpace`], which says how many bytes it needed.
+///
+/// [`TlsInputBuffer`]: crate::TlsInputBuffer
+pub trait TlsOutputBuffer {
+    /// Append `len` bytes to the buffer, returning them to be filled in.
+    ///
+    /// The contents of the returned slice are unspecified, and it counts as written
+    /// whether or not the caller overwrites it.  Rustls always overwrites it fully.
+    ///
+    /// If there is no room for `len` more bytes, return
+    /// [`Error::InsufficientOutputSpace`] without writing anything.
+    fn extend(&mut self, len: usize) -> Result<&mut [u8], Error>;
+
+    /// Return the number of bytes that can still be written, or `None` if there
+    /// is no limit.
+    fn remaining(&self) -> Option<usize>;
+
+    /// Indicate that `additional` bytes are about to be written.
+    ///
+    /// This is a hint only; the default implementation does nothing.
+    fn reserve(&mut self, additional: usize) {
+        let _ = additional;
+    }
+
+    /// Append a copy of `bytes` to the buffer.
+    fn extend_from_slice(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.extend(bytes.len())?
+            .copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+impl TlsOutputBuffer for Vec<u8> {
+    fn extend(&mut self, len: usize) -> Result<&mut [u8], Error> {
+        // `resize` zero-fills the new bytes, which the caller then overwrites.
+        let start = self.len();
+        self.resize(start + len, 0);
+        Ok(&mut self[start..])
+    }
+
+    fn remaining(&self) -> Option<usize> {
+        None
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        Self::reserve(self, additional);
+    }
+
+    fn extend_from_slice(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        Self::extend_from_slice(self, bytes);
+        Ok(())
+    }
+}
+
+impl TlsOutputBuffer for &mut [u8] {
+    fn extend(&mut self, len: usize) -> Result<&mut [u8], Error> {
+        if len > self.len() {
+            return Err(Error::InsufficientOutputSpace { required: len });
+        }
+
+        let (written, rest) = mem::take(self).split_at_mut(len);
+        *self = rest;
+        Ok(written)
+    }
+
+    fn remaining(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+/// Wraps the caller's [`TlsOutputBuffer`] for the duration of one operation,
+/// remembering whether any of the operation's output did not fit.
+///
+/// Once a write has failed, later writes fail too, so the output that is written
+/// is never missing records from the middle.
+pub(crate) struct TrackedOutput<'a> {
+    inner: &'a mut dyn TlsOutputBuffer,
+    written: usize,
+    missing: Option<usize>,
+}
+
+impl<'a> TrackedOutput<'a> {
+    pub(crate) fn new(inner: &'a mut dyn TlsOutputBuffer) -> Self {
+        Self {
+            inner,
+            written: 0,
+            missing: None,
+        }
+    }
+
+    /// Return an error if any output did not fit.
+    ///
+    /// The error says how many bytes the whole operation needed.
+    pub(crate) fn result(&self) -> Result<(), Error> {
+        match self.missing {
+            Some(missing) => Err(Error::InsufficientOutputSpace {
+                required: self.written + missing,
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+impl TlsOutputBuffer for TrackedOutput<'_> {
+    fn extend(&mut self, len: usize) -> Result<&mut [u8], Error> {
+        if let Some(missing) = &mut self.missing {
+            *missing += len;
+            return Err(Error::InsufficientOutputSpace {
+                required: self.written + *missing,
+            });
+        }
+
+        match self.inner.extend(len) {
+            Ok(buf) => {
+                self.written += len;
+                Ok(buf)
+            }
+            Err(_) => {
+                self.missing = Some(len);
+                Err(Error::InsufficientOutputSpace {
+                    required: self.written + len,
+                })
+            }
+        }
+    }
+
+    fn remaining(&self) -> Option<usize> {
+        match self.missing {
+            Some(_) => Some(0),
+            None => self.inner.remaining(),
+        }
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.inner.reserve(additional);
+    }
+}
+
 pub(crate) trait SendOutput {
     fn negotiated_version(&mut self, version: ProtocolVersion);
 
@@ -299,9 +853,28 @@ pub(crate) trait SendOutput {
 
     fn update_key_schedule(&mut self, schedule: Box<KeyScheduleTrafficSend>);
 
-    fn send_alert(&mut self, level: AlertLevel, desc: AlertDescription, tls: &mut Vec<u8>);
+    fn send_alert(
+        &mut self,
+        level: AlertLevel,
+        desc: AlertDescription,
+        tls: &mut dyn TlsOutputBuffer,
+    );
+
+    /// Send a fatal alert because of `error`.
+    fn send_fatal_alert(
+        &mut self,
+        desc: AlertDescription,
+        error: &Error,
+        tls: &mut dyn TlsOutputBuffer,
+    );
 
     fn start_traffic(&mut self);
 
-    fn send_msg(&mut self, m: Message<'_>, must_encrypt: bool, tls: &mut Vec<u8>);
+    /// Send a message, failing if it does not fit in `tls`.
+    fn send_msg(
+        &mut self,
+        m: Message<'_>,
+        must_encrypt: bool,
+        tls: &mut dyn TlsOutputBuffer,
+    ) -> Result<(), Error>;
 }
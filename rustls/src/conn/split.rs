@@ -1,16 +1,19 @@
 use alloc::boxed::Box;
 use alloc::vec::Vec;
-use core::fmt;
 use core::ops::{DerefMut, Range};
+use core::{fmt, iter};
 use std::sync::MutexGuard;
 
+use super::handoff::serialize;
 use super::receive::{Discard, JoinOutput};
 use crate::client::ClientSide;
 use crate::common_state::UnborrowedPayload;
 use crate::conn::kernel::KernelConnection;
 use crate::conn::{
-    ConnectionCommon, MessageIter, ReceivePath, SendOutput, SendPath, TlsInputBuffer,
+    ConnectionCommon, MessageIter, ReceivePath, RecordPadding, SendOutput, SendPath,
+    TlsInputBuffer, TlsOutputBuffer,
 };
+use crate::crypto::TicketProducer;
 use crate::crypto::cipher::{MessageEncrypter, OutboundPlain};
 use crate::enums::ProtocolVersion;
 use crate::error::{AlertDescription, ErrorWithAlert};
@@ -73,6 +76,38 @@ impl<Side: SideData> SplitConnection<Side> {
             state,
         )
     }
+
+    /// Serialize this connection, so it can be restored in another process.
+    ///
+    /// This is like [`ClientConnection::dangerous_serialize()`], and the result can be restored with
+    /// [`ClientConnection::dangerous_restore()`] or [`ServerConnection::dangerous_restore()`].
+    ///
+    /// The server name and resumption data received by a server are not carried over.
+    ///
+    /// [`ClientConnection::dangerous_serialize()`]: crate::ClientConnection::dangerous_serialize
+    /// [`ClientConnection::dangerous_restore()`]: crate::ClientConnection::dangerous_restore
+    /// [`ServerConnection::dangerous_restore()`]: crate::ServerConnection::dangerous_restore
+    pub fn dangerous_serialize(self, sealer: &dyn TicketProducer) -> Result<Vec<u8>, Error> {
+        let Self {
+            send,
+            receive,
+            outputs,
+        } = self;
+        drop(send);
+
+        let ReceiveTraffic {
+            state, recv, send, ..
+        } = receive;
+
+        serialize::<Side>(
+            &state,
+            &send.lock().unwrap(),
+            &recv,
+            &outputs,
+            &Side::Data::default(),
+            sealer,
+        )
+    }
 }
 
 impl<Side: SideData> TryFrom<ConnectionCommon<Side>> for SplitConnection<Side> {
@@ -108,9 +143,33 @@ impl SendTraffic {
     ///
     /// When you need to handle a [`ReceiveTrafficState::FlushSender`] state, you can call this
     /// method with [`OutboundPlain::new_empty()`] to flush any pending TLS data to the peer.
-    pub fn write(&mut self, application_data: OutboundPlain<'_>, tls: &mut Vec<u8>) {
+    ///
+    /// If `tls` does not have room for the resulting records, nothing is written and
+    /// [`Error::InsufficientOutputSpace`] is returned.
+    pub fn write(
+        &mut self,
+        application_data: OutboundPlain<'_>,
+        tls: &mut dyn TlsOutputBuffer,
+    ) -> Result<(), Error> {
+        let mut inner = self.0.lock().unwrap();
+        inner.send_appdata_encrypt(application_data, tls)?;
+        Ok(())
+    }
+
+    /// Write application data to the peer, padding its records according to `padding`.
+    ///
+    /// This is like [`Self::write()`], but `padding` is used instead of the
+    /// `record_padding` from the connection's configuration.  Any pending TLS data
+    /// written alongside the application data is padded as usual.
+    pub fn write_padded(
+        &mut self,
+        application_data: OutboundPlain<'_>,
+        padding: &RecordPadding,
+        tls: &mut dyn TlsOutputBuffer,
+    ) -> Result<(), Error> {
         let mut inner = self.0.lock().unwrap();
-        inner.send_appdata_encrypt(application_data, tls);
+        inner.send_appdata_encrypt_padded(application_data, padding, tls)?;
+        Ok(())
     }
 
     /// Conclude sending traffic by sending a `close_notify` alert.
@@ -118,11 +177,14 @@ impl SendTraffic {
     /// The alert is written into `tls` along with any pending data.
     /// This data should then be communicated to the peer.
     ///
-    /// This is the final possible operation with a [`SendTraffic`].
-    pub fn close(self, tls: &mut Vec<u8>) {
+    /// This is the final possible operation with a [`SendTraffic`].  If `tls` does not
+    /// have room for the alert, nothing is written and the [`SendTraffic`] is returned
+    /// alongside the error so that the caller can retry.
+    pub fn close(self, tls: &mut dyn TlsOutputBuffer) -> Result<(), (Self, Error)> {
         let mut inner = self.0.lock().unwrap();
-        inner.send_close_notify(tls);
+        let result = inner.send_close_notify(tls);
         drop(inner);
+        result.map_err(|err| (self, err))
     }
 
     /// Writes a TLS 1.3 `key_update` message into `tls` to refresh a connection's keys.
@@ -149,7 +211,7 @@ impl SendTraffic {
     ///
     /// rustls only allows one outstanding request at a time; this function succeeds
     /// but sends nothing if a request is already in-flight.
-    pub fn refresh_traffic_keys(&mut self, tls: &mut Vec<u8>) -> Result<(), Error> {
+    pub fn refresh_traffic_keys(&mut self, tls: &mut dyn TlsOutputBuffer) -> Result<(), Error> {
         self.0
             .lock()
             .unwrap()
@@ -187,11 +249,39 @@ impl<Side: SideData> ReceiveTraffic<Side> {
     /// which can be obtained from the returned [`ErrorWithAlert`] and sent
     /// to the peer. Following this, the underlying IO medium should be
     /// closed by the application.
-    pub fn read<'a, 't>(
+    pub fn read<'a>(
         self,
         input: &'a mut impl TlsInputBuffer,
-        tls: &'t mut Vec<u8>,
-    ) -> Result<ReceiveTrafficState<'a, Side>, ErrorWithAlert<'t>> {
+        tls: &mut dyn TlsOutputBuffer,
+    ) -> Result<ReceiveTrafficState<'a, Side>, ErrorWithAlert> {
+        self.read_records(input, tls, false)
+    }
+
+    /// Receive application data from every complete record in `input`.
+    ///
+    /// This is like [`Self::read()`], but keeps decrypting until `input` holds no
+    /// more complete records.  The plaintext of each record is left in place in
+    /// `input`, and is available from [`ReceivedApplicationData::chunks()`]; a
+    /// single discard of all the records read happens in
+    /// [`ReceivedApplicationData::into_next()`].  This allows application frames
+    /// that span several records to be parsed without copying them.
+    ///
+    /// An error is fatal to the connection as for [`Self::read()`], and any
+    /// application data decrypted before it is lost.
+    pub fn read_vectored<'a>(
+        self,
+        input: &'a mut impl TlsInputBuffer,
+        tls: &mut dyn TlsOutputBuffer,
+    ) -> Result<ReceiveTrafficState<'a, Side>, ErrorWithAlert> {
+        self.read_records(input, tls, true)
+    }
+
+    fn read_records<'a>(
+        self,
+        input: &'a mut impl TlsInputBuffer,
+        tls: &mut dyn TlsOutputBuffer,
+        vectored: bool,
+    ) -> Result<ReceiveTrafficState<'a, Side>, ErrorWithAlert> {
         let Self {
             state,
             mut recv,
@@ -210,34 +300,38 @@ impl<Side: SideData> ReceiveTraffic<Side> {
 
         let mut iter =
             MessageIter::<Side, _>::receive(input, tls, &mut state, &mut recv, output, true);
-        let received_plain = match iter.next() {
-            Some(Ok(payload)) => Some(payload),
-            Some(Err(error)) => {
-                return Err(ErrorWithAlert::new(
-                    error,
-                    send_adapter
-                        .as_locked(false)
-                        .deref_mut(),
-                    tls,
-                ));
-            }
-            None => None,
-        };
+        let mut received = Vec::new();
+        loop {
+            let error = match iter.next() {
+                Some(Ok(UnborrowedPayload::Unborrowed(range))) => {
+                    received.push(range);
+                    match vectored {
+                        true => continue,
+                        false => break,
+                    }
+                }
+                Some(Ok(UnborrowedPayload::Owned(_))) => {
+                    Error::Unreachable("decrypted data should be borrowed")
+                }
+                Some(Err(error)) => error,
+                None => break,
+            };
+
+            return Err(ErrorWithAlert::new(
+                error,
+                send_adapter
+                    .as_locked(false)
+                    .deref_mut(),
+                tls,
+            ));
+        }
 
         // nb. state consumed only on error.
         let state = state.unwrap();
 
-        if let Some(unborrowed) = received_plain {
+        let mut received = received.into_iter();
+        if let Some(range) = received.next() {
             let pending_discard = recv.deframer.take_discard();
-            let UnborrowedPayload::Unborrowed(range) = unborrowed else {
-                return Err(ErrorWithAlert::new(
-                    Error::Unreachable("decrypted data should be borrowed"),
-                    send_adapter
-                        .as_locked(false)
-                        .deref_mut(),
-                    tls,
-                ));
-            };
 
             if let SendAdapter::Locked { send_required, .. } = send_adapter {
                 pending_flush_sender |= send_required;
@@ -246,6 +340,7 @@ impl<Side: SideData> ReceiveTraffic<Side> {
             drop(send_adapter);
             return Ok(ReceiveTrafficState::Available(ReceivedApplicationData {
                 range,
+                rest: received.collect(),
                 input,
                 pending_discard,
                 rt: Self {
@@ -383,6 +478,9 @@ pub struct ReceivedApplicationData<'a, Side: SideData> {
     /// The span within the `received_tls` buffer holding the received data.
     range: Range<usize>,
 
+    /// The spans of any further records, from [`ReceiveTraffic::read_vectored()`].
+    rest: Vec<Range<usize>>,
+
     /// How many bytes on the front of the original input buffer are associated
     /// with this data.
     ///
@@ -395,10 +493,24 @@ pub struct ReceivedApplicationData<'a, Side: SideData> {
 
 impl<Side: SideData> ReceivedApplicationData<'_, Side> {
     /// Return the application data bytes.
+    ///
+    /// After [`ReceiveTraffic::read_vectored()`], this is the data from the first
+    /// record only; use [`Self::chunks()`] to see all of it.
     pub fn data(&mut self) -> &[u8] {
         Delocator::new(self.input.slice_mut()).slice_from_range(&self.range)
     }
 
+    /// Return the application data bytes from each record, in order.
+    ///
+    /// The slices borrow the decrypted records in place, so can be passed on as
+    /// (for example) `IoSlice`s without copying.
+    pub fn chunks(&mut self) -> impl Iterator<Item = &[u8]> + '_ {
+        let delocator = Delocator::new(self.input.slice_mut());
+        iter::once(&self.range)
+            .chain(&self.rest)
+            .map(move |range| delocator.slice_from_range(range))
+    }
+
     /// Finish processing this received data.
     ///
     /// This acts upon the source buffer (used with the [`ReceiveTraffic::read()`] call) to
@@ -504,16 +616,36 @@ impl SendOutput for SendAdapter<'_> {
             .update_key_schedule(schedule);
     }
 
-    fn send_alert(&mut self, level: AlertLevel, desc: AlertDescription, tls: &mut Vec<u8>) {
+    fn send_alert(
+        &mut self,
+        level: AlertLevel,
+        desc: AlertDescription,
+        tls: &mut dyn TlsOutputBuffer,
+    ) {
         self.as_locked(true)
             .send_alert(level, desc, tls)
     }
 
+    fn send_fatal_alert(
+        &mut self,
+        desc: AlertDescription,
+        error: &Error,
+        tls: &mut dyn TlsOutputBuffer,
+    ) {
+        self.as_locked(true)
+            .send_fatal_alert(desc, error, tls)
+    }
+
     fn start_traffic(&mut self) {
         self.as_locked(false).start_traffic();
     }
 
-    fn send_msg(&mut self, m: Message<'_>, must_encrypt: bool, tls: &mut Vec<u8>) {
+    fn send_msg(
+        &mut self,
+        m: Message<'_>,
+        must_encrypt: bool,
+        tls: &mut dyn TlsOutputBuffer,
+    ) -> Result<(), Error> {
         self.as_locked(true)
             .send_msg(m, must_encrypt, tls)
     }
@@ -521,6 +653,8 @@ impl SendOutput for SendAdapter<'_> {
 
 #[cfg(test)]
 mod tests {
+    use alloc::vec::Vec;
+
     use super::*;
     use crate::crypto::test_provider::Tls13Cipher;
 
@@ -542,11 +676,9 @@ mod tests {
             &mut tls,
         )));
         assert!(!send_flag_for(|adapter| adapter.start_traffic()));
-        assert!(send_flag_for(|adapter| adapter.send_msg(
-            Message::build_key_update_notify(),
-            false,
-            &mut tls,
-        )));
+        assert!(send_flag_for(|adapter| adapter
+            .send_msg(Message::build_key_update_notify(), false, &mut tls)
+            .unwrap()));
     }
 
     fn send_flag_for(f: impl FnOnce(&mut SendAdapter<'_>)) -> bool {
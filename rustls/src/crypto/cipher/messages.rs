@@ -3,6 +3,7 @@ use core::ops::{Deref, DerefMut, Range};
 use core::{fmt, slice};
 
 use crate::Protocol;
+use crate::conn::TlsOutputBuffer;
 use crate::crypto::cipher::EncryptionState;
 use crate::enums::{ContentType, ProtocolVersion};
 use crate::error::{ApiMisuse, Error, InvalidMessage, PeerMisbehaved};
@@ -146,15 +147,24 @@ impl EncodedMessage<OutboundPlain<'_>> {
         let len = self.payload.len();
         debug_assert!(len <= usize::from(u16::MAX));
         let mut buf = Vec::with_capacity(HEADER_SIZE + len);
-        self.encode_unencrypted(&mut buf);
+        buf.extend_from_slice(&encode_record_header(self.typ, self.version, len as u16));
+        self.payload.copy_to_vec(&mut buf);
         buf
     }
 
-    pub(crate) fn encode_unencrypted(&self, buf: &mut Vec<u8>) {
+    pub(crate) fn encode_unencrypted(&self, buf: &mut dyn TlsOutputBuffer) -> Result<(), Error> {
         let len = self.payload.len();
         debug_assert!(len <= usize::from(u16::MAX));
-        buf.extend_from_slice(&encode_record_header(self.typ, self.version, len as u16));
-        self.payload.copy_to_vec(buf);
+        let (header, mut rest) = buf
+            .extend(HEADER_SIZE + len)?
+            .split_at_mut(HEADER_SIZE);
+        header.copy_from_slice(&encode_record_header(self.typ, self.version, len as u16));
+        for chunk in self.payload.chunks() {
+            let (this, next) = rest.split_at_mut(chunk.len());
+            this.copy_from_slice(chunk);
+            rest = next;
+        }
+        Ok(())
     }
 
     #[expect(dead_code)]
@@ -440,6 +450,16 @@ impl<'a> EncryptBuffer<'a> {
         self.used += slice.len();
     }
 
+    /// Append `len` zero bytes.
+    ///
+    /// This is useful for writing TLS1.3 record padding.  Like the other append
+    /// methods, it panics if the write would extend beyond the `len` given to
+    /// [`Self::new()`].
+    pub fn extend_zeroed(&mut self, len: usize) {
+        self.buf[self.used..self.used + len].fill(0);
+        self.used += len;
+    }
+
     /// Consume this value, returning the written prefix of the wrapped buffer.
     pub fn into_written(self) -> &'a [u8] {
         &self.buf[..self.used]
@@ -587,6 +607,7 @@ impl EncodableVersion {
         match self {
             Self::Legacy(_) => ProtocolVersion::TLSv1_2,
             Self::InitialClientHello(Protocol::Tcp | Protocol::Quic(_)) => ProtocolVersion::TLSv1_0,
+            Self::InitialClientHello(Protocol::Dtls) => ProtocolVersion::DTLSv1_0,
         }
     }
 
@@ -595,6 +616,7 @@ impl EncodableVersion {
         match self {
             Self::Legacy(v) => *v,
             Self::InitialClientHello(Protocol::Tcp | Protocol::Quic(_)) => ProtocolVersion::TLSv1_2,
+            Self::InitialClientHello(Protocol::Dtls) => ProtocolVersion::DTLSv1_2,
         }
     }
 }
@@ -155,6 +155,50 @@ pub trait MessageDecrypter: Send + Sync {
         msg: EncodedMessage<InboundOpaque<'a>>,
         seq: u64,
     ) -> Result<EncodedMessage<&'a [u8]>, Error>;
+
+    /// Decrypt each of `records` in turn, using consecutive sequence numbers starting at `seq`.
+    ///
+    /// Record `i` is decrypted as [`Self::decrypt()`] would with sequence number `seq + i`,
+    /// and is replaced with its decryption.  If an error is returned, the records before
+    /// the one that failed have been decrypted, and the rest are left as they were.
+    ///
+    /// Rustls itself decrypts one record at a time, because a record (such as a
+    /// `KeyUpdate`) can change the keys for the records after it.  The default
+    /// implementation calls [`Self::decrypt()`] for each record.  Override it where
+    /// decrypting several records at once is cheaper.
+    fn decrypt_batch(&mut self, records: &mut [InboundRecord<'_>], seq: u64) -> Result<(), Error> {
+        for (record, seq) in records.iter_mut().zip(seq..) {
+            if let Some(msg) = record.take_encrypted() {
+                *record = InboundRecord::Decrypted(self.decrypt(msg, seq)?);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A record in a batch passed to [`MessageDecrypter::decrypt_batch()`].
+#[non_exhaustive]
+pub enum InboundRecord<'a> {
+    /// A record yet to be decrypted.
+    Encrypted(EncodedMessage<InboundOpaque<'a>>),
+    /// A record that has been decrypted.
+    Decrypted(EncodedMessage<&'a [u8]>),
+}
+
+impl<'a> InboundRecord<'a> {
+    /// Take the encrypted message out of this record, if it is yet to be decrypted.
+    ///
+    /// The record is left holding an empty payload.
+    pub fn take_encrypted(&mut self) -> Option<EncodedMessage<InboundOpaque<'a>>> {
+        match self {
+            Self::Encrypted(msg) => Some(EncodedMessage::new(
+                msg.typ,
+                msg.version,
+                InboundOpaque(core::mem::take(&mut msg.payload.0)),
+            )),
+            Self::Decrypted(_) => None,
+        }
+    }
 }
 
 /// Objects with this trait can encrypt TLS messages.
@@ -187,6 +231,92 @@ pub trait MessageEncrypter: Send + Sync {
     /// message to [`Self::encrypt()`] in chunks of length `F - A`.  Each `encrypt()`
     /// is then free to pad or otherwise transform the length at its option.
     fn encrypted_payload_len(&self, payload_len: usize) -> usize;
+
+    /// Encrypt `msg` like [`Self::encrypt()`], adding `padding` bytes of TLS1.3
+    /// record padding.
+    ///
+    /// The padding is the run of zero bytes which follows the content type in a
+    /// `TLSInnerPlaintext` (see [RFC 9846 section 5.4]).  `out` must be at least
+    /// `encrypted_payload_len(msg.payload.len() + padding)` bytes long.
+    ///
+    /// Rustls only calls this with a non-zero `padding` if [`Self::supports_padding()`]
+    /// returns `true`.  The default implementation supports only zero padding, for which
+    /// it delegates to [`Self::encrypt()`].
+    ///
+    /// [RFC 9846 section 5.4]: https://datatracker.ietf.org/doc/html/rfc9846#section-5.4
+    fn encrypt_padded<'a>(
+        &mut self,
+        msg: EncodedMessage<OutboundPlain<'_>>,
+        padding: usize,
+        seq: u64,
+        out: &'a mut [u8],
+    ) -> Result<EncodedMessage<&'a [u8]>, Error> {
+        match padding {
+            0 => self.encrypt(msg, seq, out),
+            _ => Err(Error::EncryptError),
+        }
+    }
+
+    /// Return `true` if [`Self::encrypt_padded()`] can add record padding.
+    ///
+    /// This should only be `true` for TLS1.3 encrypters.  The default is `false`.
+    fn supports_padding(&self) -> bool {
+        false
+    }
+
+    /// Encrypt each of `records` in turn, using consecutive sequence numbers starting at `seq`.
+    ///
+    /// Record `i` is encrypted as [`Self::encrypt_padded()`] would with sequence number
+    /// `seq + i`, into that record's `out`, and the result is stored in its `encrypted`
+    /// field.  If an error is returned, the records before the one that failed have been
+    /// encrypted, and the rest have not.
+    ///
+    /// Rustls uses this for the records it sends, in runs of up to 16.  The default
+    /// implementation calls [`Self::encrypt_padded()`] for each record.  Override it
+    /// where encrypting several records at once is cheaper.
+    fn encrypt_batch(&mut self, records: &mut [OutboundRecord<'_>], seq: u64) -> Result<(), Error> {
+        for (record, seq) in records.iter_mut().zip(seq..) {
+            let encrypted =
+                self.encrypt_padded(record.msg.clone(), record.padding, seq, record.out)?;
+            let (typ, version, len) = (encrypted.typ, encrypted.version, encrypted.payload.len());
+            record.encrypted = Some(EncodedMessage::new(typ, version, len));
+        }
+        Ok(())
+    }
+}
+
+/// A record in a batch passed to [`MessageEncrypter::encrypt_batch()`].
+#[non_exhaustive]
+pub struct OutboundRecord<'a> {
+    /// The message to encrypt.
+    pub msg: EncodedMessage<OutboundPlain<'a>>,
+    /// The number of bytes of TLS1.3 record padding to add.
+    ///
+    /// This is only non-zero if [`MessageEncrypter::supports_padding()`] returns `true`.
+    pub padding: usize,
+    /// Where to write the encrypted payload.
+    ///
+    /// As for [`MessageEncrypter::encrypt_padded()`], the encrypted payload is written
+    /// to the front of `out`, which is at least
+    /// `encrypted_payload_len(msg.payload.len() + padding)` bytes long.
+    pub out: &'a mut [u8],
+    /// The resulting record, once encrypted.
+    ///
+    /// Its `typ` and `version` are what the record header should carry on the wire, and
+    /// its `payload` is the length of the encrypted payload written to `out`.
+    pub encrypted: Option<EncodedMessage<usize>>,
+}
+
+impl<'a> OutboundRecord<'a> {
+    /// Make a record to encrypt `msg`, with `padding` bytes of padding, into `out`.
+    pub fn new(msg: EncodedMessage<OutboundPlain<'a>>, padding: usize, out: &'a mut [u8]) -> Self {
+        Self {
+            msg,
+            padding,
+            out,
+            encrypted: None,
+        }
+    }
 }
 
 /// A write or read IV.
@@ -1,10 +1,12 @@
 use alloc::boxed::Box;
 use alloc::vec::Vec;
 use core::cmp::min;
+use core::mem;
 
+use crate::conn::TlsOutputBuffer;
 use crate::crypto::cipher::{
     EncodedMessage, InboundOpaque, MessageDecrypter, MessageEncrypter, OutboundPlain,
-    encode_record_header,
+    OutboundRecord, encode_record_header,
 };
 use crate::error::Error;
 use crate::msgs::{HEADER_SIZE, HandshakeAlignedProof};
@@ -29,27 +31,29 @@ impl EncryptionState {
 
     /// Encrypt a TLS message, returning the fully-encoded record.
     ///
-    /// `plain` is a TLS message we'd like to send.  This function
+    /// `plain` is a TLS message we'd like to send, and `padding` the number of
+    /// bytes of TLS1.3 record padding to add to it.  `padding` is ignored if the
+    /// encrypter does not [support padding](Self::supports_padding).  This function
     /// panics if the requisite keying material hasn't been established yet.
     ///
-    /// The result including framing is appended to `output`.
+    /// The result including framing is appended to `output`.  If `output` lacks
+    /// space for it, nothing is written or encrypted.
     pub(crate) fn encrypt_outgoing(
         &mut self,
         plain: EncodedMessage<OutboundPlain<'_>>,
-        output: &mut Vec<u8>,
-    ) {
-        // Contents are fully overwritten below, so zeroing is pure cost.
-        // A fresh buffer gets pre-zeroed memory straight from the allocator
-        // while a reused one zeroes only what `resize` grows.
-        let needed = HEADER_SIZE + self.encrypted_len(plain.payload.len());
-        let start = output.len();
-        output.resize(start + needed, 0);
-        let written = self.encrypt_outgoing_into(plain, &mut output[start..]);
-        debug_assert_eq!(
+        padding: usize,
+        output: &mut dyn TlsOutputBuffer,
+    ) -> Result<(), Error> {
+        let padding = self.usable_padding(padding);
+        let needed = HEADER_SIZE + self.encrypted_len(plain.payload.len() + padding);
+        let written = self.encrypt_outgoing_into(plain, padding, output.extend(needed)?);
+        // The record is framed with its actual length, so a shortfall here would
+        // leave unwritten bytes in the output.
+        assert_eq!(
             written, needed,
             "MessageEncrypter::encrypt() returned wrong length"
         );
-        output.truncate(start + written);
+        Ok(())
     }
 
     /// Encrypt a TLS message directly into `out`, returning the encoded
@@ -57,16 +61,20 @@ impl EncryptionState {
     ///
     /// The record, header included, is written to the front of `out`,
     /// which must be at least `HEADER_SIZE` plus
-    /// [`Self::encrypted_len()`](Self::encrypted_len) bytes long.
+    /// [`Self::encrypted_len()`](Self::encrypted_len) of the payload and
+    /// `padding` bytes long.  As for [`Self::encrypt_outgoing()`], `padding`
+    /// is ignored if the encrypter does not support padding.
     ///
     /// This function panics if the requisite keying material hasn't been
     /// established yet.
     pub(crate) fn encrypt_outgoing_into(
         &mut self,
         plain: EncodedMessage<OutboundPlain<'_>>,
+        padding: usize,
         out: &mut [u8],
     ) -> usize {
         assert!(self.pre_encrypt_action(0) != Some(PreEncryptAction::Refuse));
+        let padding = self.usable_padding(padding);
         let encrypter = self.message_encrypter.as_mut().unwrap();
 
         let seq = self.write_seq;
@@ -74,9 +82,11 @@ impl EncryptionState {
 
         #[cfg(debug_assertions)]
         let (out_ptr, out_len) = (out.as_ptr(), out.len());
-        let encrypted = encrypter
-            .encrypt(plain, seq, &mut out[HEADER_SIZE..])
-            .unwrap();
+        let encrypted = match padding {
+            0 => encrypter.encrypt(plain, seq, &mut out[HEADER_SIZE..]),
+            _ => encrypter.encrypt_padded(plain, padding, seq, &mut out[HEADER_SIZE..]),
+        }
+        .unwrap();
 
         #[cfg(debug_assertions)]
         {
@@ -97,6 +107,92 @@ impl EncryptionState {
         HEADER_SIZE + len
     }
 
+    /// Encrypt several TLS messages with one call to the `MessageEncrypter`,
+    /// appending the fully-encoded records to `output`.
+    ///
+    /// `batch` holds each message with the number of bytes of TLS1.3 record
+    /// padding to add to it.  If `output` lacks space for all the records,
+    /// nothing is written or encrypted.  Like [`Self::encrypt_outgoing()`],
+    /// padding is ignored if the encrypter does not support it, and this
+    /// function panics if the requisite keying material hasn't been
+    /// established yet.
+    pub(crate) fn encrypt_outgoing_batch(
+        &mut self,
+        batch: &[(EncodedMessage<OutboundPlain<'_>>, usize)],
+        output: &mut dyn TlsOutputBuffer,
+    ) -> Result<(), Error> {
+        let last = match batch {
+            [] => return Ok(()),
+            // Not worth the bookkeeping below.
+            [(plain, padding)] => return self.encrypt_outgoing(plain.clone(), *padding, output),
+            _ => batch.len() as u64 - 1,
+        };
+        assert!(self.pre_encrypt_action(last) != Some(PreEncryptAction::Refuse));
+
+        let record_len = |plain: &EncodedMessage<OutboundPlain<'_>>, padding: usize| {
+            HEADER_SIZE + self.encrypted_len(plain.payload.len() + padding)
+        };
+        let batch = batch
+            .iter()
+            .map(|(plain, padding)| (plain, self.usable_padding(*padding)))
+            .collect::<Vec<_>>();
+        let needed = batch
+            .iter()
+            .map(|(plain, padding)| record_len(plain, *padding))
+            .sum();
+        let out = output.extend(needed)?;
+
+        let mut records = Vec::with_capacity(batch.len());
+        let mut rest = &mut *out;
+        for &(plain, padding) in &batch {
+            let len = record_len(plain, padding);
+            let (record, tail) = mem::take(&mut rest).split_at_mut(len);
+            rest = tail;
+            records.push(OutboundRecord::new(
+                plain.clone(),
+                padding,
+                &mut record[HEADER_SIZE..],
+            ));
+        }
+
+        let seq = self.write_seq;
+        self.write_seq += batch.len() as u64;
+        self.message_encrypter
+            .as_mut()
+            .unwrap()
+            .encrypt_batch(&mut records, seq)
+            .unwrap();
+
+        let encrypted = records
+            .into_iter()
+            .map(|record| {
+                let encrypted = record
+                    .encrypted
+                    .expect("MessageEncrypter::encrypt_batch() skipped a record");
+                // Each record is framed at its expected length, so a shortfall here
+                // would leave unwritten bytes in the output.
+                assert_eq!(
+                    encrypted.payload,
+                    record.out.len(),
+                    "MessageEncrypter::encrypt_batch() returned wrong length"
+                );
+                encrypted
+            })
+            .collect::<Vec<_>>();
+
+        let mut rest = out;
+        for encrypted in encrypted {
+            let (record, tail) = mem::take(&mut rest).split_at_mut(HEADER_SIZE + encrypted.payload);
+            rest = tail;
+            record[..HEADER_SIZE].copy_from_slice(&encode_record_header(
+                encrypted.typ,
+                encrypted.version,
+                encrypted.payload as u16,
+            ));
+        }
+        Ok(())
+    }
+
     /// Set and start using the given `MessageEncrypter` for future outgoing
     /// message encryption.
     pub(crate) fn set_message_encrypter(
@@ -135,6 +231,21 @@ impl EncryptionState {
         self.encrypted_len(0)
     }
 
+    /// Whether the current `MessageEncrypter` can add TLS1.3 record padding.
+    pub(crate) fn supports_padding(&self) -> bool {
+        self.message_encrypter
+            .as_ref()
+            .is_some_and(|enc| enc.supports_padding())
+    }
+
+    /// `padding`, or zero if the current `MessageEncrypter` cannot add padding.
+    fn usable_padding(&self, padding: usize) -> usize {
+        match self.supports_padding() {
+            true => padding,
+            false => 0,
+        }
+    }
+
     pub(crate) fn is_encrypting(&self) -> bool {
         self.message_encrypter.is_some()
     }
@@ -142,6 +253,11 @@ impl EncryptionState {
     pub(crate) fn write_seq(&self) -> u64 {
         self.write_seq
     }
+
+    /// Continue from `write_seq`, when restoring a serialized connection.
+    pub(crate) fn restore_write_seq(&mut self, write_seq: u64) {
+        self.write_seq = write_seq;
+    }
 }
 
 /// Record layer that tracks decryption keys.
@@ -253,6 +369,12 @@ impl DecryptionState {
         self.read_seq
     }
 
+    /// Continue from `read_seq`, when restoring a serialized connection.
+    pub(crate) fn restore_read_seq(&mut self, read_seq: u64) {
+        self.read_seq = read_seq;
+        self.has_decrypted = true;
+    }
+
     fn doing_trial_decryption(&mut self, requested: usize) -> bool {
         match self
             .trial_decryption_len
@@ -301,7 +423,7 @@ const SEQ_HARD_LIMIT: u64 = u64::MAX - 1;
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::crypto::cipher::EncodableVersion;
+    use crate::crypto::cipher::{EncodableVersion, EncryptBuffer};
     use crate::enums::{ContentType, ProtocolVersion};
     use crate::msgs::Deframer;
 
@@ -352,4 +474,59 @@ mod tests {
         assert_eq!(record_layer.read_seq, 0);
         assert!(record_layer.has_decrypted());
     }
+
+    #[test]
+    fn test_padding_ignored_without_encrypter_support() {
+        struct PassThroughEncrypter;
+        impl MessageEncrypter for PassThroughEncrypter {
+            fn encrypt<'a>(
+                &mut self,
+                msg: EncodedMessage<OutboundPlain<'_>>,
+                _: u64,
+                out: &'a mut [u8],
+            ) -> Result<EncodedMessage<&'a [u8]>, Error> {
+                let mut payload = EncryptBuffer::new(out, msg.payload.len())?;
+                payload.extend_from_chunks(&msg.payload);
+                Ok(EncodedMessage {
+                    typ: msg.typ,
+                    version: msg.version,
+                    payload: payload.into_written(),
+                })
+            }
+
+            fn encrypted_payload_len(&self, payload_len: usize) -> usize {
+                payload_len
+            }
+        }
+
+        let mut record_layer = EncryptionState::new();
+        record_layer.set_message_encrypter(Box::new(PassThroughEncrypter), u64::MAX);
+        assert!(!record_layer.supports_padding());
+
+        let plain = || {
+            EncodedMessage::new(
+                ContentType::ApplicationData,
+                EncodableVersion::Legacy(ProtocolVersion::TLSv1_2),
+                OutboundPlain::Single(b"hello"),
+            )
+        };
+
+        // Padding is dropped rather than failing in `encrypt_padded()`.
+        let mut output = Vec::new();
+        record_layer
+            .encrypt_outgoing(plain(), 16, &mut output)
+            .unwrap();
+        assert_eq!(output, b"\x17\x03\x03\x00\x05hello");
+        assert_eq!(record_layer.write_seq(), 1);
+
+        output.clear();
+        record_layer
+            .encrypt_outgoing_batch(&[(plain(), 16), (plain(), 0)], &mut output)
+            .unwrap();
+        assert_eq!(
+            output,
+            b"\x17\x03\x03\x00\x05hello\x17\x03\x03\x00\x05hello"
+        );
+        assert_eq!(record_layer.write_seq(), 3);
+    }
 }
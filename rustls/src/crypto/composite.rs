@@ -0,0 +1,628 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt::{self, Debug};
+
+use pki_types::{
+    AlgorithmIdentifier, FipsStatus, InvalidSignature, SignatureVerificationAlgorithm,
+    SubjectPublicKeyInfoDer,
+};
+
+use super::hash::Hash;
+use super::{SignatureScheme, Signer, SigningKey, public_key_to_spki};
+use crate::error::{ApiMisuse, Error};
+use crate::sync::Arc;
+use crate::x509;
+
+/// A generalization of composite signature algorithms.
+///
+/// A composite signature algorithm combines a post-quantum signature algorithm (typically
+/// ML-DSA) with a classical one (such as ECDSA or Ed25519), under a single TLS
+/// [`SignatureScheme`] and a single `SubjectPublicKeyInfo` algorithm identifier.
+/// A composite signature is only valid if both component signatures are valid.
+///
+/// This follows the construction in [draft-ietf-lamps-pq-composite-sigs]:
+///
+/// - the composite public key is the post-quantum public key followed by the classical
+///   public key;
+/// - the composite signature is the post-quantum signature followed by the classical
+///   signature;
+/// - both components sign the message representative
+///   `M' = Prefix || Label || len(ctx) || ctx || PH(M)`, where `ctx` is always empty;
+/// - the post-quantum component additionally signs with `Label` as its ML-DSA context
+///   string, so it is signed and verified via [`ContextSigningKey`] and
+///   [`ContextVerificationAlgorithm`] rather than [`Signer`] and
+///   [`SignatureVerificationAlgorithm`].
+///
+/// The post-quantum component's public key and signature must have a fixed length, which
+/// is used to split the composite encodings.
+///
+/// This type implements [`SignatureVerificationAlgorithm`], so it can be used directly
+/// in a [`WebPkiSupportedAlgorithms`].  Use [`CompositeSigningKey`] to produce signatures.
+///
+/// [draft-ietf-lamps-pq-composite-sigs]: https://datatracker.ietf.org/doc/draft-ietf-lamps-pq-composite-sigs/
+/// [`WebPkiSupportedAlgorithms`]: crate::crypto::WebPkiSupportedAlgorithms
+#[expect(clippy::exhaustive_structs)]
+pub struct CompositeAlgorithm {
+    /// TLS `SignatureScheme` for this composite algorithm.
+    pub scheme: SignatureScheme,
+    /// The `AlgorithmIdentifier` of composite public keys and signatures.
+    pub alg_id: AlgorithmIdentifier,
+    /// Domain separation label for this composite algorithm.
+    pub label: &'static [u8],
+    /// Hash function used to pre-hash the message.
+    pub prehash: &'static dyn Hash,
+    /// Post-quantum signature component.
+    pub post_quantum: PostQuantumComponent,
+    /// Classical signature component.
+    pub classical: CompositeComponent,
+}
+
+impl CompositeAlgorithm {
+    /// Compute the message representative `M'` which both components sign.
+    fn message_representative(&self, message: &[u8]) -> Vec<u8> {
+        let prehash = self.prehash.hash(message);
+        let mut m = Vec::with_capacity(
+            COMPOSITE_PREFIX.len() + self.label.len() + 1 + prehash.as_ref().len(),
+        );
+        m.extend_from_slice(COMPOSITE_PREFIX);
+        m.extend_from_slice(self.label);
+        // empty context string
+        m.push(0);
+        m.extend_from_slice(prehash.as_ref());
+        m
+    }
+
+    fn split_public_key<'a>(&self, public_key: &'a [u8]) -> Option<(&'a [u8], &'a [u8])> {
+        match public_key.len() > self.post_quantum.public_key_len {
+            true => Some(public_key.split_at(self.post_quantum.public_key_len)),
+            false => None,
+        }
+    }
+
+    fn split_signature<'a>(&self, signature: &'a [u8]) -> Option<(&'a [u8], &'a [u8])> {
+        match signature.len() > self.post_quantum.signature_len {
+            true => Some(signature.split_at(self.post_quantum.signature_len)),
+            false => None,
+        }
+    }
+}
+
+impl SignatureVerificationAlgorithm for CompositeAlgorithm {
+    fn verify_signature(
+        &self,
+        public_key: &[u8],
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<(), InvalidSignature> {
+        let (pq_public_key, classical_public_key) = self
+            .split_public_key(public_key)
+            .ok_or(InvalidSignature)?;
+        let (pq_signature, classical_signature) = self
+            .split_signature(signature)
+            .ok_or(InvalidSignature)?;
+
+        let m = self.message_representative(message);
+
+        // Both components are always verified, so that timing does not reveal
+        // which one failed.
+        let pq = self
+            .post_quantum
+            .verifier
+            .verify_signature(pq_public_key, &m, self.label, pq_signature);
+        let classical = self
+            .classical
+            .verifier
+            .verify_signature(classical_public_key, &m, classical_signature);
+
+        pq.and(classical)
+    }
+
+    fn public_key_alg_id(&self) -> AlgorithmIdentifier {
+        self.alg_id
+    }
+
+    fn signature_alg_id(&self) -> AlgorithmIdentifier {
+        self.alg_id
+    }
+
+    fn fips_status(&self) -> FipsStatus {
+        // Composite signature algorithms are not (yet) FIPS-approved, irrespective
+        // of the status of their components.
+        FipsStatus::Unvalidated
+    }
+}
+
+impl Debug for CompositeAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompositeAlgorithm")
+            .field("scheme", &self.scheme)
+            .field("alg_id", &self.alg_id)
+            .field("post_quantum", &self.post_quantum)
+            .field("classical", &self.classical)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The post-quantum half of a [`CompositeAlgorithm`].
+#[expect(clippy::exhaustive_structs)]
+#[derive(Debug)]
+pub struct PostQuantumComponent {
+    /// The algorithm used to verify this component's signatures.
+    ///
+    /// This is given the composite algorithm's label as its context string.
+    pub verifier: &'static dyn ContextVerificationAlgorithm,
+    /// Length of this component's public key.
+    pub public_key_len: usize,
+    /// Length of this component's signature.
+    pub signature_len: usize,
+}
+
+/// A signature verification algorithm which takes a context string, such as ML-DSA.
+///
+/// See [FIPS 204] section 5.2 for the definition of the ML-DSA context string.
+///
+/// [FIPS 204]: https://csrc.nist.gov/pubs/fips/204/final
+pub trait ContextVerificationAlgorithm: Debug + Send + Sync {
+    /// Verify `signature` over `message` and `context`, using the raw `public_key`.
+    fn verify_signature(
+        &self,
+        public_key: &[u8],
+        message: &[u8],
+        context: &[u8],
+        signature: &[u8],
+    ) -> Result<(), InvalidSignature>;
+}
+
+/// A signing key which signs with a context string, such as an ML-DSA key.
+///
+/// This is the post-quantum half of a [`CompositeSigningKey`].
+pub trait ContextSigningKey: Debug + Send + Sync {
+    /// Sign `message` with `context` as the context string.
+    fn sign(&self, message: &[u8], context: &[u8]) -> Result<Vec<u8>, Error>;
+
+    /// Get the `SubjectPublicKeyInfo` for this key.
+    fn public_key(&self) -> Option<SubjectPublicKeyInfoDer<'_>>;
+}
+
+/// The classical half of a [`CompositeAlgorithm`].
+#[expect(clippy::exhaustive_structs)]
+#[derive(Debug)]
+pub struct CompositeComponent {
+    /// The `SignatureScheme` used when signing with this component.
+    ///
+    /// This is passed to the component's [`SigningKey::choose_scheme()`].
+    pub scheme: SignatureScheme,
+    /// The algorithm used to verify this component's signatures.
+    pub verifier: &'static dyn SignatureVerificationAlgorithm,
+}
+
+/// A [`SigningKey`] for a [`CompositeAlgorithm`], built from two component signing keys.
+#[derive(Debug)]
+pub struct CompositeSigningKey {
+    algorithm: &'static CompositeAlgorithm,
+    post_quantum: Arc<dyn ContextSigningKey>,
+    classical: Box<dyn SigningKey>,
+    public_key: Vec<u8>,
+}
+
+impl CompositeSigningKey {
+    /// Make a new `CompositeSigningKey` from its component keys.
+    ///
+    /// Both component keys must be able to produce their public key, and
+    /// the post-quantum key's public key must have the expected length.  The
+    /// classical key must support its component's `scheme`.
+    pub fn new(
+        algorithm: &'static CompositeAlgorithm,
+        post_quantum: Box<dyn ContextSigningKey>,
+        classical: Box<dyn SigningKey>,
+    ) -> Result<Self, Error> {
+        let pq_public_key = post_quantum
+            .public_key()
+            .and_then(|spki| x509::subject_public_key(spki.as_ref()).map(|pk| pk.to_vec()))
+            .ok_or(ApiMisuse::InvalidCompositeComponent)?;
+        if pq_public_key.len() != algorithm.post_quantum.public_key_len {
+            return Err(ApiMisuse::InvalidCompositeComponent.into());
+        }
+        let classical_public_key = component_public_key(classical.as_ref(), &algorithm.classical)?;
+
+        let public_key = [pq_public_key, classical_public_key].concat();
+        Ok(Self {
+            algorithm,
+            post_quantum: Arc::from(post_quantum),
+            classical,
+            public_key,
+        })
+    }
+}
+
+impl SigningKey for CompositeSigningKey {
+    fn choose_scheme(&self, offered: &[SignatureScheme]) -> Option<Box<dyn Signer>> {
+        if !offered.contains(&self.algorithm.scheme) {
+            return None;
+        }
+
+        Some(Box::new(CompositeSigner {
+            algorithm: self.algorithm,
+            post_quantum: self.post_quantum.clone(),
+            classical: self
+                .classical
+                .choose_scheme(&[self.algorithm.classical.scheme])?,
+        }))
+    }
+
+    fn public_key(&self) -> Option<SubjectPublicKeyInfoDer<'_>> {
+        Some(public_key_to_spki(&self.algorithm.alg_id, &self.public_key))
+    }
+}
+
+fn component_public_key(
+    key: &dyn SigningKey,
+    component: &CompositeComponent,
+) -> Result<Vec<u8>, Error> {
+    if key
+        .choose_scheme(&[component.scheme])
+        .is_none()
+    {
+        return Err(ApiMisuse::InvalidCompositeComponent.into());
+    }
+
+    let spki = key
+        .public_key()
+        .ok_or(ApiMisuse::InvalidCompositeComponent)?;
+    x509::subject_public_key(spki.as_ref())
+        .map(|public_key| public_key.to_vec())
+        .ok_or_else(|| ApiMisuse::InvalidCompositeComponent.into())
+}
+
+#[derive(Debug)]
+struct CompositeSigner {
+    algorithm: &'static CompositeAlgorithm,
+    post_quantum: Arc<dyn ContextSigningKey>,
+    classical: Box<dyn Signer>,
+}
+
+impl Signer for CompositeSigner {
+    fn sign(self: Box<Self>, message: &[u8]) -> Result<Vec<u8>, Error> {
+        let m = self
+            .algorithm
+            .message_representative(message);
+
+        let mut signature = self
+            .post_quantum
+            .sign(&m, self.algorithm.label)?;
+        if signature.len()
+            != self
+                .algorithm
+                .post_quantum
+                .signature_len
+        {
+            return Err(Error::General(
+                "unexpected post-quantum signature length".into(),
+            ));
+        }
+        signature.extend(self.classical.sign(&m)?);
+        Ok(signature)
+    }
+
+    fn scheme(&self) -> SignatureScheme {
+        self.algorithm.scheme
+    }
+}
+
+/// The `Prefix` value from draft-ietf-lamps-pq-composite-sigs: "CompositeAlgorithmSignatures2025".
+const COMPOSITE_PREFIX: &[u8] = b"CompositeAlgorithmSignatures2025";
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+    use crate::crypto::HashAlgorithm;
+    use crate::crypto::hash::{Context, Output};
+
+    #[test]
+    fn message_representative() {
+        let m = TEST_ALGORITHM.message_representative(b"hello");
+        assert_eq!(
+            m,
+            [
+                COMPOSITE_PREFIX,
+                TEST_LABEL,
+                &[0],
+                &[b'h' ^ 0xff, b'e' ^ 0xff]
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn verify_requires_both_components() {
+        let public_key = [0x01, 0x02, 0x03];
+        let message = b"message";
+        let m = TEST_ALGORITHM.message_representative(message);
+
+        assert!(
+            TEST_ALGORITHM
+                .verify_signature(&public_key, message, &sig(&m, 1, 2))
+                .is_ok()
+        );
+        assert!(
+            TEST_ALGORITHM
+                .verify_signature(&public_key, message, &sig(&m, 9, 2))
+                .is_err()
+        );
+        assert!(
+            TEST_ALGORITHM
+                .verify_signature(&public_key, message, &sig(&m, 1, 9))
+                .is_err()
+        );
+        assert!(
+            TEST_ALGORITHM
+                .verify_signature(&public_key, b"other", &sig(&m, 1, 2))
+                .is_err()
+        );
+        assert!(
+            TEST_ALGORITHM
+                .verify_signature(&public_key[..2], message, &sig(&m, 1, 2))
+                .is_err()
+        );
+        assert!(
+            TEST_ALGORITHM
+                .verify_signature(&public_key, message, &[1])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn post_quantum_component_uses_label_as_context() {
+        let public_key = [0x01, 0x02, 0x03];
+        let message = b"message";
+        let m = TEST_ALGORITHM.message_representative(message);
+
+        // as `sig()`, but with the wrong context string
+        let mut pq = vec![1, 0x01, 0x02];
+        pq.extend_from_slice(&m);
+        pq.extend_from_slice(&[0; TEST_LABEL.len()]);
+        let mut classical = vec![2, 0x03];
+        classical.extend_from_slice(&m);
+        assert!(
+            TEST_ALGORITHM
+                .verify_signature(&public_key, message, &[pq, classical].concat())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn sign() {
+        let key = CompositeSigningKey::new(
+            &TEST_ALGORITHM,
+            Box::new(TestContextKey),
+            Box::new(TestClassicalKey),
+        )
+        .unwrap();
+        assert_eq!(
+            key.public_key().unwrap(),
+            public_key_to_spki(&TEST_ALGORITHM.alg_id, [0x01, 0x02, 0x03])
+        );
+        assert!(
+            key.choose_scheme(&[SignatureScheme(0xfe02)])
+                .is_none()
+        );
+
+        let signer = key
+            .choose_scheme(&[TEST_ALGORITHM.scheme])
+            .unwrap();
+        assert_eq!(signer.scheme(), TEST_ALGORITHM.scheme);
+        let m = TEST_ALGORITHM.message_representative(b"message");
+        assert_eq!(signer.sign(b"message").unwrap(), sig(&m, 1, 2));
+    }
+
+    #[test]
+    fn new_checks_components() {
+        assert!(
+            CompositeSigningKey::new(
+                &TEST_ALGORITHM,
+                Box::new(TestContextKey),
+                Box::new(TestClassicalKey),
+            )
+            .is_ok()
+        );
+        assert!(
+            CompositeSigningKey::new(
+                &TEST_ALGORITHM,
+                Box::new(TestContextKey),
+                Box::new(NoPublicKey),
+            )
+            .is_err()
+        );
+        assert!(
+            CompositeSigningKey::new(
+                &TEST_ALGORITHM_LONG_KEY,
+                Box::new(TestContextKey),
+                Box::new(TestClassicalKey),
+            )
+            .is_err()
+        );
+    }
+
+    /// Build a test composite signature: each component signature is a one-byte tag,
+    /// its public key, and the message.  The post-quantum component also covers the
+    /// context string.
+    fn sig(m: &[u8], pq_tag: u8, classical_tag: u8) -> Vec<u8> {
+        let mut pq = vec![pq_tag, 0x01, 0x02];
+        pq.extend_from_slice(m);
+        pq.extend_from_slice(TEST_LABEL);
+        let mut classical = vec![classical_tag, 0x03];
+        classical.extend_from_slice(m);
+        [pq, classical].concat()
+    }
+
+    static TEST_ALGORITHM: CompositeAlgorithm = CompositeAlgorithm {
+        scheme: SignatureScheme(0xfefe),
+        alg_id: AlgorithmIdentifier::from_slice(&[0x06, 0x01, 0x2a]),
+        label: TEST_LABEL,
+        prehash: &TestHash,
+        post_quantum: PostQuantumComponent {
+            verifier: &TestContextVerifier,
+            public_key_len: 2,
+            signature_len: TEST_PQ_SIGNATURE_LEN,
+        },
+        classical: CompositeComponent {
+            scheme: SignatureScheme(0xfe02),
+            verifier: &TestVerifier { tag: 2 },
+        },
+    };
+
+    /// As `TEST_ALGORITHM`, but expecting a longer post-quantum public key.
+    static TEST_ALGORITHM_LONG_KEY: CompositeAlgorithm = CompositeAlgorithm {
+        post_quantum: PostQuantumComponent {
+            verifier: &TestContextVerifier,
+            public_key_len: 3,
+            signature_len: TEST_PQ_SIGNATURE_LEN,
+        },
+        classical: CompositeComponent {
+            scheme: SignatureScheme(0xfe02),
+            verifier: &TestVerifier { tag: 2 },
+        },
+        ..TEST_ALGORITHM
+    };
+
+    const TEST_LABEL: &[u8] = b"TEST-LABEL";
+
+    // tag + public key + message representative + context
+    const TEST_PQ_SIGNATURE_LEN: usize =
+        1 + 2 + COMPOSITE_PREFIX.len() + TEST_LABEL.len() + 1 + 2 + TEST_LABEL.len();
+
+    /// Checks post-quantum signatures made by [`sig()`].
+    #[derive(Debug)]
+    struct TestContextVerifier;
+
+    impl ContextVerificationAlgorithm for TestContextVerifier {
+        fn verify_signature(
+            &self,
+            public_key: &[u8],
+            message: &[u8],
+            context: &[u8],
+            signature: &[u8],
+        ) -> Result<(), InvalidSignature> {
+            match signature.split_first() {
+                Some((1, rest)) if rest == [public_key, message, context].concat() => Ok(()),
+                _ => Err(InvalidSignature),
+            }
+        }
+    }
+
+    /// Makes post-quantum signatures like [`sig()`].
+    #[derive(Debug)]
+    struct TestContextKey;
+
+    impl ContextSigningKey for TestContextKey {
+        fn sign(&self, message: &[u8], context: &[u8]) -> Result<Vec<u8>, Error> {
+            Ok([&[1, 0x01, 0x02], message, context].concat())
+        }
+
+        fn public_key(&self) -> Option<SubjectPublicKeyInfoDer<'_>> {
+            Some(public_key_to_spki(&TEST_ALGORITHM.alg_id, [0x01, 0x02]))
+        }
+    }
+
+    /// Makes classical signatures like [`sig()`].
+    #[derive(Debug)]
+    struct TestClassicalKey;
+
+    impl SigningKey for TestClassicalKey {
+        fn choose_scheme(&self, offered: &[SignatureScheme]) -> Option<Box<dyn Signer>> {
+            match offered.contains(&SignatureScheme(0xfe02)) {
+                true => Some(Box::new(TestClassicalSigner)),
+                false => None,
+            }
+        }
+
+        fn public_key(&self) -> Option<SubjectPublicKeyInfoDer<'_>> {
+            Some(public_key_to_spki(&TEST_ALGORITHM.alg_id, [0x03]))
+        }
+    }
+
+    #[derive(Debug)]
+    struct TestClassicalSigner;
+
+    impl Signer for TestClassicalSigner {
+        fn sign(self: Box<Self>, message: &[u8]) -> Result<Vec<u8>, Error> {
+            Ok([&[2, 0x03], message].concat())
+        }
+
+        fn scheme(&self) -> SignatureScheme {
+            SignatureScheme(0xfe02)
+        }
+    }
+
+    /// A classical key which cannot produce its public key.
+    #[derive(Debug)]
+    struct NoPublicKey;
+
+    impl SigningKey for NoPublicKey {
+        fn choose_scheme(&self, offered: &[SignatureScheme]) -> Option<Box<dyn Signer>> {
+            TestClassicalKey.choose_scheme(offered)
+        }
+
+        fn public_key(&self) -> Option<SubjectPublicKeyInfoDer<'_>> {
+            None
+        }
+    }
+
+    /// Checks classical signatures made by [`sig()`].
+    #[derive(Debug)]
+    struct TestVerifier {
+        tag: u8,
+    }
+
+    impl SignatureVerificationAlgorithm for TestVerifier {
+        fn verify_signature(
+            &self,
+            public_key: &[u8],
+            message: &[u8],
+            signature: &[u8],
+        ) -> Result<(), InvalidSignature> {
+            match signature.split_first() {
+                Some((tag, rest)) if *tag == self.tag && rest == [public_key, message].concat() => {
+                    Ok(())
+                }
+                _ => Err(InvalidSignature),
+            }
+        }
+
+        fn public_key_alg_id(&self) -> AlgorithmIdentifier {
+            AlgorithmIdentifier::from_slice(&[])
+        }
+
+        fn signature_alg_id(&self) -> AlgorithmIdentifier {
+            AlgorithmIdentifier::from_slice(&[])
+        }
+    }
+
+    /// A "hash" which inverts and truncates its input to two bytes.
+    struct TestHash;
+
+    impl Hash for TestHash {
+        fn start(&self) -> Box<dyn Context> {
+            unimplemented!()
+        }
+
+        fn hash(&self, data: &[u8]) -> Output {
+            let out = data
+                .iter()
+                .take(2)
+                .map(|b| b ^ 0xff)
+                .collect::<Vec<u8>>();
+            Output::new(&out)
+        }
+
+        fn output_len(&self) -> usize {
+            2
+        }
+
+        fn algorithm(&self) -> HashAlgorithm {
+            HashAlgorithm::NONE
+        }
+    }
+}
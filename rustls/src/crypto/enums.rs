@@ -385,6 +385,12 @@ enum_builder! {
         ML_DSA_44 => 0x0904,
         ML_DSA_65 => 0x0905,
         ML_DSA_87 => 0x0906,
+        // https://datatracker.ietf.org/doc/html/draft-reddy-tls-composite-mldsa#name-iana-considerations
+        MLDSA44_ECDSA_SECP256R1_SHA256 => 0x0907,
+        MLDSA65_ECDSA_SECP384R1_SHA512 => 0x0908,
+        MLDSA87_ECDSA_SECP384R1_SHA512 => 0x0909,
+        MLDSA44_ED25519 => 0x090a,
+        MLDSA65_ED25519 => 0x090b,
     }
 }
 
@@ -276,6 +276,30 @@ pub trait SupportedKxGroup: Send + Sync + Debug {
     fn fips(&self) -> FipsStatus {
         FipsStatus::Unvalidated
     }
+
+    /// Return `true` if completing this key exchange should be offered to the caller.
+    ///
+    /// This is for key exchanges that cannot (or should not) be completed synchronously
+    /// in-process: for example, where the ephemeral key agreement happens inside a secure
+    /// enclave or a separate key daemon.
+    ///
+    /// A TLS1.3 server driven through [`ServerHandshake`] that selects such a group
+    /// pauses at [`ServerHandshake::CompleteKeyExchange`] once the client's key share is
+    /// known.  The caller may then perform the key exchange however it likes, and resume
+    /// the handshake with the [`CompletedKeyExchange`].
+    ///
+    /// Elsewhere (including TLS1.2, DTLS, QUIC, clients, and buffered [`ServerConnection`]s)
+    /// the key exchange is completed synchronously via [`Self::start_and_complete()`], which
+    /// must therefore still be implemented.
+    ///
+    /// The default implementation returns `false`.
+    ///
+    /// [`ServerHandshake`]: crate::server::ServerHandshake
+    /// [`ServerHandshake::CompleteKeyExchange`]: crate::server::ServerHandshake::CompleteKeyExchange
+    /// [`ServerConnection`]: crate::server::ServerConnection
+    fn defers_completion(&self) -> bool {
+        false
+    }
 }
 
 /// Return value from [`SupportedKxGroup::start()`].
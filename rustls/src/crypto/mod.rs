@@ -27,6 +27,9 @@ pub mod cipher;
 mod enums;
 pub use enums::{CipherSuite, HashAlgorithm, SignatureAlgorithm, SignatureScheme};
 
+/// Composite signature interfaces.
+pub mod composite;
+
 /// Hashing interfaces.
 pub mod hash;
 
@@ -139,6 +142,9 @@ pub use crate::suites::CipherSuiteCommon;
 /// }
 /// ```
 ///
+/// For keys held in PKCS#11 tokens, the `pkcs11` module of the `rustls-util` crate (enabled
+/// by its `pkcs11` feature) provides a `KeyProvider` and `SigningKey` implementation.
+///
 /// ## References to the individual elements
 ///
 /// The elements are documented separately:
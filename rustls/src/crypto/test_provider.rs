@@ -373,11 +373,22 @@ impl MessageEncrypter for Tls13Cipher {
         seq: u64,
         out: &'a mut [u8],
     ) -> Result<EncodedMessage<&'a [u8]>, Error> {
-        let total_len = self.encrypted_payload_len(m.payload.len());
+        self.encrypt_padded(m, 0, seq, out)
+    }
+
+    fn encrypt_padded<'a>(
+        &mut self,
+        m: EncodedMessage<OutboundPlain<'_>>,
+        padding: usize,
+        seq: u64,
+        out: &'a mut [u8],
+    ) -> Result<EncodedMessage<&'a [u8]>, Error> {
+        let total_len = self.encrypted_payload_len(m.payload.len() + padding);
         let mut payload = EncryptBuffer::new(out, total_len)?;
 
         payload.extend_from_chunks(&m.payload);
         payload.extend_from_slice(&m.typ.to_array());
+        payload.extend_zeroed(padding);
 
         for (p, mask) in payload
             .as_mut()
@@ -400,6 +411,10 @@ impl MessageEncrypter for Tls13Cipher {
     fn encrypted_payload_len(&self, payload_len: usize) -> usize {
         payload_len + 1 + AEAD_OVERHEAD
     }
+
+    fn supports_padding(&self) -> bool {
+        true
+    }
 }
 
 impl MessageDecrypter for Tls13Cipher {
@@ -0,0 +1,451 @@
+//! Fragmentation, reassembly and acknowledgement of DTLS handshake messages.
+//!
+//! See <https://www.rfc-editor.org/rfc/rfc9147#section-5>.
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use super::record::RecordNumber;
+use crate::error::{Error, InvalidMessage};
+use crate::msgs::{Codec, Reader, U24};
+
+/// A handshake message we have sent, and which the peer may not have received.
+struct OutgoingMessage {
+    epoch: u64,
+    /// Contains the TLS encoding of the message: type, length and body.
+    encoded: Vec<u8>,
+    message_seq: u16,
+    /// Ranges of the body which are yet to be sent in the current transmission.
+    unsent: Vec<Range<usize>>,
+}
+
+impl OutgoingMessage {
+    fn body(&self) -> &[u8] {
+        &self.encoded[TLS_HEADER_LEN..]
+    }
+}
+
+/// A fragment of a message in the current flight, sent in one record.
+struct SentFragment {
+    record: RecordNumber,
+    message: usize,
+    range: Range<usize>,
+    acked: bool,
+}
+
+/// The handshake messages we most recently sent, until the peer acknowledges them.
+///
+/// See <https://www.rfc-editor.org/rfc/rfc9147#section-5.8>.
+#[derive(Default)]
+pub(super) struct Flight {
+    messages: Vec<OutgoingMessage>,
+    sent: Vec<SentFragment>,
+}
+
+impl Flight {
+    /// Add a message to this flight, to be sent in `epoch`.
+    ///
+    /// `encoded` is a TLS encoding of one handshake message.
+    pub(super) fn push(&mut self, epoch: u64, message_seq: u16, encoded: Vec<u8>) {
+        let len = encoded.len() - TLS_HEADER_LEN;
+        self.messages.push(OutgoingMessage {
+            epoch,
+            encoded,
+            message_seq,
+            unsent: alloc::vec![0..len],
+        });
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Forget all messages: they need no further transmission.
+    pub(super) fn clear(&mut self) {
+        self.messages.clear();
+        self.sent.clear();
+    }
+
+    /// Whether the peer has acknowledged every message in the flight.
+    pub(super) fn is_acked(&self) -> bool {
+        self.messages
+            .iter()
+            .enumerate()
+            .all(|(i, m)| Self::missing(&self.sent, i, m.body().len()).is_empty())
+    }
+
+    /// Whether any part of the flight is waiting to be (re)sent.
+    pub(super) fn has_unsent(&self) -> bool {
+        self.messages
+            .iter()
+            .any(|m| !m.unsent.is_empty())
+    }
+
+    /// The epoch of the next fragment to send.
+    pub(super) fn next_epoch(&self) -> Option<u64> {
+        self.messages
+            .iter()
+            .find(|m| !m.unsent.is_empty())
+            .map(|m| m.epoch)
+    }
+
+    /// Encode the next fragment to send, using at most `space` bytes.
+    ///
+    /// `record` is the number of the record which will carry it.  Returns `None`
+    /// if there is nothing to send, or `space` is too small.
+    pub(super) fn next_fragment(&mut self, space: usize, record: RecordNumber) -> Option<Vec<u8>> {
+        let max = space.checked_sub(DTLS_HEADER_LEN)?;
+        let (index, message) = self
+            .messages
+            .iter_mut()
+            .enumerate()
+            .find(|(_, m)| !m.unsent.is_empty())?;
+
+        let range = message.unsent[0].clone();
+        if max == 0 && !range.is_empty() {
+            return None;
+        }
+        let end = range.start + max.min(range.len());
+        match end == range.end {
+            true => {
+                message.unsent.remove(0);
+            }
+            false => message.unsent[0].start = end,
+        }
+
+        let range = range.start..end;
+        let mut out = Vec::with_capacity(DTLS_HEADER_LEN + range.len());
+        out.push(message.encoded[0]);
+        U24(message.body().len() as u32).encode(&mut out);
+        message.message_seq.encode(&mut out);
+        U24(range.start as u32).encode(&mut out);
+        U24(range.len() as u32).encode(&mut out);
+        out.extend_from_slice(&message.body()[range.clone()]);
+
+        self.sent.push(SentFragment {
+            record,
+            message: index,
+            range,
+            acked: false,
+        });
+        Some(out)
+    }
+
+    /// Mark the fragments sent in `records` as received by the peer.
+    pub(super) fn acknowledge(&mut self, records: &[RecordNumber]) {
+        for fragment in self.sent.iter_mut() {
+            if records.contains(&fragment.record) {
+                fragment.acked = true;
+            }
+        }
+    }
+
+    /// Arrange to send again every part of the flight that has not been acknowledged.
+    pub(super) fn retransmit(&mut self) {
+        for (i, message) in self.messages.iter_mut().enumerate() {
+            message.unsent = Self::missing(&self.sent, i, message.body().len());
+            if message.body().is_empty() && message.unsent.is_empty() {
+                // An empty message is acknowledged by acknowledging its only fragment.
+                if !self
+                    .sent
+                    .iter()
+                    .any(|f| f.message == i && f.acked)
+                {
+                    message.unsent.push(0..0);
+                }
+            }
+        }
+        self.sent.retain(|f| f.acked);
+    }
+
+    /// Ranges of message `index` that have not been acknowledged.
+    fn missing(sent: &[SentFragment], index: usize, len: usize) -> Vec<Range<usize>> {
+        let mut acked = sent
+            .iter()
+            .filter(|f| f.message == index && f.acked)
+            .map(|f| f.range.clone())
+            .collect::<Vec<_>>();
+        acked.sort_by_key(|r| r.start);
+
+        let mut missing = Vec::new();
+        let mut covered = 0;
+        for range in acked {
+            if range.start > covered {
+                missing.push(covered..range.start);
+            }
+            covered = covered.max(range.end);
+        }
+        if covered < len {
+            missing.push(covered..len);
+        }
+        missing
+    }
+}
+
+/// A handshake message received from the peer, reassembled from its fragments.
+pub(super) struct IncomingMessage {
+    /// The epoch of the records which carried this message.
+    pub(super) epoch: u64,
+    /// The TLS encoding of the message: type, length and body.
+    pub(super) encoded: Vec<u8>,
+}
+
+/// A handshake message for which some fragments have been received.
+struct Partial {
+    epoch: u64,
+    encoded: Vec<u8>,
+    /// Sorted, disjoint ranges of the body which have been received.
+    received: Vec<Range<usize>>,
+}
+
+impl Partial {
+    fn is_complete(&self) -> bool {
+        let len = self.encoded.len() - TLS_HEADER_LEN;
+        match self.received.as_slice() {
+            [] => len == 0,
+            [only] => *only == (0..len),
+            _ => false,
+        }
+    }
+
+    fn insert(&mut self, range: Range<usize>, data: &[u8]) {
+        self.encoded[TLS_HEADER_LEN + range.start..TLS_HEADER_LEN + range.end]
+            .copy_from_slice(data);
+
+        self.received.push(range);
+        self.received.sort_by_key(|r| r.start);
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(self.received.len());
+        for range in self.received.drain(..) {
+            match merged.last_mut() {
+                Some(last) if last.end >= range.start => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        self.received = merged;
+    }
+}
+
+/// Reassembles handshake messages from the fragments received from the peer.
+#[derive(Default)]
+pub(super) struct Reassembler {
+    next_seq: u16,
+    partial: BTreeMap<u16, Partial>,
+}
+
+impl Reassembler {
+    /// Accept the handshake fragments contained in a record from `epoch`.
+    ///
+    /// Returns true if any fragment was of a message which has already been
+    /// delivered, which suggests the peer has not received our reply.
+    pub(super) fn add_record(&mut self, epoch: u64, content: &[u8]) -> Result<bool, Error> {
+        let mut r = Reader::new(content);
+        let mut duplicate = false;
+
+        while r.any_left() {
+            let typ = u8::read(&mut r)?;
+            let len = U24::read(&mut r)?.0 as usize;
+            let message_seq = u16::read(&mut r)?;
+            let offset = U24::read(&mut r)?.0 as usize;
+            let fragment_len = U24::read(&mut r)?.0 as usize;
+            let data = r
+                .take(fragment_len)
+                .ok_or(InvalidMessage::MessageTooShort)?;
+
+            if len > MAX_HANDSHAKE_SIZE {
+                return Err(InvalidMessage::HandshakePayloadTooLarge.into());
+            }
+            if offset + fragment_len > len {
+                return Err(InvalidMessage::MessageTooShort.into());
+            }
+
+            if message_seq < self.next_seq {
+                duplicate = true;
+                continue;
+            }
+            if message_seq - self.next_seq >= MAX_BUFFERED_MESSAGES {
+                continue;
+            }
+
+            let partial = self
+                .partial
+                .entry(message_seq)
+                .or_insert_with(|| {
+                    let mut encoded = Vec::with_capacity(TLS_HEADER_LEN + len);
+                    encoded.push(typ);
+                    U24(len as u32).encode(&mut encoded);
+                    encoded.resize(TLS_HEADER_LEN + len, 0);
+                    Partial {
+                        epoch,
+                        encoded,
+                        received: Vec::new(),
+                    }
+                });
+
+            if partial.encoded[0] != typ
+                || partial.encoded.len() != TLS_HEADER_LEN + len
+                || partial.epoch != epoch
+            {
+                return Err(InvalidMessage::UnexpectedMessage(
+                    "inconsistent DTLS handshake fragments",
+                )
+                .into());
+            }
+            partial.insert(offset..offset + fragment_len, data);
+        }
+
+        Ok(duplicate)
+    }
+
+    /// Take the next message, if it has been completely received.
+    pub(super) fn pop(&mut self) -> Option<IncomingMessage> {
+        if !self
+            .partial
+            .get(&self.next_seq)?
+            .is_complete()
+        {
+            return None;
+        }
+
+        let partial = self.partial.remove(&self.next_seq)?;
+        self.next_seq = self.next_seq.wrapping_add(1);
+        Some(IncomingMessage {
+            epoch: partial.epoch,
+            encoded: partial.encoded,
+        })
+    }
+}
+
+/// Encode the body of an ACK record.
+///
+/// See <https://www.rfc-editor.org/rfc/rfc9147#section-7>.
+pub(super) fn encode_ack(records: &[RecordNumber]) -> Vec<u8> {
+    let mut out = Vec::new();
+    records.to_vec().encode(&mut out);
+    out
+}
+
+/// Decode the body of an ACK record.
+pub(super) fn decode_ack(content: &[u8]) -> Result<Vec<RecordNumber>, InvalidMessage> {
+    Vec::read_bytes(content)
+}
+
+/// The handshake message header used by TLS: type and 24-bit length.
+pub(super) const TLS_HEADER_LEN: usize = 4;
+
+/// The handshake message header used by DTLS, which adds the message sequence
+/// number and fragment position.
+const DTLS_HEADER_LEN: usize = 12;
+
+/// Largest handshake message we accept, matching the limit for TLS.
+const MAX_HANDSHAKE_SIZE: usize = 0xffff;
+
+/// How many messages ahead of the next expected one we buffer.
+const MAX_BUFFERED_MESSAGES: u16 = 8;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fragments_reassemble() {
+        let mut encoded = alloc::vec![20u8, 0, 0, 100];
+        encoded.extend((0..100).map(|i| i as u8));
+
+        let mut flight = Flight::default();
+        flight.push(2, 5, encoded.clone());
+
+        let mut fragments = Vec::new();
+        let mut seq = 0;
+        while let Some(fragment) =
+            flight.next_fragment(DTLS_HEADER_LEN + 30, RecordNumber { epoch: 2, seq })
+        {
+            fragments.push(fragment);
+            seq += 1;
+        }
+        assert_eq!(fragments.len(), 4);
+
+        let mut reassembler = Reassembler {
+            next_seq: 5,
+            ..Reassembler::default()
+        };
+        for fragment in fragments.iter().rev() {
+            assert!(reassembler.pop().is_none());
+            assert!(
+                !reassembler
+                    .add_record(2, fragment)
+                    .unwrap()
+            );
+        }
+        let message = reassembler.pop().unwrap();
+        assert_eq!(message.epoch, 2);
+        assert_eq!(message.encoded, encoded);
+
+        assert!(
+            reassembler
+                .add_record(2, &fragments[0])
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn retransmits_unacknowledged() {
+        let mut encoded = alloc::vec![20u8, 0, 0, 20];
+        encoded.extend([0xaa; 20]);
+
+        let mut flight = Flight::default();
+        flight.push(2, 0, encoded);
+        for seq in 0..2 {
+            flight
+                .next_fragment(DTLS_HEADER_LEN + 10, RecordNumber { epoch: 2, seq })
+                .unwrap();
+        }
+        assert!(!flight.has_unsent());
+
+        flight.acknowledge(&[RecordNumber { epoch: 2, seq: 1 }]);
+        assert!(!flight.is_acked());
+        flight.retransmit();
+        assert!(flight.has_unsent());
+
+        let fragment = flight
+            .next_fragment(100, RecordNumber { epoch: 2, seq: 2 })
+            .unwrap();
+        assert_eq!(fragment.len(), DTLS_HEADER_LEN + 10);
+        assert_eq!(&fragment[6..9], &[0, 0, 0]);
+        assert!(!flight.has_unsent());
+
+        flight.acknowledge(&[RecordNumber { epoch: 2, seq: 2 }]);
+        assert!(flight.is_acked());
+    }
+
+    #[test]
+    fn ack_round_trip() {
+        let records = [
+            RecordNumber { epoch: 2, seq: 1 },
+            RecordNumber { epoch: 3, seq: 7 },
+        ];
+        assert_eq!(decode_ack(&encode_ack(&records)).unwrap(), records);
+    }
+
+    /// <https://www.rfc-editor.org/rfc/rfc9147#section-7>: a 16-bit length, then
+    /// each record number as a 64-bit epoch and 64-bit sequence number.
+    #[test]
+    fn ack_encoding() {
+        let records = [
+            RecordNumber { epoch: 2, seq: 1 },
+            RecordNumber {
+                epoch: 3,
+                seq: 0x0102_0304_0506,
+            },
+        ];
+        let encoded = [
+            0x00, 0x20, //
+            0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 1, //
+            0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 1, 2, 3, 4, 5, 6,
+        ];
+        assert_eq!(encode_ack(&records), encoded);
+        assert_eq!(decode_ack(&encoded).unwrap(), records);
+        assert_eq!(decode_ack(&[0x00, 0x00]).unwrap(), []);
+        assert!(decode_ack(&encoded[..encoded.len() - 1]).is_err());
+    }
+}
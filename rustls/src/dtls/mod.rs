@@ -0,0 +1,849 @@
+//! DTLS 1.3, as specified in [RFC 9147].
+//!
+//! DTLS runs the TLS 1.3 handshake over an unreliable datagram transport.  The
+//! connection types here do not own a socket or a clock: the caller passes each
+//! received datagram to [`Connection::read_datagram()`], sends everything
+//! returned by [`Connection::poll_datagram()`], and arranges for
+//! [`Connection::handle_timeout()`] to be called at the time given by
+//! [`Connection::poll_timeout()`], so lost handshake messages can be retransmitted.
+//!
+//! Application data is sent and received one record at a time, and each record
+//! is carried in its own datagram.  As with the underlying transport, application
+//! data may be lost or reordered.
+//!
+//! The following are not supported: early data, connection IDs, and `KeyUpdate`
+//! (so a connection must be closed before its record sequence numbers are exhausted).
+//!
+//! [RFC 9147]: https://www.rfc-editor.org/rfc/rfc9147
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::ops::Deref;
+use core::time::Duration;
+use core::{fmt, mem};
+use std::time::Instant;
+
+use pki_types::{DnsName, FipsStatus, ServerName};
+
+use crate::client::{ClientConfig, ClientSide};
+use crate::common_state::{ConnectionOutputs, Protocol, Side};
+use crate::conn::{ConnectionCommon, KeyingMaterialExporter, MessageIter, SideData};
+use crate::crypto::tls13::OkmBlock;
+use crate::enums::ContentType;
+use crate::error::{AlertDescription, ApiMisuse, Error, PeerMisbehaved};
+use crate::msgs::{
+    AlertLevel, AlertMessagePayload, ClientExtensionsInput, Codec, MAX_FRAGMENT_LEN, Message,
+    MessagePayload, ServerExtensionsInput,
+};
+use crate::quic::{QuicOutput, Suite};
+use crate::server::{ServerConfig, ServerSide};
+use crate::sync::Arc;
+use crate::tls13::Tls13CipherSuite;
+use crate::{SliceInput, TlsInputBuffer};
+
+mod handshake;
+use handshake::{Flight, Reassembler, TLS_HEADER_LEN};
+
+mod record;
+use record::{
+    ACK, APPLICATION_EPOCH, HANDSHAKE_EPOCH, ReceiveEpoch, RecordKeys, RecordNumber, SendEpoch,
+};
+
+/// A DTLS client or server connection.
+pub trait Connection: fmt::Debug + Deref<Target = ConnectionOutputs> {
+    /// Process a datagram received from the peer.
+    ///
+    /// Records which cannot be authenticated are silently discarded, as are
+    /// duplicated records.  Afterwards, call [`Self::poll_datagram()`] to collect
+    /// any resulting datagrams, and [`Self::recv()`] to collect application data.
+    ///
+    /// An error from this function is fatal to the connection.  An alert may
+    /// still be available from [`Self::poll_datagram()`], which should be sent
+    /// to the peer.
+    fn read_datagram(&mut self, datagram: &[u8]) -> Result<(), Error>;
+
+    /// Take the next datagram to send to the peer.
+    ///
+    /// `now` is the current time, used to schedule retransmission of the
+    /// handshake.  Call this repeatedly until it returns `None`.
+    fn poll_datagram(&mut self, now: Instant) -> Option<Vec<u8>>;
+
+    /// The time at which [`Self::handle_timeout()`] should next be called, if any.
+    fn poll_timeout(&self) -> Option<Instant>;
+
+    /// Retransmit any handshake messages which the peer has not acknowledged by `now`.
+    ///
+    /// After this, call [`Self::poll_datagram()`] to collect the retransmission.
+    fn handle_timeout(&mut self, now: Instant);
+
+    /// Encrypt `data` as one application data record.
+    ///
+    /// This fails if the handshake is not complete, or if `data` is too large
+    /// to fit in one datagram.  The record is then available from [`Self::poll_datagram()`].
+    fn send(&mut self, data: &[u8]) -> Result<(), Error>;
+
+    /// Take the contents of the next application data record received from the peer.
+    fn recv(&mut self) -> Option<Vec<u8>>;
+
+    /// Queue a `close_notify` alert, to tell the peer we will send no more data.
+    ///
+    /// This does nothing if a `close_notify` or fatal alert was already sent.
+    fn send_close_notify(&mut self);
+
+    /// Returns true if the peer has sent a `close_notify` alert.
+    fn peer_has_closed(&self) -> bool;
+
+    /// Returns true if the connection is currently performing the TLS handshake.
+    fn is_handshaking(&self) -> bool;
+
+    /// Set the largest datagram that will be produced.
+    ///
+    /// This should reflect the path MTU, less the size of IP and UDP headers.  The
+    /// default is 1200 bytes, and values below 256 bytes are treated as 256.
+    fn set_max_datagram_size(&mut self, size: usize);
+}
+
+/// A DTLS client connection.
+pub struct ClientConnection {
+    inner: DtlsCommon<ClientSide>,
+}
+
+impl ClientConnection {
+    /// Make a new DTLS client connection, to the server named `name`.
+    ///
+    /// The `ClientHello` is available immediately from [`Connection::poll_datagram()`].
+    pub fn new(config: Arc<ClientConfig>, name: ServerName<'static>) -> Result<Self, Error> {
+        check_suites(&config.provider().tls13_cipher_suites)?;
+        if config.enable_early_data {
+            return Err(ApiMisuse::DtlsForbidsEarlyData.into());
+        }
+
+        let exts = ClientExtensionsInput::from_alpn(config.alpn_protocols.clone());
+        let mut output = DtlsOutput::default();
+        let mut tls = Vec::new();
+        let core = ConnectionCommon::for_client(
+            config,
+            name,
+            exts,
+            Some(&mut output),
+            Protocol::Dtls,
+            &mut tls,
+        )?;
+
+        // In DTLS mode, handshake output is emitted via `DtlsOutput`, not `tls`.
+        debug_assert!(tls.is_empty());
+        Ok(Self {
+            inner: DtlsCommon::new(core, output),
+        })
+    }
+
+    /// Return the FIPS validation status of the connection.
+    pub fn fips(&self) -> FipsStatus {
+        self.inner.core.fips
+    }
+
+    /// Returns an object that can derive key material from the agreed connection secrets.
+    ///
+    /// See [RFC 5705][] for more details on what this is for.
+    ///
+    /// This function can be called at most once per connection.
+    ///
+    /// This function will error:
+    ///
+    /// - if called prior to the handshake completing; (check with
+    ///   [`Connection::is_handshaking`] first).
+    /// - if called more than once per connection.
+    ///
+    /// [RFC 5705]: https://datatracker.ietf.org/doc/html/rfc5705
+    pub fn exporter(&mut self) -> Result<KeyingMaterialExporter, Error> {
+        self.inner.core.exporter()
+    }
+}
+
+impl Connection for ClientConnection {
+    fn read_datagram(&mut self, datagram: &[u8]) -> Result<(), Error> {
+        self.inner.read_datagram(datagram)
+    }
+
+    fn poll_datagram(&mut self, now: Instant) -> Option<Vec<u8>> {
+        self.inner.poll_datagram(now)
+    }
+
+    fn poll_timeout(&self) -> Option<Instant> {
+        self.inner.timeout
+    }
+
+    fn handle_timeout(&mut self, now: Instant) {
+        self.inner.handle_timeout(now)
+    }
+
+    fn send(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.inner.send(data)
+    }
+
+    fn recv(&mut self) -> Option<Vec<u8>> {
+        self.inner.received.pop_front()
+    }
+
+    fn send_close_notify(&mut self) {
+        self.inner.send_close_notify()
+    }
+
+    fn peer_has_closed(&self) -> bool {
+        self.inner.peer_has_closed
+    }
+
+    fn is_handshaking(&self) -> bool {
+        self.inner.core.is_handshaking()
+    }
+
+    fn set_max_datagram_size(&mut self, size: usize) {
+        self.inner.max_datagram_size = size.max(MIN_DATAGRAM_SIZE);
+    }
+}
+
+impl Deref for ClientConnection {
+    type Target = ConnectionOutputs;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner.core
+    }
+}
+
+impl fmt::Debug for ClientConnection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("dtls::ClientConnection")
+            .finish_non_exhaustive()
+    }
+}
+
+/// A DTLS server connection.
+///
+/// A new server connection should be made for each client address from which a
+/// `ClientHello` is received.  The server always replies to the first `ClientHello`
+/// with a `HelloRetryRequest` containing a cookie, and only continues the handshake
+/// once the client has echoed it -- this demonstrates the client can receive
+/// datagrams at its claimed address.
+///
+/// This cookie exchange gives **no** denial-of-service protection.  The cookie is
+/// checked by the same `ServerConnection` that sent it, so the server has already
+/// created and must keep per-connection state for every spoofed `ClientHello`.
+/// (A stateless server would instead encode its state in the cookie, and only create
+/// a connection once the cookie returns.)  Servers exposed to untrusted networks
+/// should limit the number of connections they create for unverified addresses.
+pub struct ServerConnection {
+    inner: DtlsCommon<ServerSide>,
+}
+
+impl ServerConnection {
+    /// Make a new DTLS server connection.
+    pub fn new(config: Arc<ServerConfig>) -> Result<Self, Error> {
+        check_suites(&config.provider.tls13_cipher_suites)?;
+        if config.max_early_data_size != 0 {
+            return Err(ApiMisuse::DtlsForbidsEarlyData.into());
+        }
+
+        let core = ConnectionCommon::for_server(
+            config,
+            ServerExtensionsInput {
+                transport_parameters: None,
+            },
+            Protocol::Dtls,
+        )?;
+        Ok(Self {
+            inner: DtlsCommon::new(core, DtlsOutput::default()),
+        })
+    }
+
+    /// Return the FIPS validation status of the connection.
+    pub fn fips(&self) -> FipsStatus {
+        self.inner.core.fips
+    }
+
+    /// Retrieves the server name, if any, used to select the certificate and
+    /// private key.
+    ///
+    /// This returns `None` until some time after the client's server name indication
+    /// (SNI) extension value is processed during the handshake.
+    pub fn server_name(&self) -> Option<&DnsName<'_>> {
+        self.inner.core.side.server_name()
+    }
+
+    /// Returns an object that can derive key material from the agreed connection secrets.
+    ///
+    /// See [RFC 5705][] for more details on what this is for.
+    ///
+    /// This function can be called at most once per connection.
+    ///
+    /// This function will error:
+    ///
+    /// - if called prior to the handshake completing; (check with
+    ///   [`Connection::is_handshaking`] first).
+    /// - if called more than once per connection.
+    ///
+    /// [RFC 5705]: https://datatracker.ietf.org/doc/html/rfc5705
+    pub fn exporter(&mut self) -> Result<KeyingMaterialExporter, Error> {
+        self.inner.core.exporter()
+    }
+}
+
+impl Connection for ServerConnection {
+    fn read_datagram(&mut self, datagram: &[u8]) -> Result<(), Error> {
+        self.inner.read_datagram(datagram)
+    }
+
+    fn poll_datagram(&mut self, now: Instant) -> Option<Vec<u8>> {
+        self.inner.poll_datagram(now)
+    }
+
+    fn poll_timeout(&self) -> Option<Instant> {
+        self.inner.timeout
+    }
+
+    fn handle_timeout(&mut self, now: Instant) {
+        self.inner.handle_timeout(now)
+    }
+
+    fn send(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.inner.send(data)
+    }
+
+    fn recv(&mut self) -> Option<Vec<u8>> {
+        self.inner.received.pop_front()
+    }
+
+    fn send_close_notify(&mut self) {
+        self.inner.send_close_notify()
+    }
+
+    fn peer_has_closed(&self) -> bool {
+        self.inner.peer_has_closed
+    }
+
+    fn is_handshaking(&self) -> bool {
+        self.inner.core.is_handshaking()
+    }
+
+    fn set_max_datagram_size(&mut self, size: usize) {
+        self.inner.max_datagram_size = size.max(MIN_DATAGRAM_SIZE);
+    }
+}
+
+impl Deref for ServerConnection {
+    type Target = ConnectionOutputs;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner.core
+    }
+}
+
+impl fmt::Debug for ServerConnection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("dtls::ServerConnection")
+            .finish_non_exhaustive()
+    }
+}
+
+fn check_suites(suites: &[&'static Tls13CipherSuite]) -> Result<(), Error> {
+    if suites.is_empty() {
+        return Err(ApiMisuse::DtlsRequiresTls13Support.into());
+    }
+
+    if !suites
+        .iter()
+        .any(|scs| scs.quic.is_some())
+    {
+        return Err(ApiMisuse::NoDtlsCompatibleCipherSuites.into());
+    }
+
+    Ok(())
+}
+
+/// State shared by DTLS clients and servers.
+struct DtlsCommon<Side: SideData> {
+    core: ConnectionCommon<Side>,
+    output: DtlsOutput,
+
+    /// Our most recent flight of handshake messages.
+    flight: Flight,
+    /// The `message_seq` for our next handshake message.
+    next_message_seq: u16,
+    /// When to retransmit `flight`, if it has been sent.
+    timeout: Option<Instant>,
+    /// The current retransmission timeout.
+    rto: Duration,
+
+    reassembler: Reassembler,
+    /// A buffer for handshake messages being passed to `core`.
+    handshake_input: Vec<u8>,
+    /// Handshake records received from the peer, which we have not yet acknowledged.
+    unacked: Vec<RecordNumber>,
+
+    /// Datagrams ready to send, other than those containing `flight`.
+    datagrams: VecDeque<Vec<u8>>,
+    /// Application data received from the peer.
+    received: VecDeque<Vec<u8>>,
+    max_datagram_size: usize,
+
+    peer_has_closed: bool,
+    sent_close_notify: bool,
+    /// The first fatal error, which is repeated by later calls.
+    error: Option<Error>,
+}
+
+impl<Side: SideData> DtlsCommon<Side> {
+    fn new(mut core: ConnectionCommon<Side>, output: DtlsOutput) -> Self {
+        core.common.outputs.dtls = true;
+        let mut new = Self {
+            core,
+            output,
+            flight: Flight::default(),
+            next_message_seq: 0,
+            timeout: None,
+            rto: INITIAL_RTO,
+            reassembler: Reassembler::default(),
+            handshake_input: Vec::new(),
+            unacked: Vec::new(),
+            datagrams: VecDeque::new(),
+            received: VecDeque::new(),
+            max_datagram_size: DEFAULT_DATAGRAM_SIZE,
+            peer_has_closed: false,
+            sent_close_notify: false,
+            error: None,
+        };
+        new.take_messages();
+        new
+    }
+
+    fn read_datagram(&mut self, datagram: &[u8]) -> Result<(), Error> {
+        if let Some(error) = &self.error {
+            return Err(error.clone());
+        }
+
+        let result = self.process_datagram(datagram);
+        if let Err(error) = &result {
+            self.fail(error.clone());
+        }
+        result
+    }
+
+    fn process_datagram(&mut self, datagram: &[u8]) -> Result<(), Error> {
+        let mut received_handshake = false;
+        let mut duplicate = false;
+        let mut new_flight = false;
+
+        let mut rest = datagram;
+        while let Some((record, next)) = record::open(rest, &mut self.output.receive) {
+            rest = next;
+            let Some(record) = record else {
+                continue;
+            };
+
+            match record.typ {
+                ContentType::Handshake => {
+                    duplicate |= self
+                        .reassembler
+                        .add_record(record.number.epoch, &record.content)?;
+                    self.unacked.push(record.number);
+                    received_handshake = true;
+                    new_flight |= self.deliver_messages()?;
+                }
+                ACK if record.number.epoch != 0 => {
+                    let acked = handshake::decode_ack(&record.content)?;
+                    self.flight.acknowledge(&acked);
+                    if self.flight.is_acked() {
+                        self.flight_complete();
+                    }
+                }
+                ContentType::Alert => {
+                    let alert = AlertMessagePayload::read_bytes(&record.content)?;
+                    if alert.description == AlertDescription::CloseNotify {
+                        // Do not allow an unauthenticated `close_notify` to truncate data.
+                        self.peer_has_closed |= record.number.epoch >= APPLICATION_EPOCH;
+                    } else if alert.level == AlertLevel::Fatal
+                        || alert.description != AlertDescription::UserCanceled
+                    {
+                        return Err(Error::AlertReceived(alert.description));
+                    }
+                }
+                ContentType::ApplicationData
+                    if record.number.epoch >= APPLICATION_EPOCH
+                        && !self.core.is_handshaking()
+                        && !self.peer_has_closed =>
+                {
+                    self.received.push_back(record.content);
+                }
+                // Anything else (for example, application data which arrives before the
+                // handshake completes, or an unprotected ACK) is discarded.
+                _ => {}
+            }
+        }
+
+        if new_flight {
+            // Our new flight implicitly acknowledges the peer's.
+            self.unacked.clear();
+        } else if duplicate && !self.flight.is_empty() {
+            // The peer has not received our last flight.
+            self.retransmit();
+        } else if received_handshake {
+            self.send_ack()?;
+        }
+
+        Ok(())
+    }
+
+    /// Pass complete handshake messages to `core`.
+    ///
+    /// Returns true if this produced new handshake messages to send.
+    fn deliver_messages(&mut self) -> Result<bool, Error> {
+        let mut new_flight = false;
+
+        while let Some(message) = self.reassembler.pop() {
+            if message.epoch != self.receive_epoch() {
+                return Err(PeerMisbehaved::HandshakeMessageInWrongDtlsEpoch.into());
+            }
+
+            // The peer must have received our last flight to have sent this.
+            if !new_flight {
+                self.flight.clear();
+                self.flight_complete();
+            }
+
+            self.handshake_input
+                .extend_from_slice(&message.encoded);
+            let mut input = SliceInput::new(&mut self.handshake_input);
+            self.core
+                .common
+                .recv
+                .deframer
+                .input_quic(input.slice_mut())?;
+
+            let mut tls = Vec::new();
+            let mut iter = MessageIter::new(
+                &mut input,
+                &mut tls,
+                Some(&mut self.output),
+                &mut self.core,
+                true,
+            );
+            let result = match iter.next() {
+                Some(Ok(_)) | None => Ok(()),
+                Some(Err(e)) => Err(e),
+            };
+
+            input.discard(
+                self.core
+                    .common
+                    .recv
+                    .deframer
+                    .take_discard(),
+            );
+            let used = input.into_used();
+            self.handshake_input.drain(..used);
+            result?;
+
+            new_flight |= self.take_messages();
+        }
+
+        Ok(new_flight)
+    }
+
+    /// The epoch in which the peer's next handshake message must arrive.
+    fn receive_epoch(&self) -> u64 {
+        if !self.core.is_handshaking() {
+            APPLICATION_EPOCH
+        } else if self
+            .output
+            .receive
+            .iter()
+            .any(|e| e.epoch() == HANDSHAKE_EPOCH)
+        {
+            HANDSHAKE_EPOCH
+        } else {
+            0
+        }
+    }
+
+    /// Move handshake messages produced by `core` into our flight.
+    fn take_messages(&mut self) -> bool {
+        let messages = mem::take(&mut self.output.messages);
+        let any = !messages.is_empty();
+        for (epoch, encoded) in messages {
+            self.flight
+                .push(epoch, self.next_message_seq, encoded);
+            self.next_message_seq = self.next_message_seq.wrapping_add(1);
+        }
+        any
+    }
+
+    fn poll_datagram(&mut self, now: Instant) -> Option<Vec<u8>> {
+        if let Some(datagram) = self.datagrams.pop_front() {
+            return Some(datagram);
+        }
+
+        if !self.flight.has_unsent() {
+            return None;
+        }
+
+        let mut datagram = Vec::new();
+        while let Some(epoch) = self.flight.next_epoch() {
+            let send = self.output.send_epoch(epoch);
+            let space = self
+                .max_datagram_size
+                .saturating_sub(datagram.len() + send.overhead());
+            let Some(fragment) = self
+                .flight
+                .next_fragment(space, send.next_record())
+            else {
+                break;
+            };
+
+            if let Err(error) = send.seal(ContentType::Handshake, &fragment, &mut datagram) {
+                self.flight.clear();
+                self.flight_complete();
+                self.error = Some(error);
+                break;
+            }
+        }
+
+        if !self.flight.has_unsent() && !self.flight.is_empty() && self.timeout.is_none() {
+            self.timeout = Some(now + self.rto);
+        }
+
+        match datagram.is_empty() {
+            true => None,
+            false => Some(datagram),
+        }
+    }
+
+    fn handle_timeout(&mut self, now: Instant) {
+        match self.timeout {
+            Some(timeout) if timeout <= now => {
+                self.rto = Ord::min(self.rto * 2, MAX_RTO);
+                self.retransmit();
+            }
+            _ => {}
+        }
+    }
+
+    fn retransmit(&mut self) {
+        self.flight.retransmit();
+        self.timeout = None;
+    }
+
+    /// Our flight needs no further retransmission.
+    fn flight_complete(&mut self) {
+        self.timeout = None;
+        self.rto = INITIAL_RTO;
+    }
+
+    fn send(&mut self, data: &[u8]) -> Result<(), Error> {
+        if let Some(error) = &self.error {
+            return Err(error.clone());
+        } else if self.core.is_handshaking() {
+            return Err(ApiMisuse::WriteTlsBeforeHandshakeComplete.into());
+        } else if self.sent_close_notify {
+            return Err(ApiMisuse::WriteTlsAfterSendPathClosed.into());
+        }
+
+        let send = self
+            .output
+            .send_epoch(APPLICATION_EPOCH);
+        if data.len() > MAX_FRAGMENT_LEN.get()
+            || data.len() + send.overhead() > self.max_datagram_size
+        {
+            return Err(ApiMisuse::DtlsRecordTooLarge.into());
+        }
+
+        self.send_record(ContentType::ApplicationData, data)
+    }
+
+    fn send_close_notify(&mut self) {
+        if self.sent_close_notify || self.error.is_some() {
+            return;
+        }
+
+        self.sent_close_notify = true;
+        let _ = self.send_alert(AlertLevel::Warning, AlertDescription::CloseNotify);
+    }
+
+    /// Acknowledge the handshake records received since our last acknowledgement.
+    ///
+    /// See <https://www.rfc-editor.org/rfc/rfc9147#section-7>.
+    fn send_ack(&mut self) -> Result<(), Error> {
+        let send = self.output.latest_send_epoch();
+        if send.epoch() == 0 {
+            // ACKs are only sent protected; the peer will retransmit instead.
+            return Ok(());
+        }
+
+        let space = self
+            .max_datagram_size
+            .saturating_sub(send.overhead() + 2);
+        let max_records = space / RECORD_NUMBER_LEN;
+        let skip = self
+            .unacked
+            .len()
+            .saturating_sub(max_records);
+        let unacked = mem::take(&mut self.unacked);
+        let ack = handshake::encode_ack(&unacked[skip..]);
+        self.send_record(ACK, &ack)
+    }
+
+    fn send_alert(
+        &mut self,
+        level: AlertLevel,
+        description: AlertDescription,
+    ) -> Result<(), Error> {
+        let mut alert = Vec::new();
+        AlertMessagePayload { level, description }.encode(&mut alert);
+        self.send_record(ContentType::Alert, &alert)
+    }
+
+    /// Send a record in its own datagram, in our latest epoch.
+    fn send_record(&mut self, typ: ContentType, content: &[u8]) -> Result<(), Error> {
+        let mut datagram = Vec::new();
+        self.output
+            .latest_send_epoch()
+            .seal(typ, content, &mut datagram)?;
+        self.datagrams.push_back(datagram);
+        Ok(())
+    }
+
+    /// Handle a fatal error.
+    fn fail(&mut self, error: Error) {
+        if let Ok(description) = AlertDescription::try_from(&error) {
+            let _ = self.send_alert(AlertLevel::Fatal, description);
+        }
+
+        self.flight.clear();
+        self.flight_complete();
+        self.error = Some(error);
+    }
+}
+
+/// Collects the output of the TLS 1.3 handshake state machine.
+struct DtlsOutput {
+    /// Handshake messages emitted since they were last taken, along with the
+    /// epoch in which they must be sent.
+    messages: Vec<(u64, Vec<u8>)>,
+    /// Every epoch we have keys for, in order.
+    send: Vec<SendEpoch>,
+    receive: Vec<ReceiveEpoch>,
+}
+
+impl DtlsOutput {
+    fn latest_send_epoch(&mut self) -> &mut SendEpoch {
+        // `send` always contains at least epoch 0.
+        self.send.last_mut().unwrap()
+    }
+
+    fn send_epoch(&mut self, epoch: u64) -> &mut SendEpoch {
+        match self
+            .send
+            .iter()
+            .position(|e| e.epoch() == epoch)
+        {
+            Some(index) => &mut self.send[index],
+            None => self.latest_send_epoch(),
+        }
+    }
+
+    fn new_epoch(
+        &mut self,
+        epoch: u64,
+        client_secret: OkmBlock,
+        server_secret: OkmBlock,
+        suite: Suite,
+        side: Side,
+    ) {
+        let (ours, theirs) = match side {
+            Side::Client => (client_secret, server_secret),
+            Side::Server => (server_secret, client_secret),
+        };
+        self.send
+            .push(SendEpoch::new(epoch, Some(RecordKeys::new(&suite, &ours))));
+        self.receive.push(ReceiveEpoch::new(
+            epoch,
+            Some(RecordKeys::new(&suite, &theirs)),
+        ));
+    }
+}
+
+impl Default for DtlsOutput {
+    fn default() -> Self {
+        Self {
+            messages: Vec::new(),
+            send: alloc::vec![SendEpoch::new(0, None)],
+            receive: alloc::vec![ReceiveEpoch::new(0, None)],
+        }
+    }
+}
+
+impl QuicOutput for DtlsOutput {
+    fn is_quic(&self) -> bool {
+        false
+    }
+
+    fn transport_parameters(&mut self, _params: Vec<u8>) {}
+
+    fn early_secret(&mut self, _secret: Option<OkmBlock>) {}
+
+    fn handshake_secrets(
+        &mut self,
+        client_secret: OkmBlock,
+        server_secret: OkmBlock,
+        suite: Suite,
+        side: Side,
+    ) {
+        self.new_epoch(HANDSHAKE_EPOCH, client_secret, server_secret, suite, side);
+    }
+
+    fn traffic_secrets(
+        &mut self,
+        client_secret: OkmBlock,
+        server_secret: OkmBlock,
+        suite: Suite,
+        side: Side,
+    ) {
+        self.new_epoch(APPLICATION_EPOCH, client_secret, server_secret, suite, side);
+    }
+
+    fn send_msg(&mut self, m: Message<'_>, _must_encrypt: bool) {
+        match &m.payload {
+            // alerts are sent by `DtlsCommon::fail()`, as for errors not arising
+            // from the state machine.
+            MessagePayload::Alert(_) => return,
+            MessagePayload::Handshake { .. } | MessagePayload::HandshakeFlight(_) => {}
+            _ => debug_assert!(false, "DTLS uses TLS for the handshake only"),
+        }
+
+        let epoch = self.latest_send_epoch().epoch();
+        let mut bytes = Vec::new();
+        m.payload.encode(&mut bytes);
+
+        // Split a flight into its individual messages, which are fragmented separately.
+        let mut rest = &bytes[..];
+        while let Some(header) = rest.get(..TLS_HEADER_LEN) {
+            let len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+            let (message, next) = rest.split_at(TLS_HEADER_LEN + len);
+            self.messages
+                .push((epoch, message.to_vec()));
+            rest = next;
+        }
+    }
+}
+
+/// The recommended initial retransmission timeout.
+///
+/// See <https://www.rfc-editor.org/rfc/rfc9147#section-5.8.2>.
+const INITIAL_RTO: Duration = Duration::from_millis(100);
+
+/// The retransmission timeout backs off to at most this.
+const MAX_RTO: Duration = Duration::from_secs(60);
+
+const DEFAULT_DATAGRAM_SIZE: usize = 1200;
+const MIN_DATAGRAM_SIZE: usize = 256;
+
+/// The encoded length of a `RecordNumber`.
+const RECORD_NUMBER_LEN: usize = 16;
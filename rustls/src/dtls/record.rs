@@ -0,0 +1,609 @@
+//! The DTLS 1.3 record layer.
+//!
+//! See <https://www.rfc-editor.org/rfc/rfc9147#section-4>.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use crate::crypto::cipher::NONCE_LEN;
+use crate::crypto::tls13::OkmBlock;
+use crate::enums::{ContentType, ProtocolVersion};
+use crate::error::Error;
+use crate::msgs::{Codec, Reader, TlsListElement};
+use crate::quic::{self, HeaderProtectionKey, PacketKey};
+use crate::tls13::key_schedule::{
+    DTLS13_LABEL_PREFIX, hkdf_expand_prefixed_label_aead_key, hkdf_expand_prefixed_label_iv,
+};
+
+/// The record content type for ACKs.
+///
+/// <https://www.rfc-editor.org/rfc/rfc9147#section-7>
+pub(super) const ACK: ContentType = ContentType(0x1a);
+
+/// The epoch which protects the handshake.
+pub(super) const HANDSHAKE_EPOCH: u64 = 2;
+
+/// The epoch which protects the first application data.
+pub(super) const APPLICATION_EPOCH: u64 = 3;
+
+/// Identifies a record by its epoch and sequence number.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(super) struct RecordNumber {
+    pub(super) epoch: u64,
+    pub(super) seq: u64,
+}
+
+impl Codec<'_> for RecordNumber {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        self.epoch.encode(bytes);
+        self.seq.encode(bytes);
+    }
+
+    fn read(r: &mut Reader<'_>) -> Result<Self, crate::error::InvalidMessage> {
+        Ok(Self {
+            epoch: u64::read(r)?,
+            seq: u64::read(r)?,
+        })
+    }
+}
+
+impl TlsListElement for RecordNumber {
+    const SIZE_LEN: crate::msgs::ListLength = crate::msgs::ListLength::U16;
+}
+
+/// Record protection keys for one epoch and direction.
+pub(super) struct RecordKeys {
+    packet: Box<dyn PacketKey>,
+    sequence_number: Box<dyn HeaderProtectionKey>,
+}
+
+impl RecordKeys {
+    /// Derive the keys for `secret`.
+    ///
+    /// See <https://www.rfc-editor.org/rfc/rfc9147#section-4.2.3> for the
+    /// sequence number key.
+    pub(super) fn new(suite: &quic::Suite, secret: &OkmBlock) -> Self {
+        let expander = suite
+            .inner
+            .hkdf_provider
+            .expander_for_okm(secret);
+        let key_len = suite.quic.aead_key_len();
+
+        let key = hkdf_expand_prefixed_label_aead_key(
+            expander.as_ref(),
+            key_len,
+            DTLS13_LABEL_PREFIX,
+            b"key",
+            &[],
+        );
+        let iv = hkdf_expand_prefixed_label_iv(
+            expander.as_ref(),
+            DTLS13_LABEL_PREFIX,
+            b"iv",
+            &[],
+            NONCE_LEN,
+        );
+        let sn_key = hkdf_expand_prefixed_label_aead_key(
+            expander.as_ref(),
+            key_len,
+            DTLS13_LABEL_PREFIX,
+            b"sn",
+            &[],
+        );
+
+        Self {
+            packet: suite.quic.packet_key(key, iv),
+            sequence_number: suite.quic.header_protection_key(sn_key),
+        }
+    }
+
+    /// Bytes added to each record, beyond the content and its type.
+    fn overhead(&self) -> usize {
+        CIPHERTEXT_HEADER_LEN + self.packet.tag_len()
+    }
+}
+
+/// Sending state for one epoch.
+pub(super) struct SendEpoch {
+    epoch: u64,
+    /// `None` for epoch 0, which is not protected.
+    keys: Option<RecordKeys>,
+    next_seq: u64,
+}
+
+impl SendEpoch {
+    pub(super) fn new(epoch: u64, keys: Option<RecordKeys>) -> Self {
+        Self {
+            epoch,
+            keys,
+            next_seq: 0,
+        }
+    }
+
+    pub(super) fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// The number of the next record to be sealed.
+    pub(super) fn next_record(&self) -> RecordNumber {
+        RecordNumber {
+            epoch: self.epoch,
+            seq: self.next_seq,
+        }
+    }
+
+    /// Bytes this epoch adds to a record's content.
+    pub(super) fn overhead(&self) -> usize {
+        match &self.keys {
+            Some(keys) => keys.overhead() + 1,
+            None => PLAINTEXT_HEADER_LEN,
+        }
+    }
+
+    /// Append a record containing `content` to `out`.
+    pub(super) fn seal(
+        &mut self,
+        typ: ContentType,
+        content: &[u8],
+        out: &mut Vec<u8>,
+    ) -> Result<RecordNumber, Error> {
+        let seq = self.next_seq;
+        let limit = match &self.keys {
+            Some(keys) => keys
+                .packet
+                .confidentiality_limit()
+                .min(MAX_SEQ),
+            None => MAX_SEQ,
+        };
+        if seq >= limit {
+            // DTLS KeyUpdate is not supported, so the connection must end here.
+            return Err(Error::EncryptError);
+        }
+        self.next_seq += 1;
+
+        let Some(keys) = &self.keys else {
+            // DTLSPlaintext
+            typ.encode(out);
+            ProtocolVersion::DTLSv1_2.encode(out);
+            out.extend_from_slice(&(self.epoch as u16).to_be_bytes());
+            out.extend_from_slice(&seq.to_be_bytes()[2..]);
+            out.extend_from_slice(&(content.len() as u16).to_be_bytes());
+            out.extend_from_slice(content);
+            return Ok(RecordNumber {
+                epoch: self.epoch,
+                seq,
+            });
+        };
+
+        // DTLSCiphertext, with a 16-bit sequence number and length, and no connection ID.
+        let header = out.len();
+        let inner_len = content.len() + 1 + keys.packet.tag_len();
+        out.push(UNIFIED_HEADER | SEQ_16 | LENGTH_PRESENT | (self.epoch & EPOCH_BITS) as u8);
+        out.extend_from_slice(&(seq as u16).to_be_bytes());
+        out.extend_from_slice(&(inner_len as u16).to_be_bytes());
+
+        let body = out.len();
+        out.extend_from_slice(content);
+        typ.encode(out);
+
+        let (aad, payload) = out[header..].split_at_mut(CIPHERTEXT_HEADER_LEN);
+        let tag = keys
+            .packet
+            .encrypt_in_place(seq, aad, payload, None)?;
+        out.extend_from_slice(tag.as_ref());
+
+        let mask = keys
+            .sequence_number
+            .mask(&out[body..body + keys.sequence_number.sample_len()])?;
+        out[header + 1] ^= mask[0];
+        out[header + 2] ^= mask[1];
+
+        Ok(RecordNumber {
+            epoch: self.epoch,
+            seq,
+        })
+    }
+}
+
+/// Receiving state for one epoch.
+pub(super) struct ReceiveEpoch {
+    epoch: u64,
+    /// `None` for epoch 0, which is not protected.
+    keys: Option<RecordKeys>,
+    window: ReplayWindow,
+}
+
+impl ReceiveEpoch {
+    pub(super) fn new(epoch: u64, keys: Option<RecordKeys>) -> Self {
+        Self {
+            epoch,
+            keys,
+            window: ReplayWindow::default(),
+        }
+    }
+
+    pub(super) fn epoch(&self) -> u64 {
+        self.epoch
+    }
+}
+
+/// A record received from the peer, after removing its protection.
+pub(super) struct Record {
+    pub(super) number: RecordNumber,
+    pub(super) typ: ContentType,
+    pub(super) content: Vec<u8>,
+}
+
+/// Take the first record from `datagram`, and remove its protection.
+///
+/// On success, the returned slice is the rest of `datagram`.  `None` means
+/// the rest of the datagram cannot be parsed and should be dropped.  Records
+/// which cannot be deprotected (for example, because they have been tampered
+/// with, or their epoch is unknown) are skipped, by returning `None` for
+/// the record.
+pub(super) fn open<'a>(
+    datagram: &'a [u8],
+    epochs: &mut [ReceiveEpoch],
+) -> Option<(Option<Record>, &'a [u8])> {
+    let &first = datagram.first()?;
+
+    if first & UNIFIED_HEADER_MASK != UNIFIED_HEADER {
+        let (header, rest) = datagram.split_at_checked(PLAINTEXT_HEADER_LEN)?;
+        let len = u16::from_be_bytes([header[11], header[12]]) as usize;
+        let (content, rest) = rest.split_at_checked(len)?;
+
+        let epoch = u16::from_be_bytes([header[3], header[4]]);
+        let seq = u64::from_be_bytes([
+            0, 0, header[5], header[6], header[7], header[8], header[9], header[10],
+        ]);
+        let record = epochs
+            .iter_mut()
+            .find(|e| e.keys.is_none() && e.epoch == u64::from(epoch))
+            .filter(|e| !e.window.is_duplicate(seq))
+            .map(|e| {
+                e.window.mark(seq);
+                Record {
+                    number: RecordNumber {
+                        epoch: e.epoch,
+                        seq,
+                    },
+                    typ: ContentType(first),
+                    content: content.to_vec(),
+                }
+            });
+        return Some((record, rest));
+    }
+
+    if first & CONNECTION_ID != 0 {
+        // We never negotiate connection IDs, so cannot know where this record ends.
+        return None;
+    }
+
+    let seq_len = match first & SEQ_16 {
+        0 => 1,
+        _ => 2,
+    };
+    let header_len = 1 + seq_len + if first & LENGTH_PRESENT != 0 { 2 } else { 0 };
+    let header = datagram.get(..header_len)?;
+    let (record, rest) = match first & LENGTH_PRESENT {
+        0 => (datagram, &[][..]),
+        _ => {
+            let len = u16::from_be_bytes([header[1 + seq_len], header[2 + seq_len]]) as usize;
+            datagram.split_at_checked(header_len + len)?
+        }
+    };
+    let ciphertext = &record[header_len..];
+
+    let Some(epoch) = epochs
+        .iter_mut()
+        .find(|e| e.keys.is_some() && e.epoch & EPOCH_BITS == u64::from(first) & EPOCH_BITS)
+    else {
+        return Some((None, rest));
+    };
+    let keys = epoch.keys.as_ref().unwrap();
+
+    let Some(sample) = ciphertext.get(..keys.sequence_number.sample_len()) else {
+        return Some((None, rest));
+    };
+    let Ok(mask) = keys.sequence_number.mask(sample) else {
+        return Some((None, rest));
+    };
+
+    let mut aad = [0u8; MAX_CIPHERTEXT_HEADER_LEN];
+    let aad = &mut aad[..header_len];
+    aad.copy_from_slice(header);
+    let mut low = 0u64;
+    for i in 0..seq_len {
+        aad[1 + i] ^= mask[i];
+        low = (low << 8) | u64::from(aad[1 + i]);
+    }
+    let seq = reconstruct_seq(epoch.window.next(), low, seq_len as u32 * 8);
+
+    if epoch.window.is_duplicate(seq) {
+        return Some((None, rest));
+    }
+
+    let mut payload = ciphertext.to_vec();
+    let Ok(plain) = keys
+        .packet
+        .decrypt_in_place(seq, aad, &mut payload, None)
+    else {
+        return Some((None, rest));
+    };
+    let plain_len = plain.len();
+
+    // Remove padding, then the real content type.
+    let Some(typ_at) = payload[..plain_len]
+        .iter()
+        .rposition(|&b| b != 0)
+    else {
+        return Some((None, rest));
+    };
+    let typ = ContentType(payload[typ_at]);
+    payload.truncate(typ_at);
+
+    epoch.window.mark(seq);
+    Some((
+        Some(Record {
+            number: RecordNumber {
+                epoch: epoch.epoch,
+                seq,
+            },
+            typ,
+            content: payload,
+        }),
+        rest,
+    ))
+}
+
+/// Recover a full sequence number from its low `bits`, as the value closest to `expected`.
+///
+/// This is the algorithm from <https://www.rfc-editor.org/rfc/rfc9000#appendix-A.3>,
+/// which <https://www.rfc-editor.org/rfc/rfc9147#section-4.2.2> refers to.
+fn reconstruct_seq(expected: u64, low: u64, bits: u32) -> u64 {
+    let window = 1u64 << bits;
+    let half = window / 2;
+    let candidate = (expected & !(window - 1)) | low;
+
+    if candidate + half <= expected && candidate < MAX_SEQ - window {
+        candidate + window
+    } else if candidate > expected + half && candidate >= window {
+        candidate - window
+    } else {
+        candidate
+    }
+}
+
+/// Detects replayed records within one epoch.
+///
+/// See <https://www.rfc-editor.org/rfc/rfc9147#section-4.5.1>.
+#[derive(Default)]
+struct ReplayWindow {
+    /// The highest sequence number received so far.
+    highest: Option<u64>,
+    /// Bit `n` is set if `highest - n` has been received.
+    seen: u64,
+}
+
+impl ReplayWindow {
+    /// The sequence number we expect next.
+    fn next(&self) -> u64 {
+        self.highest.map_or(0, |h| h + 1)
+    }
+
+    fn is_duplicate(&self, seq: u64) -> bool {
+        let Some(highest) = self.highest else {
+            return false;
+        };
+
+        match highest.checked_sub(seq) {
+            None => false,
+            Some(behind) if behind >= 64 => true,
+            Some(behind) => self.seen & (1 << behind) != 0,
+        }
+    }
+
+    fn mark(&mut self, seq: u64) {
+        let Some(highest) = self.highest else {
+            self.highest = Some(seq);
+            self.seen = 1;
+            return;
+        };
+
+        match seq.checked_sub(highest) {
+            Some(ahead) => {
+                self.seen = self
+                    .seen
+                    .checked_shl(ahead as u32)
+                    .unwrap_or(0)
+                    | 1;
+                self.highest = Some(seq);
+            }
+            None => self.seen |= 1 << (highest - seq),
+        }
+    }
+}
+
+/// Sequence numbers are 48 bits.
+const MAX_SEQ: u64 = 1 << 48;
+
+const PLAINTEXT_HEADER_LEN: usize = 13;
+const CIPHERTEXT_HEADER_LEN: usize = 5;
+const MAX_CIPHERTEXT_HEADER_LEN: usize = 5;
+
+const UNIFIED_HEADER_MASK: u8 = 0b1110_0000;
+const UNIFIED_HEADER: u8 = 0b0010_0000;
+const CONNECTION_ID: u8 = 0b0001_0000;
+const SEQ_16: u8 = 0b0000_1000;
+const LENGTH_PRESENT: u8 = 0b0000_0100;
+const EPOCH_BITS: u64 = 0b11;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quic::Tag;
+
+    /// The unified header for a record with a 16-bit sequence number and a length,
+    /// from <https://www.rfc-editor.org/rfc/rfc9147#section-4>: `001CSLEE`, then
+    /// the sequence number, then the length.
+    #[test]
+    fn seals_unified_header() {
+        let mut epoch = SendEpoch::new(APPLICATION_EPOCH, Some(transparent_keys()));
+        epoch.next_seq = 0x0001_0203;
+
+        let mut out = Vec::new();
+        let number = epoch
+            .seal(ContentType::ApplicationData, b"hi", &mut out)
+            .unwrap();
+        assert_eq!(
+            number,
+            RecordNumber {
+                epoch: 3,
+                seq: 0x0001_0203
+            }
+        );
+        let mut expected = alloc::vec![0b0010_1111, 0x02, 0x03, 0x00, 0x13, b'h', b'i', 0x17];
+        expected.extend_from_slice(&[0; 16]);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn opens_unified_header_forms() {
+        let mut epochs = [ReceiveEpoch::new(HANDSHAKE_EPOCH, Some(transparent_keys()))];
+
+        // 8-bit sequence number, no length: the record fills the rest of the datagram.
+        // The content is followed by its type and then padding.
+        let datagram = with_tag(&[0b0010_0010, 0x05, b'a', 0x16, 0x00]);
+        let (record, rest) = open(&datagram, &mut epochs).unwrap();
+        let record = record.unwrap();
+        assert_eq!(record.number, RecordNumber { epoch: 2, seq: 5 });
+        assert_eq!(record.typ, ContentType::Handshake);
+        assert_eq!(record.content, b"a");
+        assert!(rest.is_empty());
+
+        // 16-bit sequence number and a length, followed by another record.
+        let mut datagram = with_tag(&[0b0010_1110, 0x00, 0x06, 0x00, 0x12, b'b', 0x15]);
+        datagram.push(0xff);
+        let (record, rest) = open(&datagram, &mut epochs).unwrap();
+        let record = record.unwrap();
+        assert_eq!(record.number, RecordNumber { epoch: 2, seq: 6 });
+        assert_eq!(record.typ, ContentType::Alert);
+        assert_eq!(record.content, b"b");
+        assert_eq!(rest, [0xff]);
+
+        // Connection IDs are never negotiated, so the record cannot be delimited.
+        let datagram = with_tag(&[0b0011_1110, 0x00, 0x07, 0x00, 0x12, b'c', 0x16]);
+        assert!(open(&datagram, &mut epochs).is_none());
+
+        // An unknown epoch is skipped.
+        let (record, rest) =
+            open(&[0b0010_1111, 0x00, 0x08, 0x00, 0x01, 0x17], &mut epochs).unwrap();
+        assert!(record.is_none());
+        assert!(rest.is_empty());
+    }
+
+    fn with_tag(record: &[u8]) -> Vec<u8> {
+        let mut out = record.to_vec();
+        out.extend_from_slice(&[0; 16]);
+        out
+    }
+
+    /// Keys which neither encrypt nor mask, and use an all-zero tag, so the
+    /// record layout is visible.
+    fn transparent_keys() -> RecordKeys {
+        RecordKeys {
+            packet: Box::new(Transparent),
+            sequence_number: Box::new(Transparent),
+        }
+    }
+
+    struct Transparent;
+
+    impl PacketKey for Transparent {
+        fn encrypt_in_place(
+            &self,
+            _packet_number: u64,
+            _header: &[u8],
+            _payload: &mut [u8],
+            _path_id: Option<u32>,
+        ) -> Result<Tag, Error> {
+            Ok(Tag::from(&[0; 16][..]))
+        }
+
+        fn decrypt_in_place<'a>(
+            &self,
+            _packet_number: u64,
+            _header: &[u8],
+            payload: &'a mut [u8],
+            _path_id: Option<u32>,
+        ) -> Result<&'a [u8], Error> {
+            match payload.split_last_chunk::<16>() {
+                Some((plain, tag)) if *tag == [0; 16] => Ok(plain),
+                _ => Err(Error::DecryptError),
+            }
+        }
+
+        fn tag_len(&self) -> usize {
+            16
+        }
+
+        fn confidentiality_limit(&self) -> u64 {
+            u64::MAX
+        }
+
+        fn integrity_limit(&self) -> u64 {
+            u64::MAX
+        }
+    }
+
+    impl HeaderProtectionKey for Transparent {
+        fn encrypt_in_place(
+            &self,
+            _sample: &[u8],
+            _first: &mut u8,
+            _packet_number: &mut [u8],
+        ) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn decrypt_in_place(
+            &self,
+            _sample: &[u8],
+            _first: &mut u8,
+            _packet_number: &mut [u8],
+        ) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn mask(&self, _sample: &[u8]) -> Result<[u8; 5], Error> {
+            Ok([0; 5])
+        }
+
+        fn sample_len(&self) -> usize {
+            1
+        }
+    }
+
+    #[test]
+    fn reconstructs_sequence_numbers() {
+        assert_eq!(reconstruct_seq(0, 0, 8), 0);
+        assert_eq!(reconstruct_seq(0xff, 0x00, 8), 0x100);
+        assert_eq!(reconstruct_seq(0x101, 0xff, 8), 0xff);
+        assert_eq!(reconstruct_seq(0xa82f30ea, 0x9b32, 16), 0xa82f9b32);
+    }
+
+    #[test]
+    fn replay_window() {
+        let mut window = ReplayWindow::default();
+        assert!(!window.is_duplicate(5));
+        window.mark(5);
+        assert!(window.is_duplicate(5));
+        assert!(!window.is_duplicate(3));
+        window.mark(3);
+        assert!(window.is_duplicate(3));
+        window.mark(100);
+        assert!(window.is_duplicate(5));
+        assert!(!window.is_duplicate(99));
+        assert_eq!(window.next(), 101);
+    }
+}
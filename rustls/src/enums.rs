@@ -129,6 +129,38 @@ impl AsRef<[u8]> for ApplicationProtocol<'_> {
     }
 }
 
+/// Application settings to exchange for an ALPN protocol, using the ALPS extension.
+///
+/// ALPS lets HTTP/2 and HTTP/3 send their `SETTINGS` within the TLS 1.3 handshake.
+/// It is specified in [draft-vvv-tls-alps].
+///
+/// [draft-vvv-tls-alps]: https://datatracker.ietf.org/doc/html/draft-vvv-tls-alps
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApplicationSettings {
+    /// The ALPN protocol these settings apply to.
+    pub protocol: ApplicationProtocol<'static>,
+    /// The settings, in a format defined by `protocol`.
+    pub settings: Vec<u8>,
+}
+
+impl ApplicationSettings {
+    /// Make a new value, sending `settings` if `protocol` is negotiated.
+    pub fn new(protocol: ApplicationProtocol<'static>, settings: Vec<u8>) -> Self {
+        Self { protocol, settings }
+    }
+
+    /// Find the settings configured for `protocol` in `all`.
+    pub(crate) fn find<'a>(
+        all: &'a [Self],
+        protocol: &ApplicationProtocol<'_>,
+    ) -> Option<&'a [u8]> {
+        all.iter()
+            .find(|s| s.protocol == *protocol)
+            .map(|s| s.settings.as_slice())
+    }
+}
+
 enum_builder! {
     /// The `HandshakeType` TLS protocol enum.  Values in this enum are taken
     /// from the various RFCs covering TLS, and are listed by IANA.
@@ -12,7 +12,7 @@ use pki_types::{AlgorithmIdentifier, EchConfigListBytes, ServerName, UnixTime};
 use webpki::ExtendedKeyUsage;
 
 use crate::common_state::maybe_send_fatal_alert;
-use crate::conn::SendPath;
+use crate::conn::{SendPath, TlsOutputBuffer};
 use crate::crypto::kx::KeyExchangeAlgorithm;
 use crate::crypto::{CipherSuite, GetRandomFailed, InconsistentKeys};
 use crate::enums::{ContentType, HandshakeType};
@@ -109,6 +109,21 @@ pub enum Error {
     /// or too large.
     BadMaxFragmentSize,
 
+    /// A [`TlsOutputBuffer`] did not have room for the data to be written into it.
+    ///
+    /// `required` is the number of bytes the operation needed to write.
+    ///
+    /// When sending application data, a `close_notify` alert or a key update, this
+    /// is reported before anything is written, and the operation can be retried with
+    /// a larger buffer.  When writing handshake messages, it is fatal for the
+    /// connection.
+    ///
+    /// [`TlsOutputBuffer`]: crate::TlsOutputBuffer
+    InsufficientOutputSpace {
+        /// The number of bytes needed.
+        required: usize,
+    },
+
     /// Specific failure cases from [`Credentials::new()`] or a
     /// [`crate::crypto::SigningKey`] that cannot produce a corresponding public key.
     ///
@@ -230,6 +245,12 @@ impl fmt::Display for Error {
             Self::BadMaxFragmentSize => {
                 write!(f, "the supplied max_fragment_size was too small or large")
             }
+            Self::InsufficientOutputSpace { required } => {
+                write!(
+                    f,
+                    "insufficient space in output buffer: need {required} bytes"
+                )
+            }
             Self::InconsistentKeys(why) => {
                 write!(f, "keys may not be consistent: {why:?}")
             }
@@ -1132,6 +1153,8 @@ pub enum InvalidMessage {
     UnknownHelloRetryRequestExtension,
     /// The peer sent a TLS1.3 Certificate with an unknown extension
     UnknownCertificateExtension,
+    /// A DTLS1.3 client sent a non-empty `legacy_cookie` in its ClientHello
+    NonEmptyLegacyCookie,
 }
 
 impl From<InvalidMessage> for AlertDescription {
@@ -1141,6 +1164,7 @@ impl From<InvalidMessage> for AlertDescription {
             InvalidMessage::DuplicateExtension(_) => Self::IllegalParameter,
             InvalidMessage::MisplacedExtension(_) => Self::IllegalParameter,
             InvalidMessage::UnsupportedCompression => Self::IllegalParameter,
+            InvalidMessage::NonEmptyLegacyCookie => Self::IllegalParameter,
             InvalidMessage::UnknownHelloRetryRequestExtension => Self::UnsupportedExtension,
             InvalidMessage::CertificatePayloadTooLarge => Self::BadCertificate,
             _ => Self::DecodeError,
@@ -1171,11 +1195,13 @@ pub enum PeerMisbehaved {
     DuplicateNewSessionTicketExtensions,
     DuplicateServerHelloExtensions,
     DuplicateServerNameTypes,
+    EarlyDataAcceptedWithVariedApplicationSettings,
     EarlyDataAttemptedInSecondClientHello,
     EarlyDataExtensionWithoutResumption,
     EarlyDataOfferedWithVariedCipherSuite,
     EmptyFragment,
     HandshakeHashVariedAfterRetry,
+    HandshakeMessageInWrongDtlsEpoch,
     /// Received an alert with an undefined level and the given [`AlertDescription`]
     IllegalAlertLevel(u8, AlertDescription),
     IllegalHelloRetryRequestWithEmptyCookie,
@@ -1191,13 +1217,16 @@ pub enum PeerMisbehaved {
     /// Received a warning alert with the given [`AlertDescription`]
     IllegalWarningAlert(AlertDescription),
     IncorrectBinder,
+    IncorrectCookieInSecondClientHello,
     IncorrectFinished,
+    IncorrectRenegotiationInfo,
     InvalidCertCompression,
     InvalidMaxEarlyDataSize,
     InvalidKeyShare,
     KeyEpochWithPendingFragment,
     KeyUpdateReceivedInQuicConnection,
     MessageInterleavedWithHandshakeMessage,
+    MissingApplicationSettings,
     MissingBinderInPskExtension,
     MissingKeyShare,
     MissingPskModesExtension,
@@ -1225,6 +1254,7 @@ pub enum PeerMisbehaved {
     SelectedUnofferedCompression,
     SelectedUnofferedKxGroup,
     SelectedUnofferedPsk,
+    ServerCertificateChangedDuringRenegotiation,
     ServerEchoedCompatibilitySessionId,
     ServerHelloMustOfferUncompressedEcPoints,
     ServerNameDifferedOnRetry,
@@ -1257,6 +1287,7 @@ impl From<PeerMisbehaved> for AlertDescription {
             | PeerMisbehaved::UnsolicitedServerHelloExtension => Self::UnsupportedExtension,
 
             PeerMisbehaved::IllegalMiddleboxChangeCipherSpec
+            | PeerMisbehaved::HandshakeMessageInWrongDtlsEpoch
             | PeerMisbehaved::KeyEpochWithPendingFragment
             | PeerMisbehaved::KeyUpdateReceivedInQuicConnection
             | PeerMisbehaved::IllegalTls13ContentType => Self::UnexpectedMessage,
@@ -1267,10 +1298,14 @@ impl From<PeerMisbehaved> for AlertDescription {
                 Self::DecryptError
             }
 
+            PeerMisbehaved::IncorrectRenegotiationInfo
+            | PeerMisbehaved::ServerCertificateChangedDuringRenegotiation => Self::HandshakeFailure,
+
             PeerMisbehaved::InvalidCertCompression
             | PeerMisbehaved::SelectedUnofferedCertCompression => Self::BadCertificate,
 
-            PeerMisbehaved::MissingKeyShare
+            PeerMisbehaved::MissingApplicationSettings
+            | PeerMisbehaved::MissingKeyShare
             | PeerMisbehaved::MissingPskModesExtension
             | PeerMisbehaved::MissingQuicTransportParameters => Self::MissingExtension,
 
@@ -1295,6 +1330,7 @@ pub enum PeerIncompatible {
     IncorrectCertificateTypeExtension,
     KeyShareExtensionRequired,
     MultipleRawKeys,
+    Dtls13NotOffered,
     NamedGroupsExtensionRequired,
     NoCertificateRequestSignatureSchemesInCommon,
     NoCipherSuitesInCommon,
@@ -1303,6 +1339,7 @@ pub enum PeerIncompatible {
     NoSignatureSchemesInCommon,
     NoServerNameProvided,
     NullCompressionRequired,
+    ServerDoesNotSupportDtls13,
     ServerDoesNotSupportTls12Or13,
     ServerSentHelloRetryRequestWithUnknownExtension,
     ServerTlsVersionIsDisabledByOurConfig,
@@ -1321,7 +1358,9 @@ impl From<PeerIncompatible> for AlertDescription {
         match e {
             PeerIncompatible::NullCompressionRequired => Self::IllegalParameter,
 
-            PeerIncompatible::ServerTlsVersionIsDisabledByOurConfig
+            PeerIncompatible::Dtls13NotOffered
+            | PeerIncompatible::ServerDoesNotSupportDtls13
+            | PeerIncompatible::ServerTlsVersionIsDisabledByOurConfig
             | PeerIncompatible::SupportedVersionsExtensionRequired
             | PeerIncompatible::Tls12NotOffered
             | PeerIncompatible::Tls12NotOfferedOrEnabled
@@ -1674,6 +1713,11 @@ pub enum ApiMisuse {
     /// [`ServerConnection::set_resumption_data()`]: crate::server::ServerConnection::set_resumption_data()
     ResumptionDataProvidedTooLate,
 
+    /// Data passed to [`ClientConnection::set_resumption_data()`] must be less than 2^15 bytes.
+    ///
+    /// [`ClientConnection::set_resumption_data()`]: crate::client::ClientConnection::set_resumption_data()
+    ResumptionDataTooLong,
+
     /// [`KernelConnection::update_tx_secret()`] and associated are not available for TLS1.2 connections.
     ///
     /// [`KernelConnection::update_tx_secret()`]: crate::conn::kernel::KernelConnection::update_tx_secret()
@@ -1703,6 +1747,80 @@ pub enum ApiMisuse {
 
     /// Plaintext cannot be encrypted after the send path has been closed.
     WriteTlsAfterSendPathClosed,
+
+    /// A component key given to [`CompositeSigningKey::new()`][] does not match
+    /// its [`CompositeAlgorithm`][].
+    ///
+    /// Each key must be able to produce its public key.  The post-quantum key's
+    /// public key must have the expected length, and the classical key must support
+    /// its component's `SignatureScheme`.
+    ///
+    /// [`CompositeSigningKey::new()`]: crate::crypto::composite::CompositeSigningKey::new()
+    /// [`CompositeAlgorithm`]: crate::crypto::composite::CompositeAlgorithm
+    InvalidCompositeComponent,
+
+    /// The [`CompletedKeyExchange`][] given to [`CompleteKeyExchange::continue_with()`][]
+    /// was for a different group to the one negotiated.
+    ///
+    /// [`CompletedKeyExchange`]: crate::crypto::kx::CompletedKeyExchange
+    /// [`CompleteKeyExchange::continue_with()`]: crate::server::CompleteKeyExchange::continue_with()
+    CompletedKeyExchangeGroupMismatch,
+
+    /// A connection was serialized while part of a handshake message was buffered.
+    ///
+    /// Supply the rest of the message to [`Connection::process_new_packets()`][],
+    /// and then try again.
+    ///
+    /// [`Connection::process_new_packets()`]: crate::Connection::process_new_packets()
+    SerializeWithPartialMessage,
+
+    /// A serialized connection was produced by an incompatible version of rustls,
+    /// or for the other side of the connection.
+    ///
+    /// See [`ClientConnection::dangerous_restore()`][].
+    ///
+    /// [`ClientConnection::dangerous_restore()`]: crate::ClientConnection::dangerous_restore()
+    IncompatibleSerializedConnection,
+
+    /// DTLS attempted with a configuration that does not support TLS1.3.
+    DtlsRequiresTls13Support,
+
+    /// DTLS attempted with a configuration that does not support a ciphersuite that
+    /// can protect DTLS records.
+    ///
+    /// DTLS uses the same algorithms as QUIC, so at least one cipher suite
+    /// must support QUIC.
+    NoDtlsCompatibleCipherSuites,
+
+    /// DTLS attempted with early data enabled.
+    ///
+    /// Early data is not supported for DTLS, so [`ClientConfig::enable_early_data`][]
+    /// must be false and [`ServerConfig::max_early_data_size`][] must be zero.
+    ///
+    /// [`ClientConfig::enable_early_data`]: crate::client::ClientConfig::enable_early_data
+    /// [`ServerConfig::max_early_data_size`]: crate::server::ServerConfig::max_early_data_size
+    DtlsForbidsEarlyData,
+
+    /// Application data given to [`dtls::Connection::send()`][] does not fit in
+    /// one datagram.
+    ///
+    /// [`dtls::Connection::send()`]: crate::dtls::Connection::send()
+    DtlsRecordTooLarge,
+
+    /// A QUIC Retry integrity tag was requested from a [`quic::Suite`][] other
+    /// than `TLS13_AES_128_GCM_SHA256`.
+    ///
+    /// The Retry integrity tag is always computed with AEAD_AES_128_GCM.
+    ///
+    /// [`quic::Suite`]: crate::quic::Suite
+    QuicRetryRequiresAes128Gcm,
+
+    /// The QUIC version was changed after handshake keys were derived.
+    ///
+    /// See [`quic::ClientConnection::set_version()`][].
+    ///
+    /// [`quic::ClientConnection::set_version()`]: crate::quic::ClientConnection::set_version()
+    QuicVersionChangedAfterHandshakeKeys,
 }
 
 impl fmt::Display for ApiMisuse {
@@ -1757,34 +1875,30 @@ mod other_error {
 
 pub use other_error::OtherError;
 
-/// An [`Error`] along with the (possibly encrypted) alert to send to
-/// the peer.
+/// An [`Error`] which has been accompanied by a (possibly encrypted) alert
+/// to send to the peer.
 ///
-/// This borrows the output buffer passed to the operation that failed;
-/// the alert (if one is to be sent) has been appended to that buffer.
-pub struct ErrorWithAlert<'a> {
+/// The alert (if one is to be sent) has been appended to the output buffer
+/// passed to the operation that failed, after any TLS data generated before
+/// the error.  Send that data to the peer before closing the connection.
+#[non_exhaustive]
+pub struct ErrorWithAlert {
     /// The error
     pub error: Error,
-    pub(crate) tls: &'a mut Vec<u8>,
 }
 
-impl<'a> ErrorWithAlert<'a> {
-    pub(crate) fn new(error: Error, send_path: &mut SendPath, tls: &'a mut Vec<u8>) -> Self {
+impl ErrorWithAlert {
+    pub(crate) fn new(
+        error: Error,
+        send_path: &mut SendPath,
+        tls: &mut dyn TlsOutputBuffer,
+    ) -> Self {
         maybe_send_fatal_alert(send_path, &error, tls);
-        Self { error, tls }
-    }
-
-    /// Yields remaining TLS data, if any, to send to the peer.
-    ///
-    /// The returned slice is the contents of the output buffer passed to the failed operation:
-    /// the alert (if one is to be sent) and any TLS data generated before the error.  Send it to
-    /// the peer before closing the connection.
-    pub fn tls(&self) -> &[u8] {
-        self.tls
+        Self { error }
     }
 }
 
-impl Deref for ErrorWithAlert<'_> {
+impl Deref for ErrorWithAlert {
     type Target = Error;
 
     fn deref(&self) -> &Self::Target {
@@ -1792,11 +1906,10 @@ impl Deref for ErrorWithAlert<'_> {
     }
 }
 
-impl fmt::Debug for ErrorWithAlert<'_> {
+impl fmt::Debug for ErrorWithAlert {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("ErrorWithAlert")
             .field("error", &self.error)
-            .field("tls", &self.tls.len())
-            .finish_non_exhaustive()
+            .finish()
     }
 }
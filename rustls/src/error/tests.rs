@@ -254,6 +254,7 @@ fn smoke() {
         Error::PeerSentOversizedRecord,
         Error::NoApplicationProtocol,
         Error::BadMaxFragmentSize,
+        Error::InsufficientOutputSpace { required: 1234 },
         Error::InconsistentKeys(InconsistentKeys::KeyMismatch),
         Error::InconsistentKeys(InconsistentKeys::Unknown),
         Error::InvalidCertRevocationList(CertRevocationListError::BadSignature),
@@ -325,7 +326,7 @@ fn error_with_alert() {
     );
     assert_eq!(
         std::format!("{e:?}"),
-        "ErrorWithAlert { error: NoApplicationProtocol, tls: 7, .. }"
+        "ErrorWithAlert { error: NoApplicationProtocol }"
     );
-    assert_eq!(e.tls(), &[21, 3, 3, 0, 2, 2, 120][..]);
+    assert_eq!(tls, &[21, 3, 3, 0, 2, 2, 120][..]);
 }
@@ -192,7 +192,7 @@
 //!
 //! Now you should do appropriate IO for the `client` object.  Operations that produce TLS
 //! data to send to the peer -- such as `build()` above and `client.process_new_packets()` --
-//! append it to the `Vec<u8>` you pass; write those bytes to the underlying connection
+//! append it to the [`TlsOutputBuffer`] you pass (here, a `Vec<u8>`); write those bytes to the underlying connection
 //! whenever it is able to send data.  If `client.wants_read()` yields true, you should
 //! call `client.process_new_packets()` with the data from the underlying connection.
 //! You should continue doing this as long as the connection is valid.
@@ -377,6 +377,7 @@ mod bs_debug;
 mod builder;
 pub mod enums;
 mod key_log;
+mod observer;
 mod suites;
 mod versions;
 #[cfg(feature = "webpki")]
@@ -393,8 +394,8 @@ pub mod internal {
 pub use crate::builder::{ConfigBuilder, ConfigSide, WantsVerifier};
 pub use crate::common_state::{CommonState, ConnectionOutputs, HandshakeKind, Protocol};
 pub use crate::conn::{
-    Connection, IoState, KeyingMaterialExporter, MessageHandler, SideData, SliceInput,
-    TlsInputBuffer, VecInput, kernel,
+    Connection, IoState, KeyingMaterialExporter, MessageHandler, RecordPadder, RecordPadding,
+    SideData, SliceInput, TlsInputBuffer, TlsOutputBuffer, VecInput, kernel,
 };
 /// Types related to "split" mode.
 ///
@@ -407,10 +408,11 @@ pub mod split {
 }
 pub use crate::error::Error;
 pub use crate::key_log::{KeyLog, NoKeyLog};
+pub use crate::observer::{ConnectionEvent, ConnectionObserver, ResumptionRejection};
 pub use crate::suites::{
     CipherSuiteCommon, ConnectionTrafficSecrets, ExtractedSecrets, SupportedCipherSuite,
 };
-pub use crate::ticketer::TicketRotator;
+pub use crate::ticketer::{TicketKeyGeneration, TicketRotator, TicketRotatorStatus};
 pub use crate::tls12::Tls12CipherSuite;
 pub use crate::tls13::Tls13CipherSuite;
 pub use crate::verify::{DigitallySignedStruct, DistinguishedName, SignerPublicKey};
@@ -441,9 +443,13 @@ pub mod pki_types {
     pub use pki_types::*;
 }
 
+pub mod qlog;
+
 /// APIs for implementing QUIC TLS
 pub mod quic;
 
+pub mod dtls;
+
 /// APIs for implementing TLS tickets
 pub mod ticketer;
 
@@ -84,6 +84,16 @@ impl<K: Eq + Hash + Clone + Debug, V> LimitedCache<K, V> {
 
         Some(value)
     }
+
+    /// Remove all entries for which `keep` returns false, returning how many were removed.
+    pub(crate) fn retain(&mut self, mut keep: impl FnMut(&K, &V) -> bool) -> usize {
+        let before = self.map.len();
+        self.map.retain(|k, v| keep(k, v));
+        let map = &self.map;
+        self.oldest
+            .retain(|k| map.contains_key(k));
+        before - self.map.len()
+    }
 }
 
 impl<K: Eq + Hash + Clone + Debug, V: Default> LimitedCache<K, V> {
@@ -240,4 +250,22 @@ mod tests {
 
         assert_eq!(t.get("abc"), Some(&6));
     }
+
+    #[test]
+    fn test_retain_removes_items() {
+        let mut t = Test::new(3);
+        t.insert("abc".into(), 1);
+        t.insert("def".into(), 2);
+
+        assert_eq!(t.retain(|_, v| *v != 1), 1);
+        assert_eq!(t.get("abc"), None);
+        assert_eq!(t.get("def"), Some(&2));
+
+        t.insert("ghi".into(), 3);
+        t.insert("jkl".into(), 4);
+
+        assert_eq!(t.get("def"), None);
+        assert_eq!(t.get("ghi"), Some(&3));
+        assert_eq!(t.get("jkl"), Some(&4));
+    }
 }
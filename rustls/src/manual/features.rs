@@ -36,6 +36,8 @@ and are dependent on the provider used.
   and servers `*`
 * Client-side Encrypted client hello (ECH)
    ([RFC 9849](https://datatracker.ietf.org/doc/html/rfc9849)).
+* DTLS1.3 ([RFC 9147](https://datatracker.ietf.org/doc/html/rfc9147)), without
+  early data or connection IDs, in [`crate::dtls`].
 
 [^1]: Note that, at the time of writing, Ed25519 does not have wide support
       in browsers.  It is also not supported by the WebPKI, because the
@@ -54,7 +56,8 @@ and will not support:
 * EXPORT ciphersuites
 * MAC-then-encrypt ciphersuites
 * Ciphersuites without forward secrecy
-* Renegotiation
+* Renegotiation, other than server-initiated TLS1.2 secure renegotiation
+  ([RFC 5746](https://tools.ietf.org/html/rfc5746)) by clients `*`
 * Kerberos
 * TLS 1.2 protocol compression
 * Discrete-log Diffie-Hellman `*`
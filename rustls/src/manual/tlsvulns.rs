@@ -151,15 +151,19 @@ feature of all versions of TLS allows a MitM to splice a request of their choice
 request.  A countermeasure was proposed and widely implemented to bind renegotiations to their previous negotiations;
 unfortunately this was insufficient.
 
-rustls does not support renegotiation in TLSv1.2.  TLSv1.3 also no longer supports renegotiation.
+rustls does not support renegotiation in TLSv1.2 by default.  Clients can opt in to server-initiated renegotiation
+using [`ClientConfig::max_renegotiations`][crate::client::ClientConfig::max_renegotiations], which always uses the
+RFC 5746 "Secure Renegotiation" binding.  rustls servers never renegotiate.  TLSv1.3 also no longer supports renegotiation.
 
 ## 3SHAKE
 
 [3SHAKE](https://web.archive.org/web/www.mitls.org/pages/attacks/3SHAKE) (2014) described a complex attack that broke the "Secure Renegotiation" extension
 introduced as a countermeasure to the previous protocol flaw.
 
-rustls does not support renegotiation for TLSv1.2 connections, or RSA key exchange, and both are required for this attack
-to work.  rustls implements the "Extended Main Secret" (RFC 7627, as renamed by RFC 9846) extension for TLSv1.2 which was standardised as a countermeasure.
+rustls does not support RSA key exchange, or renegotiation for TLSv1.2 connections unless a client opts in, and both are
+required for this attack to work.  rustls implements the "Extended Main Secret" (RFC 7627, as renamed by RFC 9846) extension for TLSv1.2 which was standardised as a countermeasure.
+A client that opts in to renegotiation only allows it on connections using that extension, and refuses a renegotiation in which the
+server presents a different end-entity certificate.
 
 TLSv1.3 no longer supports renegotiation and RSA key exchange.  It also effectively incorporates the improvements made in RFC 7627.
 
@@ -55,6 +55,12 @@ impl ClientHelloPayload {
             _ => self.session_id.encode(bytes),
         }
 
+        if self.client_version == ProtocolVersion::DTLSv1_2 {
+            // DTLS1.3 clients send an empty `legacy_cookie`.
+            // <https://www.rfc-editor.org/rfc/rfc9147#section-5.3>
+            0u8.encode(bytes);
+        }
+
         self.cipher_suites.encode(bytes);
         self.compression_methods.encode(bytes);
 
@@ -109,10 +115,18 @@ impl Codec<'_> for ClientHelloPayload {
 
     fn read(r: &mut Reader<'_>) -> Result<Self, InvalidMessage> {
         r.all("ClientHelloPayload", |r| {
+            let client_version = ProtocolVersion::read(r)?;
+            let random = Random::read(r)?;
+            let session_id = SessionId::read(r)?;
+
+            if client_version == ProtocolVersion::DTLSv1_2 && u8::read(r)? != 0 {
+                return Err(InvalidMessage::NonEmptyLegacyCookie);
+            }
+
             Ok(Self {
-                client_version: ProtocolVersion::read(r)?,
-                random: Random::read(r)?,
-                session_id: SessionId::read(r)?,
+                client_version,
+                random,
+                session_id,
                 cipher_suites: Vec::read(r)?,
                 compression_methods: Vec::read(r)?,
                 extensions: Box::new(ClientExtensions::read(r)?.into_owned()),
@@ -223,6 +237,10 @@ extension_struct! {
         ExtensionType::TicketRequest =>
             pub(crate) ticket_request: Option<ClientTicketRequest>,
 
+        /// Protocols for which application settings are supported (draft-vvv-tls-alps)
+        ExtensionType::ApplicationSettings =>
+            pub(crate) application_settings: Option<Vec<ApplicationProtocol<'a>>>,
+
         /// Secure renegotiation (RFC 5746)
         ExtensionType::RenegotiationInfo =>
             pub(crate) renegotiation_info: Option<SizedPayload<'a, u8>>,
@@ -266,6 +284,7 @@ impl ClientExtensions<'_> {
             key_shares,
             transport_parameters,
             ticket_request,
+            application_settings,
             renegotiation_info,
             encrypted_client_hello,
             encrypted_client_hello_outer,
@@ -297,6 +316,11 @@ impl ClientExtensions<'_> {
             key_shares,
             transport_parameters: transport_parameters.map(|x| x.into_owned()),
             ticket_request,
+            application_settings: application_settings.map(|ps| {
+                ps.into_iter()
+                    .map(|p| p.to_owned())
+                    .collect::<Vec<_>>()
+            }),
             renegotiation_info: renegotiation_info.map(|x| x.into_owned()),
             encrypted_client_hello,
             encrypted_client_hello_outer,
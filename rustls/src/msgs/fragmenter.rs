@@ -42,6 +42,11 @@ impl Fragmenter {
         })
     }
 
+    /// The maximum length of a record's payload, excluding the record header.
+    pub(crate) fn max_payload_len(&self) -> usize {
+        self.max_frag.get()
+    }
+
     /// Set the maximum fragment size that will be produced.
     ///
     /// This is the maximum size of each TLS record on the wire, including the
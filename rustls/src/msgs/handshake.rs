@@ -283,12 +283,14 @@ impl TlsListElement for KeyShareEntry {
 pub(crate) struct SupportedProtocolVersions {
     pub(crate) tls13: bool,
     pub(crate) tls12: bool,
+    /// DTLS1.3 (RFC 9147).
+    pub(crate) dtls13: bool,
 }
 
 impl SupportedProtocolVersions {
     /// Return true if `filter` returns true for any enabled version.
     pub(crate) fn any(&self, filter: impl Fn(ProtocolVersion) -> bool) -> bool {
-        if self.tls13 && filter(ProtocolVersion::TLSv1_3) {
+        if (self.tls13 || self.dtls13) && filter(ProtocolVersion::TLSv1_3) {
             return true;
         }
         if self.tls12 && filter(ProtocolVersion::TLSv1_2) {
@@ -305,6 +307,9 @@ impl SupportedProtocolVersions {
 impl Codec<'_> for SupportedProtocolVersions {
     fn encode(&self, bytes: &mut Vec<u8>) {
         let inner = LengthPrefixedBuffer::new(Self::LIST_LENGTH, bytes);
+        if self.dtls13 {
+            ProtocolVersion::DTLSv1_3.encode(inner.buf);
+        }
         if self.tls13 {
             ProtocolVersion::TLSv1_3.encode(inner.buf);
         }
@@ -316,16 +321,22 @@ impl Codec<'_> for SupportedProtocolVersions {
     fn read(reader: &mut Reader<'_>) -> Result<Self, InvalidMessage> {
         let mut tls12 = false;
         let mut tls13 = false;
+        let mut dtls13 = false;
 
         for pv in TlsListIter::<ProtocolVersion>::new(reader)? {
             match pv? {
                 ProtocolVersion::TLSv1_3 => tls13 = true,
                 ProtocolVersion::TLSv1_2 => tls12 = true,
+                ProtocolVersion::DTLSv1_3 => dtls13 = true,
                 _ => continue,
             };
         }
 
-        Ok(Self { tls13, tls12 })
+        Ok(Self {
+            tls13,
+            tls12,
+            dtls13,
+        })
     }
 }
 
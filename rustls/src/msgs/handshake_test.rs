@@ -1418,6 +1418,7 @@ fn sample_encrypted_extensions() -> Box<EncryptedExtensions<'static>> {
         encrypted_client_hello_ack: Some(ServerEncryptedClientHello {
             retry_configs: vec![],
         }),
+        application_settings: Some(Payload::new(vec![4, 5, 6])),
         unknown_extensions: Default::default(),
     })
 }
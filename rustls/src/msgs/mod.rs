@@ -50,10 +50,9 @@ pub(crate) use client_hello::{
 };
 
 mod codec;
-use codec::U24;
 pub(crate) use codec::{
     CERTIFICATE_MAX_SIZE_LIMIT, Codec, LengthPrefixedBuffer, ListLength, MaybeEmpty, NonEmpty,
-    Reader, SizedPayload, TlsListElement, hex, put_u16, put_u64,
+    Reader, SizedPayload, TlsListElement, U24, hex, put_u16, put_u64,
 };
 
 mod deframer;
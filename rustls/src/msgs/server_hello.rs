@@ -276,6 +276,10 @@ extension_struct! {
         /// Encrypted inner client hello response (RFC 9849)
         ExtensionType::EncryptedClientHello =>
             pub(crate) encrypted_client_hello_ack: Option<ServerEncryptedClientHello>,
+
+        /// Application settings for the selected protocol (draft-vvv-tls-alps)
+        ExtensionType::ApplicationSettings =>
+            pub(crate) application_settings: Option<Payload<'a>>,
     } + {
         pub(crate) unknown_extensions: BTreeSet<u16>,
     }
@@ -292,6 +296,7 @@ impl EncryptedExtensions<'_> {
             early_data_ack,
             ticket_request,
             encrypted_client_hello_ack,
+            application_settings,
             unknown_extensions,
         } = self;
         EncryptedExtensions {
@@ -303,6 +308,7 @@ impl EncryptedExtensions<'_> {
             early_data_ack,
             ticket_request,
             encrypted_client_hello_ack,
+            application_settings: application_settings.map(|x| x.into_owned()),
             unknown_extensions,
         }
     }
@@ -0,0 +1,144 @@
+use core::fmt::Debug;
+use core::time::Duration;
+
+use crate::error::{AlertDescription, Error};
+use crate::sync::Arc;
+
+/// Observes the progress of connections, for metrics and tracing.
+///
+/// Set this with [`ClientConfig::observer`] or [`ServerConfig::observer`], and it
+/// is called for each [`ConnectionEvent`] of every connection made with that config.
+///
+/// Events are delivered synchronously, on the thread progressing the connection,
+/// so implementations should return promptly.  You'll likely want some interior
+/// mutability in your implementation to make this useful.
+///
+/// [`ClientConfig::observer`]: crate::ClientConfig::observer
+/// [`ServerConfig::observer`]: crate::ServerConfig::observer
+pub trait ConnectionObserver: Debug + Send + Sync {
+    /// Called when `event` happens on a connection.
+    fn observe(&self, event: &ConnectionEvent<'_>);
+}
+
+/// Something that happened on a connection.
+///
+/// See [`ConnectionObserver`].
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum ConnectionEvent<'a> {
+    /// The client sent a `ClientHello`.
+    ///
+    /// This happens again if the server responds with a `HelloRetryRequest`.
+    ClientHelloSent,
+
+    /// The server received a `ClientHello`, and is processing it with this config.
+    ///
+    /// This happens again for the client's response to a `HelloRetryRequest`.
+    ClientHelloReceived,
+
+    /// The server sent, or the client received, a `HelloRetryRequest`.
+    HelloRetryRequest,
+
+    /// The peer's certificate was verified.
+    ///
+    /// This does not happen for a resumed handshake: the peer was
+    /// verified during the original handshake.
+    CertificateVerified,
+
+    /// The server examined a session that the client offered to resume.
+    ///
+    /// This happens for each ticket or session ID the server examines,
+    /// before `ResumptionAccepted` or `ResumptionRejected`.
+    ResumptionOffered {
+        /// How long ago the session was created, if the server could read it.
+        age: Option<Duration>,
+        /// Why the session cannot be resumed, or `None` if it can be.
+        rejection: Option<ResumptionRejection>,
+    },
+
+    /// The client offered to resume a session, and the server accepted.
+    ResumptionAccepted,
+
+    /// The client offered to resume a session, and the server did not accept.
+    ResumptionRejected,
+
+    /// The client sent early data, and the server accepted it.
+    EarlyDataAccepted,
+
+    /// The client sent early data, and the server rejected it.
+    EarlyDataRejected,
+
+    /// We updated our sending keys, and told the peer with a `KeyUpdate` message.
+    KeyUpdateSent,
+
+    /// The peer sent a `KeyUpdate` message, and we updated our receiving keys.
+    KeyUpdateReceived,
+
+    /// We sent an alert other than `close_notify`.
+    AlertSent {
+        /// The alert that was sent.
+        description: AlertDescription,
+        /// The error that caused the alert, for fatal alerts.
+        error: Option<&'a Error>,
+    },
+
+    /// We received an alert other than `close_notify`.
+    AlertReceived {
+        /// The alert that was received.
+        description: AlertDescription,
+        /// The error that the alert causes, for fatal alerts.
+        error: Option<&'a Error>,
+    },
+
+    /// We sent a `close_notify` alert: we will send no more data.
+    CloseNotifySent,
+
+    /// We received a `close_notify` alert: the peer will send no more data.
+    CloseNotifyReceived,
+}
+
+/// Why a server could not resume a session offered by a client.
+///
+/// See [`ConnectionEvent::ResumptionOffered`].
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ResumptionRejection {
+    /// The ticket could not be decrypted, or the session was not found in storage.
+    ///
+    /// This includes tickets encrypted with a key that has since been discarded,
+    /// and sessions that storage has evicted or expired.
+    Undecryptable,
+    /// The session is older than its lifetime.
+    Expired,
+    /// The session used a cipher suite that cannot be used for this connection.
+    CipherSuiteMismatch,
+    /// The session did not use the extended main secret extension, but this
+    /// connection does.
+    ///
+    /// This only applies to TLS1.2.
+    ExtendedMainSecretMismatch,
+    /// The session was for a different server name.
+    ServerNameMismatch,
+    /// The ticket was revoked or has already been used.
+    ///
+    /// See [`ServerConfig::ticket_registry`].
+    ///
+    /// [`ServerConfig::ticket_registry`]: crate::ServerConfig::ticket_registry
+    Revoked,
+}
+
+/// A connection's (optional) [`ConnectionObserver`].
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Observer(Option<Arc<dyn ConnectionObserver>>);
+
+impl Observer {
+    pub(crate) fn new(observer: Option<&Arc<dyn ConnectionObserver>>) -> Self {
+        Self(observer.cloned())
+    }
+
+    pub(crate) fn observe(&self, event: ConnectionEvent<'_>) {
+        if let Some(observer) = &self.0 {
+            observer.observe(&event);
+        }
+    }
+}
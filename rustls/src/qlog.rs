@@ -0,0 +1,341 @@
+//! Structured traces of connections, in the style of [qlog].
+//!
+//! Set [`ClientConfig::qlog`] or [`ServerConfig::qlog`] to a [`QlogTracer`], and each
+//! connection made with that config records a trace of its handshake messages (including
+//! the extensions they carry), the parameters it negotiated, and the records it sent and
+//! received.
+//!
+//! Traces are written as a sequence of JSON records, suitable for the
+//! [JSON-SEQ serialization of qlog][qlog-seq].  The first record of each trace is
+//! the qlog header; each subsequent record is an event in the `tls` category:
+//!
+//! - `tls:handshake_message_sent` and `tls:handshake_message_received`: a handshake
+//!   message, with its type, length and extensions.
+//! - `tls:parameters_set`: a negotiated parameter, such as the protocol version.
+//! - `tls:record_sent` and `tls:record_received`: a record, with its content type and
+//!   plaintext length.
+//!
+//! Event times are in milliseconds, relative to the `reference_time` of the trace (which
+//! is in milliseconds since the Unix epoch, taken from the config's `TimeProvider`).
+//!
+//! A trace starts once the config is known, so a server using an `Acceptor` does not
+//! trace the `ClientHello` it read before [`Accepted::choose_config()`].
+//!
+//! [qlog]: https://datatracker.ietf.org/doc/draft-ietf-quic-qlog-main-schema/
+//! [qlog-seq]: https://datatracker.ietf.org/doc/html/draft-ietf-quic-qlog-main-schema#section-11.2
+//! [`ClientConfig::qlog`]: crate::ClientConfig::qlog
+//! [`ServerConfig::qlog`]: crate::ServerConfig::qlog
+//! [`Accepted::choose_config()`]: crate::server::Accepted::choose_config
+
+use alloc::format;
+use alloc::string::String;
+use core::fmt::{self, Debug, Write};
+use std::time::Instant;
+
+use pki_types::UnixTime;
+
+use crate::common_state::{OutputEvent, Side};
+use crate::enums::{ContentType, ProtocolVersion};
+use crate::msgs::{HandshakeMessagePayload, HandshakePayload, Reader};
+use crate::sync::Arc;
+
+/// Starts a trace for each connection made with a config.
+///
+/// See the [module documentation](self) for details of what is traced.
+pub trait QlogTracer: Debug + Send + Sync {
+    /// Start tracing a new connection.
+    ///
+    /// Return `None` to leave this connection untraced.
+    fn new_trace(&self) -> Option<Arc<dyn QlogSink>>;
+}
+
+/// Receives the records of one connection's trace.
+///
+/// You'll likely want some interior mutability in your implementation to make this useful.
+pub trait QlogSink: Debug + Send + Sync {
+    /// Append `record` to the trace.
+    ///
+    /// `record` is a complete JSON object.  For the JSON-SEQ serialization of
+    /// qlog, write it preceded by an ASCII record separator (0x1e) and followed
+    /// by a line feed.
+    fn write_record(&self, record: &str);
+}
+
+/// A connection's (optional) qlog trace.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Qlog(Option<Arc<Trace>>);
+
+impl Qlog {
+    /// Start a trace for a connection on `side`, if `tracer` wants one.
+    pub(crate) fn start(
+        tracer: Option<&Arc<dyn QlogTracer>>,
+        side: Side,
+        now: Option<UnixTime>,
+    ) -> Self {
+        let Some(sink) = tracer.and_then(|tracer| tracer.new_trace()) else {
+            return Self(None);
+        };
+
+        let mut common_fields = Object::new();
+        common_fields.str("time_format", "relative");
+        if let Some(now) = now {
+            common_fields.num("reference_time", now.as_secs().saturating_mul(1000));
+        }
+
+        let mut vantage_point = Object::new();
+        vantage_point.str(
+            "type",
+            match side {
+                Side::Client => "client",
+                Side::Server => "server",
+            },
+        );
+
+        let mut trace = Object::new();
+        trace.object("common_fields", common_fields);
+        trace.object("vantage_point", vantage_point);
+
+        let mut header = Object::new();
+        header.str("qlog_version", "0.3");
+        header.str("qlog_format", "JSON-SEQ");
+        header.object("trace", trace);
+        sink.write_record(&header.finish());
+
+        Self(Some(Arc::new(Trace {
+            sink,
+            start: Instant::now(),
+        })))
+    }
+
+    /// Record a handshake message we sent, which was `length` bytes long.
+    pub(crate) fn message_sent(&self, message: &HandshakeMessagePayload<'_>, length: usize) {
+        if let Some(trace) = &self.0 {
+            trace.event("tls:handshake_message_sent", message_data(message, length));
+        }
+    }
+
+    /// Record each handshake message in `flight`, which we sent.
+    pub(crate) fn flight_sent(&self, flight: &[u8], version: ProtocolVersion) {
+        let Some(trace) = &self.0 else {
+            return;
+        };
+
+        let mut r = Reader::new(flight);
+        while r.any_left() {
+            let left = r.left();
+            let Ok(message) = HandshakeMessagePayload::read_version(&mut r, version) else {
+                return;
+            };
+            trace.event(
+                "tls:handshake_message_sent",
+                message_data(&message, left - r.left()),
+            );
+        }
+    }
+
+    /// Record a handshake message we received, which was `length` bytes long.
+    pub(crate) fn message_received(&self, message: &HandshakeMessagePayload<'_>, length: usize) {
+        if let Some(trace) = &self.0 {
+            trace.event(
+                "tls:handshake_message_received",
+                message_data(message, length),
+            );
+        }
+    }
+
+    /// Record a negotiated parameter.
+    pub(crate) fn parameters_set(&self, event: &OutputEvent<'_>) {
+        let Some(trace) = &self.0 else {
+            return;
+        };
+
+        let mut data = Object::new();
+        match event {
+            OutputEvent::ApplicationProtocol(protocol) => {
+                data.str("alpn", &String::from_utf8_lossy(protocol.as_ref()))
+            }
+            OutputEvent::CipherSuite(suite) => data.debug("cipher_suite", suite.suite()),
+            OutputEvent::ExtendedMainSecret(ems) => data.bool("extended_main_secret", *ems),
+            OutputEvent::HandshakeKind(kind) => data.debug("handshake_kind", kind),
+            OutputEvent::KeyExchangeGroup(group) => data.debug("key_exchange_group", group.name()),
+            OutputEvent::ProtocolVersion(version) => data.debug("version", version),
+            OutputEvent::EarlyExporter(_)
+            | OutputEvent::Exporter(_)
+            | OutputEvent::PeerApplicationSettings(_)
+            | OutputEvent::PeerIdentity(_) => return,
+        }
+        trace.event("tls:parameters_set", data);
+    }
+
+    /// Record a record we sent, with a plaintext `length` bytes long.
+    pub(crate) fn record_sent(&self, typ: ContentType, length: usize) {
+        if let Some(trace) = &self.0 {
+            trace.event("tls:record_sent", record_data(typ, length));
+        }
+    }
+
+    /// Record a record we received, with a plaintext `length` bytes long.
+    pub(crate) fn record_received(&self, typ: ContentType, length: usize) {
+        if let Some(trace) = &self.0 {
+            trace.event("tls:record_received", record_data(typ, length));
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Trace {
+    sink: Arc<dyn QlogSink>,
+    start: Instant,
+}
+
+impl Trace {
+    fn event(&self, name: &str, data: Object) {
+        let mut event = Object::new();
+        event.num("time", self.start.elapsed().as_secs_f64() * 1000.0);
+        event.str("name", name);
+        event.object("data", data);
+        self.sink.write_record(&event.finish());
+    }
+}
+
+fn message_data(message: &HandshakeMessagePayload<'_>, length: usize) -> Object {
+    let mut data = Object::new();
+    data.debug("message_type", message.0.handshake_type());
+    data.num("length", length);
+
+    match &message.0 {
+        HandshakePayload::ClientHello(hello) => {
+            data.debug_array("cipher_suites", &hello.cipher_suites);
+            data.debug_array("extensions", &hello.extensions.collect_used());
+        }
+        HandshakePayload::ServerHello(hello) => {
+            data.debug("cipher_suite", hello.cipher_suite);
+            data.debug_array("extensions", &hello.extensions.collect_used());
+        }
+        HandshakePayload::HelloRetryRequest(hrr) => {
+            data.debug("cipher_suite", hrr.cipher_suite);
+            data.debug_array("extensions", &hrr.extensions.collect_used());
+        }
+        HandshakePayload::EncryptedExtensions(extensions) => {
+            data.debug_array("extensions", &extensions.collect_used());
+        }
+        HandshakePayload::Certificate(chain) => data.num("certificates", chain.0.len()),
+        HandshakePayload::CertificateTls13(certificate) => {
+            data.num("certificates", certificate.entries.len())
+        }
+        HandshakePayload::CertificateRequestTls13(request) => {
+            data.debug_array("extensions", &request.extensions.collect_used());
+        }
+        HandshakePayload::CertificateVerify(signature) => {
+            data.debug("signature_scheme", signature.scheme)
+        }
+        HandshakePayload::NewSessionTicketTls13(ticket) => {
+            data.debug_array("extensions", &ticket.extensions.collect_used());
+        }
+        HandshakePayload::KeyUpdate(request) => data.debug("request", request),
+        _ => {}
+    }
+
+    data
+}
+
+fn record_data(typ: ContentType, length: usize) -> Object {
+    let mut data = Object::new();
+    data.debug("content_type", typ);
+    data.num("length", length);
+    data
+}
+
+/// A JSON object, under construction.
+struct Object {
+    json: String,
+}
+
+impl Object {
+    fn new() -> Self {
+        Self {
+            json: String::from("{"),
+        }
+    }
+
+    fn str(&mut self, key: &str, value: &str) {
+        self.key(key);
+        quote(&mut self.json, value);
+    }
+
+    fn debug(&mut self, key: &str, value: impl Debug) {
+        self.str(key, &format!("{value:?}"));
+    }
+
+    fn debug_array<T: Debug>(&mut self, key: &str, values: &[T]) {
+        self.key(key);
+        self.json.push('[');
+        for (i, value) in values.iter().enumerate() {
+            if i > 0 {
+                self.json.push(',');
+            }
+            quote(&mut self.json, &format!("{value:?}"));
+        }
+        self.json.push(']');
+    }
+
+    fn num(&mut self, key: &str, value: impl fmt::Display) {
+        self.key(key);
+        let _ = write!(self.json, "{value}");
+    }
+
+    fn bool(&mut self, key: &str, value: bool) {
+        self.num(key, value);
+    }
+
+    fn object(&mut self, key: &str, value: Self) {
+        self.key(key);
+        self.json.push_str(&value.finish());
+    }
+
+    fn key(&mut self, key: &str) {
+        if self.json.len() > 1 {
+            self.json.push(',');
+        }
+        quote(&mut self.json, key);
+        self.json.push(':');
+    }
+
+    fn finish(mut self) -> String {
+        self.json.push('}');
+        self.json
+    }
+}
+
+/// Append `value` to `json` as a JSON string.
+fn quote(json: &mut String, value: &str) {
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if c < ' ' => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_escaping() {
+        let mut object = Object::new();
+        object.str("a\"b", "c\\d\ne");
+        object.num("n", 1.5);
+        object.debug_array("list", &["x"]);
+        assert_eq!(
+            object.finish(),
+            r#"{"a\"b":"c\\d\u000ae","n":1.5,"list":["\"x\""]}"#
+        );
+    }
+}
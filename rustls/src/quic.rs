@@ -1,22 +1,25 @@
 use alloc::boxed::Box;
 use alloc::vec::Vec;
 use core::ops::{Deref, DerefMut};
+use core::time::Duration;
 use core::{fmt, mem};
 
-use pki_types::{DnsName, FipsStatus, ServerName};
+use pki_types::{DnsName, FipsStatus, ServerName, UnixTime};
+use subtle::ConstantTimeEq;
 
 use crate::TlsInputBuffer;
-use crate::client::{ClientConfig, ClientSide};
+use crate::client::{ClientConfig, ClientSide, ResumptionDiagnostics};
 pub use crate::common_state::Side;
 use crate::common_state::{CommonState, ConnectionOutputs, Protocol};
 use crate::conn::{ConnectionCommon, KeyingMaterialExporter, MessageIter, SideData, StateMachine};
-use crate::crypto::VerifiedIdentity;
 use crate::crypto::cipher::{AeadKey, Iv, Payload};
 use crate::crypto::tls13::{Hkdf, HkdfExpander, OkmBlock};
+use crate::crypto::{CipherSuite, TicketProducer, VerifiedIdentity};
 use crate::enums::ApplicationProtocol;
 use crate::error::{ApiMisuse, Error};
 use crate::msgs::{
-    ClientExtensionsInput, Message, MessagePayload, ServerExtensionsInput, TransportParameters,
+    ClientExtensionsInput, Codec, MaybeEmpty, Message, MessagePayload, Reader,
+    ServerExtensionsInput, SizedPayload, TransportParameters,
 };
 use crate::server::{
     ChooseConfig, ClientHello, HandshakeVerifyClientIdentity, ServerConfig, ServerSide, ServerState,
@@ -135,6 +138,27 @@ impl ClientConnection {
         self.inner.fips
     }
 
+    /// The QUIC version in use.
+    pub fn version(&self) -> Version {
+        self.inner.quic.version
+    }
+
+    /// Switch to a different QUIC version, following compatible version negotiation.
+    ///
+    /// With [compatible version negotiation][RFC 9368], the server may reply to a client's
+    /// first flight in a different version to the one the client started with.  Call this
+    /// when the server's first Initial packet arrives in another version, before passing its
+    /// handshake data to [`Connection::read_hs()`].  Initial keys for the new version must be
+    /// derived by the caller with [`Keys::initial()`]; keys from subsequent [`KeyChange`]s
+    /// (including key updates) use the new version.
+    ///
+    /// Fails if handshake keys have already been derived.
+    ///
+    /// [RFC 9368]: https://www.rfc-editor.org/rfc/rfc9368
+    pub fn set_version(&mut self, version: Version) -> Result<(), Error> {
+        self.inner.set_version(version)
+    }
+
     /// Returns True if the server signalled it will process early data.
     ///
     /// If you sent early data and this returns false at the end of the
@@ -155,6 +179,43 @@ impl ClientConnection {
             .tls13_tickets_received
     }
 
+    /// Set application data to store alongside tickets received on this connection.
+    ///
+    /// This is useful for remembering values needed for 0-RTT, such as HTTP/3 SETTINGS.
+    /// See [`crate::client::ClientConnection::set_resumption_data()`].
+    pub fn set_resumption_data(&mut self, data: &[u8]) -> Result<(), Error> {
+        if data.len() >= 2usize.pow(15) {
+            return Err(ApiMisuse::ResumptionDataTooLong.into());
+        }
+        match &mut self.inner.common.state {
+            Ok(st) => {
+                st.set_resumption_data(data);
+                Ok(())
+            }
+            Err(e) => Err(e.clone()),
+        }
+    }
+
+    /// Application data stored with the ticket this connection resumed, if any.
+    ///
+    /// See [`crate::client::ClientConnection::received_resumption_data()`].
+    pub fn received_resumption_data(&self) -> Option<&[u8]> {
+        self.inner
+            .common
+            .side
+            .received_resumption_data()
+    }
+
+    /// Describes this connection's attempt to resume a prior session.
+    ///
+    /// See [`crate::client::ClientConnection::resumption_diagnostics()`].
+    pub fn resumption_diagnostics(&self) -> Option<&ResumptionDiagnostics> {
+        self.inner
+            .common
+            .side
+            .resumption_diagnostics()
+    }
+
     /// Returns an object that can derive key material from the agreed connection secrets.
     ///
     /// See [RFC 5705][] for more details on what this is for.
@@ -248,6 +309,24 @@ impl ServerConnection {
         self.inner.fips
     }
 
+    /// The QUIC version in use.
+    pub fn version(&self) -> Version {
+        self.inner.quic.version
+    }
+
+    /// Switch to a different QUIC version, following compatible version negotiation.
+    ///
+    /// A server that chooses to upgrade the connection to a [compatible version][RFC 9368]
+    /// calls this before passing the client's first flight to [`Connection::read_hs()`].
+    /// Keys from subsequent [`KeyChange`]s (including key updates) use the new version.
+    ///
+    /// Fails if handshake keys have already been derived.
+    ///
+    /// [RFC 9368]: https://www.rfc-editor.org/rfc/rfc9368
+    pub fn set_version(&mut self, version: Version) -> Result<(), Error> {
+        self.inner.set_version(version)
+    }
+
     /// Retrieves the server name, if any, used to select the certificate and
     /// private key.
     ///
@@ -484,6 +563,14 @@ impl Accepted {
         self.choose_config.client_hello()
     }
 
+    /// Switch to a different QUIC version, following compatible version negotiation.
+    ///
+    /// See [`ServerConnection::set_version()`].  No keys have been derived at this point,
+    /// so this cannot fail.
+    pub fn set_version(&mut self, version: Version) {
+        self.inner.quic.version = version;
+    }
+
     /// Choose a [`ServerConfig`] to progress the handshake.
     ///
     /// Resolves an [`Accepted`], providing the [`ServerConfig`] that should be used for
@@ -638,6 +725,15 @@ impl<Side: SideData> QuicCommon<Side> {
         Self { common, quic }
     }
 
+    fn set_version(&mut self, version: Version) -> Result<(), Error> {
+        if self.quic.handshake_keys {
+            return Err(ApiMisuse::QuicVersionChangedAfterHandshakeKeys.into());
+        }
+
+        self.quic.version = version;
+        Ok(())
+    }
+
     fn quic_transport_parameters(&self) -> Option<&[u8]> {
         self.quic
             .params
@@ -720,6 +816,8 @@ impl<Side: SideData> DerefMut for QuicCommon<Side> {
 #[derive(Default)]
 pub(crate) struct Quic {
     pub(crate) version: Version,
+    /// Whether handshake keys have been derived, fixing `version`
+    handshake_keys: bool,
     /// QUIC transport parameters received from the peer during the handshake
     pub(crate) params: Option<Vec<u8>>,
     pub(crate) events: Vec<QuicEvent>,
@@ -752,6 +850,10 @@ impl Quic {
 }
 
 impl QuicOutput for Quic {
+    fn is_quic(&self) -> bool {
+        true
+    }
+
     fn transport_parameters(&mut self, params: Vec<u8>) {
         self.params = Some(params);
     }
@@ -767,6 +869,7 @@ impl QuicOutput for Quic {
         suite: Suite,
         side: Side,
     ) {
+        self.handshake_keys = true;
         self.events
             .push(QuicEvent::KeyChange(KeyChange::Handshake {
                 keys: Keys::new(&Secrets::new(
@@ -801,7 +904,13 @@ impl QuicOutput for Quic {
     }
 }
 
+/// Receives handshake messages and secrets when TLS records are not used.
+///
+/// This is implemented for QUIC, and also for DTLS (which protects records itself).
 pub(crate) trait QuicOutput {
+    /// False for DTLS, which has no transport parameters and does not require ALPN.
+    fn is_quic(&self) -> bool;
+
     fn transport_parameters(&mut self, params: Vec<u8>);
 
     fn early_secret(&mut self, secret: Option<OkmBlock>);
@@ -1011,6 +1120,18 @@ pub trait HeaderProtectionKey: Send + Sync {
         packet_number: &mut [u8],
     ) -> Result<(), Error>;
 
+    /// Computes the mask for `sample`, without applying it to anything.
+    ///
+    /// This is the five-byte `mask` in [Header Protection Application], before any
+    /// QUIC-specific bits are selected from it.  DTLS 1.3 uses this to encrypt
+    /// record sequence numbers, see [RFC 9147 Section 4.2.3].
+    ///
+    /// Returns an error if `sample` is not the correct length (see [`Self::sample_len()`]).
+    ///
+    /// [Header Protection Application]: https://datatracker.ietf.org/doc/html/rfc9001#section-5.4.1
+    /// [RFC 9147 Section 4.2.3]: https://www.rfc-editor.org/rfc/rfc9147#section-4.2.3
+    fn mask(&self, sample: &[u8]) -> Result<[u8; 5], Error>;
+
     /// Expected sample length for the key's algorithm
     fn sample_len(&self) -> usize;
 }
@@ -1170,6 +1291,66 @@ impl Suite {
     pub fn keys(&self, client_dst_connection_id: &[u8], side: Side, version: Version) -> Keys {
         Keys::initial(version, *self, client_dst_connection_id, side)
     }
+
+    /// Compute the integrity tag for a Retry packet
+    ///
+    /// `original_dst_connection_id` is the destination connection ID of the client's
+    /// first Initial packet, and `retry_without_tag` is the Retry packet up to (but
+    /// not including) the tag.  The tag is appended to the packet by the caller.
+    ///
+    /// This suite must be `TLS13_AES_128_GCM_SHA256`.
+    ///
+    /// See [RFC 9001 Section 5.8](https://www.rfc-editor.org/rfc/rfc9001#section-5.8).
+    pub fn retry_integrity_tag(
+        &self,
+        version: Version,
+        original_dst_connection_id: &[u8],
+        retry_without_tag: &[u8],
+    ) -> Result<Tag, Error> {
+        if self.inner.common.suite != CipherSuite::TLS13_AES_128_GCM_SHA256 {
+            return Err(ApiMisuse::QuicRetryRequiresAes128Gcm.into());
+        }
+
+        let Ok(odcid_len) = u8::try_from(original_dst_connection_id.len()) else {
+            return Err(Error::General("connection ID too long".into()));
+        };
+
+        let mut pseudo_packet =
+            Vec::with_capacity(1 + original_dst_connection_id.len() + retry_without_tag.len());
+        pseudo_packet.push(odcid_len);
+        pseudo_packet.extend_from_slice(original_dst_connection_id);
+        pseudo_packet.extend_from_slice(retry_without_tag);
+
+        let (key, nonce) = version.retry_integrity_key();
+        self.quic
+            .packet_key(AeadKey::from(*key), Iv::from(*nonce))
+            .encrypt_in_place(0, &pseudo_packet, &mut [], None)
+    }
+
+    /// Verify the integrity tag at the end of `retry_packet`
+    ///
+    /// `retry_packet` is the entire Retry packet as received, including the tag.
+    /// Returns [`Error::DecryptError`] if the tag is missing or incorrect.
+    ///
+    /// This suite must be `TLS13_AES_128_GCM_SHA256`.
+    pub fn verify_retry_integrity_tag(
+        &self,
+        version: Version,
+        original_dst_connection_id: &[u8],
+        retry_packet: &[u8],
+    ) -> Result<(), Error> {
+        let Some(split) = retry_packet.len().checked_sub(TAG_LEN) else {
+            return Err(Error::DecryptError);
+        };
+        let (retry_without_tag, received) = retry_packet.split_at(split);
+
+        let expected =
+            self.retry_integrity_tag(version, original_dst_connection_id, retry_without_tag)?;
+        match ConstantTimeEq::ct_eq(expected.as_ref(), received).into() {
+            true => Ok(()),
+            false => Err(Error::DecryptError),
+        }
+    }
 }
 
 impl TryFrom<&'static Tls13CipherSuite> for Suite {
@@ -1339,88 +1520,192 @@ impl Version {
             Self::V2 => b"quicv2 ku",
         }
     }
-}
 
-#[cfg(all(test, any(target_arch = "aarch64", target_arch = "x86_64")))]
-mod tests {
-    use super::*;
-    use crate::crypto::TLS13_TEST_SUITE;
-    use crate::crypto::tls13::OkmBlock;
-    use crate::quic::{HeaderProtectionKey, Secrets, Side, Version};
-
-    #[test]
-    fn key_update_test_vector() {
-        fn equal_okm(x: &OkmBlock, y: &OkmBlock) -> bool {
-            x.as_ref() == y.as_ref()
-        }
-
-        let mut secrets = Secrets {
-            // Constant dummy values for reproducibility
-            client: OkmBlock::new(
+    /// Fixed AEAD_AES_128_GCM key and nonce for Retry integrity tags.
+    fn retry_integrity_key(self) -> (&'static [u8; 16], &'static [u8; 12]) {
+        match self {
+            // https://www.rfc-editor.org/rfc/rfc9001.html#name-retry-packet-integrity
+            Self::V1 => (
                 &[
-                    0xb8, 0x76, 0x77, 0x08, 0xf8, 0x77, 0x23, 0x58, 0xa6, 0xea, 0x9f, 0xc4, 0x3e,
-                    0x4a, 0xdd, 0x2c, 0x96, 0x1b, 0x3f, 0x52, 0x87, 0xa6, 0xd1, 0x46, 0x7e, 0xe0,
-                    0xae, 0xab, 0x33, 0x72, 0x4d, 0xbf,
-                ][..],
-            ),
-            server: OkmBlock::new(
+                    0xbe, 0x0c, 0x69, 0x0b, 0x9f, 0x66, 0x57, 0x5a, 0x1d, 0x76, 0x6b, 0x54, 0xe3,
+                    0x68, 0xc8, 0x4e,
+                ],
                 &[
-                    0x42, 0xdc, 0x97, 0x21, 0x40, 0xe0, 0xf2, 0xe3, 0x98, 0x45, 0xb7, 0x67, 0x61,
-                    0x34, 0x39, 0xdc, 0x67, 0x58, 0xca, 0x43, 0x25, 0x9b, 0x87, 0x85, 0x06, 0x82,
-                    0x4e, 0xb1, 0xe4, 0x38, 0xd8, 0x55,
-                ][..],
+                    0x46, 0x15, 0x99, 0xd3, 0x5d, 0x63, 0x2b, 0xf2, 0x23, 0x98, 0x25, 0xbb,
+                ],
             ),
-            suite: Suite {
-                inner: TLS13_TEST_SUITE,
-                quic: &FakeAlgorithm,
-            },
-            side: Side::Client,
-            version: Version::V1,
-        };
-        secrets.update();
-
-        assert!(equal_okm(
-            &secrets.client,
-            &OkmBlock::new(
+            // https://www.rfc-editor.org/rfc/rfc9369.html#name-retry-integrity-tag
+            Self::V2 => (
                 &[
-                    0x42, 0xca, 0xc8, 0xc9, 0x1c, 0xd5, 0xeb, 0x40, 0x68, 0x2e, 0x43, 0x2e, 0xdf,
-                    0x2d, 0x2b, 0xe9, 0xf4, 0x1a, 0x52, 0xca, 0x6b, 0x22, 0xd8, 0xe6, 0xcd, 0xb1,
-                    0xe8, 0xac, 0xa9, 0x6, 0x1f, 0xce
-                ][..]
-            )
-        ));
-        assert!(equal_okm(
-            &secrets.server,
-            &OkmBlock::new(
+                    0x8f, 0xb4, 0xb0, 0x1b, 0x56, 0xac, 0x48, 0xe2, 0x60, 0xfb, 0xcb, 0xce, 0xad,
+                    0x7c, 0xcc, 0x92,
+                ],
                 &[
-                    0xeb, 0x7f, 0x5e, 0x2a, 0x12, 0x3f, 0x40, 0x7d, 0xb4, 0x99, 0xe3, 0x61, 0xca,
-                    0xe5, 0x90, 0xd4, 0xd9, 0x92, 0xe1, 0x4b, 0x7a, 0xce, 0x3, 0xc2, 0x44, 0xe0,
-                    0x42, 0x21, 0x15, 0xb6, 0xd3, 0x8a
-                ][..]
-            )
-        ));
+                    0xd8, 0x69, 0x69, 0xbc, 0x2d, 0x7c, 0x6d, 0x99, 0x90, 0xef, 0xb0, 0x4a,
+                ],
+            ),
+        }
     }
+}
 
-    struct FakeAlgorithm;
+/// Seals and opens address validation tokens
+///
+/// A QUIC server validates a client's address by sending it a token, either in a
+/// Retry packet or a `NEW_TOKEN` frame, and checking that the token is echoed
+/// back from the same address (see [RFC 9000 Section 8.1]).
+///
+/// Tokens produced here are encrypted and authenticated by a [`TicketProducer`],
+/// so they are opaque to clients and cannot be forged.  They bind the client's
+/// address and the time they were issued; Retry tokens additionally carry the
+/// original destination connection ID, which the server must echo in its
+/// `original_destination_connection_id` transport parameter.
+///
+/// `address` is any stable encoding of the client's address chosen by the caller,
+/// such as the IP address and port.  It must be no longer than 255 bytes.
+///
+/// [RFC 9000 Section 8.1]: https://www.rfc-editor.org/rfc/rfc9000#section-8.1
+pub struct AddressTokenSealer {
+    producer: Arc<dyn TicketProducer>,
+    retry_lifetime: Duration,
+    new_token_lifetime: Duration,
+}
 
-    impl Algorithm for FakeAlgorithm {
-        fn packet_key(&self, _key: AeadKey, _iv: Iv) -> Box<dyn PacketKey> {
-            unimplemented!()
+impl AddressTokenSealer {
+    /// Make a new `AddressTokenSealer` using `producer` to protect tokens
+    ///
+    /// Tokens sent in Retry packets are accepted for `retry_lifetime`, and tokens
+    /// sent in `NEW_TOKEN` frames for `new_token_lifetime`.  Retry tokens are used
+    /// immediately, so `retry_lifetime` should be short (a few seconds).
+    pub fn new(
+        producer: Arc<dyn TicketProducer>,
+        retry_lifetime: Duration,
+        new_token_lifetime: Duration,
+    ) -> Self {
+        Self {
+            producer,
+            retry_lifetime,
+            new_token_lifetime,
         }
+    }
+
+    /// Produce a token for a Retry packet sent to `address`
+    ///
+    /// Returns `None` if the token cannot be sealed.
+    pub fn seal_retry_token(
+        &self,
+        address: &[u8],
+        original_dst_connection_id: &[u8],
+        now: UnixTime,
+    ) -> Option<Vec<u8>> {
+        self.seal(TokenKind::Retry, address, original_dst_connection_id, now)
+    }
 
-        fn header_protection_key(&self, _key: AeadKey) -> Box<dyn HeaderProtectionKey> {
-            unimplemented!()
+    /// Produce a token for a `NEW_TOKEN` frame sent to `address`
+    ///
+    /// Returns `None` if the token cannot be sealed.
+    pub fn seal_new_token(&self, address: &[u8], now: UnixTime) -> Option<Vec<u8>> {
+        self.seal(TokenKind::NewToken, address, &[], now)
+    }
+
+    /// Open a `token` received from `address`
+    ///
+    /// Returns `None` if the token was not produced by this sealer, was issued to
+    /// a different address, or has expired.
+    pub fn open(&self, token: &[u8], address: &[u8], now: UnixTime) -> Option<AddressToken> {
+        let plain = self.producer.decrypt(token)?;
+        let mut r = Reader::new(&plain);
+        let kind = TokenKind::from_u8(u8::read(&mut r).ok()?)?;
+        let issued = u64::read(&mut r).ok()?;
+        let bound_address = SizedPayload::<u8, MaybeEmpty>::read(&mut r).ok()?;
+        let odcid = SizedPayload::<u8, MaybeEmpty>::read(&mut r).ok()?;
+        if r.any_left() {
+            return None;
         }
 
-        fn aead_key_len(&self) -> usize {
-            16
+        if !bool::from(ConstantTimeEq::ct_eq(bound_address.bytes(), address)) {
+            return None;
         }
+
+        let lifetime = match kind {
+            TokenKind::Retry => self.retry_lifetime,
+            TokenKind::NewToken => self.new_token_lifetime,
+        };
+        if now.as_secs().saturating_sub(issued) > lifetime.as_secs() {
+            return None;
+        }
+
+        Some(match kind {
+            TokenKind::Retry => AddressToken::Retry {
+                original_dst_connection_id: odcid.into_vec(),
+            },
+            TokenKind::NewToken => AddressToken::NewToken,
+        })
     }
 
-    #[test]
-    fn auto_traits() {
-        fn assert_auto<T: Send + Sync>() {}
-        assert_auto::<Box<dyn PacketKey>>();
-        assert_auto::<Box<dyn HeaderProtectionKey>>();
+    fn seal(
+        &self,
+        kind: TokenKind,
+        address: &[u8],
+        original_dst_connection_id: &[u8],
+        now: UnixTime,
+    ) -> Option<Vec<u8>> {
+        if address.len() > usize::from(u8::MAX)
+            || original_dst_connection_id.len() > usize::from(u8::MAX)
+        {
+            return None;
+        }
+
+        let mut plain = Vec::new();
+        kind.to_u8().encode(&mut plain);
+        now.as_secs().encode(&mut plain);
+        SizedPayload::<u8, MaybeEmpty>::from(Payload::Borrowed(address)).encode(&mut plain);
+        SizedPayload::<u8, MaybeEmpty>::from(Payload::Borrowed(original_dst_connection_id))
+            .encode(&mut plain);
+        self.producer.encrypt(&plain)
+    }
+}
+
+impl fmt::Debug for AddressTokenSealer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AddressTokenSealer")
+            .field("retry_lifetime", &self.retry_lifetime)
+            .field("new_token_lifetime", &self.new_token_lifetime)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A validated address token, returned by [`AddressTokenSealer::open()`]
+#[non_exhaustive]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AddressToken {
+    /// The token was sent in a Retry packet
+    Retry {
+        /// The destination connection ID of the client's first Initial packet
+        original_dst_connection_id: Vec<u8>,
+    },
+    /// The token was sent in a `NEW_TOKEN` frame
+    NewToken,
+}
+
+#[derive(Clone, Copy)]
+enum TokenKind {
+    Retry,
+    NewToken,
+}
+
+impl TokenKind {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::Retry),
+            1 => Some(Self::NewToken),
+            _ => None,
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            Self::Retry => 0,
+            Self::NewToken => 1,
+        }
     }
 }
@@ -2,6 +2,7 @@ use alloc::borrow::Cow;
 use alloc::vec::Vec;
 use core::fmt::Debug;
 use core::marker::PhantomData;
+use core::time::Duration;
 
 #[cfg(feature = "webpki")]
 use pki_types::PrivateKeyDer;
@@ -9,23 +10,27 @@ use pki_types::{DnsName, FipsStatus, UnixTime};
 
 use super::{ServerSessionKey, handy};
 use crate::builder::{ConfigBuilder, WantsVerifier};
-use crate::common_state::Protocol;
+use crate::common_state::{Protocol, Side};
 #[cfg(doc)]
 use crate::crypto;
 use crate::crypto::kx::NamedGroup;
 use crate::crypto::{
-    CipherSuite, CryptoProvider, SelectedCredential, SignatureScheme, TicketProducer,
+    CipherSuite, CryptoProvider, Identity, SelectedCredential, SignatureScheme, TicketProducer,
 };
 #[cfg(feature = "webpki")]
-use crate::crypto::{Credentials, Identity, SingleCredential};
-use crate::enums::{ApplicationProtocol, CertificateType, ProtocolVersion};
+use crate::crypto::{Credentials, SingleCredential};
+use crate::enums::{ApplicationProtocol, ApplicationSettings, CertificateType, ProtocolVersion};
 use crate::error::{Error, PeerMisbehaved};
 use crate::msgs::{ClientHelloPayload, ClientTicketRequest, ServerNamePayload};
+use crate::qlog::{Qlog, QlogTracer};
 use crate::suites::Suite;
 use crate::sync::Arc;
 use crate::time_provider::{DefaultTimeProvider, TimeProvider};
 use crate::verify::{ClientVerifier, DistinguishedName, NoClientAuth};
-use crate::{KeyLog, NoKeyLog, Tls12CipherSuite, Tls13CipherSuite, compress};
+use crate::{
+    ConnectionObserver, KeyLog, NoKeyLog, RecordPadding, Tls12CipherSuite, Tls13CipherSuite,
+    compress,
+};
 
 /// Common configuration for a set of server sessions.
 ///
@@ -39,13 +44,17 @@ use crate::{KeyLog, NoKeyLog, Tls12CipherSuite, Tls13CipherSuite, compress};
 /// # Defaults
 ///
 /// * [`ServerConfig::max_fragment_size`]: the default is `None` (meaning 16kB).
+/// * [`ServerConfig::record_padding`]: the default is [`RecordPadding::None`].
 /// * [`ServerConfig::session_storage`]: if the `std` feature is enabled, the default stores 256
 ///   sessions in memory. If the `std` feature is not enabled, the default is to not store any
 ///   sessions. In a no-std context, by enabling the `hashbrown` feature you may provide your
 ///   own `session_storage` using [`ServerSessionMemoryCache`] and a `crate::lock::MakeMutex`
 ///   implementation.
 /// * [`ServerConfig::alpn_protocols`]: the default is empty -- no ALPN protocol is negotiated.
+/// * [`ServerConfig::application_settings`]: the default is empty -- ALPS is not negotiated.
 /// * [`ServerConfig::key_log`]: key material is not logged.
+/// * [`ServerConfig::observer`]: connections are not observed.
+/// * [`ServerConfig::qlog`]: connections are not traced.
 /// * [`ServerConfig::send_tls13_tickets`]: 2 tickets are sent, with a maximum of 2.
 /// * [`ServerConfig::cert_compressors`]: depends on the crate features, see [`compress::default_cert_compressors()`].
 /// * [`ServerConfig::cert_compression_cache`]: caches the most recently used 4 compressions
@@ -99,18 +108,44 @@ pub struct ServerConfig {
     /// [ServerConnection::new]: crate::server::ServerConnection::new
     pub max_fragment_size: Option<usize>,
 
+    /// How to pad TLS1.3 records, to hide the length of their contents.
+    ///
+    /// This applies to every record encrypted under TLS1.3, including those
+    /// carrying handshake messages.  It can be overridden for individual writes
+    /// with [`SendTraffic::write_padded()`].
+    ///
+    /// [`SendTraffic::write_padded()`]: crate::split::SendTraffic::write_padded()
+    pub record_padding: RecordPadding,
+
     /// How to store client sessions.
     ///
     /// See [ServerConfig#sharing-resumption-storage-between-serverconfigs]
     /// for a warning related to this field.
     pub session_storage: Arc<dyn StoresServerSessions>,
 
+    /// How long sessions kept in [`Self::session_storage`] may be resumed.
+    ///
+    /// This is passed to the store with each session, and sessions older than
+    /// this are not resumed.  It does not apply to tickets produced by
+    /// [`Self::ticketer`], which have their own lifetime.
+    ///
+    /// The default is 24 hours.
+    pub session_lifetime: Duration,
+
     /// How to produce tickets.
     ///
     /// See [ServerConfig#sharing-resumption-storage-between-serverconfigs]
     /// for a warning related to this field.
     pub ticketer: Option<Arc<dyn TicketProducer>>,
 
+    /// How to track TLS1.3 tickets, so they can be single-use and revoked.
+    ///
+    /// If this is `None` (the default), stateful tickets are single-use
+    /// because [`StoresServerSessions::take()`] deletes them, but
+    /// tickets produced by [`ServerConfig::ticketer`] can be used repeatedly
+    /// until they expire.
+    pub ticket_registry: Option<Arc<dyn TicketRegistry>>,
+
     /// How to choose a server cert and key. This is usually set by
     /// [ConfigBuilder::with_single_cert] or [ConfigBuilder::with_server_credential_resolver].
     pub cert_resolver: Arc<dyn ServerCredentialResolver>,
@@ -119,6 +154,15 @@ pub struct ServerConfig {
     /// If empty we don't do ALPN at all.
     pub alpn_protocols: Vec<ApplicationProtocol<'static>>,
 
+    /// Application settings (ALPS) to exchange for particular ALPN protocols.
+    ///
+    /// If the client offers the ALPS extension for the negotiated protocol and there is
+    /// an entry for it here, these settings are sent in EncryptedExtensions and the
+    /// client's settings are required in its second flight.
+    ///
+    /// ALPS is only available in TLS1.3.  The default is empty.
+    pub application_settings: Vec<ApplicationSettings>,
+
     /// How to verify client certificates.
     pub(super) verifier: Arc<dyn ClientVerifier>,
 
@@ -129,6 +173,16 @@ pub struct ServerConfig {
     /// See [RFC 9850](https://datatracker.ietf.org/doc/html/rfc9850) for background.
     pub key_log: Arc<dyn KeyLog>,
 
+    /// Observes the progress of connections made with this config.
+    ///
+    /// The default is `None`.
+    pub observer: Option<Arc<dyn ConnectionObserver>>,
+
+    /// Records a qlog-style trace of each connection made with this config.
+    ///
+    /// See the [`qlog`](crate::qlog) module for details.  The default is `None`.
+    pub qlog: Option<Arc<dyn QlogTracer>>,
+
     /// Allows traffic secrets to be extracted after the handshake,
     /// e.g. for kTLS setup.
     pub enable_secret_extraction: bool,
@@ -299,6 +353,14 @@ impl ServerConfig {
             .current_time()
             .ok_or(Error::FailedToGetCurrentTime)
     }
+
+    pub(crate) fn start_qlog(&self) -> Qlog {
+        Qlog::start(
+            self.qlog.as_ref(),
+            Side::Server,
+            self.time_provider.current_time(),
+        )
+    }
 }
 
 /// How many TLS 1.3 session tickets the server sends after a handshake.
@@ -356,19 +418,26 @@ impl Default for Tls13Tickets {
 /// in the type system to allow implementations freedom in
 /// how to achieve interior mutability.  `Mutex` is a common
 /// choice.
+///
+/// This is used for TLS1.2 session ID resumption, and for TLS1.3
+/// tickets when [`ServerConfig::ticketer`] is `None`.
 pub trait StoresServerSessions: Debug + Send + Sync {
     /// Store session secrets encoded in `value` against `key`,
     /// overwrites any existing value against `key`.  Returns `true`
     /// if the value was stored.
-    fn put(&self, key: ServerSessionKey<'_>, value: Vec<u8>) -> bool;
+    ///
+    /// `expiry` says when the session was created and for how long it
+    /// may be resumed.  Implementations should not return the value
+    /// after that time, and may use it to set a TTL in an external store.
+    fn put(&self, key: ServerSessionKey<'_>, value: Vec<u8>, expiry: SessionExpiry) -> bool;
 
     /// Find a value with the given `key`.  Return it, or None
-    /// if it doesn't exist.
-    fn get(&self, key: ServerSessionKey<'_>) -> Option<Vec<u8>>;
+    /// if it doesn't exist or has expired at `now`.
+    fn get(&self, key: ServerSessionKey<'_>, now: UnixTime) -> Option<Vec<u8>>;
 
     /// Find a value with the given `key`.  Return it and delete it;
-    /// or None if it doesn't exist.
-    fn take(&self, key: ServerSessionKey<'_>) -> Option<Vec<u8>>;
+    /// or None if it doesn't exist or has expired at `now`.
+    fn take(&self, key: ServerSessionKey<'_>, now: UnixTime) -> Option<Vec<u8>>;
 
     /// Whether the store can cache another session. This is used to indicate to clients
     /// whether their session can be resumed; the implementation is not required to remember
@@ -376,6 +445,121 @@ pub trait StoresServerSessions: Debug + Send + Sync {
     fn can_cache(&self) -> bool;
 }
 
+/// When a stored session was created, and for how long it may be resumed.
+///
+/// This is passed to [`StoresServerSessions::put()`].
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SessionExpiry {
+    /// When the session was created.
+    pub created: UnixTime,
+    /// How long after `created` the session may be resumed.
+    pub lifetime: Duration,
+}
+
+impl SessionExpiry {
+    /// Make a new `SessionExpiry`.
+    pub fn new(created: UnixTime, lifetime: Duration) -> Self {
+        Self { created, lifetime }
+    }
+
+    /// The time after which the session must not be resumed.
+    pub fn expires_at(&self) -> UnixTime {
+        UnixTime::since_unix_epoch(Duration::from_secs(
+            self.created
+                .as_secs()
+                .saturating_add(self.lifetime.as_secs()),
+        ))
+    }
+
+    /// Whether the session has expired at `now`.
+    pub fn is_expired(&self, now: UnixTime) -> bool {
+        now.as_secs() > self.expires_at().as_secs()
+    }
+
+    /// How much longer the session may be resumed for at `now`.
+    ///
+    /// This is suitable for use as a TTL in an external store.
+    pub fn time_to_live(&self, now: UnixTime) -> Duration {
+        Duration::from_secs(
+            self.expires_at()
+                .as_secs()
+                .saturating_sub(now.as_secs()),
+        )
+    }
+}
+
+/// Tracks issued TLS1.3 tickets, so they can be made single-use and revoked.
+///
+/// This is consulted for all TLS1.3 tickets: both those encrypted by
+/// [`ServerConfig::ticketer`] and those stored in [`ServerConfig::session_storage`].
+/// A ticket is only accepted if `redeem` allows it, so a registry gives
+/// encrypted tickets the same single-use property that stateful tickets have.
+///
+/// See [`TicketRevocationRegistry`] for an in-memory implementation that can
+/// revoke all tickets for a client identity or server name.
+///
+/// [`TicketRevocationRegistry`]: crate::server::TicketRevocationRegistry
+pub trait TicketRegistry: Debug + Send + Sync {
+    /// Record that `ticket` is about to be sent to a client.
+    ///
+    /// Returns `true` if the ticket was recorded.  Otherwise, the ticket
+    /// is not sent.
+    fn register(&self, ticket: &IssuedTicket<'_>) -> bool;
+
+    /// Decide whether a client may resume using the ticket identified by `id`.
+    ///
+    /// This is called once the client has proven knowledge of the ticket's
+    /// secret, and immediately before the ticket is used.  Returns `true` if
+    /// the ticket may be used.
+    ///
+    /// To make tickets single-use, this **must** atomically forget `id`,
+    /// so that subsequent calls return `false`.
+    fn redeem(&self, id: &TicketId, now: UnixTime) -> bool;
+}
+
+/// A TLS1.3 ticket being issued to a client.
+///
+/// This is passed to [`TicketRegistry::register()`].
+#[non_exhaustive]
+#[derive(Debug)]
+pub struct IssuedTicket<'a> {
+    /// Identifies the ticket.
+    pub id: TicketId,
+    /// The client's verified identity, if client authentication was used.
+    pub peer_identity: Option<&'a Identity<'a>>,
+    /// The server name the client sent, if any.
+    pub server_name: Option<&'a DnsName<'a>>,
+    /// When the ticket was issued, and for how long it may be used.
+    pub expiry: SessionExpiry,
+}
+
+/// Identifies a TLS1.3 ticket in a [`TicketRegistry`].
+///
+/// This is a hash of the ticket (using the hash function of the
+/// cipher suite it was issued with, truncated to 32 bytes) rather
+/// than the ticket itself, so a registry does not need to store
+/// ticket contents.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct TicketId([u8; 32]);
+
+impl TicketId {
+    pub(crate) fn new(hash: &dyn crate::crypto::hash::Hash, ticket: &[u8]) -> Self {
+        let mut id = [0u8; 32];
+        let hashed = hash.hash(ticket);
+        let hashed = hashed.as_ref();
+        let len = hashed.len().min(id.len());
+        id[..len].copy_from_slice(&hashed[..len]);
+        Self(id)
+    }
+}
+
+impl AsRef<[u8]> for TicketId {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
 /// How to choose a certificate chain and signing key for use
 /// in server authentication.
 ///
@@ -717,12 +901,18 @@ impl ConfigBuilder<ServerConfig, WantsServerCert> {
             provider: self.provider,
             cipher_suite_selector: &PreferClientOrder,
             max_fragment_size: None,
+            record_padding: RecordPadding::None,
             session_storage: handy::ServerSessionMemoryCache::new(256),
+            session_lifetime: Duration::from_secs(24 * 60 * 60),
             ticketer: None,
+            ticket_registry: None,
             cert_resolver,
             alpn_protocols: Vec::new(),
+            application_settings: Vec::new(),
             verifier: self.state.verifier,
             key_log: Arc::new(NoKeyLog {}),
+            observer: None,
+            qlog: None,
             enable_secret_extraction: false,
             max_early_data_size: 0,
             send_half_rtt_data: false,
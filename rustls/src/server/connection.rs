@@ -8,20 +8,26 @@ use pki_types::{DnsName, FipsStatus};
 
 use super::config::{ClientHello, ServerConfig};
 use crate::common_state::{
-    CommonState, ConnectionOutputs, EarlyDataEvent, Event, Protocol, Side, maybe_send_fatal_alert,
+    CommonState, ConnectionOutputs, EarlyDataEvent, Event, Output, Protocol, Side,
+    maybe_send_fatal_alert,
 };
 use crate::conn::private::SideOutput;
 use crate::conn::split::SplitConnection;
 use crate::conn::{
-    Connection, ConnectionCommon, KeyingMaterialExporter, MessageHandler, MessageIter, SideData,
-    StateMachine, TlsInputBuffer,
+    Connection, ConnectionCommon, HandoffData, KeyingMaterialExporter, MessageHandler, MessageIter,
+    SideCommonOutput, SideData, StateMachine, TlsInputBuffer, TlsOutputBuffer, TrackedOutput,
+    encode_str, read_str,
 };
 #[cfg(doc)]
 use crate::crypto;
+use crate::crypto::TicketProducer;
 use crate::crypto::cipher::{OutboundPlain, Payload};
-use crate::error::Error;
-use crate::msgs::ServerExtensionsInput;
+use crate::crypto::kx::{CompletedKeyExchange, NamedGroup};
+use crate::error::{Error, InvalidMessage};
+use crate::msgs::{Codec, Reader, ServerExtensionsInput, SizedPayload};
+use crate::observer::ConnectionEvent;
 use crate::server::hs::{self, ChooseConfig, ExpectClientHello, ReadClientHello, ServerState};
+use crate::server::tls13::AwaitKeyExchange;
 use crate::suites::ExtractedSecrets;
 use crate::sync::Arc;
 use crate::tracing::trace;
@@ -113,6 +119,65 @@ impl ServerConnection {
         }
     }
 
+    /// Serialize this established connection, so it can be restored in another process.
+    ///
+    /// The result is sealed with `sealer`, and can be restored with
+    /// [`ServerConnection::dangerous_restore()`] using a `sealer` that can decrypt it.
+    /// This consumes the connection: continuing to use it after serialization would
+    /// reuse nonces once the restored connection sends data.
+    ///
+    /// The serialized connection contains the connection's traffic secrets.
+    /// It is only as safe as the `sealer`'s keys.
+    ///
+    /// This fails if:
+    ///
+    /// - the connection was not made with [`ServerConfig::enable_secret_extraction`] set.
+    /// - the handshake is not complete. Check with [`Connection::is_handshaking()`].
+    /// - a partially received message is buffered.  Supply the rest of it to
+    ///   [`Connection::process_new_packets()`] first.
+    ///
+    /// Early data state and the early exporter are not carried over.
+    pub fn dangerous_serialize(self, sealer: &dyn TicketProducer) -> Result<Vec<u8>, Error> {
+        self.inner.dangerous_serialize(sealer)
+    }
+
+    /// Restore a connection serialized by [`ServerConnection::dangerous_serialize()`].
+    ///
+    /// `config` must have the same cipher suites available as the one used to make the
+    /// original connection.
+    pub fn dangerous_restore(
+        config: Arc<ServerConfig>,
+        serialized: &[u8],
+        sealer: &dyn TicketProducer,
+    ) -> Result<Self, Error> {
+        let mut inner = ConnectionCommon::dangerous_restore(
+            serialized,
+            sealer,
+            config.provider(),
+            config.fips(),
+            |restored, output| ServerState::restore(&config, restored, output),
+        )?;
+        inner
+            .common
+            .send
+            .set_max_fragment_size(config.max_fragment_size)?;
+        inner
+            .common
+            .send
+            .set_record_padding(config.record_padding.clone());
+        inner
+            .common
+            .set_observer(config.observer.as_ref());
+        inner
+            .common
+            .set_qlog(config.start_qlog());
+        inner
+            .common
+            .send
+            .refresh_restored_traffic_keys();
+        Ok(Self { inner })
+    }
+
     /// Returns an `io::Read` implementer you can read bytes from that are
     /// received from a client as TLS1.3 0RTT/"early" data, during the handshake.
     ///
@@ -140,7 +205,11 @@ impl ServerConnection {
 impl Connection for ServerConnection {
     type Side = ServerSide;
 
-    fn write_tls(&mut self, plaintext: OutboundPlain<'_>, tls: &mut Vec<u8>) -> Result<(), Error> {
+    fn write_tls(
+        &mut self,
+        plaintext: OutboundPlain<'_>,
+        tls: &mut dyn TlsOutputBuffer,
+    ) -> Result<(), Error> {
         self.inner.write_tls(plaintext, tls)
     }
 
@@ -151,7 +220,7 @@ impl Connection for ServerConnection {
     fn process_new_packets<'a, 'm>(
         &'a mut self,
         input: &'m mut dyn TlsInputBuffer,
-        tls: &'a mut Vec<u8>,
+        tls: &'a mut dyn TlsOutputBuffer,
     ) -> MessageHandler<'a, 'm, ServerSide> {
         self.inner
             .process_new_packets(input, tls)
@@ -165,12 +234,12 @@ impl Connection for ServerConnection {
         self.inner.dangerous_extract_secrets()
     }
 
-    fn refresh_traffic_keys(&mut self, tls: &mut Vec<u8>) -> Result<(), Error> {
+    fn refresh_traffic_keys(&mut self, tls: &mut dyn TlsOutputBuffer) -> Result<(), Error> {
         self.inner.refresh_traffic_keys(tls)
     }
 
-    fn send_close_notify(&mut self, tls: &mut Vec<u8>) {
-        self.inner.send_close_notify(tls);
+    fn send_close_notify(&mut self, tls: &mut dyn TlsOutputBuffer) -> Result<(), Error> {
+        self.inner.send_close_notify(tls)
     }
 
     fn is_handshaking(&self) -> bool {
@@ -210,6 +279,12 @@ pub enum ServerHandshake {
     /// [`Accepted::client_hello()`] and providing it to [`Accepted::choose_config()`].
     Accepted(Accepted),
 
+    /// The key exchange must be completed.
+    ///
+    /// This only happens in TLS1.3 handshakes.  See [`CompleteKeyExchange`] for how to
+    /// proceed.
+    CompleteKeyExchange(CompleteKeyExchange),
+
     /// The client's presented identity must be verified.
     ///
     /// See [`VerifyClientIdentity`] for how to proceed.
@@ -250,6 +325,13 @@ impl TryFrom<ConnectionCommon<ServerSide>> for ServerHandshake {
                 choose_config,
             }),
 
+            ServerState::CompleteKeyExchange(key_exchange) => {
+                Self::CompleteKeyExchange(CompleteKeyExchange {
+                    inner,
+                    key_exchange,
+                })
+            }
+
             ServerState::VerifyClientIdentity(verify_identity) => {
                 Self::VerifyClientIdentity(VerifyClientIdentity {
                     inner,
@@ -293,7 +375,7 @@ impl NeedsInput {
     pub fn process(
         mut self,
         input: &mut dyn TlsInputBuffer,
-        tls: &mut Vec<u8>,
+        tls: &mut dyn TlsOutputBuffer,
     ) -> Result<ServerHandshake, Error> {
         let mut iter = MessageIter::new(input, tls, None, &mut self.inner, false);
         let r = loop {
@@ -303,8 +385,8 @@ impl NeedsInput {
                 None => break Ok(()),
             };
 
-            // end loop as soon as traffic state is entered, as the above loop drops
-            // incoming appdata.
+            // end loop as soon as traffic state is entered: incoming appdata
+            // is left in `input` for the `ReceiveTraffic` half.
             if iter
                 .state()
                 .as_ref()
@@ -367,7 +449,7 @@ impl Accepted {
     pub fn choose_config(
         mut self,
         config: Arc<ServerConfig>,
-        tls: &mut Vec<u8>,
+        tls: &mut dyn TlsOutputBuffer,
     ) -> Result<ServerHandshake, Error> {
         let result = self.inner.accepted(
             self.choose_config,
@@ -384,10 +466,7 @@ impl Accepted {
         }
 
         result?;
-
-        Ok(ServerHandshake::NeedsInput(NeedsInput {
-            inner: self.inner,
-        }))
+        ServerHandshake::try_from(self.inner)
     }
 }
 
@@ -398,6 +477,110 @@ impl fmt::Debug for Accepted {
     }
 }
 
+/// The key exchange must be completed.
+///
+/// This happens in TLS1.3 handshakes where the negotiated key exchange group
+/// [defers completion](crypto::kx::SupportedKxGroup::defers_completion).
+///
+/// It only happens for TLS1.3 servers.  TLS1.2 and DTLS1.3 servers, and all clients,
+/// complete the key exchange synchronously even if the group defers completion.
+///
+/// The caller has three choices:
+///
+/// - Call [`Self::use_kx_group()`].  This calls [`SupportedKxGroup::start_and_complete()`][]
+///   synchronously.
+///
+/// - Call [`Self::group()`] and [`Self::peer_key_share()`] to obtain the inputs to the key
+///   exchange, complete it outside the library (perhaps asynchronously, or in another process),
+///   and then continue the handshake with [`Self::continue_with()`].
+///
+///   If the key exchange fails, the error can be passed into [`Self::continue_with()`] to follow
+///   a uniform error handling path.
+///
+/// - Abandon the handshake by discarding this object.
+///
+/// The returned object is a further [`ServerHandshake`].  Commonly this will be a
+/// [`ServerHandshake::NeedsInput`] which will accept and process further data.
+///
+/// [`SupportedKxGroup::start_and_complete()`]: crypto::kx::SupportedKxGroup::start_and_complete()
+pub struct CompleteKeyExchange {
+    // invariant: `inner.state` is `Err(_)` and requires restoring
+    inner: ConnectionCommon<ServerSide>,
+    key_exchange: Box<AwaitKeyExchange>,
+}
+
+impl CompleteKeyExchange {
+    /// Progress the handshake by calling [`SupportedKxGroup::start_and_complete()`].
+    ///
+    /// [`SupportedKxGroup::start_and_complete()`]: crypto::kx::SupportedKxGroup::start_and_complete()
+    ///
+    /// Output to send to the peer is appended to `tls`.  Typically, this is the `ServerHello`
+    /// and the rest of the server's first flight, but it may also be an `Alert` if an
+    /// error is returned.
+    pub fn use_kx_group(self, tls: &mut dyn TlsOutputBuffer) -> Result<ServerHandshake, Error> {
+        let key_exchange = self.key_exchange;
+        Self::next(self.inner, tls, |output| key_exchange.use_kx_group(output))
+    }
+
+    /// Progress the handshake by incorporating the result of an external key exchange.
+    ///
+    /// `key_exchange_result` must be for the group given by [`Self::group()`], completed
+    /// against [`Self::peer_key_share()`].
+    ///
+    /// If `key_exchange_result` is an error, this error is returned and the handshake terminates.
+    /// Output to send to the peer is appended to `tls`, as for [`Self::use_kx_group()`].
+    pub fn continue_with(
+        self,
+        key_exchange_result: Result<CompletedKeyExchange, Error>,
+        tls: &mut dyn TlsOutputBuffer,
+    ) -> Result<ServerHandshake, Error> {
+        let key_exchange = self.key_exchange;
+        Self::next(self.inner, tls, |output| {
+            key_exchange_result.and_then(|ckx| key_exchange.continue_with(ckx, output))
+        })
+    }
+
+    /// The negotiated key exchange group.
+    pub fn group(&self) -> NamedGroup {
+        self.key_exchange.group()
+    }
+
+    /// The client's key share for [`Self::group()`].
+    pub fn peer_key_share(&self) -> &[u8] {
+        self.key_exchange.peer_key_share()
+    }
+
+    fn next(
+        mut inner: ConnectionCommon<ServerSide>,
+        tls: &mut dyn TlsOutputBuffer,
+        f: impl FnOnce(&mut dyn Output<'_>) -> Result<ServerState, Error>,
+    ) -> Result<ServerHandshake, Error> {
+        let mut output = TrackedOutput::new(tls);
+        let result = f(&mut SideCommonOutput {
+            side: &mut inner.side,
+            quic: None,
+            common: &mut inner.common,
+            tls: &mut output,
+        });
+        let result = result.and_then(|st| output.result().map(|()| st));
+
+        if let Err(err) = &result {
+            maybe_send_fatal_alert(&mut inner.common.send, err, tls);
+        }
+
+        inner.state = result;
+        ServerHandshake::try_from(inner)
+    }
+}
+
+impl fmt::Debug for CompleteKeyExchange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompleteKeyExchange")
+            .field("group", &self.group())
+            .finish_non_exhaustive()
+    }
+}
+
 /// The client's presented identity must be verified.
 ///
 /// The caller has three choices:
@@ -426,7 +609,10 @@ pub struct VerifyClientIdentity {
 
 impl VerifyClientIdentity {
     /// Progress the handshake by calling the pre-configured certificate verification trait.
-    pub fn use_verifier_trait(self, tls: &mut Vec<u8>) -> Result<ServerHandshake, Error> {
+    pub fn use_verifier_trait(
+        self,
+        tls: &mut dyn TlsOutputBuffer,
+    ) -> Result<ServerHandshake, Error> {
         Self::next(
             self.inner,
             self.verify_identity
@@ -442,7 +628,7 @@ impl VerifyClientIdentity {
     pub fn continue_with(
         self,
         verification_result: Result<VerifiedIdentity<'static>, Error>,
-        tls: &mut Vec<u8>,
+        tls: &mut dyn TlsOutputBuffer,
     ) -> Result<ServerHandshake, Error> {
         Self::next(
             self.inner,
@@ -463,10 +649,15 @@ impl VerifyClientIdentity {
     fn next(
         mut inner: ConnectionCommon<ServerSide>,
         result: Result<ServerState, Error>,
-        tls: &mut Vec<u8>,
+        tls: &mut dyn TlsOutputBuffer,
     ) -> Result<ServerHandshake, Error> {
-        if let Err(err) = &result {
-            maybe_send_fatal_alert(&mut inner.common.send, err, tls);
+        match &result {
+            Ok(_) => inner
+                .common
+                .recv
+                .observer
+                .observe(ConnectionEvent::CertificateVerified),
+            Err(err) => maybe_send_fatal_alert(&mut inner.common.send, err, tls),
         }
 
         inner.state = result;
@@ -587,6 +778,11 @@ impl ConnectionCommon<ServerSide> {
         common
             .send
             .set_max_fragment_size(config.max_fragment_size)?;
+        common
+            .send
+            .set_record_padding(config.record_padding.clone());
+        common.set_observer(config.observer.as_ref());
+        common.set_qlog(config.start_qlog());
         Ok(Self::new(
             Box::new(ExpectClientHello::new(
                 config,
@@ -651,6 +847,46 @@ impl SideData for ServerSide {}
 impl crate::conn::private::Side for ServerSide {
     type Data = ServerConnectionData;
     type State = ServerState;
+    const SIDE: Side = Side::Server;
+}
+
+impl HandoffData for ServerConnectionData {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        match &self.sni {
+            Some(sni) => {
+                bytes.push(1);
+                encode_str(sni.as_ref(), bytes);
+            }
+            None => bytes.push(0),
+        }
+        match &self.received_resumption_data {
+            Some(data) => {
+                bytes.push(1);
+                SizedPayload::<u16>::from(Payload::Borrowed(data)).encode(bytes);
+            }
+            None => bytes.push(0),
+        }
+    }
+
+    fn read(r: &mut Reader<'_>) -> Result<Self, InvalidMessage> {
+        let sni = match u8::read(r)? {
+            1 => Some(
+                DnsName::try_from(read_str(r)?)
+                    .map_err(|_| InvalidMessage::InvalidServerName)?
+                    .to_owned(),
+            ),
+            _ => None,
+        };
+        let received_resumption_data = match u8::read(r)? {
+            1 => Some(SizedPayload::<u16>::read(r)?.into_vec()),
+            _ => None,
+        };
+        Ok(Self {
+            sni,
+            received_resumption_data,
+            early_data: EarlyDataState::default(),
+        })
+    }
 }
 
 #[cfg(test)]
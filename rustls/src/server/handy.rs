@@ -1,7 +1,9 @@
 use alloc::vec::Vec;
 use core::fmt::Debug;
 
-use crate::server::{ServerSessionKey, StoresServerSessions};
+use pki_types::UnixTime;
+
+use crate::server::{ServerSessionKey, SessionExpiry, StoresServerSessions};
 
 /// Something which never stores sessions.
 #[expect(clippy::exhaustive_structs)]
@@ -9,13 +11,13 @@ use crate::server::{ServerSessionKey, StoresServerSessions};
 pub struct NoServerSessionStorage {}
 
 impl StoresServerSessions for NoServerSessionStorage {
-    fn put(&self, _id: ServerSessionKey<'_>, _sec: Vec<u8>) -> bool {
+    fn put(&self, _id: ServerSessionKey<'_>, _sec: Vec<u8>, _expiry: SessionExpiry) -> bool {
         false
     }
-    fn get(&self, _id: ServerSessionKey<'_>) -> Option<Vec<u8>> {
+    fn get(&self, _id: ServerSessionKey<'_>, _now: UnixTime) -> Option<Vec<u8>> {
         None
     }
-    fn take(&self, _id: ServerSessionKey<'_>) -> Option<Vec<u8>> {
+    fn take(&self, _id: ServerSessionKey<'_>, _now: UnixTime) -> Option<Vec<u8>> {
         None
     }
     fn can_cache(&self) -> bool {
@@ -25,6 +27,8 @@ impl StoresServerSessions for NoServerSessionStorage {
 
 mod cache {
     use core::fmt::Formatter;
+    use core::hash::BuildHasher;
+    use std::collections::hash_map::RandomState;
 
     use super::*;
     use crate::limited_cache;
@@ -35,8 +39,10 @@ mod cache {
     /// An implementer of `StoresServerSessions` that stores everything
     /// in memory.  If enforces a limit on the number of stored sessions
     /// to bound memory usage.
+    ///
+    /// Expired sessions are never returned.
     pub struct ServerSessionMemoryCache {
-        cache: Mutex<limited_cache::LimitedCache<Vec<u8>, Vec<u8>>>,
+        shard: Shard,
     }
 
     impl ServerSessionMemoryCache {
@@ -45,33 +51,22 @@ mod cache {
         /// efficiency.
         pub fn new(size: usize) -> Arc<Self> {
             Arc::new(Self {
-                cache: Mutex::new(limited_cache::LimitedCache::new(size)),
+                shard: Shard::new(size),
             })
         }
     }
 
     impl StoresServerSessions for ServerSessionMemoryCache {
-        fn put(&self, key: ServerSessionKey<'_>, value: Vec<u8>) -> bool {
-            self.cache
-                .lock()
-                .unwrap()
-                .insert(key.as_ref().to_vec(), value);
-            true
+        fn put(&self, key: ServerSessionKey<'_>, value: Vec<u8>, expiry: SessionExpiry) -> bool {
+            self.shard.put(key, value, expiry)
         }
 
-        fn get(&self, key: ServerSessionKey<'_>) -> Option<Vec<u8>> {
-            self.cache
-                .lock()
-                .unwrap()
-                .get(key.as_ref())
-                .cloned()
+        fn get(&self, key: ServerSessionKey<'_>, now: UnixTime) -> Option<Vec<u8>> {
+            self.shard.get(key, now)
         }
 
-        fn take(&self, key: ServerSessionKey<'_>) -> Option<Vec<u8>> {
-            self.cache
-                .lock()
-                .unwrap()
-                .remove(key.as_ref())
+        fn take(&self, key: ServerSessionKey<'_>, now: UnixTime) -> Option<Vec<u8>> {
+            self.shard.take(key, now)
         }
 
         fn can_cache(&self) -> bool {
@@ -86,8 +81,118 @@ mod cache {
         }
     }
 
+    /// An implementer of `StoresServerSessions` that stores everything
+    /// in memory, split across independently locked shards.
+    ///
+    /// This behaves like [`ServerSessionMemoryCache`], but reduces lock
+    /// contention for servers that resume many sessions concurrently.
+    /// Each shard evicts its own oldest sessions when full.
+    pub struct ShardedServerSessionCache {
+        shards: Vec<Shard>,
+        hasher: RandomState,
+    }
+
+    impl ShardedServerSessionCache {
+        /// Make a new ShardedServerSessionCache.  `size` is the maximum
+        /// number of stored sessions, divided evenly between `shards`
+        /// shards.  Both may be rounded-up for efficiency.
+        pub fn new(size: usize, shards: usize) -> Arc<Self> {
+            let shards = shards.max(1);
+            let per_shard = size.div_ceil(shards);
+            Arc::new(Self {
+                shards: (0..shards)
+                    .map(|_| Shard::new(per_shard))
+                    .collect(),
+                hasher: RandomState::new(),
+            })
+        }
+
+        fn shard(&self, key: &ServerSessionKey<'_>) -> &Shard {
+            &self.shards[self.shard_index(key)]
+        }
+
+        fn shard_index(&self, key: &ServerSessionKey<'_>) -> usize {
+            let hash = self.hasher.hash_one(key.as_ref());
+            (hash % self.shards.len() as u64) as usize
+        }
+    }
+
+    impl StoresServerSessions for ShardedServerSessionCache {
+        fn put(&self, key: ServerSessionKey<'_>, value: Vec<u8>, expiry: SessionExpiry) -> bool {
+            self.shard(&key).put(key, value, expiry)
+        }
+
+        fn get(&self, key: ServerSessionKey<'_>, now: UnixTime) -> Option<Vec<u8>> {
+            self.shard(&key).get(key, now)
+        }
+
+        fn take(&self, key: ServerSessionKey<'_>, now: UnixTime) -> Option<Vec<u8>> {
+            self.shard(&key).take(key, now)
+        }
+
+        fn can_cache(&self) -> bool {
+            true
+        }
+    }
+
+    impl Debug for ShardedServerSessionCache {
+        fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+            f.debug_struct("ShardedServerSessionCache")
+                .field("shards", &self.shards.len())
+                .finish_non_exhaustive()
+        }
+    }
+
+    struct Shard {
+        cache: Mutex<limited_cache::LimitedCache<Vec<u8>, StoredSession>>,
+    }
+
+    impl Shard {
+        fn new(size: usize) -> Self {
+            Self {
+                cache: Mutex::new(limited_cache::LimitedCache::new(size)),
+            }
+        }
+
+        fn put(&self, key: ServerSessionKey<'_>, value: Vec<u8>, expiry: SessionExpiry) -> bool {
+            self.cache
+                .lock()
+                .unwrap()
+                .insert(key.as_ref().to_vec(), StoredSession { value, expiry });
+            true
+        }
+
+        fn get(&self, key: ServerSessionKey<'_>, now: UnixTime) -> Option<Vec<u8>> {
+            let mut cache = self.cache.lock().unwrap();
+            let stored = cache.get(key.as_ref())?;
+            if stored.expiry.is_expired(now) {
+                cache.remove(key.as_ref());
+                return None;
+            }
+            Some(stored.value.clone())
+        }
+
+        fn take(&self, key: ServerSessionKey<'_>, now: UnixTime) -> Option<Vec<u8>> {
+            let stored = self
+                .cache
+                .lock()
+                .unwrap()
+                .remove(key.as_ref())?;
+            match stored.expiry.is_expired(now) {
+                true => None,
+                false => Some(stored.value),
+            }
+        }
+    }
+
+    struct StoredSession {
+        value: Vec<u8>,
+        expiry: SessionExpiry,
+    }
+
     #[cfg(test)]
     mod tests {
+        use core::time::Duration;
         use std::vec;
 
         use super::*;
@@ -96,57 +201,650 @@ mod cache {
         #[test]
         fn test_serversessionmemorycache_accepts_put() {
             let c = ServerSessionMemoryCache::new(4);
-            assert!(c.put(ServerSessionKey::new(&[0x01]), vec![0x02]));
+            assert!(c.put(ServerSessionKey::new(&[0x01]), vec![0x02], expiry()));
         }
 
         #[test]
         fn test_serversessionmemorycache_persists_put() {
             let c = ServerSessionMemoryCache::new(4);
-            assert!(c.put(ServerSessionKey::new(&[0x01]), vec![0x02]));
-            assert_eq!(c.get(ServerSessionKey::new(&[0x01])), Some(vec![0x02]));
-            assert_eq!(c.get(ServerSessionKey::new(&[0x01])), Some(vec![0x02]));
+            assert!(c.put(ServerSessionKey::new(&[0x01]), vec![0x02], expiry()));
+            assert_eq!(
+                c.get(ServerSessionKey::new(&[0x01]), at(0)),
+                Some(vec![0x02])
+            );
+            assert_eq!(
+                c.get(ServerSessionKey::new(&[0x01]), at(0)),
+                Some(vec![0x02])
+            );
         }
 
         #[test]
         fn test_serversessionmemorycache_overwrites_put() {
             let c = ServerSessionMemoryCache::new(4);
-            assert!(c.put(ServerSessionKey::new(&[0x01]), vec![0x02]));
-            assert!(c.put(ServerSessionKey::new(&[0x01]), vec![0x04]));
-            assert_eq!(c.get(ServerSessionKey::new(&[0x01])), Some(vec![0x04]));
+            assert!(c.put(ServerSessionKey::new(&[0x01]), vec![0x02], expiry()));
+            assert!(c.put(ServerSessionKey::new(&[0x01]), vec![0x04], expiry()));
+            assert_eq!(
+                c.get(ServerSessionKey::new(&[0x01]), at(0)),
+                Some(vec![0x04])
+            );
         }
 
         #[test]
         fn test_serversessionmemorycache_drops_to_maintain_size_invariant() {
             let c = ServerSessionMemoryCache::new(2);
-            assert!(c.put(ServerSessionKey::new(&[0x01]), vec![0x02]));
-            assert!(c.put(ServerSessionKey::new(&[0x03]), vec![0x04]));
-            assert!(c.put(ServerSessionKey::new(&[0x05]), vec![0x06]));
-            assert!(c.put(ServerSessionKey::new(&[0x07]), vec![0x08]));
-            assert!(c.put(ServerSessionKey::new(&[0x09]), vec![0x0a]));
+            assert!(c.put(ServerSessionKey::new(&[0x01]), vec![0x02], expiry()));
+            assert!(c.put(ServerSessionKey::new(&[0x03]), vec![0x04], expiry()));
+            assert!(c.put(ServerSessionKey::new(&[0x05]), vec![0x06], expiry()));
+            assert!(c.put(ServerSessionKey::new(&[0x07]), vec![0x08], expiry()));
+            assert!(c.put(ServerSessionKey::new(&[0x09]), vec![0x0a], expiry()));
 
             let count = c
-                .get(ServerSessionKey::new(&[0x01]))
+                .get(ServerSessionKey::new(&[0x01]), at(0))
                 .iter()
                 .count()
-                + c.get(ServerSessionKey::new(&[0x03]))
+                + c.get(ServerSessionKey::new(&[0x03]), at(0))
                     .iter()
                     .count()
-                + c.get(ServerSessionKey::new(&[0x05]))
+                + c.get(ServerSessionKey::new(&[0x05]), at(0))
                     .iter()
                     .count()
-                + c.get(ServerSessionKey::new(&[0x07]))
+                + c.get(ServerSessionKey::new(&[0x07]), at(0))
                     .iter()
                     .count()
-                + c.get(ServerSessionKey::new(&[0x09]))
+                + c.get(ServerSessionKey::new(&[0x09]), at(0))
                     .iter()
                     .count();
 
             assert!(count < 5);
         }
+
+        #[test]
+        fn test_serversessionmemorycache_expires() {
+            let c = ServerSessionMemoryCache::new(4);
+            assert!(c.put(ServerSessionKey::new(&[0x01]), vec![0x02], expiry()));
+            assert!(c.put(ServerSessionKey::new(&[0x03]), vec![0x04], expiry()));
+            assert_eq!(
+                c.get(ServerSessionKey::new(&[0x01]), at(LIFETIME)),
+                Some(vec![0x02])
+            );
+            assert_eq!(
+                c.get(ServerSessionKey::new(&[0x01]), at(LIFETIME + 1)),
+                None
+            );
+            assert_eq!(
+                c.take(ServerSessionKey::new(&[0x03]), at(LIFETIME + 1)),
+                None
+            );
+
+            // Expired sessions are removed when found.
+            assert_eq!(c.get(ServerSessionKey::new(&[0x01]), at(0)), None);
+            assert_eq!(c.take(ServerSessionKey::new(&[0x03]), at(0)), None);
+        }
+
+        #[test]
+        fn test_shardedserversessioncache() {
+            let c = ShardedServerSessionCache::new(64, 4);
+
+            // Shards are chosen by a randomly-keyed hash, so pick keys per shard rather
+            // than assuming an even spread: each shard gets fewer keys than its capacity.
+            let mut per_shard = [0usize; 4];
+            let mut keys = vec![];
+            for i in 0..=u8::MAX {
+                let shard = c.shard_index(&ServerSessionKey::new(&[i]));
+                if per_shard[shard] < 15 {
+                    per_shard[shard] += 1;
+                    keys.push(i);
+                }
+            }
+
+            for &i in &keys {
+                assert!(c.put(ServerSessionKey::new(&[i]), vec![i], expiry()));
+            }
+            for &i in &keys {
+                assert_eq!(c.get(ServerSessionKey::new(&[i]), at(0)), Some(vec![i]));
+            }
+            for &i in &keys {
+                assert_eq!(c.take(ServerSessionKey::new(&[i]), at(0)), Some(vec![i]));
+                assert_eq!(c.take(ServerSessionKey::new(&[i]), at(0)), None);
+            }
+
+            assert!(c.put(ServerSessionKey::new(&[0x01]), vec![0x02], expiry()));
+            assert_eq!(
+                c.get(ServerSessionKey::new(&[0x01]), at(LIFETIME + 1)),
+                None
+            );
+        }
+
+        fn expiry() -> SessionExpiry {
+            SessionExpiry::new(at(0), Duration::from_secs(LIFETIME))
+        }
+
+        fn at(offset: u64) -> UnixTime {
+            UnixTime::since_unix_epoch(Duration::from_secs(START + offset))
+        }
+
+        const START: u64 = 1_700_000_000;
+        const LIFETIME: u64 = 3600;
+    }
+}
+
+pub use cache::{ServerSessionMemoryCache, ShardedServerSessionCache};
+
+mod remote {
+    use core::time::Duration;
+    use std::io;
+
+    use super::*;
+    use crate::msgs::{Codec, Reader};
+    use crate::tracing::debug;
+
+    /// A client for an external key-value store, such as memcached.
+    ///
+    /// Wrap an implementation in [`RemoteSessionStore`] to use it as
+    /// [`ServerConfig::session_storage`], sharing sessions between servers.
+    ///
+    /// [`ServerConfig::session_storage`]: crate::server::ServerConfig::session_storage
+    pub trait SessionStoreClient: Debug + Send + Sync {
+        /// Store `value` against `key`, replacing any existing value.
+        ///
+        /// The store may delete the value after `ttl`.
+        fn set(&self, key: &[u8], value: &[u8], ttl: Duration) -> io::Result<()>;
+
+        /// Fetch the value stored against `key`, if any.
+        fn get(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>>;
+
+        /// Fetch and delete the value stored against `key`, if any.
+        ///
+        /// This **must** be atomic: a value must be returned by at most one call.
+        /// TLS1.3 tickets rely on this to be single-use.
+        fn take(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>>;
+    }
+
+    /// An implementer of `StoresServerSessions` that stores sessions using a
+    /// [`SessionStoreClient`].
+    ///
+    /// Each session is stored with a TTL of its lifetime, and its expiry time
+    /// is stored alongside it, so sessions are never returned after expiry even
+    /// if the store retains them.  Errors from the store are logged and
+    /// otherwise treated as a cache miss.
+    #[derive(Debug)]
+    pub struct RemoteSessionStore<C> {
+        client: C,
+        key_prefix: Vec<u8>,
+    }
+
+    impl<C: SessionStoreClient> RemoteSessionStore<C> {
+        /// Make a new `RemoteSessionStore` using `client`.
+        pub fn new(client: C) -> Self {
+            Self {
+                client,
+                key_prefix: Vec::new(),
+            }
+        }
+
+        /// Prefix all keys with `prefix`, to share a store between applications.
+        pub fn with_key_prefix(mut self, prefix: &[u8]) -> Self {
+            self.key_prefix = prefix.to_vec();
+            self
+        }
+
+        fn key(&self, key: &ServerSessionKey<'_>) -> Vec<u8> {
+            let mut full = self.key_prefix.clone();
+            full.extend_from_slice(key.as_ref());
+            full
+        }
+
+        fn unwrap(result: io::Result<Option<Vec<u8>>>, now: UnixTime) -> Option<Vec<u8>> {
+            let stored = match result {
+                Ok(stored) => stored?,
+                Err(_err) => {
+                    debug!("session store failed: {_err}");
+                    return None;
+                }
+            };
+
+            let mut r = Reader::new(&stored);
+            let expires_at = u64::read(&mut r).ok()?;
+            match now.as_secs() > expires_at {
+                true => None,
+                false => Some(r.rest().to_vec()),
+            }
+        }
+    }
+
+    impl<C: SessionStoreClient> StoresServerSessions for RemoteSessionStore<C> {
+        fn put(&self, key: ServerSessionKey<'_>, value: Vec<u8>, expiry: SessionExpiry) -> bool {
+            let mut stored = Vec::with_capacity(8 + value.len());
+            expiry
+                .expires_at()
+                .as_secs()
+                .encode(&mut stored);
+            stored.extend_from_slice(&value);
+
+            match self
+                .client
+                .set(&self.key(&key), &stored, expiry.lifetime)
+            {
+                Ok(()) => true,
+                Err(_err) => {
+                    debug!("session store failed: {_err}");
+                    false
+                }
+            }
+        }
+
+        fn get(&self, key: ServerSessionKey<'_>, now: UnixTime) -> Option<Vec<u8>> {
+            Self::unwrap(self.client.get(&self.key(&key)), now)
+        }
+
+        fn take(&self, key: ServerSessionKey<'_>, now: UnixTime) -> Option<Vec<u8>> {
+            Self::unwrap(self.client.take(&self.key(&key)), now)
+        }
+
+        fn can_cache(&self) -> bool {
+            true
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use core::net::SocketAddr;
+        use std::collections::HashMap;
+        use std::io::{Read, Write};
+        use std::net::{TcpListener, TcpStream};
+        use std::{thread, vec};
+
+        use super::*;
+        use crate::lock::{Mutex, MutexGuard};
+        use crate::sync::Arc;
+
+        #[test]
+        fn test_remotesessionstore_expires() {
+            let server = LoopbackServer::start();
+            let remote = RemoteSessionStore::new(server.client()).with_key_prefix(b"tls:");
+            let created = UnixTime::since_unix_epoch(Duration::from_secs(START));
+            let expiry = SessionExpiry::new(created, Duration::from_secs(60));
+
+            assert!(remote.put(ServerSessionKey::new(b"one"), vec![0x01], expiry));
+            assert!(remote.put(ServerSessionKey::new(b"two"), vec![0x02], expiry));
+            assert_eq!(server.state().ttl, Some(Duration::from_secs(60)));
+
+            assert_eq!(
+                remote.get(ServerSessionKey::new(b"one"), at(60)),
+                Some(vec![0x01])
+            );
+            assert_eq!(remote.get(ServerSessionKey::new(b"one"), at(61)), None);
+            assert_eq!(remote.take(ServerSessionKey::new(b"two"), at(61)), None);
+            assert!(
+                !server
+                    .state()
+                    .entries
+                    .contains_key(&b"tls:two"[..])
+            );
+
+            assert_eq!(
+                remote.take(ServerSessionKey::new(b"one"), at(0)),
+                Some(vec![0x01])
+            );
+            assert_eq!(remote.take(ServerSessionKey::new(b"one"), at(0)), None);
+        }
+
+        #[test]
+        fn test_remotesessionstore_faults_are_misses() {
+            let server = LoopbackServer::start();
+            let client = server.client();
+            let remote = RemoteSessionStore::new(server.client());
+            let expiry = SessionExpiry::new(at(0), Duration::from_secs(60));
+            assert!(remote.put(ServerSessionKey::new(b"one"), vec![0x01], expiry));
+
+            for (fault, expected) in [
+                (Fault::Garble, &[io::ErrorKind::InvalidData][..]),
+                (Fault::HangUp, &[io::ErrorKind::UnexpectedEof]),
+                (
+                    Fault::Stall,
+                    &[io::ErrorKind::WouldBlock, io::ErrorKind::TimedOut],
+                ),
+            ] {
+                server.state().fault = Some(fault);
+
+                // the fault reaches the client as the expected error...
+                let err = client.get(b"one").unwrap_err();
+                assert!(expected.contains(&err.kind()), "{fault:?}: {err:?}");
+
+                // ...which the store treats as a miss
+                assert!(!remote.put(ServerSessionKey::new(b"two"), vec![0x02], expiry));
+                assert_eq!(remote.get(ServerSessionKey::new(b"one"), at(0)), None);
+                assert_eq!(remote.take(ServerSessionKey::new(b"one"), at(0)), None);
+            }
+
+            // failed requests had no effect
+            server.state().fault = None;
+            assert_eq!(remote.get(ServerSessionKey::new(b"two"), at(0)), None);
+            assert_eq!(
+                remote.take(ServerSessionKey::new(b"one"), at(0)),
+                Some(vec![0x01])
+            );
+        }
+
+        #[test]
+        fn test_remotesessionstore_corrupt_value_is_miss() {
+            let server = LoopbackServer::start();
+            let remote = RemoteSessionStore::new(server.client());
+
+            // too short to hold the expiry time
+            server
+                .state()
+                .entries
+                .insert(b"one".to_vec(), vec![0x01, 0x02, 0x03]);
+            assert_eq!(remote.get(ServerSessionKey::new(b"one"), at(0)), None);
+            assert_eq!(remote.take(ServerSessionKey::new(b"one"), at(0)), None);
+        }
+
+        /// A key-value store served over a loopback TCP socket.
+        ///
+        /// Each request is a length-prefixed frame holding an operation byte,
+        /// a key, and for `SET` a TTL and value.  Replies are `+` for a
+        /// completed `SET`, `-` for no value, or `=` followed by a value.
+        struct LoopbackServer {
+            addr: SocketAddr,
+            state: Arc<Mutex<ServerState>>,
+        }
+
+        impl LoopbackServer {
+            fn start() -> Self {
+                let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+                let addr = listener.local_addr().unwrap();
+                let state = Arc::new(Mutex::new(ServerState::default()));
+
+                let shared = state.clone();
+                thread::spawn(move || {
+                    for stream in listener.incoming() {
+                        let Ok(stream) = stream else {
+                            return;
+                        };
+                        let state = shared.clone();
+                        thread::spawn(move || serve(stream, &state));
+                    }
+                });
+
+                Self { addr, state }
+            }
+
+            fn client(&self) -> LoopbackClient {
+                LoopbackClient(self.addr)
+            }
+
+            fn state(&self) -> MutexGuard<'_, ServerState> {
+                self.state.lock().unwrap()
+            }
+        }
+
+        #[derive(Debug, Default)]
+        struct ServerState {
+            entries: HashMap<Vec<u8>, Vec<u8>>,
+            ttl: Option<Duration>,
+            fault: Option<Fault>,
+        }
+
+        /// A failure injected by [`LoopbackServer`], instead of handling a request.
+        #[derive(Clone, Copy, Debug)]
+        enum Fault {
+            /// Reply with a malformed response.
+            Garble,
+            /// Close the connection without replying.
+            HangUp,
+            /// Never reply, so the client times out.
+            Stall,
+        }
+
+        fn serve(mut stream: TcpStream, state: &Mutex<ServerState>) {
+            let Ok(request) = read_frame(&mut stream) else {
+                return;
+            };
+
+            let mut state = state.lock().unwrap();
+            let reply = match state.fault {
+                Some(Fault::Garble) => vec![b'?'],
+                Some(Fault::HangUp) => return,
+                Some(Fault::Stall) => {
+                    drop(state);
+                    // wait for the client to give up
+                    let _ = io::copy(&mut stream, &mut io::sink());
+                    return;
+                }
+                None => {
+                    let mut r = Reader::new(&request);
+                    let op = u8::read(&mut r).unwrap();
+                    let key = take_sized(&mut r).to_vec();
+                    match op {
+                        b'S' => {
+                            state.ttl = Some(Duration::from_secs(u64::read(&mut r).unwrap()));
+                            let value = take_sized(&mut r).to_vec();
+                            state.entries.insert(key, value);
+                            vec![b'+']
+                        }
+                        b'G' => value_reply(state.entries.get(&key).cloned()),
+                        b'T' => value_reply(state.entries.remove(&key)),
+                        _ => vec![b'?'],
+                    }
+                }
+            };
+            drop(state);
+
+            let _ = stream.write_all(&reply);
+        }
+
+        fn value_reply(value: Option<Vec<u8>>) -> Vec<u8> {
+            match value {
+                Some(value) => {
+                    let mut reply = vec![b'='];
+                    put_sized(&value, &mut reply);
+                    reply
+                }
+                None => vec![b'-'],
+            }
+        }
+
+        /// A [`SessionStoreClient`] for [`LoopbackServer`], making one connection per request.
+        #[derive(Debug)]
+        struct LoopbackClient(SocketAddr);
+
+        impl LoopbackClient {
+            fn request(&self, op: u8, key: &[u8], body: &[u8]) -> io::Result<Vec<u8>> {
+                let mut request = vec![op];
+                put_sized(key, &mut request);
+                request.extend_from_slice(body);
+                let mut frame = Vec::new();
+                put_sized(&request, &mut frame);
+
+                let mut stream = TcpStream::connect_timeout(&self.0, TIMEOUT)?;
+                stream.set_read_timeout(Some(TIMEOUT))?;
+                stream.write_all(&frame)?;
+
+                let mut reply = Vec::new();
+                stream.read_to_end(&mut reply)?;
+                Ok(reply)
+            }
+
+            fn value(reply: &[u8]) -> io::Result<Option<Vec<u8>>> {
+                let mut r = Reader::new(reply);
+                match (u8::read(&mut r), reply.len()) {
+                    (Ok(b'-'), 1) => Ok(None),
+                    (Ok(b'='), _) => match u32::read(&mut r) {
+                        Ok(len) if r.left() == len as usize => Ok(Some(r.rest().to_vec())),
+                        _ => Err(malformed()),
+                    },
+                    (Err(_), _) => Err(io::ErrorKind::UnexpectedEof.into()),
+                    _ => Err(malformed()),
+                }
+            }
+        }
+
+        impl SessionStoreClient for LoopbackClient {
+            fn set(&self, key: &[u8], value: &[u8], ttl: Duration) -> io::Result<()> {
+                let mut body = Vec::new();
+                ttl.as_secs().encode(&mut body);
+                put_sized(value, &mut body);
+                match self.request(b'S', key, &body)?[..] {
+                    [b'+'] => Ok(()),
+                    [] => Err(io::ErrorKind::UnexpectedEof.into()),
+                    _ => Err(malformed()),
+                }
+            }
+
+            fn get(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+                Self::value(&self.request(b'G', key, &[])?)
+            }
+
+            fn take(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+                Self::value(&self.request(b'T', key, &[])?)
+            }
+        }
+
+        fn read_frame(stream: &mut TcpStream) -> io::Result<Vec<u8>> {
+            let mut len = [0u8; 4];
+            stream.read_exact(&mut len)?;
+            let mut frame = vec![0u8; u32::from_be_bytes(len) as usize];
+            stream.read_exact(&mut frame)?;
+            Ok(frame)
+        }
+
+        fn put_sized(bytes: &[u8], out: &mut Vec<u8>) {
+            (bytes.len() as u32).encode(out);
+            out.extend_from_slice(bytes);
+        }
+
+        fn take_sized<'a>(r: &mut Reader<'a>) -> &'a [u8] {
+            let len = u32::read(r).unwrap();
+            r.take(len as usize).unwrap()
+        }
+
+        fn malformed() -> io::Error {
+            io::Error::new(io::ErrorKind::InvalidData, "malformed reply")
+        }
+
+        fn at(offset: u64) -> UnixTime {
+            UnixTime::since_unix_epoch(Duration::from_secs(START + offset))
+        }
+
+        const START: u64 = 1_700_000_000;
+        const TIMEOUT: Duration = Duration::from_millis(100);
     }
 }
 
-pub use cache::ServerSessionMemoryCache;
+pub use remote::{RemoteSessionStore, SessionStoreClient};
+
+mod registry {
+    use core::fmt::Formatter;
+
+    use pki_types::DnsName;
+
+    use super::*;
+    use crate::crypto::Identity;
+    use crate::limited_cache;
+    use crate::lock::Mutex;
+    use crate::server::{IssuedTicket, TicketId, TicketRegistry};
+    use crate::sync::Arc;
+
+    /// An implementer of `TicketRegistry` that tracks tickets in memory.
+    ///
+    /// This makes every TLS1.3 ticket single-use, and allows revoking
+    /// all outstanding tickets issued to a client identity (for example,
+    /// after its credentials change) or for a server name.
+    ///
+    /// It enforces a limit on the number of tracked tickets to bound memory
+    /// usage.  When full, the oldest tickets are forgotten, and cannot
+    /// then be used.
+    pub struct TicketRevocationRegistry {
+        tickets: Mutex<limited_cache::LimitedCache<TicketId, RegisteredTicket>>,
+    }
+
+    impl TicketRevocationRegistry {
+        /// Make a new TicketRevocationRegistry.  `size` is the maximum
+        /// number of tracked tickets, and may be rounded-up for
+        /// efficiency.
+        pub fn new(size: usize) -> Arc<Self> {
+            Arc::new(Self {
+                tickets: Mutex::new(limited_cache::LimitedCache::new(size)),
+            })
+        }
+
+        /// Revoke all tickets issued to a client with `identity`.
+        ///
+        /// Returns the number of tickets revoked.
+        pub fn revoke_peer_identity(&self, identity: &Identity<'_>) -> usize {
+            self.revoke(|ticket| ticket.peer_identity.as_ref() == Some(identity))
+        }
+
+        /// Revoke all tickets issued for `server_name`.
+        ///
+        /// Returns the number of tickets revoked.
+        pub fn revoke_server_name(&self, server_name: &DnsName<'_>) -> usize {
+            self.revoke(|ticket| {
+                ticket
+                    .server_name
+                    .as_ref()
+                    .is_some_and(|name| name.as_ref() == server_name.as_ref())
+            })
+        }
+
+        /// Revoke all tickets.
+        ///
+        /// Returns the number of tickets revoked.
+        pub fn revoke_all(&self) -> usize {
+            self.revoke(|_| true)
+        }
+
+        fn revoke(&self, matches: impl Fn(&RegisteredTicket) -> bool) -> usize {
+            self.tickets
+                .lock()
+                .unwrap()
+                .retain(|_, ticket| !matches(ticket))
+        }
+    }
+
+    impl TicketRegistry for TicketRevocationRegistry {
+        fn register(&self, ticket: &IssuedTicket<'_>) -> bool {
+            self.tickets.lock().unwrap().insert(
+                ticket.id,
+                RegisteredTicket {
+                    peer_identity: ticket
+                        .peer_identity
+                        .map(|id| id.clone().into_owned()),
+                    server_name: ticket
+                        .server_name
+                        .map(|name| name.to_owned()),
+                    expiry: ticket.expiry,
+                },
+            );
+            true
+        }
+
+        fn redeem(&self, id: &TicketId, now: UnixTime) -> bool {
+            self.tickets
+                .lock()
+                .unwrap()
+                .remove(id)
+                .is_some_and(|ticket| !ticket.expiry.is_expired(now))
+        }
+    }
+
+    impl Debug for TicketRevocationRegistry {
+        fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+            f.debug_struct("TicketRevocationRegistry")
+                .finish_non_exhaustive()
+        }
+    }
+
+    #[derive(Debug)]
+    struct RegisteredTicket {
+        peer_identity: Option<Identity<'static>>,
+        server_name: Option<DnsName<'static>>,
+        expiry: SessionExpiry,
+    }
+}
+
+pub use registry::TicketRevocationRegistry;
 
 #[cfg(feature = "webpki")]
 mod sni_resolver {
@@ -258,6 +956,7 @@ pub use sni_resolver::ServerNameResolver;
 
 #[cfg(test)]
 mod tests {
+    use core::time::Duration;
     use std::vec;
 
     use super::*;
@@ -266,23 +965,31 @@ mod tests {
     #[test]
     fn test_noserversessionstorage_drops_put() {
         let c = NoServerSessionStorage {};
-        assert!(!c.put(ServerSessionKey::new(&[0x01]), vec![0x02]));
+        assert!(!c.put(ServerSessionKey::new(&[0x01]), vec![0x02], expiry()));
     }
 
     #[test]
     fn test_noserversessionstorage_denies_gets() {
         let c = NoServerSessionStorage {};
-        c.put(ServerSessionKey::new(&[0x01]), vec![0x02]);
-        assert_eq!(c.get(ServerSessionKey::new(&[])), None);
-        assert_eq!(c.get(ServerSessionKey::new(&[0x01])), None);
-        assert_eq!(c.get(ServerSessionKey::new(&[0x02])), None);
+        c.put(ServerSessionKey::new(&[0x01]), vec![0x02], expiry());
+        assert_eq!(c.get(ServerSessionKey::new(&[]), now()), None);
+        assert_eq!(c.get(ServerSessionKey::new(&[0x01]), now()), None);
+        assert_eq!(c.get(ServerSessionKey::new(&[0x02]), now()), None);
     }
 
     #[test]
     fn test_noserversessionstorage_denies_takes() {
         let c = NoServerSessionStorage {};
-        assert_eq!(c.take(ServerSessionKey::new(&[])), None);
-        assert_eq!(c.take(ServerSessionKey::new(&[0x01])), None);
-        assert_eq!(c.take(ServerSessionKey::new(&[0x02])), None);
+        assert_eq!(c.take(ServerSessionKey::new(&[]), now()), None);
+        assert_eq!(c.take(ServerSessionKey::new(&[0x01]), now()), None);
+        assert_eq!(c.take(ServerSessionKey::new(&[0x02]), now()), None);
+    }
+
+    fn expiry() -> SessionExpiry {
+        SessionExpiry::new(now(), Duration::from_secs(3600))
+    }
+
+    fn now() -> UnixTime {
+        UnixTime::since_unix_epoch(Duration::from_secs(1_700_000_000))
     }
 }
@@ -3,6 +3,7 @@ use alloc::boxed::Box;
 use alloc::vec::Vec;
 use core::borrow::Borrow;
 use core::fmt;
+use core::time::Duration;
 
 use pki_types::DnsName;
 
@@ -10,12 +11,14 @@ use super::config::{CipherSuiteSelector, VersionSuiteSelector};
 use super::{ClientHello, CommonServerSessionValue, ServerConfig, tls12, tls13};
 use crate::SupportedCipherSuite;
 use crate::common_state::{Event, Output, OutputEvent, Protocol};
-use crate::conn::{ConnectionRandoms, Input};
+use crate::conn::{ConnectionRandoms, HandoffSecrets, Input, Restored};
 use crate::crypto::cipher::Payload;
 use crate::crypto::hash::Hash;
 use crate::crypto::kx::{KeyExchangeAlgorithm, NamedGroup, SupportedKxGroup};
 use crate::crypto::{CipherSuite, CryptoProvider, SelectedCredential, SignatureScheme};
-use crate::enums::{ApplicationProtocol, CertificateType, HandshakeType, ProtocolVersion};
+use crate::enums::{
+    ApplicationProtocol, ApplicationSettings, CertificateType, HandshakeType, ProtocolVersion,
+};
 use crate::error::{ApiMisuse, Error, PeerIncompatible, PeerMisbehaved};
 use crate::hash_hs::{HandshakeHash, HandshakeHashBuffer};
 use crate::kernel::KernelState;
@@ -24,10 +27,11 @@ use crate::msgs::{
     HandshakeMessagePayload, HandshakePayload, Message, MessagePayload, Random, ServerExtensions,
     ServerExtensionsInput, ServerNamePayload, SessionId, SingleProtocolName, TransportParameters,
 };
+use crate::observer::{ConnectionEvent, ResumptionRejection};
 use crate::sealed::Sealed;
 use crate::suites::{PartiallyExtractedSecrets, Suite};
 use crate::sync::Arc;
-use crate::tls12::Tls12CipherSuite;
+use crate::tls12::{ConnectionSecrets, Tls12CipherSuite};
 use crate::tls13::Tls13CipherSuite;
 use crate::tls13::key_schedule::KeyScheduleTrafficSend;
 use crate::tracing::{debug, trace};
@@ -46,6 +50,9 @@ pub(crate) enum ServerState {
     /// Processing the received ClientHello.
     ClientHello(Box<ExpectClientHello>),
 
+    /// Completing the key exchange outside the library.
+    CompleteKeyExchange(Box<tls13::AwaitKeyExchange>),
+
     /// Verifying the client's present certificate chain.
     VerifyClientIdentity(VerifyClientIdentity),
 
@@ -68,7 +75,9 @@ impl crate::conn::StateMachine for ServerState {
     fn handle<'m>(self, input: Input<'m>, output: &mut dyn Output<'m>) -> Result<Self, Error> {
         match self {
             Self::ReadClientHello(r) => r.handle(input, output),
-            Self::ChooseConfig(_) | Self::VerifyClientIdentity(_) => {
+            Self::ChooseConfig(_)
+            | Self::CompleteKeyExchange(_)
+            | Self::VerifyClientIdentity(_) => {
                 Err(Error::Unreachable("state cannot process a message"))
             }
             Self::ClientHello(e) => e.handle(input, output),
@@ -78,11 +87,15 @@ impl crate::conn::StateMachine for ServerState {
     }
 
     fn wants_input(&self) -> bool {
-        !matches!(self, Self::ChooseConfig(_) | Self::VerifyClientIdentity(_))
+        !matches!(
+            self,
+            Self::ChooseConfig(_) | Self::CompleteKeyExchange(_) | Self::VerifyClientIdentity(_)
+        )
     }
 
-    fn handle_without_input(self) -> Result<Self, Error> {
+    fn handle_without_input<'m>(self, output: &mut dyn Output<'m>) -> Result<Self, Error> {
         match self {
+            Self::CompleteKeyExchange(ckx) => ckx.use_kx_group(output),
             Self::VerifyClientIdentity(vci) => vci.use_verifier_trait(),
             _ => Ok(self),
         }
@@ -108,6 +121,60 @@ impl crate::conn::StateMachine for ServerState {
             _ => Err(Error::HandshakeNotComplete),
         }
     }
+
+    fn handoff_secrets(
+        &self,
+        send_keys: &Option<Box<KeyScheduleTrafficSend>>,
+    ) -> Result<HandoffSecrets, Error> {
+        match self {
+            Self::Tls13(tls13::Tls13State::Traffic(e)) => e.handoff_secrets(send_keys),
+            Self::Tls12(tls12::Tls12State::Traffic(e)) => e.handoff_secrets(),
+            _ => Err(Error::HandshakeNotComplete),
+        }
+    }
+}
+
+impl ServerState {
+    /// Restore the traffic state of a serialized connection.
+    pub(crate) fn restore(
+        config: &Arc<ServerConfig>,
+        restored: Restored<'_>,
+        output: &mut dyn Output<'_>,
+    ) -> Result<Self, Error> {
+        match (restored.suite, restored.secrets) {
+            (
+                SupportedCipherSuite::Tls12(suite),
+                HandoffSecrets::Tls12 {
+                    randoms,
+                    master_secret,
+                },
+            ) => Ok(tls12::ExpectTraffic::restore(
+                config,
+                ConnectionSecrets::new_resume(randoms, suite, &master_secret),
+                restored.exporter.is_some(),
+                output,
+                &restored.proof,
+            )
+            .into()),
+            (
+                SupportedCipherSuite::Tls13(suite),
+                HandoffSecrets::Tls13 {
+                    tx,
+                    rx,
+                    resumption: None,
+                },
+            ) => Ok(tls13::ExpectTraffic::restore(
+                config,
+                suite,
+                (tx, rx),
+                restored.exporter,
+                output,
+                &restored.proof,
+            )
+            .into()),
+            _ => Err(ApiMisuse::IncompatibleSerializedConnection.into()),
+        }
+    }
 }
 
 pub(super) struct Tls12Extensions {
@@ -138,6 +205,16 @@ impl Tls12Extensions {
 
         // Renegotiation.
         // (We don't do reneg at all, but would support the secure version if we did.)
+        // This is always an initial handshake, so the client has no previous
+        // `verify_data` to send.
+        if hello
+            .renegotiation_info
+            .as_ref()
+            .is_some_and(|info| !info.is_empty())
+        {
+            return Err(PeerMisbehaved::IncorrectRenegotiationInfo.into());
+        }
+
         if hello.renegotiation_info.is_some()
             || hello
                 .cipher_suites
@@ -178,6 +255,7 @@ impl Tls12Extensions {
 pub(super) struct Tls13Extensions {
     pub(super) certificate_types: CertificateTypes,
     pub(super) alpn_protocol: Option<ApplicationProtocol<'static>>,
+    pub(super) application_settings: Option<Vec<u8>>,
 }
 
 impl Tls13Extensions {
@@ -225,11 +303,24 @@ impl Tls13Extensions {
             extensions.server_certificate_type = Some(expected_server_type);
         }
 
+        // ALPS is negotiated if the client offered it for the selected protocol,
+        // and we have settings for that protocol.
+        let application_settings = match (&alpn_protocol, &hello.application_settings) {
+            (Some(protocol), Some(offered)) if offered.contains(protocol) => {
+                ApplicationSettings::find(&config.application_settings, protocol)
+            }
+            _ => None,
+        };
+        if let Some(settings) = application_settings {
+            extensions.application_settings = Some(Payload::new(settings));
+        }
+
         let out = Self {
             certificate_types: CertificateTypes {
                 client: expected_client_type,
             },
             alpn_protocol,
+            application_settings: application_settings.map(|settings| settings.to_vec()),
         };
 
         Ok((out, extensions))
@@ -292,7 +383,7 @@ impl<'a> ExtensionProcessing<'a> {
             output.output(OutputEvent::ApplicationProtocol((*protocol).to_owned()));
         }
 
-        if let Some(quic) = output.quic() {
+        if let Some(quic) = output.quic().filter(|q| q.is_quic()) {
             // QUIC has strict ALPN, unlike TLS's more backwards-compatible behavior. RFC 9001
             // says: "The server MUST treat the inability to select a compatible application
             // protocol as a connection error of type 0x0178". We judge that ALPN was desired
@@ -333,6 +424,19 @@ impl<'a> ExtensionProcessing<'a> {
     }
 }
 
+/// Report that a session offered by the client cannot be resumed, and why.
+pub(super) fn resumption_rejected(
+    output: &mut dyn Output<'_>,
+    age: Option<Duration>,
+    rejection: ResumptionRejection,
+) {
+    debug!("cannot resume offered session: {rejection:?}");
+    output.observe(ConnectionEvent::ResumptionOffered {
+        age,
+        rejection: Some(rejection),
+    });
+}
+
 /// Extension values common to both TLS 1.2 ServerHello & TLS 1.3 EncryptedExtensions.
 ///
 /// These are negotiated identically for both protocols prior to placement in the
@@ -486,6 +590,8 @@ pub(crate) struct ExpectClientHello {
     pub(super) resumption_data: Vec<u8>,
     pub(super) using_ems: bool,
     pub(super) done_retry: bool,
+    /// The cookie sent in a DTLS `HelloRetryRequest`, which the client must echo.
+    pub(super) dtls_cookie: Option<[u8; 32]>,
     pub(super) send_tickets: usize,
 }
 
@@ -512,6 +618,7 @@ impl ExpectClientHello {
             resumption_data,
             using_ems: false,
             done_retry: false,
+            dtls_cookie: None,
             send_tickets: 0,
         }
     }
@@ -522,6 +629,8 @@ impl ExpectClientHello {
         input: ClientHelloInput<'_>,
         output: &mut dyn Output<'_>,
     ) -> Result<ServerState, Error> {
+        output.observe(ConnectionEvent::ClientHelloReceived);
+
         let tls13_enabled = self
             .config
             .supports_version(ProtocolVersion::TLSv1_3, self.protocol);
@@ -529,6 +638,16 @@ impl ExpectClientHello {
             .config
             .supports_version(ProtocolVersion::TLSv1_2, self.protocol);
 
+        // DTLS1.3 is the only version we support over datagrams.
+        if self.protocol.is_dtls() {
+            return match &input.client_hello.supported_versions {
+                Some(versions) if versions.dtls13 && tls13_enabled => {
+                    self.with_version::<Tls13CipherSuite>(input, output)
+                }
+                _ => Err(PeerIncompatible::Dtls13NotOffered.into()),
+            };
+        }
+
         // Are we doing TLS1.3?
         if let Some(versions) = &input.client_hello.supported_versions {
             if versions.tls13 && tls13_enabled {
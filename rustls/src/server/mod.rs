@@ -1,4 +1,5 @@
 use alloc::vec::Vec;
+use core::time::Duration;
 
 use pki_types::{DnsName, UnixTime};
 
@@ -7,6 +8,7 @@ use crate::crypto::{CipherSuite, Identity};
 use crate::enums::{ApplicationProtocol, ProtocolVersion};
 use crate::error::InvalidMessage;
 use crate::msgs::{Codec, MaybeEmpty, Reader, SessionId, SizedPayload};
+use crate::observer::ResumptionRejection;
 pub use crate::verify::NoClientAuth;
 use crate::verify::VerifiedIdentity;
 #[cfg(feature = "webpki")]
@@ -16,20 +18,24 @@ pub use crate::webpki::{
 
 pub(crate) mod config;
 pub use config::{
-    CipherSuiteSelector, ClientHello, InvalidSniPolicy, PreferClientOrder, PreferServerOrder,
-    ServerConfig, ServerCredentialResolver, StoresServerSessions, Tls13Tickets, WantsServerCert,
+    CipherSuiteSelector, ClientHello, InvalidSniPolicy, IssuedTicket, PreferClientOrder,
+    PreferServerOrder, ServerConfig, ServerCredentialResolver, SessionExpiry, StoresServerSessions,
+    TicketId, TicketRegistry, Tls13Tickets, WantsServerCert,
 };
 
 mod connection;
 pub use connection::{
-    Accepted, NeedsInput, ReadEarlyData, ServerConnection, ServerHandshake, ServerSide,
-    VerifyClientIdentity,
+    Accepted, CompleteKeyExchange, NeedsInput, ReadEarlyData, ServerConnection, ServerHandshake,
+    ServerSide, VerifyClientIdentity,
 };
 
 pub(crate) mod handy;
 #[cfg(feature = "webpki")]
 pub use handy::ServerNameResolver;
-pub use handy::{NoServerSessionStorage, ServerSessionMemoryCache};
+pub use handy::{
+    NoServerSessionStorage, RemoteSessionStore, ServerSessionMemoryCache, SessionStoreClient,
+    ShardedServerSessionCache, TicketRevocationRegistry,
+};
 
 mod hs;
 pub(crate) use hs::{
@@ -123,7 +129,19 @@ impl<'a> CommonServerSessionValue<'a> {
         }
     }
 
-    pub(crate) fn can_resume(&self, suite: CipherSuite, sni: Option<&DnsName<'_>>) -> bool {
+    /// Check whether this session, of `age` if known, can be resumed by a connection
+    /// using `suite` and `sni`, given sessions may be resumed for `lifetime`.
+    pub(crate) fn check_resume(
+        &self,
+        suite: CipherSuite,
+        sni: Option<&DnsName<'_>>,
+        age: Option<Duration>,
+        lifetime: Duration,
+    ) -> Result<(), ResumptionRejection> {
+        if age.is_some_and(|age| age > lifetime) {
+            return Err(ResumptionRejection::Expired);
+        }
+
         // The RFCs underspecify what happens if we try to resume to
         // an unoffered/varying suite.  We merely don't resume in weird cases.
         //
@@ -134,7 +152,22 @@ impl<'a> CommonServerSessionValue<'a> {
         //
         // RFC 9846: "The server MUST ensure that it selects
         // a compatible PSK (if any) and cipher suite."
-        self.cipher_suite == suite && self.sni.as_ref() == sni
+        if self.cipher_suite != suite {
+            return Err(ResumptionRejection::CipherSuiteMismatch);
+        }
+
+        match self.sni.as_ref() == sni {
+            true => Ok(()),
+            false => Err(ResumptionRejection::ServerNameMismatch),
+        }
+    }
+
+    /// How long ago this session was created, at `now`.
+    pub(crate) fn age(&self, now: UnixTime) -> Duration {
+        Duration::from_secs(
+            now.as_secs()
+                .saturating_sub(self.creation_time_sec),
+        )
     }
 }
 
@@ -52,6 +52,7 @@ fn serversessionvalue_is_debug() {
         ),
         &[1, 2, 3],
         0x12345678,
+        None,
     ));
     println!("{ssv:?}");
     println!("{:#04x?}", ssv.get_encoding());
@@ -88,6 +89,7 @@ fn serversessionvalue_with_cert() {
             ),
             &[1, 2, 3],
             0x12345678,
+            None,
         ))
         .get_encoding()
     );
@@ -496,6 +498,7 @@ fn minimal_client_hello() -> ClientHelloPayload {
             supported_versions: Some(SupportedProtocolVersions {
                 tls12: true,
                 tls13: true,
+                dtls13: false,
             }),
             key_shares: Some(vec![KeyShareEntry {
                 group: KEY_EXCHANGE_GROUP.name(),
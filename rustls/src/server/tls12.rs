@@ -5,16 +5,16 @@ use alloc::vec::Vec;
 pub(crate) use client_hello::TLS12_HANDLER;
 use pki_types::{DnsName, UnixTime};
 use subtle::ConstantTimeEq;
-use zeroize::Zeroize;
+use zeroize::{Zeroize, Zeroizing};
 
 use super::config::ServerConfig;
 use super::hs::ServerState;
-use super::{CommonServerSessionValue, ServerSessionKey, ServerSessionValue};
+use super::{CommonServerSessionValue, ServerSessionKey, ServerSessionValue, SessionExpiry};
 use crate::ConnectionTrafficSecrets;
 use crate::check::inappropriate_message;
 use crate::common_state::{Event, HandshakeFlightTls12, HandshakeKind, Output, OutputEvent, Side};
 use crate::conn::kernel::KernelState;
-use crate::conn::{ConnectionRandoms, Input};
+use crate::conn::{ConnectionRandoms, HandoffSecrets, Input};
 use crate::crypto::cipher::{EncodableVersion, MessageDecrypter, MessageEncrypter, Payload};
 use crate::crypto::kx::{ActiveKeyExchange, SupportedKxGroup};
 use crate::crypto::{Identity, TicketProducer};
@@ -75,8 +75,11 @@ mod client_hello {
         ClientSessionTicket, Compression, Random, ServerExtensionsInput, ServerHelloPayload,
         ServerKeyExchange, ServerKeyExchangeParams, ServerKeyExchangePayload,
     };
+    use crate::observer::{ConnectionEvent, ResumptionRejection};
     use crate::sealed::Sealed;
-    use crate::server::hs::{ClientHelloInput, ExpectClientHello, ServerHandler, Tls12Extensions};
+    use crate::server::hs::{
+        ClientHelloInput, ExpectClientHello, ServerHandler, Tls12Extensions, resumption_rejected,
+    };
     use crate::verify::DigitallySignedStruct;
 
     pub(crate) static TLS12_HANDLER: &dyn ServerHandler<Tls12CipherSuite> = &Handler;
@@ -143,8 +146,16 @@ mod client_hello {
                 st.using_ems,
                 suite,
                 &st.config,
+                output,
             );
 
+            if ticket_received || !input.client_hello.session_id.is_empty() {
+                output.observe(match resume_data {
+                    Some(_) => ConnectionEvent::ResumptionAccepted,
+                    None => ConnectionEvent::ResumptionRejected,
+                });
+            }
+
             if let Some(data) = resume_data {
                 let proof = input.proof;
                 return start_resumption(
@@ -201,7 +212,7 @@ mod client_hello {
             let doing_client_auth = emit_certificate_req(&mut flight, &st.config)?;
             emit_server_hello_done(&mut flight);
 
-            flight.finish(output);
+            flight.finish(output)?;
             let hs = HandshakeState {
                 config: st.config,
                 transcript,
@@ -243,58 +254,81 @@ mod client_hello {
         using_ems: bool,
         suite: &'static Tls12CipherSuite,
         config: &ServerConfig,
+        output: &mut dyn Output<'_>,
     ) -> (bool, Option<Tls12ServerSessionValue<'static>>) {
         // First, check for a ticket that decrypts
-        let (ticket, encoded) = match hello.session_ticket.as_ref() {
+        let (ticket, decrypted) = match hello.session_ticket.as_ref() {
             Some(ClientSessionTicket::Offer(ticket)) => {
                 debug!("Ticket received");
                 let data = config
                     .ticketer
-                    .as_ref()
-                    .and_then(|ticketer| ticketer.decrypt(ticket.bytes()));
-                match data {
-                    Some(data) => (true, Some(data)),
-                    None => {
-                        debug!("Ticket didn't decrypt");
-                        (true, None)
-                    }
+                    .as_deref()
+                    .and_then(|ticketer| {
+                        Some((ticketer.decrypt(ticket.bytes())?, ticketer.lifetime()))
+                    });
+                if data.is_none() {
+                    debug!("Ticket didn't decrypt");
                 }
+                (true, data)
             }
             Some(_) | None => (false, None),
         };
 
-        let (ticket, encoded) = match (ticket, encoded) {
-            (_, Some(data)) => (true, data),
+        // Tickets can be resumed without knowing the time, but then their age is unknown.
+        let now = config.current_time().ok();
+
+        let (ticket, encoded, lifetime) = match (ticket, decrypted) {
+            (_, Some((data, lifetime))) => (true, data, lifetime),
             // If we've received a ticket, the session ID won't be in our cache, so skip checking
             (false, None) if !hello.session_id.is_empty() => {
                 // Check for a session ID in our cache
+                let Some(now) = now else {
+                    return (false, None);
+                };
                 let store = &config.session_storage;
-                match store.get(ServerSessionKey::from(&hello.session_id)) {
-                    Some(data) => (false, data),
-                    None => return (false, None),
+                match store.get(ServerSessionKey::from(&hello.session_id), now) {
+                    Some(data) => (false, data, config.session_lifetime),
+                    None => {
+                        resumption_rejected(output, None, ResumptionRejection::Undecryptable);
+                        return (false, None);
+                    }
+                }
+            }
+            (ticket, None) => {
+                if ticket {
+                    resumption_rejected(output, None, ResumptionRejection::Undecryptable);
                 }
+                return (ticket, None);
             }
-            (ticket, None) => return (ticket, None),
         };
 
         // Try to parse the encoded session value
         let Ok(ServerSessionValue::Tls12(session)) = ServerSessionValue::read_bytes(&encoded)
         else {
+            resumption_rejected(output, None, ResumptionRejection::Undecryptable);
             return (ticket, None);
         };
 
         // Check that the session is compatible with the current connection
-        if !session
+        let age = now.map(|now| session.common.age(now));
+        if let Err(rejection) = session
             .common
-            .can_resume(suite.common.suite, sni)
+            .check_resume(suite.common.suite, sni, age, lifetime)
         {
+            resumption_rejected(output, age, rejection);
             return (ticket, None);
         }
 
-        match session.extended_ms == using_ems || session.extended_ms && !using_ems {
-            true => (ticket, Some(session.into_owned())),
-            false => (ticket, None),
+        if !session.extended_ms && using_ems {
+            resumption_rejected(output, age, ResumptionRejection::ExtendedMainSecretMismatch);
+            return (ticket, None);
         }
+
+        output.observe(ConnectionEvent::ResumptionOffered {
+            age,
+            rejection: None,
+        });
+        (ticket, Some(session.into_owned()))
     }
 
     fn start_resumption(
@@ -335,7 +369,7 @@ mod client_hello {
             &randoms,
             extra_exts,
         )?;
-        flight.finish(output);
+        flight.finish(output)?;
 
         let mut hs = HandshakeState {
             config,
@@ -383,7 +417,7 @@ mod client_hello {
                 )?;
             }
         }
-        emit_ccs(output);
+        emit_ccs(output)?;
 
         let (dec, encrypter) = secrets.make_cipher_pair(Side::Server);
         output.send().set_encrypter(
@@ -393,7 +427,7 @@ mod client_hello {
                 .common
                 .confidentiality_limit,
         );
-        emit_finished(&secrets, &mut hs.transcript, output, &proof);
+        emit_finished(&secrets, &mut hs.transcript, output, &proof)?;
 
         Ok(Box::new(ExpectCcs {
             hs,
@@ -943,18 +977,17 @@ fn emit_ticket(
     };
 
     transcript.add_message(&m);
-    output.send_msg(m, false);
-    Ok(())
+    output.send_msg(m, false)
 }
 
-fn emit_ccs(output: &mut dyn Output<'_>) {
+fn emit_ccs(output: &mut dyn Output<'_>) -> Result<(), Error> {
     output.send_msg(
         Message {
             version: EncodableVersion::Legacy(ProtocolVersion::TLSv1_2),
             payload: MessagePayload::ChangeCipherSpec(ChangeCipherSpecPayload {}),
         },
         false,
-    );
+    )
 }
 
 fn emit_finished(
@@ -962,7 +995,7 @@ fn emit_finished(
     transcript: &mut HandshakeHash,
     output: &mut dyn Output<'_>,
     proof: &HandshakeAlignedProof,
-) {
+) -> Result<(), Error> {
     let vh = transcript.current_hash();
     let verify_data = secrets.server_verify_data(&vh, proof);
     let verify_data_payload = Payload::Borrowed(&verify_data);
@@ -975,7 +1008,7 @@ fn emit_finished(
     };
 
     transcript.add_message(&f);
-    output.send_msg(f, true);
+    output.send_msg(f, true)
 }
 
 pub(super) struct ExpectFinished {
@@ -1015,6 +1048,7 @@ impl ExpectFinished {
 
         // Save connection, perhaps
         if !self.resuming && !self.hs.session_id.is_empty() {
+            let now = self.hs.config.current_time()?;
             let value = ServerSessionValue::from(Tls12ServerSessionValue::new(
                 CommonServerSessionValue::new(
                     self.hs.sni.as_ref(),
@@ -1022,7 +1056,7 @@ impl ExpectFinished {
                     self.peer_identity.clone(),
                     self.hs.alpn_protocol.clone(),
                     self.hs.resumption_data.to_vec(),
-                    self.hs.config.current_time()?,
+                    now,
                 ),
                 self.secrets.master_secret(),
                 self.hs.using_ems,
@@ -1031,6 +1065,7 @@ impl ExpectFinished {
             let worked = self.hs.config.session_storage.put(
                 ServerSessionKey::from(&self.hs.session_id),
                 value.get_encoding(),
+                SessionExpiry::new(now, self.hs.config.session_lifetime),
             );
             if worked {
                 debug!("Session saved");
@@ -1062,7 +1097,7 @@ impl ExpectFinished {
                     )?;
                 }
             }
-            emit_ccs(output);
+            emit_ccs(output)?;
             output.send().set_encrypter(
                 encrypter,
                 self.secrets
@@ -1070,28 +1105,23 @@ impl ExpectFinished {
                     .common
                     .confidentiality_limit,
             );
-            emit_finished(&self.secrets, &mut self.hs.transcript, output, &proof);
+            emit_finished(&self.secrets, &mut self.hs.transcript, output, &proof)?;
         }
 
         if let Some(identity) = self.peer_identity {
             output.output(OutputEvent::PeerIdentity(identity));
         }
 
-        let extracted_secrets = self
-            .hs
-            .config
-            .enable_secret_extraction
-            .then(|| {
-                self.secrets
-                    .extract_secrets(Side::Server)
-            });
-
         output.output(OutputEvent::ExtendedMainSecret(self.hs.using_ems));
-        output.output(OutputEvent::Exporter(self.secrets.into_exporter()));
+        output.output(OutputEvent::Exporter(self.secrets.exporter()));
         output.start_traffic();
 
         Ok(Box::new(ExpectTraffic {
-            extracted_secrets,
+            secrets: self
+                .hs
+                .config
+                .enable_secret_extraction
+                .then_some(self.secrets),
             _fin_verified: fin_verified,
         })
         .into())
@@ -1118,13 +1148,44 @@ struct HandshakeState {
 // --- Process traffic ---
 pub(super) struct ExpectTraffic {
     // only `Some` if `config.enable_secret_extraction` is true
-    extracted_secrets: Option<Result<PartiallyExtractedSecrets, Error>>,
+    secrets: Option<ConnectionSecrets>,
     _fin_verified: FinishedMessageVerified,
 }
 
-impl ExpectTraffic {}
-
 impl ExpectTraffic {
+    /// Resume traffic using `secrets` taken from a serialized connection.
+    pub(super) fn restore(
+        config: &ServerConfig,
+        secrets: ConnectionSecrets,
+        exporter: bool,
+        output: &mut dyn Output<'_>,
+        proof: &HandshakeAlignedProof,
+    ) -> Box<Self> {
+        let (dec, enc) = secrets.make_cipher_pair(Side::Server);
+        output
+            .receive()
+            .decrypt_state
+            .set_message_decrypter(dec, proof);
+        output.send().set_encrypter(
+            enc,
+            secrets
+                .suite()
+                .common
+                .confidentiality_limit,
+        );
+        if exporter {
+            output.output(OutputEvent::Exporter(secrets.exporter()));
+        }
+        output.start_traffic();
+
+        Box::new(Self {
+            secrets: config
+                .enable_secret_extraction
+                .then_some(secrets),
+            _fin_verified: FinishedMessageVerified::assertion(),
+        })
+    }
+
     fn handle<'m>(
         self: Box<Self>,
         Input { message, .. }: Input<'m>,
@@ -1146,8 +1207,18 @@ impl ExpectTraffic {
         mut self: Box<Self>,
         _send_keys: &Option<Box<KeyScheduleTrafficSend>>,
     ) -> Result<(PartiallyExtractedSecrets, Box<dyn KernelState + 'static>), Error> {
-        match self.extracted_secrets.take() {
-            Some(extracted_secrets) => Ok((extracted_secrets?, self)),
+        match self.secrets.take() {
+            Some(secrets) => Ok((secrets.extract_secrets(Side::Server)?, self)),
+            None => Err(ApiMisuse::SecretExtractionRequiresPriorOptIn.into()),
+        }
+    }
+
+    pub(super) fn handoff_secrets(&self) -> Result<HandoffSecrets, Error> {
+        match &self.secrets {
+            Some(secrets) => Ok(HandoffSecrets::Tls12 {
+                randoms: secrets.randoms.clone(),
+                master_secret: Zeroizing::new(*secrets.master_secret()),
+            }),
             None => Err(ApiMisuse::SecretExtractionRequiresPriorOptIn.into()),
         }
     }
@@ -3,20 +3,24 @@ use alloc::vec;
 use alloc::vec::Vec;
 use core::time::Duration;
 
-pub(crate) use client_hello::TLS13_HANDLER;
+pub(crate) use client_hello::{AwaitKeyExchange, TLS13_HANDLER};
 use pki_types::{DnsName, UnixTime};
 use subtle::ConstantTimeEq;
 use zeroize::Zeroizing;
 
 use super::config::ServerConfig;
 use super::hs::{HandshakeHashOrBuffer, ServerState};
-use super::{CommonServerSessionValue, ServerSessionKey, ServerSessionValue};
+use super::{
+    CommonServerSessionValue, IssuedTicket, ServerSessionKey, ServerSessionValue, SessionExpiry,
+    TicketId,
+};
 use crate::check::{inappropriate_handshake_message, inappropriate_message};
 use crate::common_state::{Event, HandshakeFlightTls13, HandshakeKind, Output, OutputEvent, Side};
 use crate::conn::kernel::KernelState;
-use crate::conn::{ConnectionRandoms, Input, TrafficTemperCounters};
+use crate::conn::{ConnectionRandoms, HandoffSecrets, Input, TrafficTemperCounters};
 use crate::crypto::cipher::Payload;
 use crate::crypto::kx::NamedGroup;
+use crate::crypto::tls13::OkmBlock;
 use crate::crypto::{Identity, rand};
 use crate::enums::{
     ApplicationProtocol, CertificateType, ContentType, HandshakeType, ProtocolVersion,
@@ -24,16 +28,18 @@ use crate::enums::{
 use crate::error::{ApiMisuse, Error, InvalidMessage, PeerIncompatible, PeerMisbehaved};
 use crate::hash_hs::HandshakeHash;
 use crate::msgs::{
-    CERTIFICATE_MAX_SIZE_LIMIT, CertificatePayloadTls13, Codec, HandshakeMessagePayload,
-    HandshakePayload, KeyUpdateRequest, Message, MessagePayload, NewSessionTicketPayloadTls13,
-    PresharedKeyIdentity, Reader, ServerTicketRequestHint, SizedPayload,
+    CERTIFICATE_MAX_SIZE_LIMIT, CertificatePayloadTls13, Codec, ExtensionType,
+    HandshakeAlignedProof, HandshakeMessagePayload, HandshakePayload, KeyUpdateRequest, MaybeEmpty,
+    Message, MessagePayload, NewSessionTicketPayloadTls13, PresharedKeyIdentity, Reader,
+    ServerTicketRequestHint, SizedPayload,
 };
+use crate::observer::ConnectionEvent;
 use crate::server::hs::{ExpectClientHello, VerifyClientIdentity, VerifyClientIdentityInternal};
 use crate::suites::PartiallyExtractedSecrets;
 use crate::sync::Arc;
 use crate::tls13::key_schedule::{
-    KeyScheduleResumption, KeyScheduleTrafficReceive, KeyScheduleTrafficSend,
-    KeyScheduleTrafficWithClientFinishedPending,
+    KeyScheduleExporter, KeyScheduleResumption, KeyScheduleTraffic, KeyScheduleTrafficReceive,
+    KeyScheduleTrafficSend, KeyScheduleTrafficWithClientFinishedPending,
 };
 use crate::tls13::{
     Tls13CipherSuite, construct_client_verify_message, construct_server_verify_message,
@@ -47,6 +53,7 @@ use crate::{ConnectionTrafficSecrets, compress};
 #[expect(private_interfaces)]
 pub(crate) enum Tls13State {
     SkipRejectedEarlyData(Box<ExpectAndSkipRejectedEarlyData>),
+    ClientEncryptedExtensions(Box<ExpectClientEncryptedExtensions>),
     CertificateOrCompressedCertificate(Box<ExpectCertificateOrCompressedCertificate>),
     Certificate(Box<ExpectCertificate>),
     CertificateVerify(Box<ExpectCertificateVerify>),
@@ -64,6 +71,7 @@ impl Tls13State {
     ) -> Result<ServerState, Error> {
         match self {
             Self::SkipRejectedEarlyData(e) => e.handle(input, output),
+            Self::ClientEncryptedExtensions(e) => e.handle(input, output),
             Self::CertificateOrCompressedCertificate(e) => e.handle(input, output),
             Self::Certificate(e) => e.handle(input, output),
             Self::CertificateVerify(e) => e.handle(input, output),
@@ -80,7 +88,7 @@ mod client_hello {
     use crate::common_state::{EarlyDataEvent, Protocol};
     use crate::compress::CertCompressor;
     use crate::crypto::cipher::{EncodableVersion, Payload};
-    use crate::crypto::kx::SupportedKxGroup;
+    use crate::crypto::kx::{CompletedKeyExchange, SupportedKxGroup};
     use crate::crypto::{SelectedCredential, Signer};
     use crate::enums::ApplicationProtocol;
     use crate::msgs::{
@@ -89,10 +97,12 @@ mod client_hello {
         HelloRetryRequest, HelloRetryRequestExtensions, KeyShareEntry, Random, ServerExtensions,
         ServerExtensionsInput, ServerHelloPayload, SessionId, SizedPayload,
     };
-    use crate::quic;
+    use crate::observer::{ConnectionEvent, ResumptionRejection};
     use crate::sealed::Sealed;
     use crate::server::Tls13ServerSessionValue;
-    use crate::server::hs::{ClientHelloInput, ExpectClientHello, ServerHandler, Tls13Extensions};
+    use crate::server::hs::{
+        ClientHelloInput, ExpectClientHello, ServerHandler, Tls13Extensions, resumption_rejected,
+    };
     use crate::tls13::Tls13ProtocolSuite;
     use crate::tls13::key_schedule::{
         KeyScheduleEarlyServer, KeyScheduleHandshake, KeySchedulePreHandshake,
@@ -171,14 +181,33 @@ mod client_hello {
                 return Err(PeerMisbehaved::EarlyDataAttemptedInSecondClientHello.into());
             }
 
+            if let Some(cookie) = &st.dtls_cookie {
+                let echoed = input
+                    .client_hello
+                    .cookie
+                    .as_ref()
+                    .map(|c| c.bytes());
+                if echoed != Some(&cookie[..]) {
+                    return Err(PeerMisbehaved::IncorrectCookieInSecondClientHello.into());
+                }
+            }
+
             // See if there is a KeyShare for the selected kx group.
             let chosen_share_and_kxg = shares_ext
                 .iter()
                 .find_map(|share| (share.group == kx_group.name()).then_some((share, kx_group)));
 
-            let Some(chosen_share_and_kxg) = chosen_share_and_kxg else {
-                // We don't have a suitable key share.  Send a HelloRetryRequest
-                // for the mutually_preferred_group.
+            // DTLS servers check the client can receive at its claimed address before
+            // sending their much larger first flight.
+            // <https://www.rfc-editor.org/rfc/rfc9147#section-5.1>
+            let dtls_cookie = match st.protocol.is_dtls() && !st.done_retry {
+                true => Some(rand::random_array::<32>(st.config.provider.secure_random)?),
+                false => None,
+            };
+
+            let (Some(chosen_share_and_kxg), None) = (chosen_share_and_kxg, dtls_cookie) else {
+                // We don't have a suitable key share (or need a cookie).  Send a
+                // HelloRetryRequest for the mutually_preferred_group.
                 transcript.add_message(input.message);
 
                 if st.done_retry {
@@ -189,11 +218,15 @@ mod client_hello {
                     &mut transcript,
                     suite,
                     input.client_hello.session_id,
+                    st.protocol,
                     output,
-                    kx_group.name(),
-                );
-                if !st.protocol.is_quic() {
-                    emit_fake_ccs(output);
+                    chosen_share_and_kxg
+                        .is_none()
+                        .then(|| kx_group.name()),
+                    dtls_cookie.as_ref(),
+                )?;
+                if !st.protocol.is_datagram() {
+                    emit_fake_ccs(output)?;
                 }
 
                 let skip_early_data = max_early_data_size(st.config.max_early_data_size);
@@ -203,9 +236,11 @@ mod client_hello {
                     session_id: SessionId::empty(),
                     using_ems: false,
                     done_retry: true,
+                    dtls_cookie,
                     ..st
                 });
                 return if early_data_requested {
+                    output.observe(ConnectionEvent::EarlyDataRejected);
                     Ok(Box::new(ExpectAndSkipRejectedEarlyData {
                         skip_data_left: skip_early_data,
                         next,
@@ -216,18 +251,16 @@ mod client_hello {
                 };
             };
 
-            let suite = match st.protocol {
-                Protocol::Tcp => Tls13ProtocolSuite::Tcp(suite),
-                Protocol::Quic(_) => Tls13ProtocolSuite::Quic(quic::Suite {
-                    inner: suite,
-                    quic: suite
-                        .quic
-                        .ok_or_else(|| Error::from(ApiMisuse::NoQuicCompatibleCipherSuites))?,
-                }),
-            };
+            let suite = Tls13ProtocolSuite::new(suite, st.protocol)?;
 
-            let mut resuming =
-                handle_psk_offer(&input, &transcript, st.sni.as_ref(), suite, &st.config)?;
+            let mut resuming = handle_psk_offer(
+                &input,
+                &transcript,
+                st.sni.as_ref(),
+                suite,
+                &st.config,
+                output,
+            )?;
 
             if !input
                 .client_hello
@@ -249,6 +282,17 @@ mod client_hello {
                 );
             }
 
+            if input
+                .client_hello
+                .preshared_key_offer
+                .is_some()
+            {
+                output.observe(match resuming {
+                    Some(_) => ConnectionEvent::ResumptionAccepted,
+                    None => ConnectionEvent::ResumptionRejected,
+                });
+            }
+
             if let Some((_, session)) = &resuming {
                 output.emit(Event::ResumptionData(
                     session
@@ -259,25 +303,159 @@ mod client_hello {
                 ));
             }
 
-            let full_handshake = resuming.is_none();
             transcript.add_message(input.message);
+
+            let (share, kx_group) = chosen_share_and_kxg;
+            debug_assert_eq!(kx_group.name(), share.group);
+            let server_hello = EmitServerHello {
+                config: st.config,
+                protocol: st.protocol,
+                extra_exts: st.extra_exts,
+                sni: st.sni,
+                resumption_data: st.resumption_data,
+                done_retry: st.done_retry,
+                send_tickets: st.send_tickets,
+                transcript,
+                randoms,
+                suite,
+                kx_group,
+                signer,
+                cert_compressor,
+                resuming,
+                proof: input.proof,
+            };
+
+            if kx_group.defers_completion() && !server_hello.protocol.is_datagram() {
+                return Ok(ServerState::CompleteKeyExchange(Box::new(
+                    AwaitKeyExchange {
+                        server_hello,
+                        client_hello: input.client_hello.clone(),
+                        peer_key_share: share.payload.bytes().to_vec(),
+                    },
+                )));
+            }
+
+            let ckx = kx_group.start_and_complete(share.payload.bytes())?;
+            server_hello.emit(input.client_hello, ckx, output)
+        }
+    }
+
+    impl Sealed for Handler {}
+
+    /// A TLS1.3 key exchange awaiting completion outside the library.
+    ///
+    /// This is entered instead of calling [`SupportedKxGroup::start_and_complete()`] if the
+    /// negotiated group [defers completion](SupportedKxGroup::defers_completion).
+    pub(crate) struct AwaitKeyExchange {
+        server_hello: EmitServerHello,
+        client_hello: ClientHelloPayload,
+        peer_key_share: Vec<u8>,
+    }
+
+    impl AwaitKeyExchange {
+        pub(crate) fn group(&self) -> NamedGroup {
+            self.server_hello.kx_group.name()
+        }
+
+        pub(crate) fn peer_key_share(&self) -> &[u8] {
+            &self.peer_key_share
+        }
+
+        /// Progress the handshake by completing the key exchange synchronously.
+        pub(crate) fn use_kx_group(
+            self: Box<Self>,
+            output: &mut dyn Output<'_>,
+        ) -> Result<ServerState, Error> {
+            let ckx = self
+                .server_hello
+                .kx_group
+                .start_and_complete(&self.peer_key_share)?;
+            self.continue_with(ckx, output)
+        }
+
+        /// Progress the handshake by incorporating an externally completed key exchange.
+        pub(crate) fn continue_with(
+            self: Box<Self>,
+            ckx: CompletedKeyExchange,
+            output: &mut dyn Output<'_>,
+        ) -> Result<ServerState, Error> {
+            if ckx.group != self.group() {
+                return Err(ApiMisuse::CompletedKeyExchangeGroupMismatch.into());
+            }
+
+            let Self {
+                server_hello,
+                client_hello,
+                ..
+            } = *self;
+            server_hello.emit(&client_hello, ckx, output)
+        }
+    }
+
+    /// Everything decided from the `ClientHello`, pending only the key exchange.
+    struct EmitServerHello {
+        config: Arc<ServerConfig>,
+        protocol: Protocol,
+        extra_exts: ServerExtensionsInput,
+        sni: Option<DnsName<'static>>,
+        resumption_data: Vec<u8>,
+        done_retry: bool,
+        send_tickets: usize,
+        transcript: HandshakeHash,
+        randoms: ConnectionRandoms,
+        suite: Tls13ProtocolSuite,
+        kx_group: &'static dyn SupportedKxGroup,
+        signer: SelectedCredential,
+        cert_compressor: Option<&'static dyn CertCompressor>,
+        resuming: Option<(usize, Tls13ServerSessionValue<'static>)>,
+        proof: HandshakeAlignedProof,
+    }
+
+    impl EmitServerHello {
+        fn emit(
+            self,
+            client_hello: &ClientHelloPayload,
+            ckx: CompletedKeyExchange,
+            output: &mut dyn Output<'_>,
+        ) -> Result<ServerState, Error> {
+            let Self {
+                config,
+                protocol,
+                extra_exts,
+                sni,
+                resumption_data,
+                done_retry,
+                send_tickets,
+                mut transcript,
+                randoms,
+                suite,
+                kx_group,
+                signer,
+                cert_compressor,
+                resuming,
+                proof,
+            } = self;
+
+            let full_handshake = resuming.is_none();
             let key_schedule = emit_server_hello(
                 &mut transcript,
                 &randoms,
                 suite,
+                protocol,
                 output,
-                &input.client_hello.session_id,
-                chosen_share_and_kxg,
+                &client_hello.session_id,
+                ckx,
+                kx_group,
                 resuming.as_ref(),
-                &input.proof,
-                &st.config,
+                &proof,
+                &config,
             )?;
-            if !st.done_retry && !st.protocol.is_quic() {
-                emit_fake_ccs(output);
+            if !done_retry && !protocol.is_datagram() {
+                emit_fake_ccs(output)?;
             }
 
             output.output(OutputEvent::HandshakeKind(
-                match (full_handshake, st.done_retry) {
+                match (full_handshake, done_retry) {
                     (true, true) => HandshakeKind::FullWithHelloRetryRequest,
                     (true, false) => HandshakeKind::Full,
                     (false, true) => HandshakeKind::ResumedWithHelloRetryRequest,
@@ -291,6 +469,7 @@ mod client_hello {
                 Tls13Extensions {
                     certificate_types,
                     alpn_protocol,
+                    application_settings,
                 },
                 doing_early_data,
             ) = emit_encrypted_extensions(
@@ -298,22 +477,22 @@ mod client_hello {
                 suite.suite(),
                 output,
                 &mut ocsp_response,
-                input.client_hello,
+                client_hello,
                 resuming
                     .as_ref()
                     .map(|(_, session)| session),
-                st.extra_exts,
-                &st.config,
-                st.send_tickets,
+                extra_exts,
+                &config,
+                send_tickets,
             )?;
 
             let doing_client_auth = if full_handshake {
-                let client_auth = emit_certificate_req_tls13(&mut flight, &st.config)?;
+                let client_auth = emit_certificate_req_tls13(&mut flight, &config)?;
 
                 if let Some(compressor) = cert_compressor {
                     emit_compressed_certificate_tls13(
                         &mut flight,
-                        &st.config,
+                        &config,
                         &signer,
                         ocsp_response,
                         compressor,
@@ -337,33 +516,29 @@ mod client_hello {
             // are encrypted with the handshake keys.
             match doing_early_data {
                 EarlyDataDecision::Disabled => {
-                    key_schedule.set_handshake_decrypter(None, output.receive(), &input.proof);
+                    key_schedule.set_handshake_decrypter(None, output.receive(), &proof);
                 }
                 EarlyDataDecision::RequestedButRejected => {
                     debug!(
                         "Client requested early_data, but not accepted: switching to handshake keys with trial decryption"
                     );
                     key_schedule.set_handshake_decrypter(
-                        Some(max_early_data_size(st.config.max_early_data_size)),
+                        Some(max_early_data_size(config.max_early_data_size)),
                         output.receive(),
-                        &input.proof,
+                        &proof,
                     );
+                    output.observe(ConnectionEvent::EarlyDataRejected);
                 }
                 EarlyDataDecision::Accepted { .. } => {
                     output.emit(Event::EarlyData(EarlyDataEvent::Accepted));
+                    output.observe(ConnectionEvent::EarlyDataAccepted);
                 }
             }
 
-            let key_schedule_traffic = emit_finished_tls13(
-                flight,
-                &randoms,
-                output,
-                key_schedule,
-                &st.config,
-                &input.proof,
-            );
+            let key_schedule_traffic =
+                emit_finished_tls13(flight, &randoms, output, key_schedule, &config, &proof)?;
 
-            if !doing_client_auth && st.config.send_half_rtt_data {
+            if !doing_client_auth && config.send_half_rtt_data {
                 // Application data can be sent immediately after Finished, in one
                 // flight.  However, if client auth is enabled, we don't want to send
                 // application data to an unauthenticated peer.
@@ -371,33 +546,24 @@ mod client_hello {
             }
 
             let hs = HandshakeState {
-                config: st.config,
+                config,
                 transcript,
                 suite: suite.suite(),
                 alpn_protocol,
-                sni: st.sni,
-                resumption_data: st.resumption_data,
-                send_tickets: st.send_tickets,
+                application_settings,
+                sni,
+                resumption_data,
+                send_tickets,
             };
 
             if doing_client_auth {
-                if hs.config.cert_decompressors.is_empty() {
-                    Ok(Box::new(ExpectCertificate {
-                        hs,
-                        key_schedule: key_schedule_traffic,
-                        expected_certificate_type: certificate_types.client,
-                    })
-                    .into())
-                } else {
-                    Ok(Box::new(ExpectCertificateOrCompressedCertificate {
-                        hs,
-                        key_schedule: key_schedule_traffic,
-                        expected_certificate_type: certificate_types.client,
-                    })
-                    .into())
-                }
+                Ok(ExpectClientEncryptedExtensions::or_next(
+                    hs,
+                    key_schedule_traffic,
+                    AfterClientEncryptedExtensions::Certificate(certificate_types.client),
+                ))
             } else if matches!(doing_early_data, EarlyDataDecision::Accepted { .. })
-                && !st.protocol.is_quic()
+                && !protocol.is_datagram()
             {
                 let EarlyDataDecision::Accepted { max_length } = doing_early_data else {
                     unreachable!();
@@ -405,6 +571,8 @@ mod client_hello {
                 // Not used for QUIC: RFC 9001 §8.3: Clients MUST NOT send the EndOfEarlyData
                 // message. A server MUST treat receipt of a CRYPTO frame in a 0-RTT packet as a
                 // connection error of type PROTOCOL_VIOLATION.
+                //
+                // Nor for DTLS: RFC 9147 §5.6 omits the EndOfEarlyData message.
                 Ok(Box::new(ExpectEarlyData {
                     hs,
                     key_schedule: key_schedule_traffic,
@@ -413,18 +581,17 @@ mod client_hello {
                 })
                 .into())
             } else {
-                Ok(Box::new(ExpectFinished {
+                Ok(ExpectClientEncryptedExtensions::or_next(
                     hs,
-                    key_schedule: key_schedule_traffic,
-                    peer_identity: resuming.and_then(|(_, session)| session.common.peer_identity),
-                })
-                .into())
+                    key_schedule_traffic,
+                    AfterClientEncryptedExtensions::Finished(
+                        resuming.and_then(|(_, session)| session.common.peer_identity),
+                    ),
+                ))
             }
         }
     }
 
-    impl Sealed for Handler {}
-
     #[derive(PartialEq)]
     pub(super) enum EarlyDataDecision {
         Disabled,
@@ -453,6 +620,7 @@ mod client_hello {
         sni: Option<&DnsName<'_>>,
         suite: Tls13ProtocolSuite,
         config: &ServerConfig,
+        output: &mut dyn Output<'_>,
     ) -> Result<Option<(usize, Tls13ServerSessionValue<'static>)>, Error> {
         let Some(psk_offer) = &input.client_hello.preshared_key_offer else {
             return Ok(None);
@@ -480,15 +648,21 @@ mod client_hello {
 
         let now = config.current_time()?;
         for (i, psk_id) in psk_offer.identities.iter().enumerate() {
-            let Some(mut session) = Tls13ServerSessionValue::from_ticket(psk_id, config) else {
+            let Some((mut session, lifetime)) =
+                Tls13ServerSessionValue::from_ticket(psk_id, config)
+            else {
+                resumption_rejected(output, None, ResumptionRejection::Undecryptable);
                 continue;
             };
 
+            let age = Some(session.common.age(now));
             session.set_freshness(psk_id.obfuscated_ticket_age, now);
-            if !session
-                .common
-                .can_resume(suite.suite().common.suite, sni)
+            if let Err(rejection) =
+                session
+                    .common
+                    .check_resume(suite.suite().common.suite, sni, age, lifetime)
             {
+                resumption_rejected(output, age, rejection);
                 continue;
             }
 
@@ -501,6 +675,18 @@ mod client_hello {
                 return Err(PeerMisbehaved::IncorrectBinder.into());
             }
 
+            if let Some(registry) = &config.ticket_registry {
+                let id = TicketId::new(suite.suite().common.hash_provider, psk_id.identity.bytes());
+                if !registry.redeem(&id, now) {
+                    resumption_rejected(output, age, ResumptionRejection::Revoked);
+                    continue;
+                }
+            }
+
+            output.observe(ConnectionEvent::ResumptionOffered {
+                age,
+                rejection: None,
+            });
             return Ok(Some((i, session.into_owned())));
         }
 
@@ -533,23 +719,21 @@ mod client_hello {
         transcript: &mut HandshakeHash,
         randoms: &ConnectionRandoms,
         suite: Tls13ProtocolSuite,
+        protocol: Protocol,
         output: &mut dyn Output<'_>,
         session_id: &SessionId,
-        share_and_kxgroup: (&KeyShareEntry, &'static dyn SupportedKxGroup),
+        ckx: CompletedKeyExchange,
+        kxgroup: &'static dyn SupportedKxGroup,
         resuming: Option<&(usize, Tls13ServerSessionValue<'_>)>,
         proof: &HandshakeAlignedProof,
         config: &ServerConfig,
     ) -> Result<KeyScheduleHandshake, Error> {
-        // Prepare key exchange; the caller already found the matching SupportedKxGroup
-        let (share, kxgroup) = share_and_kxgroup;
-        debug_assert_eq!(kxgroup.name(), share.group);
-        let ckx = kxgroup.start_and_complete(share.payload.bytes())?;
         output.output(OutputEvent::KeyExchangeGroup(kxgroup));
 
         let extensions = Box::new(ServerExtensions {
             key_share: Some(KeyShareEntry::new(ckx.group, ckx.pub_key)),
             preshared_key: resuming.map(|&(idx, _)| idx as u16),
-            selected_version: Some(ProtocolVersion::TLSv1_3),
+            selected_version: Some(protocol.tls13_version()),
             ..Default::default()
         });
 
@@ -557,7 +741,7 @@ mod client_hello {
             version: EncodableVersion::Legacy(ProtocolVersion::TLSv1_2),
             payload: MessagePayload::handshake(HandshakeMessagePayload(
                 HandshakePayload::ServerHello(ServerHelloPayload {
-                    legacy_version: ProtocolVersion::TLSv1_2,
+                    legacy_version: protocol.legacy_version(),
                     random: Random::from(randoms.server),
                     session_id: *session_id,
                     cipher_suite: suite.suite().common.suite,
@@ -571,7 +755,7 @@ mod client_hello {
 
         trace!("sending server hello {sh:?}");
         transcript.add_message(&sh);
-        output.send_msg(sh, false);
+        output.send_msg(sh, false)?;
 
         // Start key schedule
         let key_schedule_pre_handshake = if let Some((_, psk)) = resuming {
@@ -613,28 +797,31 @@ mod client_hello {
         Ok(key_schedule)
     }
 
-    fn emit_fake_ccs(output: &mut dyn Output<'_>) {
+    fn emit_fake_ccs(output: &mut dyn Output<'_>) -> Result<(), Error> {
         let m = Message {
             version: EncodableVersion::Legacy(ProtocolVersion::TLSv1_3),
             payload: MessagePayload::ChangeCipherSpec(ChangeCipherSpecPayload {}),
         };
-        output.send_msg(m, false);
+        output.send_msg(m, false)
     }
 
     fn emit_hello_retry_request(
         transcript: &mut HandshakeHash,
         suite: &'static Tls13CipherSuite,
         session_id: SessionId,
+        protocol: Protocol,
         output: &mut dyn Output<'_>,
-        group: NamedGroup,
-    ) {
+        group: Option<NamedGroup>,
+        cookie: Option<&[u8; 32]>,
+    ) -> Result<(), Error> {
         let req = HelloRetryRequest {
-            legacy_version: ProtocolVersion::TLSv1_2,
+            legacy_version: protocol.legacy_version(),
             session_id,
             cipher_suite: suite.common.suite,
             extensions: HelloRetryRequestExtensions {
-                key_share: Some(group),
-                supported_versions: Some(ProtocolVersion::TLSv1_3),
+                key_share: group,
+                cookie: cookie.map(|c| SizedPayload::from(c.to_vec())),
+                supported_versions: Some(protocol.tls13_version()),
                 ..Default::default()
             },
         };
@@ -649,7 +836,9 @@ mod client_hello {
         trace!("Requesting retry {m:?}");
         transcript.rollup_for_hrr();
         transcript.add_message(&m);
-        output.send_msg(m, false);
+        output.send_msg(m, false)?;
+        output.observe(ConnectionEvent::HelloRetryRequest);
+        Ok(())
     }
 
     fn decide_if_early_data_allowed(
@@ -657,6 +846,7 @@ mod client_hello {
         client_hello: &ClientHelloPayload,
         resumedata: Option<&Tls13ServerSessionValue<'_>>,
         chosen_alpn_protocol: Option<&ApplicationProtocol<'_>>,
+        application_settings: Option<&[u8]>,
         suite: &'static Tls13CipherSuite,
         config: &ServerConfig,
     ) -> EarlyDataDecision {
@@ -692,11 +882,19 @@ mod client_hello {
          *  - The selected cipher suite
          *  - The selected ALPN [RFC 7301] protocol, if any"
          *
-         * (RFC 9846, section 4.3.10) */
+         * (RFC 9846, section 4.3.10)
+         *
+         * The client sent its early data assuming our previous ALPS settings, so
+         * those must be unchanged too. */
         let early_data_possible = early_data_requested
             && resume.is_fresh()
             && resume.common.cipher_suite == suite.common.suite
-            && resume.common.alpn.as_ref() == chosen_alpn_protocol;
+            && resume.common.alpn.as_ref() == chosen_alpn_protocol
+            && resume
+                .application_settings
+                .as_ref()
+                .map(|settings| settings.bytes())
+                == application_settings;
 
         if early_data_configured && early_data_possible {
             EarlyDataDecision::Accepted {
@@ -742,6 +940,7 @@ mod client_hello {
             hello,
             resumedata,
             out.alpn_protocol.as_ref(),
+            out.application_settings.as_deref(),
             suite,
             config,
         );
@@ -856,7 +1055,7 @@ mod client_hello {
         key_schedule: KeyScheduleHandshake,
         config: &ServerConfig,
         proof: &HandshakeAlignedProof,
-    ) -> KeyScheduleTrafficWithClientFinishedPending {
+    ) -> Result<KeyScheduleTrafficWithClientFinishedPending, Error> {
         let handshake_hash = flight.transcript.current_hash();
         let verify_data = key_schedule.sign_server_finish(&handshake_hash, proof);
         let verify_data_payload = Payload::new(verify_data.as_ref());
@@ -866,16 +1065,16 @@ mod client_hello {
         trace!("sending finished {fin:?}");
         flight.add(fin);
         let hash_at_server_fin = flight.transcript.current_hash();
-        flight.finish(output);
+        flight.finish(output)?;
 
         // Now move to application data keys.  Read key change is deferred until
         // the Finish message is received & validated.
-        key_schedule.into_traffic_with_client_finished_pending(
+        Ok(key_schedule.into_traffic_with_client_finished_pending(
             hash_at_server_fin,
             &*config.key_log,
             &randoms.client,
             output,
-        )
+        ))
     }
 }
 
@@ -911,6 +1110,107 @@ impl From<Box<ExpectAndSkipRejectedEarlyData>> for ServerState {
     }
 }
 
+// --- Process the client's EncryptedExtensions, sent if ALPS was negotiated ---
+struct ExpectClientEncryptedExtensions {
+    hs: HandshakeState,
+    key_schedule: KeyScheduleTrafficWithClientFinishedPending,
+    next: AfterClientEncryptedExtensions,
+}
+
+impl ExpectClientEncryptedExtensions {
+    /// Expect the client's EncryptedExtensions before `next`, if ALPS was negotiated.
+    fn or_next(
+        hs: HandshakeState,
+        key_schedule: KeyScheduleTrafficWithClientFinishedPending,
+        next: AfterClientEncryptedExtensions,
+    ) -> ServerState {
+        match hs.application_settings {
+            Some(_) => Box::new(Self {
+                hs,
+                key_schedule,
+                next,
+            })
+            .into(),
+            None => next.into_state(hs, key_schedule),
+        }
+    }
+
+    fn handle(
+        mut self: Box<Self>,
+        Input { message, .. }: Input<'_>,
+        output: &mut dyn Output<'_>,
+    ) -> Result<ServerState, Error> {
+        let exts = require_handshake_msg!(
+            message,
+            HandshakeType::EncryptedExtensions,
+            HandshakePayload::EncryptedExtensions
+        )?;
+
+        if !exts.only_contains(&[ExtensionType::ApplicationSettings]) {
+            return Err(PeerMisbehaved::UnsolicitedEncryptedExtension.into());
+        }
+
+        let Some(settings) = &exts.application_settings else {
+            return Err(PeerMisbehaved::MissingApplicationSettings.into());
+        };
+
+        output.output(OutputEvent::PeerApplicationSettings(Some(
+            settings.bytes().to_vec(),
+        )));
+        self.hs.transcript.add_message(&message);
+        Ok(self
+            .next
+            .into_state(self.hs, self.key_schedule))
+    }
+}
+
+impl From<Box<ExpectClientEncryptedExtensions>> for ServerState {
+    fn from(value: Box<ExpectClientEncryptedExtensions>) -> Self {
+        Self::Tls13(Tls13State::ClientEncryptedExtensions(value))
+    }
+}
+
+/// What follows the client's EncryptedExtensions.
+enum AfterClientEncryptedExtensions {
+    /// The client's certificate, of the given type.
+    Certificate(CertificateType),
+    /// The client's Finished, authenticating the given (resumed) identity.
+    Finished(Option<VerifiedIdentity<'static>>),
+}
+
+impl AfterClientEncryptedExtensions {
+    fn into_state(
+        self,
+        hs: HandshakeState,
+        key_schedule: KeyScheduleTrafficWithClientFinishedPending,
+    ) -> ServerState {
+        match self {
+            Self::Certificate(expected_certificate_type) => {
+                match hs.config.cert_decompressors.is_empty() {
+                    true => Box::new(ExpectCertificate {
+                        hs,
+                        key_schedule,
+                        expected_certificate_type,
+                    })
+                    .into(),
+                    false => Box::new(ExpectCertificateOrCompressedCertificate {
+                        hs,
+                        key_schedule,
+                        expected_certificate_type,
+                    })
+                    .into(),
+                }
+            }
+            Self::Finished(peer_identity) => Box::new(ExpectFinished {
+                hs,
+                key_schedule,
+                peer_identity,
+            })
+            .into(),
+        }
+    }
+}
+
 struct ExpectCertificateOrCompressedCertificate {
     hs: HandshakeState,
     key_schedule: KeyScheduleTrafficWithClientFinishedPending,
@@ -1228,12 +1528,11 @@ impl ExpectEarlyData {
                 self.hs
                     .transcript
                     .add_message(&input.message);
-                Ok(Box::new(ExpectFinished {
-                    hs: self.hs,
-                    key_schedule: self.key_schedule,
-                    peer_identity: self.peer_identity,
-                })
-                .into())
+                Ok(ExpectClientEncryptedExtensions::or_next(
+                    self.hs,
+                    self.key_schedule,
+                    AfterClientEncryptedExtensions::Finished(self.peer_identity),
+                ))
             }
             payload => Err(inappropriate_handshake_message(
                 &payload,
@@ -1255,39 +1554,49 @@ pub(crate) struct Tls13ServerSessionValue<'a> {
     common: CommonServerSessionValue<'a>,
     secret: ZeroizingCow<'a>,
     age_obfuscation_offset: u32,
+    /// Our ALPS settings, if negotiated. Encoded last, and only if present.
+    application_settings: Option<SizedPayload<'a, u16, MaybeEmpty>>,
 
     // not encoded vv
     freshness: Option<bool>,
 }
 
 impl<'a> Tls13ServerSessionValue<'a> {
+    /// Recover the session from a ticket, along with the ticket's lifetime.
     fn from_ticket(
         id: &PresharedKeyIdentity,
         config: &ServerConfig,
-    ) -> Option<Tls13ServerSessionValue<'static>> {
-        let plain = match config.ticketer.as_deref() {
-            Some(ticketer) => ticketer.decrypt(id.identity.bytes())?,
-            None => config
-                .session_storage
-                .take(ServerSessionKey::new(id.identity.bytes()))?,
+    ) -> Option<(Tls13ServerSessionValue<'static>, Duration)> {
+        let (plain, lifetime) = match config.ticketer.as_deref() {
+            Some(ticketer) => (ticketer.decrypt(id.identity.bytes())?, ticketer.lifetime()),
+            None => (
+                config.session_storage.take(
+                    ServerSessionKey::new(id.identity.bytes()),
+                    config.current_time().ok()?,
+                )?,
+                config.session_lifetime,
+            ),
         };
 
         let Ok(ServerSessionValue::Tls13(tls13)) = ServerSessionValue::read_bytes(&plain) else {
             return None;
         };
 
-        Some(tls13.into_owned())
+        Some((tls13.into_owned(), lifetime))
     }
 
     pub(super) fn new(
         common: CommonServerSessionValue<'a>,
         secret: &'a [u8],
         age_obfuscation_offset: u32,
+        application_settings: Option<&'a [u8]>,
     ) -> Self {
         Self {
             common,
             secret: ZeroizingCow::Borrowed(SizedPayload::from(Payload::Borrowed(secret))),
             age_obfuscation_offset,
+            application_settings: application_settings
+                .map(|settings| SizedPayload::from(Payload::Borrowed(settings))),
             freshness: None,
         }
     }
@@ -1300,6 +1609,9 @@ impl<'a> Tls13ServerSessionValue<'a> {
                 ZeroizingCow::Owned(o) => o,
             }),
             age_obfuscation_offset: self.age_obfuscation_offset,
+            application_settings: self
+                .application_settings
+                .map(|settings| settings.into_owned()),
             freshness: self.freshness,
         }
     }
@@ -1326,6 +1638,9 @@ impl<'a> Codec<'a> for Tls13ServerSessionValue<'a> {
         self.secret.encode(bytes);
         self.age_obfuscation_offset
             .encode(bytes);
+        if let Some(settings) = &self.application_settings {
+            settings.encode(bytes);
+        }
     }
 
     fn read(r: &mut Reader<'a>) -> Result<Self, InvalidMessage> {
@@ -1333,6 +1648,10 @@ impl<'a> Codec<'a> for Tls13ServerSessionValue<'a> {
             common: CommonServerSessionValue::read(r)?,
             secret: ZeroizingCow::read(r)?,
             age_obfuscation_offset: u32::read(r)?,
+            application_settings: match r.any_left() {
+                true => Some(SizedPayload::read(r)?),
+                false => None,
+            },
             freshness: None,
         })
     }
@@ -1384,6 +1703,7 @@ impl ExpectFinished {
         suite: &'static Tls13CipherSuite,
         peer_identity: Option<VerifiedIdentity<'static>>,
         chosen_alpn_protocol: Option<ApplicationProtocol<'static>>,
+        application_settings: Option<&[u8]>,
         sni: Option<DnsName<'static>>,
         resumption_data: &[u8],
         resumption: &KeyScheduleResumption,
@@ -1399,13 +1719,14 @@ impl ExpectFinished {
             CommonServerSessionValue::new(
                 sni.as_ref(),
                 suite.common.suite,
-                peer_identity,
+                peer_identity.clone(),
                 chosen_alpn_protocol,
                 resumption_data.to_vec(),
                 now,
             ),
             secret.as_ref(),
             age_add,
+            application_settings,
         ))
         .get_encoding();
 
@@ -1417,16 +1738,33 @@ impl ExpectFinished {
             (ticket, ticketer.lifetime())
         } else {
             let id = rand::random_array::<32>(secure_random)?;
-            let stored = config
-                .session_storage
-                .put(ServerSessionKey::new(&id), plain);
-            if !stored {
-                trace!("resumption not available; not issuing ticket");
+            (id.to_vec(), config.session_lifetime)
+        };
+        let expiry = SessionExpiry::new(now, lifetime);
+
+        // Register before storing, so a declined ticket never leaves a resumable
+        // session behind in `session_storage`.
+        if let Some(registry) = &config.ticket_registry {
+            let registered = registry.register(&IssuedTicket {
+                id: TicketId::new(suite.common.hash_provider, &ticket),
+                peer_identity: peer_identity.as_deref(),
+                server_name: sni.as_ref(),
+                expiry,
+            });
+            if !registered {
+                trace!("ticket registry declined ticket; not issuing it");
                 return Ok(());
             }
-            let stateful_lifetime = Duration::from_secs(24 * 60 * 60); // this is a bit of a punt
-            (id.to_vec(), stateful_lifetime)
-        };
+        }
+
+        if ticketer.is_none()
+            && !config
+                .session_storage
+                .put(ServerSessionKey::new(&ticket), plain, expiry)
+        {
+            trace!("resumption not available; not issuing ticket");
+            return Ok(());
+        }
 
         let mut payload = NewSessionTicketPayloadTls13::new(lifetime, age_add, nonce, ticket);
 
@@ -1491,13 +1829,14 @@ impl ExpectFinished {
                 self.hs.suite,
                 self.peer_identity.clone(),
                 self.hs.alpn_protocol.clone(),
+                self.hs.application_settings.as_deref(),
                 self.hs.sni.clone(),
                 &self.hs.resumption_data,
                 &resumption,
                 &self.hs.config,
             )?;
         }
-        flight.finish(output);
+        flight.finish(output)?;
 
         let (key_schedule_send, key_schedule_recv) = key_schedule_traffic.split();
 
@@ -1511,7 +1850,7 @@ impl ExpectFinished {
             .update_key_schedule(Box::new(key_schedule_send));
         output.start_traffic();
 
-        Ok(match key_schedule_recv.is_quic() {
+        Ok(match key_schedule_recv.is_datagram() {
             true => Box::new(ExpectQuicTraffic { _fin_verified: fin }).into(),
             false => Box::new(ExpectTraffic {
                 config: self.hs.config,
@@ -1535,6 +1874,7 @@ struct HandshakeState {
     transcript: HandshakeHash,
     suite: &'static Tls13CipherSuite,
     alpn_protocol: Option<ApplicationProtocol<'static>>,
+    application_settings: Option<Vec<u8>>,
     sni: Option<DnsName<'static>>,
     resumption_data: Vec<u8>,
     send_tickets: usize,
@@ -1549,6 +1889,32 @@ pub(super) struct ExpectTraffic {
 }
 
 impl ExpectTraffic {
+    /// Resume traffic using secrets taken from a serialized connection.
+    pub(super) fn restore(
+        config: &Arc<ServerConfig>,
+        suite: &'static Tls13CipherSuite,
+        (tx, rx): (OkmBlock, OkmBlock),
+        exporter: Option<&[u8]>,
+        output: &mut dyn Output<'_>,
+        proof: &HandshakeAlignedProof,
+    ) -> Box<Self> {
+        let key_schedule_recv =
+            KeyScheduleTraffic::restore(Side::Server, suite, tx, rx, output, proof);
+        if let Some(secret) = exporter {
+            output.output(OutputEvent::Exporter(Box::new(
+                KeyScheduleExporter::restore(Side::Server, suite, OkmBlock::new(secret)),
+            )));
+        }
+        output.start_traffic();
+
+        Box::new(Self {
+            config: config.clone(),
+            key_schedule_recv,
+            counters: TrafficTemperCounters::default(),
+            _fin_verified: FinishedMessageVerified::assertion(),
+        })
+    }
+
     fn handle_key_update(
         &mut self,
         input: Input<'_>,
@@ -1572,6 +1938,7 @@ impl ExpectTraffic {
         // Update our read-side keys.
         self.key_schedule_recv
             .update_decrypter(output.receive(), &proof);
+        output.observe(ConnectionEvent::KeyUpdateReceived);
         Ok(())
     }
 }
@@ -1627,6 +1994,25 @@ impl ExpectTraffic {
             self,
         ))
     }
+
+    pub(super) fn handoff_secrets(
+        &self,
+        send_keys: &Option<Box<KeyScheduleTrafficSend>>,
+    ) -> Result<HandoffSecrets, Error> {
+        if !self.config.enable_secret_extraction {
+            return Err(ApiMisuse::SecretExtractionRequiresPriorOptIn.into());
+        }
+        let Some(send_keys) = send_keys else {
+            return Err(Error::Unreachable(
+                "send_keys required for TLS1.3 handoff_secrets",
+            ));
+        };
+        Ok(HandoffSecrets::Tls13 {
+            tx: send_keys.secret().clone(),
+            rx: self.key_schedule_recv.secret().clone(),
+            resumption: None,
+        })
+    }
 }
 
 impl KernelState for ExpectTraffic {
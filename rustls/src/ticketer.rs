@@ -1,6 +1,7 @@
 use alloc::boxed::Box;
 use alloc::vec::Vec;
 use core::mem;
+use core::sync::atomic::{AtomicU64, Ordering};
 use core::time::Duration;
 use std::sync::{RwLock, RwLockReadGuard};
 use std::time::Instant;
@@ -15,6 +16,7 @@ pub struct TicketRotator {
     pub(crate) generator: fn() -> Result<Box<dyn TicketProducer>, Error>,
     lifetime: Duration,
     state: RwLock<TicketRotatorState>,
+    retired_key_rejections: AtomicU64,
 }
 
 impl TicketRotator {
@@ -32,26 +34,54 @@ impl TicketRotator {
         lifetime: Duration,
         generator: fn() -> Result<Box<dyn TicketProducer>, Error>,
     ) -> Result<Self, Error> {
+        let now = Instant::now();
         Ok(Self {
             generator,
             lifetime,
             state: RwLock::new(TicketRotatorState {
                 current: Some(Generation {
                     producer: generator()?,
-                    expires_at: Instant::now() + lifetime,
+                    id: 0,
+                    created_at: now,
+                    expires_at: now + lifetime,
                 }),
                 previous: None,
+                next_id: 1,
             }),
+            retired_key_rejections: AtomicU64::new(0),
         })
     }
 
+    /// Describe the sub-ticketers currently in use.
+    ///
+    /// This performs any rotation that is due first.
+    pub fn status(&self) -> TicketRotatorStatus {
+        let Some(state) = self.maybe_roll(Instant::now()) else {
+            return TicketRotatorStatus::default();
+        };
+
+        TicketRotatorStatus {
+            current: state
+                .current
+                .as_ref()
+                .map(Generation::describe),
+            previous: state
+                .previous
+                .as_ref()
+                .map(Generation::describe),
+            retired_key_rejections: self
+                .retired_key_rejections
+                .load(Ordering::Relaxed),
+        }
+    }
+
     fn encrypt_at(&self, message: &[u8], now: Instant) -> Option<Vec<u8>> {
         let state = self.maybe_roll(now)?;
 
         // If we have a current ticketer, use it. We don't need to check its
         // expiration time; if it would have expired, we would have rolled above.
         if let Some(current) = &state.current {
-            return current.producer.encrypt(message);
+            return current.encrypt(message);
         }
 
         // If we don't have a previous ticketer, we can't encrypt.
@@ -64,32 +94,39 @@ impl TicketRotator {
             return None;
         }
 
-        prev.producer.encrypt(message)
+        prev.encrypt(message)
     }
 
-    fn decrypt_at(&self, ciphertext: &[u8], now: Instant) -> Option<Vec<u8>> {
+    fn decrypt_at(&self, ticket: &[u8], now: Instant) -> Option<Vec<u8>> {
+        let (id, ciphertext) = ticket.split_first_chunk::<GENERATION_ID_LEN>()?;
+        let id = u64::from_be_bytes(*id);
         let state = self.maybe_roll(now)?;
 
-        // If we have a current ticketer, use it. We don't need to check its
-        // expiration time; if it would have expired, we would have rolled above.
-        if let Some(current) = &state.current {
-            // If decryption fails, we're going to try the previous ticketer below.
-            if let Some(plain) = current.producer.decrypt(ciphertext) {
-                return Some(plain);
-            }
+        // We don't need to check the current ticketer's expiration time;
+        // if it would have expired, we would have rolled above.
+        if let Some(current) = state
+            .current
+            .as_ref()
+            .filter(|current| current.id == id)
+        {
+            return current.producer.decrypt(ciphertext);
         }
 
-        // If we don't have a previous ticketer, we can't decrypt.
-        let Some(prev) = &state.previous else {
-            return None;
-        };
-
-        // If the previous ticketer is more than one `lifetime` old, decline to decrypt.
-        if !prev.in_grace_period(now, self.lifetime) {
-            return None;
+        // The previous ticketer is only used until it is one `lifetime` old.
+        if let Some(prev) = state
+            .previous
+            .as_ref()
+            .filter(|prev| prev.id == id && prev.in_grace_period(now, self.lifetime))
+        {
+            return prev.producer.decrypt(ciphertext);
         }
 
-        prev.producer.decrypt(ciphertext)
+        // Any other generation we made has been retired.
+        if id < state.next_id {
+            self.retired_key_rejections
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        None
     }
 
     /// If it's time, demote the `current` ticketer to `previous` (so it
@@ -130,8 +167,13 @@ impl TicketRotator {
             .ok()
             .map(|producer| Generation {
                 producer,
+                id: write.next_id,
+                created_at: now,
                 expires_at: now + self.lifetime,
             });
+        if next.is_some() {
+            write.next_id += 1;
+        }
 
         // Now we have:
         // - confirmed we need rotation
@@ -168,24 +210,85 @@ impl core::fmt::Debug for TicketRotator {
     }
 }
 
+/// The sub-ticketers in use by a [`TicketRotator`].
+///
+/// This is returned by [`TicketRotator::status()`].
+#[non_exhaustive]
+#[derive(Clone, Debug, Default)]
+pub struct TicketRotatorStatus {
+    /// The sub-ticketer used for new tickets.
+    ///
+    /// This is `None` if making a new sub-ticketer failed.
+    pub current: Option<TicketKeyGeneration>,
+    /// The sub-ticketer that `current` replaced.
+    ///
+    /// This is still used to decrypt tickets until one `lifetime`
+    /// after it was replaced.
+    pub previous: Option<TicketKeyGeneration>,
+    /// How many tickets were rejected because the sub-ticketer that
+    /// encrypted them has been retired.
+    ///
+    /// Each ticket names the sub-ticketer that encrypted it.  This count
+    /// is taken from that name without decrypting the ticket, so it
+    /// also includes forged tickets that name a retired sub-ticketer.
+    pub retired_key_rejections: u64,
+}
+
+/// One of the sub-ticketers used by a [`TicketRotator`].
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TicketKeyGeneration {
+    /// Identifies this sub-ticketer.
+    ///
+    /// These start from zero, and increase by one for each rotation.
+    pub id: u64,
+    /// When this sub-ticketer was created.
+    pub created_at: Instant,
+    /// When this sub-ticketer stops being used for new tickets.
+    pub expires_at: Instant,
+}
+
 #[derive(Debug)]
 pub(crate) struct TicketRotatorState {
     current: Option<Generation>,
     previous: Option<Generation>,
+    next_id: u64,
 }
 
 #[derive(Debug)]
 struct Generation {
     producer: Box<dyn TicketProducer>,
+    id: u64,
+    created_at: Instant,
     expires_at: Instant,
 }
 
 impl Generation {
+    /// Encrypt `message`, prefixed with this generation's id so
+    /// [`TicketRotator::decrypt_at()`] knows which generation to use.
+    fn encrypt(&self, message: &[u8]) -> Option<Vec<u8>> {
+        let ciphertext = self.producer.encrypt(message)?;
+        let mut ticket = Vec::with_capacity(GENERATION_ID_LEN + ciphertext.len());
+        ticket.extend_from_slice(&self.id.to_be_bytes());
+        ticket.extend_from_slice(&ciphertext);
+        Some(ticket)
+    }
+
+    fn describe(&self) -> TicketKeyGeneration {
+        TicketKeyGeneration {
+            id: self.id,
+            created_at: self.created_at,
+            expires_at: self.expires_at,
+        }
+    }
+
     fn in_grace_period(&self, now: Instant, lifetime: Duration) -> bool {
         now <= self.expires_at + lifetime
     }
 }
 
+const GENERATION_ID_LEN: usize = 8;
+
 #[cfg(test)]
 mod tests {
     use core::sync::atomic::{AtomicU8, Ordering};
@@ -266,6 +369,54 @@ mod tests {
         assert!(t.encrypt(b"ticket 6").is_none());
     }
 
+    #[test]
+    fn ticketrotator_status() {
+        let t = TicketRotator::new(Duration::from_secs(1), FakeTicketer::new).unwrap();
+        let status = t.status();
+        let first = status.current.unwrap();
+        assert_eq!(first.id, 0);
+        assert!(status.previous.is_none());
+        assert_eq!(status.retired_key_rejections, 0);
+
+        let cipher1 = t
+            .encrypt_at(b"ticket 1", first.created_at)
+            .unwrap();
+        drop(t.maybe_roll(first.expires_at + Duration::from_secs(1)));
+        let status = t.status();
+        let second = status.current.unwrap();
+        assert_eq!(second.id, 1);
+        assert!(second.created_at > first.created_at);
+        assert_eq!(status.previous, Some(first));
+
+        // The previous ticketer is retired after another `lifetime`.
+        let retired = first.expires_at + Duration::from_secs(2);
+        assert_eq!(t.decrypt_at(b"garbage", retired), None);
+        assert_eq!(t.status().retired_key_rejections, 0);
+        assert_eq!(t.decrypt_at(&cipher1, retired), None);
+        assert_eq!(t.status().retired_key_rejections, 1);
+
+        // Tickets are classified by the generation they name, without decrypting them.
+        let mut forged = 0u64.to_be_bytes().to_vec();
+        forged.extend_from_slice(b"garbage");
+        assert_eq!(t.decrypt_at(&forged, retired), None);
+        assert_eq!(t.status().retired_key_rejections, 2);
+
+        let mut unknown = 2u64.to_be_bytes().to_vec();
+        unknown.extend_from_slice(&cipher1[GENERATION_ID_LEN..]);
+        assert_eq!(t.decrypt_at(&unknown, retired), None);
+        assert_eq!(t.status().retired_key_rejections, 2);
+
+        // A ticket naming the current generation is only decrypted by it.
+        let mut misnamed = 1u64.to_be_bytes().to_vec();
+        misnamed.extend_from_slice(&cipher1[GENERATION_ID_LEN..]);
+        assert_eq!(t.decrypt_at(&misnamed, retired), None);
+        let cipher2 = t
+            .encrypt_at(b"ticket 2", retired)
+            .unwrap();
+        assert_eq!(&cipher2[..GENERATION_ID_LEN], &1u64.to_be_bytes());
+        assert_eq!(t.decrypt_at(&cipher2, retired).unwrap(), b"ticket 2");
+    }
+
     #[derive(Debug)]
     struct FakeTicketer {
         generation: u8,
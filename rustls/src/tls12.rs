@@ -302,16 +302,14 @@ impl ConnectionSecrets {
         self.make_verify_data(handshake_hash, b"server finished", proof)
     }
 
-    pub(crate) fn into_exporter(self) -> Box<dyn Exporter> {
-        let Self {
-            randoms,
-            master_secret_prf,
-            master_secret: _,
-            suite: _,
-        } = self;
+    pub(crate) fn exporter(&self) -> Box<dyn Exporter> {
         Box::new(Tls12Exporter {
-            randoms,
-            master_secret_prf,
+            randoms: self.randoms.clone(),
+            master_secret: self.master_secret.clone(),
+            master_secret_prf: self
+                .suite
+                .prf_provider
+                .new_secret(&self.master_secret),
         })
     }
 
@@ -346,6 +344,7 @@ impl ConnectionSecrets {
 
 pub(crate) struct Tls12Exporter {
     randoms: ConnectionRandoms,
+    master_secret: Zeroizing<[u8; 48]>,
     master_secret_prf: Box<dyn PrfSecret>,
 }
 
@@ -372,6 +371,10 @@ impl Exporter for Tls12Exporter {
             .prf(output, label, &randoms);
         Ok(())
     }
+
+    fn secret(&self) -> &[u8] {
+        &*self.master_secret
+    }
 }
 
 enum Seed {
@@ -11,7 +11,7 @@ use crate::crypto::tls13::{Hkdf, HkdfExpander, OkmBlock, OutputLengthError, expa
 use crate::crypto::{hash, hmac};
 use crate::error::{ApiMisuse, Error};
 use crate::msgs::HandshakeAlignedProof;
-use crate::tls13::Tls13ProtocolSuite;
+use crate::tls13::{Tls13CipherSuite, Tls13ProtocolSuite};
 use crate::{ConnectionTrafficSecrets, KeyLog};
 
 // We express the state of a contained KeySchedule using these
@@ -43,8 +43,8 @@ impl KeyScheduleEarlyClient {
         );
     }
 
-    pub(crate) fn is_quic(&self) -> bool {
-        self.0.ks.state.is_quic()
+    pub(crate) fn is_datagram(&self) -> bool {
+        self.0.ks.state.is_datagram()
     }
 }
 
@@ -349,7 +349,8 @@ impl KeyScheduleHandshakeStart {
         );
 
         if let Some(quic) = output.quic() {
-            if let Tls13ProtocolSuite::Quic(suite) = self.ks.state {
+            if let Tls13ProtocolSuite::Quic(suite) | Tls13ProtocolSuite::Dtls(suite) = self.ks.state
+            {
                 quic.handshake_secrets(
                     client_secret.clone(),
                     server_secret.clone(),
@@ -433,7 +434,9 @@ impl KeyScheduleHandshake {
             .set_encrypter(server_secret, output.send());
 
         if let Some(quic) = output.quic() {
-            if let Tls13ProtocolSuite::Quic(suite) = before_finished.ks.state {
+            if let Tls13ProtocolSuite::Quic(suite) | Tls13ProtocolSuite::Dtls(suite) =
+                before_finished.ks.state
+            {
                 quic.traffic_secrets(
                     client_secret.clone(),
                     server_secret.clone(),
@@ -467,6 +470,10 @@ impl KeyScheduleHandshake {
     pub(crate) fn is_quic(&self) -> bool {
         self.ks.state.is_quic()
     }
+
+    pub(crate) fn is_datagram(&self) -> bool {
+        self.ks.state.is_datagram()
+    }
 }
 
 /// Keys derived (but not installed) before client's Finished message.
@@ -583,7 +590,8 @@ impl KeyScheduleClientBeforeFinished {
             .set_encrypter(client_secret, output.send());
 
         if let Some(quic) = output.quic() {
-            if let Tls13ProtocolSuite::Quic(suite) = next.ks.state {
+            if let Tls13ProtocolSuite::Quic(suite) | Tls13ProtocolSuite::Dtls(suite) = next.ks.state
+            {
                 quic.traffic_secrets(
                     client_secret.clone(),
                     server_secret.clone(),
@@ -676,6 +684,27 @@ impl KeyScheduleTraffic {
             },
         )
     }
+
+    /// Install traffic keys derived from the current traffic secrets of a
+    /// serialized connection.
+    ///
+    /// The send direction is handed to `output`, and the receive direction returned.
+    pub(crate) fn restore(
+        side: Side,
+        suite: &'static Tls13CipherSuite,
+        tx: OkmBlock,
+        rx: OkmBlock,
+        output: &mut dyn Output<'_>,
+        proof: &HandshakeAlignedProof,
+    ) -> KeyScheduleTrafficReceive {
+        let ks = KeyScheduleSuite::tcp(side, suite);
+        ks.set_encrypter(&tx, output.send());
+        ks.set_decrypter(&rx, output.receive(), proof);
+        output
+            .send()
+            .update_key_schedule(Box::new(KeyScheduleTrafficSend { ks, current: tx }));
+        KeyScheduleTrafficReceive { ks, current: rx }
+    }
 }
 
 /// KeySchedule during traffic stage for send direction.
@@ -713,6 +742,10 @@ impl KeyScheduleTrafficSend {
 
         Ok(suite.aead_alg.extract_keys(key, iv)?)
     }
+
+    pub(crate) fn secret(&self) -> &OkmBlock {
+        &self.current
+    }
 }
 
 /// KeySchedule during traffic stage for receive direction.
@@ -753,6 +786,14 @@ impl KeyScheduleTrafficReceive {
     pub(crate) fn is_quic(&self) -> bool {
         self.ks.state.is_quic()
     }
+
+    pub(crate) fn is_datagram(&self) -> bool {
+        self.ks.state.is_datagram()
+    }
+
+    pub(crate) fn secret(&self) -> &OkmBlock {
+        &self.current
+    }
 }
 
 pub(crate) struct KeyScheduleExporter {
@@ -765,6 +806,24 @@ impl Exporter for KeyScheduleExporter {
         self.ks
             .export_keying_material(&self.current_exporter_secret, label, context, out)
     }
+
+    fn secret(&self) -> &[u8] {
+        self.current_exporter_secret.as_ref()
+    }
+}
+
+impl KeyScheduleExporter {
+    /// Resume exporting from the `exporter_secret` of a serialized connection.
+    pub(crate) fn restore(
+        side: Side,
+        suite: &'static Tls13CipherSuite,
+        current_exporter_secret: OkmBlock,
+    ) -> Self {
+        Self {
+            ks: KeyScheduleSuite::tcp(side, suite),
+            current_exporter_secret,
+        }
+    }
 }
 
 pub(crate) struct KeyScheduleResumption {
@@ -773,6 +832,23 @@ pub(crate) struct KeyScheduleResumption {
 }
 
 impl KeyScheduleResumption {
+    /// Resume deriving ticket PSKs from the `resumption_master_secret` of a
+    /// serialized connection.
+    pub(crate) fn restore(
+        side: Side,
+        suite: &'static Tls13CipherSuite,
+        resumption_master_secret: OkmBlock,
+    ) -> Self {
+        Self {
+            ks: KeyScheduleSuite::tcp(side, suite),
+            resumption_master_secret,
+        }
+    }
+
+    pub(crate) fn secret(&self) -> &OkmBlock {
+        &self.resumption_master_secret
+    }
+
     pub(crate) fn derive_ticket_psk(&self, nonce: &[u8]) -> OkmBlock {
         self.ks
             .derive_ticket_psk(&self.resumption_master_secret, nonce)
@@ -855,7 +931,12 @@ impl KeySchedule {
     /// ```
     /// where `hs_hash` is `Messages`.
     fn derive(&self, kind: SecretKind, hs_hash: &[u8]) -> OkmBlock {
-        hkdf_expand_label_block(self.current.as_ref(), kind.to_bytes(), hs_hash)
+        hkdf_expand_prefixed_label_block(
+            self.current.as_ref(),
+            self.state.label_prefix(),
+            kind.to_bytes(),
+            hs_hash,
+        )
     }
 
     fn derive_logged_secret(
@@ -916,6 +997,13 @@ struct KeyScheduleSuite {
 }
 
 impl KeyScheduleSuite {
+    fn tcp(side: Side, suite: &'static Tls13CipherSuite) -> Self {
+        Self {
+            side,
+            state: Tls13ProtocolSuite::Tcp(suite),
+        }
+    }
+
     fn set_encrypter(&self, secret: &OkmBlock, send: &mut dyn SendOutput) {
         let suite = self.state.suite();
         let expander = suite
@@ -968,7 +1056,12 @@ impl KeyScheduleSuite {
         let expander = suite
             .hkdf_provider
             .expander_for_okm(base_key);
-        let hmac_key = hkdf_expand_label_block(expander.as_ref(), b"finished", &[]);
+        let hmac_key = hkdf_expand_prefixed_label_block(
+            expander.as_ref(),
+            self.state.label_prefix(),
+            b"finished",
+            &[],
+        );
 
         suite
             .hkdf_provider
@@ -984,7 +1077,12 @@ impl KeyScheduleSuite {
             .suite()
             .hkdf_provider
             .expander_for_okm(base_key);
-        hkdf_expand_label_block(expander.as_ref(), b"traffic upd", &[])
+        hkdf_expand_prefixed_label_block(
+            expander.as_ref(),
+            self.state.label_prefix(),
+            b"traffic upd",
+            &[],
+        )
     }
 
     /// Derive the PSK to use given a resumption_master_secret and
@@ -995,7 +1093,12 @@ impl KeyScheduleSuite {
             .suite()
             .hkdf_provider
             .expander_for_okm(rms);
-        hkdf_expand_label_block(expander.as_ref(), b"resumption", nonce)
+        hkdf_expand_prefixed_label_block(
+            expander.as_ref(),
+            self.state.label_prefix(),
+            b"resumption",
+            nonce,
+        )
     }
 
     fn export_keying_material(
@@ -1012,7 +1115,12 @@ impl KeyScheduleSuite {
             let expander = suite
                 .hkdf_provider
                 .expander_for_okm(current_exporter_secret);
-            hkdf_expand_label_block(expander.as_ref(), label, h_empty.as_ref())
+            hkdf_expand_prefixed_label_block(
+                expander.as_ref(),
+                self.state.label_prefix(),
+                label,
+                h_empty.as_ref(),
+            )
         };
 
         let h_context = suite
@@ -1023,8 +1131,14 @@ impl KeyScheduleSuite {
         let expander = suite
             .hkdf_provider
             .expander_for_okm(&secret);
-        hkdf_expand_label_slice(expander.as_ref(), b"exporter", h_context.as_ref(), out)
-            .map_err(|_| ApiMisuse::ExporterOutputTooLong.into())
+        hkdf_expand_label_slice(
+            expander.as_ref(),
+            self.state.label_prefix(),
+            b"exporter",
+            h_context.as_ref(),
+            out,
+        )
+        .map_err(|_| ApiMisuse::ExporterOutputTooLong.into())
     }
 }
 
@@ -1054,7 +1168,14 @@ pub(crate) fn hkdf_expand_label<T: From<[u8; N]>, const N: usize>(
     label: &[u8],
     context: &[u8],
 ) -> T {
-    hkdf_expand_label_inner(expander, label, context, N, |e, info| expand(e, info))
+    hkdf_expand_label_inner(
+        expander,
+        TLS13_LABEL_PREFIX,
+        label,
+        context,
+        N,
+        |e, info| expand(e, info),
+    )
 }
 
 /// [HKDF-Expand-Label] where the output is one block in size.
@@ -1063,9 +1184,24 @@ pub(crate) fn hkdf_expand_label_block(
     label: &[u8],
     context: &[u8],
 ) -> OkmBlock {
-    hkdf_expand_label_inner(expander, label, context, expander.hash_len(), |e, info| {
-        e.expand_block(info)
-    })
+    hkdf_expand_prefixed_label_block(expander, TLS13_LABEL_PREFIX, label, context)
+}
+
+/// [HKDF-Expand-Label] where the output is one block in size, with a given label `prefix`.
+pub(crate) fn hkdf_expand_prefixed_label_block(
+    expander: &dyn HkdfExpander,
+    prefix: &[u8],
+    label: &[u8],
+    context: &[u8],
+) -> OkmBlock {
+    hkdf_expand_label_inner(
+        expander,
+        prefix,
+        label,
+        context,
+        expander.hash_len(),
+        |e, info| e.expand_block(info),
+    )
 }
 
 /// [HKDF-Expand-Label] where the output is an AEAD key.
@@ -1075,7 +1211,18 @@ pub(crate) fn hkdf_expand_label_aead_key(
     label: &[u8],
     context: &[u8],
 ) -> AeadKey {
-    hkdf_expand_label_inner(expander, label, context, key_len, |e, info| {
+    hkdf_expand_prefixed_label_aead_key(expander, key_len, TLS13_LABEL_PREFIX, label, context)
+}
+
+/// [HKDF-Expand-Label] where the output is an AEAD key, with a given label `prefix`.
+pub(crate) fn hkdf_expand_prefixed_label_aead_key(
+    expander: &dyn HkdfExpander,
+    key_len: usize,
+    prefix: &[u8],
+    label: &[u8],
+    context: &[u8],
+) -> AeadKey {
+    hkdf_expand_label_inner(expander, prefix, label, context, key_len, |e, info| {
         expand::<AeadKey, { AeadKey::MAX_LEN }>(e, info).with_length(key_len)
     })
 }
@@ -1087,7 +1234,18 @@ pub(crate) fn hkdf_expand_label_iv(
     context: &[u8],
     iv_len: usize,
 ) -> Iv {
-    hkdf_expand_label_inner(expander, label, context, iv_len, |e, info| {
+    hkdf_expand_prefixed_label_iv(expander, TLS13_LABEL_PREFIX, label, context, iv_len)
+}
+
+/// [HKDF-Expand-Label] where the output is an IV, with a given label `prefix`.
+pub(crate) fn hkdf_expand_prefixed_label_iv(
+    expander: &dyn HkdfExpander,
+    prefix: &[u8],
+    label: &[u8],
+    context: &[u8],
+    iv_len: usize,
+) -> Iv {
+    hkdf_expand_label_inner(expander, prefix, label, context, iv_len, |e, info| {
         let mut buf = [0u8; Iv::MAX_LEN];
         e.expand_slice(info, &mut buf[..iv_len])
             .unwrap();
@@ -1100,11 +1258,12 @@ pub(crate) fn hkdf_expand_label_iv(
 /// This can fail because HKDF-Expand is limited in its maximum output length.
 fn hkdf_expand_label_slice(
     expander: &dyn HkdfExpander,
+    prefix: &[u8],
     label: &[u8],
     context: &[u8],
     output: &mut [u8],
 ) -> Result<(), OutputLengthError> {
-    hkdf_expand_label_inner(expander, label, context, output.len(), |e, info| {
+    hkdf_expand_label_inner(expander, prefix, label, context, output.len(), |e, info| {
         e.expand_slice(info, output)
     })
 }
@@ -1134,6 +1293,7 @@ pub(crate) fn server_ech_hrr_confirmation_secret(
 
 fn hkdf_expand_label_inner<F, T>(
     expander: &dyn HkdfExpander,
+    prefix: &[u8],
     label: &[u8],
     context: &[u8],
     n: usize,
@@ -1142,16 +1302,14 @@ fn hkdf_expand_label_inner<F, T>(
 where
     F: FnOnce(&dyn HkdfExpander, &[&[u8]]) -> T,
 {
-    const LABEL_PREFIX: &[u8] = b"tls13 ";
-
     let output_len = u16::to_be_bytes(n as u16);
-    let label_len = u8::to_be_bytes((LABEL_PREFIX.len() + label.len()) as u8);
+    let label_len = u8::to_be_bytes((prefix.len() + label.len()) as u8);
     let context_len = u8::to_be_bytes(context.len() as u8);
 
     let info = &[
         &output_len[..],
         &label_len[..],
-        LABEL_PREFIX,
+        prefix,
         label,
         &context_len[..],
         context,
@@ -1160,6 +1318,14 @@ where
     f(expander, info)
 }
 
+/// Label prefix for TLS1.3, and for QUIC.
+pub(crate) const TLS13_LABEL_PREFIX: &[u8] = b"tls13 ";
+
+/// Label prefix for DTLS1.3.
+///
+/// See <https://www.rfc-editor.org/rfc/rfc9147#section-5.9>.
+pub(crate) const DTLS13_LABEL_PREFIX: &[u8] = b"dtls13";
+
 /// The kinds of secret we can extract from `KeySchedule`.
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum SecretKind {
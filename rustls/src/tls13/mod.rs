@@ -5,6 +5,7 @@ use pki_types::FipsStatus;
 use crate::common_state::Protocol;
 use crate::crypto::{self, SignatureScheme, hash};
 use crate::enums::ProtocolVersion;
+use crate::error::Error;
 use crate::quic;
 use crate::suites::{CipherSuiteCommon, Suite, SupportedCipherSuite};
 use crate::version::Tls13Version;
@@ -15,19 +16,41 @@ pub(crate) mod key_schedule;
 pub(crate) enum Tls13ProtocolSuite {
     Tcp(&'static Tls13CipherSuite),
     Quic(quic::Suite),
+    /// DTLS protects records using the suite's QUIC algorithms.
+    Dtls(quic::Suite),
 }
 
 impl Tls13ProtocolSuite {
+    pub(crate) fn new(suite: &'static Tls13CipherSuite, protocol: Protocol) -> Result<Self, Error> {
+        Ok(match protocol {
+            Protocol::Tcp => Self::Tcp(suite),
+            Protocol::Quic(_) => Self::Quic(quic::Suite::try_from(suite)?),
+            Protocol::Dtls => Self::Dtls(quic::Suite::try_from(suite)?),
+        })
+    }
+
     pub(crate) fn suite(&self) -> &'static Tls13CipherSuite {
         match self {
             Self::Tcp(suite) => suite,
-            Self::Quic(quic) => quic.inner,
+            Self::Quic(quic) | Self::Dtls(quic) => quic.inner,
         }
     }
 
     pub(crate) fn is_quic(&self) -> bool {
         matches!(self, Self::Quic(_))
     }
+
+    pub(crate) fn is_datagram(&self) -> bool {
+        matches!(self, Self::Quic(_) | Self::Dtls(_))
+    }
+
+    /// The prefix for all HKDF-Expand-Label labels.
+    pub(crate) fn label_prefix(&self) -> &'static [u8] {
+        match self {
+            Self::Tcp(_) | Self::Quic(_) => key_schedule::TLS13_LABEL_PREFIX,
+            Self::Dtls(_) => key_schedule::DTLS13_LABEL_PREFIX,
+        }
+    }
 }
 
 /// A TLS 1.3 cipher suite supported by rustls.
@@ -111,11 +134,11 @@ impl Suite for Tls13CipherSuite {
 
     /// Does this suite support the `proto` protocol?
     ///
-    /// All TLS1.3 suites support TCP-TLS. QUIC support is conditional on `quic` slot.
+    /// All TLS1.3 suites support TCP-TLS. QUIC and DTLS support is conditional on `quic` slot.
     fn usable_for_protocol(&self, proto: Protocol) -> bool {
         match proto {
             Protocol::Tcp => true,
-            Protocol::Quic(_) => self.quic.is_some(),
+            Protocol::Quic(_) | Protocol::Dtls => self.quic.is_some(),
         }
     }
 
@@ -15,7 +15,7 @@ mod verify;
 
 pub use anchors::RootCertStore;
 pub use client_verifier::{ClientVerifierBuilder, WebPkiClientVerifier};
-pub use server_verifier::{ServerVerifierBuilder, WebPkiServerVerifier};
+pub use server_verifier::{RootHintTrimming, ServerVerifierBuilder, WebPkiServerVerifier};
 pub use verify::{
     ParsedCertificate, verify_identity_signed_by_trust_anchor, verify_server_name,
     verify_tls12_signature, verify_tls13_signature,
@@ -8,8 +8,8 @@ use crate::crypto::{CryptoProvider, Identity, SignatureScheme, WebPkiSupportedAl
 use crate::error::ApiMisuse;
 use crate::sync::Arc;
 use crate::verify::{
-    HandshakeSignatureValid, ServerIdentity, ServerVerifier, SignatureVerificationInput,
-    VerifiedIdentity,
+    DistinguishedName, HandshakeSignatureValid, ServerIdentity, ServerVerifier,
+    SignatureVerificationInput, VerifiedIdentity,
 };
 use crate::webpki::verify::{
     ParsedCertificate, verify_identity_signed_by_trust_anchor_impl, verify_tls12_signature,
@@ -31,6 +31,7 @@ pub struct ServerVerifierBuilder {
     unknown_revocation_policy: UnknownStatusPolicy,
     revocation_expiration_policy: ExpirationPolicy,
     supported_algs: WebPkiSupportedAlgorithms,
+    root_hints: Option<RootHintLimit>,
 }
 
 impl ServerVerifierBuilder {
@@ -45,6 +46,7 @@ impl ServerVerifierBuilder {
             unknown_revocation_policy: UnknownStatusPolicy::Deny,
             revocation_expiration_policy: ExpirationPolicy::Ignore,
             supported_algs,
+            root_hints: None,
         }
     }
 
@@ -99,6 +101,28 @@ impl ServerVerifierBuilder {
         self
     }
 
+    /// Advertise the subjects of the trust anchors to servers.
+    ///
+    /// The subjects from [`RootCertStore::subjects()`] are sent in the TLS1.3
+    /// [`certificate_authorities`] ClientHello extension, so a server with several
+    /// certificate chains can choose one we trust.  See [`ServerVerifier::root_hint_subjects()`].
+    ///
+    /// At most `max_len` bytes of encoded subjects are sent, with `trimming` deciding which
+    /// to send if they do not all fit.  A full public root store (like the Mozilla one) encodes
+    /// to many kilobytes, so is impractical to send in its entirety.
+    ///
+    /// By default, no subjects are advertised.
+    ///
+    /// [`certificate_authorities`]: https://datatracker.ietf.org/doc/html/rfc9846#section-4.3.4
+    pub fn advertise_root_hint_subjects(
+        mut self,
+        max_len: usize,
+        trimming: RootHintTrimming,
+    ) -> Self {
+        self.root_hints = Some(RootHintLimit { max_len, trimming });
+        self
+    }
+
     /// Build a server certificate verifier, allowing control over the root certificates to use as
     /// trust anchors, and to control how server certificate revocation checking is performed.
     ///
@@ -125,12 +149,64 @@ impl ServerVerifierBuilder {
             self.unknown_revocation_policy,
             self.revocation_expiration_policy,
             self.supported_algs,
+            self.root_hints,
         ))
     }
 }
 
+/// How a [`WebPkiServerVerifier`] chooses root hint subjects that do not all fit.
+///
+/// See [`ServerVerifierBuilder::advertise_root_hint_subjects()`].
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RootHintTrimming {
+    /// Take subjects in [`RootCertStore`] order, skipping any that do not fit in the remaining space.
+    ///
+    /// Put the roots you most want servers to know about first.
+    InOrder,
+
+    /// Advertise no subjects at all unless all of them fit.
+    ///
+    /// This avoids a server choosing a chain based on an incomplete list.
+    AllOrNothing,
+}
+
+#[derive(Debug, Clone, Copy, Hash)]
+pub(crate) struct RootHintLimit {
+    max_len: usize,
+    trimming: RootHintTrimming,
+}
+
+impl RootHintLimit {
+    fn subjects(&self, roots: &RootCertStore) -> Option<Arc<[DistinguishedName]>> {
+        // The extension cannot exceed this in any case.
+        let max_len = Ord::min(self.max_len, usize::from(u16::MAX));
+
+        let mut len = 0;
+        let mut subjects = Vec::new();
+        for subject in roots.subjects() {
+            // Each is encoded with a two-byte length prefix.
+            let encoded_len = 2 + subject.as_ref().len();
+            if len + encoded_len > max_len {
+                match self.trimming {
+                    RootHintTrimming::InOrder => continue,
+                    RootHintTrimming::AllOrNothing => return None,
+                }
+            }
+
+            len += encoded_len;
+            subjects.push(subject);
+        }
+
+        match subjects.is_empty() {
+            true => None,
+            false => Some(Arc::from(subjects)),
+        }
+    }
+}
+
 /// Default `ServerVerifier`, see the trait impl for more information.
-#[derive(Debug, Hash)]
+#[derive(Debug)]
 pub struct WebPkiServerVerifier {
     roots: Arc<RootCertStore>,
     crls: Vec<CertRevocationList<'static>>,
@@ -138,6 +214,8 @@ pub struct WebPkiServerVerifier {
     unknown_revocation_policy: UnknownStatusPolicy,
     revocation_expiration_policy: ExpirationPolicy,
     supported: WebPkiSupportedAlgorithms,
+    root_hints: Option<RootHintLimit>,
+    root_hint_subjects: Option<Arc<[DistinguishedName]>>,
 }
 
 impl WebPkiServerVerifier {
@@ -166,6 +244,7 @@ impl WebPkiServerVerifier {
             UnknownStatusPolicy::Allow,
             ExpirationPolicy::Ignore,
             supported_algs,
+            None,
         )
     }
 
@@ -180,6 +259,7 @@ impl WebPkiServerVerifier {
     ///   are handled when `crls` are provided.
     /// * `supported` is the set of supported algorithms that will be used for
     ///   certificate verification and TLS handshake signature verification.
+    /// * `root_hints` controls which trust anchor subjects are advertised, if any.
     pub(crate) fn new(
         roots: impl Into<Arc<RootCertStore>>,
         crls: Vec<CertRevocationList<'static>>,
@@ -187,18 +267,47 @@ impl WebPkiServerVerifier {
         unknown_revocation_policy: UnknownStatusPolicy,
         revocation_expiration_policy: ExpirationPolicy,
         supported: WebPkiSupportedAlgorithms,
+        root_hints: Option<RootHintLimit>,
     ) -> Self {
+        let roots = roots.into();
+        let root_hint_subjects = root_hints.and_then(|limit| limit.subjects(&roots));
         Self {
-            roots: roots.into(),
+            roots,
             crls,
             revocation_check_depth,
             unknown_revocation_policy,
             revocation_expiration_policy,
             supported,
+            root_hints,
+            root_hint_subjects,
         }
     }
 }
 
+impl Hash for WebPkiServerVerifier {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let Self {
+            roots,
+            crls,
+            revocation_check_depth,
+            unknown_revocation_policy,
+            revocation_expiration_policy,
+            supported,
+            root_hints,
+            // derived from `roots` and `root_hints`
+            root_hint_subjects: _,
+        } = self;
+
+        roots.hash(state);
+        crls.hash(state);
+        revocation_check_depth.hash(state);
+        unknown_revocation_policy.hash(state);
+        revocation_expiration_policy.hash(state);
+        supported.hash(state);
+        root_hints.hash(state);
+    }
+}
+
 impl ServerVerifier for WebPkiServerVerifier {
     /// Will verify the certificate is valid in the following ways:
     /// - Signed by a trusted `RootCertStore` CA
@@ -277,6 +386,10 @@ impl ServerVerifier for WebPkiServerVerifier {
         false
     }
 
+    fn root_hint_subjects(&self) -> Option<Arc<[DistinguishedName]>> {
+        self.root_hint_subjects.clone()
+    }
+
     fn hash_config(&self, h: &mut dyn Hasher) {
         self.hash(&mut DynHasher(h));
     }
@@ -290,10 +403,11 @@ mod tests {
     use pki_types::pem::PemObject;
     use pki_types::{CertificateDer, CertificateRevocationListDer};
 
-    use super::{VerifierBuilderError, WebPkiServerVerifier};
+    use super::{RootHintTrimming, VerifierBuilderError, WebPkiServerVerifier};
     use crate::RootCertStore;
     use crate::crypto::TEST_PROVIDER;
     use crate::sync::Arc;
+    use crate::verify::ServerVerifier;
 
     fn load_crls(crls_der: &[&[u8]]) -> Vec<CertificateRevocationListDer<'static>> {
         crls_der
@@ -406,4 +520,54 @@ mod tests {
         println!("{builder:?}");
         builder.build().unwrap();
     }
+
+    #[test]
+    fn test_server_verifier_root_hint_subjects() {
+        let roots = test_roots();
+        let subjects = roots.subjects();
+        let first_len = 2 + subjects[0].as_ref().len();
+        let all_len = subjects
+            .iter()
+            .map(|s| 2 + s.as_ref().len())
+            .sum::<usize>();
+
+        let hints = |max_len, trimming| {
+            WebPkiServerVerifier::builder(roots.clone(), &TEST_PROVIDER)
+                .advertise_root_hint_subjects(max_len, trimming)
+                .build()
+                .unwrap()
+                .root_hint_subjects()
+                .map(|hints| hints.to_vec())
+        };
+
+        // Not advertised by default.
+        let verifier = WebPkiServerVerifier::builder(roots.clone(), &TEST_PROVIDER)
+            .build()
+            .unwrap();
+        assert!(verifier.root_hint_subjects().is_none());
+
+        // Everything fits.
+        assert_eq!(
+            hints(all_len, RootHintTrimming::InOrder),
+            Some(subjects.clone())
+        );
+        assert_eq!(
+            hints(all_len, RootHintTrimming::AllOrNothing),
+            Some(subjects.clone())
+        );
+
+        // Only the first fits.
+        assert_eq!(
+            hints(all_len - 1, RootHintTrimming::InOrder),
+            Some(vec![subjects[0].clone()])
+        );
+        assert_eq!(hints(all_len - 1, RootHintTrimming::AllOrNothing), None);
+        assert_eq!(
+            hints(first_len, RootHintTrimming::InOrder),
+            Some(vec![subjects[0].clone()])
+        );
+
+        // Nothing fits.
+        assert_eq!(hints(0, RootHintTrimming::InOrder), None);
+    }
 }
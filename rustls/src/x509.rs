@@ -45,6 +45,60 @@ fn asn1_wrap(tag: u8, bytes_a: &[u8], bytes_b: &[u8]) -> Vec<u8> {
     }
 }
 
+/// Return the `subjectPublicKey` contents of a DER-encoded `SubjectPublicKeyInfo`.
+///
+/// Returns `None` if `spki` is not well-formed, or if the `BIT STRING` has unused bits.
+pub(crate) fn subject_public_key(spki: &[u8]) -> Option<&[u8]> {
+    let (inner, rest) = asn1_read(DER_SEQUENCE_TAG, spki)?;
+    if !rest.is_empty() {
+        return None;
+    }
+
+    let (_algorithm, rest) = asn1_read(DER_SEQUENCE_TAG, inner)?;
+    let (bit_string, rest) = asn1_read(DER_BIT_STRING_TAG, rest)?;
+    if !rest.is_empty() {
+        return None;
+    }
+
+    match bit_string.split_first() {
+        Some((0, public_key)) => Some(public_key),
+        _ => None,
+    }
+}
+
+/// Read a single DER element with the given `tag` from the front of `input`.
+///
+/// Returns the element's contents and the remainder of `input`.
+fn asn1_read(tag: u8, input: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (&actual_tag, rest) = input.split_first()?;
+    if actual_tag != tag {
+        return None;
+    }
+
+    let (&first, rest) = rest.split_first()?;
+    let (len, rest) = match first {
+        0..=0x7f => (usize::from(first), rest),
+        0x81..=0x84 => {
+            let len_bytes = usize::from(first & 0x7f);
+            if rest.len() < len_bytes {
+                return None;
+            }
+            let (len, rest) = rest.split_at(len_bytes);
+            let len = len
+                .iter()
+                .fold(0usize, |acc, b| (acc << 8) | usize::from(*b));
+            (len, rest)
+        }
+        _ => return None,
+    };
+
+    if rest.len() < len {
+        return None;
+    }
+
+    Some(rest.split_at(len))
+}
+
 const DER_SEQUENCE_TAG: u8 = 0x30;
 const DER_BIT_STRING_TAG: u8 = 0x03;
 
@@ -54,6 +108,37 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_subject_public_key() {
+        let public_key = [0x04u8; 300];
+        let spki = wrap_in_sequence(
+            &[
+                wrap_in_sequence(&[0x06, 0x01, 0x2a]),
+                wrap_in_bit_string(&public_key),
+            ]
+            .concat(),
+        );
+        assert_eq!(subject_public_key(&spki), Some(&public_key[..]));
+
+        // trailing data
+        let mut trailing = spki.clone();
+        trailing.push(0);
+        assert_eq!(subject_public_key(&trailing), None);
+
+        // truncated
+        assert_eq!(subject_public_key(&spki[..spki.len() - 1]), None);
+
+        // unused bits
+        let spki = wrap_in_sequence(
+            &[
+                wrap_in_sequence(&[0x06, 0x01, 0x2a]),
+                asn1_wrap(DER_BIT_STRING_TAG, &[1u8], &public_key),
+            ]
+            .concat(),
+        );
+        assert_eq!(subject_public_key(&spki), None);
+    }
+
     #[test]
     fn test_empty() {
         assert_eq!(vec![0x30, 0x00], wrap_in_sequence(&[]));